@@ -110,8 +110,79 @@ mod access {
         w & 1 == 1
     }
 
-    const RLW_RUNNING_BITS: usize = 32;
-    const RLW_LARGEST_RUNNING_COUNT: usize = (1 << RLW_RUNNING_BITS) - 1;
+    pub(super) const RLW_RUNNING_BITS: usize = 32;
+    pub(super) const RLW_LARGEST_RUNNING_COUNT: usize = (1 << RLW_RUNNING_BITS) - 1;
+    pub(super) const RLW_LARGEST_LITERAL_COUNT: usize = (1 << (64 - 1 - RLW_RUNNING_BITS)) - 1;
+}
+
+mod write {
+    use std::convert::TryInto;
+
+    use super::{
+        access::{RLW_LARGEST_LITERAL_COUNT, RLW_LARGEST_RUNNING_COUNT, RLW_RUNNING_BITS},
+        Vec,
+    };
+
+    impl Vec {
+        /// Create a new instance holding `num_bits` bits, of which only the ones at the indices produced by `set_bits` are true.
+        ///
+        /// Indices that are out of bounds are ignored.
+        pub fn from_set_bits(num_bits: usize, set_bits: impl IntoIterator<Item = usize>) -> Self {
+            let mut words = vec![0u64; (num_bits + 63) / 64];
+            for index in set_bits.into_iter().filter(|index| *index < num_bits) {
+                words[index / 64] |= 1 << (index % 64);
+            }
+
+            let mut bits = std::vec::Vec::with_capacity(words.len() / 2 + 1);
+            let mut rlw;
+            let mut words = words.into_iter().peekable();
+            loop {
+                rlw = bits.len();
+                bits.push(0);
+
+                let run_bit = words.peek().map_or(false, |w| *w == u64::MAX);
+                let run_word = if run_bit { u64::MAX } else { 0 };
+                let mut running_len = 0;
+                while running_len < RLW_LARGEST_RUNNING_COUNT && words.next_if_eq(&run_word).is_some() {
+                    running_len += 1;
+                }
+
+                let mut literal_words = 0;
+                while literal_words < RLW_LARGEST_LITERAL_COUNT {
+                    match words.next_if(|w| *w != 0 && *w != u64::MAX) {
+                        Some(word) => {
+                            bits.push(word);
+                            literal_words += 1;
+                        }
+                        None => break,
+                    }
+                }
+
+                bits[rlw] = run_bit as u64 | (running_len as u64) << 1 | (literal_words as u64) << (1 + RLW_RUNNING_BITS);
+                if words.peek().is_none() {
+                    break;
+                }
+            }
+
+            Vec {
+                num_bits: num_bits.try_into().expect("no more than u32::MAX bits"),
+                bits,
+                rlw,
+            }
+        }
+
+        /// Serialize this instance into `out` in a format understood by [`decode()`][super::decode()].
+        pub fn write_to(&self, mut out: impl std::io::Write) -> std::io::Result<()> {
+            let len: u32 = self.bits.len().try_into().expect("no more than u32::MAX words");
+            let rlw: u32 = self.rlw.try_into().expect("rlw position fits into u32");
+            out.write_all(&self.num_bits.to_be_bytes())?;
+            out.write_all(&len.to_be_bytes())?;
+            for word in &self.bits {
+                out.write_all(&word.to_be_bytes())?;
+            }
+            out.write_all(&rlw.to_be_bytes())
+        }
+    }
 }
 
 /// A growable collection of u64 that are seen as stream of individual bits.
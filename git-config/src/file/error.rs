@@ -1,4 +1,4 @@
-use std::{error::Error, fmt::Display};
+use std::{error::Error, fmt::Display, path::PathBuf};
 
 use crate::parser::SectionHeaderName;
 
@@ -64,3 +64,44 @@ impl Display for GitConfigFromEnvError {
 }
 
 impl Error for GitConfigFromEnvError {}
+
+/// Represents the errors that may occur when calling
+/// [`GitConfig::write_back`].
+///
+/// [`GitConfig::write_back`]: crate::file::GitConfig::write_back
+#[derive(Debug)]
+pub enum WriteBackError {
+    /// The file to write wasn't read into this config.
+    UnknownFile(PathBuf),
+    /// The file was changed on disk since it was read, and writing it would
+    /// lose these changes.
+    ModifiedOnDisk(PathBuf),
+    /// The file couldn't be read to check for modifications, or its lock
+    /// couldn't be written or committed.
+    Io(std::io::Error),
+}
+
+impl Display for WriteBackError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownFile(path) => write!(f, "'{}' is not a file of this configuration.", path.display()),
+            Self::ModifiedOnDisk(path) => write!(f, "'{}' was modified since it was read.", path.display()),
+            Self::Io(err) => write!(f, "Failed to write configuration file: {}", err),
+        }
+    }
+}
+
+impl Error for WriteBackError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for WriteBackError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
@@ -8,13 +8,15 @@ use std::{
 
 use crate::{
     file::{
-        error::{GitConfigError, GitConfigFromEnvError},
+        error::{GitConfigError, GitConfigFromEnvError, WriteBackError},
+        includes,
         section::{MutableSection, SectionBody},
         value::{EntryData, MutableMultiValue, MutableValue},
         Index, Size,
     },
+    keys,
     parser::{
-        parse_from_bytes, parse_from_path, parse_from_str, Error, Event, Key, ParsedSectionHeader, Parser,
+        parse_from_bytes, parse_from_bytes_owned, parse_from_str, Error, Event, Key, ParsedSectionHeader, Parser,
         ParserOrIoError, SectionHeaderName,
     },
 };
@@ -98,7 +100,7 @@ pub struct GitConfig<'event> {
     /// The list of events that occur before an actual section. Since a
     /// `git-config` file prohibits global values, this vec is limited to only
     /// comment, newline, and whitespace events.
-    frontmatter_events: SectionBody<'event>,
+    pub(super) frontmatter_events: SectionBody<'event>,
     /// Section name and subsection name to section id lookup tree. This is
     /// effectively a n-tree (opposed to a binary tree) that can have a height
     /// of at most three (including an implicit root node).
@@ -109,11 +111,37 @@ pub struct GitConfig<'event> {
     /// This indirection with the SectionId as the key is critical to flexibly
     /// supporting `git-config` sections, as duplicated keys are permitted.
     pub(super) sections: HashMap<SectionId, SectionBody<'event>>,
-    section_headers: HashMap<SectionId, ParsedSectionHeader<'event>>,
+    pub(super) section_headers: HashMap<SectionId, ParsedSectionHeader<'event>>,
+    /// The path of the file each section was read from, if it was read from
+    /// a file.
+    pub(super) section_paths: HashMap<SectionId, PathBuf>,
+    /// The inclusion of its file each section was read by, counting from 0,
+    /// which tells apart the sections of files included more than once.
+    /// Sections without an entry belong to the first inclusion.
+    pub(super) section_inclusions: HashMap<SectionId, usize>,
     /// Internal monotonically increasing counter for section ids.
     section_id_counter: usize,
     /// Section order for output ordering.
-    section_order: VecDeque<SectionId>,
+    pub(super) section_order: VecDeque<SectionId>,
+    /// The path of the file this config was opened from, which receives all
+    /// sections added later.
+    pub(super) path: Option<PathBuf>,
+    /// The state of each file this config was read from, to be able to write
+    /// changes back to them.
+    pub(super) files: HashMap<PathBuf, FileState<'event>>,
+}
+
+/// A file a [`GitConfig`] was read from.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub(super) struct FileState<'event> {
+    /// The events before the first section of the file.
+    pub(super) frontmatter_events: SectionBody<'event>,
+    /// The content of the file as it was read or last written.
+    pub(super) content: Vec<u8>,
+    /// The amount of times the file was read, where only the sections of the
+    /// last inclusion are written back as these are the ones changed when
+    /// setting values.
+    pub(super) inclusions: usize,
 }
 
 impl<'event> GitConfig<'event> {
@@ -130,9 +158,41 @@ impl<'event> GitConfig<'event> {
     ///
     /// Returns an error if there was an IO error or if the file wasn't a valid
     /// git-config file.
-    #[inline]
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, ParserOrIoError<'static>> {
-        parse_from_path(path).map(Self::from)
+        let path = path.as_ref();
+        let content = std::fs::read(path)?;
+        let mut config = parse_from_bytes_owned(&content)
+            .map(Self::from)
+            .map_err(ParserOrIoError::Parser)?;
+        config.section_paths = config.section_order.iter().map(|id| (*id, path.to_owned())).collect();
+        config.path = Some(path.to_owned());
+        config.files.insert(
+            path.to_owned(),
+            FileState {
+                frontmatter_events: config.frontmatter_events.clone(),
+                content,
+                inclusions: 1,
+            },
+        );
+        Ok(config)
+    }
+
+    /// Constructs a `git-config` file from the provided path, along with all
+    /// files it includes with `include.path` and `includeIf.<condition>.path`
+    /// as configured by `options`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if this or an included file couldn't be read or
+    /// parsed, or if includes couldn't be resolved.
+    pub fn open_with_includes<P: AsRef<Path>>(path: P, options: &includes::Options) -> Result<Self, includes::Error> {
+        let path = path.as_ref();
+        Self::open(path)
+            .map_err(|err| includes::Error::Parse {
+                path: path.to_owned(),
+                source: err,
+            })?
+            .resolve_includes(options)
     }
 
     /// Constructs a `git-config` file from the provided paths in the order provided.
@@ -149,12 +209,23 @@ impl<'event> GitConfig<'event> {
         let mut config = Self::new();
 
         for path in paths {
-            let other = Self::open(path)?;
-            for (section_id, section_header) in other.section_headers {
-                config.push_section(
-                    section_header.name.0.to_owned(),
-                    section_header.subsection_name.to_owned(),
-                    other.sections[&section_id].clone(),
+            let mut other = Self::open(path)?;
+            let first_inclusion = config.add_files(std::mem::take(&mut other.files));
+            for section_id in other.section_order {
+                let path = other.section_paths.remove(&section_id);
+                let inclusion = other.section_inclusions.get(&section_id).copied().unwrap_or(0);
+                let inclusion = inclusion
+                    + path
+                        .as_ref()
+                        .map_or(0, |path| first_inclusion.get(path).copied().unwrap_or(0));
+                config.push_section_with_path(
+                    other
+                        .section_headers
+                        .remove(&section_id)
+                        .expect("header for each section"),
+                    other.sections.remove(&section_id).expect("body for each section"),
+                    path,
+                    inclusion,
                 );
             }
         }
@@ -228,30 +299,8 @@ impl<'event> GitConfig<'event> {
             let key = env::var(format!("GIT_CONFIG_KEY_{}", i)).map_err(|_| GitConfigFromEnvError::InvalidKeyId(i))?;
             let value =
                 env::var(format!("GIT_CONFIG_VALUE_{}", i)).map_err(|_| GitConfigFromEnvError::InvalidValueId(i))?;
-            if let Some((section, maybe_subsection)) = key.split_once('.') {
-                let (subsection, key) = if let Some((subsection, key)) = maybe_subsection.rsplit_once('.') {
-                    (Some(subsection), key)
-                } else {
-                    (None, maybe_subsection)
-                };
-
-                let mut section = if let Ok(section) = config.section_mut(section, subsection) {
-                    section
-                } else {
-                    // Need to have config own the section and subsection names
-                    // else they get dropped at the end of the loop.
-                    config.new_section(
-                        section.to_string(),
-                        subsection.map(|subsection| Cow::Owned(subsection.to_string())),
-                    )
-                };
-
-                section.push(
-                    Cow::<str>::Owned(key.to_string()).into(),
-                    Cow::Owned(value.into_bytes()),
-                );
-            } else {
-                return Err(GitConfigFromEnvError::InvalidKeyValue(i, key.to_string()));
+            if !config.push_key_value(&key, Some(value.into_bytes())) {
+                return Err(GitConfigFromEnvError::InvalidKeyValue(i, key));
             }
         }
 
@@ -263,6 +312,41 @@ impl<'event> GitConfig<'event> {
         }
     }
 
+    /// Appends `value` to the key described by `key` in the form of
+    /// `section[.subsection].name`, creating the section if needed. If `value`
+    /// is `None`, the key is added without `=`, as implicit boolean `true`.
+    ///
+    /// Returns false if `key` didn't contain a section.
+    pub(crate) fn push_key_value(&mut self, key: &str, value: Option<Vec<u8>>) -> bool {
+        let (section, maybe_subsection) = match key.split_once('.') {
+            Some(split) => split,
+            None => return false,
+        };
+        let (subsection, key) = if let Some((subsection, key)) = maybe_subsection.rsplit_once('.') {
+            (Some(subsection), key)
+        } else {
+            (None, maybe_subsection)
+        };
+
+        let mut section = if let Ok(section) = self.section_mut(section, subsection) {
+            section
+        } else {
+            // Need to have config own the section and subsection names
+            // as they are borrowed from `key`.
+            self.new_section(
+                section.to_string(),
+                subsection.map(|subsection| Cow::Owned(subsection.to_string())),
+            )
+        };
+
+        let key = Cow::<str>::Owned(key.to_string()).into();
+        match value {
+            Some(value) => section.push(key, Cow::Owned(value)),
+            None => section.push_implicit(key),
+        }
+        true
+    }
+
     /// Returns an interpreted value given a section, an optional subsection and
     /// key.
     ///
@@ -463,6 +547,46 @@ impl<'event> GitConfig<'event> {
             .collect()
     }
 
+    /// Returns the headers and bodies of all sections named `section_name`, with or without subsection, in the
+    /// order they appear in, for access to their subsection names.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use git_config::file::GitConfig;
+    /// # use std::convert::TryFrom;
+    /// let config = r#"
+    ///     [gc "refs/remotes/*"]
+    ///         reflogExpire = never
+    ///     [gc]
+    ///         auto = 0
+    /// "#;
+    /// let git_config = GitConfig::try_from(config).unwrap();
+    /// let subsections: Vec<_> = git_config
+    ///     .sections_by_name_with_header("gc")
+    ///     .into_iter()
+    ///     .map(|(header, _)| header.subsection_name.as_deref())
+    ///     .collect();
+    /// assert_eq!(subsections, vec![Some("refs/remotes/*"), None]);
+    /// ```
+    #[must_use]
+    pub fn sections_by_name_with_header<'lookup>(
+        &self,
+        section_name: &'lookup str,
+    ) -> Vec<(&ParsedSectionHeader<'event>, &SectionBody<'event>)> {
+        let ids = self.get_section_ids_by_name(section_name).unwrap_or_default();
+        self.section_order
+            .iter()
+            .filter(|id| ids.contains(id))
+            .map(|id| {
+                (
+                    self.section_headers.get(id).expect("header for each section"),
+                    self.sections.get(id).expect("section doesn't have id from from lookup"),
+                )
+            })
+            .collect()
+    }
+
     /// Adds a new section to config. If a subsection name was provided, then
     /// the generated header will use the modern subsection syntax. Returns a
     /// reference to the new section for immediate editing.
@@ -551,17 +675,24 @@ impl<'event> GitConfig<'event> {
                 .position(|v| *v == id)
                 .expect("Section order does not contain section that we were trying to remove"),
         );
+        self.section_paths.remove(&id);
+        self.section_inclusions.remove(&id);
         self.sections.remove(&id)
     }
 
     /// Adds the provided section to the config, returning a mutable reference
-    /// to it.
+    /// to it. If this config was opened from a file, the section is written
+    /// to it by [`write_back`](Self::write_back).
     pub fn push_section(
         &mut self,
         section_name: impl Into<Cow<'event, str>>,
         subsection_name: impl Into<Option<Cow<'event, str>>>,
         section: SectionBody<'event>,
     ) -> MutableSection<'_, 'event> {
+        if let Some(path) = &self.path {
+            self.section_paths
+                .insert(SectionId(self.section_id_counter), path.clone());
+        }
         let subsection_name = subsection_name.into();
         if subsection_name.is_some() {
             self.push_section_internal(
@@ -625,6 +756,23 @@ impl<'event> GitConfig<'event> {
     }
 }
 
+/// # Well-known keys
+impl<'event> GitConfig<'event> {
+    /// Returns the value of the well-known `key`, or its default if it isn't
+    /// set. `None` is returned only if there is no default either.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error pointing to the file and line of the value if it
+    /// couldn't be converted or isn't allowed.
+    pub fn get<T: keys::Value>(&self, key: &keys::Key<T>) -> Result<Option<T>, keys::Error> {
+        match keys::lookup(key, self) {
+            Some(value) => value.map(Some),
+            None => keys::default(key),
+        }
+    }
+}
+
 /// # Raw value API
 ///
 /// These functions are the raw value API. Instead of returning Rust structures,
@@ -668,6 +816,156 @@ impl<'event> GitConfig<'event> {
         Err(GitConfigError::KeyDoesNotExist)
     }
 
+    /// Returns the path of the file the value returned by
+    /// [`get_raw_value`][Self::get_raw_value] was read from, or `None` if it
+    /// wasn't read from a file.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the key is not in the requested
+    /// section and subsection, or if the section and subsection do not exist.
+    pub fn value_path<'lookup>(
+        &self,
+        section_name: &'lookup str,
+        subsection_name: Option<&'lookup str>,
+        key: &'lookup str,
+    ) -> Result<Option<&Path>, GitConfigError<'lookup>> {
+        let key = Key(key.into());
+        for section_id in self
+            .get_section_ids_by_name_and_subname(section_name, subsection_name)?
+            .iter()
+            .rev()
+        {
+            if self
+                .sections
+                .get(section_id)
+                .expect("sections does not have section id from section ids")
+                .value(&key)
+                .is_some()
+            {
+                return Ok(self.section_paths.get(section_id).map(PathBuf::as_path));
+            }
+        }
+
+        Err(GitConfigError::KeyDoesNotExist)
+    }
+
+    /// Returns the line number, starting at 1, of the value returned by
+    /// [`get_raw_value`][Self::get_raw_value] in the file it was read from, or
+    /// `None` if it wasn't read from a file.
+    ///
+    /// Lines are counted in the file as it would be written, so changes made
+    /// since it was read are taken into account.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the key is not in the requested
+    /// section and subsection, or if the section and subsection do not exist.
+    pub fn value_line<'lookup>(
+        &self,
+        section_name: &'lookup str,
+        subsection_name: Option<&'lookup str>,
+        key: &'lookup str,
+    ) -> Result<Option<usize>, GitConfigError<'lookup>> {
+        let key = Key(key.into());
+        for section_id in self
+            .get_section_ids_by_name_and_subname(section_name, subsection_name)?
+            .iter()
+            .rev()
+        {
+            let events = self
+                .sections
+                .get(section_id)
+                .expect("sections does not have section id from section ids")
+                .as_ref();
+            let key_pos = match events
+                .iter()
+                .rposition(|event| matches!(event, Event::Key(k) if *k == key))
+            {
+                Some(pos) => pos,
+                None => continue,
+            };
+            let (path, file) = match self
+                .section_paths
+                .get(section_id)
+                .and_then(|path| self.files.get(path).map(|file| (path, file)))
+            {
+                Some(path_and_file) => path_and_file,
+                None => return Ok(None),
+            };
+
+            let newlines = |bytes: Vec<u8>| bytes.iter().filter(|b| **b == b'\n').count();
+            let mut line = 1 + file
+                .frontmatter_events
+                .as_ref()
+                .iter()
+                .map(|event| newlines(event.to_vec()))
+                .sum::<usize>();
+            let inclusion = self.inclusion(*section_id);
+            for id in self
+                .section_order
+                .iter()
+                .filter(|id| self.section_paths.get(id) == Some(path) && self.inclusion(**id) == inclusion)
+            {
+                line += newlines(self.section_headers.get(id).expect("header for each section").to_vec());
+                let events = self.sections.get(id).expect("body for each section").as_ref();
+                if id == section_id {
+                    line += events[..key_pos]
+                        .iter()
+                        .map(|event| newlines(event.to_vec()))
+                        .sum::<usize>();
+                    break;
+                }
+                line += events.iter().map(|event| newlines(event.to_vec())).sum::<usize>();
+            }
+            return Ok(Some(line));
+        }
+
+        Err(GitConfigError::KeyDoesNotExist)
+    }
+
+    /// Returns true if the value returned by
+    /// [`get_raw_value`][Self::get_raw_value] has no `=`, like `bare` as
+    /// opposed to `bare =`. Such values are an implicit boolean `true`, while
+    /// empty values are a boolean `false`, even though both are empty.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the key is not in the requested
+    /// section and subsection, or if the section and subsection do not exist.
+    pub fn value_is_implicit<'lookup>(
+        &self,
+        section_name: &'lookup str,
+        subsection_name: Option<&'lookup str>,
+        key: &'lookup str,
+    ) -> Result<bool, GitConfigError<'lookup>> {
+        let key = Key(key.into());
+        for section_id in self
+            .get_section_ids_by_name_and_subname(section_name, subsection_name)?
+            .iter()
+            .rev()
+        {
+            let events = self
+                .sections
+                .get(section_id)
+                .expect("sections does not have section id from section ids")
+                .as_ref();
+            if let Some(key_pos) = events
+                .iter()
+                .rposition(|event| matches!(event, Event::Key(k) if *k == key))
+            {
+                return Ok(!matches!(
+                    events[key_pos + 1..]
+                        .iter()
+                        .find(|event| !matches!(event, Event::Whitespace(_))),
+                    Some(Event::KeyValueSeparator)
+                ));
+            }
+        }
+
+        Err(GitConfigError::KeyDoesNotExist)
+    }
+
     /// Returns a mutable reference to an uninterpreted value given a section,
     /// an optional subsection and key.
     ///
@@ -1048,8 +1346,146 @@ impl<'event> GitConfig<'event> {
     }
 }
 
+/// # Writing files
+///
+/// These functions write the changes made to a config back to the files it
+/// was read from, which includes files it includes if these were resolved.
+impl<'event> GitConfig<'event> {
+    /// Returns the paths of all files whose sections were changed since they
+    /// were read, in lexicographical order.
+    #[must_use]
+    pub fn changed_files(&self) -> Vec<&Path> {
+        let mut paths: Vec<_> = self
+            .files
+            .iter()
+            .filter(|(path, state)| self.file_to_bytes(path, state) != state.content)
+            .map(|(path, _)| path.as_path())
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    /// Writes the file locked by `lock` if its sections were changed since it
+    /// was read, returning true if it was written. Otherwise the lock is
+    /// released and the file is left untouched.
+    ///
+    /// The file is replaced atomically once the lock is committed, preserving
+    /// the formatting and comments of everything that wasn't changed. Files
+    /// included more than once are written as read by their last inclusion,
+    /// so changes to the sections of earlier ones are lost.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the locked file wasn't read into this config, if it
+    /// was modified on disk since it was read, or if it couldn't be written.
+    pub fn write_back(&mut self, mut lock: git_lock::File) -> Result<bool, WriteBackError> {
+        use std::io::Write;
+
+        let path = lock.resource_path();
+        let state = match self.files.get(&path) {
+            Some(state) => state,
+            None => return Err(WriteBackError::UnknownFile(path)),
+        };
+        let content = self.file_to_bytes(&path, state);
+        if content == state.content {
+            return Ok(false);
+        }
+        match std::fs::read(&path) {
+            Ok(on_disk) if on_disk == state.content => {}
+            Ok(_) => return Err(WriteBackError::ModifiedOnDisk(path)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Err(WriteBackError::ModifiedOnDisk(path)),
+            Err(err) => return Err(err.into()),
+        }
+
+        lock.write_all(&content)?;
+        let (path, _) = lock.commit().map_err(|err| err.error)?;
+        self.files.get_mut(&path).expect("present").content = content;
+        Ok(true)
+    }
+
+    /// Returns true if the file at `path` was read into this config.
+    pub(crate) fn contains_file(&self, path: &Path) -> bool {
+        self.files.contains_key(path)
+    }
+
+    /// Serializes the frontmatter and all sections of the last inclusion of
+    /// the file at `path`.
+    fn file_to_bytes(&self, path: &Path, state: &FileState<'_>) -> Vec<u8> {
+        let mut value = Vec::new();
+        for event in state.frontmatter_events.as_ref() {
+            value.extend(event.to_vec());
+        }
+        for section_id in self.section_order.iter().filter(|id| {
+            self.section_paths.get(id).map(PathBuf::as_path) == Some(path)
+                && self.inclusion(**id) + 1 == state.inclusions
+        }) {
+            value.extend(
+                self.section_headers
+                    .get(section_id)
+                    .expect("section_header does not contain section id from section_order")
+                    .to_vec(),
+            );
+            for event in self
+                .sections
+                .get(section_id)
+                .expect("sections does not contain section id from section_order")
+                .as_ref()
+            {
+                value.extend(event.to_vec());
+            }
+        }
+        value
+    }
+}
+
 /// Private helper functions
 impl<'event> GitConfig<'event> {
+    /// Adds a section read by the given `inclusion` of the file at `path`, if
+    /// any, to the config file.
+    pub(super) fn push_section_with_path(
+        &mut self,
+        header: ParsedSectionHeader<'event>,
+        section: SectionBody<'event>,
+        path: Option<PathBuf>,
+        inclusion: usize,
+    ) {
+        let id = SectionId(self.section_id_counter);
+        self.push_section_internal(header, section);
+        if let Some(path) = path {
+            self.section_paths.insert(id, path);
+        }
+        if inclusion != 0 {
+            self.section_inclusions.insert(id, inclusion);
+        }
+    }
+
+    /// Adds the state of `files` read by another config, and returns the
+    /// inclusion their first inclusion in the other config has in this one.
+    pub(super) fn add_files(&mut self, files: HashMap<PathBuf, FileState<'event>>) -> HashMap<PathBuf, usize> {
+        files
+            .into_iter()
+            .map(|(path, state)| {
+                let first_inclusion = match self.files.get_mut(&path) {
+                    Some(existing) => {
+                        let first_inclusion = existing.inclusions;
+                        existing.inclusions += state.inclusions;
+                        first_inclusion
+                    }
+                    None => {
+                        self.files.insert(path.clone(), state);
+                        0
+                    }
+                };
+                (path, first_inclusion)
+            })
+            .collect()
+    }
+
+    /// Returns the inclusion of its file the section with `id` was read by.
+    fn inclusion(&self, id: SectionId) -> usize {
+        self.section_inclusions.get(&id).copied().unwrap_or(0)
+    }
+
     /// Adds a new section to the config file.
     fn push_section_internal(
         &mut self,
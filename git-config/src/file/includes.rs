@@ -0,0 +1,371 @@
+//! Resolution of `include.path` and `includeIf.<condition>.path` directives,
+//! which place the sections of the included file right after the section
+//! containing the directive.
+//!
+//! Relative paths are resolved against the directory of the file containing
+//! the directive, and included files that don't exist are ignored, just like
+//! `git` does.
+
+use std::{
+    borrow::Cow,
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use git_glob::wildmatch;
+
+use crate::{
+    file::GitConfig,
+    parser::{Key, ParsedSectionHeader, ParserOrIoError},
+    values::{self, PathInterpolationError},
+};
+
+/// Options to control how includes are resolved, along with the context that
+/// `includeIf` conditions are evaluated against.
+#[derive(Debug, PartialEq, Clone, Eq, Hash)]
+pub struct Options {
+    /// The maximum depth of nested includes, with files included by the
+    /// configuration itself being at depth 1. Defaults to 10, like in `git`.
+    pub max_depth: u8,
+    /// The `.git` directory of the repository, to evaluate `gitdir:` and
+    /// `gitdir/i:` conditions against. These never match if [`None`].
+    pub git_dir: Option<PathBuf>,
+    /// The full name of the reference `HEAD` points to, like `refs/heads/main`,
+    /// to evaluate `onbranch:` conditions against. These never match if
+    /// [`None`] or if it isn't a branch.
+    pub branch_name: Option<String>,
+    /// The URLs of all configured remotes, to evaluate
+    /// `hasconfig:remote.*.url:` conditions against. These never match if
+    /// empty.
+    pub remote_urls: Vec<Vec<u8>>,
+    /// The directory the program was installed into, to expand paths starting
+    /// with `%(prefix)/`, see [`Path::interpolate`][crate::values::Path::interpolate].
+    pub git_install_dir: Option<PathBuf>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            max_depth: 10,
+            git_dir: None,
+            branch_name: None,
+            remote_urls: Vec::new(),
+            git_install_dir: None,
+        }
+    }
+}
+
+/// The error returned when resolving includes.
+#[derive(Debug)]
+pub enum Error {
+    /// A configuration file couldn't be read or parsed.
+    Parse {
+        /// The path to the file.
+        path: PathBuf,
+        /// The underlying error.
+        source: ParserOrIoError<'static>,
+    },
+    /// Includes were nested deeper than [`Options::max_depth`].
+    MaxDepthExceeded {
+        /// The maximum depth.
+        max_depth: u8,
+    },
+    /// A file included itself, directly or indirectly.
+    Cycle {
+        /// The path of the file included again.
+        path: PathBuf,
+    },
+    /// A path relative to the file containing it was used in a configuration
+    /// not read from a file.
+    RelativePathWithoutConfigPath {
+        /// The relative path.
+        path: PathBuf,
+    },
+    /// A path or the pattern of a condition couldn't be interpolated.
+    Interpolate {
+        /// The key of the path, like `include.path`, or the condition, like
+        /// `includeIf.gitdir:~/work/`.
+        key: String,
+        /// The underlying error.
+        source: PathInterpolationError,
+    },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Parse { path, source } => write!(f, "Could not load '{}': {}", path.display(), source),
+            Error::MaxDepthExceeded { max_depth } => write!(
+                f,
+                "Exceeded the maximum include depth of {}, which might be due to circular includes",
+                max_depth
+            ),
+            Error::Cycle { path } => write!(f, "'{}' includes itself", path.display()),
+            Error::RelativePathWithoutConfigPath { path } => write!(
+                f,
+                "The relative path '{}' can only be used in configuration read from a file",
+                path.display()
+            ),
+            Error::Interpolate { key, source } => write!(f, "Could not interpolate the value of '{}': {}", key, source),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Interpolate { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl<'event> GitConfig<'event> {
+    /// Returns this config with all files it includes with `include.path` and
+    /// `includeIf.<condition>.path` inserted right after the section
+    /// containing the directive, as configured by `options`.
+    ///
+    /// Files included by files are resolved as well. Sections remember the
+    /// path of the file they were read from, see [`value_path`][Self::value_path].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an included file couldn't be read or parsed, if
+    /// files include each other or are nested too deeply, or if a path
+    /// couldn't be resolved.
+    pub fn resolve_includes(self, options: &Options) -> Result<Self, Error> {
+        let mut target = GitConfig::new();
+        target.frontmatter_events = self.frontmatter_events.clone();
+        target.path = self.path.clone();
+        resolve_into(self, &mut target, &mut Vec::new(), 0, options)?;
+        Ok(target)
+    }
+}
+
+/// Returns true if `config` contains `hasconfig:remote.*.url:` conditions,
+/// whose evaluation requires the URLs of all remotes to be known.
+pub(crate) fn uses_remote_urls(config: &GitConfig<'_>) -> bool {
+    config
+        .section_headers
+        .values()
+        .filter_map(|header| condition(header))
+        .any(|condition| condition.starts_with("hasconfig:remote.*.url:"))
+}
+
+/// Returns the URLs of all remotes configured in `config`.
+pub(crate) fn remote_urls<'a>(config: &'a GitConfig<'_>) -> impl Iterator<Item = Vec<u8>> + 'a {
+    config
+        .sections_by_name("remote")
+        .into_iter()
+        .flat_map(|section| section.values(&Key("url".into())))
+        .map(Cow::into_owned)
+}
+
+fn resolve_into<'event>(
+    config: GitConfig<'event>,
+    target: &mut GitConfig<'event>,
+    chain: &mut Vec<PathBuf>,
+    depth: u8,
+    options: &Options,
+) -> Result<(), Error> {
+    let GitConfig {
+        mut section_headers,
+        mut sections,
+        mut section_paths,
+        section_inclusions,
+        section_order,
+        files,
+        ..
+    } = config;
+    let first_inclusion = target.add_files(files);
+    for id in section_order {
+        let header = section_headers.remove(&id).expect("header for each section");
+        let body = sections.remove(&id).expect("body for each section");
+        let config_path = section_paths.remove(&id);
+        let inclusion = section_inclusions.get(&id).copied().unwrap_or(0)
+            + config_path
+                .as_ref()
+                .map_or(0, |path| first_inclusion.get(path).copied().unwrap_or(0));
+        let (key, include_paths) = match include_key(&header, config_path.as_deref(), options)? {
+            Some(key) => (key, body.values(&Key("path".into()))),
+            None => (String::new(), Vec::new()),
+        };
+        target.push_section_with_path(header, body, config_path.clone(), inclusion);
+
+        for value in include_paths.into_iter().filter(|value| !value.is_empty()) {
+            let path = resolve_path(value, &key, config_path.as_deref(), options)?;
+            let included = match GitConfig::open(&path) {
+                Ok(included) => included,
+                Err(ParserOrIoError::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(Error::Parse { path, source: err }),
+            };
+
+            let current = config_path.as_deref().map(canonicalized);
+            let canonical_path = canonicalized(&path);
+            if current.as_ref() == Some(&canonical_path) || chain.contains(&canonical_path) {
+                return Err(Error::Cycle { path });
+            }
+            if depth >= options.max_depth {
+                return Err(Error::MaxDepthExceeded {
+                    max_depth: options.max_depth,
+                });
+            }
+
+            let pushed = current.map(|path| chain.push(path)).is_some();
+            resolve_into(included, target, chain, depth + 1, options)?;
+            if pushed {
+                chain.pop();
+            }
+        }
+    }
+    Ok(())
+}
+
+fn condition<'a>(header: &'a ParsedSectionHeader<'_>) -> Option<&'a str> {
+    if header.name.0.eq_ignore_ascii_case("includeIf") {
+        header.subsection_name.as_deref()
+    } else {
+        None
+    }
+}
+
+/// Returns the key of the paths to include if `header` is the one of an
+/// include section, and if its condition is true.
+fn include_key(
+    header: &ParsedSectionHeader<'_>,
+    config_path: Option<&Path>,
+    options: &Options,
+) -> Result<Option<String>, Error> {
+    if header.name.0.eq_ignore_ascii_case("include") {
+        return Ok(header.subsection_name.is_none().then(|| "include.path".into()));
+    }
+    let condition = match condition(header) {
+        Some(condition) => condition,
+        None => return Ok(None),
+    };
+    Ok(condition_is_true(condition, config_path, options)?.then(|| format!("includeIf.{}.path", condition)))
+}
+
+fn condition_is_true(condition: &str, config_path: Option<&Path>, options: &Options) -> Result<bool, Error> {
+    if let Some(pattern) = condition.strip_prefix("gitdir:") {
+        gitdir_matches(condition, pattern, config_path, options, wildmatch::Mode::empty())
+    } else if let Some(pattern) = condition.strip_prefix("gitdir/i:") {
+        gitdir_matches(condition, pattern, config_path, options, wildmatch::Mode::IGNORE_CASE)
+    } else if let Some(pattern) = condition.strip_prefix("onbranch:") {
+        let branch = match options
+            .branch_name
+            .as_deref()
+            .and_then(|name| name.strip_prefix("refs/heads/"))
+        {
+            Some(branch) => branch,
+            None => return Ok(false),
+        };
+        let mut pattern = pattern.as_bytes().to_vec();
+        if pattern.ends_with(b"/") {
+            pattern.extend_from_slice(b"**");
+        }
+        Ok(wildmatch(
+            pattern.as_slice().into(),
+            branch.as_bytes().into(),
+            wildmatch::Mode::NO_MATCH_SLASH_LITERAL,
+        ))
+    } else if let Some(pattern) = condition.strip_prefix("hasconfig:remote.*.url:") {
+        Ok(options.remote_urls.iter().any(|url| {
+            wildmatch(
+                pattern.as_bytes().into(),
+                url.as_slice().into(),
+                wildmatch::Mode::NO_MATCH_SLASH_LITERAL,
+            )
+        }))
+    } else {
+        Ok(false)
+    }
+}
+
+fn gitdir_matches(
+    condition: &str,
+    pattern: &str,
+    config_path: Option<&Path>,
+    options: &Options,
+    mode: wildmatch::Mode,
+) -> Result<bool, Error> {
+    let git_dir = match &options.git_dir {
+        Some(git_dir) => git_dir,
+        None => return Ok(false),
+    };
+
+    let interpolated = interpolate(pattern.as_bytes(), &format!("includeIf.{}", condition), options)?;
+    let interpolated = path_to_bytes(&interpolated);
+    let mut pattern = if let Some(rest) = interpolated.strip_prefix(b"./") {
+        let dir = config_path
+            .and_then(Path::parent)
+            .ok_or_else(|| Error::RelativePathWithoutConfigPath { path: pattern.into() })?;
+        let mut dir = path_to_bytes(&canonicalized(dir)).into_owned();
+        dir.push(b'/');
+        dir.extend_from_slice(rest);
+        dir
+    } else if interpolated.starts_with(b"/") || values::bytes_to_path(&interpolated).map_or(false, Path::is_absolute) {
+        interpolated.into_owned()
+    } else {
+        let mut prefixed = b"**/".to_vec();
+        prefixed.extend_from_slice(&interpolated);
+        prefixed
+    };
+    if pattern.ends_with(b"/") {
+        pattern.extend_from_slice(b"**");
+    }
+
+    let matches = |git_dir: &Path| {
+        wildmatch(
+            pattern.as_slice().into(),
+            path_to_bytes(git_dir).as_ref().into(),
+            wildmatch::Mode::NO_MATCH_SLASH_LITERAL | mode,
+        )
+    };
+    Ok(matches(git_dir) || matches(&canonicalized(git_dir)))
+}
+
+/// Turn the `value` of the path at `key` into a path by interpolating it, and
+/// by resolving it against the directory of the file at `config_path` if it
+/// is relative.
+fn resolve_path(
+    value: Cow<'_, [u8]>,
+    key: &str,
+    config_path: Option<&Path>,
+    options: &Options,
+) -> Result<PathBuf, Error> {
+    let path = interpolate(&value, key, options)?;
+    if path.is_absolute() {
+        return Ok(path.into_owned());
+    }
+    match config_path.and_then(Path::parent) {
+        Some(dir) => Ok(dir.join(path)),
+        None => Err(Error::RelativePathWithoutConfigPath {
+            path: path.into_owned(),
+        }),
+    }
+}
+
+fn interpolate<'a>(value: &'a [u8], key: &str, options: &Options) -> Result<Cow<'a, Path>, Error> {
+    values::Path::from(Cow::Borrowed(value))
+        .interpolate(options.git_install_dir.as_deref())
+        .map_err(|source| Error::Interpolate {
+            key: key.to_owned(),
+            source,
+        })
+}
+
+fn canonicalized(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_owned())
+}
+
+#[cfg(unix)]
+fn path_to_bytes(path: &Path) -> Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().into()
+}
+
+#[cfg(not(unix))]
+fn path_to_bytes(path: &Path) -> Cow<'_, [u8]> {
+    path.to_string_lossy().replace('\\', "/").into_bytes().into()
+}
@@ -2,6 +2,7 @@
 
 mod error;
 mod git_config;
+pub mod includes;
 mod resolved;
 mod section;
 mod value;
@@ -38,6 +38,22 @@ impl<'borrow, 'event> MutableSection<'borrow, 'event> {
         }
     }
 
+    /// Adds `key` without a value to the end of this section, which is an
+    /// implicit boolean `true`.
+    pub(crate) fn push_implicit(&mut self, key: Key<'event>) {
+        if self.whitespace > 0 {
+            self.section
+                .0
+                .push(Event::Whitespace(" ".repeat(self.whitespace).into()));
+        }
+
+        self.section.0.push(Event::Key(key));
+        self.section.0.push(Event::Value(Cow::Borrowed(&[])));
+        if self.implicit_newline {
+            self.section.0.push(Event::Newline("\n".into()));
+        }
+    }
+
     /// Removes all events until a key value pair is removed. This will also
     /// remove the whitespace preceding the key value pair, if any is found.
     pub fn pop(&mut self) -> Option<(Key, Cow<'event, [u8]>)> {
@@ -1,41 +1,89 @@
-#![allow(unused)]
-#![allow(clippy::result_unit_err)]
+//! A layered view on all configuration files of a repository, as seen by `git`.
 
 use std::{
     borrow::Cow,
     convert::TryFrom,
+    fmt::Display,
     path::{Path, PathBuf},
 };
 
-use crate::file::{GitConfig, GitConfigError};
+use crate::{
+    file::{includes, GitConfig, GitConfigError, GitConfigFromEnvError, WriteBackError},
+    keys,
+    parser::ParserOrIoError,
+};
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+/// The location a configuration value was read from, in ascending order of precedence.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
 pub enum ConfigSource {
     /// System-wide configuration path. This is defined as
-    /// `$(prefix)/etc/gitconfig`.
+    /// `$(prefix)/etc/gitconfig`, with the prefix set by
+    /// [`ConfigBuilder::git_install_dir()`], or `/etc/gitconfig` if it is
+    /// `/usr` or unset.
     System,
-    /// Also known as the user configuration path. This is usually `~/.gitconfig`.
-    Global,
     /// Second user-specific configuration path; if `$XDG_CONFIG_HOME` is not
-    /// set or empty, `$HOME/.config/git/config` will be used. Any single-valued
+    /// set, empty or not [read][ConfigBuilder::load_environment_entries()],
+    /// `$HOME/.config/git/config` will be used. Any single-valued
     /// variable set in this file will be overridden by whatever is in the
     /// Global configuration file.
     User,
-
+    /// Also known as the user configuration path. This is usually `~/.gitconfig`.
+    Global,
+    /// The configuration of the repository itself, usually `.git/config`.
     Repository,
-    // Worktree(&'a Path),
+    /// The configuration of a single work tree at `$GIT_DIR/config.worktree`, which is only used
+    /// if `extensions.worktreeConfig` is set.
+    Worktree,
     /// Config values parsed from the environment.
     Env,
+    /// Config values passed on the command-line, like with `git -c section.key=value`.
     Cli,
 }
 
+/// The error returned by [`ConfigBuilder::try_build()`].
+#[derive(Debug)]
+pub enum Error {
+    /// A configuration file couldn't be read or parsed.
+    File {
+        /// The path to the file.
+        path: PathBuf,
+        /// The underlying error.
+        source: ParserOrIoError<'static>,
+    },
+    /// The files included by a configuration couldn't be resolved.
+    Includes(includes::Error),
+    /// The `GIT_CONFIG_COUNT` family of environment variables was invalid.
+    Env(GitConfigFromEnvError),
+    /// An override passed as if on the command-line wasn't of the form `section[.subsection].key[=value]`.
+    InvalidOverride(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::File { path, source } => write!(f, "Could not load '{}': {}", path.display(), source),
+            Error::Includes(err) => err.fmt(f),
+            Error::Env(err) => err.fmt(f),
+            Error::InvalidOverride(value) => write!(f, "Invalid configuration override: {:?}", value),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Configures which configuration files make up a [`Config`] and where to find them.
 #[derive(Debug, PartialEq, Clone, Eq, Hash, Default)]
 pub struct ConfigBuilder {
     no_system: bool,
+    no_global: bool,
     load_env_conf: bool,
     override_system_config: Option<PathBuf>,
     override_global_config: Option<PathBuf>,
+    override_user_config: Option<PathBuf>,
     override_repo_config: Option<PathBuf>,
+    worktree_config: Option<PathBuf>,
+    cli_overrides: Vec<String>,
+    include_options: includes::Options,
 }
 
 impl ConfigBuilder {
@@ -52,23 +100,31 @@ impl ConfigBuilder {
     /// Whether or not to skip reading settings from the system-wide
     /// `$(prefix)/etc/gitconfig` file. This corresponds to setting the
     /// `GIT_CONFIG_NOSYSTEM` environment variable.
-    #[must_use]
     pub fn no_system(&mut self, no_system: bool) -> &mut Self {
         self.no_system = no_system;
         self
     }
 
+    /// Whether or not to skip reading settings from the user-specific
+    /// `~/.gitconfig` and `$XDG_CONFIG_HOME/git/config` files.
+    pub fn no_global(&mut self, no_global: bool) -> &mut Self {
+        self.no_global = no_global;
+        self
+    }
+
     /// Whether or not to respect `GIT_CONFIG_COUNT`, `GIT_CONFIG_KEY_<n>`, and
-    /// `GIT_CONFIG_VALUE_<n>` environment variables. By default, this is true.
-    #[must_use]
+    /// `GIT_CONFIG_VALUE_<n>` environment variables, along with `GIT_CONFIG_NOSYSTEM`,
+    /// `GIT_CONFIG_SYSTEM`, `GIT_CONFIG_GLOBAL` and `XDG_CONFIG_HOME` to control
+    /// the location of configuration files. By default, this is true.
     pub fn load_environment_entries(&mut self, load_conf: bool) -> &mut Self {
         self.load_env_conf = load_conf;
         self
     }
 
     /// Override the system-wide configuration file location. Providing [`None`]
-    /// or not calling this method will use the default location.
-    #[must_use]
+    /// or not calling this method will use the default location, which is
+    /// `$(prefix)/etc/gitconfig` with the prefix set by
+    /// [`git_install_dir()`][Self::git_install_dir()].
     pub fn system_config_path(&mut self, path: Option<PathBuf>) -> &mut Self {
         self.override_system_config = path;
         self
@@ -76,65 +132,242 @@ impl ConfigBuilder {
 
     /// Override the global (user) configuration file location. Providing
     /// [`None`] or not calling this method will use the default location.
-    #[must_use]
     pub fn global_config_path(&mut self, path: Option<PathBuf>) -> &mut Self {
         self.override_global_config = path;
         self
     }
 
+    /// Override the location of the second user-specific configuration file,
+    /// usually `$XDG_CONFIG_HOME/git/config`. Providing [`None`] or not calling
+    /// this method will use the default location.
+    pub fn user_config_path(&mut self, path: Option<PathBuf>) -> &mut Self {
+        self.override_user_config = path;
+        self
+    }
+
     /// Sets where to read the repository-specific configuration file. This
     /// is equivalent to setting `GIT_CONFIG`. If none is provided, then the
     /// builder will look in the default location, `.git/config`.
-    #[must_use]
     pub fn repository_config_path(&mut self, path: Option<PathBuf>) -> &mut Self {
         self.override_repo_config = path;
         self
     }
 
+    /// Sets the path to the work tree specific configuration file, usually
+    /// `$GIT_DIR/config.worktree`, which is not read if [`None`].
+    pub fn worktree_config_path(&mut self, path: Option<PathBuf>) -> &mut Self {
+        self.worktree_config = path;
+        self
+    }
+
+    /// Use `overrides` of the form `section[.subsection].key[=value]` with the
+    /// highest precedence, as if passed with `git -c`. A key without `=value`
+    /// is set to the implicit boolean `true`, while `key=` sets it to an empty
+    /// string, which is `false`.
+    pub fn cli_overrides(&mut self, overrides: impl IntoIterator<Item = impl Into<String>>) -> &mut Self {
+        self.cli_overrides = overrides.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the `.git` directory of the repository, for `includeIf.gitdir:`
+    /// conditions to match against.
+    pub fn git_dir(&mut self, git_dir: Option<PathBuf>) -> &mut Self {
+        self.include_options.git_dir = git_dir;
+        self
+    }
+
+    /// Sets the full name of the reference `HEAD` points to, like
+    /// `refs/heads/main`, for `includeIf.onbranch:` conditions to match
+    /// against.
+    pub fn branch_name(&mut self, branch_name: Option<String>) -> &mut Self {
+        self.include_options.branch_name = branch_name;
+        self
+    }
+
+    /// Sets the directory the program was installed into, to expand paths
+    /// of included files starting with `%(prefix)/`. It is also the
+    /// `$(prefix)` of the system-wide configuration file.
+    pub fn git_install_dir(&mut self, git_install_dir: Option<PathBuf>) -> &mut Self {
+        self.include_options.git_install_dir = git_install_dir;
+        self
+    }
+
+    /// Sets the maximum depth of nested includes, which is 10 by default.
+    pub fn max_include_depth(&mut self, max_depth: u8) -> &mut Self {
+        self.include_options.max_depth = max_depth;
+        self
+    }
+
     /// Builds a config, ignoring any failed configuration files.
     #[must_use]
-    pub fn build(&self) -> Config {
-        let system_conf = if self.no_system { None } else { todo!() };
+    pub fn build(&self) -> Config<'static> {
+        self.build_inner(false).expect("errors are ignored")
+    }
 
-        let global_conf = {
-            let path = self
-                .override_global_config
-                .as_ref()
-                .map_or_else(|| Path::new(".git/config"), PathBuf::as_path);
+    /// Attempts to build a config, returning error if the environment variable
+    /// is invalid, if a config file is invalid, or if an overridden config file
+    /// does not exist. This is only recommended when you have a very controlled
+    /// system state. Otherwise, this will likely fail more often than you'd
+    /// like.
+    ///
+    /// Configuration files at their default locations may be missing, as well
+    /// as files included by them.
+    pub fn try_build(&self) -> Result<Config<'static>, Error> {
+        self.build_inner(true)
+    }
 
-            GitConfig::open(path).ok()
+    /// Returns `$(prefix)/etc/gitconfig`, where `/usr` and an unknown prefix
+    /// use `/etc/gitconfig` instead, just like git does when installed there.
+    fn default_system_config_path(&self) -> PathBuf {
+        match &self.include_options.git_install_dir {
+            Some(prefix) if prefix != Path::new("/usr") => prefix.join("etc").join("gitconfig"),
+            _ => PathBuf::from("/etc/gitconfig"),
+        }
+    }
+
+    fn build_inner(&self, fail: bool) -> Result<Config<'static>, Error> {
+        let env = |name: &str| {
+            if self.load_env_conf {
+                std::env::var_os(name).map(PathBuf::from)
+            } else {
+                None
+            }
+        };
+        let open = |path: Option<PathBuf>, must_exist: bool| -> Result<Option<GitConfig<'static>>, Error> {
+            let path = match path {
+                Some(path) => path,
+                None => return Ok(None),
+            };
+            match GitConfig::open(&path) {
+                Ok(config) => Ok(Some(config)),
+                Err(ParserOrIoError::Io(err)) if !must_exist && err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(_) if !fail => Ok(None),
+                Err(err) => Err(Error::File { path, source: err }),
+            }
         };
 
+        let xdg_config_home = env("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .or_else(|| dirs::home_dir().map(|home| home.join(".config")));
+
+        let no_system = self.no_system || env("GIT_CONFIG_NOSYSTEM").is_some();
+        let system_conf = if no_system {
+            None
+        } else {
+            match self.override_system_config.clone().or_else(|| env("GIT_CONFIG_SYSTEM")) {
+                Some(path) => open(Some(path), true)?,
+                None => open(Some(self.default_system_config_path()), false)?,
+            }
+        };
+
+        let (global_conf, user_conf) = if self.no_global {
+            (None, None)
+        } else {
+            match self.override_global_config.clone().or_else(|| env("GIT_CONFIG_GLOBAL")) {
+                // Like git, don't read the XDG location if the global configuration is overridden.
+                Some(path) => (open(Some(path), true)?, open(self.override_user_config.clone(), true)?),
+                None => (
+                    open(dirs::home_dir().map(|home| home.join(".gitconfig")), false)?,
+                    match &self.override_user_config {
+                        Some(path) => open(Some(path.clone()), true)?,
+                        None => open(
+                            xdg_config_home.as_ref().map(|dir| dir.join("git").join("config")),
+                            false,
+                        )?,
+                    },
+                ),
+            }
+        };
+
+        let repository_conf = open(
+            Some(
+                self.override_repo_config
+                    .clone()
+                    .unwrap_or_else(|| Path::new(".git").join("config")),
+            ),
+            self.override_repo_config.is_some(),
+        )?;
+        let worktree_conf = open(self.worktree_config.clone(), false)?;
+
         let env_conf = if self.load_env_conf {
-            GitConfig::from_env().ok().flatten()
+            match GitConfig::from_env() {
+                Ok(config) => config,
+                Err(_) if !fail => None,
+                Err(err) => return Err(Error::Env(err)),
+            }
         } else {
             None
         };
 
-        // let user_conf = if self.
+        let cli_conf = if self.cli_overrides.is_empty() {
+            None
+        } else {
+            let mut config = GitConfig::new();
+            for item in &self.cli_overrides {
+                let (key, value) = match item.split_once('=') {
+                    Some((key, value)) => (key, Some(value.as_bytes().to_owned())),
+                    None => (item.as_str(), None),
+                };
+                if !config.push_key_value(key, value) && fail {
+                    return Err(Error::InvalidOverride(item.to_owned()));
+                }
+            }
+            Some(config)
+        };
 
-        Config {
+        let layers = [
             system_conf,
+            user_conf,
             global_conf,
-            user_conf: todo!(),
-            repository_conf: todo!(),
-            worktree_conf: todo!(),
+            repository_conf,
+            worktree_conf,
             env_conf,
-            cli_conf: todo!(),
+            cli_conf,
+        ];
+        let mut resolved = resolve_includes(layers.clone(), &self.include_options, fail)?;
+        // Remote URLs may be configured in any file, so conditions on them can
+        // only be evaluated once all files are known.
+        if resolved.iter().flatten().any(includes::uses_remote_urls) {
+            let mut options = self.include_options.clone();
+            options
+                .remote_urls
+                .extend(resolved.iter().flatten().flat_map(includes::remote_urls));
+            resolved = resolve_includes(layers, &options, fail)?;
         }
+        let [system_conf, user_conf, global_conf, repository_conf, worktree_conf, env_conf, cli_conf] = resolved;
+
+        Ok(Config {
+            xdg_config_home,
+            system_conf,
+            global_conf,
+            user_conf,
+            repository_conf,
+            worktree_conf,
+            env_conf,
+            cli_conf,
+        })
     }
+}
 
-    /// Attempts to build a config, returning error if the environment variable
-    /// is invalid, if a config file is invalid, or if an overridden config file
-    /// does not exist. This is only recommended when you have a very controlled
-    /// system state. Otherwise, this will likely fail more often than you'd
-    /// like.
-    pub fn try_build(&self) -> Result<Config, ()> {
-        todo!()
+fn resolve_includes(
+    mut layers: [Option<GitConfig<'static>>; 7],
+    options: &includes::Options,
+    fail: bool,
+) -> Result<[Option<GitConfig<'static>>; 7], Error> {
+    for layer in layers.iter_mut() {
+        *layer = match layer.take().map(|config| config.resolve_includes(options)).transpose() {
+            Ok(config) => config,
+            Err(_) if !fail => None,
+            Err(err) => return Err(Error::Includes(err)),
+        };
     }
+    Ok(layers)
 }
 
+/// All configuration files of a repository, to be queried in order of precedence.
+#[derive(Debug, Clone, Default)]
 pub struct Config<'config> {
+    xdg_config_home: Option<PathBuf>,
     system_conf: Option<GitConfig<'config>>,
     global_conf: Option<GitConfig<'config>>,
     user_conf: Option<GitConfig<'config>>,
@@ -145,6 +378,20 @@ pub struct Config<'config> {
 }
 
 impl<'config> Config<'config> {
+    /// Returns the path to the file `name` in git's directory below
+    /// `$XDG_CONFIG_HOME`, like `~/.config/git/ignore`, or `None` if the home
+    /// directory is unknown.
+    ///
+    /// `$XDG_CONFIG_HOME` is only used if [environment entries were
+    /// loaded][ConfigBuilder::load_environment_entries()], otherwise
+    /// `~/.config` is used.
+    #[must_use]
+    pub fn xdg_config_path(&self, name: impl AsRef<Path>) -> Option<PathBuf> {
+        self.xdg_config_home.as_ref().map(|dir| dir.join("git").join(name))
+    }
+
+    /// Returns the value of `key` in the given section and optional subsection from the configuration
+    /// file with the highest precedence which contains it and in which it could be converted into `T`.
     #[inline]
     #[must_use]
     pub fn value<T: TryFrom<Cow<'config, [u8]>>>(
@@ -157,7 +404,9 @@ impl<'config> Config<'config> {
             .map(|(value, _)| value)
     }
 
-    fn value_with_source<T: TryFrom<Cow<'config, [u8]>>>(
+    /// Like [`value()`][Self::value()], but also returns the source the value was read from.
+    #[must_use]
+    pub fn value_with_source<T: TryFrom<Cow<'config, [u8]>>>(
         &'config self,
         section_name: &str,
         subsection_name: Option<&str>,
@@ -176,6 +425,62 @@ impl<'config> Config<'config> {
         None
     }
 
+    /// Returns the boolean value of `key` in the given section and optional
+    /// subsection from the configuration file with the highest precedence
+    /// which contains it as valid boolean.
+    ///
+    /// Unlike converting values into [`Boolean`][crate::values::Boolean], this
+    /// tells empty values like `key =`, which are `false`, apart from keys
+    /// without `=`, which are `true`.
+    #[must_use]
+    pub fn boolean(&self, section_name: &str, subsection_name: Option<&str>, key: &str) -> Option<bool> {
+        self.mapping().iter().find_map(|(conf, _)| {
+            let conf = conf.as_ref()?;
+            let value = conf.get_raw_value(section_name, subsection_name, key).ok()?;
+            if conf.value_is_implicit(section_name, subsection_name, key).ok()? {
+                <bool as keys::Value>::from_implicit()
+            } else {
+                <bool as keys::Value>::from_bytes(value)
+            }
+        })
+    }
+
+    /// Returns the source of the value of `key` with the highest precedence,
+    /// along with the path of the file it was read from, if it was read from
+    /// a file.
+    #[must_use]
+    pub fn value_origin(
+        &self,
+        section_name: &str,
+        subsection_name: Option<&str>,
+        key: &str,
+    ) -> Option<(ConfigSource, Option<&Path>)> {
+        self.mapping().iter().find_map(|(conf, source)| {
+            conf.as_ref()?
+                .value_path(section_name, subsection_name, key)
+                .ok()
+                .map(|path| (*source, path))
+        })
+    }
+
+    /// Returns the value of the well-known `key` from the configuration file with the highest precedence which
+    /// contains it, or its default if none does. `None` is returned only if there is no default either.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error pointing to the file and line of the value if it couldn't be converted or isn't allowed.
+    pub fn get<T: keys::Value>(&self, key: &keys::Key<T>) -> Result<Option<T>, keys::Error> {
+        match self
+            .mapping()
+            .iter()
+            .find_map(|(conf, _)| keys::lookup(key, conf.as_ref()?))
+        {
+            Some(value) => value.map(Some),
+            None => keys::default(key),
+        }
+    }
+
+    /// Like [`value()`][Self::value()], but fails if the value with the highest precedence can't be converted into `T`.
     #[inline]
     pub fn try_value<'lookup, T: TryFrom<Cow<'config, [u8]>>>(
         &'config self,
@@ -187,10 +492,10 @@ impl<'config> Config<'config> {
             .map(|res| res.map(|(value, _)| value))
     }
 
-    /// Tries to retrieve the value, returning an error if the parsing fails or
-    /// if the key was not found. On a successful parse, the value will be
-    /// returned as well as the source location. This respects the priority of
-    /// the various configuration files.
+    /// Tries to retrieve the value, returning an error if the parsing fails.
+    /// On a successful parse, the value will be returned as well as the source
+    /// location, or `None` if the key was not found. This respects the priority
+    /// of the various configuration files.
     pub fn try_value_with_source<'lookup, T: TryFrom<Cow<'config, [u8]>>>(
         &'config self,
         section_name: &'lookup str,
@@ -201,21 +506,26 @@ impl<'config> Config<'config> {
 
         for (conf, source) in mapping.iter() {
             if let Some(conf) = conf {
-                return Ok(Some((conf.value(section_name, subsection_name, key)?, *source)));
+                match conf.value(section_name, subsection_name, key) {
+                    Ok(value) => return Ok(Some((value, *source))),
+                    Err(GitConfigError::FailedConversion) => return Err(GitConfigError::FailedConversion),
+                    Err(_) => continue,
+                }
             }
         }
 
         Ok(None)
     }
 
-    /// Returns a mapping from [`GitConfig`] to [`ConfigSource`]
-    const fn mapping(&self) -> [(&Option<GitConfig>, ConfigSource); 6] {
+    /// Returns a mapping from [`GitConfig`] to [`ConfigSource`], in order of precedence.
+    const fn mapping(&self) -> [(&Option<GitConfig<'config>>, ConfigSource); 7] {
         [
             (&self.cli_conf, ConfigSource::Cli),
             (&self.env_conf, ConfigSource::Env),
+            (&self.worktree_conf, ConfigSource::Worktree),
             (&self.repository_conf, ConfigSource::Repository),
-            (&self.user_conf, ConfigSource::User),
             (&self.global_conf, ConfigSource::Global),
+            (&self.user_conf, ConfigSource::User),
             (&self.system_conf, ConfigSource::System),
         ]
     }
@@ -232,6 +542,7 @@ impl<'config> Config<'config> {
             ConfigSource::Global => self.global_conf.as_ref(),
             ConfigSource::User => self.user_conf.as_ref(),
             ConfigSource::Repository => self.repository_conf.as_ref(),
+            ConfigSource::Worktree => self.worktree_conf.as_ref(),
             ConfigSource::Env => self.env_conf.as_ref(),
             ConfigSource::Cli => self.cli_conf.as_ref(),
         }
@@ -246,8 +557,40 @@ impl<'config> Config<'config> {
             ConfigSource::Global => self.global_conf.as_mut(),
             ConfigSource::User => self.user_conf.as_mut(),
             ConfigSource::Repository => self.repository_conf.as_mut(),
+            ConfigSource::Worktree => self.worktree_conf.as_mut(),
             ConfigSource::Env => self.env_conf.as_mut(),
             ConfigSource::Cli => self.cli_conf.as_mut(),
         }
     }
+
+    /// Returns the paths of all files whose sections were changed since they
+    /// were read, in order of precedence.
+    #[must_use]
+    pub fn changed_files(&self) -> Vec<&Path> {
+        self.mapping()
+            .iter()
+            .filter_map(|(conf, _)| conf.as_ref())
+            .flat_map(GitConfig::changed_files)
+            .collect()
+    }
+
+    /// Writes the file locked by `lock` if it was changed, as done by
+    /// [`GitConfig::write_back`] of the configuration it was read into.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the locked file isn't part of any configuration, or
+    /// if it couldn't be written.
+    pub fn write_back(&mut self, lock: git_lock::File) -> Result<bool, WriteBackError> {
+        let path = lock.resource_path();
+        let source = self
+            .mapping()
+            .iter()
+            .find(|(conf, _)| conf.as_ref().map_or(false, |conf| conf.contains_file(&path)))
+            .map(|(_, source)| *source);
+        match source.and_then(|source| self.get_config_mut(source)) {
+            Some(config) => config.write_back(lock),
+            None => Err(WriteBackError::UnknownFile(path)),
+        }
+    }
 }
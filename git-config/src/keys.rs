@@ -0,0 +1,506 @@
+//! A registry of well-known configuration keys along with their type, default
+//! value, allowed values and deprecation status, for typed access with
+//! [`GitConfig::get`] and [`Config::get`].
+//!
+//! ```
+//! # use git_config::{file::GitConfig, keys};
+//! # use std::convert::TryFrom;
+//! let config = GitConfig::try_from("[core]\n\tcompression = 5\n").unwrap();
+//! assert_eq!(config.get(&keys::core::COMPRESSION).unwrap(), Some(5));
+//! assert_eq!(config.get(&keys::fetch::PRUNE).unwrap(), Some(false), "defaults are applied");
+//! ```
+//!
+//! [`GitConfig::get`]: crate::file::GitConfig::get
+//! [`Config::get`]: crate::fs::Config::get
+
+use std::{
+    borrow::Cow,
+    convert::TryFrom,
+    fmt::{self, Display},
+    marker::PhantomData,
+    path::PathBuf,
+};
+
+use crate::{
+    file::GitConfig,
+    values::{Boolean, Integer, Path},
+};
+
+/// The type of the value of a [`Key`].
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum Kind {
+    /// A boolean like `true`, `no` or `1`.
+    Boolean,
+    /// An integer with an optional `k`, `m` or `g` suffix.
+    Integer,
+    /// An arbitrary string.
+    String,
+    /// A path, which may need interpolation.
+    Path,
+}
+
+impl Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Boolean => "boolean",
+            Self::Integer => "integer",
+            Self::String => "string",
+            Self::Path => "path",
+        })
+    }
+}
+
+/// Everything we know about a [`Key`], independently of the type of its
+/// value.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Metadata {
+    /// The name of the key in the form of `section.name`, like `core.bare`.
+    pub name: &'static str,
+    /// The type of the value.
+    pub kind: Kind,
+    /// The value used if the key isn't set, in its serialized form.
+    pub default: Option<&'static str>,
+    /// If not empty, the only values the key may have, compared
+    /// case-insensitively.
+    pub allowed_values: &'static [&'static str],
+    /// The smallest value an integer may have.
+    pub min: Option<i64>,
+    /// The largest value an integer may have.
+    pub max: Option<i64>,
+    /// If set, the key shouldn't be used anymore, for the given reason.
+    pub deprecated: Option<&'static str>,
+}
+
+/// A configuration key whose value is of type `T`.
+pub struct Key<T> {
+    /// Everything we know about the key.
+    pub metadata: Metadata,
+    value: PhantomData<T>,
+}
+
+impl<T> Clone for Key<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Key<T> {}
+
+impl<T> fmt::Debug for Key<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.metadata.fmt(f)
+    }
+}
+
+impl Key<bool> {
+    /// Creates a boolean key with the given `name`.
+    #[must_use]
+    pub const fn boolean(name: &'static str) -> Self {
+        Self::new(name, Kind::Boolean)
+    }
+}
+
+impl Key<i64> {
+    /// Creates an integer key with the given `name`.
+    #[must_use]
+    pub const fn integer(name: &'static str) -> Self {
+        Self::new(name, Kind::Integer)
+    }
+
+    /// Only allow values between `min` and `max`, inclusive.
+    #[must_use]
+    pub const fn with_range(mut self, min: Option<i64>, max: Option<i64>) -> Self {
+        self.metadata.min = min;
+        self.metadata.max = max;
+        self
+    }
+}
+
+impl Key<Vec<u8>> {
+    /// Creates a string key with the given `name`.
+    #[must_use]
+    pub const fn string(name: &'static str) -> Self {
+        Self::new(name, Kind::String)
+    }
+}
+
+impl Key<Path<'static>> {
+    /// Creates a path key with the given `name`.
+    #[must_use]
+    pub const fn path(name: &'static str) -> Self {
+        Self::new(name, Kind::Path)
+    }
+}
+
+impl<T> Key<T> {
+    const fn new(name: &'static str, kind: Kind) -> Self {
+        Key {
+            metadata: Metadata {
+                name,
+                kind,
+                default: None,
+                allowed_values: &[],
+                min: None,
+                max: None,
+                deprecated: None,
+            },
+            value: PhantomData,
+        }
+    }
+
+    /// Use `default` if the key isn't set.
+    #[must_use]
+    pub const fn with_default(mut self, default: &'static str) -> Self {
+        self.metadata.default = Some(default);
+        self
+    }
+
+    /// Only allow the given `values`.
+    #[must_use]
+    pub const fn with_allowed_values(mut self, values: &'static [&'static str]) -> Self {
+        self.metadata.allowed_values = values;
+        self
+    }
+
+    /// Mark the key as deprecated for the given `reason`.
+    #[must_use]
+    pub const fn deprecated(mut self, reason: &'static str) -> Self {
+        self.metadata.deprecated = Some(reason);
+        self
+    }
+
+    /// The name of the key in the form of `section.name`.
+    #[must_use]
+    pub const fn name(&self) -> &'static str {
+        self.metadata.name
+    }
+}
+
+/// A type that values of a [`Key`] can be converted into.
+pub trait Value: Sized {
+    /// Returns the value represented by `value`, or `None` if it isn't valid.
+    fn from_bytes(value: Cow<'_, [u8]>) -> Option<Self>;
+
+    /// Returns the value represented by a key without `=`, like `bare` as
+    /// opposed to `bare =`, or `None` if it isn't valid.
+    fn from_implicit() -> Option<Self> {
+        Self::from_bytes(Cow::Borrowed(&[]))
+    }
+}
+
+impl Value for bool {
+    fn from_bytes(value: Cow<'_, [u8]>) -> Option<Self> {
+        if value.is_empty() {
+            return Some(false);
+        }
+        Boolean::try_from(value).ok().map(|b| matches!(b, Boolean::True(_)))
+    }
+
+    fn from_implicit() -> Option<Self> {
+        Some(true)
+    }
+}
+
+impl Value for i64 {
+    fn from_bytes(value: Cow<'_, [u8]>) -> Option<Self> {
+        let integer = Integer::try_from(value).ok()?;
+        match integer.suffix {
+            Some(suffix) => integer.value.checked_mul(1 << suffix.bitwise_offset()),
+            None => Some(integer.value),
+        }
+    }
+}
+
+impl Value for Vec<u8> {
+    fn from_bytes(value: Cow<'_, [u8]>) -> Option<Self> {
+        Some(value.into_owned())
+    }
+}
+
+impl Value for Path<'static> {
+    fn from_bytes(value: Cow<'_, [u8]>) -> Option<Self> {
+        Some(Cow::<'static, [u8]>::Owned(value.into_owned()).into())
+    }
+}
+
+/// The reason a value couldn't be converted, as part of an [`Error`].
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum ErrorKind {
+    /// The value couldn't be converted into the type of the key.
+    InvalidValue(Kind),
+    /// The value isn't one of the allowed values.
+    NotAllowed(&'static [&'static str]),
+    /// The integer is outside of the allowed range.
+    OutOfRange {
+        /// The smallest allowed value.
+        min: Option<i64>,
+        /// The largest allowed value.
+        max: Option<i64>,
+    },
+}
+
+/// The error returned when the value of a [`Key`] is invalid.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Error {
+    /// The name of the key.
+    pub key: &'static str,
+    /// The invalid value.
+    pub value: Vec<u8>,
+    /// Why the value is invalid.
+    pub kind: ErrorKind,
+    /// The path of the file containing the value, if it was read from a file.
+    pub path: Option<PathBuf>,
+    /// The line in the file containing the value, starting at 1.
+    pub line: Option<usize>,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}", path.display())?;
+            if let Some(line) = self.line {
+                write!(f, ":{}", line)?;
+            }
+            write!(f, ": ")?;
+        }
+        write!(
+            f,
+            "Invalid value '{}' for {}: ",
+            String::from_utf8_lossy(&self.value),
+            self.key
+        )?;
+        match &self.kind {
+            ErrorKind::InvalidValue(kind) => write!(f, "expected a {}", kind),
+            ErrorKind::NotAllowed(values) => write!(f, "expected one of {}", values.join(", ")),
+            ErrorKind::OutOfRange { min, max } => match (min, max) {
+                (Some(min), Some(max)) => write!(f, "expected a value between {} and {}", min, max),
+                (Some(min), None) => write!(f, "expected a value of at least {}", min),
+                (None, Some(max)) => write!(f, "expected a value of at most {}", max),
+                (None, None) => unreachable!("a range has at least one bound"),
+            },
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Returns the converted value of `key` in `config`, or `None` if it isn't
+/// set there.
+pub(crate) fn lookup<T: Value>(key: &Key<T>, config: &GitConfig<'_>) -> Option<Result<T, Error>> {
+    let (section, subsection, name) = split_name(key.metadata.name);
+    let value = config.get_raw_value(section, subsection, name).ok()?;
+    let is_implicit = config.value_is_implicit(section, subsection, name).unwrap_or(false);
+    Some(convert(key, value.clone(), is_implicit).map_err(|kind| {
+        Error {
+            key: key.metadata.name,
+            value: value.into_owned(),
+            kind,
+            path: config
+                .value_path(section, subsection, name)
+                .ok()
+                .flatten()
+                .map(ToOwned::to_owned),
+            line: config.value_line(section, subsection, name).ok().flatten(),
+        }
+    }))
+}
+
+/// Returns the default value of `key`, if it has one.
+pub(crate) fn default<T: Value>(key: &Key<T>) -> Result<Option<T>, Error> {
+    key.metadata
+        .default
+        .map(|default| {
+            convert(key, Cow::Borrowed(default.as_bytes()), false).map_err(|kind| Error {
+                key: key.metadata.name,
+                value: default.into(),
+                kind,
+                path: None,
+                line: None,
+            })
+        })
+        .transpose()
+}
+
+/// Returns the metadata of the well-known key with the given `name`, compared
+/// case-insensitively.
+#[must_use]
+pub fn find(name: &str) -> Option<&'static Metadata> {
+    ALL.iter().find(|metadata| metadata.name.eq_ignore_ascii_case(name))
+}
+
+fn convert<T: Value>(key: &Key<T>, value: Cow<'_, [u8]>, is_implicit: bool) -> Result<T, ErrorKind> {
+    let metadata = &key.metadata;
+    if !metadata.allowed_values.is_empty()
+        && !metadata
+            .allowed_values
+            .iter()
+            .any(|allowed| allowed.as_bytes().eq_ignore_ascii_case(&value))
+    {
+        return Err(ErrorKind::NotAllowed(metadata.allowed_values));
+    }
+    let integer = if metadata.kind == Kind::Integer {
+        i64::from_bytes(value.clone())
+    } else {
+        None
+    };
+    let converted = if is_implicit {
+        T::from_implicit()
+    } else {
+        T::from_bytes(value)
+    }
+    .ok_or(ErrorKind::InvalidValue(metadata.kind))?;
+    if let Some(integer) = integer {
+        if metadata.min.map_or(false, |min| integer < min) || metadata.max.map_or(false, |max| integer > max) {
+            return Err(ErrorKind::OutOfRange {
+                min: metadata.min,
+                max: metadata.max,
+            });
+        }
+    }
+    Ok(converted)
+}
+
+/// Split `name` into section, subsection and name, with the subsection possibly
+/// containing dots.
+fn split_name(name: &str) -> (&str, Option<&str>, &str) {
+    let (section, rest) = name.split_once('.').expect("keys have a section");
+    match rest.rsplit_once('.') {
+        Some((subsection, name)) => (section, Some(subsection), name),
+        None => (section, None, rest),
+    }
+}
+
+/// All well-known keys.
+pub const ALL: &[Metadata] = &[
+    self::core::BARE.metadata,
+    self::core::COMPRESSION.metadata,
+    self::core::LOOSE_COMPRESSION.metadata,
+    self::core::MULTI_PACK_INDEX.metadata,
+    self::core::HOOKS_PATH.metadata,
+    self::core::EXCLUDES_FILE.metadata,
+    self::core::SPLIT_INDEX.metadata,
+    self::core::WORKTREE.metadata,
+    self::split_index::MAX_PERCENT_CHANGE.metadata,
+    self::pack::COMPRESSION.metadata,
+    self::pack::THREADS.metadata,
+    self::pack::WINDOW.metadata,
+    self::pack::DEPTH.metadata,
+    self::http::LOW_SPEED_LIMIT.metadata,
+    self::http::LOW_SPEED_TIME.metadata,
+    self::http::SSL_VERIFY.metadata,
+    self::http::VERSION.metadata,
+    self::fetch::PRUNE.metadata,
+    self::fetch::PRUNE_TAGS.metadata,
+    self::fetch::NEGOTIATION_ALGORITHM.metadata,
+    self::push::DEFAULT.metadata,
+    self::sendemail::SMTP_SSL.metadata,
+];
+
+/// Keys of the `core` section.
+pub mod core {
+    use super::Key;
+    use crate::values::Path;
+
+    /// `core.bare`, true if the repository has no work tree.
+    pub const BARE: Key<bool> = Key::boolean("core.bare").with_default("false");
+    /// `core.compression`, the default zlib compression level.
+    pub const COMPRESSION: Key<i64> = Key::integer("core.compression")
+        .with_default("-1")
+        .with_range(Some(-1), Some(9));
+    /// `core.looseCompression`, the zlib compression level of loose objects.
+    pub const LOOSE_COMPRESSION: Key<i64> = Key::integer("core.looseCompression").with_range(Some(-1), Some(9));
+    /// `core.multiPackIndex`, true if multi-pack indices should be used.
+    pub const MULTI_PACK_INDEX: Key<bool> = Key::boolean("core.multiPackIndex").with_default("true");
+    /// `core.hooksPath`, the directory containing hooks.
+    pub const HOOKS_PATH: Key<Path<'static>> = Key::path("core.hooksPath");
+    /// `core.excludesFile`, the file with additional exclude patterns.
+    pub const EXCLUDES_FILE: Key<Path<'static>> = Key::path("core.excludesFile");
+    /// `core.splitIndex`, true if the index should be written as split index.
+    pub const SPLIT_INDEX: Key<bool> = Key::boolean("core.splitIndex").with_default("false");
+    /// `core.worktree`, the work tree of a repository whose git dir isn't contained in it, relative to the git dir.
+    pub const WORKTREE: Key<Path<'static>> = Key::path("core.worktree");
+}
+
+/// Keys of the `splitIndex` section.
+pub mod split_index {
+    use super::Key;
+
+    /// `splitIndex.maxPercentChange`, the percentage of entries of the shared index that may be changed in the split index
+    /// before a new shared index is written.
+    pub const MAX_PERCENT_CHANGE: Key<i64> = Key::integer("splitIndex.maxPercentChange")
+        .with_default("20")
+        .with_range(Some(0), Some(100));
+}
+
+/// Keys of the `pack` section.
+pub mod pack {
+    use super::Key;
+
+    /// `pack.compression`, the zlib compression level of objects in packs.
+    pub const COMPRESSION: Key<i64> = Key::integer("pack.compression").with_range(Some(-1), Some(9));
+    /// `pack.threads`, the amount of threads to use when packing, with 0
+    /// meaning one per logical core.
+    pub const THREADS: Key<i64> = Key::integer("pack.threads").with_default("0").with_range(Some(0), None);
+    /// `pack.window`, the amount of objects to consider for deltas.
+    pub const WINDOW: Key<i64> = Key::integer("pack.window").with_default("10").with_range(Some(0), None);
+    /// `pack.depth`, the maximum length of delta chains.
+    pub const DEPTH: Key<i64> = Key::integer("pack.depth")
+        .with_default("50")
+        .with_range(Some(0), Some(4095));
+}
+
+/// Keys of the `http` section.
+pub mod http {
+    use super::Key;
+
+    /// `http.lowSpeedLimit`, the transfer rate in bytes per second below which
+    /// transfers are aborted after `http.lowSpeedTime` seconds.
+    pub const LOW_SPEED_LIMIT: Key<i64> = Key::integer("http.lowSpeedLimit")
+        .with_default("0")
+        .with_range(Some(0), None);
+    /// `http.lowSpeedTime`, the amount of seconds to tolerate transfer rates
+    /// below `http.lowSpeedLimit`.
+    pub const LOW_SPEED_TIME: Key<i64> = Key::integer("http.lowSpeedTime")
+        .with_default("0")
+        .with_range(Some(0), None);
+    /// `http.sslVerify`, true if SSL certificates should be verified.
+    pub const SSL_VERIFY: Key<bool> = Key::boolean("http.sslVerify").with_default("true");
+    /// `http.version`, the HTTP protocol version to use.
+    pub const VERSION: Key<Vec<u8>> = Key::string("http.version").with_allowed_values(&["HTTP/1.1", "HTTP/2"]);
+}
+
+/// Keys of the `fetch` section.
+pub mod fetch {
+    use super::Key;
+
+    /// `fetch.prune`, true if remote-tracking references that don't exist on
+    /// the remote anymore should be removed.
+    pub const PRUNE: Key<bool> = Key::boolean("fetch.prune").with_default("false");
+    /// `fetch.pruneTags`, true if local tags that don't exist on the remote
+    /// anymore should be removed when pruning.
+    pub const PRUNE_TAGS: Key<bool> = Key::boolean("fetch.pruneTags").with_default("false");
+    /// `fetch.negotiationAlgorithm`, the way to tell the server which objects
+    /// we have.
+    pub const NEGOTIATION_ALGORITHM: Key<Vec<u8>> = Key::string("fetch.negotiationAlgorithm")
+        .with_default("default")
+        .with_allowed_values(&["default", "consecutive", "skipping", "noop"]);
+}
+
+/// Keys of the `push` section.
+pub mod push {
+    use super::Key;
+
+    /// `push.default`, the references to push if none are given.
+    pub const DEFAULT: Key<Vec<u8>> = Key::string("push.default")
+        .with_default("simple")
+        .with_allowed_values(&["nothing", "current", "upstream", "tracking", "simple", "matching"]);
+}
+
+/// Keys of the `sendemail` section.
+pub mod sendemail {
+    use super::Key;
+
+    /// `sendemail.smtpSSL`, true if SSL should be used.
+    pub const SMTP_SSL: Key<bool> =
+        Key::boolean("sendemail.smtpSSL").deprecated("use 'sendemail.smtpEncryption = ssl' instead");
+}
@@ -57,6 +57,7 @@ extern crate serde_crate as serde;
 
 pub mod file;
 pub mod fs;
+pub mod keys;
 pub mod parser;
 pub mod values;
 
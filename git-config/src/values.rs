@@ -1124,6 +1124,144 @@ impl TryFrom<&[u8]> for ColorAttribute {
     }
 }
 
+/// Any value that can be interpreted as a file path.
+///
+/// Paths are kept as written in the configuration and need to be
+/// [interpolated][Path::interpolate] before they can be used.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct Path<'a> {
+    /// The path as written in the configuration, without interpolation.
+    pub value: Cow<'a, [u8]>,
+}
+
+impl<'a> Path<'a> {
+    /// Interpolates this path into a path on the file system.
+    ///
+    /// - `~/` and `~` are expanded to the home directory of the current user.
+    /// - `~user/` and `~user` are expanded to the home directory of `user` as
+    ///   listed in `/etc/passwd`, which is only supported on unix.
+    /// - `%(prefix)/` is expanded to `git_install_dir`, the directory the
+    ///   program was installed into, like `/usr` or `/usr/local`.
+    ///
+    /// All other paths are returned as they are.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a directory to expand to couldn't be determined,
+    /// or if the path isn't valid UTF-8 on a platform that requires it.
+    pub fn interpolate(
+        self,
+        git_install_dir: Option<&std::path::Path>,
+    ) -> Result<Cow<'a, std::path::Path>, PathInterpolationError> {
+        const PREFIX: &[u8] = b"%(prefix)/";
+        if let Some(rest) = self.value.strip_prefix(PREFIX) {
+            let install_dir = git_install_dir.ok_or(PathInterpolationError::MissingInstallDir)?;
+            return Ok(install_dir.join(bytes_to_path(rest)?).into());
+        }
+        if let Some(rest) = self.value.strip_prefix(b"~") {
+            let (user, rest) = match rest.iter().position(|b| *b == b'/') {
+                Some(pos) => (&rest[..pos], &rest[pos + 1..]),
+                None => (rest, &[][..]),
+            };
+            let home = if user.is_empty() {
+                dirs::home_dir().ok_or(PathInterpolationError::MissingHomeDir)?
+            } else {
+                let user = std::str::from_utf8(user).map_err(|_| PathInterpolationError::IllformedUtf8)?;
+                home_dir_of(user)?
+            };
+            return Ok(home.join(bytes_to_path(rest)?).into());
+        }
+        match self.value {
+            Cow::Borrowed(value) => bytes_to_path(value).map(Cow::Borrowed),
+            Cow::Owned(value) => bytes_to_path(&value).map(|path| Cow::Owned(path.to_owned())),
+        }
+    }
+}
+
+impl<'a> From<Cow<'a, [u8]>> for Path<'a> {
+    #[inline]
+    fn from(value: Cow<'a, [u8]>) -> Self {
+        Path { value }
+    }
+}
+
+impl AsRef<[u8]> for Path<'_> {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        &self.value
+    }
+}
+
+/// The error returned by [`Path::interpolate`].
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum PathInterpolationError {
+    /// `%(prefix)/` was used, but the installation directory is unknown.
+    MissingInstallDir,
+    /// `~/` was used, but the home directory of the current user is unknown.
+    MissingHomeDir,
+    /// `~user/` was used, but the home directory of the user couldn't be
+    /// found.
+    UnknownUser(String),
+    /// `~user/` was used on a platform that doesn't support it.
+    UserInterpolationUnsupported,
+    /// The path isn't valid UTF-8 on a platform that requires it.
+    IllformedUtf8,
+}
+
+impl Display for PathInterpolationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingInstallDir => write!(
+                f,
+                "'%(prefix)/' can't be expanded as the installation directory is unknown"
+            ),
+            Self::MissingHomeDir => write!(f, "'~/' can't be expanded as the home directory is unknown"),
+            Self::UnknownUser(user) => write!(f, "The home directory of user '{}' could not be found", user),
+            Self::UserInterpolationUnsupported => write!(f, "'~user/' can't be expanded on this platform"),
+            Self::IllformedUtf8 => write!(f, "The path is not valid UTF-8"),
+        }
+    }
+}
+
+impl std::error::Error for PathInterpolationError {}
+
+#[cfg(unix)]
+fn home_dir_of(user: &str) -> Result<std::path::PathBuf, PathInterpolationError> {
+    std::fs::read_to_string("/etc/passwd")
+        .ok()
+        .and_then(|passwd| home_dir_in_passwd(&passwd, user))
+        .ok_or_else(|| PathInterpolationError::UnknownUser(user.to_owned()))
+}
+
+/// Returns the home directory of `user` as listed in `passwd`, in the format of `/etc/passwd`.
+#[cfg(unix)]
+fn home_dir_in_passwd(passwd: &str, user: &str) -> Option<std::path::PathBuf> {
+    passwd.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        let name = fields.next()?;
+        let home = fields.nth(4)?;
+        (name == user).then(|| home.into())
+    })
+}
+
+#[cfg(not(unix))]
+fn home_dir_of(_user: &str) -> Result<std::path::PathBuf, PathInterpolationError> {
+    Err(PathInterpolationError::UserInterpolationUnsupported)
+}
+
+#[cfg(unix)]
+pub(crate) fn bytes_to_path(value: &[u8]) -> Result<&std::path::Path, PathInterpolationError> {
+    use std::os::unix::ffi::OsStrExt;
+    Ok(std::ffi::OsStr::from_bytes(value).as_ref())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn bytes_to_path(value: &[u8]) -> Result<&std::path::Path, PathInterpolationError> {
+    std::str::from_utf8(value)
+        .map(std::path::Path::new)
+        .map_err(|_| PathInterpolationError::IllformedUtf8)
+}
+
 #[cfg(test)]
 mod normalize {
     use std::borrow::Cow;
@@ -1390,3 +1528,71 @@ mod color_attribute {
         assert!(ColorAttribute::from_str("no-").is_err());
     }
 }
+
+#[cfg(test)]
+mod path {
+    use std::{borrow::Cow, path::Path as FsPath};
+
+    use super::{Path, PathInterpolationError};
+
+    fn interpolate<'a>(
+        value: &'a str,
+        git_install_dir: Option<&FsPath>,
+    ) -> Result<Cow<'a, FsPath>, PathInterpolationError> {
+        Path::from(Cow::Borrowed(value.as_bytes())).interpolate(git_install_dir)
+    }
+
+    #[test]
+    fn plain_paths_are_borrowed() {
+        assert_eq!(
+            interpolate("relative/path", None),
+            Ok(Cow::Borrowed(FsPath::new("relative/path")))
+        );
+        assert_eq!(
+            interpolate("/absolute", None),
+            Ok(Cow::Borrowed(FsPath::new("/absolute")))
+        );
+        assert_eq!(
+            interpolate("/absolute/%(prefix)/~/", None),
+            Ok(Cow::Borrowed(FsPath::new("/absolute/%(prefix)/~/"))),
+            "only prefixes are interpolated"
+        );
+    }
+
+    #[test]
+    fn prefix_is_the_installation_directory() {
+        assert_eq!(
+            interpolate("%(prefix)/etc/gitconfig", Some(FsPath::new("/usr/local"))),
+            Ok(Cow::Owned(FsPath::new("/usr/local/etc/gitconfig").to_owned()))
+        );
+        assert_eq!(
+            interpolate("%(prefix)/etc/gitconfig", None),
+            Err(PathInterpolationError::MissingInstallDir)
+        );
+    }
+
+    #[test]
+    fn tilde_is_the_home_directory() {
+        let home = dirs::home_dir().expect("home is set in tests");
+        assert_eq!(
+            interpolate("~/.gitignore", None),
+            Ok(Cow::Owned(home.join(".gitignore")))
+        );
+        assert_eq!(interpolate("~", None), Ok(Cow::Owned(home.join(""))));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn tilde_user_is_the_home_directory_of_that_user() {
+        let passwd = "# comment\nalice:x:1000:1000:Alice:/home/alice:/bin/sh\nbob:x:1001:1001::/srv/bob:/bin/sh\n";
+        assert_eq!(
+            super::home_dir_in_passwd(passwd, "bob"),
+            Some(FsPath::new("/srv/bob").to_owned())
+        );
+        assert_eq!(super::home_dir_in_passwd(passwd, "Alice"), None, "names aren't users");
+        assert_eq!(
+            interpolate("~does-not-exist/x", None),
+            Err(PathInterpolationError::UnknownUser("does-not-exist".into()))
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use std::{borrow::Cow, path::Path};
+
+use git_config::{
+    fs::{ConfigBuilder, ConfigSource, Error},
+    keys,
+    values::{Boolean, Integer},
+};
+
+fn builder(dir: &Path) -> ConfigBuilder {
+    let mut builder = ConfigBuilder::default();
+    builder
+        .system_config_path(Some(dir.join("system")))
+        .global_config_path(Some(dir.join("global")))
+        .user_config_path(Some(dir.join("user")))
+        .repository_config_path(Some(dir.join("config")))
+        .worktree_config_path(Some(dir.join("config.worktree")));
+    builder
+}
+
+#[test]
+fn values_are_looked_up_by_precedence_along_with_their_source() -> crate::Result {
+    let dir = tempfile::tempdir()?;
+    for (name, value) in [
+        ("system", "system"),
+        ("user", "user"),
+        ("global", "global"),
+        ("config", "repository"),
+        ("config.worktree", "worktree"),
+    ] {
+        std::fs::write(
+            dir.path().join(name),
+            format!("[core]\n\tsource = {}\n\t{} = 42\n", value, value),
+        )?;
+    }
+    let config = builder(dir.path())
+        .cli_overrides(vec!["core.cli", "core.source=cli"])
+        .try_build()?;
+
+    let source = |key: &str| {
+        config
+            .value_with_source::<Integer>("core", None, key)
+            .map(|(value, source)| (value.value, source))
+    };
+    assert_eq!(source("system"), Some((42, ConfigSource::System)));
+    assert_eq!(source("user"), Some((42, ConfigSource::User)));
+    assert_eq!(source("global"), Some((42, ConfigSource::Global)));
+    assert_eq!(source("repository"), Some((42, ConfigSource::Repository)));
+    assert_eq!(source("worktree"), Some((42, ConfigSource::Worktree)));
+    assert_eq!(source("missing"), None);
+
+    assert_eq!(
+        config.value::<Cow<'_, [u8]>>("core", None, "source").as_deref(),
+        Some(&b"cli"[..]),
+        "the command-line overrides everything"
+    );
+    assert!(matches!(
+        config.value::<Boolean<'_>>("core", None, "cli"),
+        Some(Boolean::True(_))
+    ));
+    assert!(
+        config.try_value::<Integer>("core", None, "source").is_err(),
+        "the value with the highest precedence must be convertible"
+    );
+    assert_eq!(
+        config
+            .get_config(ConfigSource::Worktree)
+            .expect("present")
+            .value::<Integer>("core", None, "worktree")?
+            .value,
+        42
+    );
+    Ok(())
+}
+
+#[test]
+fn missing_and_invalid_files() -> crate::Result {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("config"), "[core]\n\tbare = false\n")?;
+    std::fs::write(dir.path().join("user"), "")?;
+    assert!(
+        matches!(builder(dir.path()).try_build(), Err(Error::File { path, .. }) if path == dir.path().join("system")),
+        "overridden files must exist"
+    );
+
+    let config = builder(dir.path()).build();
+    assert!(config.get_config(ConfigSource::System).is_none());
+    assert!(config.get_config(ConfigSource::Repository).is_some());
+    assert!(
+        config.get_config(ConfigSource::Worktree).is_none(),
+        "work tree configuration is optional"
+    );
+
+    let config = builder(dir.path()).no_system(true).no_global(true).try_build()?;
+    assert!(config.get_config(ConfigSource::Global).is_none());
+
+    std::fs::write(dir.path().join("config"), "[core")?;
+    assert!(builder(dir.path()).no_system(true).no_global(true).try_build().is_err());
+    assert!(matches!(
+        ConfigBuilder::default()
+            .no_system(true)
+            .no_global(true)
+            .repository_config_path(Some(dir.path().join("user")))
+            .cli_overrides(Some("no-section"))
+            .try_build(),
+        Err(Error::InvalidOverride(value)) if value == "no-section"
+    ));
+    Ok(())
+}
+
+#[test]
+fn overrides_with_empty_values_are_false_and_without_values_are_true() -> crate::Result {
+    let config = ConfigBuilder::default()
+        .no_system(true)
+        .no_global(true)
+        .cli_overrides(vec!["core.bare=", "core.splitIndex", "user.name="])
+        .try_build()?;
+    assert_eq!(config.get(&keys::core::BARE)?, Some(false));
+    assert_eq!(config.get(&keys::core::SPLIT_INDEX)?, Some(true));
+    assert_eq!(config.boolean("core", None, "bare"), Some(false));
+    assert_eq!(config.boolean("core", None, "splitIndex"), Some(true));
+    assert_eq!(
+        config.value::<Cow<'_, [u8]>>("user", None, "name").as_deref(),
+        Some(&b""[..]),
+        "empty values are empty strings"
+    );
+    Ok(())
+}
+
+#[test]
+fn the_system_configuration_is_read_relative_to_the_install_prefix() -> crate::Result {
+    let dir = tempfile::tempdir()?;
+    std::fs::create_dir(dir.path().join("etc"))?;
+    std::fs::write(dir.path().join("etc").join("gitconfig"), "[core]\n\tsource = system\n")?;
+    let config = ConfigBuilder::default()
+        .no_global(true)
+        .git_install_dir(Some(dir.path().to_owned()))
+        .try_build()?;
+    assert_eq!(
+        config.value_with_source::<Cow<'_, [u8]>>("core", None, "source"),
+        Some((Cow::Borrowed(&b"system"[..]), ConfigSource::System))
+    );
+    Ok(())
+}
+
+#[test]
+fn xdg_config_home_is_only_read_from_the_environment_if_allowed() -> crate::Result {
+    let config = ConfigBuilder::default().no_system(true).no_global(true).try_build()?;
+    assert_eq!(
+        config.xdg_config_path("ignore"),
+        dirs::home_dir().map(|home| home.join(".config").join("git").join("ignore"))
+    );
+    Ok(())
+}
@@ -0,0 +1,191 @@
+use std::{borrow::Cow, convert::TryFrom, fs};
+
+use git_config::{
+    file::{includes, GitConfig},
+    fs::{ConfigBuilder, ConfigSource, Error},
+    values::PathInterpolationError,
+};
+
+fn value<'a>(config: &'a GitConfig<'_>, key: &str) -> Option<Cow<'a, [u8]>> {
+    config.get_raw_value("core", None, key).ok()
+}
+
+#[test]
+fn included_files_take_effect_where_they_are_included() -> crate::Result {
+    let dir = tempfile::tempdir()?;
+    fs::create_dir(dir.path().join("sub"))?;
+    fs::write(
+        dir.path().join("config"),
+        "[core]\n\ta = config\n\tb = config\n[include]\n\tpath = sub/first\n\tpath = missing\n[core]\n\tb = config-after\n",
+    )?;
+    fs::write(
+        dir.path().join("sub").join("first"),
+        "[core]\n\ta = first\n\tb = first\n[include]\n\tpath = second\n",
+    )?;
+    fs::write(dir.path().join("sub").join("second"), "[core]\n\tc = second\n")?;
+
+    let config = GitConfig::open_with_includes(dir.path().join("config"), &includes::Options::default())?;
+    assert_eq!(value(&config, "a").as_deref(), Some(&b"first"[..]));
+    assert_eq!(
+        value(&config, "b").as_deref(),
+        Some(&b"config-after"[..]),
+        "sections after the include override it"
+    );
+    assert_eq!(
+        value(&config, "c").as_deref(),
+        Some(&b"second"[..]),
+        "paths are relative to the including file"
+    );
+
+    assert_eq!(
+        config.value_path("core", None, "a")?,
+        Some(dir.path().join("sub").join("first").as_path())
+    );
+    assert_eq!(
+        config.value_path("core", None, "b")?,
+        Some(dir.path().join("config").as_path())
+    );
+    assert_eq!(
+        config.value_path("core", None, "c")?,
+        Some(dir.path().join("sub").join("second").as_path())
+    );
+    Ok(())
+}
+
+#[test]
+fn cycles_and_excessive_depth_are_errors() -> crate::Result {
+    let dir = tempfile::tempdir()?;
+    fs::write(dir.path().join("a"), "[include]\n\tpath = b\n")?;
+    fs::write(dir.path().join("b"), "[include]\n\tpath = a\n")?;
+    assert!(matches!(
+        GitConfig::open_with_includes(dir.path().join("a"), &includes::Options::default()),
+        Err(includes::Error::Cycle { path }) if path == dir.path().join("a")
+    ));
+
+    fs::write(dir.path().join("b"), "[include]\n\tpath = c\n")?;
+    fs::write(dir.path().join("c"), "[core]\n\tdeep = true\n")?;
+    let options = includes::Options {
+        max_depth: 1,
+        ..Default::default()
+    };
+    assert!(matches!(
+        GitConfig::open_with_includes(dir.path().join("a"), &options),
+        Err(includes::Error::MaxDepthExceeded { max_depth: 1 })
+    ));
+
+    let config = GitConfig::try_from("[include]\n\tpath = relative\n")?;
+    assert!(matches!(
+        config.resolve_includes(&includes::Options::default()),
+        Err(includes::Error::RelativePathWithoutConfigPath { .. })
+    ));
+    Ok(())
+}
+
+#[test]
+fn conditional_includes_are_evaluated_across_all_files() -> crate::Result {
+    let dir = tempfile::tempdir()?;
+    let git_dir = dir.path().join("repo").join(".git");
+    fs::create_dir_all(&git_dir)?;
+    for (name, condition) in [
+        ("gitdir", "gitdir:repo/.git"),
+        ("gitdir-trailing-slash", "gitdir:repo/"),
+        ("gitdir-icase", "gitdir/i:REPO/"),
+        ("gitdir-case", "gitdir:REPO/"),
+        ("gitdir-relative", "gitdir:./repo/.git"),
+        ("onbranch", "onbranch:feature/"),
+        ("onbranch-other", "onbranch:main"),
+        ("hasconfig", "hasconfig:remote.*.url:https://example.com/**"),
+        ("hasconfig-other", "hasconfig:remote.*.url:https://example.org/**"),
+    ] {
+        fs::write(dir.path().join(name), format!("[core]\n\t{} = true\n", name))?;
+        fs::write(
+            dir.path().join("global"),
+            format!(
+                "{}[includeIf \"{}\"]\n\tpath = {}\n",
+                fs::read_to_string(dir.path().join("global")).unwrap_or_default(),
+                condition,
+                name
+            ),
+        )?;
+    }
+    fs::write(
+        git_dir.join("config"),
+        "[remote \"origin\"]\n\turl = https://example.com/project\n",
+    )?;
+
+    let config = ConfigBuilder::default()
+        .no_system(true)
+        .global_config_path(Some(dir.path().join("global")))
+        .user_config_path(Some(dir.path().join("global")))
+        .repository_config_path(Some(git_dir.join("config")))
+        .git_dir(Some(git_dir))
+        .branch_name(Some("refs/heads/feature/x".into()))
+        .try_build()?;
+    let global = config.get_config(ConfigSource::Global).expect("present");
+    for (name, expected) in [
+        ("gitdir", true),
+        ("gitdir-trailing-slash", true),
+        ("gitdir-icase", true),
+        ("gitdir-case", false),
+        ("gitdir-relative", true),
+        ("onbranch", true),
+        ("onbranch-other", false),
+        ("hasconfig", true),
+        ("hasconfig-other", false),
+    ] {
+        assert_eq!(value(global, name).is_some(), expected, "{}", name);
+    }
+    assert_eq!(
+        config.value_origin("core", None, "hasconfig"),
+        Some((ConfigSource::Global, Some(dir.path().join("hasconfig").as_path())))
+    );
+
+    fs::write(dir.path().join("global"), "[include]\n\tpath = global\n")?;
+    assert!(matches!(
+        ConfigBuilder::default()
+            .no_system(true)
+            .global_config_path(Some(dir.path().join("global")))
+            .try_build(),
+        Err(Error::Includes(includes::Error::Cycle { .. }))
+    ));
+    Ok(())
+}
+
+#[test]
+fn paths_are_interpolated_with_errors_naming_the_key() -> crate::Result {
+    let dir = tempfile::tempdir()?;
+    fs::create_dir(dir.path().join("etc"))?;
+    fs::write(dir.path().join("etc").join("included"), "[core]\n\tincluded = true\n")?;
+    fs::write(
+        dir.path().join("config"),
+        "[includeIf \"gitdir:%(prefix)/repo/\"]\n\tpath = %(prefix)/etc/included\n",
+    )?;
+
+    let options = includes::Options {
+        git_dir: Some(dir.path().join("repo").join(".git")),
+        git_install_dir: Some(dir.path().to_owned()),
+        ..Default::default()
+    };
+    let config = GitConfig::open_with_includes(dir.path().join("config"), &options)?;
+    assert_eq!(value(&config, "included").as_deref(), Some(&b"true"[..]));
+
+    let options = includes::Options {
+        git_install_dir: None,
+        ..options
+    };
+    assert!(matches!(
+        GitConfig::open_with_includes(dir.path().join("config"), &options),
+        Err(includes::Error::Interpolate { key, source: PathInterpolationError::MissingInstallDir })
+            if key == "includeIf.gitdir:%(prefix)/repo/"
+    ));
+
+    fs::write(
+        dir.path().join("config"),
+        "[include]\n\tpath = %(prefix)/etc/included\n",
+    )?;
+    assert!(matches!(
+        GitConfig::open_with_includes(dir.path().join("config"), &options),
+        Err(includes::Error::Interpolate { key, .. }) if key == "include.path"
+    ));
+    Ok(())
+}
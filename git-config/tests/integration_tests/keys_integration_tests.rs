@@ -0,0 +1,119 @@
+use std::{convert::TryFrom, fs};
+
+use git_config::{
+    file::GitConfig,
+    fs::ConfigBuilder,
+    keys::{self, ErrorKind, Key, Kind},
+};
+
+#[test]
+fn values_are_converted_with_defaults_applied() -> crate::Result {
+    let config = GitConfig::try_from(
+        "[core]\n\tbare\n\tcompression = 9\n[pack]\n\tthreads = 1k\n[push]\n\tdefault = Current\n[core]\n\thooksPath = ~/hooks\n",
+    )?;
+    assert_eq!(config.get(&keys::core::BARE)?, Some(true));
+    assert_eq!(config.get(&keys::core::COMPRESSION)?, Some(9));
+    assert_eq!(config.get(&keys::core::MULTI_PACK_INDEX)?, Some(true));
+    assert_eq!(config.get(&keys::core::LOOSE_COMPRESSION)?, None, "no default");
+    assert_eq!(config.get(&keys::pack::THREADS)?, Some(1024), "suffixes are applied");
+    assert_eq!(config.get(&keys::pack::WINDOW)?, Some(10));
+    assert_eq!(
+        config.get(&keys::push::DEFAULT)?.as_deref(),
+        Some(&b"Current"[..]),
+        "allowed values are compared case-insensitively"
+    );
+    assert_eq!(
+        config.get(&keys::fetch::NEGOTIATION_ALGORITHM)?.as_deref(),
+        Some(&b"default"[..])
+    );
+    assert_eq!(
+        config.get(&keys::core::HOOKS_PATH)?.as_ref().map(AsRef::as_ref),
+        Some(&b"~/hooks"[..]),
+        "paths aren't interpolated"
+    );
+    Ok(())
+}
+
+#[test]
+fn invalid_values_point_to_their_location() -> crate::Result {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("config");
+    fs::write(
+        &path,
+        "# comment\n[core]\n\tcompression = 3\n[fetch]\n\tprune = maybe\n[core]\n\tbare = false\n\tcompression = 10\n[http]\n\tversion = HTTP/3\n",
+    )?;
+    let config = GitConfig::open(&path)?;
+
+    let err = config.get(&keys::core::COMPRESSION).unwrap_err();
+    assert_eq!(
+        err.kind,
+        ErrorKind::OutOfRange {
+            min: Some(-1),
+            max: Some(9)
+        }
+    );
+    assert_eq!(err.path.as_deref(), Some(path.as_path()));
+    assert_eq!(err.line, Some(8), "the value that is used is reported");
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "{}:8: Invalid value '10' for core.compression: expected a value between -1 and 9",
+            path.display()
+        )
+    );
+
+    let err = config.get(&keys::fetch::PRUNE).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::InvalidValue(Kind::Boolean));
+    assert_eq!((err.value.as_slice(), err.line), (&b"maybe"[..], Some(5)));
+
+    let err = config.get(&keys::http::VERSION).unwrap_err();
+    assert_eq!(err.kind, ErrorKind::NotAllowed(&["HTTP/1.1", "HTTP/2"]));
+    assert_eq!(err.line, Some(10));
+
+    let err = GitConfig::try_from("[pack]\n\tdepth = -1\n")?
+        .get(&keys::pack::DEPTH)
+        .unwrap_err();
+    assert_eq!(
+        (err.path, err.line),
+        (None, None),
+        "values not read from files have no location"
+    );
+    Ok(())
+}
+
+#[test]
+fn layered_configs_use_the_value_with_the_highest_precedence() -> crate::Result {
+    let dir = tempfile::tempdir()?;
+    fs::write(
+        dir.path().join("global"),
+        "[fetch]\n\tprune = true\n\tpruneTags = invalid\n",
+    )?;
+    fs::write(dir.path().join("config"), "[fetch]\n\tpruneTags = false\n")?;
+    let config = ConfigBuilder::new()
+        .no_system(true)
+        .global_config_path(Some(dir.path().join("global")))
+        .repository_config_path(Some(dir.path().join("config")))
+        .try_build()?;
+
+    assert_eq!(config.get(&keys::fetch::PRUNE)?, Some(true));
+    assert_eq!(
+        config.get(&keys::fetch::PRUNE_TAGS)?,
+        Some(false),
+        "invalid values with lower precedence are ignored"
+    );
+    assert_eq!(config.get(&keys::http::SSL_VERIFY)?, Some(true));
+    Ok(())
+}
+
+#[test]
+fn registry() {
+    let metadata = keys::find("Core.Compression").expect("known key");
+    assert_eq!(metadata, &keys::core::COMPRESSION.metadata);
+    assert_eq!(metadata.default, Some("-1"));
+    assert!(keys::find("core.unknown").is_none());
+    assert!(keys::find("sendemail.smtpssl").expect("known key").deprecated.is_some());
+
+    const CUSTOM: Key<i64> = Key::integer("custom.level").with_default("2").with_range(Some(1), None);
+    assert_eq!(GitConfig::new().get(&CUSTOM).expect("valid default"), Some(2));
+    assert_eq!(CUSTOM.name(), "custom.level");
+}
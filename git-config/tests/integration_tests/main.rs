@@ -7,4 +7,8 @@
 type Result = std::result::Result<(), Box<dyn std::error::Error>>;
 
 mod file_integeration_test;
+mod fs_integration_tests;
+mod include_integration_tests;
+mod keys_integration_tests;
 mod parser_integration_tests;
+mod write_back_integration_tests;
@@ -0,0 +1,128 @@
+use std::{convert::TryFrom, fs, path::Path};
+
+use git_config::{
+    file::{includes, GitConfig, WriteBackError},
+    fs::ConfigBuilder,
+};
+
+fn lock(path: &Path) -> git_lock::File {
+    git_lock::File::acquire_to_update_resource(path, git_lock::acquire::Fail::Immediately, None).expect("lockable")
+}
+
+#[test]
+fn only_changed_files_are_written_with_their_formatting_intact() -> crate::Result {
+    let dir = tempfile::tempdir()?;
+    let config_path = dir.path().join("config");
+    let included_path = dir.path().join("included");
+    fs::write(
+        &config_path,
+        "# frontmatter\n[core]\n\ta = 1 ; comment\n[include]\n\tpath = included\n",
+    )?;
+    fs::write(&included_path, "[other]\n  b=2\n")?;
+
+    let mut config = GitConfig::open_with_includes(&config_path, &includes::Options::default())?;
+    assert!(config.changed_files().is_empty());
+    assert!(
+        !config.write_back(lock(&config_path))?,
+        "unchanged files aren't written"
+    );
+    assert!(!dir.path().join("config.lock").exists(), "the lock is released");
+
+    config.set_raw_value("other", None, "b", b"3".to_vec())?;
+    config.new_section("new", None).push("c".into(), b"4".to_vec().into());
+    assert_eq!(
+        config.changed_files(),
+        vec![config_path.as_path(), included_path.as_path()]
+    );
+
+    assert!(config.write_back(lock(&included_path))?);
+    assert_eq!(fs::read(&included_path)?, b"[other]\n  b=3\n");
+    assert!(config.write_back(lock(&config_path))?);
+    assert_eq!(
+        fs::read_to_string(&config_path)?,
+        "# frontmatter\n[core]\n\ta = 1 ; comment\n[include]\n\tpath = included\n[new]\n  c=4\n",
+        "new sections go into the file the config was opened from"
+    );
+    assert!(config.changed_files().is_empty());
+
+    let reopened = GitConfig::open_with_includes(&config_path, &includes::Options::default())?;
+    assert_eq!(reopened.to_string(), config.to_string());
+    Ok(())
+}
+
+#[test]
+fn files_modified_on_disk_are_not_overwritten() -> crate::Result {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("config");
+    fs::write(&path, "[core]\n\ta = 1\n")?;
+
+    let mut config = GitConfig::open(&path)?;
+    config.set_raw_value("core", None, "a", b"2".to_vec())?;
+    fs::write(&path, "[core]\n\ta = 5\n")?;
+    assert!(matches!(
+        config.write_back(lock(&path)),
+        Err(WriteBackError::ModifiedOnDisk(p)) if p == path
+    ));
+    assert_eq!(fs::read(&path)?, b"[core]\n\ta = 5\n", "the file is left untouched");
+
+    assert!(matches!(
+        GitConfig::try_from("[core]\n\ta = 1\n")?.write_back(lock(&path)),
+        Err(WriteBackError::UnknownFile(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn layered_configs_write_back_to_the_layer_the_file_belongs_to() -> crate::Result {
+    let dir = tempfile::tempdir()?;
+    let global = dir.path().join("global");
+    let repository = dir.path().join("config");
+    fs::write(&global, "[user]\n\tname = global\n")?;
+    fs::write(&repository, "[core]\n\tbare = false\n")?;
+
+    let mut config = ConfigBuilder::new()
+        .no_system(true)
+        .global_config_path(Some(global.clone()))
+        .repository_config_path(Some(repository.clone()))
+        .try_build()?;
+    config
+        .get_config_mut(git_config::fs::ConfigSource::Repository)
+        .expect("present")
+        .set_raw_value("core", None, "bare", b"true".to_vec())?;
+    assert_eq!(config.changed_files(), vec![repository.as_path()]);
+
+    assert!(!config.write_back(lock(&global))?);
+    assert!(config.write_back(lock(&repository))?);
+    assert_eq!(fs::read(&repository)?, b"[core]\n\tbare=true\n");
+    assert!(matches!(
+        config.write_back(lock(&dir.path().join("unknown"))),
+        Err(WriteBackError::UnknownFile(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn files_included_more_than_once_are_written_once() -> crate::Result {
+    let dir = tempfile::tempdir()?;
+    let config_path = dir.path().join("config");
+    let included_path = dir.path().join("included");
+    fs::write(
+        &config_path,
+        "[include]\n\tpath = included\n[core]\n\ta = 1\n[include]\n\tpath = included\n",
+    )?;
+    fs::write(&included_path, "[other]\n\tb = 2\n")?;
+
+    let mut config = GitConfig::open_with_includes(&config_path, &includes::Options::default())?;
+    assert_eq!(config.sections_by_name("other").len(), 2, "both inclusions are read");
+    assert!(config.changed_files().is_empty(), "the included file isn't duplicated");
+    assert!(!config.write_back(lock(&included_path))?);
+    assert_eq!(config.value_line("other", None, "b")?, Some(2));
+
+    config.set_raw_value("other", None, "b", b"3".to_vec())?;
+    assert_eq!(config.changed_files(), vec![included_path.as_path()]);
+    assert!(config.write_back(lock(&included_path))?);
+    assert_eq!(fs::read(&included_path)?, b"[other]\n\tb=3\n");
+    assert!(config.changed_files().is_empty());
+    assert!(!config.write_back(lock(&config_path))?);
+    Ok(())
+}
@@ -0,0 +1,312 @@
+use std::{borrow::BorrowMut, cmp::Ordering};
+
+use git_hash::{oid, ObjectId};
+use git_index::{entry, extension, Entry};
+use git_object::{
+    bstr::{BStr, BString, ByteSlice},
+    tree::EntryMode,
+};
+use quick_error::quick_error;
+
+use crate::{index, tree, tree::visit::Change};
+
+quick_error! {
+    /// The error returned by [index::Changes::needed_to_obtain()].
+    #[derive(Debug)]
+    #[allow(missing_docs)]
+    pub enum Error {
+        NotFound { oid: ObjectId } {
+            display("The object {} referenced by the tree or the tree itself was not found in the database", oid)
+        }
+        Cancelled {
+            display("The delegate cancelled the operation")
+        }
+        EntriesDecode(err: git_object::decode::Error) {
+            display("tree entries could not be decoded.")
+            from()
+            source(err)
+        }
+    }
+}
+
+impl<'a> index::Changes<'a> {
+    /// Calculate the changes that would need to be applied to `self` to get the tree represented by `index`, which are the
+    /// changes staged for the next commit if `self` is the tree of `HEAD`.
+    ///
+    /// * The `state` maybe owned or mutably borrowed to allow reuses allocated data structures through multiple runs.
+    /// * `find` is a function `f(object_id, &mut buffer) -> Option<TreeIter>` to return a `TreeIter` for the given object id backing
+    ///   its data in the given buffer. Returning `None` is unexpected as these trees are obtained during iteration, and in a typical
+    ///   database errors are not expected either which is why the error case is omitted. To allow proper error reporting, [`Error::NotFound`]
+    ///   should be converted into a more telling error.
+    /// * `delegate` will receive the computed changes, see the [`Visit`][`tree::Visit`] trait for more information on what to expect.
+    ///
+    /// # Notes
+    ///
+    /// * Only changes to non-tree entries are reported, similar to `git diff-index --cached -r`, with the exception of
+    ///   directories of a sparse index which are reported like blobs using [`EntryMode::Tree`].
+    /// * The traversal is depth-first, hence the `delegate` will only see calls to [`push_path_component()`][tree::Visit::push_path_component()]
+    ///   and [`pop_path_component()`][tree::Visit::pop_path_component()] to track the path of changes.
+    /// * Directories whose tree id is cached in the `TREE` extension of the `index` and matches the id in `self` are skipped
+    ///   entirely without loading their trees.
+    /// * Unmerged paths with conflicting stages are skipped, as are entries marked as _intent-to-add_ which have no content yet.
+    pub fn needed_to_obtain<FindFn, R, StateMut>(
+        mut self,
+        index: &git_index::State,
+        mut state: StateMut,
+        find: FindFn,
+        delegate: &mut R,
+    ) -> Result<(), Error>
+    where
+        FindFn: for<'b> FnMut(&oid, &'b mut Vec<u8>) -> Option<git_object::TreeRefIter<'b>>,
+        R: tree::Visit,
+        StateMut: BorrowMut<index::State>,
+    {
+        let state = state.borrow_mut();
+        state.buf.clear();
+        let tree_entries = self
+            .0
+            .take()
+            .map(|iter| {
+                iter.map(|entry| entry.map(TreeEntry::from))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?;
+        let mut walk = Walk {
+            index,
+            find,
+            delegate,
+            buf: &mut state.buf,
+        };
+        walk.tree(tree_entries.unwrap_or_default(), index.entries(), 0, index.tree())
+    }
+}
+
+struct TreeEntry {
+    filename: BString,
+    mode: EntryMode,
+    oid: ObjectId,
+}
+
+impl<'a> From<git_object::tree::EntryRef<'a>> for TreeEntry {
+    fn from(entry: git_object::tree::EntryRef<'a>) -> Self {
+        TreeEntry {
+            filename: entry.filename.into(),
+            mode: entry.mode,
+            oid: entry.oid.to_owned(),
+        }
+    }
+}
+
+/// One or more index entries which are at the same level as the entries of the tree we compare them to.
+enum IndexItem<'a> {
+    /// All entries of a directory, without the directory itself.
+    Directory(&'a [Entry]),
+    /// A directory of a sparse index.
+    SparseDirectory(&'a Entry),
+    /// A file, symlink or submodule, or `None` if it should be ignored.
+    NonTree(Option<&'a Entry>),
+}
+
+struct Walk<'a, FindFn, R> {
+    index: &'a git_index::State,
+    find: FindFn,
+    delegate: &'a mut R,
+    buf: &'a mut Vec<u8>,
+}
+
+impl<'a, FindFn, R> Walk<'a, FindFn, R>
+where
+    FindFn: for<'b> FnMut(&oid, &'b mut Vec<u8>) -> Option<git_object::TreeRefIter<'b>>,
+    R: tree::Visit,
+{
+    /// Compare `tree_entries` with index `entries` whose paths all start with the same directory of `prefix_len` bytes,
+    /// using the `cache` for this directory if available.
+    fn tree(
+        &mut self,
+        tree_entries: Vec<TreeEntry>,
+        entries: &'a [Entry],
+        prefix_len: usize,
+        cache: Option<&'a extension::Tree>,
+    ) -> Result<(), Error> {
+        let mut tree_entries = tree_entries.into_iter().peekable();
+        let mut entries_pos = 0;
+        loop {
+            let index_item =
+                (entries_pos < entries.len()).then(|| self.index_item(&entries[entries_pos..], prefix_len));
+            match (tree_entries.peek(), index_item) {
+                (None, None) => break,
+                (Some(_), None) => {
+                    let tree_entry = tree_entries.next().expect("peeked");
+                    self.deleted(tree_entry)?;
+                }
+                (None, Some((name, item, consumed))) => {
+                    entries_pos += consumed;
+                    self.added(name, item, prefix_len)?;
+                }
+                (Some(tree_entry), Some((name, item, consumed))) => {
+                    match compare(
+                        tree_entry.filename.as_bstr(),
+                        tree_entry.mode.is_tree(),
+                        name,
+                        !matches!(item, IndexItem::NonTree(_)),
+                    ) {
+                        Ordering::Less => {
+                            let tree_entry = tree_entries.next().expect("peeked");
+                            self.deleted(tree_entry)?;
+                        }
+                        Ordering::Greater => {
+                            entries_pos += consumed;
+                            self.added(name, item, prefix_len)?;
+                        }
+                        Ordering::Equal => {
+                            let tree_entry = tree_entries.next().expect("peeked");
+                            entries_pos += consumed;
+                            self.delegate.push_path_component(name);
+                            match item {
+                                IndexItem::Directory(entries) => {
+                                    let cache = cache
+                                        .and_then(|cache| cache.children().iter().find(|child| child.name() == name));
+                                    if cache.and_then(|cache| cache.id()) != Some(tree_entry.oid.as_ref()) {
+                                        let tree_entries = self.tree_entries(&tree_entry.oid)?;
+                                        self.tree(tree_entries, entries, prefix_len + name.len() + 1, cache)?;
+                                    }
+                                }
+                                IndexItem::SparseDirectory(entry) => {
+                                    if entry.id != tree_entry.oid {
+                                        self.modified(&tree_entry, EntryMode::Tree, entry.id)?;
+                                    }
+                                }
+                                IndexItem::NonTree(Some(entry)) => {
+                                    let mode = entry_mode(entry.mode);
+                                    if entry.id != tree_entry.oid || mode != tree_entry.mode {
+                                        self.modified(&tree_entry, mode, entry.id)?;
+                                    }
+                                }
+                                IndexItem::NonTree(None) => {}
+                            }
+                            self.delegate.pop_path_component();
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Return the name and kind of the first item in `entries` along with the amount of entries it consists of.
+    fn index_item(&self, entries: &'a [Entry], prefix_len: usize) -> (&'a BStr, IndexItem<'a>, usize) {
+        let index = self.index;
+        let first = &entries[0];
+        let rela_path = &first.path(index)[prefix_len..];
+        if first.mode.is_sparse() {
+            let name = rela_path.strip_suffix(b"/").unwrap_or(rela_path).as_bstr();
+            return (name, IndexItem::SparseDirectory(first), 1);
+        }
+        match rela_path.find_byte(b'/') {
+            Some(pos) => {
+                let dir = &rela_path[..=pos];
+                let consumed = entries
+                    .iter()
+                    .position(|entry| !entry.path(index)[prefix_len..].starts_with(dir))
+                    .unwrap_or(entries.len());
+                (
+                    rela_path[..pos].as_bstr(),
+                    IndexItem::Directory(&entries[..consumed]),
+                    consumed,
+                )
+            }
+            None => {
+                let stages = entries
+                    .iter()
+                    .take_while(|entry| entry.path(index)[prefix_len..] == *rela_path)
+                    .count();
+                let entry = (stages == 1 && first.stage() == 0 && !first.flags.contains(entry::Flags::INTENT_TO_ADD))
+                    .then(|| first);
+                (rela_path.as_bstr(), IndexItem::NonTree(entry), stages)
+            }
+        }
+    }
+
+    fn tree_entries(&mut self, id: &oid) -> Result<Vec<TreeEntry>, Error> {
+        let iter = (self.find)(id, self.buf).ok_or_else(|| Error::NotFound { oid: id.to_owned() })?;
+        iter.map(|entry| entry.map(TreeEntry::from).map_err(Into::into))
+            .collect()
+    }
+
+    fn added(&mut self, name: &BStr, item: IndexItem<'a>, prefix_len: usize) -> Result<(), Error> {
+        self.delegate.push_path_component(name);
+        match item {
+            IndexItem::Directory(entries) => self.tree(Vec::new(), entries, prefix_len + name.len() + 1, None)?,
+            IndexItem::SparseDirectory(entry) => self.visit(Change::Addition {
+                entry_mode: EntryMode::Tree,
+                oid: entry.id,
+            })?,
+            IndexItem::NonTree(Some(entry)) => self.visit(Change::Addition {
+                entry_mode: entry_mode(entry.mode),
+                oid: entry.id,
+            })?,
+            IndexItem::NonTree(None) => {}
+        }
+        self.delegate.pop_path_component();
+        Ok(())
+    }
+
+    fn deleted(&mut self, tree_entry: TreeEntry) -> Result<(), Error> {
+        self.delegate.push_path_component(tree_entry.filename.as_bstr());
+        if tree_entry.mode.is_tree() {
+            let tree_entries = self.tree_entries(&tree_entry.oid)?;
+            self.tree(tree_entries, &[], 0, None)?;
+        } else {
+            self.visit(Change::Deletion {
+                entry_mode: tree_entry.mode,
+                oid: tree_entry.oid,
+            })?;
+        }
+        self.delegate.pop_path_component();
+        Ok(())
+    }
+
+    fn modified(&mut self, tree_entry: &TreeEntry, entry_mode: EntryMode, oid: ObjectId) -> Result<(), Error> {
+        self.visit(Change::Modification {
+            previous_entry_mode: tree_entry.mode,
+            previous_oid: tree_entry.oid,
+            entry_mode,
+            oid,
+        })
+    }
+
+    fn visit(&mut self, change: Change) -> Result<(), Error> {
+        if self.delegate.visit(change).cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Compare names like git does when sorting tree entries, where directories sort as if their name ended with a slash.
+fn compare(a: &BStr, a_is_dir: bool, b: &BStr, b_is_dir: bool) -> Ordering {
+    let common_len = a.len().min(b.len());
+    a[..common_len].cmp(&b[..common_len]).then_with(|| {
+        let terminator = |name: &BStr, is_dir: bool| {
+            name.get(common_len)
+                .copied()
+                .unwrap_or(if is_dir { b'/' } else { b'\0' })
+        };
+        terminator(a, a_is_dir).cmp(&terminator(b, b_is_dir))
+    })
+}
+
+fn entry_mode(mode: entry::Mode) -> EntryMode {
+    if mode == entry::Mode::FILE_EXECUTABLE {
+        EntryMode::BlobExecutable
+    } else if mode == entry::Mode::SYMLINK {
+        EntryMode::Link
+    } else if mode == entry::Mode::COMMIT {
+        EntryMode::Commit
+    } else if mode == entry::Mode::DIR {
+        EntryMode::Tree
+    } else {
+        EntryMode::Blob
+    }
+}
@@ -0,0 +1,22 @@
+use git_object::TreeRefIter;
+
+/// The state required to compute [Changes], to be instantiated with `State::default()`.
+#[derive(Default, Clone)]
+pub struct State {
+    buf: Vec<u8>,
+}
+
+/// The tree to compare with an index, instantiated using `Changes::from(…)`.
+pub struct Changes<'a>(Option<TreeRefIter<'a>>);
+
+impl<'a, T> From<T> for Changes<'a>
+where
+    T: Into<Option<TreeRefIter<'a>>>,
+{
+    fn from(v: T) -> Self {
+        Changes(v.into())
+    }
+}
+
+///
+pub mod changes;
@@ -41,6 +41,9 @@ pub mod visit;
 #[doc(inline)]
 pub use visit::Visit;
 
+///
+pub mod pathspec;
+
 /// Useful for use as delegate implementing [`Visit`] to keep track of all seen changes. Useful for debugging or printing primarily.
 pub mod recorder;
 #[doc(inline)]
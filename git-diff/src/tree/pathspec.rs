@@ -0,0 +1,101 @@
+use std::collections::VecDeque;
+
+use git_object::bstr::{BStr, BString, ByteSlice, ByteVec};
+use git_pathspec::{AttributeState, Search};
+
+use crate::tree::{
+    visit::{Action, Change},
+    Visit,
+};
+
+/// A [`Visit`] implementation which passes only changes to entries included by a pathspec [`Search`] on to its `delegate`,
+/// for use with [tree diffs][crate::tree::Changes::needed_to_obtain()] and [index diffs][crate::index::Changes::needed_to_obtain()].
+///
+/// Changes to trees are passed on only if they lead to included entries.
+pub struct Filter<'a, V, A> {
+    search: &'a Search,
+    attributes: A,
+    path_deque: VecDeque<BString>,
+    path: BString,
+    /// The delegate receiving all included changes.
+    pub delegate: V,
+}
+
+impl<'a, V, A> Filter<'a, V, A>
+where
+    V: Visit,
+    A: FnMut(&BStr, &BStr) -> AttributeState,
+{
+    /// Create a new instance to pass changes included by `search` on to `delegate`, with `attributes(path, name)` returning
+    /// the state of the attribute `name` for `path`, which is only called for pathspecs with `:(attr:…)` magic.
+    pub fn new(search: &'a Search, delegate: V, attributes: A) -> Self {
+        Filter {
+            search,
+            attributes,
+            path_deque: VecDeque::new(),
+            path: BString::default(),
+            delegate,
+        }
+    }
+
+    fn push_element(&mut self, name: &BStr) {
+        if !self.path.is_empty() {
+            self.path.push(b'/');
+        }
+        self.path.push_str(name);
+    }
+}
+
+impl<'a, V, A> Visit for Filter<'a, V, A>
+where
+    V: Visit,
+    A: FnMut(&BStr, &BStr) -> AttributeState,
+{
+    fn pop_front_tracked_path_and_set_current(&mut self) {
+        self.path = self
+            .path_deque
+            .pop_front()
+            .expect("every call is matched with push_tracked_path_component");
+        self.delegate.pop_front_tracked_path_and_set_current();
+    }
+
+    fn push_back_tracked_path_component(&mut self, component: &BStr) {
+        self.push_element(component);
+        self.path_deque.push_back(self.path.clone());
+        self.delegate.push_back_tracked_path_component(component);
+    }
+
+    fn push_path_component(&mut self, component: &BStr) {
+        self.push_element(component);
+        self.delegate.push_path_component(component);
+    }
+
+    fn pop_path_component(&mut self) {
+        match self.path.rfind_byte(b'/') {
+            Some(pos) => self.path.truncate(pos),
+            None => self.path.clear(),
+        }
+        self.delegate.pop_path_component();
+    }
+
+    fn visit(&mut self, change: Change) -> Action {
+        let entry_mode = match &change {
+            Change::Addition { entry_mode, .. }
+            | Change::Deletion { entry_mode, .. }
+            | Change::Modification { entry_mode, .. } => *entry_mode,
+        };
+        let is_included = if entry_mode.is_tree() {
+            self.search.can_match_relative_path(self.path.as_bstr())
+        } else {
+            let is_dir = entry_mode == git_object::tree::EntryMode::Commit;
+            self.search
+                .pattern_matching_relative_path(self.path.as_bstr(), is_dir, &mut self.attributes)
+                .is_some()
+        };
+        if is_included {
+            self.delegate.visit(change)
+        } else {
+            Action::Continue
+        }
+    }
+}
@@ -0,0 +1,28 @@
+#!/bin/bash
+set -eu -o pipefail
+
+git init -q
+git config commit.gpgsign false
+
+git checkout -q -b main
+
+mkdir -p unchanged/sub changed removed-dir
+for path in a b c unchanged/a unchanged/sub/a changed/a changed/b removed-dir/a; do
+  echo "$path" > "$path"
+done
+
+git add .
+git commit -qm "init"
+
+echo "modified" > changed/a
+git add changed/a
+git update-index --chmod=+x b
+git rm -q --cached c
+git rm -qr --cached removed-dir
+mkdir added-dir
+echo "added" > added-dir/a
+git add added-dir
+rm a && ln -s b a
+git add a
+echo "intent" > intent-to-add
+git add --intent-to-add intent-to-add
@@ -0,0 +1,205 @@
+mod changes {
+    mod to_obtain_index {
+        use git_diff::tree::{recorder, recorder::Change::*};
+        use git_hash::ObjectId;
+        use git_object::{bstr::ByteSlice, tree::EntryMode};
+        use git_odb::{linked, pack::Find};
+
+        use crate::hex_to_id;
+
+        fn fixture() -> crate::Result<(linked::Store, git_index::File)> {
+            let dir = git_testtools::scripted_fixture_repo_read_only("make_index_diff_repo.sh")?;
+            let db = linked::Store::at(dir.join(".git").join("objects"))?;
+            let index = git_index::File::at(dir.join(".git").join("index"), Default::default())?;
+            Ok((db, index))
+        }
+
+        fn head_tree_id(db: &linked::Store) -> crate::Result<ObjectId> {
+            let head = ObjectId::from_hex(
+                std::fs::read(db.dbs[0].loose.path().parent().unwrap().join("refs").join("heads").join("main"))?
+                    .as_bstr()
+                    .trim(),
+            )?;
+            let mut buf = Vec::new();
+            let tree_id = db
+                .try_find(head, &mut buf)?
+                .expect("head commit present")
+                .0
+                .decode()?
+                .into_commit()
+                .expect("a commit")
+                .tree();
+            Ok(tree_id)
+        }
+
+        fn diff_head_with_index(
+            db: &linked::Store,
+            index: &git_index::State,
+        ) -> crate::Result<(Vec<recorder::Change>, Vec<ObjectId>)> {
+            let tree_id = head_tree_id(db)?;
+            let mut buf = Vec::new();
+            let tree = db
+                .try_find(tree_id, &mut buf)?
+                .expect("tree present")
+                .0
+                .try_into_tree_iter()
+                .expect("a tree");
+            let mut recorder = git_diff::tree::Recorder::default();
+            let mut trees_looked_up = Vec::new();
+            git_diff::index::Changes::from(tree).needed_to_obtain(
+                index,
+                git_diff::index::State::default(),
+                |oid, buf| {
+                    trees_looked_up.push(oid.to_owned());
+                    db.try_find(oid, buf)
+                        .ok()
+                        .flatten()
+                        .and_then(|obj| obj.0.try_into_tree_iter())
+                },
+                &mut recorder,
+            )?;
+            Ok((recorder.records, trees_looked_up))
+        }
+
+        #[test]
+        fn staged_changes_like_git_diff_cached() -> crate::Result {
+            let (db, index) = fixture()?;
+            let (changes, _) = diff_head_with_index(&db, &index)?;
+            assert_eq!(
+                changes,
+                vec![
+                    Modification {
+                        previous_entry_mode: EntryMode::Blob,
+                        previous_oid: hex_to_id("78981922613b2afb6025042ff6bd878ac1994e85"),
+                        entry_mode: EntryMode::Link,
+                        oid: hex_to_id("63d8dbd40c23542e740659a7168a0ce3138ea748"),
+                        path: "a".into()
+                    },
+                    Addition {
+                        entry_mode: EntryMode::Blob,
+                        oid: hex_to_id("d5f7fc3f74f7dec08280f370a975b112e8f60818"),
+                        path: "added-dir/a".into()
+                    },
+                    Modification {
+                        previous_entry_mode: EntryMode::Blob,
+                        previous_oid: hex_to_id("61780798228d17af2d34fce4cfbdf35556832472"),
+                        entry_mode: EntryMode::BlobExecutable,
+                        oid: hex_to_id("61780798228d17af2d34fce4cfbdf35556832472"),
+                        path: "b".into()
+                    },
+                    Deletion {
+                        entry_mode: EntryMode::Blob,
+                        oid: hex_to_id("f2ad6c76f0115a6ba5b00456a849810e7ec0af20"),
+                        path: "c".into()
+                    },
+                    Modification {
+                        previous_entry_mode: EntryMode::Blob,
+                        previous_oid: hex_to_id("957fc19438218156f035aa93a41dd2aabe297e26"),
+                        entry_mode: EntryMode::Blob,
+                        oid: hex_to_id("2e0996000b7e9019eabcad29391bf0f5c7702f0b"),
+                        path: "changed/a".into()
+                    },
+                    Deletion {
+                        entry_mode: EntryMode::Blob,
+                        oid: hex_to_id("dfcad45ba5d65101b82287926014e373249e2219"),
+                        path: "removed-dir/a".into()
+                    },
+                ],
+                "intent-to-add files are ignored, see `git diff --cached --raw -r`"
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn unchanged_directories_are_skipped_using_the_tree_extension() -> crate::Result {
+            let (db, index) = fixture()?;
+            let unchanged_tree_id = hex_to_id("ca4937ad1e43e7c6090760fe2ad68a2765b13bb4");
+            assert_eq!(
+                index
+                    .tree()
+                    .expect("tree extension present")
+                    .children()
+                    .iter()
+                    .find(|tree| tree.name() == "unchanged")
+                    .and_then(|tree| tree.id()),
+                Some(unchanged_tree_id.as_ref()),
+                "the tree extension knows the unchanged directory"
+            );
+
+            let (_, trees_looked_up) = diff_head_with_index(&db, &index)?;
+            assert!(
+                !trees_looked_up.contains(&unchanged_tree_id),
+                "the unchanged directory doesn't need to be looked at"
+            );
+            assert_eq!(trees_looked_up.len(), 2, "only 'changed' and 'removed-dir' are loaded");
+            Ok(())
+        }
+
+        #[test]
+        fn empty_tree_yields_additions_only() -> crate::Result {
+            let (_db, index) = fixture()?;
+            let mut recorder = git_diff::tree::Recorder::default();
+            git_diff::index::Changes::from(None).needed_to_obtain(
+                &index,
+                git_diff::index::State::default(),
+                |_, _| None,
+                &mut recorder,
+            )?;
+            assert_eq!(
+                recorder
+                    .records
+                    .iter()
+                    .map(|change| match change {
+                        Addition { path, .. } => path.to_str().expect("valid UTF-8"),
+                        _ => unreachable!("only additions"),
+                    })
+                    .collect::<Vec<_>>(),
+                vec![
+                    "a",
+                    "added-dir/a",
+                    "b",
+                    "changed/a",
+                    "changed/b",
+                    "unchanged/a",
+                    "unchanged/sub/a"
+                ]
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn pathspecs_limit_changes() -> crate::Result {
+            let (_db, index) = fixture()?;
+            let search = git_pathspec::Search::from_patterns(
+                vec![
+                    git_pathspec::parse(b"*/a", Default::default())?,
+                    git_pathspec::parse(b":!unchanged/", Default::default())?,
+                ],
+                "".into(),
+            )?;
+            let mut filter =
+                git_diff::tree::pathspec::Filter::new(&search, git_diff::tree::Recorder::default(), |_, _| {
+                    git_pathspec::AttributeState::Unspecified
+                });
+            git_diff::index::Changes::from(None).needed_to_obtain(
+                &index,
+                git_diff::index::State::default(),
+                |_, _| None,
+                &mut filter,
+            )?;
+            assert_eq!(
+                filter
+                    .delegate
+                    .records
+                    .iter()
+                    .map(|change| match change {
+                        Addition { path, .. } => path.to_str().expect("valid UTF-8"),
+                        _ => unreachable!("only additions"),
+                    })
+                    .collect::<Vec<_>>(),
+                vec!["added-dir/a", "changed/a"]
+            );
+            Ok(())
+        }
+    }
+}
@@ -53,3 +53,20 @@ pub mod walkdir {
 
 #[cfg(any(feature = "walkdir", feature = "jwalk"))]
 pub use self::walkdir::{walkdir_new, walkdir_sorted_new, WalkDir};
+
+/// Return true if the file described by `metadata` is executable by anyone.
+///
+/// On platforms without an executable bit, all files are considered executable.
+#[cfg(unix)]
+pub fn is_executable(metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+}
+
+/// Return true if the file described by `metadata` is executable by anyone.
+///
+/// On platforms without an executable bit, all files are considered executable.
+#[cfg(not(unix))]
+pub fn is_executable(metadata: &std::fs::Metadata) -> bool {
+    metadata.is_file()
+}
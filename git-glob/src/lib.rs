@@ -0,0 +1,166 @@
+#![forbid(unsafe_code, rust_2018_idioms)]
+#![deny(missing_docs)]
+//! Provide glob [`Pattern`]s as used in `.gitignore` and `.gitattributes` files, along with git's [`wildmatch()`] to match them.
+
+use bstr::{BStr, BString, ByteSlice};
+
+///
+pub mod wildmatch;
+pub use wildmatch::wildmatch;
+
+///
+pub mod pattern {
+    use bitflags::bitflags;
+
+    bitflags! {
+        /// Information about a [`Pattern`][super::Pattern], derived from its text when parsing it.
+        pub struct Mode: u32 {
+            /// The pattern contains no slash after removing the trailing one, and thus matches the last path component.
+            const NO_SUB_DIR = 1 << 0;
+            /// The pattern is `*literal`, which matches everything ending with `literal`.
+            const ENDS_WITH = 1 << 1;
+            /// The pattern must match a directory, and not a file.
+            const MUST_BE_DIR = 1 << 2;
+            /// The pattern matches everything but what it describes, it was prefixed with `!`.
+            const NEGATIVE = 1 << 3;
+            /// The pattern starts with a slash and thus matches only from the beginning.
+            const ABSOLUTE = 1 << 4;
+        }
+    }
+}
+
+/// Whether or not case should be ignored when matching.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Case {
+    /// Case is significant, `a` won't match `A`.
+    Sensitive,
+    /// Ascii characters are compared without regard to their case, like `core.ignoreCase`.
+    Fold,
+}
+
+impl Default for Case {
+    fn default() -> Self {
+        Case::Sensitive
+    }
+}
+
+/// A glob pattern as it is used in `.gitignore` files, optimized for matching.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Pattern {
+    /// The pattern without its leading `!` or `/` and without its trailing `/`, which are represented by [`mode`][Pattern::mode] instead.
+    pub text: BString,
+    /// Additional information derived from the pattern's text.
+    pub mode: pattern::Mode,
+    /// The position in `text` with the first wildcard character, or the length of `text` if it contains none.
+    pub first_wildcard_pos: usize,
+}
+
+impl Pattern {
+    /// Parse `text` like a single line of a `.gitignore` file after comments and trailing whitespace were removed,
+    /// or return `None` if it describes no pattern.
+    pub fn from_bytes(text: &[u8]) -> Option<Self> {
+        let mut mode = pattern::Mode::empty();
+        let mut text = text;
+        if let Some(rest) = text.strip_prefix(b"!") {
+            mode |= pattern::Mode::NEGATIVE;
+            text = rest;
+        }
+        if let Some(rest) = text.strip_suffix(b"/") {
+            mode |= pattern::Mode::MUST_BE_DIR;
+            text = rest;
+        }
+        if let Some(rest) = text.strip_prefix(b"/") {
+            mode |= pattern::Mode::ABSOLUTE;
+            text = rest;
+        }
+        if text.is_empty() {
+            return None;
+        }
+        if !mode.contains(pattern::Mode::ABSOLUTE) && !text.contains(&b'/') {
+            mode |= pattern::Mode::NO_SUB_DIR;
+        }
+        let first_wildcard_pos = find_wildcard(text).unwrap_or(text.len());
+        if text[0] == b'*' && find_wildcard(&text[1..]).is_none() {
+            mode |= pattern::Mode::ENDS_WITH;
+        }
+        Some(Pattern {
+            text: text.into(),
+            mode,
+            first_wildcard_pos,
+        })
+    }
+
+    /// Return true if this pattern is negated, i.e. it was prefixed with `!` to re-include what was excluded before.
+    pub fn is_negative(&self) -> bool {
+        self.mode.contains(pattern::Mode::NEGATIVE)
+    }
+
+    /// Match `path`, which is relative to the directory the pattern is defined in, with this pattern, using `is_dir` to
+    /// know whether `path` points to a directory and `case` to control case-sensitivity.
+    ///
+    /// Note that negation is not considered here, see [`is_negative()`][Pattern::is_negative()].
+    pub fn matches_repo_relative_path(&self, path: &BStr, is_dir: bool, case: Case) -> bool {
+        if self.mode.contains(pattern::Mode::MUST_BE_DIR) && !is_dir {
+            return false;
+        }
+        let wildmatch_mode = match case {
+            Case::Sensitive => wildmatch::Mode::empty(),
+            Case::Fold => wildmatch::Mode::IGNORE_CASE,
+        };
+        let text = self.text.as_bstr();
+        let prefix_len = self.first_wildcard_pos;
+
+        if self.mode.contains(pattern::Mode::NO_SUB_DIR) {
+            let basename = path.rfind_byte(b'/').map_or(path, |pos| path[pos + 1..].as_bstr());
+            if prefix_len == text.len() {
+                eq(text, basename, case)
+            } else if self.mode.contains(pattern::Mode::ENDS_WITH) {
+                let suffix = &text[1..];
+                basename.len() >= suffix.len() && eq(suffix, &basename[basename.len() - suffix.len()..], case)
+            } else {
+                wildmatch(text, basename, wildmatch_mode)
+            }
+        } else {
+            if prefix_len > 0 {
+                if prefix_len > path.len() || !eq(&text[..prefix_len], &path[..prefix_len], case) {
+                    return false;
+                }
+                if prefix_len == text.len() && prefix_len == path.len() {
+                    return true;
+                }
+            }
+            wildmatch(
+                text[prefix_len..].as_bstr(),
+                path[prefix_len..].as_bstr(),
+                wildmatch_mode | wildmatch::Mode::NO_MATCH_SLASH_LITERAL,
+            )
+        }
+    }
+}
+
+impl std::fmt::Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_negative() {
+            f.write_str("!")?;
+        }
+        if self.mode.contains(pattern::Mode::ABSOLUTE) {
+            f.write_str("/")?;
+        }
+        std::fmt::Display::fmt(&self.text, f)?;
+        if self.mode.contains(pattern::Mode::MUST_BE_DIR) {
+            f.write_str("/")?;
+        }
+        Ok(())
+    }
+}
+
+fn eq(a: &[u8], b: &[u8], case: Case) -> bool {
+    match case {
+        Case::Sensitive => a == b,
+        Case::Fold => a.eq_ignore_ascii_case(b),
+    }
+}
+
+fn find_wildcard(text: &[u8]) -> Option<usize> {
+    text.iter().position(|b| matches!(b, b'*' | b'?' | b'[' | b'\\'))
+}
@@ -0,0 +1,275 @@
+use bitflags::bitflags;
+use bstr::BStr;
+
+bitflags! {
+    /// The match mode employed in [`wildmatch()`].
+    pub struct Mode: u8 {
+        /// Let globs like `*` and `?` not match the slash `/` literal, which is useful when matching paths.
+        const NO_MATCH_SLASH_LITERAL = 1 << 0;
+        /// Match case insensitively for ascii characters only.
+        const IGNORE_CASE = 1 << 1;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Result {
+    Match,
+    NoMatch,
+    AbortAll,
+    AbortToStarStar,
+}
+
+const STAR: u8 = b'*';
+const BACKSLASH: u8 = b'\\';
+const SLASH: u8 = b'/';
+const BRACKET_OPEN: u8 = b'[';
+const BRACKET_CLOSE: u8 = b']';
+const COLON: u8 = b':';
+
+/// Return the byte at `pos`, or `0` past the end to mirror the NUL terminator the original algorithm relies on.
+fn at(s: &[u8], pos: usize) -> u8 {
+    s.get(pos).copied().unwrap_or(0)
+}
+
+fn is_glob_special(b: u8) -> bool {
+    matches!(b, b'*' | b'?' | b'[' | b'\\')
+}
+
+/// Employ pattern matching to see if `value` matches `pattern`, with `mode` controlling the details of the match.
+///
+/// This is a port of git's `wildmatch()`, which handles `*`, `?`, `[…]` character classes including `[:class:]`
+/// names, escaping with `\` and `**` for matching any amount of directories if [`Mode::NO_MATCH_SLASH_LITERAL`] is set.
+pub fn wildmatch(pattern: &BStr, value: &BStr, mode: Mode) -> bool {
+    match_recursive(pattern.as_ref(), value.as_ref(), mode) == Result::Match
+}
+
+fn fold(b: u8, mode: Mode) -> u8 {
+    if mode.contains(Mode::IGNORE_CASE) {
+        b.to_ascii_lowercase()
+    } else {
+        b
+    }
+}
+
+fn match_recursive(pattern: &[u8], text: &[u8], mode: Mode) -> Result {
+    let mut p = 0;
+    let mut t = 0;
+    while p < pattern.len() {
+        let mut p_ch = fold(pattern[p], mode);
+        let mut t_ch = fold(at(text, t), mode);
+        if t_ch == 0 && p_ch != STAR {
+            return Result::AbortAll;
+        }
+        match p_ch {
+            b'?' => {
+                if mode.contains(Mode::NO_MATCH_SLASH_LITERAL) && t_ch == SLASH {
+                    return Result::NoMatch;
+                }
+            }
+            STAR => {
+                let match_slash;
+                p += 1;
+                if at(pattern, p) == STAR {
+                    let prev_p = p.checked_sub(2);
+                    while at(pattern, p) == STAR {
+                        p += 1;
+                    }
+                    if !mode.contains(Mode::NO_MATCH_SLASH_LITERAL) {
+                        // without matching paths, `*` is the same as `**`
+                        match_slash = true;
+                    } else if prev_p.map_or(true, |prev_p| pattern[prev_p] == SLASH)
+                        && (at(pattern, p) == 0
+                            || at(pattern, p) == SLASH
+                            || (at(pattern, p) == BACKSLASH && at(pattern, p + 1) == SLASH))
+                    {
+                        if at(pattern, p) == SLASH
+                            && match_recursive(&pattern[p + 1..], &text[t..], mode) == Result::Match
+                        {
+                            return Result::Match;
+                        }
+                        match_slash = true;
+                    } else {
+                        match_slash = false;
+                    }
+                } else {
+                    match_slash = !mode.contains(Mode::NO_MATCH_SLASH_LITERAL);
+                }
+
+                if at(pattern, p) == 0 {
+                    if !match_slash && text[t..].contains(&SLASH) {
+                        return Result::NoMatch;
+                    }
+                    return Result::Match;
+                } else if !match_slash && at(pattern, p) == SLASH {
+                    match text[t..].iter().position(|b| *b == SLASH) {
+                        Some(pos) => {
+                            t += pos;
+                            p += 1;
+                            t += 1;
+                            continue;
+                        }
+                        None => return Result::NoMatch,
+                    }
+                }
+
+                loop {
+                    if t_ch == 0 {
+                        break;
+                    }
+                    if !is_glob_special(at(pattern, p)) {
+                        p_ch = fold(at(pattern, p), mode);
+                        loop {
+                            t_ch = fold(at(text, t), mode);
+                            if t_ch == 0 || (!match_slash && t_ch == SLASH) || t_ch == p_ch {
+                                break;
+                            }
+                            t += 1;
+                        }
+                        if t_ch != p_ch {
+                            return Result::NoMatch;
+                        }
+                    }
+                    match match_recursive(&pattern[p..], &text[t..], mode) {
+                        Result::NoMatch => {
+                            if !match_slash && t_ch == SLASH {
+                                return Result::AbortToStarStar;
+                            }
+                        }
+                        res => {
+                            if !match_slash || res != Result::AbortToStarStar {
+                                return res;
+                            }
+                        }
+                    }
+                    t += 1;
+                    t_ch = fold(at(text, t), mode);
+                }
+                return Result::AbortAll;
+            }
+            BRACKET_OPEN => {
+                p += 1;
+                p_ch = at(pattern, p);
+                if p_ch == b'^' {
+                    p_ch = b'!';
+                }
+                let negated = p_ch == b'!';
+                if negated {
+                    p += 1;
+                    p_ch = at(pattern, p);
+                }
+                let mut prev_ch = 0;
+                let mut matched = false;
+                loop {
+                    if p_ch == 0 {
+                        return Result::AbortAll;
+                    }
+                    if p_ch == BACKSLASH {
+                        p += 1;
+                        p_ch = at(pattern, p);
+                        if p_ch == 0 {
+                            return Result::AbortAll;
+                        }
+                        if t_ch == fold(p_ch, mode) {
+                            matched = true;
+                        }
+                    } else if p_ch == b'-'
+                        && prev_ch != 0
+                        && at(pattern, p + 1) != 0
+                        && at(pattern, p + 1) != BRACKET_CLOSE
+                    {
+                        p += 1;
+                        p_ch = at(pattern, p);
+                        if p_ch == BACKSLASH {
+                            p += 1;
+                            p_ch = at(pattern, p);
+                            if p_ch == 0 {
+                                return Result::AbortAll;
+                            }
+                        }
+                        if t_ch <= p_ch && t_ch >= prev_ch {
+                            matched = true;
+                        } else if mode.contains(Mode::IGNORE_CASE) && t_ch.is_ascii_lowercase() {
+                            let t_ch_upper = t_ch.to_ascii_uppercase();
+                            if t_ch_upper <= p_ch && t_ch_upper >= prev_ch {
+                                matched = true;
+                            }
+                        }
+                        p_ch = 0;
+                    } else if p_ch == BRACKET_OPEN && at(pattern, p + 1) == COLON {
+                        p += 2;
+                        let class_start = p;
+                        while at(pattern, p) != 0 && at(pattern, p) != BRACKET_CLOSE {
+                            p += 1;
+                        }
+                        if at(pattern, p) == 0 {
+                            return Result::AbortAll;
+                        }
+                        if p == class_start || pattern[p - 1] != COLON {
+                            // Didn't find ":]", so treat like a normal set.
+                            p = class_start - 2;
+                            p_ch = BRACKET_OPEN;
+                            if t_ch == p_ch {
+                                matched = true;
+                            }
+                        } else {
+                            let t = t_ch;
+                            let is_match = match &pattern[class_start..p - 1] {
+                                b"alnum" => t.is_ascii_alphanumeric(),
+                                b"alpha" => t.is_ascii_alphabetic(),
+                                b"blank" => t == b' ' || t == b'\t',
+                                b"cntrl" => t.is_ascii_control(),
+                                b"digit" => t.is_ascii_digit(),
+                                b"graph" => t.is_ascii_graphic(),
+                                b"lower" => t.is_ascii_lowercase(),
+                                b"print" => t.is_ascii_graphic() || t == b' ',
+                                b"punct" => t.is_ascii_punctuation(),
+                                b"space" => t.is_ascii_whitespace() || t == b'\x0b',
+                                b"upper" => {
+                                    t.is_ascii_uppercase()
+                                        || (mode.contains(Mode::IGNORE_CASE) && t.is_ascii_lowercase())
+                                }
+                                b"xdigit" => t.is_ascii_hexdigit(),
+                                _ => return Result::AbortAll,
+                            };
+                            if is_match {
+                                matched = true;
+                            }
+                            p_ch = 0;
+                        }
+                    } else if t_ch == fold(p_ch, mode) {
+                        matched = true;
+                    }
+
+                    prev_ch = p_ch;
+                    p += 1;
+                    p_ch = at(pattern, p);
+                    if p_ch == BRACKET_CLOSE {
+                        break;
+                    }
+                }
+                if matched == negated || (mode.contains(Mode::NO_MATCH_SLASH_LITERAL) && t_ch == SLASH) {
+                    return Result::NoMatch;
+                }
+            }
+            BACKSLASH => {
+                p += 1;
+                p_ch = fold(at(pattern, p), mode);
+                if t_ch != p_ch {
+                    return Result::NoMatch;
+                }
+            }
+            _ => {
+                if t_ch != p_ch {
+                    return Result::NoMatch;
+                }
+            }
+        }
+        p += 1;
+        t += 1;
+    }
+    if t < text.len() {
+        Result::NoMatch
+    } else {
+        Result::Match
+    }
+}
@@ -0,0 +1,150 @@
+use git_glob::{self as glob, pattern::Mode, wildmatch, Case, Pattern};
+
+/// Cases from git's `t3070-wildmatch.sh`, with the expected outcome for matching paths, paths ignoring case,
+/// anything and anything ignoring case.
+const CASES: &[(u8, u8, u8, u8, &str, &str)] = &[
+    (1, 1, 1, 1, "foo", "foo"),
+    (0, 0, 0, 0, "foo", "bar"),
+    (1, 1, 1, 1, "", ""),
+    (1, 1, 1, 1, "foo", "???"),
+    (0, 0, 0, 0, "foo", "??"),
+    (1, 1, 1, 1, "foo", "*"),
+    (1, 1, 1, 1, "foo", "f*"),
+    (0, 0, 0, 0, "foo", "*f"),
+    (1, 1, 1, 1, "foo", "*foo*"),
+    (1, 1, 1, 1, "foobar", "*ob*a*r*"),
+    (1, 1, 1, 1, "aaaaaaabababab", "*ab"),
+    (1, 1, 1, 1, "foo*", "foo\\*"),
+    (0, 0, 0, 0, "foobar", "foo\\*bar"),
+    (1, 1, 1, 1, "f\\oo", "f\\\\oo"),
+    (1, 1, 1, 1, "ball", "*[al]?"),
+    (0, 0, 0, 0, "ten", "[ten]"),
+    (1, 1, 1, 1, "ten", "**[!te]"),
+    (0, 0, 0, 0, "ten", "**[!ten]"),
+    (1, 1, 1, 1, "ten", "t[a-g]n"),
+    (0, 0, 0, 0, "ten", "t[!a-g]n"),
+    (1, 1, 1, 1, "ton", "t[!a-g]n"),
+    (1, 1, 1, 1, "ton", "t[^a-g]n"),
+    (1, 1, 1, 1, "a]b", "a[]]b"),
+    (1, 1, 1, 1, "a-b", "a[]-]b"),
+    (1, 1, 1, 1, "a]b", "a[]-]b"),
+    (0, 0, 0, 0, "aab", "a[]-]b"),
+    (1, 1, 1, 1, "aab", "a[]a-]b"),
+    (1, 1, 1, 1, "]", "]"),
+    (0, 0, 1, 1, "foo/baz/bar", "foo*bar"),
+    (0, 0, 1, 1, "foo/baz/bar", "foo**bar"),
+    (1, 1, 1, 1, "foobazbar", "foo**bar"),
+    (1, 1, 1, 1, "foo/baz/bar", "foo/**/bar"),
+    (1, 1, 0, 0, "foo/baz/bar", "foo/**/**/bar"),
+    (1, 1, 1, 1, "foo/b/a/z/bar", "foo/**/bar"),
+    (1, 1, 1, 1, "foo/b/a/z/bar", "foo/**/**/bar"),
+    (1, 1, 0, 0, "foo/bar", "foo/**/bar"),
+    (1, 1, 0, 0, "foo/bar", "foo/**/**/bar"),
+    (0, 0, 1, 1, "foo/bar", "foo?bar"),
+    (0, 0, 1, 1, "foo/bar", "foo[/]bar"),
+    (0, 0, 1, 1, "foo/bar", "foo[^a-z]bar"),
+    (0, 0, 1, 1, "foo/bar", "f[^eiu][^eiu][^eiu][^eiu][^eiu]r"),
+    (1, 1, 1, 1, "foo-bar", "f[^eiu][^eiu][^eiu][^eiu][^eiu]r"),
+    (1, 1, 0, 0, "foo", "**/foo"),
+    (1, 1, 1, 1, "XXX/foo", "**/foo"),
+    (1, 1, 1, 1, "bar/baz/foo", "**/foo"),
+    (0, 0, 1, 1, "bar/baz/foo", "*/foo"),
+    (0, 0, 1, 1, "foo/bar/baz", "**/bar*"),
+    (1, 1, 1, 1, "deep/foo/bar/baz", "**/bar/*"),
+    (0, 0, 1, 1, "deep/foo/bar/baz/", "**/bar/*"),
+    (1, 1, 1, 1, "deep/foo/bar/baz/", "**/bar/**"),
+    (0, 0, 0, 0, "deep/foo/bar", "**/bar/*"),
+    (1, 1, 1, 1, "deep/foo/bar/", "**/bar/**"),
+    (0, 0, 1, 1, "foo/bar/baz", "**/bar**"),
+    (1, 1, 1, 1, "foo/bar/baz/x", "*/bar/**"),
+    (0, 0, 1, 1, "deep/foo/bar/baz/x", "*/bar/**"),
+    (1, 1, 1, 1, "deep/foo/bar/baz/x", "**/bar/*/*"),
+    (1, 1, 1, 1, "a1B", "[[:alpha:]][[:digit:]][[:upper:]]"),
+    (0, 1, 0, 1, "a", "[[:digit:][:upper:][:space:]]"),
+    (1, 1, 1, 1, "A", "[[:digit:][:upper:][:space:]]"),
+    (1, 1, 1, 1, "1", "[[:digit:][:upper:][:space:]]"),
+    (0, 0, 0, 0, "1", "[[:digit:][:upper:][:spaci:]]"),
+    (1, 1, 1, 1, " ", "[[:digit:][:upper:][:space:]]"),
+    (0, 0, 0, 0, ".", "[[:digit:][:upper:][:space:]]"),
+    (1, 1, 1, 1, ".", "[[:digit:][:punct:][:space:]]"),
+    (1, 1, 1, 1, "5", "[[:xdigit:]]"),
+    (1, 1, 1, 1, "f", "[[:xdigit:]]"),
+    (1, 1, 1, 1, "D", "[[:xdigit:]]"),
+    (0, 1, 0, 1, "a", "[A-Z]"),
+    (1, 1, 1, 1, "A", "[A-Z]"),
+    (0, 1, 0, 1, "A", "[a-z]"),
+    (1, 1, 1, 1, "a", "[a-z]"),
+    (0, 1, 0, 1, "a", "[[:upper:]]"),
+    (1, 1, 1, 1, "A", "[[:upper:]]"),
+    (0, 1, 0, 1, "A", "[[:lower:]]"),
+    (1, 1, 1, 1, "a", "[[:lower:]]"),
+    (0, 1, 0, 1, "A", "[B-Za]"),
+    (1, 1, 1, 1, "a", "[B-Za]"),
+    (0, 1, 0, 1, "A", "[B-a]"),
+    (1, 1, 1, 1, "a", "[B-a]"),
+    (0, 1, 0, 1, "z", "[Z-y]"),
+    (1, 1, 1, 1, "Z", "[Z-y]"),
+];
+
+#[test]
+fn wildmatch_like_git() {
+    use wildmatch::Mode;
+    for (path, path_icase, any, any_icase, text, pattern) in CASES {
+        for (expected, mode) in [
+            (path, Mode::NO_MATCH_SLASH_LITERAL),
+            (path_icase, Mode::NO_MATCH_SLASH_LITERAL | Mode::IGNORE_CASE),
+            (any, Mode::empty()),
+            (any_icase, Mode::IGNORE_CASE),
+        ] {
+            assert_eq!(
+                glob::wildmatch((*pattern).into(), (*text).into(), mode),
+                *expected == 1,
+                "{:?} matching {:?} with {:?}",
+                pattern,
+                text,
+                mode
+            );
+        }
+    }
+}
+
+#[test]
+fn pattern_parsing_derives_its_mode() {
+    let parse = |text: &str| Pattern::from_bytes(text.as_bytes()).expect("valid pattern");
+    assert_eq!(parse("*.o").mode, Mode::NO_SUB_DIR | Mode::ENDS_WITH);
+    assert_eq!(
+        parse("!build/").mode,
+        Mode::NO_SUB_DIR | Mode::NEGATIVE | Mode::MUST_BE_DIR
+    );
+    assert_eq!(parse("/top").mode, Mode::ABSOLUTE);
+    assert_eq!(parse("doc/*.html").mode, Mode::empty());
+    assert_eq!(parse("doc/*.html").first_wildcard_pos, 4);
+    assert_eq!(parse("!/dir/").to_string(), "!/dir/");
+    assert!(Pattern::from_bytes(b"/").is_none());
+    assert!(Pattern::from_bytes(b"!").is_none());
+}
+
+#[test]
+fn pattern_matching() {
+    let matches = |pattern: &str, path: &str, is_dir: bool, case: Case| {
+        Pattern::from_bytes(pattern.as_bytes())
+            .expect("valid pattern")
+            .matches_repo_relative_path(path.into(), is_dir, case)
+    };
+    assert!(matches("*.o", "dir/file.o", false, Case::Sensitive));
+    assert!(!matches("*.o", "dir/file.O", false, Case::Sensitive));
+    assert!(matches("*.o", "dir/file.O", false, Case::Fold));
+    assert!(matches("build/", "a/build", true, Case::Sensitive));
+    assert!(!matches("build/", "a/build", false, Case::Sensitive));
+    assert!(matches("/top", "top", false, Case::Sensitive));
+    assert!(!matches("/top", "a/top", false, Case::Sensitive));
+    assert!(matches("doc/*.html", "doc/index.html", false, Case::Sensitive));
+    assert!(!matches("doc/*.html", "doc/api/index.html", false, Case::Sensitive));
+    assert!(matches("DOC/*.html", "doc/index.HTML", false, Case::Fold));
+    assert!(matches(
+        "doc/**/index.html",
+        "doc/api/index.html",
+        false,
+        Case::Sensitive
+    ));
+}
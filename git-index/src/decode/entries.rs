@@ -85,6 +85,7 @@ fn load_one<'a>(
     has_delta_paths: bool,
     prev_path_and_buf: Option<(Range<usize>, &mut Vec<u8>)>,
 ) -> Option<(Entry, &'a [u8])> {
+    let entry_start_len = data.len();
     let (ctime_secs, data) = read_u32(data)?;
     let (ctime_nsecs, data) = read_u32(data)?;
     let (mtime_secs, data) = read_u32(data)?;
@@ -125,7 +126,8 @@ fn load_one<'a>(
 
         data
     } else {
-        let (path, data) = if flags.contains(entry::Flags::PATH_LEN) {
+        let entry_len_sans_path = entry_start_len - data.len();
+        let (path, _) = if flags.contains(entry::Flags::PATH_LEN) {
             split_at_byte_exclusive(data, 0)?
         } else {
             let path_len = (flags.bits() & entry::Flags::PATH_LEN.bits()) as usize;
@@ -133,7 +135,7 @@ fn load_one<'a>(
         };
 
         path_backing.extend_from_slice(path);
-        skip_padding(data)
+        skip_padded_path(data, entry_len_sans_path, path.len())?
     };
     let path_range = start..path_backing.len();
 
@@ -165,9 +167,11 @@ fn load_one<'a>(
 }
 
 #[inline]
-fn skip_padding(data: &[u8]) -> &[u8] {
-    let skip = data.iter().take_while(|b| **b == 0).count();
-    &data[skip..]
+/// Skip the path of `path_len` bytes at the beginning of `data` along with the NUL bytes padding the entry to a multiple of 8,
+/// knowing that everything before the path is `entry_len_sans_path` bytes long.
+fn skip_padded_path(data: &[u8], entry_len_sans_path: usize, path_len: usize) -> Option<&[u8]> {
+    let padded_len = (entry_len_sans_path + path_len + 8) & !7;
+    data.get(padded_len - entry_len_sans_path..)
 }
 
 #[inline]
@@ -37,7 +37,7 @@ use git_features::parallel::InOrderIter;
 
 use crate::util::read_u32;
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct Options {
     pub object_hash: git_hash::Kind,
     /// If Some(_), we are allowed to use more than one thread. If Some(N), use no more than N threads. If Some(0)|None, use as many threads
@@ -278,11 +278,11 @@ pub(crate) fn stat(data: &[u8]) -> Option<(entry::Stat, &[u8])> {
     let (size, data) = read_u32(data)?;
     Some((
         entry::Stat {
-            mtime: entry::Time {
+            ctime: entry::Time {
                 secs: ctime_secs,
                 nsecs: ctime_nsecs,
             },
-            ctime: entry::Time {
+            mtime: entry::Time {
                 secs: mtime_secs,
                 nsecs: mtime_nsecs,
             },
@@ -31,8 +31,12 @@ pub(crate) mod at_rest {
         pub struct Flags: u16 {
             /// A portion of a the flags that encodes the length of the path that follows.
             const PATH_LEN = 0x0fff;
+            /// The stage of the entry, with 0 being the normal stage and 1 to 3 being used during merge conflicts.
+            const STAGE_MASK = 0x3000;
             /// If set, there is more extended flags past this one
             const EXTENDED = 0x4000;
+            /// If set, the entry is assumed to be unchanged in the worktree.
+            const ASSUME_VALID = 0x8000;
         }
     }
 
@@ -52,8 +56,24 @@ pub(crate) mod at_rest {
 
     impl Flags {
         pub fn to_memory(self) -> super::Flags {
-            super::Flags::from_bits((self & Flags::PATH_LEN).bits as u32)
-                .expect("PATHLEN is part of memory representation")
+            super::Flags::from_bits((self & (Flags::PATH_LEN | Flags::STAGE_MASK | Flags::ASSUME_VALID)).bits as u32)
+                .expect("PATHLEN, STAGE_MASK and ASSUME_VALID are part of memory representation")
+        }
+    }
+
+    impl super::Flags {
+        /// Return the flags as stored on disk, without the path length, and the extended flags if there are any.
+        pub fn to_storage(self) -> (Flags, Option<FlagsExtended>) {
+            let flags =
+                Flags::from_bits_truncate((self & (super::Flags::STAGE_MASK | super::Flags::ASSUME_VALID)).bits as u16);
+            let extended = FlagsExtended::from_bits_truncate(
+                ((self & (super::Flags::INTENT_TO_ADD | super::Flags::SKIP_WORKTREE)).bits >> 16) as u16,
+            );
+            if extended.is_empty() {
+                (flags, None)
+            } else {
+                (flags | Flags::EXTENDED, Some(extended))
+            }
         }
     }
 }
@@ -63,6 +83,10 @@ bitflags! {
     pub struct Flags: u32 {
         // TODO: could we use the pathlen ourselves to save 8 bytes? And how to handle longer paths than that? 0 as sentinel maybe?
         const PATH_LEN = 0x0fff;
+        /// The stage of the entry, stored at rest, see `stage()`
+        const STAGE_MASK = 0x3000;
+        /// Assume the entry is unchanged in the worktree, stored at rest
+        const ASSUME_VALID = 0x8000;
         const UPDATE = 1 << 16;
         const REMOVE = 1 << 17;
         const UPTODATE = 1 << 18;
@@ -88,11 +112,13 @@ bitflags! {
     }
 }
 
+#[derive(PartialEq, Eq, Debug, Hash, Ord, PartialOrd, Clone, Copy, Default)]
 pub struct Time {
     pub secs: u32,
     pub nsecs: u32,
 }
 
+#[derive(PartialEq, Eq, Debug, Hash, Ord, PartialOrd, Clone, Copy, Default)]
 pub struct Stat {
     pub mtime: Time,
     pub ctime: Time,
@@ -104,6 +130,56 @@ pub struct Stat {
     pub size: u32,
 }
 
+mod stat {
+    use std::convert::TryInto;
+
+    use super::{Stat, Time};
+
+    impl Time {
+        fn from_file_time(time: filetime::FileTime) -> Self {
+            Time {
+                secs: time.unix_seconds().try_into().unwrap_or_default(),
+                nsecs: time.nanoseconds(),
+            }
+        }
+    }
+
+    impl Stat {
+        /// Obtain stat information from filesystem `metadata` just like git would, truncating all values to 32 bits.
+        pub fn from_fs(metadata: &std::fs::Metadata) -> Self {
+            let mtime = Time::from_file_time(filetime::FileTime::from_last_modification_time(metadata));
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::MetadataExt;
+                Stat {
+                    mtime,
+                    ctime: Time {
+                        secs: metadata.ctime() as u32,
+                        nsecs: metadata.ctime_nsec() as u32,
+                    },
+                    dev: metadata.dev() as u32,
+                    ino: metadata.ino() as u32,
+                    uid: metadata.uid(),
+                    gid: metadata.gid(),
+                    size: metadata.len() as u32,
+                }
+            }
+            #[cfg(not(unix))]
+            Stat {
+                mtime,
+                ctime: filetime::FileTime::from_creation_time(metadata)
+                    .map(Time::from_file_time)
+                    .unwrap_or(mtime),
+                dev: 0,
+                ino: 0,
+                uid: 0,
+                gid: 0,
+                size: metadata.len() as u32,
+            }
+        }
+    }
+}
+
 mod access {
     use bstr::{BStr, ByteSlice};
 
@@ -113,5 +189,11 @@ mod access {
         pub fn path<'a>(&self, state: &'a State) -> &'a BStr {
             (&state.path_backing[self.path.clone()]).as_bstr()
         }
+
+        /// Return the stage of this entry, with 0 being the normal stage and 1 to 3 indicating the base, ours and theirs
+        /// during merge conflicts.
+        pub fn stage(&self) -> u32 {
+            (self.flags & super::Flags::STAGE_MASK).bits() >> 12
+        }
     }
 }
@@ -1,32 +1,40 @@
 use crate::{decode::header, extension, extension::Signature, util::from_be_u32};
 
 pub const SIGNATURE: Signature = *b"EOIE";
-pub const SIZE: usize = 4 /* offset to extensions */ + git_hash::Kind::Sha1.len_in_bytes();
-pub const SIZE_WITH_HEADER: usize = crate::extension::MIN_SIZE + SIZE;
+
+/// The size of the extension's data, an offset to the extensions and a hash of their headers using `object_hash`.
+pub fn size(object_hash: git_hash::Kind) -> usize {
+    4 /* offset to extensions */ + object_hash.len_in_bytes()
+}
+
+fn size_with_header(object_hash: git_hash::Kind) -> usize {
+    crate::extension::MIN_SIZE + size(object_hash)
+}
 
 pub fn decode(data: &[u8], object_hash: git_hash::Kind) -> Option<usize> {
     let hash_len = object_hash.len_in_bytes();
-    if data.len() < SIZE_WITH_HEADER + hash_len {
+    let size_with_header = size_with_header(object_hash);
+    if data.len() < size_with_header + hash_len {
         return None;
     }
 
-    let start_of_eoie = data.len() - SIZE_WITH_HEADER - hash_len;
+    let start_of_eoie = data.len() - size_with_header - hash_len;
     let ext_data = &data[start_of_eoie..data.len() - hash_len];
 
     let (signature, ext_size, ext_data) = extension::decode::header(ext_data);
-    if signature != SIGNATURE || ext_size as usize != SIZE {
+    if signature != SIGNATURE || ext_size as usize != size(object_hash) {
         return None;
     }
 
     let (offset, checksum) = ext_data.split_at(4);
     let offset = from_be_u32(offset) as usize;
-    if offset < header::SIZE || offset > start_of_eoie || checksum.len() != git_hash::Kind::Sha1.len_in_bytes() {
+    if offset < header::SIZE || offset > start_of_eoie || checksum.len() != hash_len {
         return None;
     }
 
-    let mut hasher = git_features::hash::hasher(git_hash::Kind::Sha1);
+    let mut hasher = git_features::hash::hasher(object_hash);
     let mut last_chunk = None;
-    for (signature, chunk) in extension::Iter::new(&data[offset..data.len() - SIZE_WITH_HEADER - hash_len]) {
+    for (signature, chunk) in extension::Iter::new(&data[offset..start_of_eoie]) {
         hasher.update(&signature);
         hasher.update(&(chunk.len() as u32).to_be_bytes());
         last_chunk = Some(chunk);
@@ -55,3 +55,90 @@ pub(crate) fn decode(data: &[u8], object_hash: git_hash::Kind) -> Result<Link, d
         bitmaps: Some(Bitmaps { delete, replace }),
     })
 }
+
+/// Write `link` into `out`, without the extension header.
+pub(crate) fn write_to(link: &Link, mut out: impl std::io::Write) -> std::io::Result<()> {
+    out.write_all(link.shared_index_checksum.as_slice())?;
+    if let Some(bitmaps) = &link.bitmaps {
+        bitmaps.delete.write_to(&mut out)?;
+        bitmaps.replace.write_to(&mut out)?;
+    }
+    Ok(())
+}
+
+impl Link {
+    /// Load the shared index this link points to from the directory containing `split_index` and merge the entries of
+    /// `split_index` into it, according to our bitmaps. Afterwards `split_index` contains all entries, and remembers the
+    /// shared index it was based on.
+    pub(crate) fn dissolve_into(
+        self,
+        split_index: &mut crate::File,
+        options: crate::decode::Options,
+    ) -> Result<(), crate::file::init::Error> {
+        use crate::file::init::Error;
+
+        let shared_index_path = split_index
+            .path
+            .parent()
+            .expect("split index file in .git folder")
+            .join(format!("sharedindex.{}", self.shared_index_checksum));
+        let shared_index = crate::File::at(shared_index_path, options)?;
+        if shared_index.checksum != self.shared_index_checksum {
+            return Err(Error::SharedIndexChecksumMismatch {
+                expected: self.shared_index_checksum,
+                actual: shared_index.checksum,
+            });
+        }
+
+        let mut entries = shared_index.state.entries.clone();
+        let mut path_backing = shared_index.state.path_backing.clone();
+        let split_entries = std::mem::take(&mut split_index.state.entries);
+        let split_path_backing = std::mem::take(&mut split_index.state.path_backing);
+
+        let mut split_entry_index = 0;
+        if let Some(Bitmaps { delete, replace }) = self.bitmaps {
+            replace
+                .for_each_set_bit(|index| {
+                    let shared_entry = entries.get_mut(index)?;
+                    let split_entry = split_entries.get(split_entry_index)?;
+                    shared_entry.stat = split_entry.stat;
+                    shared_entry.id = split_entry.id;
+                    shared_entry.flags = split_entry.flags;
+                    shared_entry.mode = split_entry.mode;
+                    split_entry_index += 1;
+                    Some(())
+                })
+                .ok_or(Error::SplitIndexCorrupt(
+                    "replace bitmap refers to entries that don't exist in shared or split index",
+                ))?;
+
+            delete
+                .for_each_set_bit(|index| {
+                    entries.get_mut(index)?.flags.insert(crate::entry::Flags::REMOVE);
+                    Some(())
+                })
+                .ok_or(Error::SplitIndexCorrupt(
+                    "delete bitmap refers to entries that don't exist in the shared index",
+                ))?;
+            entries.retain(|e| !e.flags.contains(crate::entry::Flags::REMOVE));
+        }
+
+        for mut entry in split_entries.into_iter().skip(split_entry_index) {
+            let start = path_backing.len();
+            path_backing.extend_from_slice(&split_path_backing[entry.path.clone()]);
+            entry.path = start..path_backing.len();
+            entries.push(entry);
+        }
+        entries.sort_by(|a, b| {
+            path_backing[a.path.clone()]
+                .cmp(&path_backing[b.path.clone()])
+                .then_with(|| a.stage().cmp(&b.stage()))
+        });
+
+        split_index.state.is_sparse |= shared_index.state.is_sparse;
+        split_index.state.entries = entries;
+        split_index.state.path_backing = path_backing;
+        split_index.shared_index = Some(Box::new(shared_index));
+        Ok(())
+    }
+}
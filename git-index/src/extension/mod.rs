@@ -29,17 +29,18 @@ pub struct Link {
 pub struct UntrackedCache {
     /// Something identifying the location and machine that this cache is for.
     /// Should the repository be copied to a different machine, the entire cache can immediately be invalidated.
-    identifier: BString,
+    pub identifier: BString,
     /// Stat for the .git/info/exclude file
-    info_exclude: Option<untracked_cache::OidStat>,
+    pub info_exclude: Option<untracked_cache::OidStat>,
     /// Stat for the `core.excludesfile`
-    excludes_file: Option<untracked_cache::OidStat>,
+    pub excludes_file: Option<untracked_cache::OidStat>,
     /// Usually `.gitignore`
-    exclude_filename_per_dir: BString,
-    dir_flags: u32,
+    pub exclude_filename_per_dir: BString,
+    /// The flags used by the directory walk that produced this cache, see `untracked_cache::dir_flags`.
+    pub dir_flags: u32,
 
     /// A list of directories and sub-directories, with `directories[0]` being the root.
-    directories: Vec<untracked_cache::Directory>,
+    pub directories: Vec<untracked_cache::Directory>,
 }
 
 pub struct FsMonitor {
@@ -51,6 +52,8 @@ pub struct FsMonitor {
 mod iter;
 
 pub(crate) mod fs_monitor {
+    use std::convert::TryInto;
+
     use bstr::BString;
 
     use crate::{
@@ -88,6 +91,26 @@ pub(crate) mod fs_monitor {
 
         FsMonitor { token, entry_dirty }.into()
     }
+
+    /// Write `fs_monitor` into `out`, without the extension header.
+    pub fn write_to(fs_monitor: &FsMonitor, mut out: impl std::io::Write) -> std::io::Result<()> {
+        match &fs_monitor.token {
+            Token::V1 { nanos_since_1970 } => {
+                out.write_all(&1u32.to_be_bytes())?;
+                out.write_all(&nanos_since_1970.to_be_bytes())?;
+            }
+            Token::V2 { token } => {
+                out.write_all(&2u32.to_be_bytes())?;
+                out.write_all(token)?;
+                out.write_all(b"\0")?;
+            }
+        }
+        let mut entry_dirty = Vec::new();
+        fs_monitor.entry_dirty.write_to(&mut entry_dirty)?;
+        let ewah_size: u32 = entry_dirty.len().try_into().expect("bitmap is smaller than 4GB");
+        out.write_all(&ewah_size.to_be_bytes())?;
+        out.write_all(&entry_dirty)
+    }
 }
 
 pub(crate) mod decode;
@@ -58,3 +58,19 @@ pub fn decode(mut data: &[u8], object_hash: git_hash::Kind) -> Option<Paths> {
     }
     out.into()
 }
+
+/// Write `paths` into `out`, without the extension header.
+pub(crate) fn write_to(paths: &[ResolvePath], mut out: impl std::io::Write) -> std::io::Result<()> {
+    for path in paths {
+        out.write_all(&path.name)?;
+        out.write_all(b"\0")?;
+        for stage in &path.stages {
+            let mode = stage.as_ref().map_or(0, |stage| stage.mode);
+            write!(out, "{:o}\0", mode)?;
+        }
+        for stage in path.stages.iter().flatten() {
+            out.write_all(stage.id.as_slice())?;
+        }
+    }
+    Ok(())
+}
@@ -7,10 +7,38 @@ use crate::{
 
 pub const SIGNATURE: Signature = *b"TREE";
 
+mod access {
+    use bstr::{BStr, ByteSlice};
+
+    use crate::extension::Tree;
+
+    impl Tree {
+        /// The name of the directory this tree represents, which is empty for the root tree.
+        pub fn name(&self) -> &BStr {
+            self.name.as_bstr()
+        }
+
+        /// The id of the tree object of this directory, or `None` if it was invalidated by changes to the index.
+        pub fn id(&self) -> Option<&git_hash::oid> {
+            self.id.as_ref().map(|node| node.id.as_ref())
+        }
+
+        /// The amount of index entries within this directory and all of its sub-directories, or `None` if it was invalidated.
+        pub fn num_entries(&self) -> Option<u32> {
+            self.id.as_ref().map(|node| node.entry_count)
+        }
+
+        /// The trees of all sub-directories that are known.
+        pub fn children(&self) -> &[Tree] {
+            &self.children
+        }
+    }
+}
+
 pub struct NodeId {
     /// The id of the directory tree of the associated tree object.
     id: git_hash::ObjectId,
-    /// The amount of non-tree entries contained within, and definitely not zero.
+    /// The amount of non-tree entries contained within, which is only zero for the empty tree.
     entry_count: u32,
 }
 
@@ -29,12 +57,13 @@ pub fn one_recursive(data: &[u8], hash_len: usize) -> Option<(Tree, &[u8])> {
     let (path, data) = split_at_byte_exclusive(data, 0)?;
 
     let (entry_count, data) = split_at_byte_exclusive(data, b' ')?;
-    let entry_count: u32 = atoi::atoi(entry_count)?;
+    // A negative entry count marks an invalidated tree, which doesn't have an id.
+    let entry_count: i32 = atoi::atoi(entry_count)?;
 
     let (subtree_count, mut data) = split_at_byte_exclusive(data, b'\n')?;
     let subtree_count: usize = atoi::atoi(subtree_count)?;
 
-    let node_id = (entry_count != 0)
+    let node_id = (entry_count >= 0)
         .then(|| {
             (data.len() >= hash_len).then(|| {
                 let (hash, rest) = data.split_at(hash_len);
@@ -43,7 +72,10 @@ pub fn one_recursive(data: &[u8], hash_len: usize) -> Option<(Tree, &[u8])> {
             })
         })
         .flatten()
-        .map(|id| NodeId { id, entry_count });
+        .map(|id| NodeId {
+            id,
+            entry_count: entry_count as u32,
+        });
 
     let mut subtrees = Vec::with_capacity(subtree_count);
     for _ in 0..subtree_count {
@@ -62,6 +94,23 @@ pub fn one_recursive(data: &[u8], hash_len: usize) -> Option<(Tree, &[u8])> {
     ))
 }
 
+/// Write `tree` and all of its children recursively into `out`, without the extension header.
+pub fn write_to(tree: &Tree, out: &mut impl std::io::Write) -> std::io::Result<()> {
+    out.write_all(&tree.name)?;
+    out.write_all(b"\0")?;
+    match &tree.id {
+        Some(NodeId { id, entry_count }) => {
+            writeln!(out, "{} {}", entry_count, tree.children.len())?;
+            out.write_all(id.as_bytes())?;
+        }
+        None => writeln!(out, "-1 {}", tree.children.len())?,
+    }
+    for child in &tree.children {
+        write_to(child, out)?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -6,9 +6,19 @@ use git_hash::ObjectId;
 use crate::{
     entry,
     extension::{Signature, UntrackedCache},
-    util::{read_u32, split_at_byte_exclusive, split_at_pos, var_int},
+    util::{read_u32, split_at_byte_exclusive, split_at_pos, var_int, write_var_int},
 };
 
+/// Flags describing how the directory walk that produced the cache was configured, as stored in `UntrackedCache::dir_flags`.
+pub mod dir_flags {
+    /// Ignored files were listed as well.
+    pub const SHOW_IGNORED: u32 = 1 << 0;
+    /// Untracked directories are listed as a whole instead of listing the files they contain.
+    pub const SHOW_OTHER_DIRECTORIES: u32 = 1 << 1;
+    /// Untracked directories without any untracked file are not listed.
+    pub const HIDE_EMPTY_DIRECTORIES: u32 = 1 << 2;
+}
+
 pub struct OidStat {
     pub stat: entry::Stat,
     pub id: ObjectId,
@@ -23,11 +33,11 @@ pub struct Directory {
     /// indices for sub-directories similar to this one.
     pub sub_directories: Vec<usize>,
 
-    /// The directories stat data, if available or valid // TODO: or is it the exclude file?
+    /// The directories stat data, if available or valid.
     pub stat: Option<entry::Stat>,
     /// The oid of a .gitignore file, if it exists
     pub exclude_file_oid: Option<ObjectId>,
-    /// TODO: figure out what this really does
+    /// If true, the directory was only checked for containing untracked files, which is why `untracked_entries` are incomplete.
     pub check_only: bool,
 }
 
@@ -43,9 +53,19 @@ pub fn decode(data: &[u8], object_hash: git_hash::Kind) -> Option<UntrackedCache
     let (identifier, data) = split_at_pos(data, identifier_len.try_into().ok()?)?;
 
     let hash_len = object_hash.len_in_bytes();
-    let (info_exclude, data) = decode_oid_stat(data, hash_len)?;
-    let (excludes_file, data) = decode_oid_stat(data, hash_len)?;
+    let (info_exclude_stat, data) = crate::decode::stat(data)?;
+    let (excludes_file_stat, data) = crate::decode::stat(data)?;
     let (dir_flags, data) = read_u32(data)?;
+    let (info_exclude_id, data) = split_at_pos(data, hash_len)?;
+    let (excludes_file_id, data) = split_at_pos(data, hash_len)?;
+    let info_exclude = OidStat {
+        stat: info_exclude_stat,
+        id: ObjectId::from(info_exclude_id),
+    };
+    let excludes_file = OidStat {
+        stat: excludes_file_stat,
+        id: ObjectId::from(excludes_file_id),
+    };
     let (exclude_filename_per_dir, data) = split_at_byte_exclusive(data, 0)?;
 
     let (num_directory_blocks, data) = var_int(data)?;
@@ -137,14 +157,91 @@ fn decode_directory_block<'a>(data: &'a [u8], directories: &mut Vec<Directory>)
     data.into()
 }
 
-fn decode_oid_stat(data: &[u8], hash_len: usize) -> Option<(OidStat, &[u8])> {
-    let (stat, data) = crate::decode::stat(data)?;
-    let (hash, data) = split_at_pos(data, hash_len)?;
-    Some((
-        OidStat {
-            stat,
-            id: ObjectId::from(hash),
-        },
-        data,
-    ))
+/// Write `cache` into `out`, without the extension header.
+pub(crate) fn write_to(
+    cache: &UntrackedCache,
+    mut out: impl std::io::Write,
+    object_hash: git_hash::Kind,
+) -> std::io::Result<()> {
+    write_var_int(cache.identifier.len() as u64, &mut out)?;
+    out.write_all(&cache.identifier)?;
+    // like git, write both stats before the flags, followed by both ids
+    let oid_stats = [&cache.info_exclude, &cache.excludes_file];
+    for oid_stat in &oid_stats {
+        match oid_stat {
+            Some(OidStat { stat, .. }) => write_stat(stat, &mut out)?,
+            None => write_stat(&entry::Stat::default(), &mut out)?,
+        }
+    }
+    out.write_all(&cache.dir_flags.to_be_bytes())?;
+    for oid_stat in &oid_stats {
+        match oid_stat {
+            Some(OidStat { id, .. }) => out.write_all(id.as_slice())?,
+            None => out.write_all(object_hash.null_ref().as_bytes())?,
+        }
+    }
+    out.write_all(&cache.exclude_filename_per_dir)?;
+    out.write_all(b"\0")?;
+
+    let directories = &cache.directories;
+    write_var_int(directories.len() as u64, &mut out)?;
+    if !directories.is_empty() {
+        write_directory_block(directories, 0, &mut out)?;
+
+        let set_bits = |f: fn(&Directory) -> bool| {
+            git_bitmap::ewah::Vec::from_set_bits(
+                directories.len(),
+                directories.iter().enumerate().filter(|(_, d)| f(d)).map(|(idx, _)| idx),
+            )
+        };
+        set_bits(|d| d.stat.is_some()).write_to(&mut out)?;
+        set_bits(|d| d.check_only).write_to(&mut out)?;
+        set_bits(|d| d.exclude_file_oid.is_some()).write_to(&mut out)?;
+
+        for stat in directories.iter().filter_map(|d| d.stat.as_ref()) {
+            write_stat(stat, &mut out)?;
+        }
+        for id in directories.iter().filter_map(|d| d.exclude_file_oid.as_ref()) {
+            out.write_all(id.as_slice())?;
+        }
+    }
+    out.write_all(b"\0")
+}
+
+/// Directories are written depth-first, which is also the order in which they are expected in `directories`.
+fn write_directory_block(
+    directories: &[Directory],
+    index: usize,
+    out: &mut impl std::io::Write,
+) -> std::io::Result<()> {
+    let dir = &directories[index];
+    write_var_int(dir.untracked_entries.len() as u64, &mut *out)?;
+    write_var_int(dir.sub_directories.len() as u64, &mut *out)?;
+    out.write_all(&dir.name)?;
+    out.write_all(b"\0")?;
+    for name in &dir.untracked_entries {
+        out.write_all(name)?;
+        out.write_all(b"\0")?;
+    }
+    for subdir_index in &dir.sub_directories {
+        write_directory_block(directories, *subdir_index, out)?;
+    }
+    Ok(())
+}
+
+fn write_stat(stat: &entry::Stat, mut out: impl std::io::Write) -> std::io::Result<()> {
+    for field in &[
+        stat.ctime.secs,
+        stat.ctime.nsecs,
+        stat.mtime.secs,
+        stat.mtime.nsecs,
+        stat.dev,
+        stat.ino,
+        stat.uid,
+        stat.gid,
+        stat.size,
+    ] {
+        out.write_all(&field.to_be_bytes())?;
+    }
+    Ok(())
 }
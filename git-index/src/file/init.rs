@@ -0,0 +1,62 @@
+use std::path::PathBuf;
+
+use memmap2::Mmap;
+
+use crate::{decode, File, State};
+
+mod error {
+    use quick_error::quick_error;
+
+    quick_error! {
+        #[derive(Debug)]
+        pub enum Error {
+            Io(err: std::io::Error) {
+                display("An IO error occurred while opening the index")
+                source(err)
+                from()
+            }
+            Decode(err: crate::decode::Error) {
+                display("The file could not be decoded")
+                source(err)
+                from()
+            }
+            SharedIndexChecksumMismatch { expected: git_hash::ObjectId, actual: git_hash::ObjectId } {
+                display("The shared index was expected to have checksum {}, but it was {}", expected, actual)
+            }
+            SplitIndexCorrupt(message: &'static str) {
+                display("{}", message)
+            }
+        }
+    }
+}
+pub use error::Error;
+
+impl File {
+    /// Open an index file at `path` with `options`.
+    ///
+    /// If it is a split index, the shared index it links to is loaded from the same directory and merged with it,
+    /// so that the resulting state contains all entries. Use [`State::from_bytes()`] to obtain the state as stored in
+    /// the split index instead.
+    pub fn at(path: impl Into<PathBuf>, options: decode::Options) -> Result<Self, Error> {
+        let path = path.into();
+        let (data, mtime) = {
+            // SAFETY: we have to take the risk of somebody changing the file underneath. Git never writes into the same file.
+            let file = std::fs::File::open(&path)?;
+            #[allow(unsafe_code)]
+            let data = unsafe { Mmap::map(&file)? };
+            (data, filetime::FileTime::from_last_modification_time(&file.metadata()?))
+        };
+
+        let (state, checksum) = State::from_bytes(&data, mtime, options.clone())?;
+        let mut file = File {
+            state,
+            path,
+            checksum,
+            shared_index: None,
+        };
+        if let Some(link) = file.state.link.take() {
+            link.dissolve_into(&mut file, options)?;
+        }
+        Ok(file)
+    }
+}
@@ -0,0 +1,34 @@
+mod impls {
+    use std::ops::{Deref, DerefMut};
+
+    use crate::{File, State};
+
+    impl Deref for File {
+        type Target = State;
+
+        fn deref(&self) -> &Self::Target {
+            &self.state
+        }
+    }
+
+    impl DerefMut for File {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            &mut self.state
+        }
+    }
+}
+
+mod access {
+    use crate::File;
+
+    impl File {
+        /// Return the shared index this file was merged with if it was a split index, as it was last read or written.
+        pub fn shared_index(&self) -> Option<&File> {
+            self.shared_index.as_deref()
+        }
+    }
+}
+
+pub mod init;
+
+pub mod write;
@@ -0,0 +1,234 @@
+use std::path::Path;
+
+use git_tempfile::{AutoRemove, ContainingDirectory};
+
+use crate::{
+    extension::{link, Link},
+    write, Entry, File, State,
+};
+
+mod error {
+    use quick_error::quick_error;
+
+    quick_error! {
+        #[derive(Debug)]
+        pub enum Error {
+            Io(err: std::io::Error) {
+                display("An IO error occurred while writing the index")
+                source(err)
+                from()
+            }
+            AcquireLock(err: git_lock::acquire::Error) {
+                display("Could not obtain the lock to write the index")
+                source(err)
+                from()
+            }
+        }
+    }
+}
+pub use error::Error;
+
+impl File {
+    /// Write our state back to our [path][File::path] under a lock, using the given `options`, and update our checksum.
+    ///
+    /// If [`options.split_index`][write::Options::split_index] is set, only the changes compared to our
+    /// [shared index][File::shared_index()] are written, or a new shared index is written if there is none yet or if there
+    /// were too many changes.
+    pub fn write(&mut self, options: write::Options) -> Result<(), Error> {
+        match options.split_index {
+            Some(split_index) => {
+                let changes = self
+                    .shared_index
+                    .as_deref()
+                    .map(|shared| Changes::compute(&shared.state, &self.state))
+                    .filter(|changes| changes.is_within(split_index.max_percent_change));
+                let changes = match changes {
+                    Some(changes) => changes,
+                    None => {
+                        self.write_shared_index(options)?;
+                        Changes::default()
+                    }
+                };
+                self.write_split_index(changes, options)
+            }
+            None => {
+                self.write_locked(options)?;
+                self.shared_index = None;
+                Ok(())
+            }
+        }
+    }
+
+    fn write_locked(&mut self, options: write::Options) -> Result<(), Error> {
+        let mut lock =
+            git_lock::File::acquire_to_update_resource(&self.path, git_lock::acquire::Fail::Immediately, None)?;
+        let (_version, checksum) = self.state.write_to(&mut lock, options)?;
+        let (_path, file) = lock.commit().map_err(|err| err.error)?;
+        if let Some(file) = file {
+            self.state.timestamp = filetime::FileTime::from_last_modification_time(&file.metadata()?);
+        }
+        self.checksum = checksum;
+        Ok(())
+    }
+
+    /// Write all of our entries into a new shared index next to our path, and remember it as our base.
+    fn write_shared_index(&mut self, options: write::Options) -> Result<(), Error> {
+        let dir = self.path.parent().unwrap_or_else(|| Path::new("."));
+        let shared_state = State {
+            timestamp: self.state.timestamp,
+            version: self.state.version,
+            entries: self.state.entries.clone(),
+            path_backing: self.state.path_backing.clone(),
+            is_sparse: self.state.is_sparse,
+            tree: None,
+            link: None,
+            resolve_undo: None,
+            untracked: None,
+            fs_monitor: None,
+        };
+        let mut tempfile = git_tempfile::new(dir, ContainingDirectory::Exists, AutoRemove::Tempfile)?;
+        let (version, checksum) = shared_state.write_to(
+            &mut tempfile,
+            write::Options {
+                tree_cache: false,
+                ..options
+            },
+        )?;
+        let path = dir.join(format!("sharedindex.{}", checksum));
+        let file = tempfile.persist(&path).map_err(|err| err.error)?;
+        #[cfg(unix)]
+        if let Some(file) = file {
+            use std::os::unix::fs::PermissionsExt;
+            // tempfiles are only readable by us, but the shared index should be as accessible as the index itself.
+            file.set_permissions(std::fs::Permissions::from_mode(0o644))?;
+        }
+        #[cfg(not(unix))]
+        drop(file);
+
+        self.shared_index = Some(Box::new(File {
+            state: State {
+                version,
+                ..shared_state
+            },
+            path,
+            checksum,
+            shared_index: None,
+        }));
+        Ok(())
+    }
+
+    /// Write only the `changes` compared to our shared index along with a link to it.
+    fn write_split_index(&mut self, changes: Changes, options: write::Options) -> Result<(), Error> {
+        let shared = self
+            .shared_index
+            .as_deref()
+            .expect("shared index was read or written before");
+        let num_shared_entries = shared.state.entries.len();
+
+        let mut split_entries = Vec::with_capacity(changes.replaced.len() + changes.added.len());
+        let mut split_path_backing = Vec::new();
+        for idx in changes.replaced.iter().map(|(_shared_idx, idx)| *idx) {
+            split_entries.push(Entry {
+                path: 0..0,
+                ..self.state.entries[idx].clone()
+            });
+        }
+        for idx in changes.added {
+            let entry = &self.state.entries[idx];
+            let start = split_path_backing.len();
+            split_path_backing.extend_from_slice(entry.path(&self.state));
+            split_entries.push(Entry {
+                path: start..split_path_backing.len(),
+                ..entry.clone()
+            });
+        }
+        let link = Link {
+            shared_index_checksum: shared.checksum,
+            bitmaps: Some(link::Bitmaps {
+                delete: git_bitmap::ewah::Vec::from_set_bits(num_shared_entries, changes.deleted),
+                replace: git_bitmap::ewah::Vec::from_set_bits(
+                    num_shared_entries,
+                    changes.replaced.into_iter().map(|(shared_idx, _idx)| shared_idx),
+                ),
+            }),
+        };
+
+        let entries = std::mem::replace(&mut self.state.entries, split_entries);
+        let path_backing = std::mem::replace(&mut self.state.path_backing, split_path_backing);
+        self.state.link = Some(link);
+        let res = self.write_locked(options);
+        self.state.entries = entries;
+        self.state.path_backing = path_backing;
+        self.state.link = None;
+        res
+    }
+}
+
+/// The changes of a state compared to the shared index it is based on.
+#[derive(Default)]
+struct Changes {
+    num_shared_entries: usize,
+    /// Pairs of indices into the shared index and the current state.
+    replaced: Vec<(usize, usize)>,
+    /// Indices into the shared index.
+    deleted: Vec<usize>,
+    /// Indices into the current state.
+    added: Vec<usize>,
+}
+
+impl Changes {
+    fn compute(shared: &State, current: &State) -> Self {
+        let mut changes = Changes {
+            num_shared_entries: shared.entries.len(),
+            ..Default::default()
+        };
+        let (mut shared_idx, mut idx) = (0, 0);
+        loop {
+            match (shared.entries.get(shared_idx), current.entries.get(idx)) {
+                (Some(shared_entry), Some(entry)) => {
+                    use std::cmp::Ordering::*;
+                    match shared_entry
+                        .path(shared)
+                        .cmp(entry.path(current))
+                        .then_with(|| shared_entry.stage().cmp(&entry.stage()))
+                    {
+                        Less => {
+                            changes.deleted.push(shared_idx);
+                            shared_idx += 1;
+                        }
+                        Greater => {
+                            changes.added.push(idx);
+                            idx += 1;
+                        }
+                        Equal => {
+                            if !is_same_at_rest(shared_entry, entry) {
+                                changes.replaced.push((shared_idx, idx));
+                            }
+                            shared_idx += 1;
+                            idx += 1;
+                        }
+                    }
+                }
+                (Some(_), None) => {
+                    changes.deleted.push(shared_idx);
+                    shared_idx += 1;
+                }
+                (None, Some(_)) => {
+                    changes.added.push(idx);
+                    idx += 1;
+                }
+                (None, None) => break,
+            }
+        }
+        changes
+    }
+
+    fn is_within(&self, max_percent_change: u32) -> bool {
+        let num_changes = self.replaced.len() + self.deleted.len() + self.added.len();
+        num_changes * 100 <= self.num_shared_entries * max_percent_change as usize
+    }
+}
+
+fn is_same_at_rest(a: &Entry, b: &Entry) -> bool {
+    a.stat == b.stat && a.id == b.id && a.mode == b.mode && a.flags.to_storage() == b.flags.to_storage()
+}
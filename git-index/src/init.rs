@@ -0,0 +1,59 @@
+use bstr::ByteSlice;
+use git_hash::oid;
+use git_object::{tree::EntryMode, TreeRefIter};
+use git_traverse::tree::{breadthfirst, Recorder};
+
+use crate::{entry, Entry, State, Version};
+
+impl State {
+    /// Create an index state with one entry for each non-tree entry in the tree with `id`, recursively, like `git read-tree`.
+    ///
+    /// `find` is used to look up trees by id, writing them into the given buffer. The stat information of all entries
+    /// is left empty, which makes them appear as modified until they were checked out or refreshed.
+    pub fn from_tree<Find>(id: &oid, mut find: Find) -> Result<Self, breadthfirst::Error>
+    where
+        Find: for<'a> FnMut(&oid, &'a mut Vec<u8>) -> Option<TreeRefIter<'a>>,
+    {
+        let mut buf = Vec::new();
+        let root = find(id, &mut buf).ok_or_else(|| breadthfirst::Error::NotFound { oid: id.to_owned() })?;
+        let mut recorder = Recorder::default();
+        git_traverse::tree::breadthfirst(root, breadthfirst::State::default(), &mut find, &mut recorder)?;
+
+        let mut tree_entries: Vec<_> = recorder
+            .records
+            .into_iter()
+            .filter(|entry| entry.mode != EntryMode::Tree)
+            .collect();
+        tree_entries.sort_by(|a, b| a.filepath.cmp(&b.filepath));
+
+        let mut path_backing = Vec::with_capacity(tree_entries.iter().map(|e| e.filepath.len()).sum());
+        let entries = tree_entries
+            .into_iter()
+            .map(|tree_entry| {
+                let start = path_backing.len();
+                path_backing.extend_from_slice(tree_entry.filepath.as_bytes());
+                Entry {
+                    stat: entry::Stat::default(),
+                    id: tree_entry.oid,
+                    flags: entry::Flags::empty(),
+                    mode: entry::Mode::from_bits(tree_entry.mode as u32)
+                        .expect("tree entry modes are valid index modes"),
+                    path: start..path_backing.len(),
+                }
+            })
+            .collect();
+
+        Ok(State {
+            timestamp: filetime::FileTime::now(),
+            version: Version::V2,
+            entries,
+            path_backing,
+            is_sparse: false,
+            tree: None,
+            link: None,
+            resolve_undo: None,
+            untracked: None,
+            fs_monitor: None,
+        })
+    }
+}
@@ -7,12 +7,15 @@ use filetime::FileTime;
 
 pub mod file;
 
-pub(crate) mod extension;
+pub mod extension;
 
 pub mod entry;
 
 mod access {
-    use crate::{Entry, State, Version};
+    use bstr::{BStr, ByteSlice};
+    use git_pathspec::{AttributeState, Search};
+
+    use crate::{entry, extension, Entry, State, Version};
 
     impl State {
         pub fn version(&self) -> Version {
@@ -22,11 +25,67 @@ mod access {
         pub fn entries(&self) -> &[Entry] {
             &self.entries
         }
+
+        pub fn entries_mut(&mut self) -> &mut [Entry] {
+            &mut self.entries
+        }
+
+        /// Return all entries whose path is included by `search`, with `attributes(path, name)` returning the state of the
+        /// attribute `name` for `path`, which is only called for pathspecs with `:(attr:…)` magic.
+        ///
+        /// Only entries within the [common prefix][Search::common_prefix()] of all pathspecs are looked at. Directories
+        /// of a sparse index are returned if they may contain included paths.
+        pub fn entries_matching<'a>(
+            &'a self,
+            search: &'a Search,
+            mut attributes: impl FnMut(&BStr, &BStr) -> AttributeState + 'a,
+        ) -> impl Iterator<Item = &'a Entry> + 'a {
+            let prefix = search.common_prefix();
+            let start = self.entries.partition_point(|entry| entry.path(self) < prefix);
+            self.entries[start..]
+                .iter()
+                .take_while(move |entry| entry.path(self).starts_with(prefix))
+                .filter(move |entry| {
+                    let path = entry.path(self);
+                    if entry.mode.is_sparse() {
+                        let dir = path.strip_suffix(b"/").unwrap_or(path).as_bstr();
+                        search.can_match_relative_path(dir)
+                    } else {
+                        let is_dir = entry.mode == entry::Mode::COMMIT;
+                        search
+                            .pattern_matching_relative_path(path, is_dir, &mut attributes)
+                            .is_some()
+                    }
+                })
+        }
+
+        /// The time at which the state was created or last written, which is needed to detect racily-clean entries.
+        pub fn timestamp(&self) -> filetime::FileTime {
+            self.timestamp
+        }
+
+        /// The cached tree ids of directories, as stored in the `TREE` extension.
+        pub fn tree(&self) -> Option<&extension::Tree> {
+            self.tree.as_ref()
+        }
+
+        pub fn untracked(&self) -> Option<&extension::UntrackedCache> {
+            self.untracked.as_ref()
+        }
+
+        /// Set the untracked cache to `cache`, returning the previous one, so that it is written along with the index.
+        pub fn set_untracked(&mut self, cache: Option<extension::UntrackedCache>) -> Option<extension::UntrackedCache> {
+            std::mem::replace(&mut self.untracked, cache)
+        }
     }
 }
 
 pub mod decode;
 
+mod init;
+
+pub mod write;
+
 /// All known versions of a git index file.
 #[derive(PartialEq, Eq, Debug, Hash, Ord, PartialOrd, Clone, Copy)]
 #[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
@@ -40,6 +99,7 @@ pub enum Version {
 }
 
 /// An entry in the index, identifying a non-tree item on disk.
+#[derive(PartialEq, Eq, Debug, Clone)]
 pub struct Entry {
     pub stat: entry::Stat,
     pub id: git_hash::ObjectId,
@@ -54,6 +114,8 @@ pub struct File {
     pub path: PathBuf,
     /// The checksum of all bytes prior to the checksum itself.
     pub checksum: git_hash::ObjectId,
+    /// The shared index our state is based on if it was read from or written as split index.
+    shared_index: Option<Box<File>>,
 }
 
 /// An in-memory cache of a fully parsed git index file.
@@ -91,6 +153,21 @@ pub(crate) mod util {
         (num, data).into()
     }
 
+    /// Write `num` as variable int in the format understood by [`var_int()`].
+    pub fn write_var_int(mut num: u64, mut out: impl std::io::Write) -> std::io::Result<()> {
+        let mut buf = [0u8; 16];
+        let mut pos = buf.len() - 1;
+        buf[pos] = (num & 0x7f) as u8;
+        num >>= 7;
+        while num != 0 {
+            num -= 1;
+            pos -= 1;
+            buf[pos] = 0x80 | (num & 0x7f) as u8;
+            num >>= 7;
+        }
+        out.write_all(&buf[pos..])
+    }
+
     #[inline]
     pub fn read_u32(data: &[u8]) -> Option<(u32, &[u8])> {
         split_at_pos(data, 4).map(|(num, data)| (u32::from_be_bytes(num.try_into().unwrap()), data))
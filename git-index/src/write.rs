@@ -0,0 +1,215 @@
+use std::{convert::TryInto, io::Write};
+
+use crate::{entry, extension, State, Version};
+
+/// Configure how a split index should be written when writing an index [`File`][crate::File].
+///
+/// Typically derived from `core.splitIndex` and `splitIndex.maxPercentChange`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SplitIndex {
+    /// If the amount of changed entries compared to the shared index exceeds this percentage of its entries, a new shared
+    /// index will be written instead of adding more changes to the split index. Defaults to 20, just like git.
+    pub max_percent_change: u32,
+}
+
+impl Default for SplitIndex {
+    fn default() -> Self {
+        SplitIndex { max_percent_change: 20 }
+    }
+}
+
+/// The options for use when [writing an index][State::write_to()].
+#[derive(Debug, Clone, Copy)]
+pub struct Options {
+    /// The kind of hash to use for the trailing checksum, which must match the kind of the entries ids.
+    pub object_hash: git_hash::Kind,
+    /// If true, write the `TREE` extension if it is present.
+    pub tree_cache: bool,
+    /// If true, write the `EOIE` extension which allows readers to load extensions in parallel to the entries.
+    pub end_of_index_entry: bool,
+    /// If set, [writing a file][crate::File::write()] will produce a split index along with a shared index to speed up
+    /// writing large indices. It is ignored when [writing only the state][State::write_to()].
+    pub split_index: Option<SplitIndex>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            object_hash: Default::default(),
+            tree_cache: true,
+            end_of_index_entry: true,
+            split_index: None,
+        }
+    }
+}
+
+impl State {
+    /// Serialize this instance to `out` with [`options`][Options], returning the version that was actually written along
+    /// with the checksum trailing the file.
+    ///
+    /// Note that `V4` isn't supported yet, and indices of this version will be written as `V2` or `V3` instead.
+    /// Extensions other than `TREE`, `link`, `REUC`, `UNTR`, `FSMN`, `sdir` and `EOIE` are not written.
+    pub fn write_to(
+        &self,
+        out: impl std::io::Write,
+        Options {
+            object_hash,
+            tree_cache,
+            end_of_index_entry,
+            split_index: _,
+        }: Options,
+    ) -> std::io::Result<(Version, git_hash::ObjectId)> {
+        let version = self.detect_required_version();
+        let mut out = git_features::hash::Write::new(CountBytes { count: 0, inner: out }, object_hash);
+
+        let num_entries: u32 = self.entries.len().try_into().expect("no more than u32::MAX entries");
+        out.write_all(b"DIRC")?;
+        out.write_all(&(version as u32).to_be_bytes())?;
+        out.write_all(&num_entries.to_be_bytes())?;
+        self.write_entries(&mut out)?;
+
+        let offset_to_extensions: u32 = out.inner.count.try_into().expect("index is smaller than 4GB");
+        let mut extension_headers = Vec::new();
+        if let Some(tree) = self.tree.as_ref().filter(|_| tree_cache) {
+            let mut buf = Vec::new();
+            extension::tree::write_to(tree, &mut buf)?;
+            write_extension(&mut out, extension::tree::SIGNATURE, &buf, &mut extension_headers)?;
+        }
+        if let Some(link) = &self.link {
+            let mut buf = Vec::new();
+            extension::link::write_to(link, &mut buf)?;
+            write_extension(&mut out, extension::link::SIGNATURE, &buf, &mut extension_headers)?;
+        }
+        if let Some(paths) = &self.resolve_undo {
+            let mut buf = Vec::new();
+            extension::resolve_undo::write_to(paths, &mut buf)?;
+            write_extension(
+                &mut out,
+                extension::resolve_undo::SIGNATURE,
+                &buf,
+                &mut extension_headers,
+            )?;
+        }
+        if let Some(cache) = &self.untracked {
+            let mut buf = Vec::new();
+            extension::untracked_cache::write_to(cache, &mut buf, object_hash)?;
+            write_extension(
+                &mut out,
+                extension::untracked_cache::SIGNATURE,
+                &buf,
+                &mut extension_headers,
+            )?;
+        }
+        if let Some(fs_monitor) = &self.fs_monitor {
+            let mut buf = Vec::new();
+            extension::fs_monitor::write_to(fs_monitor, &mut buf)?;
+            write_extension(&mut out, extension::fs_monitor::SIGNATURE, &buf, &mut extension_headers)?;
+        }
+        if self.is_sparse {
+            write_extension(&mut out, extension::sparse::SIGNATURE, &[], &mut extension_headers)?;
+        }
+        if end_of_index_entry && !extension_headers.is_empty() {
+            let mut hasher = git_features::hash::hasher(object_hash);
+            hasher.update(&extension_headers);
+            let mut buf = Vec::with_capacity(extension::end_of_index_entry::size(object_hash));
+            buf.extend_from_slice(&offset_to_extensions.to_be_bytes());
+            buf.extend_from_slice(&hasher.digest());
+            write_extension(
+                &mut out,
+                extension::end_of_index_entry::SIGNATURE,
+                &buf,
+                &mut Vec::new(),
+            )?;
+        }
+
+        let checksum = git_hash::ObjectId::from(out.hash.digest());
+        out.inner.inner.write_all(checksum.as_slice())?;
+        Ok((version, checksum))
+    }
+
+    fn detect_required_version(&self) -> Version {
+        let needs_extended_flags = self.entries.iter().any(|e| {
+            e.flags
+                .intersects(entry::Flags::INTENT_TO_ADD | entry::Flags::SKIP_WORKTREE)
+        });
+        if needs_extended_flags {
+            Version::V3
+        } else {
+            Version::V2
+        }
+    }
+
+    fn write_entries(&self, mut out: impl std::io::Write) -> std::io::Result<()> {
+        for entry in &self.entries {
+            let path = entry.path(self);
+            let stat = &entry.stat;
+            for field in &[
+                stat.ctime.secs,
+                stat.ctime.nsecs,
+                stat.mtime.secs,
+                stat.mtime.nsecs,
+                stat.dev,
+                stat.ino,
+                entry.mode.bits(),
+                stat.uid,
+                stat.gid,
+                stat.size,
+            ] {
+                out.write_all(&field.to_be_bytes())?;
+            }
+            out.write_all(entry.id.as_slice())?;
+
+            let (flags, extended_flags) = entry.flags.to_storage();
+            let path_len = path.len().min(entry::at_rest::Flags::PATH_LEN.bits() as usize) as u16;
+            out.write_all(&(flags.bits() | path_len).to_be_bytes())?;
+            let mut size = 10 * 4 + entry.id.as_slice().len() + 2;
+            if let Some(extended_flags) = extended_flags {
+                out.write_all(&extended_flags.bits().to_be_bytes())?;
+                size += 2;
+            }
+            out.write_all(path)?;
+            size += path.len();
+
+            const PADDING: [u8; 8] = [0; 8];
+            let padded_size = (size + 8) & !7;
+            out.write_all(&PADDING[..padded_size - size])?;
+        }
+        Ok(())
+    }
+}
+
+fn write_extension(
+    mut out: impl std::io::Write,
+    signature: extension::Signature,
+    data: &[u8],
+    headers: &mut Vec<u8>,
+) -> std::io::Result<()> {
+    let size: u32 = data.len().try_into().expect("extension is smaller than 4GB");
+    let size = size.to_be_bytes();
+    out.write_all(&signature)?;
+    out.write_all(&size)?;
+    out.write_all(data)?;
+    headers.extend_from_slice(&signature);
+    headers.extend_from_slice(&size);
+    Ok(())
+}
+
+struct CountBytes<T> {
+    count: usize,
+    inner: T,
+}
+
+impl<T> std::io::Write for CountBytes<T>
+where
+    T: std::io::Write,
+{
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,16 @@
+#!/bin/bash
+set -eu -o pipefail
+
+export GIT_INDEX_VERSION=2
+git init -q
+git config commit.gpgsign false
+git config core.untrackedCache true
+
+touch tracked
+git add tracked
+git commit -q -m "tracked"
+
+mkdir -p dir/sub
+touch untracked dir/sub/file
+echo "*.o" > .git/info/exclude
+git status --porcelain >/dev/null
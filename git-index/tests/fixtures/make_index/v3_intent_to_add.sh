@@ -0,0 +1,12 @@
+#!/bin/bash
+set -eu -o pipefail
+
+git init -q
+git config commit.gpgsign false
+git config index.threads 1
+
+touch a b
+git add a
+git add --intent-to-add b
+touch c
+git add c
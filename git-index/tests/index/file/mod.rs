@@ -52,6 +52,31 @@ mod init {
         }
     }
 
+    #[test]
+    fn entries_matching_pathspecs() {
+        let file = file("v2_more_files");
+        let paths = |specs: &[&str]| {
+            let search = git_pathspec::Search::from_patterns(
+                specs
+                    .iter()
+                    .map(|spec| git_pathspec::parse(spec.as_bytes(), Default::default()).unwrap()),
+                "".into(),
+            )
+            .unwrap();
+            file.entries_matching(&search, |_, _| git_pathspec::AttributeState::Unspecified)
+                .map(|entry| entry.path(&file).to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            paths(&[]),
+            vec!["a", "b", "c", "d/a", "d/b", "d/c"],
+            "no pathspec includes everything"
+        );
+        assert_eq!(paths(&["d/", ":!d/b"]), vec!["d/a", "d/c"]);
+        assert_eq!(paths(&["*a"]), vec!["a", "d/a"]);
+        assert_eq!(paths(&[":!d"]), vec!["a", "b", "c"]);
+    }
+
     fn find_shared_index_for(index: impl AsRef<Path>) -> PathBuf {
         let mut matches = std::fs::read_dir(index.as_ref().parent().unwrap())
             .unwrap()
@@ -76,6 +101,39 @@ mod init {
     fn read_v2_split_index() {
         let file = file("v2_split_index");
         assert_eq!(file.version(), Version::V2);
+
+        assert_eq!(file.entries().len(), 1);
+        assert_eq!(file.entries()[0].path(&file), "a");
+        assert!(
+            file.shared_index().is_some(),
+            "the shared index is kept as base for writing"
+        );
+    }
+
+    #[test]
+    fn read_v2_split_index_with_replaced_deleted_and_added_entries() {
+        let file = file("v2_split_index_with_changes");
+        assert_eq!(file.version(), Version::V2);
+
+        let shared_index = file.shared_index().expect("split index");
+        assert_eq!(shared_index.entries().len(), 4);
+        assert_eq!(
+            file.entries()
+                .iter()
+                .map(|e| e.path(&file).to_string())
+                .collect::<Vec<_>>(),
+            ["a", "b", "d", "e"]
+        );
+        for (idx, path) in ["a", "d", "e"].iter().enumerate() {
+            let e = &file.entries()[if idx == 0 { 0 } else { idx + 1 }];
+            assert_eq!(e.path(&file), path);
+            assert_eq!(e.id, hex_to_id("e69de29bb2d1d6434b8b29ae775ad8c2e48c5391"));
+        }
+        assert_eq!(
+            file.entries()[1].id,
+            hex_to_id("587be6b4c3f93f93c489c0111bba5596147a26cb"),
+            "the replaced entry has the new id"
+        );
     }
 
     #[test]
@@ -84,6 +142,19 @@ mod init {
         assert_eq!(file.version(), Version::V3);
     }
 
+    #[test]
+    fn read_v3_intent_to_add_entry_without_stat_information() {
+        let file = file("v3_intent_to_add");
+        assert_eq!(file.version(), Version::V3);
+        assert_eq!(
+            file.entries()
+                .iter()
+                .map(|e| (e.path(&file).to_string(), e.flags.contains(entry::Flags::INTENT_TO_ADD)))
+                .collect::<Vec<_>>(),
+            vec![("a".into(), false), ("b".into(), true), ("c".into(), false)]
+        );
+    }
+
     #[test]
     fn read_v2_very_long_path() {
         let file = loose_file("very-long-path");
@@ -152,3 +223,188 @@ mod init {
         }
     }
 }
+
+mod write {
+    use std::{convert::TryInto, path::Path};
+
+    use git_index::{decode, write, Version};
+    use git_testtools::hex_to_id;
+
+    fn paths_and_ids(state: &git_index::State) -> Vec<(String, git_hash::ObjectId)> {
+        state
+            .entries()
+            .iter()
+            .map(|e| (e.path(state).to_string(), e.id))
+            .collect()
+    }
+
+    #[test]
+    fn roundtrip_through_state() {
+        for name in ["v2", "v2_more_files", "v4_more_files_IEOT"] {
+            let file = git_index::File::at(crate::fixture_path(name), decode::Options::default()).unwrap();
+            let mut buf = Vec::new();
+            let (version, checksum) = file.state.write_to(&mut buf, write::Options::default()).unwrap();
+            assert_eq!(version, Version::V2, "V4 is written as V2");
+
+            let (state, actual_checksum) =
+                git_index::State::from_bytes(&buf, filetime::FileTime::now(), decode::Options::default()).unwrap();
+            assert_eq!(actual_checksum, checksum);
+            assert_eq!(paths_and_ids(&state), paths_and_ids(&file));
+            for (actual, expected) in state.entries().iter().zip(file.entries()) {
+                assert_eq!(actual.stat, expected.stat);
+                assert_eq!(actual.flags, expected.flags);
+                assert_eq!(actual.mode, expected.mode);
+            }
+        }
+    }
+
+    /// Return the content of the last extension with `signature` in the serialized index `data`.
+    fn extension<'a>(data: &'a [u8], signature: &[u8; 4]) -> Option<&'a [u8]> {
+        let start = data.windows(4).rposition(|window| window == signature)? + 8;
+        let mut size = [0u8; 4];
+        size.copy_from_slice(&data[start - 4..start]);
+        data.get(start..start + u32::from_be_bytes(size) as usize)
+    }
+
+    #[test]
+    fn resolve_undo_and_fs_monitor_extensions_roundtrip() {
+        for name in &["REUC", "FSMN"] {
+            let signature: &[u8; 4] = name.as_bytes().try_into().expect("four bytes");
+            let path = git_testtools::fixture_path(Path::new("loose_index").join(name).with_extension("git-index"));
+            let expected = std::fs::read(&path).unwrap();
+            let file = git_index::File::at(&path, decode::Options::default()).unwrap();
+
+            let mut buf = Vec::new();
+            file.state.write_to(&mut buf, write::Options::default()).unwrap();
+            assert_eq!(
+                extension(&buf, signature),
+                Some(extension(&expected, signature).expect("present in fixture")),
+                "{} is written exactly like git did",
+                name
+            );
+            git_index::State::from_bytes(&buf, filetime::FileTime::now(), decode::Options::default())
+                .expect("we can read what we wrote");
+        }
+    }
+
+    #[test]
+    fn untracked_cache_written_by_git_is_decoded() {
+        use git_index::extension::untracked_cache::dir_flags;
+
+        let file = git_index::File::at(crate::fixture_path("v2_untracked_cache"), decode::Options::default()).unwrap();
+        let cache = file.untracked().expect("present");
+        assert!(cache.identifier.starts_with(b"Location "));
+        assert_eq!(
+            cache.dir_flags,
+            dir_flags::SHOW_OTHER_DIRECTORIES | dir_flags::HIDE_EMPTY_DIRECTORIES
+        );
+        assert_eq!(cache.exclude_filename_per_dir, ".gitignore");
+        assert_eq!(
+            cache.info_exclude.as_ref().map(|e| e.id),
+            Some(hex_to_id("874c63cfa699b0cb28ada8b48e0f107ee5716f85")),
+            "git hashes the content of .git/info/exclude with a newline appended"
+        );
+        let root = &cache.directories[0];
+        assert_eq!(root.name, "");
+        let mut untracked = root.untracked_entries.clone();
+        untracked.sort();
+        assert_eq!(untracked, vec!["dir/", "untracked"]);
+    }
+
+    #[test]
+    fn untracked_cache_written_by_us_is_used_by_git() {
+        let dir = git_testtools::scripted_fixture_repo_writable("make_index/v2_untracked_cache.sh").unwrap();
+        // git invalidates caches made for a different location, so let it refresh the cache in the copy first
+        assert_eq!(git_status(dir.path()).0, "?? dir/\n?? untracked\n");
+        let path = dir.path().join(".git").join("index");
+        let mut file = git_index::File::at(&path, decode::Options::default()).unwrap();
+        assert!(file.untracked().is_some());
+
+        let mut buf = Vec::new();
+        file.state.write_to(&mut buf, write::Options::default()).unwrap();
+        std::fs::write(&path, &buf).unwrap();
+        file = git_index::File::at(&path, decode::Options::default()).unwrap();
+        assert!(file.untracked().is_some(), "we can read what we wrote");
+
+        let (status, trace) = git_status(dir.path());
+        assert_eq!(status, "?? dir/\n?? untracked\n");
+        assert!(
+            trace.contains("node-creation:0"),
+            "git reuses our cache instead of building a new one: {}",
+            trace
+        );
+    }
+
+    /// Return the output of `git status` in `dir` along with its performance trace.
+    fn git_status(dir: &std::path::Path) -> (String, String) {
+        let trace_path = dir.join(".git").join("status.trace");
+        let output = std::process::Command::new("git")
+            .args(&["status", "--porcelain", "--untracked-files=normal"])
+            .current_dir(dir)
+            .env("GIT_TRACE2_PERF", &trace_path)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let trace = std::fs::read_to_string(&trace_path).unwrap();
+        std::fs::remove_file(trace_path).unwrap();
+        (String::from_utf8(output.stdout).unwrap(), trace)
+    }
+
+    #[test]
+    fn split_index_writes_shared_index_once_and_only_changes_afterwards() {
+        let dir = git_testtools::scripted_fixture_repo_writable("make_index/v2_more_files.sh").unwrap();
+        let path = dir.path().join(".git").join("index");
+        let options = write::Options {
+            split_index: Some(write::SplitIndex {
+                max_percent_change: 100,
+            }),
+            ..Default::default()
+        };
+
+        let mut file = git_index::File::at(&path, decode::Options::default()).unwrap();
+        assert!(file.shared_index().is_none());
+        file.write(options).unwrap();
+        let shared_index_checksum = file.shared_index().expect("written").checksum;
+        assert!(path
+            .with_file_name(format!("sharedindex.{}", shared_index_checksum))
+            .is_file());
+
+        let new_id = hex_to_id("587be6b4c3f93f93c489c0111bba5596147a26cb");
+        file.entries_mut()[1].id = new_id;
+        file.write(options).unwrap();
+        assert_eq!(
+            file.shared_index().expect("still present").checksum,
+            shared_index_checksum,
+            "changes are small enough to be written into the split index"
+        );
+
+        let split_only = git_index::State::from_bytes(
+            &std::fs::read(&path).unwrap(),
+            filetime::FileTime::now(),
+            Default::default(),
+        )
+        .unwrap()
+        .0;
+        assert_eq!(split_only.entries().len(), 1, "only the replaced entry is stored");
+
+        let actual = git_index::File::at(&path, decode::Options::default()).unwrap();
+        assert_eq!(actual.checksum, file.checksum);
+        assert_eq!(paths_and_ids(&actual), paths_and_ids(&file));
+        assert_eq!(actual.entries()[1].id, new_id);
+    }
+
+    #[test]
+    fn writing_without_split_index_drops_the_shared_index() {
+        let dir = git_testtools::scripted_fixture_repo_writable("make_index/v2_split_index.sh").unwrap();
+        let path = dir.path().join(".git").join("index");
+
+        let mut file = git_index::File::at(&path, decode::Options::default()).unwrap();
+        assert!(file.shared_index().is_some());
+        file.write(write::Options::default()).unwrap();
+        assert!(file.shared_index().is_none());
+
+        let actual = git_index::File::at(&path, decode::Options::default()).unwrap();
+        assert!(actual.shared_index().is_none());
+        assert_eq!(paths_and_ids(&actual), paths_and_ids(&file));
+    }
+}
@@ -30,6 +30,14 @@ impl<'a> Data<'a> {
         }
     }
 
+    /// Returns this object as blob, or `None` if this is not a blob object.
+    pub fn try_into_blob(self) -> Option<BlobRef<'a>> {
+        match self.kind {
+            crate::Kind::Blob => Some(BlobRef { data: self.data }),
+            _ => None,
+        }
+    }
+
     /// Returns this object as commit iterator to parse tokens one at a time to avoid allocations, or
     /// `None` if this is not a commit object.
     pub fn try_into_commit_iter(self) -> Option<CommitRefIter<'a>> {
@@ -118,6 +118,8 @@ pub struct Store {
     pub(crate) num_disk_state_consolidation: AtomicUsize,
     /// If true, we are allowed to use multi-pack indices and they must have the `object_hash` or be ignored.
     use_multi_pack_index: bool,
+    /// Object directories to use as alternates in addition to the ones in `info/alternates`.
+    extra_alternates: Vec<PathBuf>,
     /// The hash kind to use for some operations
     object_hash: git_hash::Kind,
 }
@@ -8,7 +8,7 @@ use crate::{
 };
 
 /// Options for use in [`Store::at_opts()`].
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub struct Options {
     /// How to obtain a size for the slot map.
     pub slots: Slots,
@@ -16,6 +16,9 @@ pub struct Options {
     pub object_hash: git_hash::Kind,
     /// If false, no multi-pack indices will be used. If true, they will be used if their hash matches `object_hash`.
     pub use_multi_pack_index: bool,
+    /// Object directories to use as read-only alternates in addition to the ones listed in `info/alternates`, and which are
+    /// searched before these, similar to what's configured with the `GIT_ALTERNATE_OBJECT_DIRECTORIES` environment variable.
+    pub extra_alternates: Vec<PathBuf>,
 }
 
 impl Default for Options {
@@ -24,6 +27,7 @@ impl Default for Options {
             slots: Default::default(),
             object_hash: Default::default(),
             use_multi_pack_index: true,
+            extra_alternates: Vec::new(),
         }
     }
 }
@@ -69,6 +73,7 @@ impl Store {
             slots,
             object_hash,
             use_multi_pack_index,
+            extra_alternates,
         }: Options,
     ) -> std::io::Result<Self> {
         let objects_dir = objects_dir.into();
@@ -81,9 +86,8 @@ impl Store {
         let slot_count = match slots {
             Slots::Given(n) => n as usize,
             Slots::AsNeededByDiskState { multiplier, minimum } => {
-                let mut db_paths = crate::alternate::resolve(&objects_dir)
+                let db_paths = super::Store::db_paths(&objects_dir, &extra_alternates)
                     .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
-                db_paths.insert(0, objects_dir.clone());
                 let num_slots = super::Store::collect_indices_and_mtime_sorted_by_size(db_paths, None, None)
                     .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?
                     .len();
@@ -103,6 +107,7 @@ impl Store {
             files: Vec::from_iter(std::iter::repeat_with(MutableIndexAndPack::default).take(slot_count)),
             index: ArcSwap::new(Arc::new(SlotMapIndex::default())),
             use_multi_pack_index,
+            extra_alternates,
             object_hash,
             num_handles_stable: Default::default(),
             num_handles_unstable: Default::default(),
@@ -175,9 +175,7 @@ impl super::Store {
         let was_uninitialized = !index.is_initialized();
         self.num_disk_state_consolidation.fetch_add(1, Ordering::Relaxed);
 
-        let db_paths: Vec<_> = std::iter::once(objects_directory.to_owned())
-            .chain(crate::alternate::resolve(objects_directory)?)
-            .collect();
+        let db_paths = Self::db_paths(objects_directory, &self.extra_alternates)?;
 
         // turn db paths into loose object databases. Reuse what's there, but only if it is in the right order.
         let loose_dbs = if was_uninitialized
@@ -389,6 +387,17 @@ impl super::Store {
         })
     }
 
+    /// Return `objects_directory` followed by `extra_alternates` and all alternates listed in `info/alternates`, in search order.
+    pub(crate) fn db_paths(
+        objects_directory: &Path,
+        extra_alternates: &[PathBuf],
+    ) -> Result<Vec<PathBuf>, crate::alternate::Error> {
+        Ok(std::iter::once(objects_directory.to_owned())
+            .chain(extra_alternates.iter().cloned())
+            .chain(crate::alternate::resolve(objects_directory)?)
+            .collect())
+    }
+
     pub(crate) fn collect_indices_and_mtime_sorted_by_size(
         db_paths: Vec<PathBuf>,
         initial_capacity: Option<usize>,
@@ -0,0 +1,74 @@
+use std::ffi::OsString;
+
+use crate::{Defaults, MagicSignature, SearchMode};
+
+mod error {
+    use quick_error::quick_error;
+
+    quick_error! {
+        /// The error returned by [`Defaults::from_environment()`][crate::Defaults::from_environment()].
+        #[derive(Debug)]
+        #[allow(missing_docs)]
+        pub enum Error {
+            InvalidValue { name: &'static str, value: String } {
+                display("The value '{}' of {} is not a boolean", value, name)
+            }
+            GlobAndNoGlob {
+                display("GIT_GLOB_PATHSPECS and GIT_NOGLOB_PATHSPECS cannot be set together")
+            }
+            LiteralWithOthers {
+                display("GIT_LITERAL_PATHSPECS cannot be set together with other GIT_*_PATHSPECS variables")
+            }
+        }
+    }
+}
+pub use error::Error;
+
+impl Defaults {
+    /// Obtain defaults from the `GIT_LITERAL_PATHSPECS`, `GIT_GLOB_PATHSPECS`, `GIT_NOGLOB_PATHSPECS` and
+    /// `GIT_ICASE_PATHSPECS` environment variables.
+    pub fn from_environment() -> Result<Self, Error> {
+        Self::from_variables(|name| std::env::var_os(name))
+    }
+
+    /// Like [`from_environment()`][Defaults::from_environment()], but obtain the value of variables with `var`.
+    pub fn from_variables(mut var: impl FnMut(&str) -> Option<OsString>) -> Result<Self, Error> {
+        let mut flag = |name: &'static str| -> Result<bool, Error> {
+            let value = match var(name) {
+                Some(value) => value.to_string_lossy().to_ascii_lowercase(),
+                None => return Ok(false),
+            };
+            match value.as_str() {
+                "1" | "true" | "yes" | "on" => Ok(true),
+                "0" | "false" | "no" | "off" | "" => Ok(false),
+                _ => Err(Error::InvalidValue { name, value }),
+            }
+        };
+        let literal = flag("GIT_LITERAL_PATHSPECS")?;
+        let glob = flag("GIT_GLOB_PATHSPECS")?;
+        let noglob = flag("GIT_NOGLOB_PATHSPECS")?;
+        let icase = flag("GIT_ICASE_PATHSPECS")?;
+
+        if glob && noglob {
+            return Err(Error::GlobAndNoGlob);
+        }
+        if literal && (glob || noglob || icase) {
+            return Err(Error::LiteralWithOthers);
+        }
+        Ok(Defaults {
+            signature: if icase {
+                MagicSignature::ICASE
+            } else {
+                MagicSignature::empty()
+            },
+            search_mode: if glob {
+                SearchMode::PathAwareGlob
+            } else if noglob {
+                SearchMode::Literal
+            } else {
+                SearchMode::ShellGlob
+            },
+            literal,
+        })
+    }
+}
@@ -0,0 +1,128 @@
+#![forbid(unsafe_code, rust_2018_idioms)]
+#![deny(missing_docs)]
+//! Parse [pathspecs][pathspec] including their magic signatures, and [search][Search] for paths matching them.
+//!
+//! Paths are always relative to the root of the repository and use `/` as separator, which makes the [`Search`] usable
+//! with index entries, during tree traversal and while diffing trees alike.
+//!
+//! [pathspec]: https://git-scm.com/docs/gitglossary#Documentation/gitglossary.txt-aiddefpathspecapathspec
+
+use bitflags::bitflags;
+use bstr::{BStr, BString, ByteSlice};
+
+///
+pub mod parse;
+pub use parse::parse;
+
+///
+pub mod normalize;
+
+///
+pub mod search;
+pub use search::Search;
+
+///
+pub mod defaults;
+
+bitflags! {
+    /// Flags to represent the magic signatures of a pathspec, like `:(top)` or `:!`.
+    pub struct MagicSignature: u32 {
+        /// Match from the root of the repository instead of the current working directory, also known as `:/`.
+        const TOP = 1 << 0;
+        /// Match case-insensitively.
+        const ICASE = 1 << 1;
+        /// Exclude paths matching the pattern, also known as `:!` or `:^`.
+        const EXCLUDE = 1 << 2;
+    }
+}
+
+/// How the path of a [`Pattern`] is matched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SearchMode {
+    /// Wildcards in the path match `/` as well, which is the default.
+    ShellGlob,
+    /// The path is matched literally, as set with `:(literal)`.
+    Literal,
+    /// Wildcards don't match `/` unless they are `**`, like in `.gitignore` files, as set with `:(glob)`.
+    PathAwareGlob,
+}
+
+impl Default for SearchMode {
+    fn default() -> Self {
+        SearchMode::ShellGlob
+    }
+}
+
+/// The state an attribute must have for a path to match, as specified with `:(attr:…)`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum AttributeState {
+    /// The attribute must be set, as in `name`.
+    Set,
+    /// The attribute must be unset, as in `-name`.
+    Unset,
+    /// The attribute must have the given value, as in `name=value`.
+    Value(BString),
+    /// The attribute must not be specified, as in `!name`.
+    Unspecified,
+}
+
+/// An attribute requirement of a [`Pattern`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Attribute {
+    /// The name of the attribute.
+    pub name: BString,
+    /// The state the attribute must have.
+    pub state: AttributeState,
+}
+
+/// Defaults affecting how pathspecs are parsed, typically obtained from environment variables with
+/// [`Defaults::from_environment()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Defaults {
+    /// The magic signature every pathspec starts out with.
+    pub signature: MagicSignature,
+    /// The search mode of pathspecs that don't set it with their magic signature.
+    pub search_mode: SearchMode,
+    /// If true, pathspecs are taken literally without parsing any magic.
+    pub literal: bool,
+}
+
+impl Default for Defaults {
+    fn default() -> Self {
+        Defaults {
+            signature: MagicSignature::empty(),
+            search_mode: SearchMode::default(),
+            literal: false,
+        }
+    }
+}
+
+/// A parsed pathspec.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Pattern {
+    /// The path of the pathspec, which is relative to the repository root once [normalized][Pattern::normalize()].
+    ///
+    /// A trailing `/` means that only directories and their contents are matched.
+    pub path: BString,
+    /// The magic signature of the pathspec.
+    pub signature: MagicSignature,
+    /// How the path is matched.
+    pub search_mode: SearchMode,
+    /// The attributes paths must have to match.
+    pub attributes: Vec<Attribute>,
+    /// The amount of bytes at the start of `path` which were prepended by [`normalize()`][Pattern::normalize()] and
+    /// are always matched literally.
+    pub prefix_len: usize,
+}
+
+impl Pattern {
+    /// Return the path of this pattern.
+    pub fn path(&self) -> &BStr {
+        self.path.as_bstr()
+    }
+
+    /// Return true if this pattern excludes the paths it matches.
+    pub fn is_excluded(&self) -> bool {
+        self.signature.contains(MagicSignature::EXCLUDE)
+    }
+}
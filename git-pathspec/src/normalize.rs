@@ -0,0 +1,62 @@
+use bstr::{BStr, BString, ByteSlice};
+
+use crate::{MagicSignature, Pattern};
+
+mod error {
+    use bstr::BString;
+    use quick_error::quick_error;
+
+    quick_error! {
+        /// The error returned by [`Pattern::normalize()`][crate::Pattern::normalize()].
+        #[derive(Debug)]
+        #[allow(missing_docs)]
+        pub enum Error {
+            OutsideOfWorktree { path: BString } {
+                display("The path '{}' is outside of the working tree", path)
+            }
+        }
+    }
+}
+pub use error::Error;
+
+impl Pattern {
+    /// Make the path of this pattern relative to the repository root, assuming it was given in the directory `prefix`
+    /// which is relative to the repository root as well, like the current working directory.
+    ///
+    /// `prefix` isn't used if the pattern has the [`TOP`][MagicSignature::TOP] signature. `.` and `..` components are
+    /// resolved, which fails if the path leaves the working tree.
+    pub fn normalize(&mut self, prefix: &BStr) -> Result<&mut Self, Error> {
+        let mut components: Vec<&[u8]> = Vec::new();
+        if !self.signature.contains(MagicSignature::TOP) {
+            components.extend(prefix.split_str("/").filter(|c| !c.is_empty() && *c != b"."));
+        }
+        let mut prefix_components = components.len();
+        let must_be_dir = self.path.ends_with(b"/");
+        for component in self.path.split_str("/") {
+            match component {
+                b"" | b"." => {}
+                b".." => {
+                    if components.pop().is_none() {
+                        return Err(Error::OutsideOfWorktree {
+                            path: self.path.clone(),
+                        });
+                    }
+                    prefix_components = prefix_components.min(components.len());
+                }
+                component => components.push(component),
+            }
+        }
+
+        let mut path = BString::from(components.join(&b"/"[..]));
+        let mut prefix_len = components[..prefix_components].iter().map(|c| c.len() + 1).sum();
+        if prefix_components == components.len() {
+            prefix_len = path.len();
+        }
+        if must_be_dir && !path.is_empty() {
+            path.push(b'/');
+        }
+        self.path = path;
+        self.prefix_len = prefix_len;
+        Ok(self)
+    }
+}
@@ -0,0 +1,199 @@
+use bstr::{BString, ByteSlice};
+
+use crate::{Attribute, AttributeState, Defaults, MagicSignature, Pattern, SearchMode};
+
+mod error {
+    use bstr::BString;
+    use quick_error::quick_error;
+
+    quick_error! {
+        /// The error returned by [`parse()`][crate::parse()].
+        #[derive(Debug)]
+        #[allow(missing_docs)]
+        pub enum Error {
+            EmptyString {
+                display("An empty string is not a valid pathspec")
+            }
+            Unimplemented { short_keyword: char } {
+                display("Short magic '{}' is not implemented", short_keyword)
+            }
+            InvalidKeyword { keyword: BString } {
+                display("Magic keyword '{}' is not implemented", keyword)
+            }
+            MissingClosingParenthesis { pathspec: BString } {
+                display("Missing ')' at the end of the magic signature of '{}'", pathspec)
+            }
+            IncompatibleSearchModes {
+                display("'literal' and 'glob' keywords cannot be used together in the same pathspec")
+            }
+            MultipleAttributeSpecifications {
+                display("Only one 'attr:' specification is allowed in the same pathspec")
+            }
+            EmptyAttribute {
+                display("Attribute specification cannot be empty")
+            }
+            InvalidAttribute { attribute: BString } {
+                display("'{}' is not a valid attribute name", attribute)
+            }
+            InvalidAttributeValue { character: char } {
+                display("'{}' is not allowed in attribute values", character)
+            }
+            TrailingEscapeCharacter {
+                display("Escape character '\\' is not allowed as last character of an attribute value")
+            }
+        }
+    }
+}
+pub use error::Error;
+
+/// Parse `input` as a pathspec, using `defaults` for everything it doesn't specify itself.
+///
+/// The path of the returned [`Pattern`] is relative to the current working directory, see
+/// [`Pattern::normalize()`] to make it relative to the repository root.
+pub fn parse(input: &[u8], defaults: Defaults) -> Result<Pattern, Error> {
+    if input.is_empty() {
+        return Err(Error::EmptyString);
+    }
+    let mut pattern = Pattern {
+        path: BString::default(),
+        signature: defaults.signature,
+        search_mode: defaults.search_mode,
+        attributes: Vec::new(),
+        prefix_len: 0,
+    };
+    if defaults.literal {
+        pattern.path = input.into();
+        pattern.search_mode = SearchMode::Literal;
+        return Ok(pattern);
+    }
+
+    let path = match input.strip_prefix(b":") {
+        Some(rest) => match rest.strip_prefix(b"(") {
+            Some(long) => parse_long_keywords(long, input, &mut pattern)?,
+            None => parse_short_keywords(rest, &mut pattern)?,
+        },
+        None => input,
+    };
+    pattern.path = path.into();
+    Ok(pattern)
+}
+
+/// Parse magic like `:/!` in `input`, past the leading colon, returning the remaining path.
+fn parse_short_keywords<'a>(input: &'a [u8], pattern: &mut Pattern) -> Result<&'a [u8], Error> {
+    let mut pos = 0;
+    while let Some(b) = input.get(pos) {
+        match b {
+            b':' => return Ok(&input[pos + 1..]),
+            b'/' => pattern.signature |= MagicSignature::TOP,
+            b'!' | b'^' => pattern.signature |= MagicSignature::EXCLUDE,
+            b if is_magic(*b) => {
+                return Err(Error::Unimplemented {
+                    short_keyword: *b as char,
+                })
+            }
+            _ => break,
+        }
+        pos += 1;
+    }
+    Ok(&input[pos..])
+}
+
+/// Characters reserved for short magic, like git's `GIT_PATHSPEC_MAGIC`.
+fn is_magic(b: u8) -> bool {
+    b"!\"#%&',-/:;<=>@_`~".contains(&b)
+}
+
+/// Parse magic like `(top,exclude)` in `input`, past the opening parenthesis, returning the remaining path.
+fn parse_long_keywords<'a>(input: &'a [u8], full_input: &[u8], pattern: &mut Pattern) -> Result<&'a [u8], Error> {
+    let end = input.find_byte(b')').ok_or_else(|| Error::MissingClosingParenthesis {
+        pathspec: full_input.into(),
+    })?;
+    let mut search_mode = None;
+    for keyword in input[..end].split_str(",").filter(|keyword| !keyword.is_empty()) {
+        match keyword {
+            b"top" => pattern.signature |= MagicSignature::TOP,
+            b"icase" => pattern.signature |= MagicSignature::ICASE,
+            b"exclude" => pattern.signature |= MagicSignature::EXCLUDE,
+            b"literal" | b"glob" => {
+                let mode = if keyword == b"glob" {
+                    SearchMode::PathAwareGlob
+                } else {
+                    SearchMode::Literal
+                };
+                if search_mode.map_or(false, |previous| previous != mode) {
+                    return Err(Error::IncompatibleSearchModes);
+                }
+                search_mode = Some(mode);
+            }
+            _ => {
+                if let Some(attributes) = keyword.strip_prefix(b"attr:") {
+                    if !pattern.attributes.is_empty() {
+                        return Err(Error::MultipleAttributeSpecifications);
+                    }
+                    pattern.attributes = parse_attributes(attributes)?;
+                } else if keyword.starts_with(b"prefix:") {
+                    // Used internally by git to pass prefixes to sub-processes, and not useful to us.
+                } else {
+                    return Err(Error::InvalidKeyword {
+                        keyword: keyword.into(),
+                    });
+                }
+            }
+        }
+    }
+    if let Some(mode) = search_mode {
+        pattern.search_mode = mode;
+    }
+    Ok(&input[end + 1..])
+}
+
+/// Parse space separated attribute requirements like `a -b !c d=value`.
+fn parse_attributes(input: &[u8]) -> Result<Vec<Attribute>, Error> {
+    if input.is_empty() {
+        return Err(Error::EmptyAttribute);
+    }
+    input
+        .split_str(" ")
+        .filter(|spec| !spec.is_empty())
+        .map(|spec| {
+            let (name, state) = match spec {
+                [b'-', name @ ..] => (name, AttributeState::Unset),
+                [b'!', name @ ..] => (name, AttributeState::Unspecified),
+                _ => match spec.find_byte(b'=') {
+                    Some(pos) => (&spec[..pos], AttributeState::Value(unescape_value(&spec[pos + 1..])?)),
+                    None => (spec, AttributeState::Set),
+                },
+            };
+            if !is_valid_attribute_name(name) {
+                return Err(Error::InvalidAttribute { attribute: name.into() });
+            }
+            Ok(Attribute {
+                name: name.into(),
+                state,
+            })
+        })
+        .collect()
+}
+
+fn is_valid_attribute_name(name: &[u8]) -> bool {
+    !name.is_empty()
+        && name[0] != b'-'
+        && name
+            .iter()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_'))
+}
+
+fn unescape_value(value: &[u8]) -> Result<BString, Error> {
+    let mut out = BString::default();
+    let mut bytes = value.iter();
+    while let Some(mut b) = bytes.next() {
+        if *b == b'\\' {
+            b = bytes.next().ok_or(Error::TrailingEscapeCharacter)?;
+        }
+        if !(b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b',')) {
+            return Err(Error::InvalidAttributeValue { character: *b as char });
+        }
+        out.push(*b);
+    }
+    Ok(out)
+}
@@ -0,0 +1,316 @@
+use bstr::{BStr, BString, ByteSlice};
+use git_glob::{wildmatch, Case};
+
+use crate::{normalize, AttributeState, MagicSignature, Pattern, SearchMode};
+
+/// How a path was matched by a [`Pattern`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchKind {
+    /// The pattern matches everything as it is empty, or the path is inside of the directory the patterns were given in
+    /// while there are only excluding patterns.
+    Always,
+    /// The path is the same as the path of the pattern.
+    Verbatim,
+    /// The path is inside of the directory named by the pattern.
+    Prefix,
+    /// The path matched the wildcards of the pattern.
+    WildcardMatch,
+}
+
+/// The result of [`Search::pattern_matching_relative_path()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Match<'a> {
+    /// The pattern that matched, or `None` if the path was included implicitly as there are only excluding patterns.
+    pub pattern: Option<&'a Pattern>,
+    /// The index of the matching pattern in the [list of patterns][Search::patterns()].
+    pub sequence_number: usize,
+    /// How the path was matched.
+    pub kind: MatchKind,
+}
+
+/// A list of normalized [`Pattern`]s to match paths relative to the repository root with.
+///
+/// Paths are included if they match at least one pattern without the [`EXCLUDE`][MagicSignature::EXCLUDE] signature
+/// and none of the excluding ones. All paths are included if there are no patterns, and all paths in the directory
+/// the patterns were given in if there are only excluding ones, just like git does it.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Search {
+    patterns: Vec<Pattern>,
+    /// The directory the patterns were given in, to include everything in it if there are only excluding patterns.
+    prefix: BString,
+    common_prefix_len: usize,
+}
+
+impl Search {
+    /// Create a search from `patterns` after [normalizing][Pattern::normalize()] them with `prefix`, the directory
+    /// they were given in relative to the repository root.
+    pub fn from_patterns(patterns: impl IntoIterator<Item = Pattern>, prefix: &BStr) -> Result<Self, normalize::Error> {
+        let patterns = patterns
+            .into_iter()
+            .map(|mut pattern| {
+                pattern.normalize(prefix)?;
+                Ok(pattern)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let common_prefix_len = common_prefix_len(&patterns);
+        let prefix = prefix_pattern(prefix)?.path;
+        Ok(Search {
+            patterns,
+            prefix,
+            common_prefix_len,
+        })
+    }
+
+    /// Return all patterns of this search.
+    pub fn patterns(&self) -> &[Pattern] {
+        &self.patterns
+    }
+
+    /// Return the longest directory prefix, including its trailing `/`, that all paths included by this search share.
+    ///
+    /// It can be used to start traversals or index iterations at this directory.
+    pub fn common_prefix(&self) -> &BStr {
+        self.patterns.iter().find(|pattern| !pattern.is_excluded()).map_or_else(
+            || self.implicit_prefix(),
+            |pattern| pattern.path[..self.common_prefix_len].as_bstr(),
+        )
+    }
+
+    /// The directory everything is included in if there are only excluding patterns, with a trailing `/` unless empty.
+    fn implicit_prefix(&self) -> &BStr {
+        if self.patterns.is_empty() {
+            "".into()
+        } else {
+            self.prefix.as_bstr()
+        }
+    }
+
+    /// Return the match of the path `relative_path` if it is included by this search, using `is_dir` to know whether
+    /// it is a directory.
+    ///
+    /// `attributes(relative_path, name)` is called to obtain the state of attributes, but only for patterns with
+    /// `:(attr:…)` magic.
+    pub fn pattern_matching_relative_path(
+        &self,
+        relative_path: &BStr,
+        is_dir: bool,
+        mut attributes: impl FnMut(&BStr, &BStr) -> AttributeState,
+    ) -> Option<Match<'_>> {
+        let mut matches = |pattern: &Pattern| {
+            match_pattern(pattern, relative_path, is_dir).filter(|_| {
+                pattern
+                    .attributes
+                    .iter()
+                    .all(|attr| attributes(relative_path, attr.name.as_bstr()) == attr.state)
+            })
+        };
+
+        let mut has_includes = false;
+        let mut included = None;
+        for (sequence_number, pattern) in self.patterns.iter().enumerate() {
+            if pattern.is_excluded() {
+                continue;
+            }
+            has_includes = true;
+            if let Some(kind) = matches(pattern) {
+                included = Some(Match {
+                    pattern: Some(pattern),
+                    sequence_number,
+                    kind,
+                });
+                break;
+            }
+        }
+        let included = match included {
+            Some(included) => included,
+            None if has_includes => return None,
+            None => {
+                let prefix = self.implicit_prefix();
+                if !relative_path.starts_with(prefix) {
+                    return None;
+                }
+                Match {
+                    pattern: None,
+                    sequence_number: 0,
+                    kind: MatchKind::Always,
+                }
+            }
+        };
+
+        let is_excluded = self
+            .patterns
+            .iter()
+            .filter(|pattern| pattern.is_excluded())
+            .any(|pattern| matches(pattern).is_some());
+        (!is_excluded).then(|| included)
+    }
+
+    /// Return false if neither the directory `relative_path` nor anything inside of it can be included by this search,
+    /// which allows to skip it entirely during traversals.
+    pub fn can_match_relative_path(&self, relative_path: &BStr) -> bool {
+        let directory_is_excluded = self.patterns.iter().any(|pattern| {
+            pattern.is_excluded()
+                && pattern.attributes.is_empty()
+                && matches!(
+                    match_pattern(pattern, relative_path, true),
+                    Some(MatchKind::Always) | Some(MatchKind::Verbatim) | Some(MatchKind::Prefix)
+                )
+        });
+        if directory_is_excluded {
+            return false;
+        }
+
+        let mut includes = self.patterns.iter().filter(|pattern| !pattern.is_excluded()).peekable();
+        if includes.peek().is_none() {
+            if relative_path.is_empty() {
+                return true;
+            }
+            let prefix = self.implicit_prefix();
+            let common_len = prefix.len().min(relative_path.len() + 1);
+            let mut dir = BString::from(relative_path);
+            dir.push(b'/');
+            return dir[..common_len] == prefix[..common_len];
+        }
+        includes.any(|pattern| {
+            let (text, _) = strip_trailing_slash(&pattern.path);
+            if text.is_empty() {
+                return true;
+            }
+            let literal_len = literal_len(pattern, text);
+            let literal = &text[..literal_len];
+            let common_len = literal.len().min(relative_path.len());
+            if !eq(&literal[..common_len], &relative_path[..common_len], case(pattern)) {
+                return false;
+            }
+            if literal.len() > relative_path.len() {
+                // The directory leads to the pattern.
+                relative_path.is_empty() || literal[relative_path.len()] == b'/'
+            } else if literal_len == text.len() {
+                // The directory is the pattern or inside of it.
+                relative_path.len() == literal.len() || relative_path[literal.len()] == b'/'
+            } else {
+                // Wildcards may match anything within.
+                true
+            }
+        })
+    }
+}
+
+/// Return the normalized path of `prefix` with a trailing `/` unless it is empty.
+fn prefix_pattern(prefix: &BStr) -> Result<Pattern, normalize::Error> {
+    let mut pattern = Pattern {
+        path: BString::default(),
+        signature: MagicSignature::empty(),
+        search_mode: SearchMode::Literal,
+        attributes: Vec::new(),
+        prefix_len: 0,
+    };
+    pattern.normalize(prefix)?;
+    if !pattern.path.is_empty() {
+        pattern.path.push(b'/');
+    }
+    Ok(pattern)
+}
+
+fn case(pattern: &Pattern) -> Case {
+    if pattern.signature.contains(MagicSignature::ICASE) {
+        Case::Fold
+    } else {
+        Case::Sensitive
+    }
+}
+
+fn eq(a: &[u8], b: &[u8], case: Case) -> bool {
+    match case {
+        Case::Sensitive => a == b,
+        Case::Fold => a.eq_ignore_ascii_case(b),
+    }
+}
+
+fn strip_trailing_slash(path: &BString) -> (&[u8], bool) {
+    match path.strip_suffix(b"/") {
+        Some(path) => (path, true),
+        None => (path.as_slice(), false),
+    }
+}
+
+/// Return the amount of bytes at the start of `text`, the path of `pattern`, that are matched literally.
+fn literal_len(pattern: &Pattern, text: &[u8]) -> usize {
+    if pattern.search_mode == SearchMode::Literal {
+        return text.len();
+    }
+    let prefix_len = pattern.prefix_len.min(text.len());
+    prefix_len
+        + text[prefix_len..]
+            .iter()
+            .position(|b| matches!(b, b'*' | b'?' | b'[' | b'\\'))
+            .unwrap_or(text.len() - prefix_len)
+}
+
+fn common_prefix_len(patterns: &[Pattern]) -> usize {
+    let mut includes = patterns.iter().filter(|pattern| !pattern.is_excluded());
+    let first = match includes.next() {
+        Some(first) => first,
+        None => return 0,
+    };
+    let literal_of = |pattern: &Pattern| {
+        if pattern.signature.contains(MagicSignature::ICASE) {
+            return 0;
+        }
+        let (text, _) = strip_trailing_slash(&pattern.path);
+        literal_len(pattern, text)
+    };
+    let mut len = literal_of(first);
+    for pattern in includes {
+        len = len.min(literal_of(pattern)).min(
+            first.path[..len]
+                .iter()
+                .zip(pattern.path.iter())
+                .take_while(|(a, b)| a == b)
+                .count(),
+        );
+    }
+    first.path[..len].rfind_byte(b'/').map_or(0, |pos| pos + 1)
+}
+
+/// Match `path` with `pattern` without considering attributes or whether the pattern excludes.
+fn match_pattern(pattern: &Pattern, path: &BStr, is_dir: bool) -> Option<MatchKind> {
+    let (text, must_be_dir) = strip_trailing_slash(&pattern.path);
+    if text.is_empty() {
+        return Some(MatchKind::Always);
+    }
+    let case = case(pattern);
+    let literal_len = literal_len(pattern, text);
+    // Like git, patterns with wildcards are matched literally as well.
+    if path.len() >= text.len() && eq(&path[..text.len()], text, case) {
+        if path.len() == text.len() {
+            if !must_be_dir || is_dir {
+                return Some(MatchKind::Verbatim);
+            }
+        } else if path[text.len()] == b'/' {
+            return Some(MatchKind::Prefix);
+        }
+    }
+    if literal_len == text.len() {
+        return None;
+    }
+
+    if path.len() < literal_len || !eq(&path[..literal_len], &text[..literal_len], case) {
+        return None;
+    }
+    let mut mode = match case {
+        Case::Sensitive => wildmatch::Mode::empty(),
+        Case::Fold => wildmatch::Mode::IGNORE_CASE,
+    };
+    if pattern.search_mode == SearchMode::PathAwareGlob {
+        mode |= wildmatch::Mode::NO_MATCH_SLASH_LITERAL;
+    }
+    let wildcards = pattern.path[literal_len..].as_bstr();
+    let matches = wildmatch(wildcards, path[literal_len..].as_bstr(), mode)
+        || (is_dir && {
+            let mut dir = BString::from(&path[literal_len..]);
+            dir.push(b'/');
+            wildmatch(wildcards, dir.as_bstr(), mode)
+        });
+    matches.then(|| MatchKind::WildcardMatch)
+}
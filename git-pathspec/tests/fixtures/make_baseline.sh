@@ -0,0 +1,35 @@
+#!/bin/bash
+set -eu -o pipefail
+
+git init -q
+
+mkdir -p dir/sub d2 'w*ld' Upper
+touch top.c top.h dir/file dir/a.c dir/sub/file dir/sub/b.c d2/x 'w*ld/one' 'w*ld/two.c' Upper/CASE.c
+cat <<EOT > .gitattributes
+*.c lang=c
+dir/sub/** generated
+*.h -generated
+EOT
+git add -A
+
+function baseline() {
+  local prefix=${1:?first argument is the prefix}
+  local spec=${2:?second argument is the pathspec}
+  echo "$prefix	$spec"
+  (cd "./$prefix" && git ls-files --full-name -- "$spec")
+  echo
+}
+
+{
+  for spec in 'dir' 'dir/' 'dir/sub/' 'di' 'd*' 'd*/' '*.c' ':(glob)*.c' ':(glob)**/*.c' ':(glob)dir/*' ':(glob)dir/**' \
+              ':(icase)DIR' ':(icase)upper/case.C' ':(literal)w*ld' 'w*ld' ':(literal)d*' 'w\*ld/one' ':!dir' ':^*.c' \
+              ':(exclude)dir/sub' ':(attr:lang=c)' ':(attr:generated)' ':(attr:-generated)' ':(attr:!generated)' \
+              ':(attr:lang=c !generated)dir' './dir' 'dir/sub/..' ':/' '.'; do
+    baseline . "$spec"
+  done
+  for spec in '*.c' 'file' ':/top.c' ':(top)d2' '../top.c' ':!b.c' ':(glob)*' 'sub/'; do
+    baseline dir "$spec"
+  done
+} > baseline
+
+git ls-files | git check-attr --stdin -a > attributes
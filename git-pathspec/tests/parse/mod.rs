@@ -0,0 +1,163 @@
+use git_pathspec::{parse, Attribute, AttributeState, Defaults, MagicSignature, SearchMode};
+
+fn pattern(input: &str) -> git_pathspec::Pattern {
+    parse(input.as_bytes(), Defaults::default()).unwrap()
+}
+
+fn attr(name: &str, state: AttributeState) -> Attribute {
+    Attribute {
+        name: name.into(),
+        state,
+    }
+}
+
+#[test]
+fn plain_paths_have_no_magic() {
+    for input in ["a/b", "*.c", "(top)", "\\:a"] {
+        let p = pattern(input);
+        assert_eq!(p.path, input);
+        assert_eq!(p.signature, MagicSignature::empty());
+        assert_eq!(p.search_mode, SearchMode::ShellGlob);
+        assert!(p.attributes.is_empty());
+    }
+}
+
+#[test]
+fn short_magic() {
+    for (input, path, signature) in [
+        (":/a", "a", MagicSignature::TOP),
+        (":!a", "a", MagicSignature::EXCLUDE),
+        (":^a", "a", MagicSignature::EXCLUDE),
+        (":/!:a", "a", MagicSignature::TOP | MagicSignature::EXCLUDE),
+        (":a", "a", MagicSignature::empty()),
+        ("::/a", "/a", MagicSignature::empty()),
+        (":/", "", MagicSignature::TOP),
+    ] {
+        let p = pattern(input);
+        assert_eq!(p.path, path, "{}", input);
+        assert_eq!(p.signature, signature, "{}", input);
+    }
+}
+
+#[test]
+fn long_magic() {
+    let p = pattern(":(top,icase,exclude,glob)a/**");
+    assert_eq!(p.path, "a/**");
+    assert_eq!(
+        p.signature,
+        MagicSignature::TOP | MagicSignature::ICASE | MagicSignature::EXCLUDE
+    );
+    assert_eq!(p.search_mode, SearchMode::PathAwareGlob);
+
+    let p = pattern(":(literal,prefix:2)*");
+    assert_eq!(p.path, "*");
+    assert_eq!(p.search_mode, SearchMode::Literal);
+
+    let p = pattern(":(attr:a -b !c d=v\\-1)");
+    assert_eq!(p.path, "");
+    assert_eq!(
+        p.attributes,
+        vec![
+            attr("a", AttributeState::Set),
+            attr("b", AttributeState::Unset),
+            attr("c", AttributeState::Unspecified),
+            attr("d", AttributeState::Value("v-1".into())),
+        ]
+    );
+}
+
+#[test]
+fn defaults() {
+    let p = parse(
+        b":(glob)a",
+        Defaults {
+            signature: MagicSignature::ICASE,
+            search_mode: SearchMode::Literal,
+            literal: false,
+        },
+    )
+    .unwrap();
+    assert_eq!(p.signature, MagicSignature::ICASE);
+    assert_eq!(p.search_mode, SearchMode::PathAwareGlob, "magic overrides defaults");
+
+    let p = parse(
+        b":(glob)a",
+        Defaults {
+            literal: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(p.path, ":(glob)a", "magic isn't parsed at all");
+    assert_eq!(p.search_mode, SearchMode::Literal);
+
+    let var = |vars: &'static [(&'static str, &'static str)]| {
+        move |name: &str| vars.iter().find(|(n, _)| *n == name).map(|(_, v)| v.into())
+    };
+    assert_eq!(
+        Defaults::from_variables(var(&[("GIT_GLOB_PATHSPECS", "1"), ("GIT_ICASE_PATHSPECS", "true")])).unwrap(),
+        Defaults {
+            signature: MagicSignature::ICASE,
+            search_mode: SearchMode::PathAwareGlob,
+            literal: false
+        }
+    );
+    assert!(Defaults::from_variables(var(&[("GIT_GLOB_PATHSPECS", "1"), ("GIT_NOGLOB_PATHSPECS", "1")])).is_err());
+    assert!(Defaults::from_variables(var(&[("GIT_LITERAL_PATHSPECS", "1"), ("GIT_ICASE_PATHSPECS", "1")])).is_err());
+    assert!(Defaults::from_variables(var(&[("GIT_LITERAL_PATHSPECS", "maybe")])).is_err());
+}
+
+#[test]
+fn errors() {
+    use git_pathspec::parse::Error;
+    for (input, expected) in [
+        ("", "An empty string is not a valid pathspec"),
+        (":#a", "Short magic '#' is not implemented"),
+        (":(top", "Missing ')' at the end of the magic signature of ':(top'"),
+        (":(unknown)a", "Magic keyword 'unknown' is not implemented"),
+        (
+            ":(glob,literal)a",
+            "'literal' and 'glob' keywords cannot be used together in the same pathspec",
+        ),
+        (
+            ":(attr:a,attr:b)",
+            "Only one 'attr:' specification is allowed in the same pathspec",
+        ),
+        (":(attr:)", "Attribute specification cannot be empty"),
+        (":(attr:-)", "'' is not a valid attribute name"),
+        (":(attr:a/b)", "'a/b' is not a valid attribute name"),
+        (":(attr:a=b.c)", "'.' is not allowed in attribute values"),
+        (
+            ":(attr:a=b\\)",
+            "Escape character '\\' is not allowed as last character of an attribute value",
+        ),
+    ] {
+        let err: Error = parse(input.as_bytes(), Defaults::default()).unwrap_err();
+        assert_eq!(err.to_string(), expected, "{}", input);
+    }
+}
+
+#[test]
+fn normalize() {
+    for (input, prefix, expected, prefix_len) in [
+        ("a", "", "a", 0),
+        ("a", "dir", "dir/a", 4),
+        ("./a/../b/", "dir/", "dir/b/", 4),
+        ("../a", "dir/sub", "dir/a", 4),
+        ("..", "dir", "", 0),
+        (".", "dir", "dir", 3),
+        (":/a", "dir", "a", 0),
+        ("a//b", "", "a/b", 0),
+    ] {
+        let mut p = pattern(input);
+        p.normalize(prefix.into()).unwrap();
+        assert_eq!(p.path, expected, "{} in {}", input, prefix);
+        assert_eq!(p.prefix_len, prefix_len, "{} in {}", input, prefix);
+    }
+
+    let mut p = pattern("../../a");
+    assert_eq!(
+        p.normalize("dir".into()).unwrap_err().to_string(),
+        "The path '../../a' is outside of the working tree"
+    );
+}
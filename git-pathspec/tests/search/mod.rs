@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+
+use bstr::{BStr, BString, ByteSlice};
+use git_pathspec::{parse, search::MatchKind, AttributeState, Defaults, Search};
+
+struct Baseline {
+    prefix: BString,
+    spec: BString,
+    matches: Vec<BString>,
+}
+
+fn baseline(dir: &std::path::Path) -> Vec<Baseline> {
+    let baseline = std::fs::read(dir.join("baseline")).unwrap();
+    let mut out = Vec::new();
+    let mut lines = baseline.lines();
+    while let Some(header) = lines.next() {
+        let mut tokens = header.splitn_str(2, "\t");
+        let (prefix, spec) = (tokens.next().unwrap(), tokens.next().expect("prefix and spec"));
+        out.push(Baseline {
+            prefix: if prefix == b"." { "".into() } else { prefix.into() },
+            spec: spec.into(),
+            matches: lines
+                .by_ref()
+                .take_while(|line| !line.is_empty())
+                .map(Into::into)
+                .collect(),
+        });
+    }
+    out
+}
+
+fn attributes(dir: &std::path::Path) -> HashMap<(BString, BString), AttributeState> {
+    std::fs::read(dir.join("attributes"))
+        .unwrap()
+        .lines()
+        .map(|line| {
+            let mut tokens = line.splitn_str(3, ": ");
+            let path = tokens.next().unwrap();
+            let name = tokens.next().unwrap();
+            let state = match tokens.next().unwrap() {
+                b"set" => AttributeState::Set,
+                b"unset" => AttributeState::Unset,
+                b"unspecified" => AttributeState::Unspecified,
+                value => AttributeState::Value(value.into()),
+            };
+            ((path.into(), name.into()), state)
+        })
+        .collect()
+}
+
+fn search(spec: &BStr, prefix: &BStr) -> Search {
+    Search::from_patterns(Some(parse(spec, Defaults::default()).unwrap()), prefix).unwrap()
+}
+
+#[test]
+fn paths_are_matched_like_git() {
+    let dir = git_testtools::scripted_fixture_repo_read_only("make_baseline.sh").unwrap();
+    let attributes = attributes(&dir);
+    let files: Vec<BString> = baseline(&dir)
+        .into_iter()
+        .find(|b| b.spec == ":/")
+        .expect("all files")
+        .matches;
+
+    let mut count = 0;
+    for Baseline { prefix, spec, matches } in baseline(&dir) {
+        let search = search(spec.as_bstr(), prefix.as_bstr());
+        let actual: Vec<_> = files
+            .iter()
+            .filter(|path| {
+                search
+                    .pattern_matching_relative_path(path.as_bstr(), false, |path, name| {
+                        attributes
+                            .get(&(path.into(), name.into()))
+                            .cloned()
+                            .unwrap_or(AttributeState::Unspecified)
+                    })
+                    .is_some()
+            })
+            .cloned()
+            .collect();
+        assert_eq!(actual, matches, "'{}' in '{}'", spec, prefix);
+
+        for path in &matches {
+            let mut components: Vec<_> = path.split_str("/").collect();
+            components.pop();
+            for depth in 0..=components.len() {
+                let dir = BString::from(components[..depth].join(&b"/"[..]));
+                assert!(
+                    search.can_match_relative_path(dir.as_bstr()),
+                    "'{}' in '{}' must not prune '{}' leading to {}",
+                    spec,
+                    prefix,
+                    dir,
+                    path
+                );
+            }
+            assert!(
+                path.starts_with(search.common_prefix()),
+                "'{}' in '{}' has prefix '{}'",
+                spec,
+                prefix,
+                search.common_prefix()
+            );
+        }
+        count += 1;
+    }
+    assert_eq!(count, 37);
+}
+
+#[test]
+fn directories_can_be_pruned() {
+    for (spec, prefix, dir, expected) in [
+        ("dir/sub", "", "dir", true),
+        ("dir/sub", "", "dir/sub", true),
+        ("dir/sub", "", "dir/sub/deeper", true),
+        ("dir/sub", "", "dir/subway", false),
+        ("dir/sub", "", "d2", false),
+        ("dir/s*", "", "dir/sub/deeper", true),
+        ("dir/s*", "", "dir/other", false),
+        (":(icase)DIR/sub", "", "dir", true),
+        (":!dir", "", "dir", false),
+        (":!dir", "", "d2", true),
+        (":!*.c", "sub", "", true),
+        (":!*.c", "sub", "sub/deeper", true),
+        (":!*.c", "sub", "other", false),
+    ] {
+        let search = search(spec.into(), prefix.into());
+        assert_eq!(
+            search.can_match_relative_path(dir.into()),
+            expected,
+            "'{}' in '{}' with '{}'",
+            spec,
+            prefix,
+            dir
+        );
+    }
+}
+
+#[test]
+fn common_prefix_and_match_kinds() {
+    let search = Search::from_patterns(
+        ["dir/sub/a", "dir/sub/b*", ":!dir/sub/c", "dir/subway/"]
+            .iter()
+            .map(|spec| parse(spec.as_bytes(), Defaults::default()).unwrap()),
+        "".into(),
+    )
+    .unwrap();
+    assert_eq!(search.common_prefix(), "dir/");
+    assert_eq!(search.patterns().len(), 4);
+
+    let kind = |path: &str, is_dir: bool| {
+        search
+            .pattern_matching_relative_path(path.into(), is_dir, |_, _| AttributeState::Unspecified)
+            .map(|m| (m.kind, m.sequence_number))
+    };
+    assert_eq!(kind("dir/sub/a", false), Some((MatchKind::Verbatim, 0)));
+    assert_eq!(kind("dir/sub/a/file", false), Some((MatchKind::Prefix, 0)));
+    assert_eq!(kind("dir/sub/bar", false), Some((MatchKind::WildcardMatch, 1)));
+    assert_eq!(kind("dir/sub/c", false), None);
+    assert_eq!(kind("dir/subway", false), None, "trailing slashes require directories");
+    assert_eq!(kind("dir/subway", true), Some((MatchKind::Verbatim, 3)));
+
+    let search = Search::from_patterns(None, "".into()).unwrap();
+    assert_eq!(
+        search
+            .pattern_matching_relative_path("anything".into(), false, |_, _| AttributeState::Unspecified)
+            .map(|m| m.kind),
+        Some(MatchKind::Always)
+    );
+    assert_eq!(search.common_prefix(), "");
+}
@@ -26,7 +26,7 @@ use git_object::bstr::{BStr, BString};
 
 #[path = "store/mod.rs"]
 mod store_impl;
-pub use store_impl::{file, packed};
+pub use store_impl::{file, packed, reftable};
 
 mod fullname;
 ///
@@ -49,6 +49,8 @@ pub mod log;
 ///
 pub mod peel;
 
+pub mod pseudo;
+
 ///
 pub mod store {
     /// The way a file store handles the reflog
@@ -60,6 +62,21 @@ pub mod store {
         Disable,
     }
 
+    /// The format in which references are stored, as configured by `extensions.refStorage`.
+    #[derive(Debug, PartialOrd, PartialEq, Ord, Eq, Hash, Clone, Copy)]
+    pub enum Format {
+        /// References are stored as loose files and in the `packed-refs` file, with reflogs in `logs/`.
+        Files,
+        /// References and their logs are stored in a stack of tables in the `reftable/` directory.
+        Reftable,
+    }
+
+    impl Default for Format {
+        fn default() -> Self {
+            Format::Files
+        }
+    }
+
     /// A thread-local handle for interacting with a [`Store`][crate::Store] to find and iterate references.
     #[derive(Clone)]
     pub struct Handle {
@@ -70,6 +87,7 @@ pub mod store {
 
     pub(crate) enum State {
         Loose { store: file::Store },
+        Reftable { store: reftable::Stack },
     }
 
     #[path = "general/mod.rs"]
@@ -79,9 +97,10 @@ pub mod store {
     #[path = "general/handle/mod.rs"]
     mod handle;
 
-    pub use handle::find;
+    pub use general::init;
+    pub use handle::{find, iter, transaction};
 
-    use crate::file;
+    use crate::{file, reftable};
 }
 
 /// The git reference store.
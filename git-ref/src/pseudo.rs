@@ -0,0 +1,157 @@
+//! Pseudo-references are files in the git directory like `FETCH_HEAD` or `MERGE_HEAD` which aren't references
+//! but are written by operations to remember what they are operating on.
+use std::io;
+
+use git_hash::ObjectId;
+use git_object::bstr::BString;
+
+/// The kinds of pseudo-references git knows about.
+#[derive(PartialEq, Eq, Debug, Hash, Ord, PartialOrd, Clone, Copy)]
+pub enum Kind {
+    /// `FETCH_HEAD`, the tips of all branches fetched last, with one [entry][FetchHeadEntry] per line.
+    FetchHead,
+    /// `MERGE_HEAD`, the ids of all commits being merged into `HEAD`, one per line.
+    MergeHead,
+    /// `ORIG_HEAD`, the value of `HEAD` prior to a potentially dangerous operation like a merge, rebase or reset.
+    OrigHead,
+    /// `CHERRY_PICK_HEAD`, the commit being cherry-picked.
+    CherryPickHead,
+    /// `REVERT_HEAD`, the commit being reverted.
+    RevertHead,
+    /// `AUTO_MERGE`, the tree with the outcome of the automatic merge while a merge has conflicts.
+    AutoMerge,
+}
+
+impl Kind {
+    /// All kinds of pseudo-references.
+    pub const ALL: &'static [Kind] = &[
+        Kind::FetchHead,
+        Kind::MergeHead,
+        Kind::OrigHead,
+        Kind::CherryPickHead,
+        Kind::RevertHead,
+        Kind::AutoMerge,
+    ];
+
+    /// Return the name of the file in the git directory storing this pseudo-reference, which is also its name.
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            Kind::FetchHead => "FETCH_HEAD",
+            Kind::MergeHead => "MERGE_HEAD",
+            Kind::OrigHead => "ORIG_HEAD",
+            Kind::CherryPickHead => "CHERRY_PICK_HEAD",
+            Kind::RevertHead => "REVERT_HEAD",
+            Kind::AutoMerge => "AUTO_MERGE",
+        }
+    }
+}
+
+/// A line of the `FETCH_HEAD` file, like `<id>\tnot-for-merge\tbranch 'main' of https://example.com/repo`.
+#[derive(PartialEq, Eq, Debug, Hash, Ord, PartialOrd, Clone)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+pub struct FetchHeadEntry {
+    /// The id of the fetched object.
+    pub id: ObjectId,
+    /// If true, the fetched object is not supposed to be merged by `git pull`.
+    pub not_for_merge: bool,
+    /// A description of where the object was fetched from, like `branch 'main' of https://example.com/repo`.
+    pub description: BString,
+}
+
+const NOT_FOR_MERGE: &[u8] = b"not-for-merge";
+
+impl FetchHeadEntry {
+    /// Serialize this instance as a line to `out` in the format git uses.
+    pub fn write_to(&self, mut out: impl io::Write) -> io::Result<()> {
+        write!(out, "{}\t", self.id)?;
+        if self.not_for_merge {
+            out.write_all(NOT_FOR_MERGE)?;
+        }
+        out.write_all(b"\t")?;
+        out.write_all(&self.description)?;
+        out.write_all(b"\n")
+    }
+}
+
+///
+pub mod decode {
+    use git_hash::ObjectId;
+    use git_object::bstr::{BString, ByteSlice};
+    use quick_error::quick_error;
+
+    use super::{FetchHeadEntry, NOT_FOR_MERGE};
+
+    quick_error! {
+        /// The error returned by [`fetch_head()`] and [`ids()`].
+        #[derive(Debug)]
+        #[allow(missing_docs)]
+        pub enum Error {
+            Parse(line: BString) {
+                display("{:?} could not be parsed as pseudo-reference line", line)
+            }
+        }
+    }
+
+    /// Parse the contents of a `FETCH_HEAD` file, ignoring empty lines.
+    ///
+    /// Lines only consisting of an object id are accepted as well and are considered for merge without description.
+    pub fn fetch_head(data: &[u8]) -> Result<Vec<FetchHeadEntry>, Error> {
+        lines(data)
+            .map(|line| {
+                let mut tokens = line.splitn(3, |b| *b == b'\t');
+                let id = to_id(tokens.next().unwrap_or_default(), line)?;
+                let not_for_merge = match tokens.next() {
+                    None | Some(b"") => false,
+                    Some(marker) if marker == NOT_FOR_MERGE => true,
+                    Some(_) => return Err(Error::Parse(line.into())),
+                };
+                Ok(FetchHeadEntry {
+                    id,
+                    not_for_merge,
+                    description: tokens.next().unwrap_or_default().into(),
+                })
+            })
+            .collect()
+    }
+
+    /// Parse the contents of a pseudo-reference file with one object id per line, like `MERGE_HEAD` or `ORIG_HEAD`,
+    /// ignoring empty lines and everything past the object id.
+    pub fn ids(data: &[u8]) -> Result<Vec<ObjectId>, Error> {
+        lines(data)
+            .map(|line| {
+                let hex_len = line.iter().take_while(|b| b.is_ascii_hexdigit()).count();
+                to_id(&line[..hex_len], line)
+            })
+            .collect()
+    }
+
+    fn lines(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+        data.lines().filter(|line| !line.is_empty())
+    }
+
+    fn to_id(hex: &[u8], line: &[u8]) -> Result<ObjectId, Error> {
+        match crate::parse::hex_hash::<()>(hex) {
+            Ok((rest, hex)) if rest.is_empty() => ObjectId::from_hex(hex).map_err(|_| Error::Parse(line.into())),
+            _ => Err(Error::Parse(line.into())),
+        }
+    }
+}
+
+/// Serialize `entries` into the format of a `FETCH_HEAD` file.
+pub fn encode_fetch_head<'a>(entries: impl IntoIterator<Item = &'a FetchHeadEntry>) -> Vec<u8> {
+    let mut out = Vec::new();
+    for entry in entries {
+        entry.write_to(&mut out).expect("write to memory works");
+    }
+    out
+}
+
+/// Serialize `ids` into the format of a `MERGE_HEAD` file, or any other pseudo-reference with a single id.
+pub fn encode_ids(ids: impl IntoIterator<Item = ObjectId>) -> Vec<u8> {
+    use std::io::Write;
+    let mut out = Vec::new();
+    for id in ids {
+        writeln!(out, "{}", id).expect("write to memory works");
+    }
+    out
+}
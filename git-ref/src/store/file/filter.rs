@@ -0,0 +1,417 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashSet, VecDeque},
+};
+
+use git_hash::ObjectId;
+use git_object::bstr::{BStr, BString, ByteSlice};
+
+use crate::{
+    file::ReferenceExt,
+    store_impl::{file, file::loose::reflog::expire::tree_and_parents},
+    Reference,
+};
+
+/// The key to sort references by in [`Platform::filtered()`][file::iter::Platform::filtered()].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Sort {
+    /// Sort by the full name of the reference, byte by byte.
+    RefName,
+    /// Sort by the committer date of the commit the reference points to after peeling tags, with references to
+    /// other objects and dangling symbolic references coming first.
+    CommitterDate,
+    /// Sort by the full name of the reference, comparing numbers by value so that `v1.10` comes after `v1.9`.
+    Version,
+}
+
+impl Default for Sort {
+    fn default() -> Self {
+        Sort::RefName
+    }
+}
+
+/// Options to select and sort references similar to `git for-each-ref`, for use in
+/// [`Platform::filtered()`][file::iter::Platform::filtered()].
+///
+/// Patterns like `refs/heads/feature/*` are matched against the full name of references, where `*` doesn't match `/`
+/// but `**` does. Patterns without wildcards match references equal to them or within them, so `refs/tags` selects all tags.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Options {
+    /// Only select references matching at least one of these patterns, or all references if empty.
+    pub patterns: Vec<BString>,
+    /// Don't select references matching any of these patterns.
+    pub exclude: Vec<BString>,
+    /// Only select references pointing at this object, directly or through the tags they point to.
+    pub points_at: Option<ObjectId>,
+    /// Only select references to commits reachable from this commit.
+    pub merged: Option<ObjectId>,
+    /// Only select references to commits not reachable from this commit.
+    pub no_merged: Option<ObjectId>,
+    /// Only select references to commits which have this commit in their history.
+    pub contains: Option<ObjectId>,
+    /// Only select references to commits which don't have this commit in their history.
+    pub no_contains: Option<ObjectId>,
+    /// The key to sort references by, with the full name breaking ties.
+    pub sort: Sort,
+    /// If true, sort in descending order, with ties still broken by the full name in ascending order like in git.
+    pub descending: bool,
+}
+
+impl Options {
+    fn selects_by_object(&self) -> bool {
+        self.points_at.is_some() || self.needs_commits()
+    }
+
+    fn needs_commits(&self) -> bool {
+        self.merged.is_some() || self.no_merged.is_some() || self.contains.is_some() || self.no_contains.is_some()
+    }
+}
+
+/// Return true if `pattern` matches the reference `full_name` as described in [`Options`].
+pub fn matches(pattern: &BStr, full_name: &BStr) -> bool {
+    let is_prefix = full_name.starts_with(pattern)
+        && (full_name.len() == pattern.len() || full_name[pattern.len()] == b'/' || pattern.last() == Some(&b'/'));
+    is_prefix || git_glob::wildmatch(pattern, full_name, git_glob::wildmatch::Mode::NO_MATCH_SLASH_LITERAL)
+}
+
+/// Compare `a` and `b` like versions, where all runs of digits are compared by their numeric value and everything
+/// else byte by byte, so `v1.10` is greater than `v1.9`.
+pub fn version_cmp(a: &BStr, b: &BStr) -> Ordering {
+    let (mut a, mut b): (&[u8], &[u8]) = (a, b);
+    loop {
+        match (a.first(), b.first()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (a_digits, a_rest) = split_digits(a);
+                let (b_digits, b_rest) = split_digits(b);
+                let (a_value, b_value) = (trim_leading_zeros(a_digits), trim_leading_zeros(b_digits));
+                let ordering = a_value
+                    .len()
+                    .cmp(&b_value.len())
+                    .then_with(|| a_value.cmp(b_value))
+                    .then_with(|| a_digits.len().cmp(&b_digits.len()));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a = a_rest;
+                b = b_rest;
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(y);
+                }
+                a = &a[1..];
+                b = &b[1..];
+            }
+        }
+    }
+}
+
+fn split_digits(input: &[u8]) -> (&[u8], &[u8]) {
+    let len = input.iter().take_while(|b| b.is_ascii_digit()).count();
+    input.split_at(len)
+}
+
+fn trim_leading_zeros(digits: &[u8]) -> &[u8] {
+    let zeros = digits.iter().take_while(|b| **b == b'0').count();
+    &digits[zeros..]
+}
+
+/// The objects a reference points to.
+struct Ids {
+    /// The object the reference points to after following symbolic references.
+    direct: ObjectId,
+    /// The object after peeling tags.
+    peeled: ObjectId,
+    /// The kind of the peeled object.
+    peeled_kind: git_object::Kind,
+    /// The committer time if the peeled object is a commit.
+    committer_time: Option<u32>,
+}
+
+impl<'s> file::iter::Platform<'s> {
+    /// Return all references selected by `options`, sorted as configured, using `find` to read objects for
+    /// peeling, sorting and reachability checks.
+    ///
+    /// Symbolic references are selected by the object of the reference they point to. If that reference doesn't exist,
+    /// they are skipped if objects are needed for selection, and otherwise sorted like references to non-commits.
+    pub fn filtered<E>(
+        &self,
+        options: &Options,
+        mut find: impl FnMut(ObjectId, &mut Vec<u8>) -> Result<Option<(git_object::Kind, &[u8])>, E>,
+    ) -> Result<Vec<Reference>, Error>
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        let merged = match options.merged {
+            Some(tip) => Some(ancestors(tip, &mut find)?),
+            None => None,
+        };
+        let no_merged = match options.no_merged {
+            Some(tip) => Some(ancestors(tip, &mut find)?),
+            None => None,
+        };
+        let mut contains = Contains::default();
+        let mut buf = Vec::new();
+
+        let mut selected = Vec::new();
+        for reference in self.all()? {
+            let reference = reference?;
+            let name = reference.name.as_bstr();
+            if (!options.patterns.is_empty() && !options.patterns.iter().any(|p| matches(p.as_bstr(), name)))
+                || options.exclude.iter().any(|p| matches(p.as_bstr(), name))
+            {
+                continue;
+            }
+            if !options.selects_by_object() {
+                let ids = match options.sort {
+                    Sort::CommitterDate => self.ids(&reference, &mut buf, &mut find)?,
+                    Sort::RefName | Sort::Version => None,
+                };
+                selected.push((reference, ids));
+                continue;
+            }
+
+            let ids = match self.ids(&reference, &mut buf, &mut find)? {
+                Some(ids) => ids,
+                None => continue,
+            };
+            if let Some(points_at) = options.points_at {
+                if ids.direct != points_at && ids.peeled != points_at {
+                    continue;
+                }
+            }
+            if options.needs_commits() {
+                if ids.peeled_kind != git_object::Kind::Commit {
+                    continue;
+                }
+                if merged.as_ref().map_or(false, |merged| !merged.contains(&ids.peeled))
+                    || no_merged
+                        .as_ref()
+                        .map_or(false, |no_merged| no_merged.contains(&ids.peeled))
+                {
+                    continue;
+                }
+                if let Some(commit) = options.contains {
+                    if !contains.check(ids.peeled, commit, &mut find)? {
+                        continue;
+                    }
+                }
+                if let Some(commit) = options.no_contains {
+                    if contains.check(ids.peeled, commit, &mut find)? {
+                        continue;
+                    }
+                }
+            }
+            selected.push((reference, Some(ids)));
+        }
+
+        selected.sort_by(|(a, a_ids), (b, b_ids)| {
+            let ordering = match options.sort {
+                Sort::RefName => a.name.cmp(&b.name),
+                Sort::Version => version_cmp(a.name.as_bstr(), b.name.as_bstr()),
+                Sort::CommitterDate => {
+                    let time = |ids: &Option<Ids>| ids.as_ref().and_then(|ids| ids.committer_time);
+                    time(a_ids).cmp(&time(b_ids))
+                }
+            };
+            let ordering = if options.descending {
+                ordering.reverse()
+            } else {
+                ordering
+            };
+            ordering.then_with(|| a.name.cmp(&b.name))
+        });
+        Ok(selected.into_iter().map(|(reference, _)| reference).collect())
+    }
+
+    /// Return the ids `reference` points to, or `None` if it is a symbolic reference that doesn't point to an existing one.
+    fn ids<E>(
+        &self,
+        reference: &Reference,
+        buf: &mut Vec<u8>,
+        find: &mut impl FnMut(ObjectId, &mut Vec<u8>) -> Result<Option<(git_object::Kind, &[u8])>, E>,
+    ) -> Result<Option<Ids>, Error>
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        const MAX_REF_DEPTH: usize = 5;
+        let mut leaf = None;
+        for _ in 0..MAX_REF_DEPTH {
+            let current = leaf.as_ref().unwrap_or(reference);
+            if current.target.as_id().is_some() {
+                break;
+            }
+            let next = match current.follow_packed(self.store, self.packed.as_deref()) {
+                None => break,
+                Some(Ok(next)) => next,
+                Some(Err(file::find::existing::Error::NotFound(_))) => return Ok(None),
+                Some(Err(err)) => return Err(Error::Follow(err)),
+            };
+            leaf = Some(next);
+        }
+        let leaf = leaf.as_ref().unwrap_or(reference);
+        let direct = match leaf.target.as_id() {
+            Some(id) => id.to_owned(),
+            None => {
+                return Err(Error::DepthLimitExceeded {
+                    name: reference.name.0.clone(),
+                })
+            }
+        };
+
+        let mut peeled = direct;
+        loop {
+            let (kind, data) = find(peeled, buf)
+                .map_err(|err| Error::FindObject(Box::new(err)))?
+                .ok_or_else(|| Error::ObjectMissing {
+                    oid: peeled,
+                    name: reference.name.0.clone(),
+                })?;
+            match kind {
+                git_object::Kind::Tag => match git_object::TagRefIter::from_bytes(data).target_id() {
+                    Some(target) => peeled = target,
+                    None => {
+                        return Err(Error::ObjectMissing {
+                            oid: peeled,
+                            name: reference.name.0.clone(),
+                        })
+                    }
+                },
+                kind => {
+                    return Ok(Some(Ids {
+                        direct,
+                        peeled,
+                        peeled_kind: kind,
+                        committer_time: (kind == git_object::Kind::Commit)
+                            .then(|| {
+                                git_object::CommitRefIter::from_bytes(data)
+                                    .committer()
+                                    .map(|s| s.time.time)
+                            })
+                            .flatten(),
+                    }))
+                }
+            }
+        }
+    }
+}
+
+/// Return `tip` and all commits in its history.
+fn ancestors<E>(
+    tip: ObjectId,
+    find: &mut impl FnMut(ObjectId, &mut Vec<u8>) -> Result<Option<(git_object::Kind, &[u8])>, E>,
+) -> Result<HashSet<ObjectId>, Error>
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    let mut buf = Vec::new();
+    let mut seen: HashSet<_> = Some(tip).into_iter().collect();
+    let mut queue: VecDeque<_> = Some(tip).into_iter().collect();
+    while let Some(id) = queue.pop_front() {
+        for parent in parents(id, &mut buf, find)? {
+            if seen.insert(parent) {
+                queue.push_back(parent);
+            }
+        }
+    }
+    Ok(seen)
+}
+
+fn parents<E>(
+    id: ObjectId,
+    buf: &mut Vec<u8>,
+    find: &mut impl FnMut(ObjectId, &mut Vec<u8>) -> Result<Option<(git_object::Kind, &[u8])>, E>,
+) -> Result<Vec<ObjectId>, Error>
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    Ok(match find(id, buf).map_err(|err| Error::FindObject(Box::new(err)))? {
+        Some((git_object::Kind::Commit, data)) => tree_and_parents(data).1,
+        _ => Vec::new(),
+    })
+}
+
+/// Answers whether commits contain a given commit in their history, remembering commits known not to contain it.
+#[derive(Default)]
+struct Contains {
+    commit: Option<ObjectId>,
+    without: HashSet<ObjectId>,
+    buf: Vec<u8>,
+}
+
+impl Contains {
+    fn check<E>(
+        &mut self,
+        tip: ObjectId,
+        commit: ObjectId,
+        find: &mut impl FnMut(ObjectId, &mut Vec<u8>) -> Result<Option<(git_object::Kind, &[u8])>, E>,
+    ) -> Result<bool, Error>
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        if self.commit != Some(commit) {
+            self.commit = Some(commit);
+            self.without.clear();
+        }
+        let mut seen: HashSet<_> = Some(tip).into_iter().collect();
+        let mut queue: VecDeque<_> = Some(tip).into_iter().collect();
+        while let Some(id) = queue.pop_front() {
+            if id == commit {
+                return Ok(true);
+            }
+            if self.without.contains(&id) {
+                continue;
+            }
+            for parent in parents(id, &mut self.buf, find)? {
+                if seen.insert(parent) {
+                    queue.push_back(parent);
+                }
+            }
+        }
+        // none of the commits we have seen has `commit` in its history
+        self.without.extend(seen);
+        Ok(false)
+    }
+}
+
+mod error {
+    use git_hash::ObjectId;
+    use git_object::bstr::BString;
+    use quick_error::quick_error;
+
+    quick_error! {
+        /// The error returned by [`Platform::filtered()`][crate::file::iter::Platform::filtered()].
+        #[derive(Debug)]
+        #[allow(missing_docs)]
+        pub enum Error {
+            Io(err: std::io::Error) {
+                display("The iteration of references could not be started")
+                from()
+                source(err)
+            }
+            Iter(err: crate::file::iter::loose_then_packed::Error) {
+                display("A reference could not be read during iteration")
+                from()
+                source(err)
+            }
+            Follow(err: crate::file::find::existing::Error) {
+                display("A symbolic reference could not be followed")
+                source(err)
+            }
+            DepthLimitExceeded { name: BString } {
+                display("The symbolic reference '{}' points to too many other symbolic references", name)
+            }
+            FindObject(err: Box<dyn std::error::Error + Send + Sync + 'static>) {
+                display("An object could not be read")
+                source(&**err)
+            }
+            ObjectMissing { oid: ObjectId, name: BString } {
+                display("The object {} reachable from reference '{}' does not exist", oid, name)
+            }
+        }
+    }
+}
+pub use error::Error;
@@ -81,7 +81,7 @@ impl file::Store {
             .to_string_lossy()
             .as_ref()
             .chars()
-            .all(|c| c.is_ascii_uppercase());
+            .all(|c| c.is_ascii_uppercase() || c == '_');
         if relative_path.components().count() == 1 && is_all_uppercase {
             if let Some(r) = self.find_inner("", relative_path, None, Transform::None)? {
                 return Ok(Some(r));
@@ -165,10 +165,17 @@ impl file::Store {
 impl file::Store {
     /// Implements the logic required to transform a fully qualified refname into a filesystem path
     pub(crate) fn reference_path(&self, name: &Path) -> PathBuf {
-        match &self.namespace {
-            None => self.base.join(name),
-            Some(namespace) => self.base.join(namespace.to_path()).join(name),
-        }
+        self.reference_path_with_base(name).1
+    }
+
+    /// Like [`reference_path()`][file::Store::reference_path()], but also returns the directory the reference is stored in.
+    pub(crate) fn reference_path_with_base(&self, name: &Path) -> (&Path, PathBuf) {
+        let name = match &self.namespace {
+            None => name.to_owned(),
+            Some(namespace) => namespace.to_path().join(name),
+        };
+        let base = self.base_for(&name);
+        (base, base.join(name))
     }
 
     /// Read the file contents with a verified full reference path and return it in the given vector if possible.
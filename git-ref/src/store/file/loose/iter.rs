@@ -1,6 +1,7 @@
 use std::{
     ffi::OsString,
     io::Read,
+    iter::Peekable,
     path::{Path, PathBuf},
 };
 
@@ -17,6 +18,8 @@ use crate::{
 pub(in crate::store_impl::file) struct SortedLoosePaths {
     pub(crate) base: PathBuf,
     filename_prefix: Option<OsString>,
+    /// If set, only yield references which are per-worktree if `true`, or shared if `false`.
+    per_worktree: Option<bool>,
     file_walk: DirEntryIter,
 }
 
@@ -30,11 +33,53 @@ impl SortedLoosePaths {
         SortedLoosePaths {
             base: base.into(),
             filename_prefix,
+            per_worktree: None,
             file_walk,
         }
     }
 }
 
+/// An iterator over all valid loose reference paths of a store, merging the per-worktree references of a linked work tree
+/// with the shared ones of its common directory while maintaining the sort order.
+pub(in crate::store_impl::file) struct MergedLoosePaths {
+    private: Option<Peekable<SortedLoosePaths>>,
+    common: Option<Peekable<SortedLoosePaths>>,
+}
+
+impl From<SortedLoosePaths> for MergedLoosePaths {
+    fn from(paths: SortedLoosePaths) -> Self {
+        MergedLoosePaths {
+            private: Some(paths.peekable()),
+            common: None,
+        }
+    }
+}
+
+impl Iterator for MergedLoosePaths {
+    type Item = std::io::Result<(PathBuf, FullName)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match (self.private.as_mut(), self.common.as_mut()) {
+            (Some(private), Some(common)) => {
+                let take_private = match (private.peek(), common.peek()) {
+                    (Some(Ok((_, private_name))), Some(Ok((_, common_name)))) => {
+                        private_name.as_bstr() <= common_name.as_bstr()
+                    }
+                    (Some(_), _) => true,
+                    (None, _) => false,
+                };
+                if take_private {
+                    private.next()
+                } else {
+                    common.next()
+                }
+            }
+            (Some(paths), None) | (None, Some(paths)) => paths.next(),
+            (None, None) => None,
+        }
+    }
+}
+
 impl Iterator for SortedLoosePaths {
     type Item = std::io::Result<(PathBuf, FullName)>;
 
@@ -55,10 +100,15 @@ impl Iterator for SortedLoosePaths {
                             continue;
                         }
                     }
-                    let full_name = full_path
+                    let relative_path = full_path
                         .strip_prefix(&self.base)
-                        .expect("prefix-stripping cannot fail as prefix is our root")
-                        .to_raw_bytes();
+                        .expect("prefix-stripping cannot fail as prefix is our root");
+                    if let Some(per_worktree) = self.per_worktree {
+                        if file::is_per_worktree(relative_path) != per_worktree {
+                            continue;
+                        }
+                    }
+                    let full_name = relative_path.to_raw_bytes();
                     #[cfg(windows)]
                     let full_name: Vec<u8> = full_name.into_owned().replace(b"\\", b"/");
 
@@ -81,7 +131,7 @@ impl Iterator for SortedLoosePaths {
 
 /// An iterator over all loose references as seen from a particular base directory.
 pub struct Loose {
-    ref_paths: SortedLoosePaths,
+    ref_paths: MergedLoosePaths,
     buf: Vec<u8>,
 }
 
@@ -90,7 +140,7 @@ impl Loose {
     /// path to which resulting reference names should be relative to.
     pub fn at_root(root: impl AsRef<Path>, base: impl Into<PathBuf>) -> Self {
         Loose {
-            ref_paths: SortedLoosePaths::at_root_with_names(root, base, None),
+            ref_paths: SortedLoosePaths::at_root_with_names(root, base, None).into(),
             buf: Vec::new(),
         }
     }
@@ -103,7 +153,7 @@ impl Loose {
         prefix: Option<OsString>,
     ) -> Self {
         Loose {
-            ref_paths: SortedLoosePaths::at_root_with_names(root, base, prefix),
+            ref_paths: SortedLoosePaths::at_root_with_names(root, base, prefix).into(),
             buf: Vec::new(),
         }
     }
@@ -122,13 +172,9 @@ impl Iterator for Loose {
                     })
                     .map_err(loose::Error::ReadFileContents)
                     .and_then(|_| {
-                        let relative_path = validated_path
-                            .strip_prefix(&self.ref_paths.base)
-                            .expect("root contains path");
-                        Reference::try_from_path(name, &self.buf).map_err(|err| loose::Error::ReferenceCreation {
-                            err,
-                            relative_path: relative_path.into(),
-                        })
+                        let relative_path = name.to_path().into_owned();
+                        Reference::try_from_path(name, &self.buf)
+                            .map_err(|err| loose::Error::ReferenceCreation { err, relative_path })
                     })
             })
         })
@@ -148,27 +194,64 @@ impl file::Store {
     ///
     /// See [`Store::packed()`][file::Store::open_packed_buffer()] for interacting with packed references.
     pub fn loose_iter(&self) -> std::io::Result<Loose> {
-        let refs = self.refs_dir();
-        if !refs.is_dir() {
+        if !self.refs_dir().is_dir() {
             return Err(std::io::ErrorKind::NotFound.into());
         }
-        Ok(Loose::at_root(refs, self.base.clone()))
+        Ok(Loose {
+            ref_paths: self.loose_paths(Path::new("refs"), None),
+            buf: Vec::new(),
+        })
     }
 
     /// Return an iterator over all loose references that start with the given `prefix`.
     ///
     /// Otherwise it's similar to [`loose_iter()`][file::Store::loose_iter()].
     pub fn loose_iter_prefixed(&self, prefix: impl AsRef<Path>) -> std::io::Result<Loose> {
-        let (root, remainder) = self.validate_prefix(&self.base, prefix.as_ref())?;
-        Ok(Loose::at_root_with_filename_prefix(root, self.base.clone(), remainder))
+        let (root, remainder) = self.validate_prefix(prefix.as_ref())?;
+        Ok(Loose {
+            ref_paths: self.loose_paths(&root, remainder),
+            buf: Vec::new(),
+        })
     }
 
+    /// Return the directory containing all shared loose references.
     pub(in crate::store_impl::file) fn refs_dir(&self) -> PathBuf {
-        self.base.join("refs")
+        self.common_dir_resolved().join("refs")
+    }
+
+    /// Return sorted paths to all loose references in the `relative_root` directory whose file names start with `filename_prefix`,
+    /// looking into both the private and the common directory of linked work trees.
+    pub(in crate::store_impl::file) fn loose_paths(
+        &self,
+        relative_root: &Path,
+        filename_prefix: Option<OsString>,
+    ) -> MergedLoosePaths {
+        match &self.common_dir {
+            None => {
+                SortedLoosePaths::at_root_with_names(self.base.join(relative_root), self.base.clone(), filename_prefix)
+                    .into()
+            }
+            Some(common_dir) => {
+                let paths_at = |base: &Path, per_worktree: bool| {
+                    let root = base.join(relative_root);
+                    root.is_dir().then(|| {
+                        let mut paths = SortedLoosePaths::at_root_with_names(root, base, filename_prefix.clone());
+                        paths.per_worktree = Some(per_worktree);
+                        paths.peekable()
+                    })
+                };
+                MergedLoosePaths {
+                    private: paths_at(&self.base, true),
+                    common: paths_at(common_dir, false),
+                }
+            }
+        }
     }
+
+    /// Return the directory relative to our base in which to look for references starting with `prefix`, along
+    /// with the file name prefix they must have if `prefix` isn't a directory.
     pub(in crate::store_impl::file) fn validate_prefix(
         &self,
-        base: &Path,
         prefix: &Path,
     ) -> std::io::Result<(PathBuf, Option<OsString>)> {
         if prefix.is_absolute() {
@@ -184,13 +267,12 @@ impl file::Store {
                 "Refusing to handle prefixes with relative path components",
             ));
         }
-        let base = base.join(prefix);
-        if base.is_dir() {
-            Ok((base, None))
+        if self.base.join(prefix).is_dir() || self.common_dir_resolved().join(prefix).is_dir() {
+            Ok((prefix.to_owned(), None))
         } else {
             Ok((
-                base.parent().expect("a parent is always there unless empty").to_owned(),
-                base.file_name().map(ToOwned::to_owned),
+                prefix.parent().map(ToOwned::to_owned).unwrap_or_default(),
+                prefix.file_name().map(ToOwned::to_owned),
             ))
         }
     }
@@ -40,11 +40,29 @@ mod init {
         ) -> Self {
             file::Store {
                 base: git_dir.into(),
+                common_dir: None,
                 write_reflog,
                 namespace: None,
                 packed: Default::default(),
                 object_hash,
             }
         }
+
+        /// Create a new instance for the linked work tree whose private `git_dir` is usually located at
+        /// `$GIT_COMMON_DIR/worktrees/<id>`, and which shares all but its per-worktree references with the repository
+        /// at `common_dir`.
+        ///
+        /// `write_reflog` and `object_hash` are used just like in [`at()`][file::Store::at()].
+        pub fn for_linked_worktree(
+            git_dir: impl Into<PathBuf>,
+            common_dir: impl Into<PathBuf>,
+            write_reflog: crate::file::WriteReflog,
+            object_hash: git_hash::Kind,
+        ) -> Self {
+            file::Store {
+                common_dir: Some(common_dir.into()),
+                ..Self::at(git_dir, write_reflog, object_hash)
+            }
+        }
     }
 }
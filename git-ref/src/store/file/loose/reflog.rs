@@ -80,8 +80,28 @@ impl file::Store {
     pub(crate) fn reflog_path(&self, name: FullNameRef<'_>) -> PathBuf {
         self.reflog_path_inner(&name.to_path())
     }
+
+    /// Return the contents of the reflog of `name`, or `None` if there is no reflog.
+    pub(crate) fn reflog_contents(&self, name: FullNameRef<'_>) -> std::io::Result<Option<Vec<u8>>> {
+        match std::fs::read(self.reflog_path(name)) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(_) if self.reflog_path(name).is_dir() => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Write `contents` as reflog of `name`, replacing it if it exists.
+    pub(crate) fn reflog_replace(&self, name: FullNameRef<'_>, contents: &[u8]) -> std::io::Result<()> {
+        let path = self.reflog_path(name);
+        git_tempfile::create_dir::all(path.parent().expect("always with parent directory"), Default::default())?;
+        std::fs::write(path, contents)
+    }
 }
 
+///
+pub mod expire;
+
 ///
 pub mod create_or_update {
     use std::{
@@ -104,14 +124,34 @@ pub mod create_or_update {
             message: &BStr,
             force_create_reflog: bool,
         ) -> Result<(), Error> {
-            let full_name = self.reflock_resource_full_name(lock);
+            self.reflog_create_or_append_unlocked(
+                &self.reflock_resource_full_name(lock),
+                previous_oid,
+                new,
+                committer,
+                message,
+                force_create_reflog,
+            )
+        }
+
+        /// Like [`reflog_create_or_append()`][file::Store::reflog_create_or_append()], but for the reference with the
+        /// possibly namespaced `full_name` whose lock isn't held, as packed-refs are locked instead.
+        pub(in crate::store_impl::file) fn reflog_create_or_append_unlocked(
+            &self,
+            full_name: &Path,
+            previous_oid: Option<ObjectId>,
+            new: &oid,
+            committer: &git_actor::Signature,
+            message: &BStr,
+            force_create_reflog: bool,
+        ) -> Result<(), Error> {
             match self.write_reflog {
                 WriteReflog::Normal => {
                     let mut options = std::fs::OpenOptions::new();
                     options.append(true).read(false);
-                    let log_path = self.reflock_resource_to_log_path(lock);
+                    let log_path = self.reflog_path_inner(full_name);
 
-                    if force_create_reflog || self.should_autocreate_reflog(&full_name) {
+                    if force_create_reflog || self.should_autocreate_reflog(full_name) {
                         let parent_dir = log_path.parent().expect("always with parent directory");
                         git_tempfile::create_dir::all(parent_dir, Default::default()).map_err(|err| {
                             Error::CreateLeadingDirectories {
@@ -133,7 +173,7 @@ pub mod create_or_update {
                                     .map(Some)
                                     .map_err(|_| Error::Append {
                                         err,
-                                        reflog_path: self.reflock_resource_to_log_path(lock),
+                                        reflog_path: log_path.clone(),
                                     })?
                             } else {
                                 return Err(Error::Append {
@@ -156,7 +196,7 @@ pub mod create_or_update {
                             })
                             .map_err(|err| Error::Append {
                                 err,
-                                reflog_path: self.reflock_resource_to_log_path(lock),
+                                reflog_path: log_path.clone(),
                             })?;
                     }
                     Ok(())
@@ -173,30 +213,28 @@ pub mod create_or_update {
         }
 
         fn reflock_resource_full_name(&self, reflock: &git_lock::Marker) -> PathBuf {
-            reflock
-                .resource_path()
-                .strip_prefix(&self.base)
+            let path = reflock.resource_path();
+            path.strip_prefix(&self.base)
+                .ok()
+                .filter(|name| file::is_per_worktree(name))
+                .or_else(|| path.strip_prefix(self.common_dir_resolved()).ok())
                 .expect("lock must be held within this store")
                 .to_owned()
         }
 
+        #[cfg(test)]
         fn reflock_resource_to_log_path(&self, reflock: &git_lock::Marker) -> PathBuf {
-            self.reflog_path_inner(
-                reflock
-                    .resource_path()
-                    .strip_prefix(&self.base)
-                    .expect("lock must be held within this store"),
-            )
+            self.reflog_path_inner(&self.reflock_resource_full_name(reflock))
         }
 
         /// Returns the base and a full path (including the base) to the reflog for a ref of the given `full_name`
         pub(in crate::store_impl::file::loose::reflog) fn reflog_path_inner(&self, full_name: &Path) -> PathBuf {
-            self.reflog_root().join(full_name)
+            self.reflog_root(full_name).join(full_name)
         }
 
-        /// Returns the base paths for all reflogs
-        pub(in crate::store_impl::file) fn reflog_root(&self) -> PathBuf {
-            self.base.join("logs")
+        /// Returns the base path for all reflogs stored alongside the ref with the given `full_name`.
+        pub(in crate::store_impl::file) fn reflog_root(&self, full_name: &Path) -> PathBuf {
+            self.base_for(full_name).join("logs")
         }
     }
 
@@ -0,0 +1,298 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    convert::TryInto,
+    io::Write,
+};
+
+use git_hash::ObjectId;
+use git_object::{bstr::ByteSlice, commit::ref_iter::Token};
+
+use crate::{
+    store_impl::{file, file::log, file::ReferenceExt},
+    FullNameRef,
+};
+
+/// Options for use in [`file::Store::reflog_expire()`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Options {
+    /// Remove all entries made before this time in seconds since the unix epoch, or keep them if `None`.
+    ///
+    /// This is what `gc.reflogExpire` configures.
+    pub expire: Option<u32>,
+    /// Remove entries made before this time in seconds since the unix epoch if their previous or new object isn't
+    /// reachable from the tip of the reference or from `HEAD`, or keep them if `None`. Entries in the log of `HEAD` are
+    /// reachable if they are reachable from the tip of any reference.
+    ///
+    /// This is what `gc.reflogExpireUnreachable` configures.
+    pub expire_unreachable: Option<u32>,
+    /// If true, remove entries whose previous or new object is missing, or is a commit with missing commits or trees
+    /// in its history, which happens if objects were pruned without considering the reflog.
+    pub stale_fix: bool,
+    /// If true, set the previous object of each kept entry to the new object of the kept entry before it, to keep the
+    /// log consistent with itself after removing entries.
+    pub rewrite: bool,
+    /// If true, determine which entries would be removed without changing the log.
+    pub dry_run: bool,
+}
+
+/// The outcome of [`file::Store::reflog_expire()`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Outcome {
+    /// The amount of entries kept in the log.
+    pub kept: usize,
+    /// The amount of entries removed from the log, or which would have been removed in a dry-run.
+    pub removed: usize,
+}
+
+impl file::Store {
+    /// Remove entries from the reflog of the reference `name` according to `options`, while holding the lock of the
+    /// reference acquired according to `lock_fail_mode`. `find` is used to read commits for reachability checks.
+    ///
+    /// The reflog is rewritten through a lock file, so readers see either the old or the new log. Lines which can't be
+    /// parsed are kept as they are. Returns `Ok(None)` if there is no reflog.
+    pub fn reflog_expire<'a, Name, E, FindError>(
+        &self,
+        name: Name,
+        options: Options,
+        lock_fail_mode: git_lock::acquire::Fail,
+        mut find: impl FnMut(ObjectId, &mut Vec<u8>) -> Result<Option<(git_object::Kind, &[u8])>, FindError>,
+    ) -> Result<Option<Outcome>, Error>
+    where
+        Name: TryInto<FullNameRef<'a>, Error = E>,
+        crate::name::Error: From<E>,
+        FindError: std::error::Error + Send + Sync + 'static,
+    {
+        let name: FullNameRef<'_> = name.try_into().map_err(|err| Error::RefnameValidation(err.into()))?;
+        let relative_path = name.to_path();
+        let (ref_base, ref_path) = self.reference_path_with_base(&relative_path);
+        let _lock = git_lock::Marker::acquire_to_hold_resource(ref_path, lock_fail_mode, Some(ref_base.to_owned()))?;
+
+        let log_path = self.reflog_path(name);
+        let log = match std::fs::read(&log_path) {
+            Ok(log) => log,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut tips = Vec::new();
+        if options.expire_unreachable.is_some() {
+            for tip_name in &[name.as_bstr(), "HEAD".into()] {
+                if let Some(mut reference) = self.try_find(*tip_name).map_err(Error::FindReference)? {
+                    tips.push(reference.peel_to_id_in_place(self, &mut find)?);
+                }
+            }
+            if name.as_bstr() == "HEAD" {
+                for reference in self.iter()?.all()? {
+                    let mut reference = reference?;
+                    if let crate::Target::Peeled(_) = reference.target {
+                        tips.push(reference.peel_to_id_in_place(self, &mut find)?);
+                    }
+                }
+            }
+        }
+        let mut reachable = Reachable::new(tips);
+        let mut complete = HashSet::new();
+        let mut buf = Vec::new();
+
+        let mut outcome = Outcome::default();
+        let mut out = Vec::with_capacity(log.len());
+        let mut last_kept = None;
+        for line in log.lines_with_terminator() {
+            let entry = match log::LineRef::from_bytes(line.strip_suffix(b"\n").unwrap_or(line)) {
+                Ok(entry) => entry,
+                Err(_) => {
+                    out.extend_from_slice(line);
+                    outcome.kept += 1;
+                    continue;
+                }
+            };
+            let (previous, new) = (entry.previous_oid(), entry.new_oid());
+            let time = entry.signature.time.time;
+
+            let remove = options.expire.map_or(false, |cutoff| time < cutoff)
+                || (options.stale_fix
+                    && !(is_complete(previous, &mut complete, &mut buf, &mut find)?
+                        && is_complete(new, &mut complete, &mut buf, &mut find)?))
+                || (options.expire_unreachable.map_or(false, |cutoff| time < cutoff)
+                    && !(reachable.contains(previous, &mut find)? && reachable.contains(new, &mut find)?));
+            if remove {
+                outcome.removed += 1;
+                continue;
+            }
+
+            outcome.kept += 1;
+            if options.rewrite {
+                let previous_hex_len = entry.previous_oid.len();
+                let previous = last_kept.unwrap_or_else(|| new.kind().null());
+                write!(out, "{}", previous)?;
+                out.extend_from_slice(&line[previous_hex_len..]);
+            } else {
+                out.extend_from_slice(line);
+            }
+            last_kept = Some(new);
+        }
+
+        if !options.dry_run && out != log {
+            let mut lock = git_lock::File::acquire_to_update_resource(&log_path, lock_fail_mode, None)?;
+            lock.with_mut(|file| file.write_all(&out))?;
+            lock.commit().map_err(|err| Error::Commit(err.error))?;
+        }
+        Ok(Some(outcome))
+    }
+}
+
+/// The commits reachable from the tips of references, discovered lazily by traversing their history only as far as needed.
+struct Reachable {
+    queue: VecDeque<ObjectId>,
+    seen: HashSet<ObjectId>,
+    buf: Vec<u8>,
+}
+
+impl Reachable {
+    fn new(tips: Vec<ObjectId>) -> Self {
+        Reachable {
+            seen: tips.iter().copied().collect(),
+            queue: tips.into(),
+            buf: Vec::new(),
+        }
+    }
+
+    /// Return true if `id` is reachable from any tip, with null ids always being reachable.
+    fn contains<E>(
+        &mut self,
+        id: ObjectId,
+        find: &mut impl FnMut(ObjectId, &mut Vec<u8>) -> Result<Option<(git_object::Kind, &[u8])>, E>,
+    ) -> Result<bool, Error>
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        if id.is_null() || self.seen.contains(&id) {
+            return Ok(true);
+        }
+        while let Some(next) = self.queue.pop_front() {
+            let parents = match find(next, &mut self.buf).map_err(|err| Error::FindObject(Box::new(err)))? {
+                Some((git_object::Kind::Commit, data)) => tree_and_parents(data).1,
+                _ => continue,
+            };
+            for parent in parents {
+                if self.seen.insert(parent) {
+                    self.queue.push_back(parent);
+                }
+            }
+            if self.seen.contains(&id) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+/// Return true if the object `id` exists, and if it's a commit, all commits in its history along with their trees exist
+/// as well. Objects known to be complete are kept in `complete` to avoid traversing their history more than once.
+fn is_complete<E>(
+    id: ObjectId,
+    complete: &mut HashSet<ObjectId>,
+    buf: &mut Vec<u8>,
+    find: &mut impl FnMut(ObjectId, &mut Vec<u8>) -> Result<Option<(git_object::Kind, &[u8])>, E>,
+) -> Result<bool, Error>
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    if id.is_null() || complete.contains(&id) {
+        return Ok(true);
+    }
+    let mut find = |id: ObjectId, buf: &mut Vec<u8>| {
+        find(id, buf)
+            .map(|obj| {
+                obj.map(|(kind, data)| (kind, (kind == git_object::Kind::Commit).then(|| tree_and_parents(data))))
+            })
+            .map_err(|err| Error::FindObject(Box::new(err)))
+    };
+    let mut visited = HashSet::new();
+    let mut queue = vec![id];
+    while let Some(id) = queue.pop() {
+        if complete.contains(&id) || !visited.insert(id) {
+            continue;
+        }
+        match find(id, buf)? {
+            None => return Ok(false),
+            Some((_, Some((tree, parents)))) => {
+                match tree {
+                    Some(tree) if find(tree, buf)?.is_some() => {}
+                    _ => return Ok(false),
+                }
+                queue.extend(parents);
+            }
+            Some((_, None)) => {}
+        }
+    }
+    complete.extend(visited);
+    Ok(true)
+}
+
+pub(in crate::store_impl::file) fn tree_and_parents(data: &[u8]) -> (Option<ObjectId>, Vec<ObjectId>) {
+    let mut iter = git_object::CommitRefIter::from_bytes(data);
+    let tree = iter.tree_id();
+    let parents = iter
+        .take_while(|token| matches!(token, Ok(Token::Parent { .. })))
+        .filter_map(|token| match token {
+            Ok(Token::Parent { id }) => Some(id),
+            _ => None,
+        })
+        .collect();
+    (tree, parents)
+}
+
+mod error {
+    use quick_error::quick_error;
+
+    quick_error! {
+        /// The error returned by [`crate::file::Store::reflog_expire()`].
+        #[derive(Debug)]
+        #[allow(missing_docs)]
+        pub enum Error {
+            RefnameValidation(err: crate::name::Error) {
+                display("The reflog name or path is not a valid ref name")
+                source(err)
+            }
+            LockAcquire(err: git_lock::acquire::Error) {
+                display("The reference or its reflog could not be locked")
+                from()
+                source(err)
+            }
+            Io(err: std::io::Error) {
+                display("The reflog could not be read or written")
+                from()
+                source(err)
+            }
+            FindReference(err: crate::file::find::Error) {
+                display("The reference whose log to expire could not be read")
+                source(err)
+            }
+            PackedOpen(err: crate::packed::buffer::open::Error) {
+                display("The packed-refs file could not be opened to find the tips of all references")
+                from()
+                source(err)
+            }
+            IterReferences(err: crate::file::iter::loose_then_packed::Error) {
+                display("The tips of all references could not be read")
+                from()
+                source(err)
+            }
+            Peel(err: crate::peel::to_id::Error) {
+                display("The tip of the reference could not be peeled")
+                from()
+                source(err)
+            }
+            FindObject(err: Box<dyn std::error::Error + Send + Sync + 'static>) {
+                display("An object referred to by the reflog could not be read")
+                source(&**err)
+            }
+            Commit(err: std::io::Error) {
+                display("The rewritten reflog could not be moved into place")
+                source(err)
+            }
+        }
+    }
+}
+pub use error::Error;
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use git_features::threading::{MutableOnDemand, OwnShared};
 
@@ -12,6 +12,9 @@ pub struct Store {
     ///
     /// Typical base paths are `.git` repository folders.
     base: PathBuf,
+    /// The location of all references that are shared among work trees if this store belongs to a linked work tree,
+    /// that is the `$GIT_COMMON_DIR`. If unset, all references are read from and written to `base`.
+    common_dir: Option<PathBuf>,
     /// The kind of hash to assume in a couple of situations. Note that currently we are able to read any valid hash from files
     /// which might want to change one day.
     object_hash: git_hash::Kind,
@@ -32,22 +35,60 @@ mod access {
     use crate::file;
 
     impl file::Store {
-        /// Return the root at which all references are loaded.
+        /// Return the root at which all references are loaded, or the private `.git` directory of a linked work tree
+        /// which only contains per-worktree references like `HEAD`.
         pub fn base(&self) -> &Path {
             &self.base
         }
+
+        /// Return the directory containing references shared by all work trees if this store is for a linked work tree.
+        pub fn common_dir(&self) -> Option<&Path> {
+            self.common_dir.as_deref()
+        }
+
+        /// Return the directory containing shared references, which is the [`base()`][file::Store::base()] if this isn't
+        /// a store for a linked work tree.
+        pub fn common_dir_resolved(&self) -> &Path {
+            self.common_dir.as_deref().unwrap_or(&self.base)
+        }
+
+        /// Return the directory in which the reference at the full, possibly namespaced `relative_path` is stored.
+        ///
+        /// Per-worktree references like `HEAD`, `refs/bisect/…` and `refs/worktree/…` are stored in the `base`,
+        /// all others in the common directory.
+        pub(crate) fn base_for(&self, relative_path: &Path) -> &Path {
+            match &self.common_dir {
+                Some(common_dir) if !file::is_per_worktree(relative_path) => common_dir,
+                _ => &self.base,
+            }
+        }
     }
 }
 
+/// Returns true if the reference at the full `relative_path` is private to each work tree, like `HEAD` or `refs/bisect/good`.
+pub(in crate::store_impl::file) fn is_per_worktree(relative_path: &Path) -> bool {
+    !relative_path.starts_with("refs")
+        || ["bisect", "worktree", "rewritten"]
+            .iter()
+            .any(|dir| relative_path.starts_with(Path::new("refs").join(dir)))
+}
+
 /// A transaction on a file store
 pub struct Transaction<'s> {
     store: &'s Store,
     packed_transaction: Option<crate::store_impl::packed::Transaction>,
     updates: Option<Vec<transaction::Edit>>,
     packed_refs: transaction::PackedRefs,
+    /// If true, all updates are written into packed-refs directly without locking or writing loose references.
+    initial: bool,
+    /// The `reference-transaction` hook to invoke, if any.
+    hook: Option<transaction::Hook>,
+    /// If true, the hook ran successfully in the `prepared` phase and is waiting to learn whether the transaction
+    /// is committed or aborted.
+    hook_prepared: bool,
 }
 
-pub(in crate::store_impl::file) fn path_to_name(path: impl Into<PathBuf>) -> git_object::bstr::BString {
+pub(crate) fn path_to_name(path: impl Into<PathBuf>) -> git_object::bstr::BString {
     use os_str_bytes::OsStringBytes;
     let path = path.into().into_raw_vec();
     #[cfg(windows)]
@@ -75,6 +116,9 @@ pub mod iter {
     }
 }
 
+///
+pub mod filter;
+
 ///
 pub mod log;
 
@@ -87,6 +131,9 @@ pub mod transaction;
 ///
 pub mod packed;
 
+///
+pub mod pseudo;
+
 mod raw_ext;
 pub use raw_ext::ReferenceExt;
 
@@ -16,18 +16,17 @@ use crate::{
 ///
 /// All errors will be returned verbatim, while packed errors are depleted first if loose refs also error.
 pub struct LooseThenPacked<'p, 's> {
-    base: &'s Path,
     namespace: Option<&'s Namespace>,
     packed: Option<Peekable<packed::Iter<'p>>>,
-    loose: Peekable<loose::iter::SortedLoosePaths>,
+    loose: Peekable<loose::iter::MergedLoosePaths>,
     buf: Vec<u8>,
 }
 
 /// An intermediate structure to hold shared state alive long enough for iteration to happen.
 #[must_use = "Iterators should be obtained from this platform"]
 pub struct Platform<'s> {
-    store: &'s file::Store,
-    packed: Option<OwnShared<packed::Buffer>>,
+    pub(in crate::store_impl::file) store: &'s file::Store,
+    pub(in crate::store_impl::file) packed: Option<OwnShared<packed::Buffer>>,
 }
 
 impl<'p, 's> LooseThenPacked<'p, 's> {
@@ -65,11 +64,9 @@ impl<'p, 's> LooseThenPacked<'p, 's> {
                 f.read_to_end(&mut self.buf)
             })
             .map_err(Error::ReadFileContents)?;
+        let relative_path = name.to_path().into_owned();
         loose::Reference::try_from_path(name, &self.buf)
-            .map_err(|err| Error::ReferenceCreation {
-                err,
-                relative_path: refpath.strip_prefix(&self.base).expect("base contains path").into(),
-            })
+            .map_err(|err| Error::ReferenceCreation { err, relative_path })
             .map(Into::into)
             .map(|r| self.strip_namespace(r))
     }
@@ -153,7 +150,6 @@ impl file::Store {
         match self.namespace.as_ref() {
             Some(namespace) => self.iter_prefixed_unvalidated(namespace.to_path(), (None, None), packed),
             None => Ok(LooseThenPacked {
-                base: &self.base,
                 packed: match packed {
                     Some(packed) => Some(
                         packed
@@ -163,8 +159,7 @@ impl file::Store {
                     ),
                     None => None,
                 },
-                loose: loose::iter::SortedLoosePaths::at_root_with_names(self.refs_dir(), self.base.to_owned(), None)
-                    .peekable(),
+                loose: self.loose_paths(Path::new("refs"), None).peekable(),
                 buf: Vec::new(),
                 namespace: None,
             }),
@@ -181,12 +176,12 @@ impl file::Store {
     ) -> std::io::Result<LooseThenPacked<'p, 's>> {
         match self.namespace.as_ref() {
             None => {
-                let (root, remainder) = self.validate_prefix(&self.base, prefix.as_ref())?;
+                let (root, remainder) = self.validate_prefix(prefix.as_ref())?;
                 self.iter_prefixed_unvalidated(prefix, (root.into(), remainder), packed)
             }
             Some(namespace) => {
                 let prefix = namespace.to_owned().into_namespaced_prefix(prefix);
-                let (root, remainder) = self.validate_prefix(&self.base, &prefix)?;
+                let (root, remainder) = self.validate_prefix(&prefix)?;
                 self.iter_prefixed_unvalidated(prefix, (root.into(), remainder), packed)
             }
         }
@@ -200,7 +195,6 @@ impl file::Store {
     ) -> std::io::Result<LooseThenPacked<'p, 's>> {
         let packed_prefix = path_to_name(prefix.as_ref());
         Ok(LooseThenPacked {
-            base: &self.base,
             packed: match packed {
                 Some(packed) => Some(
                     packed
@@ -210,14 +204,14 @@ impl file::Store {
                 ),
                 None => None,
             },
-            loose: loose::iter::SortedLoosePaths::at_root_with_names(
-                loose_root_and_filename_prefix
-                    .0
-                    .unwrap_or_else(|| self.base.join(prefix)),
-                self.base.to_owned(),
-                loose_root_and_filename_prefix.1,
-            )
-            .peekable(),
+            loose: self
+                .loose_paths(
+                    &loose_root_and_filename_prefix
+                        .0
+                        .unwrap_or_else(|| prefix.as_ref().to_owned()),
+                    loose_root_and_filename_prefix.1,
+                )
+                .peekable(),
             buf: Vec::new(),
             namespace: self.namespace.as_ref(),
         })
@@ -0,0 +1,155 @@
+use std::{io::Write, path::PathBuf};
+
+use git_hash::ObjectId;
+
+use crate::{
+    pseudo::{self, FetchHeadEntry, Kind},
+    store_impl::file,
+};
+
+/// Reading and writing of pseudo-references, which are always stored in the [`base()`][file::Store::base()] as they are
+/// private to each work tree.
+impl file::Store {
+    /// Return the path to the file of the pseudo-reference of the given `kind`.
+    pub fn pseudo_ref_path(&self, kind: Kind) -> PathBuf {
+        self.base.join(kind.file_name())
+    }
+
+    /// Return true if the pseudo-reference of the given `kind` exists, which for some kinds indicates that an operation
+    /// is in progress, like a merge if [`MERGE_HEAD`][Kind::MergeHead] exists.
+    pub fn pseudo_ref_exists(&self, kind: Kind) -> bool {
+        self.pseudo_ref_path(kind).is_file()
+    }
+
+    /// Return the entries of `FETCH_HEAD`, or `None` if it doesn't exist.
+    pub fn fetch_head(&self) -> Result<Option<Vec<FetchHeadEntry>>, Error> {
+        self.read_pseudo_ref(Kind::FetchHead, pseudo::decode::fetch_head)
+    }
+
+    /// Return the ids of all commits in `MERGE_HEAD` in the order they are merged, or `None` if it doesn't exist.
+    pub fn merge_heads(&self) -> Result<Option<Vec<ObjectId>>, Error> {
+        self.read_pseudo_ref(Kind::MergeHead, pseudo::decode::ids)
+    }
+
+    /// Return the id stored in the pseudo-reference of the given `kind`, or `None` if it doesn't exist.
+    ///
+    /// For pseudo-references with multiple ids like `FETCH_HEAD` and `MERGE_HEAD`, this is the first one, which is
+    /// also the one git uses when resolving their name.
+    pub fn pseudo_ref_id(&self, kind: Kind) -> Result<Option<ObjectId>, Error> {
+        let ids = match kind {
+            Kind::FetchHead => self
+                .fetch_head()?
+                .map(|entries| entries.into_iter().map(|entry| entry.id).collect()),
+            _ => self.read_pseudo_ref(kind, pseudo::decode::ids)?,
+        };
+        ids.map(|ids: Vec<_>| ids.into_iter().next().ok_or(Error::Empty(kind)))
+            .transpose()
+    }
+
+    /// Write all `entries` into `FETCH_HEAD` through a lock file acquired according to `lock_fail_mode`, replacing
+    /// its previous content.
+    pub fn write_fetch_head<'a>(
+        &self,
+        entries: impl IntoIterator<Item = &'a FetchHeadEntry>,
+        lock_fail_mode: git_lock::acquire::Fail,
+    ) -> Result<(), Error> {
+        self.write_pseudo_ref(Kind::FetchHead, &pseudo::encode_fetch_head(entries), lock_fail_mode)
+    }
+
+    /// Write `ids` into `MERGE_HEAD` through a lock file acquired according to `lock_fail_mode`, replacing its
+    /// previous content.
+    pub fn write_merge_heads(
+        &self,
+        ids: impl IntoIterator<Item = ObjectId>,
+        lock_fail_mode: git_lock::acquire::Fail,
+    ) -> Result<(), Error> {
+        self.write_pseudo_ref(Kind::MergeHead, &pseudo::encode_ids(ids), lock_fail_mode)
+    }
+
+    /// Write `id` into the pseudo-reference of the given `kind` through a lock file acquired according to
+    /// `lock_fail_mode`, replacing its previous content.
+    ///
+    /// Writing `FETCH_HEAD` this way yields a single entry for merge without description.
+    pub fn write_pseudo_ref_id(
+        &self,
+        kind: Kind,
+        id: ObjectId,
+        lock_fail_mode: git_lock::acquire::Fail,
+    ) -> Result<(), Error> {
+        match kind {
+            Kind::FetchHead => self.write_fetch_head(
+                Some(&FetchHeadEntry {
+                    id,
+                    not_for_merge: false,
+                    description: Default::default(),
+                }),
+                lock_fail_mode,
+            ),
+            _ => self.write_pseudo_ref(kind, &pseudo::encode_ids(Some(id)), lock_fail_mode),
+        }
+    }
+
+    /// Delete the pseudo-reference of the given `kind`, returning true if it existed.
+    pub fn remove_pseudo_ref(&self, kind: Kind) -> Result<bool, Error> {
+        match std::fs::remove_file(self.pseudo_ref_path(kind)) {
+            Ok(()) => Ok(true),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn read_pseudo_ref<T>(
+        &self,
+        kind: Kind,
+        decode: impl FnOnce(&[u8]) -> Result<T, pseudo::decode::Error>,
+    ) -> Result<Option<T>, Error> {
+        match std::fs::read(self.pseudo_ref_path(kind)) {
+            Ok(data) => decode(&data).map(Some).map_err(|err| Error::Decode { kind, err }),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn write_pseudo_ref(&self, kind: Kind, data: &[u8], lock_fail_mode: git_lock::acquire::Fail) -> Result<(), Error> {
+        let mut lock = git_lock::File::acquire_to_update_resource(self.pseudo_ref_path(kind), lock_fail_mode, None)?;
+        lock.with_mut(|file| file.write_all(data))?;
+        lock.commit().map_err(|err| Error::Commit(err.error))?;
+        Ok(())
+    }
+}
+
+mod error {
+    use quick_error::quick_error;
+
+    use crate::pseudo::Kind;
+
+    quick_error! {
+        /// The error returned when reading or writing pseudo-references with a [`crate::file::Store`].
+        #[derive(Debug)]
+        #[allow(missing_docs)]
+        pub enum Error {
+            Io(err: std::io::Error) {
+                display("The pseudo-reference could not be read or written")
+                from()
+                source(err)
+            }
+            Decode { kind: Kind, err: crate::pseudo::decode::Error } {
+                display("The pseudo-reference {} could not be parsed", kind.file_name())
+                source(err)
+            }
+            Empty(kind: Kind) {
+                display("The pseudo-reference {} didn't contain any object id", kind.file_name())
+            }
+            LockAcquire(err: git_lock::acquire::Error) {
+                display("The pseudo-reference could not be locked")
+                from()
+                source(err)
+            }
+            Commit(err: std::io::Error) {
+                display("The written pseudo-reference could not be moved into place")
+                source(err)
+            }
+        }
+    }
+}
+pub use error::Error;
@@ -1,5 +1,8 @@
 use crate::{
-    store_impl::file::{transaction::PackedRefs, Transaction},
+    store_impl::file::{
+        transaction::{hook, prepare, Edit, PackedRefs},
+        Transaction,
+    },
     transaction::{Change, LogChange, RefEdit, RefLog},
     Target,
 };
@@ -15,27 +18,101 @@ impl<'s> Transaction<'s> {
     ///
     /// In this stage, we perform the following operations:
     ///
-    /// * update the ref log
+    /// * delete references and their reflogs which are in the way of new references, and lock the latter
+    /// * copy the reflog of renamed or copied references and update the ref log
     /// * move updated refs into place
     /// * delete reflogs and empty parent directories
     /// * delete packed refs
     /// * delete their corresponding reference (if applicable)
     ///   along with empty parent directories
+    /// * run the [`reference-transaction` hook][Transaction::reference_transaction_hook()] in the `committed` phase
+    ///
+    /// Initial transactions only write the reflogs and packed-refs.
     ///
     /// Note that transactions will be prepared automatically as needed.
-    pub fn commit(self, committer: &git_actor::Signature) -> Result<Vec<RefEdit>, Error> {
-        let mut updates = self.updates.expect("BUG: must call prepare before commit");
-        let delete_loose_refs = matches!(
-            self.packed_refs,
-            PackedRefs::DeletionsAndNonSymbolicUpdatesRemoveLooseSourceReference(_)
-        );
+    pub fn commit(mut self, committer: &git_actor::Signature) -> Result<Vec<RefEdit>, Error> {
+        let mut updates = self.updates.take().expect("BUG: must call prepare before commit");
+        // Once committing starts, the transaction can't be aborted anymore.
+        let notify_hook = std::mem::replace(&mut self.hook_prepared, false);
+        let delete_loose_refs = !self.initial
+            && matches!(
+                self.packed_refs,
+                PackedRefs::DeletionsAndNonSymbolicUpdatesRemoveLooseSourceReference(_)
+            );
+
+        // Keep the reflogs of renamed or copied references as the former are deleted before the new names are created.
+        let mut source_reflogs = Vec::with_capacity(updates.len());
+        for change in updates.iter() {
+            source_reflogs.push(match &change.reflog_source {
+                Some(source) => self
+                    .store
+                    .reflog_contents(source.to_ref())
+                    .map_err(|err| Error::CopyReflog {
+                        err,
+                        full_name: change.name(),
+                    })?,
+                None => None,
+            });
+        }
+
+        // References deleted to make room for new ones, or which need room for new ones, are removed first.
+        // Only then can the new references in their directory be locked.
+        let deleted = prepare::deleted_references(&updates);
+        let in_the_way: Vec<_> = updates
+            .iter()
+            .enumerate()
+            .filter(|(_, change)| {
+                let name = change.update.name.as_bstr();
+                deleted.iter().any(|deleted| deleted == name)
+                    && updates.iter().any(|other| {
+                        matches!(other.update.change, Change::Update { .. })
+                            && (prepare::is_parent_of(name, other.update.name.as_bstr())
+                                || prepare::is_parent_of(other.update.name.as_bstr(), name))
+                    })
+            })
+            .map(|(idx, _)| idx)
+            .collect();
+        for &idx in &in_the_way {
+            let change = &mut updates[idx];
+            self.delete_reflog(change)?;
+            self.delete_loose_reference(change)?;
+            // the lock may be in the directory that is to become a reference.
+            drop(change.lock.take());
+        }
+        for change in updates.iter_mut().filter(|change| change.lock_on_commit) {
+            // The lock of the deleted reference prevents others from creating references in its place.
+            Self::lock_ref_and_apply_change(
+                self.store,
+                git_lock::acquire::Fail::Immediately,
+                self.packed_transaction.as_ref().and_then(|t| t.buffer()),
+                change,
+            )
+            .map_err(|err| Error::LockAfterDeletion {
+                full_name: change.name(),
+                err: Box::new(err),
+            })?;
+        }
 
         // Perform updates first so live commits remain referenced
-        for change in updates.iter_mut() {
+        for (change, source_reflog) in updates.iter_mut().zip(source_reflogs) {
             assert!(!change.update.deref, "Deref mode is turned into splits and turned off");
             match &change.update.change {
                 // reflog first, then reference
                 Change::Update { log, new, expected } => {
+                    if self.initial {
+                        // Only packed-refs were locked, and all references are new and peeled.
+                        if let Target::Peeled(new_oid) = new {
+                            self.store.reflog_create_or_append_unlocked(
+                                &change.update.name.to_path(),
+                                None,
+                                new_oid,
+                                committer,
+                                log.message.as_ref(),
+                                log.force_create_reflog,
+                            )?;
+                        }
+                        continue;
+                    }
                     let lock = change.lock.take().expect("each ref is locked");
                     let (update_ref, update_reflog) = match log.mode {
                         RefLog::Only => (false, true),
@@ -45,12 +122,31 @@ impl<'s> Transaction<'s> {
                         match new {
                             Target::Symbolic(_) => {} // no reflog for symref changes
                             Target::Peeled(new_oid) => {
-                                let previous = match expected {
-                                    PreviousValue::MustExistAndMatch(Target::Peeled(oid)) => Some(oid.to_owned()),
-                                    _ => None,
-                                }
-                                .or(change.leaf_referent_previous_oid);
-                                let do_update = previous.as_ref().map_or(true, |previous| previous != new_oid);
+                                let (previous, do_update) = match &change.reflog_source {
+                                    // like git, log the unchanged value of the renamed or copied reference
+                                    Some(_) => {
+                                        if let Some(reflog) = source_reflog {
+                                            self.store
+                                                .reflog_replace(change.update.name.to_ref(), &reflog)
+                                                .map_err(|err| Error::CopyReflog {
+                                                    err,
+                                                    full_name: change.name(),
+                                                })?;
+                                        }
+                                        (Some(*new_oid), true)
+                                    }
+                                    None => {
+                                        let previous = match expected {
+                                            PreviousValue::MustExistAndMatch(Target::Peeled(oid)) => {
+                                                Some(oid.to_owned())
+                                            }
+                                            _ => None,
+                                        }
+                                        .or(change.leaf_referent_previous_oid);
+                                        let do_update = previous.as_ref().map_or(true, |previous| previous != new_oid);
+                                        (previous, do_update)
+                                    }
+                                };
                                 if do_update {
                                     self.store.reflog_create_or_append(
                                         &lock,
@@ -93,67 +189,90 @@ impl<'s> Transaction<'s> {
                     }
                 }
                 Change::Delete { .. } => {}
+                Change::Rename { .. } | Change::Copy { .. } => {
+                    unreachable!("BUG: renames and copies were split during preprocessing")
+                }
             }
         }
 
-        let reflog_root = self.store.reflog_root();
-        for change in updates.iter_mut() {
+        for (idx, change) in updates.iter().enumerate() {
             match &change.update.change {
-                Change::Update { .. } => {}
-                Change::Delete { .. } => {
-                    // Reflog deletion happens first in case it fails a ref without log is less terrible than
-                    // a log without a reference.
-                    let reflog_path = self.store.reflog_path(change.update.name.to_ref());
-                    if let Err(err) = std::fs::remove_file(&reflog_path) {
-                        if err.kind() != std::io::ErrorKind::NotFound {
-                            return Err(Error::DeleteReflog {
-                                err,
-                                full_name: change.name(),
-                            });
-                        }
-                    } else {
-                        git_tempfile::remove_dir::empty_upward_until_boundary(
-                            reflog_path.parent().expect("never without parent"),
-                            &reflog_root,
-                        )
-                        .ok();
-                    }
-                }
+                Change::Update { .. } | Change::Rename { .. } | Change::Copy { .. } => {}
+                Change::Delete { .. } if in_the_way.contains(&idx) => {}
+                Change::Delete { .. } => self.delete_reflog(change)?,
             }
         }
 
-        if let Some(t) = self.packed_transaction {
+        if let Some(t) = self.packed_transaction.take() {
             t.commit().map_err(Error::PackedTransactionCommit)?;
             // Always refresh ourselves right away to avoid races. We ignore errors as there may be many reasons this fails, and it's not
             // critical to be done here. In other words, the pack may be refreshed at a later time and then it might work.
             self.store.force_refresh_packed_buffer().ok();
         }
 
-        for change in updates.iter_mut() {
+        for (idx, change) in updates.iter_mut().enumerate() {
+            if in_the_way.contains(&idx) {
+                continue;
+            }
             let take_lock_and_delete = match &change.update.change {
                 Change::Update {
                     log: LogChange { mode, .. },
                     ..
                 } => delete_loose_refs && *mode == RefLog::AndReference,
                 Change::Delete { log: mode, .. } => *mode == RefLog::AndReference,
+                Change::Rename { .. } | Change::Copy { .. } => false,
             };
             if take_lock_and_delete {
                 let lock = change.lock.take().expect("lock must still be present in delete mode");
-                let reference_path = self.store.reference_path(change.update.name.to_path().as_ref());
-                if let Err(err) = std::fs::remove_file(reference_path) {
-                    if err.kind() != std::io::ErrorKind::NotFound {
-                        return Err(Error::DeleteReference {
-                            err,
-                            full_name: change.name(),
-                        });
-                    }
-                }
+                self.delete_loose_reference(change)?;
                 drop(lock)
             }
         }
+        if notify_hook {
+            // Like in git, the transaction is complete and its outcome can't be affected by the hook anymore.
+            self.run_hook(hook::State::Committed, &updates).ok();
+        }
         Ok(updates.into_iter().map(|edit| edit.update).collect())
     }
+
+    /// Delete the reflog of the reference deleted by `change` along with empty parent directories.
+    ///
+    /// It happens first in case it fails, as a ref without log is less terrible than a log without a reference.
+    fn delete_reflog(&self, change: &Edit) -> Result<(), Error> {
+        let reflog_root = self.store.reflog_root(&change.update.name.to_path());
+        let reflog_path = self.store.reflog_path(change.update.name.to_ref());
+        if let Err(err) = std::fs::remove_file(&reflog_path) {
+            if err.kind() != std::io::ErrorKind::NotFound {
+                return Err(Error::DeleteReflog {
+                    err,
+                    full_name: change.name(),
+                });
+            }
+        } else {
+            git_tempfile::remove_dir::empty_upward_until_boundary(
+                reflog_path.parent().expect("never without parent"),
+                &reflog_root,
+            )
+            .ok();
+        }
+        Ok(())
+    }
+
+    /// Delete the loose file of the reference changed by `change`, if it exists.
+    fn delete_loose_reference(&self, change: &Edit) -> Result<(), Error> {
+        let reference_path = self.store.reference_path(change.update.name.to_path().as_ref());
+        if let Err(err) = std::fs::remove_file(reference_path) {
+            if err.kind() != std::io::ErrorKind::NotFound {
+                return Err(Error::DeleteReference {
+                    err,
+                    full_name: change.name(),
+                });
+            }
+        }
+        Ok(())
+    }
 }
+
 mod error {
     use git_object::bstr::BString;
     use quick_error::quick_error;
@@ -185,6 +304,14 @@ mod error {
                 display("The reflog of reference '{}' could not be deleted", full_name)
                 source(err)
             }
+            CopyReflog{ full_name: BString, err: std::io::Error } {
+                display("The reflog of the original reference could not be copied to reference '{}'", full_name)
+                source(err)
+            }
+            LockAfterDeletion{ full_name: BString, err: Box<crate::file::transaction::prepare::Error> } {
+                display("The reference '{}' could not be locked after deleting the reference in its place", full_name)
+                source(err)
+            }
             CreateOrUpdateRefLog(err: file::log::create_or_update::Error) {
                 display("The reflog could not be created or updated")
                 from()
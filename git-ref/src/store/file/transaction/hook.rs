@@ -0,0 +1,105 @@
+use std::{
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::{
+    store_impl::file::transaction::{Edit, Hook},
+    transaction::{Change, LogChange, RefLog},
+    TargetRef,
+};
+
+/// The phases of a transaction in which the hook is invoked, passed to it as its only argument.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(in crate::store_impl::file) enum State {
+    /// All references are locked, and the hook can abort the transaction by exiting with a non-zero status.
+    Prepared,
+    /// The transaction was committed.
+    Committed,
+    /// The prepared transaction was dropped without committing it.
+    Aborted,
+}
+
+impl State {
+    fn as_str(&self) -> &'static str {
+        match self {
+            State::Prepared => "prepared",
+            State::Committed => "committed",
+            State::Aborted => "aborted",
+        }
+    }
+}
+
+impl Hook {
+    /// Run the hook in `state` with `input` as its standard input, with `git_dir` being the repository the hook is
+    /// run for. Return true if it doesn't exist, if there are no changed references or if it exited successfully.
+    pub(in crate::store_impl::file) fn run(&self, git_dir: &Path, state: State, input: &[u8]) -> std::io::Result<bool> {
+        if input.is_empty()
+            || !self
+                .path
+                .metadata()
+                .map_or(false, |m| git_features::fs::is_executable(&m))
+        {
+            return Ok(true);
+        }
+        let mut child = Command::new(&self.path)
+            .arg(state.as_str())
+            .current_dir(&self.working_dir)
+            .env("GIT_DIR", git_dir)
+            .stdin(Stdio::piped())
+            .spawn()?;
+        let mut stdin = child.stdin.take().expect("configured");
+        match stdin.write_all(input) {
+            // like git, allow the hook to not read all of its input
+            Err(err) if err.kind() != std::io::ErrorKind::BrokenPipe => return Err(err),
+            _ => {}
+        }
+        drop(stdin);
+        Ok(child.wait()?.success())
+    }
+}
+
+/// Return the input for the hook, which is a line `<old-value> SP <new-value> SP <ref-name> LF` for each reference
+/// that is changed by `edits`, with the null id denoting a missing reference or unknown previous value, and `ref:<name>`
+/// a symbolic one.
+pub(in crate::store_impl::file) fn input(edits: &[Edit], object_hash: git_hash::Kind) -> Vec<u8> {
+    let null = object_hash.null_ref();
+    let mut out = Vec::new();
+    for edit in edits {
+        let new = match &edit.update.change {
+            Change::Update {
+                log: LogChange { mode: RefLog::Only, .. },
+                ..
+            }
+            | Change::Delete { log: RefLog::Only, .. } => continue,
+            Change::Update { new, .. } => new.to_ref(),
+            Change::Delete { .. } => TargetRef::Peeled(null),
+            Change::Rename { .. } | Change::Copy { .. } => {
+                unreachable!("BUG: renames and copies were split during preprocessing")
+            }
+        };
+        // like git, pass the value expected by the caller, not the one the reference actually had.
+        let previous = edit
+            .expected_previous
+            .as_ref()
+            .map_or(TargetRef::Peeled(null), |previous| previous.to_ref());
+        write_value(&mut out, previous);
+        out.push(b' ');
+        write_value(&mut out, new);
+        out.push(b' ');
+        out.extend_from_slice(edit.update.name.as_bstr());
+        out.push(b'\n');
+    }
+    out
+}
+
+fn write_value(out: &mut Vec<u8>, value: TargetRef<'_>) {
+    match value {
+        TargetRef::Peeled(oid) => write!(out, "{}", oid).expect("write to memory works"),
+        TargetRef::Symbolic(name) => {
+            out.extend_from_slice(b"ref:");
+            out.extend_from_slice(name.as_bstr());
+        }
+    }
+}
@@ -1,9 +1,12 @@
+use std::path::PathBuf;
+
 use git_hash::ObjectId;
 use git_object::bstr::BString;
 
 use crate::{
     store_impl::{file, file::Transaction},
     transaction::RefEdit,
+    FullName, Target,
 };
 
 /// A function receiving an object id to resolve, returning its decompressed bytes.
@@ -20,9 +23,15 @@ pub enum PackedRefs {
     /// Only propagate deletions of references. This is the default
     DeletionsOnly,
     /// Propagate deletions as well as updates to references which are peeled, that is contain an object id
+    ///
+    /// If the store doesn't contain any reference yet and all edits create peeled references, the transaction is
+    /// considered _initial_ and writes them all into packed-refs in one pass, without creating a lock for each of them.
+    /// This is the case when cloning, and makes creating many references much faster. Reflogs are written as usual.
     DeletionsAndNonSymbolicUpdates(Box<FindObjectFn>),
     /// Propagate deletions as well as updates to references which are peeled, that is contain an object id. Furthermore delete the
     /// reference which is originally updated if it exists. If it doesn't, the new value will be written into the packed ref right away.
+    ///
+    /// Initial transactions are handled like in [`DeletionsAndNonSymbolicUpdates`][PackedRefs::DeletionsAndNonSymbolicUpdates].
     DeletionsAndNonSymbolicUpdatesRemoveLooseSourceReference(Box<FindObjectFn>),
 }
 
@@ -32,6 +41,18 @@ impl Default for PackedRefs {
     }
 }
 
+/// The `reference-transaction` hook to invoke during a transaction, see
+/// [`Transaction::reference_transaction_hook()`].
+#[derive(Debug, Clone)]
+pub struct Hook {
+    /// The path to the hook program, typically `<hooks-dir>/reference-transaction`.
+    ///
+    /// It is ignored if it doesn't exist or isn't executable.
+    pub path: PathBuf,
+    /// The directory to run the hook in, which is the work tree, or the git directory in bare repositories.
+    pub working_dir: PathBuf,
+}
+
 #[derive(Debug)]
 pub(in crate::store_impl::file) struct Edit {
     update: RefEdit,
@@ -42,6 +63,15 @@ pub(in crate::store_impl::file) struct Edit {
     /// For symbolic refs, this is the previous OID to put into the reflog instead of our own previous value. It's the
     /// peeled value of the leaf referent.
     leaf_referent_previous_oid: Option<ObjectId>,
+    /// Set if this update creates a renamed or copied reference, with the reflog of the original reference to be copied
+    /// to ours before adding our own entry.
+    reflog_source: Option<FullName>,
+    /// If true, this update creates a reference within the directory of a reference deleted in the same transaction, which
+    /// can only be locked once the latter is gone.
+    lock_on_commit: bool,
+    /// The previous value the caller expected, before it is replaced with the actual value of the reference while
+    /// preparing, as passed to the hook.
+    expected_previous: Option<Target>,
 }
 
 impl Edit {
@@ -76,6 +106,9 @@ impl file::Store {
             packed_transaction: None,
             updates: None,
             packed_refs: PackedRefs::default(),
+            initial: false,
+            hook: None,
+            hook_prepared: false,
         }
     }
 }
@@ -86,6 +119,41 @@ impl<'s> Transaction<'s> {
         self.packed_refs = packed_refs;
         self
     }
+
+    /// Invoke `hook` in each phase of the transaction just like git does.
+    ///
+    /// It's run with `prepared` as argument once all references are locked, and exiting with a non-zero status
+    /// aborts the transaction. Thereafter, it's run with `committed` once the transaction was committed, or with
+    /// `aborted` if it rejected the transaction or if the prepared transaction is dropped instead. Its standard input receives a line
+    /// `<old-value> SP <new-value> SP <ref-name> LF` for each changed reference, with `ref:<name>` for symbolic references.
+    /// The null id is used for deleted references and if the edit didn't expect a specific previous value, like in git.
+    pub fn reference_transaction_hook(mut self, hook: Option<Hook>) -> Self {
+        self.hook = hook;
+        self
+    }
+
+    /// Run the hook, if there is one, in `state` for all edits, returning true if it doesn't exist or succeeded.
+    fn run_hook(&self, state: hook::State, updates: &[Edit]) -> std::io::Result<bool> {
+        match &self.hook {
+            Some(hook) => hook.run(self.store.base(), state, &hook::input(updates, self.store.object_hash)),
+            None => Ok(true),
+        }
+    }
+}
+
+impl<'s> Drop for Transaction<'s> {
+    fn drop(&mut self) {
+        if self.hook_prepared {
+            let updates = self.updates.take().unwrap_or_default();
+            let input = hook::input(&updates, self.store.object_hash);
+            // release all locks before notifying the hook, like git does.
+            drop(updates);
+            self.packed_transaction.take();
+            if let Some(hook) = &self.hook {
+                hook.run(self.store.base(), hook::State::Aborted, &input).ok();
+            }
+        }
+    }
 }
 
 ///
@@ -93,3 +161,5 @@ pub mod prepare;
 
 ///
 pub mod commit;
+
+mod hook;
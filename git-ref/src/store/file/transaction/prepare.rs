@@ -1,19 +1,23 @@
+use std::{convert::TryInto, path::PathBuf};
+
+use git_object::bstr::{BStr, BString, ByteSlice};
+
 use crate::{
     packed,
     store_impl::{
         file,
         file::{
             loose,
-            transaction::{Edit, PackedRefs},
+            transaction::{hook, Edit, PackedRefs},
             Transaction,
         },
     },
     transaction::{Change, LogChange, RefEdit, RefEditsExt, RefLog},
-    Reference, Target,
+    FullName, FullNameRef, Reference, Target,
 };
 
 impl<'s> Transaction<'s> {
-    fn lock_ref_and_apply_change(
+    pub(in crate::store_impl::file) fn lock_ref_and_apply_change(
         store: &file::Store,
         lock_fail_mode: git_lock::acquire::Fail,
         packed: Option<&packed::Buffer>,
@@ -50,17 +54,15 @@ impl<'s> Transaction<'s> {
                 (None, None) => Ok(None),
                 (maybe_loose, _) => Ok(maybe_loose),
             });
+        let (ref_base, ref_path) = store.reference_path_with_base(&relative_path);
         let lock = match &mut change.update.change {
             Change::Delete { expected, .. } => {
-                let lock = git_lock::Marker::acquire_to_hold_resource(
-                    store.reference_path(&relative_path),
-                    lock_fail_mode,
-                    Some(store.base.to_owned()),
-                )
-                .map_err(|err| Error::LockAcquire {
-                    err,
-                    full_name: "borrowchk wont allow change.name()".into(),
-                })?;
+                let lock =
+                    git_lock::Marker::acquire_to_hold_resource(ref_path, lock_fail_mode, Some(ref_base.to_owned()))
+                        .map_err(|err| Error::LockAcquire {
+                            err,
+                            full_name: "borrowchk wont allow change.name()".into(),
+                        })?;
                 let existing_ref = existing_ref?;
                 match (&expected, &existing_ref) {
                     (PreviousValue::MustNotExist, _) => {
@@ -96,16 +98,16 @@ impl<'s> Transaction<'s> {
 
                 lock
             }
+            Change::Rename { .. } | Change::Copy { .. } => {
+                unreachable!("BUG: renames and copies are split into updates and deletions during preprocessing")
+            }
             Change::Update { expected, new, .. } => {
-                let mut lock = git_lock::File::acquire_to_update_resource(
-                    store.reference_path(&relative_path),
-                    lock_fail_mode,
-                    Some(store.base.to_owned()),
-                )
-                .map_err(|err| Error::LockAcquire {
-                    err,
-                    full_name: "borrowchk wont allow change.name() and this will be corrected by caller".into(),
-                })?;
+                let mut lock =
+                    git_lock::File::acquire_to_update_resource(ref_path, lock_fail_mode, Some(ref_base.to_owned()))
+                        .map_err(|err| Error::LockAcquire {
+                            err,
+                            full_name: "borrowchk wont allow change.name() and this will be corrected by caller".into(),
+                        })?;
 
                 let existing_ref = existing_ref?;
                 match (&expected, &existing_ref) {
@@ -186,8 +188,57 @@ impl<'s> Transaction<'s> {
                 lock: None,
                 parent_index: None,
                 leaf_referent_previous_oid: None,
+                reflog_source: None,
+                lock_on_commit: false,
+                expected_previous: None,
             })
             .collect();
+        if updates
+            .iter()
+            .any(|edit| matches!(edit.update.change, Change::Rename { .. } | Change::Copy { .. }))
+        {
+            for edit in &updates {
+                if let Change::Rename { .. } = edit.update.change {
+                    if let Some(git_dir) = worktree_with_head_pointing_to(store, &edit.update.name)? {
+                        return Err(Error::RenamedReferenceCheckedOut {
+                            full_name: edit.name(),
+                            git_dir,
+                        });
+                    }
+                }
+            }
+            let packed = store.assure_packed_refs_uptodate()?;
+            updates
+                .extend_with_splits_of_renames_and_copies(
+                    |name| {
+                        store
+                            .find_existing_inner(name, packed.as_deref())
+                            .map(|r| r.target)
+                            .ok()
+                    },
+                    |update, reflog_source| Edit {
+                        update,
+                        lock: None,
+                        parent_index: None,
+                        leaf_referent_previous_oid: None,
+                        reflog_source,
+                        lock_on_commit: false,
+                        expected_previous: None,
+                    },
+                )
+                .map_err(Error::PreprocessingFailed)?;
+            let deleted = deleted_references(&updates);
+            for edit in updates.iter().filter(|edit| edit.reflog_source.is_some()) {
+                if let Some(existing) =
+                    conflicting_reference(store, edit.update.name.to_ref(), packed.as_deref(), &deleted)?
+                {
+                    return Err(Error::NameConflict {
+                        full_name: edit.name(),
+                        existing,
+                    });
+                }
+            }
+        }
         updates
             .pre_process(
                 |name| {
@@ -202,9 +253,46 @@ impl<'s> Transaction<'s> {
                     lock: None,
                     parent_index: Some(idx),
                     leaf_referent_previous_oid: None,
+                    reflog_source: None,
+                    lock_on_commit: false,
+                    expected_previous: None,
                 },
             )
             .map_err(Error::PreprocessingFailed)?;
+        for change in updates.iter_mut() {
+            change.expected_previous = change.update.change.previous_value().map(Into::into);
+        }
+
+        if let Some((full_name, existing)) = conflicting_updates(&updates) {
+            return Err(Error::NameConflict { full_name, existing });
+        }
+
+        let packs_updates = matches!(
+            self.packed_refs,
+            PackedRefs::DeletionsAndNonSymbolicUpdates(_)
+                | PackedRefs::DeletionsAndNonSymbolicUpdatesRemoveLooseSourceReference(_)
+        );
+        if packs_updates && store.namespace.is_none() && !updates.is_empty() && updates.iter().all(creates_packable_ref)
+        {
+            let transaction = store.packed_transaction(lock_fail_mode).map_err(|err| match err {
+                file::packed::transaction::Error::BufferOpen(err) => Error::from(err),
+                file::packed::transaction::Error::TransactionLock(err) => Error::PackedTransactionAcquire(err),
+            })?;
+            // With packed-refs locked, no other transaction can write into it, and we assume no one adds loose
+            // references to a store which doesn't have any either.
+            if !has_references(store, transaction.buffer())? {
+                self.packed_transaction = Some(match &mut self.packed_refs {
+                    PackedRefs::DeletionsAndNonSymbolicUpdatesRemoveLooseSourceReference(f)
+                    | PackedRefs::DeletionsAndNonSymbolicUpdates(f) => {
+                        transaction.prepare(updates.iter().map(|edit| edit.update.clone()), f)?
+                    }
+                    PackedRefs::DeletionsOnly => unreachable!("BUG: we only get here if updates are packed"),
+                });
+                self.initial = true;
+                self.updates = Some(updates);
+                return self.run_prepared_hook();
+            }
+        }
 
         let mut maybe_updates_for_packed_refs = match self.packed_refs {
             PackedRefs::DeletionsAndNonSymbolicUpdates(_)
@@ -221,8 +309,13 @@ impl<'s> Transaction<'s> {
                         ..
                     } => mode,
                     Change::Delete { log, .. } => log,
+                    Change::Rename { .. } | Change::Copy { .. } => {
+                        unreachable!("BUG: renames and copies were split during preprocessing")
+                    }
                 };
                 if log_mode == RefLog::Only {
+                    // The reference isn't changed, but its current value may have to be checked.
+                    needs_packed_refs_lookups |= edit.update.change.previous_value().is_some();
                     continue;
                 }
                 if let Some(ref mut num_updates) = maybe_updates_for_packed_refs {
@@ -295,8 +388,18 @@ impl<'s> Transaction<'s> {
             }
         }
 
+        let deleted = deleted_references(&updates);
+        for change in updates.iter_mut() {
+            change.lock_on_commit = matches!(change.update.change, Change::Update { .. })
+                && deleted
+                    .iter()
+                    .any(|deleted| is_parent_of(deleted.as_bstr(), change.update.name.as_bstr()));
+        }
         for cid in 0..updates.len() {
             let change = &mut updates[cid];
+            if change.lock_on_commit {
+                continue;
+            }
             if let Err(err) = Self::lock_ref_and_apply_change(
                 self.store,
                 lock_fail_mode,
@@ -339,11 +442,170 @@ impl<'s> Transaction<'s> {
             }
         }
         self.updates = Some(updates);
+        self.run_prepared_hook()
+    }
+
+    /// Run the hook in the `prepared` phase once all references are locked, failing if it rejects the transaction.
+    ///
+    /// Like git, a rejecting hook is run again with `aborted` once the transaction is dropped along with its locks.
+    fn run_prepared_hook(mut self) -> Result<Self, Error> {
+        if self.hook.is_some() {
+            let updates = self.updates.as_deref().expect("set once prepared");
+            let accepted = self.run_hook(hook::State::Prepared, updates).map_err(Error::Hook)?;
+            self.hook_prepared = true;
+            if !accepted {
+                return Err(Error::HookRejected);
+            }
+        }
         Ok(self)
     }
 }
 
+/// Return true if `edit` creates a peeled reference in the shared part of the store, which can be written into packed-refs
+/// directly as part of an initial transaction.
+fn creates_packable_ref(edit: &Edit) -> bool {
+    match &edit.update.change {
+        Change::Update {
+            log,
+            expected,
+            new: Target::Peeled(_),
+        } => {
+            log.mode == RefLog::AndReference
+                && matches!(expected, PreviousValue::Any | PreviousValue::MustNotExist)
+                && !file::is_per_worktree(&edit.update.name.to_path())
+        }
+        _ => false,
+    }
+}
+
+/// Return true if there is any loose reference in `store` or any packed reference in `packed`.
+fn has_references(store: &file::Store, packed: Option<&packed::Buffer>) -> Result<bool, Error> {
+    let has_loose_references = match store.loose_iter() {
+        Ok(mut references) => references.next().is_some(),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => false,
+        Err(err) => return Err(err.into()),
+    };
+    Ok(has_loose_references
+        || packed.map_or(false, |packed| {
+            packed.iter().map_or(true, |mut references| references.next().is_some())
+        }))
+}
+
+/// Return the git dir of a worktree other than the one of `store` whose `HEAD` is a symbolic reference to `name`.
+///
+/// Only the `HEAD` of `store` can be updated when renaming the reference it points to, so the `HEAD` of all other
+/// worktrees sharing the same common dir is checked to not leave it pointing to a reference that doesn't exist anymore.
+fn worktree_with_head_pointing_to(store: &file::Store, name: &FullName) -> std::io::Result<Option<PathBuf>> {
+    let common_dir = store.common_dir_resolved();
+    let mut git_dirs = vec![common_dir.to_owned()];
+    match std::fs::read_dir(common_dir.join("worktrees")) {
+        Ok(entries) => {
+            for entry in entries {
+                git_dirs.push(entry?.path());
+            }
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err),
+    }
+    let head: FullName = "HEAD".try_into().expect("valid name");
+    for git_dir in git_dirs.into_iter().filter(|git_dir| git_dir != store.base()) {
+        let contents = match std::fs::read(git_dir.join("HEAD")) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+        if let Ok(head) = loose::Reference::try_from_path(head.clone(), &contents) {
+            if head.target.as_name() == Some(name.as_bstr()) {
+                return Ok(Some(git_dir));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Return the names of all references deleted by `updates`.
+pub(in crate::store_impl::file) fn deleted_references(updates: &[Edit]) -> Vec<BString> {
+    updates
+        .iter()
+        .filter(|edit| {
+            matches!(
+                edit.update.change,
+                Change::Delete {
+                    log: RefLog::AndReference,
+                    ..
+                }
+            )
+        })
+        .map(Edit::name)
+        .collect()
+}
+
+/// Return true if the reference named `parent` would be a directory containing the reference named `name`.
+pub(in crate::store_impl::file) fn is_parent_of(parent: &BStr, name: &BStr) -> bool {
+    name.len() > parent.len() && name.starts_with(parent) && name[parent.len()] == b'/'
+}
+
+/// Return the names of two references written by `updates` of which the first can't be created as the second would have
+/// to be its directory.
+fn conflicting_updates(updates: &[Edit]) -> Option<(BString, BString)> {
+    let written: std::collections::BTreeSet<_> = updates
+        .iter()
+        .filter(|edit| {
+            matches!(
+                edit.update.change,
+                Change::Update {
+                    log: LogChange {
+                        mode: RefLog::AndReference,
+                        ..
+                    },
+                    ..
+                }
+            )
+        })
+        .map(|edit| edit.update.name.as_bstr())
+        .collect();
+    written.iter().find_map(|name| {
+        name.find_iter("/")
+            .map(|end| &name[..end])
+            .find(|parent| written.contains(parent.as_bstr()))
+            .map(|parent| ((*name).to_owned(), parent.to_owned()))
+    })
+}
+
+/// Return the name of an existing reference that prevents a reference named `name` from being created, as it would have to
+/// be a directory to contain our reference, or because it is contained in a directory named like our reference.
+///
+/// References in `deleted` are ignored as they are removed before our reference is created.
+fn conflicting_reference(
+    store: &file::Store,
+    name: FullNameRef<'_>,
+    packed: Option<&packed::Buffer>,
+    deleted: &[BString],
+) -> Result<Option<BString>, Error> {
+    let name = name.as_bstr();
+    let is_deleted = |name: &BStr| deleted.iter().any(|deleted| deleted == name);
+    for end in name.find_iter("/").skip(1) {
+        let parent = &name[..end];
+        if is_deleted(parent.as_bstr()) {
+            continue;
+        }
+        let exists_loose = store
+            .ref_contents(parent.to_path().expect("UTF-8 conversion always succeeds"))?
+            .is_some();
+        if exists_loose || packed.map_or(Ok(None), |packed| packed.try_find(parent))?.is_some() {
+            return Ok(Some(parent.to_owned()));
+        }
+    }
+    let contained = store
+        .iter_prefixed_packed(name.to_path().expect("UTF-8 conversion always succeeds"), packed)?
+        .filter_map(Result::ok)
+        .find(|r| is_parent_of(name, r.name.as_bstr()) && !is_deleted(r.name.as_bstr()));
+    Ok(contained.map(|r| r.name.into_inner()))
+}
+
 mod error {
+    use std::path::PathBuf;
+
     use git_object::bstr::BString;
     use quick_error::quick_error;
 
@@ -401,6 +663,19 @@ mod error {
             ReferenceOutOfDate { full_name: BString, expected: Target, actual: Target } {
                 display("The reference '{}' should have content {}, actual content was {}", full_name, expected, actual)
             }
+            NameConflict { full_name: BString, existing: BString } {
+                display("The reference '{}' cannot be created as it conflicts with the existing reference '{}'", full_name, existing)
+            }
+            Hook(err: std::io::Error) {
+                display("The reference-transaction hook could not be run")
+                source(err)
+            }
+            RenamedReferenceCheckedOut { full_name: BString, git_dir: PathBuf } {
+                display("The reference '{}' can't be renamed as it is checked out in the worktree with git dir at '{}'", full_name, git_dir.display())
+            }
+            HookRejected {
+                display("The reference-transaction hook aborted the transaction in the 'prepared' phase")
+            }
             ReferenceDecode(err: file::loose::reference::decode::Error) {
                 display("Could not read reference")
                 from()
@@ -1,6 +1,8 @@
 use std::convert::TryInto;
 
-use crate::{store, PartialNameRef, Reference};
+use git_object::bstr::{BString, ByteSlice, ByteVec};
+
+use crate::{reftable, store, Namespace, PartialNameRef, Reference};
 
 mod error {
     use std::convert::Infallible;
@@ -17,6 +19,11 @@ mod error {
                 from()
                 source(err)
             }
+            Reftable(err: crate::reftable::stack::Error) {
+                display("An error occurred while finding a reference in the reftable stack")
+                from()
+                source(err)
+            }
             RefnameValidation(err: crate::name::Error) {
                 display("The ref name or path is not a valid ref name")
                 from()
@@ -37,21 +44,78 @@ pub use error::Error;
 use crate::store::handle;
 
 impl store::Handle {
-    /// TODO: actually implement this with handling of the packed buffer.
+    /// Find a single reference by the given partial name, following the lookup rules of
+    /// [`file::Store::try_find()`][crate::file::Store::try_find()] for all storage formats.
+    ///
+    /// Returns `Ok(None)` if no such ref exists.
     pub fn try_find<'a, Name, E>(&self, partial: Name) -> Result<Option<Reference>, Error>
     where
         Name: TryInto<PartialNameRef<'a>, Error = E>,
         Error: From<E>,
     {
-        let _name = partial.try_into()?;
+        let name = partial.try_into()?;
         match &self.state {
-            handle::State::Loose { store: _, .. } => {
-                todo!()
+            handle::State::Loose { store } => Ok(store.try_find(name)?),
+            handle::State::Reftable { store, namespace } => {
+                // see changes made by other writers, which is cheap as unchanged tables are kept.
+                let mut store = store.clone();
+                store.reload()?;
+                Ok(find_in_stack(&store, name, namespace.as_ref())?)
             }
         }
     }
 }
 
+/// Try the same full names as the loose reference lookup in the `stack`, with all names prefixed with `namespace`.
+fn find_in_stack(
+    stack: &reftable::Stack,
+    partial: PartialNameRef<'_>,
+    namespace: Option<&Namespace>,
+) -> Result<Option<Reference>, reftable::stack::Error> {
+    let name = partial.as_bstr();
+    let mut candidates = Vec::<BString>::new();
+    if !name.contains(&b'/') && name.iter().all(|b| b.is_ascii_uppercase() || *b == b'_') {
+        candidates.push(name.to_owned());
+    }
+    let prefix = if name == "refs" || name.starts_with(b"refs/") {
+        ""
+    } else {
+        "refs/"
+    };
+    for inbetween in &["", "tags/", "heads/", "remotes/"] {
+        let mut candidate = BString::from(prefix);
+        candidate.push_str(inbetween);
+        candidate.push_str(name);
+        candidates.push(candidate);
+    }
+    let mut remote_head = BString::from(prefix);
+    remote_head.push_str("remotes/");
+    remote_head.push_str(name);
+    remote_head.push_str("/HEAD");
+    candidates.push(remote_head);
+
+    for candidate in candidates {
+        let full_name = match namespace {
+            Some(namespace) => {
+                let mut full_name = namespace.as_bstr().to_owned();
+                full_name.push_str(candidate);
+                full_name
+            }
+            None => candidate,
+        };
+        if let Some(mut r) = stack
+            .find(full_name.as_bstr())?
+            .and_then(reftable::RefRecord::into_reference)
+        {
+            if let Some(namespace) = namespace {
+                r.strip_namespace(namespace);
+            }
+            return Ok(Some(r));
+        }
+    }
+    Ok(None)
+}
+
 mod existing {
     mod error {
         use std::path::PathBuf;
@@ -83,18 +147,19 @@ mod existing {
 
     impl store::Handle {
         /// Similar to [`crate::file::Store::find()`] but a non-existing ref is treated as error.
-        pub fn find<'a, Name, E>(&self, _partial: Name) -> Result<Reference, Error>
+        pub fn find<'a, Name, E>(&self, partial: Name) -> Result<Reference, Error>
         where
             Name: TryInto<PartialNameRef<'a>, Error = E>,
             crate::name::Error: From<E>,
         {
-            todo!()
-            // match self.try_find(partial) {}
-            // match self.find_one_with_verified_input(path.to_partial_path().as_ref(), packed) {
-            //     Ok(Some(r)) => Ok(r),
-            //     Ok(None) => Err(Error::NotFound(path.to_partial_path().into_owned())),
-            //     Err(err) => Err(err.into()),
-            // }
+            let path = partial
+                .try_into()
+                .map_err(|err| Error::Find(crate::store::find::Error::RefnameValidation(err.into())))?;
+            match self.try_find(path.clone()) {
+                Ok(Some(r)) => Ok(r),
+                Ok(None) => Err(Error::NotFound(path.to_partial_path().into_owned())),
+                Err(err) => Err(err.into()),
+            }
         }
     }
 }
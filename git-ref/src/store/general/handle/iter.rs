@@ -0,0 +1,150 @@
+use std::path::Path;
+
+use git_object::bstr::ByteSlice;
+
+use crate::{file, reftable, store, Namespace, Reference};
+
+mod error {
+    use quick_error::quick_error;
+
+    quick_error! {
+        /// The error returned by [`store::Handle::iter()`][crate::store::Handle::iter()] and the iterators it produces.
+        #[derive(Debug)]
+        #[allow(missing_docs)]
+        pub enum Error {
+            PackedOpen(err: crate::packed::buffer::open::Error) {
+                display("The packed-refs file could not be opened")
+                from()
+                source(err)
+            }
+            Traversal(err: std::io::Error) {
+                display("The iteration over loose references could not be started")
+                from()
+                source(err)
+            }
+            Loose(err: crate::file::iter::loose_then_packed::Error) {
+                display("An error occurred while iterating loose and packed references")
+                from()
+                source(err)
+            }
+            Reftable(err: crate::reftable::stack::Error) {
+                display("An error occurred while iterating references in the reftable stack")
+                from()
+                source(err)
+            }
+        }
+    }
+}
+
+pub use error::Error;
+
+use crate::store::handle;
+
+/// An intermediate structure to hold shared state alive long enough for iteration to happen.
+#[must_use = "Iterators should be obtained from this platform"]
+pub struct Platform<'s> {
+    state: PlatformState<'s>,
+}
+
+enum PlatformState<'s> {
+    Loose(file::iter::Platform<'s>),
+    Reftable {
+        stack: reftable::Stack,
+        namespace: Option<&'s Namespace>,
+    },
+}
+
+/// An iterator over references of any storage format, sorted by their name.
+pub struct Iter<'p> {
+    state: IterState<'p>,
+}
+
+enum IterState<'p> {
+    Loose(Box<file::iter::LooseThenPacked<'p, 'p>>),
+    Reftable {
+        refs: reftable::stack::Refs<'p>,
+        namespace: Option<&'p Namespace>,
+    },
+}
+
+impl store::Handle {
+    /// Return a platform to obtain iterators over all references, or prefixed ones, for all storage formats.
+    pub fn iter(&self) -> Result<Platform<'_>, Error> {
+        Ok(Platform {
+            state: match &self.state {
+                handle::State::Loose { store } => PlatformState::Loose(store.iter()?),
+                handle::State::Reftable { store, namespace } => {
+                    // see changes made by other writers, which is cheap as unchanged tables are kept.
+                    let mut stack = store.clone();
+                    stack.reload()?;
+                    PlatformState::Reftable {
+                        stack,
+                        namespace: namespace.as_ref(),
+                    }
+                }
+            },
+        })
+    }
+}
+
+impl<'s> Platform<'s> {
+    /// Return an iterator over all references, sorted by their name.
+    pub fn all(&self) -> Result<Iter<'_>, Error> {
+        Ok(Iter {
+            state: match &self.state {
+                PlatformState::Loose(platform) => IterState::Loose(Box::new(platform.all()?)),
+                PlatformState::Reftable { stack, namespace } => IterState::Reftable {
+                    refs: match namespace {
+                        Some(namespace) => stack.refs_prefixed(namespace.as_bstr())?,
+                        None => stack.refs()?,
+                    },
+                    namespace: *namespace,
+                },
+            },
+        })
+    }
+
+    /// As [`all()`][Platform::all()], but filters by `prefix`, i.e. "refs/heads".
+    ///
+    /// Please note that reftables match `prefix` byte by byte, while loose references match it by directory.
+    pub fn prefixed(&self, prefix: impl AsRef<Path>) -> Result<Iter<'_>, Error> {
+        Ok(Iter {
+            state: match &self.state {
+                PlatformState::Loose(platform) => IterState::Loose(Box::new(platform.prefixed(prefix)?)),
+                PlatformState::Reftable { stack, namespace } => {
+                    let prefix = match *namespace {
+                        Some(namespace) => file::path_to_name(namespace.clone().into_namespaced_prefix(prefix)),
+                        None => file::path_to_name(prefix.as_ref()),
+                    };
+                    IterState::Reftable {
+                        refs: stack.refs_prefixed(prefix.as_bstr())?,
+                        namespace: *namespace,
+                    }
+                }
+            },
+        })
+    }
+}
+
+impl<'p> Iterator for Iter<'p> {
+    type Item = Result<Reference, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.state {
+            IterState::Loose(iter) => iter.next().map(|r| r.map_err(Into::into)),
+            IterState::Reftable { refs, namespace } => loop {
+                match refs.next()? {
+                    Ok(record) => {
+                        if let Some(mut r) = record.into_reference() {
+                            if let Some(namespace) = namespace {
+                                r.strip_namespace(namespace);
+                            }
+                            return Some(Ok(r));
+                        }
+                    }
+                    Err(err) => return Some(Err(err.into())),
+                }
+            },
+        }
+    }
+}
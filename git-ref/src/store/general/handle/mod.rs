@@ -1,9 +1,14 @@
-#![allow(dead_code)]
 use crate::{store, Namespace};
 
 #[derive(Clone)]
 pub(crate) enum State {
-    Loose { store: crate::file::Store },
+    Loose {
+        store: crate::file::Store,
+    },
+    Reftable {
+        store: crate::reftable::Stack,
+        namespace: Option<Namespace>,
+    },
 }
 
 impl crate::Store {
@@ -28,6 +33,10 @@ impl crate::Store {
                         store
                     },
                 },
+                store::State::Reftable { store } => store::handle::State::Reftable {
+                    store: store.clone(),
+                    namespace,
+                },
             },
         }
     }
@@ -36,8 +45,8 @@ impl crate::Store {
 ///
 pub mod find;
 
-mod iter {
-    // impl store::Handle {
-    //     pub fn iter<'p, 's>(&'s self, packed: Option<&'p packed::Buffer>) -> std::io::Result<LooseThenPacked<'p, 's>> {
-    // }
-}
+///
+pub mod iter;
+
+///
+pub mod transaction;
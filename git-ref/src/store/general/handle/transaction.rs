@@ -0,0 +1,118 @@
+use crate::{
+    file, reftable, store,
+    transaction::{Change, RefEdit},
+    Namespace,
+};
+
+mod error {
+    use quick_error::quick_error;
+
+    quick_error! {
+        /// The error returned by [`Transaction::prepare()`][super::Transaction::prepare()] and
+        /// [`Transaction::commit()`][super::Transaction::commit()].
+        #[derive(Debug)]
+        #[allow(missing_docs)]
+        pub enum Error {
+            LoosePrepare(err: crate::file::transaction::prepare::Error) {
+                display("The transaction on loose references could not be prepared")
+                from()
+                source(err)
+            }
+            LooseCommit(err: crate::file::transaction::commit::Error) {
+                display("The transaction on loose references could not be committed")
+                from()
+                source(err)
+            }
+            Reftable(err: crate::reftable::transaction::Error) {
+                display("The transaction on the reftable stack failed")
+                from()
+                source(err)
+            }
+        }
+    }
+}
+
+pub use error::Error;
+
+use crate::store::handle;
+
+/// A transaction to change references in any storage format, obtained by [`store::Handle::transaction()`].
+pub struct Transaction<'s> {
+    state: State<'s>,
+}
+
+enum State<'s> {
+    Loose(file::Transaction<'s>),
+    Reftable {
+        transaction: reftable::Transaction<'s>,
+        namespace: Option<&'s Namespace>,
+    },
+}
+
+impl store::Handle {
+    /// Open a transaction to change references, which is limited to the namespace of this handle.
+    pub fn transaction(&mut self) -> Transaction<'_> {
+        Transaction {
+            state: match &mut self.state {
+                handle::State::Loose { store } => State::Loose(store.transaction()),
+                handle::State::Reftable { store, namespace } => State::Reftable {
+                    transaction: store.transaction(),
+                    namespace: namespace.as_ref(),
+                },
+            },
+        }
+    }
+}
+
+impl<'s> Transaction<'s> {
+    /// Lock all references or the reftable stack, determining how to fail if a lock cannot be obtained, and verify that
+    /// the `edits` can be applied.
+    ///
+    /// If the operation succeeds, the transaction can be committed or dropped to cause a rollback automatically.
+    pub fn prepare(
+        self,
+        edits: impl IntoIterator<Item = RefEdit>,
+        lock_fail_mode: git_lock::acquire::Fail,
+    ) -> Result<Self, Error> {
+        Ok(Transaction {
+            state: match self.state {
+                State::Loose(transaction) => State::Loose(transaction.prepare(edits, lock_fail_mode)?),
+                State::Reftable { transaction, namespace } => {
+                    let edits = edits.into_iter().map(|mut edit| {
+                        if let Some(namespace) = namespace {
+                            edit.name.prefix_namespace(namespace);
+                            match &mut edit.change {
+                                Change::Rename { new_name, .. } | Change::Copy { new_name, .. } => {
+                                    new_name.prefix_namespace(namespace);
+                                }
+                                Change::Update { .. } | Change::Delete { .. } => {}
+                            }
+                        }
+                        edit
+                    });
+                    State::Reftable {
+                        transaction: transaction.prepare(edits, lock_fail_mode)?,
+                        namespace,
+                    }
+                }
+            },
+        })
+    }
+
+    /// Make all [prepared][Transaction::prepare()] edits permanent, using `committer` for reflog entries, and return
+    /// the edits that were performed with their previous values filled in.
+    pub fn commit(self, committer: &git_actor::Signature) -> Result<Vec<RefEdit>, Error> {
+        match self.state {
+            State::Loose(transaction) => Ok(transaction.commit(committer)?),
+            State::Reftable { transaction, namespace } => {
+                let mut edits = transaction.commit(committer)?;
+                if let Some(namespace) = namespace {
+                    for edit in &mut edits {
+                        edit.name.strip_namespace(namespace);
+                    }
+                }
+                Ok(edits)
+            }
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use std::path::PathBuf;
 
-use crate::store::WriteReflog;
+use crate::store::{Format, WriteReflog};
 
 mod error {
     use quick_error::quick_error;
@@ -15,16 +15,21 @@ mod error {
                 from()
                 source(err)
             }
+            Reftable(err: crate::reftable::stack::Error) {
+                display("The reftable stack could not be loaded")
+                from()
+                source(err)
+            }
         }
     }
 }
 
 pub use error::Error;
 
-use crate::file;
+use crate::{file, reftable};
 
 impl crate::Store {
-    /// Create a new store at the given location, typically the `.git/` directory.
+    /// Create a new store at the given location, typically the `.git/` directory, which stores references as files.
     ///
     /// `object_hash` defines the kind of hash to assume when dealing with refs.
     pub fn at(
@@ -32,12 +37,29 @@ impl crate::Store {
         reflog_mode: WriteReflog,
         object_hash: git_hash::Kind,
     ) -> Result<Self, Error> {
-        // for now, just try to read the directory - later we will do that naturally as we have to figure out if it's a ref-table or not.
+        Self::at_with_format(git_dir, Format::Files, reflog_mode, object_hash)
+    }
+
+    /// Like [`at()`][crate::Store::at()], but stores references in the given `format`, which is typically obtained
+    /// from the `extensions.refStorage` configuration value.
+    ///
+    /// Reftables are read from the `reftable/` directory within `git_dir`.
+    pub fn at_with_format(
+        git_dir: impl Into<PathBuf>,
+        format: Format,
+        reflog_mode: WriteReflog,
+        object_hash: git_hash::Kind,
+    ) -> Result<Self, Error> {
         let git_dir = git_dir.into();
         std::fs::read_dir(&git_dir)?;
         Ok(crate::Store {
-            inner: crate::store::State::Loose {
-                store: file::Store::at(git_dir, reflog_mode, object_hash),
+            inner: match format {
+                Format::Files => crate::store::State::Loose {
+                    store: file::Store::at(git_dir, reflog_mode, object_hash),
+                },
+                Format::Reftable => crate::store::State::Reftable {
+                    store: reftable::Stack::at(git_dir.join("reftable"), reflog_mode, object_hash)?,
+                },
             },
         })
     }
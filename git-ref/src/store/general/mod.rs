@@ -1 +1,2 @@
-mod init;
+///
+pub mod init;
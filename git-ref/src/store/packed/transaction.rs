@@ -207,6 +207,9 @@ fn write_edit(mut out: impl std::io::Write, edit: &Edit, lines_written: &mut i32
             new: Target::Symbolic(_),
             ..
         } => unreachable!("BUG: packed refs cannot contain symbolic refs, catch that in prepare(…)"),
+        Change::Rename { .. } | Change::Copy { .. } => {
+            unreachable!("BUG: renames and copies are split into updates and deletions by the loose ref store")
+        }
     }
     Ok(())
 }
@@ -0,0 +1,436 @@
+//! Reading and writing of the blocks that make up a reftable, each holding prefix-compressed records with restart points.
+use std::{borrow::Cow, convert::TryInto};
+
+use crate::store_impl::reftable::{table::Error, varint};
+
+/// The type of blocks with reference records.
+pub(crate) const REF: u8 = b'r';
+/// The type of blocks with object records.
+pub(crate) const OBJ: u8 = b'o';
+/// The type of blocks with index records.
+pub(crate) const INDEX: u8 = b'i';
+/// The type of blocks with log records, which are compressed.
+pub(crate) const LOG: u8 = b'g';
+
+/// The amount of records after which the key is written in full again to allow binary searches.
+const RESTART_INTERVAL: usize = 16;
+/// The size of the block header, consisting of the type and the 24 bit length of the block.
+pub(crate) const HEADER_LEN: usize = 4;
+
+pub(crate) fn read_u24(data: &[u8]) -> u32 {
+    u32::from(data[0]) << 16 | u32::from(data[1]) << 8 | u32::from(data[2])
+}
+
+pub(crate) fn put_u24(value: u32, out: &mut [u8]) {
+    out[0] = (value >> 16) as u8;
+    out[1] = (value >> 8) as u8;
+    out[2] = value as u8;
+}
+
+/// Accumulates records of a single type until the block is full.
+pub(crate) struct Writer {
+    typ: u8,
+    /// The block so far, beginning with `header_offset` bytes reserved for the file header if this is the first block.
+    buf: Vec<u8>,
+    header_offset: usize,
+    block_size: usize,
+    restarts: Vec<u32>,
+    entries: usize,
+    last_key: Vec<u8>,
+}
+
+impl Writer {
+    pub(crate) fn new(typ: u8, header_offset: usize, block_size: usize) -> Self {
+        let mut buf = vec![0; header_offset + HEADER_LEN];
+        buf[header_offset] = typ;
+        Writer {
+            typ,
+            buf,
+            header_offset,
+            block_size,
+            restarts: Vec::new(),
+            entries: 0,
+            last_key: Vec::new(),
+        }
+    }
+
+    pub(crate) fn last_key(&self) -> &[u8] {
+        &self.last_key
+    }
+
+    /// Add a record with `key`, the 3 bit `value_type` and the encoded `value`, or return `false` if it doesn't fit.
+    ///
+    /// Records always fit into empty blocks, whose size then exceeds the block size if the record is larger.
+    pub(crate) fn add(&mut self, key: &[u8], value_type: u8, value: &[u8]) -> bool {
+        let restart = self.entries % RESTART_INTERVAL == 0;
+        let prefix_len = if restart {
+            0
+        } else {
+            key.iter().zip(self.last_key.iter()).take_while(|(a, b)| a == b).count()
+        };
+        let mut record = Vec::with_capacity(key.len() - prefix_len + value.len() + 4);
+        varint::encode(prefix_len as u64, &mut record);
+        varint::encode(
+            (((key.len() - prefix_len) as u64) << 3) | u64::from(value_type),
+            &mut record,
+        );
+        record.extend_from_slice(&key[prefix_len..]);
+        record.extend_from_slice(value);
+
+        let restarts = self.restarts.len() + usize::from(restart);
+        let size = self.buf.len() + record.len() + 3 * restarts + 2;
+        if size > self.block_size && self.entries > 0 {
+            return false;
+        }
+        if restart {
+            self.restarts.push(self.buf.len() as u32);
+        }
+        self.buf.extend_from_slice(&record);
+        self.entries += 1;
+        self.last_key.clear();
+        self.last_key.extend_from_slice(key);
+        true
+    }
+
+    /// Finish the block and return its bytes, padded to the block size unless it holds log records which are compressed.
+    pub(crate) fn finish(mut self) -> std::io::Result<Vec<u8>> {
+        for restart in &self.restarts {
+            let mut offset = [0u8; 3];
+            put_u24(*restart, &mut offset);
+            self.buf.extend_from_slice(&offset);
+        }
+        self.buf.extend_from_slice(&(self.restarts.len() as u16).to_be_bytes());
+        let block_len = self.buf.len() as u32;
+        put_u24(block_len, &mut self.buf[self.header_offset + 1..]);
+
+        if self.typ == LOG {
+            use std::io::Write;
+            let records_start = self.header_offset + HEADER_LEN;
+            let mut out = git_features::zlib::stream::deflate::Write::new(self.buf[..records_start].to_vec());
+            out.write_all(&self.buf[records_start..])?;
+            out.flush()?;
+            Ok(out.into_inner())
+        } else {
+            if self.buf.len() < self.block_size {
+                self.buf.resize(self.block_size, 0);
+            }
+            Ok(self.buf)
+        }
+    }
+}
+
+/// A single block as read from a table.
+pub(crate) struct Block<'a> {
+    pub(crate) typ: u8,
+    /// The block from its start, including the file header if it's the first one, inflated if it contains logs.
+    data: Cow<'a, [u8]>,
+    header_offset: usize,
+    /// The offset at which the restart offsets begin, which is also where the records end.
+    restarts_start: usize,
+    restart_count: usize,
+    /// The amount of bytes the block occupies in the table, before padding.
+    pub(crate) len_in_table: usize,
+    /// The kind of hash of the object ids in records.
+    object_hash: git_hash::Kind,
+}
+
+impl<'a> Block<'a> {
+    /// Read the block at `offset` of `table`, which ends at `end`, or return `None` if there is no block at `offset`.
+    ///
+    /// `header_offset` is the length of the file header for the first block, and 0 otherwise, and `object_hash` is the
+    /// kind of hash used by the table.
+    pub(crate) fn read(
+        table: &'a [u8],
+        offset: usize,
+        end: usize,
+        header_offset: usize,
+        object_hash: git_hash::Kind,
+    ) -> Result<Option<Self>, Error> {
+        let start = offset + header_offset;
+        if start + HEADER_LEN > end {
+            return Ok(None);
+        }
+        let typ = table[start];
+        if ![REF, OBJ, INDEX, LOG].contains(&typ) {
+            return Ok(None);
+        }
+        let block_len = read_u24(&table[start + 1..]) as usize;
+        if block_len < header_offset + HEADER_LEN + 2 {
+            return Err(Error::Corrupt("block is too small"));
+        }
+        let (data, len_in_table) = if typ == LOG {
+            let mut inflated = table[offset..start + HEADER_LEN].to_vec();
+            inflated.resize(block_len, 0);
+            let mut inflate = git_features::zlib::Inflate::default();
+            let mut consumed_in = 0;
+            let mut written = start - offset + HEADER_LEN;
+            loop {
+                let (status, consumed, produced) = inflate
+                    .once(&table[start + HEADER_LEN + consumed_in..end], &mut inflated[written..])
+                    .map_err(|_| Error::Corrupt("log block could not be decompressed"))?;
+                consumed_in += consumed;
+                written += produced;
+                match status {
+                    git_features::zlib::Status::StreamEnd => break,
+                    _ if consumed == 0 && produced == 0 => {
+                        return Err(Error::Corrupt("log block could not be decompressed"))
+                    }
+                    _ => continue,
+                }
+            }
+            if written != block_len {
+                return Err(Error::Corrupt("log block has an unexpected size"));
+            }
+            (Cow::Owned(inflated), header_offset + HEADER_LEN + consumed_in)
+        } else {
+            if offset + block_len > end {
+                return Err(Error::Corrupt("block exceeds the table"));
+            }
+            (Cow::Borrowed(&table[offset..offset + block_len]), block_len)
+        };
+
+        let restart_count = u16::from_be_bytes(data[block_len - 2..].try_into().expect("2 bytes")) as usize;
+        let restarts_start = block_len
+            .checked_sub(2 + 3 * restart_count)
+            .filter(|pos| *pos >= header_offset + HEADER_LEN)
+            .ok_or(Error::Corrupt("too many restart points"))?;
+        Ok(Some(Block {
+            typ,
+            data,
+            header_offset,
+            restarts_start,
+            restart_count,
+            len_in_table,
+            object_hash,
+        }))
+    }
+
+    fn restart(&self, idx: usize) -> usize {
+        read_u24(&self.data[self.restarts_start + idx * 3..]) as usize
+    }
+
+    fn records(&self) -> &[u8] {
+        &self.data[..self.restarts_start]
+    }
+
+    /// Return a cursor to the first record of this block.
+    pub(crate) fn cursor(&self) -> Cursor {
+        Cursor {
+            pos: self.header_offset + HEADER_LEN,
+            key: Vec::new(),
+        }
+    }
+
+    /// Return a cursor positioned so that the first record it yields is the first one whose key is equal or greater
+    /// than `key`, if there is one in this block.
+    pub(crate) fn seek(&self, key: &[u8]) -> Result<Cursor, Error> {
+        // find the first restart point whose key is greater than `key`, the record can only be past the one before.
+        let (mut lo, mut hi) = (0, self.restart_count);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let mut cursor = Cursor {
+                pos: self.restart(mid),
+                key: Vec::new(),
+            };
+            let (restart_key, _) = cursor
+                .next_key(self)?
+                .ok_or(Error::Corrupt("restart points past records"))?;
+            if restart_key > key {
+                hi = mid;
+            } else {
+                lo = mid + 1;
+            }
+        }
+        let mut cursor = if lo == 0 {
+            self.cursor()
+        } else {
+            Cursor {
+                pos: self.restart(lo - 1),
+                key: Vec::new(),
+            }
+        };
+        loop {
+            let (pos, previous_key) = (cursor.pos, cursor.key.clone());
+            let value_type = match cursor.next_key(self)? {
+                Some((record_key, value_type)) if record_key < key => value_type,
+                Some(_) => {
+                    cursor.pos = pos;
+                    cursor.key = previous_key;
+                    break;
+                }
+                None => break,
+            };
+            cursor.skip_value(self, value_type)?;
+        }
+        Ok(cursor)
+    }
+}
+
+/// A position within a block to iterate its records, decoding their keys and leaving the decoding of values to the caller.
+pub(crate) struct Cursor {
+    pos: usize,
+    key: Vec<u8>,
+}
+
+impl Cursor {
+    /// Decode the next key and its value type, leaving the position at the start of the value.
+    fn next_key<'b>(&'b mut self, block: &Block<'_>) -> Result<Option<(&'b [u8], u8)>, Error> {
+        let data = block.records();
+        if self.pos >= data.len() {
+            return Ok(None);
+        }
+        let mut value = Value {
+            data,
+            pos: self.pos,
+            object_hash: block.object_hash,
+        };
+        let prefix_len = value.varint()? as usize;
+        let suffix_and_type = value.varint()?;
+        let suffix = value.take((suffix_and_type >> 3) as usize)?;
+        if prefix_len > self.key.len() {
+            return Err(Error::Corrupt("record key prefix exceeds previous key"));
+        }
+        self.key.truncate(prefix_len);
+        self.key.extend_from_slice(suffix);
+        self.pos = value.pos;
+        Ok(Some((&self.key, (suffix_and_type & 0x7) as u8)))
+    }
+
+    /// Return the next record of `block` with its key, value type and a cursor to decode its value. The latter has to be
+    /// passed to [`advance()`][Cursor::advance()] once the value was fully decoded.
+    pub(crate) fn next_record<'b>(&mut self, block: &'b Block<'_>) -> Result<Option<(Vec<u8>, u8, Value<'b>)>, Error> {
+        let (key, value_type) = match self.next_key(block)? {
+            Some((key, value_type)) => (key.to_owned(), value_type),
+            None => return Ok(None),
+        };
+        let value = Value {
+            data: block.records(),
+            pos: self.pos,
+            object_hash: block.object_hash,
+        };
+        Ok(Some((key, value_type, value)))
+    }
+
+    /// Return true if there are no more records in `block`.
+    pub(crate) fn is_at_end(&self, block: &Block<'_>) -> bool {
+        self.pos >= block.records().len()
+    }
+
+    /// Continue after the value that was fully decoded by `value`.
+    pub(crate) fn advance(&mut self, value: &Value<'_>) {
+        self.pos = value.pos;
+    }
+
+    fn skip_value(&mut self, block: &Block<'_>, value_type: u8) -> Result<(), Error> {
+        let mut value = Value {
+            data: block.records(),
+            pos: self.pos,
+            object_hash: block.object_hash,
+        };
+        match block.typ {
+            REF => {
+                value.varint()?;
+                match value_type {
+                    0 => {}
+                    1 => value.skip_id()?,
+                    2 => {
+                        value.skip_id()?;
+                        value.skip_id()?
+                    }
+                    3 => {
+                        value.bytes()?;
+                    }
+                    _ => return Err(Error::Corrupt("unknown reference value type")),
+                }
+            }
+            INDEX => {
+                value.varint()?;
+            }
+            OBJ => {
+                let count = if value_type == 0 {
+                    value.varint()?
+                } else {
+                    u64::from(value_type)
+                };
+                for _ in 0..count {
+                    value.varint()?;
+                }
+            }
+            LOG => {
+                if value_type == 1 {
+                    value.skip_id()?;
+                    value.skip_id()?;
+                    value.bytes()?;
+                    value.bytes()?;
+                    value.varint()?;
+                    value.take(2)?;
+                    value.bytes()?;
+                }
+            }
+            _ => unreachable!("only known blocks are read"),
+        }
+        self.pos = value.pos;
+        Ok(())
+    }
+}
+
+/// A cursor to decode the value of a record.
+pub(crate) struct Value<'a> {
+    data: &'a [u8],
+    pos: usize,
+    object_hash: git_hash::Kind,
+}
+
+impl<'a> Value<'a> {
+    pub(crate) fn varint(&mut self) -> Result<u64, Error> {
+        let (value, consumed) =
+            varint::decode(&self.data[self.pos..]).ok_or(Error::Corrupt("invalid variable-length integer"))?;
+        self.pos += consumed;
+        Ok(value)
+    }
+
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .ok_or(Error::Corrupt("record value exceeds its block"))?;
+        let data = self
+            .data
+            .get(self.pos..end)
+            .ok_or(Error::Corrupt("record value exceeds its block"))?;
+        self.pos = end;
+        Ok(data)
+    }
+
+    /// Read a length-prefixed byte string.
+    pub(crate) fn bytes(&mut self) -> Result<&'a [u8], Error> {
+        let len = self.varint()? as usize;
+        self.take(len)
+    }
+
+    pub(crate) fn id(&mut self) -> Result<git_hash::ObjectId, Error> {
+        Ok(git_hash::ObjectId::from(self.take(self.object_hash.len_in_bytes())?))
+    }
+
+    fn skip_id(&mut self) -> Result<(), Error> {
+        self.take(self.object_hash.len_in_bytes()).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{varint, Error, Value};
+
+    #[test]
+    fn lengths_exceeding_the_data_are_rejected_without_overflow() {
+        let mut buf = vec![b'x'];
+        varint::encode(u64::MAX, &mut buf);
+        let mut value = Value {
+            data: &buf,
+            pos: 1,
+            object_hash: git_hash::Kind::Sha1,
+        };
+        assert!(matches!(value.bytes(), Err(Error::Corrupt(_))));
+        assert!(matches!(value.take(usize::MAX), Err(Error::Corrupt(_))));
+    }
+}
@@ -0,0 +1,152 @@
+//! An implementation of the [reftable format][format] to store references and their logs in a stack of binary tables.
+//!
+//! Each table is immutable and written once, with newer tables overriding the references of older ones. The stack of
+//! tables is listed in `reftable/tables.list`, which is replaced through a lock file for every change, and compacted
+//! to keep the amount of tables small.
+//!
+//! [format]: https://git-scm.com/docs/reftable
+use std::path::PathBuf;
+
+use git_features::threading::OwnShared;
+use git_hash::ObjectId;
+use memmap2::Mmap;
+
+use crate::{store::WriteReflog, FullName, Reference, Target};
+
+/// The name of the file listing all tables of a stack, oldest first.
+pub(crate) const TABLES_LIST: &str = "tables.list";
+
+#[derive(Debug)]
+enum Backing {
+    /// The table is loaded entirely in memory.
+    InMemory(Vec<u8>),
+    /// The table is mapping the file on disk.
+    Mapped(Mmap),
+}
+
+/// A single reftable, holding references, an index of the objects they point to and reference logs.
+#[derive(Debug)]
+pub struct Table {
+    data: Backing,
+    object_hash: git_hash::Kind,
+    /// The length of the file header, which precedes the first block.
+    header_len: usize,
+    block_size: u32,
+    min_update_index: u64,
+    max_update_index: u64,
+    footer: Footer,
+}
+
+/// The positions of the sections of a table as stored in its footer, with 0 indicating a section isn't present.
+#[derive(Debug, Clone, Copy)]
+struct Footer {
+    /// The offset at which the footer starts, which is where the last block ends.
+    start: usize,
+    ref_index_position: u64,
+    obj_position: u64,
+    /// The amount of bytes of object ids used as keys of object records.
+    obj_id_len: usize,
+    obj_index_position: u64,
+    log_position: u64,
+    log_index_position: u64,
+}
+
+/// A reference as stored in a reftable.
+#[derive(PartialEq, Eq, Debug, Hash, Ord, PartialOrd, Clone)]
+pub struct RefRecord {
+    /// The full name of the reference.
+    pub name: FullName,
+    /// The index of the update that last changed this reference, increasing monotonically across the tables of a stack.
+    pub update_index: u64,
+    /// The value of the reference.
+    pub value: RefValue,
+}
+
+/// The value of a [`RefRecord`].
+#[derive(PartialEq, Eq, Debug, Hash, Ord, PartialOrd, Clone)]
+pub enum RefValue {
+    /// The reference was deleted, hiding its value in older tables.
+    Deletion,
+    /// The reference points to an object.
+    Peeled(ObjectId),
+    /// The reference points to an annotated tag `target`, which ultimately points to the `peeled` object.
+    PeeledTag {
+        /// The object the reference points to.
+        target: ObjectId,
+        /// The object the tag points to, after following all tags.
+        peeled: ObjectId,
+    },
+    /// The reference points to another reference.
+    Symbolic(FullName),
+}
+
+impl RefRecord {
+    /// Convert this record into a reference, or return `None` if it's a [deletion][RefValue::Deletion].
+    pub fn into_reference(self) -> Option<Reference> {
+        let (target, peeled) = match self.value {
+            RefValue::Deletion => return None,
+            RefValue::Peeled(id) => (Target::Peeled(id), None),
+            RefValue::PeeledTag { target, peeled } => (Target::Peeled(target), Some(peeled)),
+            RefValue::Symbolic(name) => (Target::Symbolic(name), None),
+        };
+        Some(Reference {
+            name: self.name,
+            target,
+            peeled,
+        })
+    }
+}
+
+/// A reference log entry as stored in a reftable.
+#[derive(PartialEq, Eq, Debug, Hash, Ord, PartialOrd, Clone)]
+pub struct LogRecord {
+    /// The full name of the reference the entry belongs to.
+    pub name: FullName,
+    /// The index of the update that created this entry.
+    pub update_index: u64,
+    /// The entry itself, or `None` if it was deleted which hides it in older tables.
+    pub line: Option<crate::log::Line>,
+}
+
+/// A stack of [tables][Table] in a `reftable` directory, with newer tables overriding the records of older ones.
+///
+/// It's a snapshot of the stack at the time it was loaded or [reloaded][Stack::reload()], and only
+/// [transactions][Stack::transaction()] and [compactions][Stack::compact()] change it.
+#[derive(Debug, Clone)]
+pub struct Stack {
+    /// The `reftable` directory.
+    dir: PathBuf,
+    object_hash: git_hash::Kind,
+    /// The file names of all tables along with the tables themselves, oldest first.
+    tables: Vec<(String, OwnShared<Table>)>,
+    /// The way to handle reflog edits.
+    pub write_reflog: WriteReflog,
+    /// The options to use when writing new tables.
+    pub write_options: write::Options,
+    /// If true, tables are compacted after each transaction to keep the size of each table at least twice as large
+    /// as the one of the next newer table.
+    pub auto_compact: bool,
+}
+
+/// A transaction on a reftable [`Stack`].
+pub struct Transaction<'s> {
+    stack: &'s mut Stack,
+    /// The lock on `tables.list`, held from preparation to commit.
+    lock: Option<git_lock::File>,
+    updates: Option<Vec<transaction::Edit>>,
+}
+
+mod block;
+mod varint;
+
+///
+pub mod table;
+
+///
+pub mod write;
+
+///
+pub mod stack;
+
+///
+pub mod transaction;
@@ -0,0 +1,385 @@
+use std::{collections::BTreeMap, io::Write, iter::Peekable, ops::Range, path::PathBuf};
+
+use git_features::threading::OwnShared;
+use git_object::bstr::{BStr, ByteSlice};
+
+use crate::{
+    store::WriteReflog,
+    store_impl::reftable::{table, write, LogRecord, RefRecord, RefValue, Stack, Table, TABLES_LIST},
+    FullName,
+};
+
+/// The size from which on tables are memory mapped instead of being read into memory.
+const USE_MEMORY_MAP_IF_LARGER_THAN_BYTES: u64 = 32 * 1024;
+/// The amount of times reading the list of tables is retried if a listed table was removed by a concurrent compaction.
+const MAX_RELOAD_ATTEMPTS: usize = 5;
+
+/// Initialization
+impl Stack {
+    /// Load the stack of tables in `dir`, typically `.git/reftable`, which is empty if `dir` doesn't contain
+    /// a `tables.list` file yet.
+    ///
+    /// `object_hash` defines the kind of hash to assume when dealing with refs.
+    pub fn at(dir: impl Into<PathBuf>, write_reflog: WriteReflog, object_hash: git_hash::Kind) -> Result<Self, Error> {
+        let mut stack = Stack {
+            dir: dir.into(),
+            object_hash,
+            tables: Vec::new(),
+            write_reflog,
+            write_options: Default::default(),
+            auto_compact: true,
+        };
+        stack.reload()?;
+        Ok(stack)
+    }
+
+    /// Read the list of tables again to see changes made by other writers, keeping tables that are still listed.
+    pub fn reload(&mut self) -> Result<(), Error> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let list = match std::fs::read(self.dir.join(TABLES_LIST)) {
+                Ok(list) => list,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+                Err(err) => return Err(err.into()),
+            };
+            let mut tables = Vec::new();
+            let mut removed_concurrently = false;
+            for name in list.lines().filter(|line| !line.is_empty()) {
+                let name = name
+                    .to_str()
+                    .ok()
+                    .filter(|name| !name.contains('/') && !name.contains('\\'))
+                    .ok_or_else(|| Error::InvalidTableName { name: name.into() })?;
+                if let Some((_, table)) = self.tables.iter().find(|(existing, _)| existing == name) {
+                    tables.push((name.to_owned(), OwnShared::clone(table)));
+                    continue;
+                }
+                match Table::at(
+                    self.dir.join(name),
+                    USE_MEMORY_MAP_IF_LARGER_THAN_BYTES,
+                    self.object_hash,
+                ) {
+                    Ok(table) => tables.push((name.to_owned(), OwnShared::new(table))),
+                    Err(table::Error::Io(err))
+                        if err.kind() == std::io::ErrorKind::NotFound && attempt < MAX_RELOAD_ATTEMPTS =>
+                    {
+                        removed_concurrently = true;
+                        break;
+                    }
+                    Err(err) => {
+                        return Err(Error::Open {
+                            err,
+                            name: name.to_owned(),
+                        })
+                    }
+                }
+            }
+            if !removed_concurrently {
+                self.tables = tables;
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Access
+impl Stack {
+    /// The `reftable` directory containing all tables.
+    pub fn dir(&self) -> &std::path::Path {
+        &self.dir
+    }
+
+    /// The kind of hash used by the references in this stack.
+    pub fn object_hash(&self) -> git_hash::Kind {
+        self.object_hash
+    }
+
+    /// Return all tables, from oldest to newest.
+    pub fn tables(&self) -> impl ExactSizeIterator<Item = &Table> + '_ {
+        self.tables.iter().map(|(_, table)| &**table)
+    }
+
+    /// The update index to use for the next table added to the stack.
+    pub fn next_update_index(&self) -> u64 {
+        self.tables.last().map_or(1, |(_, table)| table.max_update_index() + 1)
+    }
+
+    /// Find the reference with the full `name`, or `None` if it doesn't exist or was deleted.
+    pub fn find(&self, name: &BStr) -> Result<Option<RefRecord>, Error> {
+        for (_, table) in self.tables.iter().rev() {
+            if let Some(r) = table.find(name)? {
+                return Ok(match r.value {
+                    RefValue::Deletion => None,
+                    _ => Some(r),
+                });
+            }
+        }
+        Ok(None)
+    }
+
+    /// Return an iterator over all existing references, ordered by name, with newer tables overriding older ones.
+    pub fn refs(&self) -> Result<Refs<'_>, Error> {
+        self.refs_prefixed(b"".as_bstr())
+    }
+
+    /// Return an iterator over all existing references whose name starts with `prefix`, ordered by name.
+    pub fn refs_prefixed(&self, prefix: &BStr) -> Result<Refs<'_>, Error> {
+        self.merged_refs(0..self.tables.len(), prefix, false)
+    }
+
+    /// Return all log entries of the reference with the full `name`, from newest to oldest.
+    pub fn logs_for(&self, name: &BStr) -> Result<Vec<LogRecord>, Error> {
+        let mut logs = BTreeMap::new();
+        for (_, table) in &self.tables {
+            for log in table.logs_for(name)? {
+                let log = log?;
+                logs.insert(std::cmp::Reverse(log.update_index), log);
+            }
+        }
+        Ok(logs
+            .into_iter()
+            .map(|(_, log)| log)
+            .filter(|log| log.line.is_some())
+            .collect())
+    }
+
+    /// Return true if there is at least one log entry for the reference with the full `name`.
+    pub fn log_exists(&self, name: &BStr) -> Result<bool, Error> {
+        Ok(!self.logs_for(name)?.is_empty())
+    }
+
+    fn merged_refs(&self, range: Range<usize>, prefix: &BStr, include_deletions: bool) -> Result<Refs<'_>, Error> {
+        Ok(Refs {
+            iters: self.tables[range]
+                .iter()
+                .map(|(_, table)| table.refs_prefixed(prefix).map(Iterator::peekable))
+                .collect::<Result<_, _>>()?,
+            include_deletions,
+        })
+    }
+}
+
+/// Modification
+impl Stack {
+    /// Merge all tables into one, dropping deleted references and log entries.
+    ///
+    /// `lock_fail_mode` defines how to handle the `tables.list` lock being held by another writer.
+    pub fn compact(&mut self, lock_fail_mode: git_lock::acquire::Fail) -> Result<(), Error> {
+        let lock = self.lock(lock_fail_mode)?;
+        self.reload()?;
+        self.compact_range(lock, 0..self.tables.len())
+    }
+
+    /// Merge the newest tables until the next older table is more than twice as large as the merged ones, keeping the
+    /// amount of tables logarithmic in the amount of changes.
+    ///
+    /// Compaction is skipped if another writer holds the lock on `tables.list`.
+    pub fn auto_compact(&mut self) -> Result<(), Error> {
+        let lock = match self.lock(git_lock::acquire::Fail::Immediately) {
+            Ok(lock) => lock,
+            Err(Error::LockAcquire(_)) => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        self.reload()?;
+        let sizes: Vec<_> = self.tables().map(|table| table.data().len()).collect();
+        let end = sizes.len();
+        if end < 2 {
+            return Ok(());
+        }
+        let (mut start, mut total) = (end - 1, sizes[end - 1]);
+        while start > 0 && sizes[start - 1] <= 2 * total {
+            start -= 1;
+            total += sizes[start];
+        }
+        if start + 1 == end {
+            return Ok(());
+        }
+        self.compact_range(lock, start..end)
+    }
+
+    pub(crate) fn lock(&self, lock_fail_mode: git_lock::acquire::Fail) -> Result<git_lock::File, Error> {
+        git_lock::File::acquire_to_update_resource(self.dir.join(TABLES_LIST), lock_fail_mode, None)
+            .map_err(Error::LockAcquire)
+    }
+
+    /// Write a table with `refs` and `logs` using the [next update index][Stack::next_update_index()], add it to the stack
+    /// and release `lock`.
+    pub(crate) fn add_table(
+        &mut self,
+        lock: git_lock::File,
+        refs: &[RefRecord],
+        logs: &[LogRecord],
+    ) -> Result<(), Error> {
+        let update_index = self.next_update_index();
+        let name = self.write_table(refs, logs, update_index..update_index + 1)?;
+        let mut names: Vec<_> = self.tables.iter().map(|(name, _)| name.clone()).collect();
+        names.push(name);
+        self.commit_list(lock, &names)
+    }
+
+    /// Replace the tables in `range` with a single table while holding `lock`, the lock on `tables.list`.
+    fn compact_range(&mut self, lock: git_lock::File, range: Range<usize>) -> Result<(), Error> {
+        if range.len() < 2 {
+            return Ok(());
+        }
+        let drop_deletions = range.start == 0;
+        let refs = self
+            .merged_refs(range.clone(), b"".as_bstr(), !drop_deletions)?
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut logs = BTreeMap::new();
+        for (_, table) in &self.tables[range.clone()] {
+            for log in table.logs()? {
+                let log = log?;
+                logs.insert((log.name.clone(), std::cmp::Reverse(log.update_index)), log);
+            }
+        }
+        let logs: Vec<_> = logs
+            .into_iter()
+            .map(|(_, log)| log)
+            .filter(|log| !drop_deletions || log.line.is_some())
+            .collect();
+
+        let tables = &self.tables[range.clone()];
+        let update_indices = tables
+            .iter()
+            .map(|(_, t)| t.min_update_index())
+            .min()
+            .expect("at least two")
+            ..tables
+                .iter()
+                .map(|(_, t)| t.max_update_index())
+                .max()
+                .expect("at least two")
+                + 1;
+        let name = self.write_table(&refs, &logs, update_indices)?;
+        let mut names: Vec<_> = self.tables.iter().map(|(name, _)| name.clone()).collect();
+        let obsolete: Vec<_> = names.splice(range, std::iter::once(name)).collect();
+        self.commit_list(lock, &names)?;
+        for name in obsolete {
+            // Readers may still have it open, which is fine as the file is only unlinked.
+            std::fs::remove_file(self.dir.join(name)).ok();
+        }
+        Ok(())
+    }
+
+    /// Write a new table with all update indices in `update_indices` and return its file name.
+    fn write_table(&self, refs: &[RefRecord], logs: &[LogRecord], update_indices: Range<u64>) -> Result<String, Error> {
+        let (min, max) = (update_indices.start, update_indices.end - 1);
+        let data = write::table(refs, logs, min, max, self.write_options)?;
+        let name = format!(
+            "0x{:012x}-0x{:012x}-{:08x}.ref",
+            min,
+            max,
+            git_features::hash::crc32(&data)
+        );
+        let mut file = git_tempfile::new(
+            &self.dir,
+            git_tempfile::ContainingDirectory::Exists,
+            git_tempfile::AutoRemove::Tempfile,
+        )?;
+        file.with_mut(|file| file.write_all(&data))??;
+        file.persist(self.dir.join(&name)).map_err(|err| err.error)?;
+        Ok(name)
+    }
+
+    fn commit_list(&mut self, mut lock: git_lock::File, names: &[String]) -> Result<(), Error> {
+        lock.with_mut(|file| {
+            for name in names {
+                writeln!(file, "{}", name)?;
+            }
+            Ok(())
+        })?;
+        lock.commit().map_err(|err| Error::LockCommit(err.error))?;
+        self.reload()
+    }
+}
+
+/// An iterator over the references of all tables of a [`Stack`], with newer tables overriding older ones.
+pub struct Refs<'a> {
+    /// Iterators of all tables, oldest first.
+    iters: Vec<Peekable<table::Refs<'a>>>,
+    include_deletions: bool,
+}
+
+impl<'a> Iterator for Refs<'a> {
+    type Item = Result<RefRecord, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let mut smallest: Option<(usize, FullName)> = None;
+            for (idx, iter) in self.iters.iter_mut().enumerate() {
+                match iter.peek() {
+                    Some(Ok(r)) => {
+                        // the newest table wins if names are equal
+                        if smallest.as_ref().map_or(true, |(_, name)| r.name <= *name) {
+                            smallest = Some((idx, r.name.clone()));
+                        }
+                    }
+                    Some(Err(_)) => return iter.next().map(|r| r.map_err(Into::into)),
+                    None => {}
+                }
+            }
+            let (newest, name) = smallest?;
+            let mut newest_record = None;
+            for (idx, iter) in self.iters.iter_mut().enumerate() {
+                if matches!(iter.peek(), Some(Ok(r)) if r.name == name) {
+                    let r = iter.next().expect("peeked").expect("peeked without error");
+                    if idx == newest {
+                        newest_record = Some(r);
+                    }
+                }
+            }
+            let r = newest_record.expect("the newest record was among the ones with the same name");
+            if self.include_deletions || !matches!(r.value, RefValue::Deletion) {
+                return Some(Ok(r));
+            }
+        }
+    }
+}
+
+mod error {
+    use git_object::bstr::BString;
+    use quick_error::quick_error;
+
+    use crate::store_impl::reftable::{table, write};
+
+    quick_error! {
+        /// The error returned by [`Stack`][crate::reftable::Stack] methods.
+        #[derive(Debug)]
+        #[allow(missing_docs)]
+        pub enum Error {
+            Io(err: std::io::Error) {
+                display("An IO error occurred while accessing the reftable directory")
+                from()
+                source(err)
+            }
+            InvalidTableName { name: BString } {
+                display("The table name '{}' in tables.list is invalid", name)
+            }
+            Open { name: String, err: table::Error } {
+                display("The table '{}' could not be opened", name)
+                source(err)
+            }
+            Table(err: table::Error) {
+                display("A table could not be read")
+                from()
+                source(err)
+            }
+            Write(err: write::Error) {
+                display("A new table could not be written")
+                from()
+                source(err)
+            }
+            LockAcquire(err: git_lock::acquire::Error) {
+                display("The lock for tables.list could not be obtained")
+                source(err)
+            }
+            LockCommit(err: std::io::Error) {
+                display("The lock for tables.list could not be committed")
+                source(err)
+            }
+        }
+    }
+}
+pub use error::Error;
@@ -0,0 +1,533 @@
+use std::{
+    convert::{TryFrom, TryInto},
+    path::Path,
+};
+
+use git_hash::oid;
+use git_object::bstr::{BStr, ByteSlice};
+use memmap2::Mmap;
+
+use crate::{
+    store_impl::reftable::{
+        block::{self, Block, Cursor, Value},
+        Backing, Footer, LogRecord, RefRecord, RefValue, Table,
+    },
+    FullName,
+};
+
+/// The magic bytes at the start of each table, and at the start of its footer.
+pub(crate) const MAGIC: &[u8; 4] = b"REFT";
+/// The length of the header of tables of version 1, which only support SHA1.
+pub(crate) const HEADER_LEN_V1: usize = 24;
+/// The length of the header of tables of version 2, which name the hash they use.
+const HEADER_LEN_V2: usize = 28;
+/// The length of the footer past its copy of the header, with all section positions and a CRC-32 of the footer.
+pub(crate) const FOOTER_LEN_WITHOUT_HEADER: usize = 5 * 8 + 4;
+
+impl AsRef<[u8]> for Backing {
+    fn as_ref(&self) -> &[u8] {
+        match self {
+            Backing::InMemory(data) => data,
+            Backing::Mapped(map) => map,
+        }
+    }
+}
+
+/// Initialization
+impl Table {
+    /// Open the table at `path` and map it into memory if the file size is larger than `use_memory_map_if_larger_than_bytes`.
+    ///
+    /// `object_hash` is the kind of hash the table is expected to use for the objects it refers to.
+    pub fn at(
+        path: impl AsRef<Path>,
+        use_memory_map_if_larger_than_bytes: u64,
+        object_hash: git_hash::Kind,
+    ) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let data = if std::fs::metadata(path)?.len() <= use_memory_map_if_larger_than_bytes {
+            Backing::InMemory(std::fs::read(path)?)
+        } else {
+            Backing::Mapped(
+                // SAFETY: we have to take the risk of somebody changing the file underneath. Tables are never written in place.
+                #[allow(unsafe_code)]
+                unsafe {
+                    Mmap::map(&std::fs::File::open(path)?)?
+                },
+            )
+        };
+        Self::from_backing(data, object_hash)
+    }
+
+    /// Parse a table from `data` as created by [`write::table()`][crate::reftable::write::table()], which is expected
+    /// to use `object_hash`.
+    pub fn from_bytes(data: Vec<u8>, object_hash: git_hash::Kind) -> Result<Self, Error> {
+        Self::from_backing(Backing::InMemory(data), object_hash)
+    }
+
+    fn from_backing(data: Backing, object_hash: git_hash::Kind) -> Result<Self, Error> {
+        let bytes = data.as_ref();
+        if bytes.len() < HEADER_LEN_V1 || &bytes[..4] != MAGIC {
+            return Err(Error::Corrupt("missing table header"));
+        }
+        let header_len = match bytes[4] {
+            1 => HEADER_LEN_V1,
+            2 => HEADER_LEN_V2,
+            version => return Err(Error::UnsupportedVersion(version)),
+        };
+        // version 1 tables always use SHA1, while version 2 tables name their hash.
+        let hash_id: &[u8] = match object_hash {
+            git_hash::Kind::Sha1 => b"sha1",
+        };
+        if header_len == HEADER_LEN_V2 && bytes.get(24..28) != Some(hash_id) {
+            return Err(Error::ObjectHashMismatch { expected: object_hash });
+        }
+        let footer_len = header_len + FOOTER_LEN_WITHOUT_HEADER;
+        let footer_start = bytes
+            .len()
+            .checked_sub(footer_len)
+            .filter(|start| *start >= header_len)
+            .ok_or(Error::Corrupt("table is too small to hold its footer"))?;
+        let footer = &bytes[footer_start..];
+        if footer[..header_len] != bytes[..header_len] {
+            return Err(Error::Corrupt("footer doesn't repeat the table header"));
+        }
+        let crc = u32::from_be_bytes(footer[footer_len - 4..].try_into().expect("4 bytes"));
+        if git_features::hash::crc32(&footer[..footer_len - 4]) != crc {
+            return Err(Error::Corrupt("footer checksum mismatch"));
+        }
+        let u64_at = |pos: usize| u64::from_be_bytes(footer[pos..pos + 8].try_into().expect("8 bytes"));
+        let obj_position_and_id_len = u64_at(header_len + 8);
+        let footer = Footer {
+            start: footer_start,
+            ref_index_position: u64_at(header_len),
+            obj_position: obj_position_and_id_len >> 5,
+            obj_id_len: (obj_position_and_id_len & 0x1f) as usize,
+            obj_index_position: u64_at(header_len + 16),
+            log_position: u64_at(header_len + 24),
+            log_index_position: u64_at(header_len + 32),
+        };
+        let block_size = block::read_u24(&bytes[5..]);
+        let min_update_index = u64::from_be_bytes(bytes[8..16].try_into().expect("8 bytes"));
+        let max_update_index = u64::from_be_bytes(bytes[16..24].try_into().expect("8 bytes"));
+        Ok(Table {
+            data,
+            object_hash,
+            header_len,
+            block_size,
+            min_update_index,
+            max_update_index,
+            footer,
+        })
+    }
+}
+
+/// Access
+impl Table {
+    /// The kind of hash used for the objects the records of this table refer to.
+    pub fn object_hash(&self) -> git_hash::Kind {
+        self.object_hash
+    }
+
+    /// The smallest update index of all records in this table.
+    pub fn min_update_index(&self) -> u64 {
+        self.min_update_index
+    }
+
+    /// The largest update index of all records in this table.
+    pub fn max_update_index(&self) -> u64 {
+        self.max_update_index
+    }
+
+    /// Find the reference with the full `name`, which may also be a [deletion][RefValue::Deletion].
+    pub fn find(&self, name: &BStr) -> Result<Option<RefRecord>, Error> {
+        let mut refs = self.refs_prefixed(name)?;
+        refs.next().transpose().map(|r| r.filter(|r| r.name.as_bstr() == name))
+    }
+
+    /// Return an iterator over all references, including deletions, ordered by name.
+    pub fn refs(&self) -> Result<Refs<'_>, Error> {
+        self.refs_prefixed(b"".as_bstr())
+    }
+
+    /// Return an iterator over all references whose name starts with `prefix`, including deletions, ordered by name.
+    pub fn refs_prefixed(&self, prefix: &BStr) -> Result<Refs<'_>, Error> {
+        Ok(Refs {
+            records: self.seek(block::REF, self.footer.ref_index_position, Some(0), prefix)?,
+            min_update_index: self.min_update_index,
+        })
+    }
+
+    /// Return an iterator over all log entries, ordered by reference name and from newest to oldest.
+    pub fn logs(&self) -> Result<Logs<'_>, Error> {
+        Ok(Logs {
+            records: self.log_records(b"")?,
+        })
+    }
+
+    /// Return an iterator over all log entries of the reference with the full `name`, from newest to oldest.
+    pub fn logs_for(&self, name: &BStr) -> Result<Logs<'_>, Error> {
+        let mut key = name.to_vec();
+        key.push(0);
+        Ok(Logs {
+            records: self.log_records(&key)?,
+        })
+    }
+
+    /// Return all references that point to `id`, directly or after peeling, including references to tags pointing to it.
+    ///
+    /// The object index of the table is used if present.
+    pub fn refs_pointing_to(&self, id: &oid) -> Result<Vec<RefRecord>, Error> {
+        let matches = |r: &RefRecord| match &r.value {
+            RefValue::Peeled(target) => target.as_ref() == id,
+            RefValue::PeeledTag { target, peeled } => target.as_ref() == id || peeled.as_ref() == id,
+            RefValue::Deletion | RefValue::Symbolic(_) => false,
+        };
+        if self.footer.obj_position == 0 {
+            return self.refs()?.filter(|r| r.as_ref().map_or(true, matches)).collect();
+        }
+
+        let key = &id.as_bytes()[..self.footer.obj_id_len.min(id.as_bytes().len())];
+        let mut objects = self.seek(
+            block::OBJ,
+            self.footer.obj_index_position,
+            Some(self.footer.obj_position as usize),
+            key.as_bstr(),
+        )?;
+        let positions = objects.next_with(|record_key, value_type, value| {
+            let count = if value_type == 0 {
+                value.varint()?
+            } else {
+                u64::from(value_type)
+            };
+            let mut positions = Vec::with_capacity(count as usize);
+            let mut position = 0;
+            for _ in 0..count {
+                position += value.varint()?;
+                positions.push(position as usize);
+            }
+            Ok((record_key == key, positions))
+        })?;
+        let positions = match positions {
+            Some((true, positions)) if positions.is_empty() => {
+                return self.refs()?.filter(|r| r.as_ref().map_or(true, matches)).collect()
+            }
+            Some((true, positions)) => positions,
+            _ => return Ok(Vec::new()),
+        };
+
+        let mut out = Vec::new();
+        for position in positions {
+            let block = self
+                .block_at(position)?
+                .filter(|b| b.typ == block::REF)
+                .ok_or(Error::Corrupt("object record doesn't point to a reference block"))?;
+            let mut refs = Refs {
+                records: Records {
+                    blocks: Blocks {
+                        table: self,
+                        typ: block::REF,
+                        offset: None,
+                    },
+                    current: Some((block.cursor(), block)),
+                    prefix: Vec::new(),
+                },
+                min_update_index: self.min_update_index,
+            };
+            for r in &mut refs {
+                let r = r?;
+                if matches(&r) {
+                    out.push(r);
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Block access
+impl Table {
+    pub(crate) fn data(&self) -> &[u8] {
+        self.data.as_ref()
+    }
+
+    fn block_at(&self, offset: usize) -> Result<Option<Block<'_>>, Error> {
+        let header_offset = if offset == 0 { self.header_len } else { 0 };
+        Block::read(self.data(), offset, self.footer.start, header_offset, self.object_hash)
+    }
+
+    /// Return the offset of the block following `block` at `offset`, skipping the padding of blocks that aren't compressed.
+    fn next_block_offset(&self, offset: usize, block: &Block<'_>) -> usize {
+        let next = offset + block.len_in_table;
+        if block.typ != block::LOG && next < self.footer.start && self.data()[next] == 0 {
+            next.max(offset + self.block_size as usize)
+        } else {
+            next
+        }
+    }
+
+    fn log_records(&self, key: &[u8]) -> Result<Records<'_>, Error> {
+        self.seek(
+            block::LOG,
+            self.footer.log_index_position,
+            Some(self.footer.log_position as usize),
+            key.as_bstr(),
+        )
+    }
+
+    /// Position a cursor at the first record of type `typ` whose key is equal or greater than `key`, yielding only
+    /// records whose key starts with `key`.
+    ///
+    /// The index at `index_position` is used if it isn't 0, otherwise blocks are searched from the `first` one on.
+    fn seek(&self, typ: u8, index_position: u64, first: Option<usize>, key: &BStr) -> Result<Records<'_>, Error> {
+        let mut records = Records {
+            blocks: Blocks {
+                table: self,
+                typ,
+                offset: first,
+            },
+            current: None,
+            prefix: key.to_vec(),
+        };
+        if index_position != 0 && !key.is_empty() {
+            let mut position = index_position as usize;
+            loop {
+                let block = self
+                    .block_at(position)?
+                    .ok_or(Error::Corrupt("index points past the last block"))?;
+                let mut cursor = block.seek(key)?;
+                if block.typ == typ {
+                    records.blocks.offset = Some(self.next_block_offset(position, &block));
+                    records.current = Some((cursor, block));
+                    return Ok(records);
+                } else if block.typ != block::INDEX {
+                    return Err(Error::Corrupt("index points to a block of the wrong type"));
+                }
+                position = match cursor.next_record(&block)? {
+                    Some((_, _, mut value)) => value.varint()? as usize,
+                    None => {
+                        records.blocks.offset = None;
+                        return Ok(records);
+                    }
+                };
+            }
+        }
+
+        while let Some((_, block)) = records.blocks.next_block()? {
+            let cursor = block.seek(key)?;
+            if !cursor.is_at_end(&block) {
+                records.current = Some((cursor, block));
+                break;
+            }
+        }
+        Ok(records)
+    }
+}
+
+/// Consecutive blocks of the same type.
+struct Blocks<'a> {
+    table: &'a Table,
+    typ: u8,
+    /// The offset of the next block, or `None` if there are no more blocks.
+    offset: Option<usize>,
+}
+
+impl<'a> Blocks<'a> {
+    fn next_block(&mut self) -> Result<Option<(usize, Block<'a>)>, Error> {
+        let offset = match self.offset.take() {
+            Some(offset) => offset,
+            None => return Ok(None),
+        };
+        match self.table.block_at(offset)? {
+            Some(block) if block.typ == self.typ => {
+                self.offset = Some(self.table.next_block_offset(offset, &block));
+                Ok(Some((offset, block)))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Records of consecutive blocks of the same type whose keys start with a prefix.
+struct Records<'a> {
+    blocks: Blocks<'a>,
+    current: Option<(Cursor, Block<'a>)>,
+    prefix: Vec<u8>,
+}
+
+impl<'a> Records<'a> {
+    /// Decode the next record with `decode(key, value_type, value)`, or return `None` if there is none.
+    fn next_with<T>(
+        &mut self,
+        decode: impl FnOnce(Vec<u8>, u8, &mut Value<'_>) -> Result<T, Error>,
+    ) -> Result<Option<T>, Error> {
+        let res = self.next_with_inner(decode);
+        if !matches!(res, Ok(Some(_))) {
+            self.current = None;
+            self.blocks.offset = None;
+        }
+        res
+    }
+
+    fn next_with_inner<T>(
+        &mut self,
+        decode: impl FnOnce(Vec<u8>, u8, &mut Value<'_>) -> Result<T, Error>,
+    ) -> Result<Option<T>, Error> {
+        loop {
+            if let Some((cursor, block)) = &mut self.current {
+                if let Some((key, value_type, mut value)) = cursor.next_record(block)? {
+                    if !key.starts_with(&self.prefix) {
+                        return Ok(None);
+                    }
+                    let res = decode(key, value_type, &mut value)?;
+                    cursor.advance(&value);
+                    return Ok(Some(res));
+                }
+            }
+            match self.blocks.next_block()? {
+                Some((_, block)) => self.current = Some((block.cursor(), block)),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+/// An iterator over the references of a [`Table`].
+pub struct Refs<'a> {
+    records: Records<'a>,
+    min_update_index: u64,
+}
+
+impl<'a> Iterator for Refs<'a> {
+    type Item = Result<RefRecord, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let min_update_index = self.min_update_index;
+        self.records
+            .next_with(|key, value_type, value| decode_ref(key, value_type, value, min_update_index))
+            .transpose()
+    }
+}
+
+/// An iterator over the log entries of a [`Table`].
+pub struct Logs<'a> {
+    records: Records<'a>,
+}
+
+impl<'a> Iterator for Logs<'a> {
+    type Item = Result<LogRecord, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.records.next_with(decode_log).transpose()
+    }
+}
+
+fn full_name(name: Vec<u8>) -> Result<FullName, Error> {
+    match git_validate::refname(name.as_bstr()) {
+        Ok(_) => Ok(FullName(name.into())),
+        Err(err) => Err(Error::RefnameValidation { err, name: name.into() }),
+    }
+}
+
+fn decode_ref(key: Vec<u8>, value_type: u8, value: &mut Value<'_>, min_update_index: u64) -> Result<RefRecord, Error> {
+    let name = full_name(key)?;
+    let update_index = min_update_index
+        .checked_add(value.varint()?)
+        .ok_or(Error::Corrupt("update index overflow"))?;
+    let value = match value_type {
+        0 => RefValue::Deletion,
+        1 => RefValue::Peeled(value.id()?),
+        2 => RefValue::PeeledTag {
+            target: value.id()?,
+            peeled: value.id()?,
+        },
+        3 => RefValue::Symbolic(full_name(value.bytes()?.to_owned())?),
+        _ => return Err(Error::Corrupt("unknown reference value type")),
+    };
+    Ok(RefRecord {
+        name,
+        update_index,
+        value,
+    })
+}
+
+fn decode_log(mut key: Vec<u8>, value_type: u8, value: &mut Value<'_>) -> Result<LogRecord, Error> {
+    let name_len = key
+        .len()
+        .checked_sub(9)
+        .filter(|pos| key[*pos] == 0)
+        .ok_or(Error::Corrupt("invalid log record key"))?;
+    let update_index = u64::MAX - u64::from_be_bytes(key[name_len + 1..].try_into().expect("8 bytes"));
+    key.truncate(name_len);
+    let name = full_name(key)?;
+    let line = match value_type {
+        0 => None,
+        1 => {
+            let previous_oid = value.id()?;
+            let new_oid = value.id()?;
+            let name = value.bytes()?.into();
+            let email = value.bytes()?.into();
+            let time = u32::try_from(value.varint()?).map_err(|_| Error::Corrupt("log time out of range"))?;
+            let tz_minutes = i16::from_be_bytes(value.take(2)?.try_into().expect("2 bytes"));
+            let mut message = value.bytes()?;
+            if message.last() == Some(&b'\n') {
+                message = &message[..message.len() - 1];
+            }
+            Some(crate::log::Line {
+                previous_oid,
+                new_oid,
+                signature: git_actor::Signature {
+                    name,
+                    email,
+                    time: git_actor::Time {
+                        time,
+                        offset: i32::from(tz_minutes) * 60,
+                        sign: if tz_minutes < 0 {
+                            git_actor::Sign::Minus
+                        } else {
+                            git_actor::Sign::Plus
+                        },
+                    },
+                },
+                message: message.into(),
+            })
+        }
+        _ => return Err(Error::Corrupt("unknown log value type")),
+    };
+    Ok(LogRecord {
+        name,
+        update_index,
+        line,
+    })
+}
+
+mod error {
+    use git_object::bstr::BString;
+    use quick_error::quick_error;
+
+    quick_error! {
+        /// The error returned when reading a [`Table`][crate::reftable::Table].
+        #[derive(Debug)]
+        #[allow(missing_docs)]
+        pub enum Error {
+            Io(err: std::io::Error) {
+                display("The table could not be read")
+                from()
+                source(err)
+            }
+            UnsupportedVersion(version: u8) {
+                display("Reftables of version {} are not supported", version)
+            }
+            ObjectHashMismatch { expected: git_hash::Kind } {
+                display("The reftable doesn't use the expected object hash {:?}", expected)
+            }
+            Corrupt(message: &'static str) {
+                display("The reftable is corrupt: {}", message)
+            }
+            RefnameValidation { err: git_validate::refname::Error, name: BString } {
+                display("Invalid reference name '{}' in table", name)
+                source(err)
+            }
+        }
+    }
+}
+pub use error::Error;
@@ -0,0 +1,400 @@
+use git_hash::ObjectId;
+use git_object::bstr::{BStr, BString, ByteSlice};
+
+use crate::{
+    store::WriteReflog,
+    store_impl::reftable::{LogRecord, RefRecord, RefValue, Stack, Transaction},
+    transaction::{Change, PreviousValue, RefEdit, RefEditsExt, RefLog},
+    FullName, Target,
+};
+
+#[derive(Debug)]
+pub(in crate::store_impl::reftable) struct Edit {
+    update: RefEdit,
+    /// Set if this update is coming from a symbolic reference and used to make it appear like it is the one that is handled,
+    /// instead of the referent reference.
+    parent_index: Option<usize>,
+    /// For symbolic refs, this is the previous OID to put into the reflog instead of our own previous value. It's the
+    /// peeled value of the leaf referent.
+    leaf_referent_previous_oid: Option<ObjectId>,
+    /// Set if this update creates a renamed or copied reference, with the reflog of the original reference to be copied
+    /// to ours before adding our own entry.
+    reflog_source: Option<FullName>,
+}
+
+impl Edit {
+    fn name(&self) -> BString {
+        self.update.name.0.clone()
+    }
+}
+
+impl std::borrow::Borrow<RefEdit> for Edit {
+    fn borrow(&self) -> &RefEdit {
+        &self.update
+    }
+}
+
+impl std::borrow::BorrowMut<RefEdit> for Edit {
+    fn borrow_mut(&mut self) -> &mut RefEdit {
+        &mut self.update
+    }
+}
+
+/// Edits
+impl Stack {
+    /// Open a transaction to change references, which are written into a new table once committed.
+    ///
+    /// Namespaces are not supported.
+    pub fn transaction(&mut self) -> Transaction<'_> {
+        Transaction {
+            stack: self,
+            lock: None,
+            updates: None,
+        }
+    }
+}
+
+impl<'s> Transaction<'s> {
+    /// Lock the stack, determining how to fail if the lock on `tables.list` cannot be obtained, and verify that the
+    /// `edits` can be applied to the most recent state of the stack.
+    ///
+    /// If the operation succeeds, the transaction can be committed or dropped to release the lock without any change.
+    pub fn prepare(
+        mut self,
+        edits: impl IntoIterator<Item = RefEdit>,
+        lock_fail_mode: git_lock::acquire::Fail,
+    ) -> Result<Self, Error> {
+        assert!(self.updates.is_none(), "BUG: Must not call prepare(…) multiple times");
+        let lock = self.stack.lock(lock_fail_mode).map_err(|err| match err {
+            super::stack::Error::LockAcquire(err) => Error::LockAcquire(err),
+            other => Error::Stack(other),
+        })?;
+        self.stack.reload()?;
+        let stack = &*self.stack;
+        let mut updates: Vec<_> = edits
+            .into_iter()
+            .map(|update| Edit {
+                update,
+                parent_index: None,
+                leaf_referent_previous_oid: None,
+                reflog_source: None,
+            })
+            .collect();
+        let find = |name: crate::PartialNameRef<'_>| {
+            stack
+                .find(name.as_bstr())
+                .ok()
+                .flatten()
+                .and_then(RefRecord::into_reference)
+                .map(|r| r.target)
+        };
+        updates
+            .extend_with_splits_of_renames_and_copies(find, |update, reflog_source| Edit {
+                update,
+                parent_index: None,
+                leaf_referent_previous_oid: None,
+                reflog_source,
+            })
+            .map_err(Error::PreprocessingFailed)?;
+        let deleted: Vec<_> = updates
+            .iter()
+            .filter(|edit| matches!(edit.update.change, Change::Delete { .. }))
+            .map(Edit::name)
+            .collect();
+        for edit in updates.iter().filter(|edit| edit.reflog_source.is_some()) {
+            if let Some(existing) = conflicting_reference(stack, edit.update.name.as_bstr(), &deleted)? {
+                return Err(Error::NameConflict {
+                    full_name: edit.name(),
+                    existing,
+                });
+            }
+        }
+        updates
+            .pre_process(find, |idx, update| Edit {
+                update,
+                parent_index: Some(idx),
+                leaf_referent_previous_oid: None,
+                reflog_source: None,
+            })
+            .map_err(Error::PreprocessingFailed)?;
+
+        for cid in 0..updates.len() {
+            let change = &mut updates[cid];
+            let existing = stack
+                .find(change.update.name.as_bstr())?
+                .and_then(RefRecord::into_reference)
+                .map(|r| r.target);
+            verify_expectation(change, existing, stack.object_hash())?;
+
+            // traverse parent chain from leaf/peeled ref and set the leaf previous oid accordingly
+            // to help with their reflog entries
+            if let (Some(crate::TargetRef::Peeled(oid)), Some(parent_idx)) =
+                (change.update.change.previous_value(), change.parent_index)
+            {
+                let oid = oid.to_owned();
+                let mut parent_idx_cursor = Some(parent_idx);
+                while let Some(parent) = parent_idx_cursor.take().map(|idx| &mut updates[idx]) {
+                    parent_idx_cursor = parent.parent_index;
+                    parent.leaf_referent_previous_oid = Some(oid);
+                }
+            }
+        }
+        self.lock = Some(lock);
+        self.updates = Some(updates);
+        Ok(self)
+    }
+
+    /// Write all [prepared][Transaction::prepare()] edits into a new table and add it to the stack, along with reflog
+    /// entries signed by `committer`. Return the performed edits which represent the state of the affected refs before
+    /// the change.
+    ///
+    /// Deleting a reference also deletes its reflog. If [auto-compaction][Stack::auto_compact] is enabled, tables are
+    /// compacted afterwards.
+    pub fn commit(self, committer: &git_actor::Signature) -> Result<Vec<RefEdit>, Error> {
+        let updates = self.updates.expect("BUG: must call prepare before commit");
+        let lock = self.lock.expect("prepared transactions are locked");
+        let stack = self.stack;
+        let update_index = stack.next_update_index();
+
+        let mut refs = Vec::new();
+        let mut logs = Vec::new();
+        for change in &updates {
+            assert!(!change.update.deref, "Deref mode is turned into splits and turned off");
+            let name = &change.update.name;
+            match &change.update.change {
+                Change::Update { log, new, expected } => {
+                    if log.mode == RefLog::AndReference {
+                        refs.push(RefRecord {
+                            name: name.clone(),
+                            update_index,
+                            value: match new {
+                                Target::Peeled(oid) => RefValue::Peeled(*oid),
+                                Target::Symbolic(target) => RefValue::Symbolic(target.clone()),
+                            },
+                        });
+                    }
+                    // no reflog for symref changes
+                    if let Target::Peeled(new_oid) = new {
+                        let (previous, do_update, has_log) = match &change.reflog_source {
+                            // like git, log the unchanged value of the renamed or copied reference
+                            Some(source) => {
+                                let copied = stack.logs_for(source.as_bstr())?;
+                                let has_log = !copied.is_empty();
+                                logs.extend(copied.into_iter().map(|log| LogRecord {
+                                    name: name.clone(),
+                                    ..log
+                                }));
+                                (Some(*new_oid), true, has_log)
+                            }
+                            None => {
+                                let previous = match expected {
+                                    PreviousValue::MustExistAndMatch(Target::Peeled(oid)) => Some(oid.to_owned()),
+                                    _ => None,
+                                }
+                                .or(change.leaf_referent_previous_oid);
+                                (previous, previous.as_ref() != Some(new_oid), false)
+                            }
+                        };
+                        if do_update
+                            && stack.write_reflog == WriteReflog::Normal
+                            && (log.force_create_reflog
+                                || has_log
+                                || should_autocreate_reflog(name.as_bstr())
+                                || stack.log_exists(name.as_bstr())?)
+                        {
+                            logs.push(LogRecord {
+                                name: name.clone(),
+                                update_index,
+                                line: Some(crate::log::Line {
+                                    previous_oid: previous.unwrap_or_else(|| stack.object_hash().null()),
+                                    new_oid: *new_oid,
+                                    signature: committer.to_owned(),
+                                    message: log.message.clone(),
+                                }),
+                            });
+                        }
+                    }
+                }
+                Change::Delete { log: mode, .. } => {
+                    if *mode == RefLog::AndReference {
+                        refs.push(RefRecord {
+                            name: name.clone(),
+                            update_index,
+                            value: RefValue::Deletion,
+                        });
+                    }
+                    for log in stack.logs_for(name.as_bstr())? {
+                        logs.push(LogRecord { line: None, ..log });
+                    }
+                }
+                Change::Rename { .. } | Change::Copy { .. } => {
+                    unreachable!("BUG: renames and copies were split during preprocessing")
+                }
+            }
+        }
+
+        if !refs.is_empty() || !logs.is_empty() {
+            stack.add_table(lock, &refs, &logs)?;
+            if stack.auto_compact {
+                // The transaction is committed at this point, and compaction will be attempted again with the next one.
+                stack.auto_compact().ok();
+            }
+        }
+        Ok(updates.into_iter().map(|edit| edit.update).collect())
+    }
+}
+
+fn should_autocreate_reflog(full_name: &git_object::bstr::BStr) -> bool {
+    full_name.starts_with_str("refs/heads/")
+        || full_name.starts_with_str("refs/remotes/")
+        || full_name.starts_with_str("refs/notes/")
+        || full_name == "HEAD"
+}
+
+/// Return the name of an existing reference that prevents a reference named `name` from being created, as it would be
+/// a prefix of ours, or because it is named like ours followed by a slash.
+///
+/// References in `deleted` are ignored as they are removed in the same table that creates our reference.
+fn conflicting_reference(stack: &Stack, name: &BStr, deleted: &[BString]) -> Result<Option<BString>, Error> {
+    let is_deleted = |name: &BStr| deleted.iter().any(|deleted| deleted == name);
+    for end in name.find_iter("/").skip(1) {
+        let parent = &name[..end];
+        if !is_deleted(parent.as_bstr()) && stack.find(parent)?.and_then(RefRecord::into_reference).is_some() {
+            return Ok(Some(parent.to_owned()));
+        }
+    }
+    let mut prefix = name.to_owned();
+    prefix.push(b'/');
+    for record in stack.refs_prefixed(prefix.as_bstr())? {
+        if let Some(r) = record?.into_reference() {
+            if !is_deleted(r.name.as_bstr()) {
+                return Ok(Some(r.name.into_inner()));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Check the expected value of `change` against the `existing` target of its reference, and set it to the latter.
+fn verify_expectation(change: &mut Edit, existing: Option<Target>, object_hash: git_hash::Kind) -> Result<(), Error> {
+    let full_name = change.name();
+    match &mut change.update.change {
+        Change::Delete { expected, .. } => {
+            match (&*expected, &existing) {
+                (PreviousValue::MustNotExist, _) => {
+                    panic!("BUG: MustNotExist constraint makes no sense if references are to be deleted")
+                }
+                (PreviousValue::ExistingMustMatch(_), None)
+                | (PreviousValue::MustExist, Some(_))
+                | (PreviousValue::Any, Some(_))
+                | (PreviousValue::Any, None) => {}
+                (PreviousValue::MustExist, None) | (PreviousValue::MustExistAndMatch(_), None) => {
+                    return Err(Error::DeleteReferenceMustExist { full_name })
+                }
+                (PreviousValue::MustExistAndMatch(previous), Some(actual))
+                | (PreviousValue::ExistingMustMatch(previous), Some(actual)) => {
+                    if previous != actual {
+                        return Err(Error::ReferenceOutOfDate {
+                            full_name,
+                            expected: previous.clone(),
+                            actual: actual.clone(),
+                        });
+                    }
+                }
+            }
+            if let Some(existing) = existing {
+                *expected = PreviousValue::MustExistAndMatch(existing);
+            }
+        }
+        Change::Update { expected, new, .. } => {
+            match (&*expected, &existing) {
+                (PreviousValue::Any, _)
+                | (PreviousValue::MustExist, Some(_))
+                | (PreviousValue::MustNotExist, None)
+                | (PreviousValue::ExistingMustMatch(_), None) => {}
+                (PreviousValue::MustExist, None) => {
+                    return Err(Error::MustExist {
+                        full_name,
+                        expected: Target::Peeled(object_hash.null()),
+                    })
+                }
+                (PreviousValue::MustNotExist, Some(actual)) => {
+                    if actual != new {
+                        return Err(Error::MustNotExist {
+                            full_name,
+                            actual: actual.clone(),
+                            new: new.clone(),
+                        });
+                    }
+                }
+                (PreviousValue::MustExistAndMatch(previous), Some(actual))
+                | (PreviousValue::ExistingMustMatch(previous), Some(actual)) => {
+                    if previous != actual {
+                        return Err(Error::ReferenceOutOfDate {
+                            full_name,
+                            expected: previous.clone(),
+                            actual: actual.clone(),
+                        });
+                    }
+                }
+                (PreviousValue::MustExistAndMatch(previous), None) => {
+                    return Err(Error::MustExist {
+                        full_name,
+                        expected: previous.clone(),
+                    })
+                }
+            }
+            if let Some(existing) = existing {
+                *expected = PreviousValue::MustExistAndMatch(existing);
+            }
+        }
+        Change::Rename { .. } | Change::Copy { .. } => {
+            unreachable!("BUG: renames and copies were split during preprocessing")
+        }
+    }
+    Ok(())
+}
+
+mod error {
+    use git_object::bstr::BString;
+    use quick_error::quick_error;
+
+    use crate::{store_impl::reftable::stack, Target};
+
+    quick_error! {
+        /// The error returned by [`Transaction`][crate::reftable::Transaction] methods.
+        #[derive(Debug)]
+        #[allow(missing_docs)]
+        pub enum Error {
+            LockAcquire(err: git_lock::acquire::Error) {
+                display("The lock for tables.list could not be obtained")
+                source(err)
+            }
+            Stack(err: stack::Error) {
+                display("The reftable stack could not be read or written")
+                from()
+                source(err)
+            }
+            PreprocessingFailed(err: std::io::Error) {
+                display("Edit preprocessing failed with error: {}", err.to_string())
+                source(err)
+            }
+            DeleteReferenceMustExist { full_name: BString } {
+                display("The reference '{}' for deletion did not exist", full_name)
+            }
+            MustNotExist { full_name: BString, actual: Target, new: Target } {
+                display("Reference '{}' was not supposed to exist when writing it with value {}, but actual content was {}", full_name, new, actual)
+            }
+            MustExist { full_name: BString, expected: Target } {
+                display("Reference '{}' was supposed to exist with value {}, but didn't.", full_name, expected)
+            }
+            ReferenceOutOfDate { full_name: BString, expected: Target, actual: Target } {
+                display("The reference '{}' should have content {}, actual content was {}", full_name, expected, actual)
+            }
+            NameConflict { full_name: BString, existing: BString } {
+                display("The reference '{}' cannot be created as it conflicts with the existing reference '{}'", full_name, existing)
+            }
+        }
+    }
+}
+pub use error::Error;
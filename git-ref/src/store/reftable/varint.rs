@@ -0,0 +1,55 @@
+//! The variable-length integer encoding used by reftables, which is the same as the one for offsets in packs.
+
+/// Append `value` to `out` in its variable-length encoding.
+pub(crate) fn encode(mut value: u64, out: &mut Vec<u8>) {
+    let mut buf = [0u8; 10];
+    let mut pos = buf.len() - 1;
+    buf[pos] = (value & 0x7f) as u8;
+    value >>= 7;
+    while value != 0 {
+        value -= 1;
+        pos -= 1;
+        buf[pos] = 0x80 | (value & 0x7f) as u8;
+        value >>= 7;
+    }
+    out.extend_from_slice(&buf[pos..]);
+}
+
+/// Decode a variable-length integer from the beginning of `data` and return it along with the amount of bytes consumed,
+/// or `None` if `data` ends prematurely or the value overflows.
+pub(crate) fn decode(data: &[u8]) -> Option<(u64, usize)> {
+    let mut bytes = data.iter();
+    let mut c = *bytes.next()?;
+    let mut value = u64::from(c & 0x7f);
+    let mut consumed = 1;
+    while c & 0x80 != 0 {
+        c = *bytes.next()?;
+        consumed += 1;
+        value = value.checked_add(1)?;
+        if value.leading_zeros() < 7 {
+            return None;
+        }
+        value = (value << 7) | u64::from(c & 0x7f);
+    }
+    Some((value, consumed))
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn round_trip() {
+        for value in &[0u64, 1, 127, 128, 255, 16383, 16384, 1 << 32, u64::MAX - 1, u64::MAX] {
+            let mut buf = Vec::new();
+            super::encode(*value, &mut buf);
+            assert_eq!(super::decode(&buf), Some((*value, buf.len())), "{}", value);
+        }
+    }
+
+    #[test]
+    fn truncated_input_is_rejected() {
+        let mut buf = Vec::new();
+        super::encode(1 << 20, &mut buf);
+        assert_eq!(super::decode(&buf[..buf.len() - 1]), None);
+        assert_eq!(super::decode(&[]), None);
+    }
+}
@@ -0,0 +1,331 @@
+use std::collections::BTreeMap;
+
+use git_hash::ObjectId;
+
+use crate::store_impl::reftable::{block, table, varint, LogRecord, RefRecord, RefValue};
+
+/// The amount of blocks in a section from which on an index is written for it.
+const INDEX_THRESHOLD: usize = 4;
+/// The smallest amount of bytes the object id prefixes used as keys of object records may have.
+const MIN_OBJ_ID_LEN: usize = 2;
+
+/// Options for writing [tables][table()].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Options {
+    /// The size of each block in bytes, which should be the size of a page of the file system.
+    pub block_size: u32,
+    /// If true, write an index of all objects pointed to by references to quickly find the references pointing to them.
+    pub write_object_index: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            block_size: 4096,
+            write_object_index: true,
+        }
+    }
+}
+
+/// Serialize `refs` and `logs` into a table whose references have update indices from `min_update_index` to
+/// `max_update_index`, configured by `options`.
+///
+/// Log records may have any update index, as deleting or copying log entries keeps their original one.
+///
+/// The records don't have to be sorted, but each reference may only appear once, as well as each log entry for a
+/// reference and update index.
+pub fn table(
+    refs: &[RefRecord],
+    logs: &[LogRecord],
+    min_update_index: u64,
+    max_update_index: u64,
+    options: Options,
+) -> Result<Vec<u8>, Error> {
+    if !(256..1 << 24).contains(&options.block_size) {
+        return Err(Error::BlockSize(options.block_size));
+    }
+    let block_size = options.block_size as usize;
+    let check_update_index = |name: &crate::FullName, update_index: u64| {
+        if update_index < min_update_index || update_index > max_update_index {
+            Err(Error::UpdateIndexOutOfRange {
+                name: name.as_bstr().to_owned(),
+                update_index,
+            })
+        } else {
+            Ok(())
+        }
+    };
+
+    let mut refs: Vec<_> = refs.iter().collect();
+    refs.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut logs: Vec<_> = logs.iter().map(|log| (log_key(log), log)).collect();
+    logs.sort_by(|a, b| a.0.cmp(&b.0));
+    if let Some(r) = refs.windows(2).find(|r| r[0].name == r[1].name) {
+        return Err(Error::DuplicateRecord {
+            name: r[0].name.as_bstr().to_owned(),
+        });
+    }
+    if let Some(log) = logs.windows(2).find(|log| log[0].0 == log[1].0) {
+        return Err(Error::DuplicateRecord {
+            name: log[0].1.name.as_bstr().to_owned(),
+        });
+    }
+
+    let mut out = header(block_size as u32, min_update_index, max_update_index);
+    let mut ref_records = Vec::with_capacity(refs.len());
+    for r in &refs {
+        check_update_index(&r.name, r.update_index)?;
+        ref_records.push((r.name.as_bstr().to_vec(), ref_value(r, min_update_index)));
+    }
+    let (ref_blocks, block_of_ref) = write_section(&mut out, block::REF, ref_records, block_size)?;
+    let ref_index_position = write_index(&mut out, ref_blocks.clone(), block_size)?;
+
+    let (mut obj_position, mut obj_id_len, mut obj_index_position) = (0, 0, 0);
+    if options.write_object_index && !refs.is_empty() {
+        let mut blocks_by_id = BTreeMap::<ObjectId, Vec<u64>>::new();
+        for (r, block) in refs.iter().zip(block_of_ref) {
+            let position = ref_blocks[block].1;
+            let mut add = |id: &ObjectId| {
+                let positions = blocks_by_id.entry(*id).or_default();
+                if positions.last() != Some(&position) {
+                    positions.push(position);
+                }
+            };
+            match &r.value {
+                RefValue::Peeled(id) => add(id),
+                RefValue::PeeledTag { target, peeled } => {
+                    add(target);
+                    add(peeled);
+                }
+                RefValue::Deletion | RefValue::Symbolic(_) => {}
+            }
+        }
+        if !blocks_by_id.is_empty() {
+            let ids: Vec<_> = blocks_by_id.keys().collect();
+            obj_id_len = ids
+                .windows(2)
+                .map(|ids| {
+                    ids[0]
+                        .as_bytes()
+                        .iter()
+                        .zip(ids[1].as_bytes())
+                        .take_while(|(a, b)| a == b)
+                        .count()
+                        + 1
+                })
+                .max()
+                .unwrap_or(0)
+                .max(MIN_OBJ_ID_LEN);
+            let obj_records = blocks_by_id.iter().map(|(id, positions)| {
+                let mut value = Vec::new();
+                let value_type = if positions.len() < 8 {
+                    positions.len() as u8
+                } else {
+                    varint::encode(positions.len() as u64, &mut value);
+                    0
+                };
+                let mut previous = 0;
+                for position in positions {
+                    varint::encode(position - previous, &mut value);
+                    previous = *position;
+                }
+                (id.as_bytes()[..obj_id_len].to_vec(), (value_type, value))
+            });
+            obj_position = out.len() as u64;
+            let (obj_blocks, _) = write_section(&mut out, block::OBJ, obj_records, block_size)?;
+            obj_index_position = write_index(&mut out, obj_blocks, block_size)?;
+        }
+    }
+
+    let mut log_records = Vec::with_capacity(logs.len());
+    for (key, log) in logs {
+        log_records.push((key, log_value(log)));
+    }
+    let log_position = if log_records.is_empty() { 0 } else { out.len() as u64 };
+    let (log_blocks, _) = write_section(&mut out, block::LOG, log_records, block_size)?;
+    let log_index_position = write_index(&mut out, log_blocks, block_size)?;
+
+    let footer_start = out.len();
+    out.extend_from_slice(&header(block_size as u32, min_update_index, max_update_index));
+    for position in &[
+        ref_index_position,
+        obj_position << 5 | obj_id_len as u64,
+        obj_index_position,
+        log_position,
+        log_index_position,
+    ] {
+        out.extend_from_slice(&position.to_be_bytes());
+    }
+    let crc = git_features::hash::crc32(&out[footer_start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+    Ok(out)
+}
+
+fn header(block_size: u32, min_update_index: u64, max_update_index: u64) -> Vec<u8> {
+    let mut header = Vec::with_capacity(table::HEADER_LEN_V1);
+    header.extend_from_slice(table::MAGIC);
+    header.push(1);
+    let mut size = [0u8; 3];
+    block::put_u24(block_size, &mut size);
+    header.extend_from_slice(&size);
+    header.extend_from_slice(&min_update_index.to_be_bytes());
+    header.extend_from_slice(&max_update_index.to_be_bytes());
+    header
+}
+
+fn ref_value(r: &RefRecord, min_update_index: u64) -> (u8, Vec<u8>) {
+    let mut value = Vec::new();
+    varint::encode(r.update_index - min_update_index, &mut value);
+    let value_type = match &r.value {
+        RefValue::Deletion => 0,
+        RefValue::Peeled(id) => {
+            value.extend_from_slice(id.as_bytes());
+            1
+        }
+        RefValue::PeeledTag { target, peeled } => {
+            value.extend_from_slice(target.as_bytes());
+            value.extend_from_slice(peeled.as_bytes());
+            2
+        }
+        RefValue::Symbolic(target) => {
+            varint::encode(target.as_bstr().len() as u64, &mut value);
+            value.extend_from_slice(target.as_bstr());
+            3
+        }
+    };
+    (value_type, value)
+}
+
+/// Log records are ordered by name, and then from newest to oldest.
+fn log_key(log: &LogRecord) -> Vec<u8> {
+    let mut key = log.name.as_bstr().to_vec();
+    key.push(0);
+    key.extend_from_slice(&(u64::MAX - log.update_index).to_be_bytes());
+    key
+}
+
+fn log_value(log: &LogRecord) -> (u8, Vec<u8>) {
+    let line = match &log.line {
+        Some(line) => line,
+        None => return (0, Vec::new()),
+    };
+    let mut value = Vec::new();
+    value.extend_from_slice(line.previous_oid.as_bytes());
+    value.extend_from_slice(line.new_oid.as_bytes());
+    for field in &[&line.signature.name, &line.signature.email] {
+        varint::encode(field.len() as u64, &mut value);
+        value.extend_from_slice(field);
+    }
+    varint::encode(u64::from(line.signature.time.time), &mut value);
+    value.extend_from_slice(&((line.signature.time.offset / 60) as i16).to_be_bytes());
+    let message_len = line.message.len() + usize::from(!line.message.is_empty());
+    varint::encode(message_len as u64, &mut value);
+    value.extend_from_slice(&line.message);
+    if !line.message.is_empty() {
+        value.push(b'\n');
+    }
+    (1, value)
+}
+
+/// The last key and position of each block of a section.
+type BlockKeys = Vec<(Vec<u8>, u64)>;
+
+/// Write `records` with their key, value type and value into blocks of type `typ` and return the last key and position
+/// of each block, along with the number of the block each record was written to.
+fn write_section(
+    out: &mut Vec<u8>,
+    typ: u8,
+    records: impl IntoIterator<Item = (Vec<u8>, (u8, Vec<u8>))>,
+    block_size: usize,
+) -> std::io::Result<(BlockKeys, Vec<usize>)> {
+    let mut blocks = Vec::new();
+    let mut block_of_record = Vec::new();
+    let mut writer: Option<block::Writer> = None;
+    for (key, (value_type, value)) in records {
+        let w = writer.get_or_insert_with(|| new_block(out, typ, block_size));
+        if !w.add(&key, value_type, &value) {
+            let full = writer.take().expect("present");
+            finish_block(out, full, &mut blocks)?;
+            let w = writer.get_or_insert_with(|| new_block(out, typ, block_size));
+            let added = w.add(&key, value_type, &value);
+            debug_assert!(added, "empty blocks take any record");
+        }
+        block_of_record.push(blocks.len());
+    }
+    if let Some(w) = writer {
+        finish_block(out, w, &mut blocks)?;
+    }
+    Ok((blocks, block_of_record))
+}
+
+fn new_block(out: &[u8], typ: u8, block_size: usize) -> block::Writer {
+    let header_offset = if out.len() == table::HEADER_LEN_V1 {
+        table::HEADER_LEN_V1
+    } else {
+        0
+    };
+    block::Writer::new(typ, header_offset, block_size)
+}
+
+fn finish_block(out: &mut Vec<u8>, writer: block::Writer, blocks: &mut BlockKeys) -> std::io::Result<()> {
+    let last_key = writer.last_key().to_owned();
+    let is_first = out.len() == table::HEADER_LEN_V1;
+    let bytes = writer.finish()?;
+    if is_first {
+        // the header was reserved in the block, but is already written.
+        blocks.push((last_key, 0));
+        out.extend_from_slice(&bytes[table::HEADER_LEN_V1..]);
+    } else {
+        blocks.push((last_key, out.len() as u64));
+        out.extend_from_slice(&bytes);
+    }
+    Ok(())
+}
+
+/// Write an index for `blocks` if there are enough of them, with as many levels as needed for the top level to fit into
+/// a single block, and return the position of the top level index block or 0 if there is no index.
+fn write_index(out: &mut Vec<u8>, mut blocks: BlockKeys, block_size: usize) -> std::io::Result<u64> {
+    if blocks.len() < INDEX_THRESHOLD {
+        return Ok(0);
+    }
+    loop {
+        let records = blocks.into_iter().map(|(key, position)| {
+            let mut value = Vec::new();
+            varint::encode(position, &mut value);
+            (key, (0, value))
+        });
+        let (index_blocks, _) = write_section(out, block::INDEX, records, block_size)?;
+        if index_blocks.len() == 1 {
+            return Ok(index_blocks[0].1);
+        }
+        blocks = index_blocks;
+    }
+}
+
+mod error {
+    use git_object::bstr::BString;
+    use quick_error::quick_error;
+
+    quick_error! {
+        /// The error returned by [`table()`][super::table()].
+        #[derive(Debug)]
+        #[allow(missing_docs)]
+        pub enum Error {
+            Io(err: std::io::Error) {
+                display("A log block could not be compressed")
+                from()
+                source(err)
+            }
+            BlockSize(size: u32) {
+                display("The block size must be at least 256 bytes and less than 16MB, got {}", size)
+            }
+            DuplicateRecord { name: BString } {
+                display("The reference '{}' or one of its log entries was written more than once", name)
+            }
+            UpdateIndexOutOfRange { name: BString, update_index: u64 } {
+                display("The update index {} of a record of reference '{}' is out of range", update_index, name)
+            }
+        }
+    }
+}
+pub use error::Error;
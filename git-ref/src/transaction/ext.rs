@@ -1,8 +1,10 @@
+use std::convert::TryInto;
+
 use git_object::bstr::BString;
 
 use crate::{
     transaction::{Change, LogChange, PreviousValue, RefEdit, RefLog, Target},
-    PartialNameRef,
+    FullName, PartialNameRef,
 };
 
 /// An extension trait to perform commonly used operations on edits across different ref stores.
@@ -22,6 +24,21 @@ where
         make_entry: impl FnMut(usize, RefEdit) -> T,
     ) -> Result<(), std::io::Error>;
 
+    /// Split all renames and copies into an update of the reference with the new name, created with `make_entry` along with
+    /// the name of the reference whose reflog to move or copy, and an update of `HEAD` if it is a symbolic reference to a
+    /// renamed reference. `find` is used to obtain the current value of references.
+    ///
+    /// The renames themselves turn into deletions, and the copies into updates of the copied reference to its current
+    /// value which only serve to assure it doesn't change.
+    ///
+    /// Note that only the `HEAD` visible through `find` is updated, which is why stores supporting linked worktrees must
+    /// refuse renaming references that the `HEAD` of any other worktree points to.
+    fn extend_with_splits_of_renames_and_copies(
+        &mut self,
+        find: impl FnMut(PartialNameRef<'_>) -> Option<Target>,
+        make_entry: impl FnMut(RefEdit, Option<FullName>) -> T,
+    ) -> Result<(), std::io::Error>;
+
     /// All processing steps in one and in the correct order.
     ///
     /// Users call this to assure derefs are honored and duplicate checks are done.
@@ -53,6 +70,102 @@ where
         }
     }
 
+    fn extend_with_splits_of_renames_and_copies(
+        &mut self,
+        mut find: impl FnMut(PartialNameRef<'_>) -> Option<Target>,
+        mut make_entry: impl FnMut(RefEdit, Option<FullName>) -> E,
+    ) -> Result<(), std::io::Error> {
+        let head: FullName = "HEAD".try_into().expect("valid name");
+        let mut head_has_edit = self.iter().any(|edit| edit.borrow().name == head);
+        let mut new_edits = Vec::new();
+        for edit in self.iter_mut() {
+            let edit = edit.borrow_mut();
+            let (expected, new_name, log, is_rename) = match &edit.change {
+                Change::Rename {
+                    expected,
+                    new_name,
+                    log,
+                } => (expected.clone(), new_name.clone(), log.clone(), true),
+                Change::Copy {
+                    expected,
+                    new_name,
+                    log,
+                } => (expected.clone(), new_name.clone(), log.clone(), false),
+                Change::Update { .. } | Change::Delete { .. } => continue,
+            };
+            edit.deref = false;
+            let current = match expected {
+                PreviousValue::MustExistAndMatch(target) | PreviousValue::ExistingMustMatch(target) => target,
+                PreviousValue::Any | PreviousValue::MustExist => find(edit.name.to_partial()).ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        format!(
+                            "The reference '{}' to rename or copy does not exist",
+                            edit.name.as_bstr()
+                        ),
+                    )
+                })?,
+                PreviousValue::MustNotExist => {
+                    panic!("BUG: MustNotExist constraint makes no sense if references are to be renamed or copied")
+                }
+            };
+
+            new_edits.push(make_entry(
+                RefEdit {
+                    change: Change::Update {
+                        log: LogChange {
+                            mode: RefLog::AndReference,
+                            force_create_reflog: log.force_create_reflog,
+                            message: log.message.clone(),
+                        },
+                        expected: PreviousValue::MustNotExist,
+                        new: current.clone(),
+                    },
+                    name: new_name.clone(),
+                    deref: false,
+                },
+                Some(edit.name.clone()),
+            ));
+            if is_rename {
+                if !head_has_edit && find(head.to_partial()) == Some(Target::Symbolic(edit.name.clone())) {
+                    head_has_edit = true;
+                    new_edits.push(make_entry(
+                        RefEdit {
+                            change: Change::Update {
+                                log: LogChange {
+                                    mode: RefLog::AndReference,
+                                    force_create_reflog: false,
+                                    message: log.message,
+                                },
+                                expected: PreviousValue::MustExistAndMatch(Target::Symbolic(edit.name.clone())),
+                                new: Target::Symbolic(new_name),
+                            },
+                            name: head.clone(),
+                            deref: false,
+                        },
+                        None,
+                    ));
+                }
+                edit.change = Change::Delete {
+                    expected: PreviousValue::MustExistAndMatch(current),
+                    log: RefLog::AndReference,
+                };
+            } else {
+                edit.change = Change::Update {
+                    log: LogChange {
+                        mode: RefLog::Only,
+                        force_create_reflog: false,
+                        message: log.message,
+                    },
+                    expected: PreviousValue::MustExistAndMatch(current.clone()),
+                    new: current,
+                };
+            }
+        }
+        self.append(&mut new_edits);
+        Ok(())
+    }
+
     fn extend_with_splits_of_symbolic_refs(
         &mut self,
         mut find: impl FnMut(PartialNameRef<'_>) -> Option<Target>,
@@ -111,6 +224,9 @@ where
                                     deref: true,
                                 }
                             }
+                            Change::Rename { .. } | Change::Copy { .. } => {
+                                unreachable!("BUG: renames and copies are split before following symbolic refs")
+                            }
                         },
                     ));
                 }
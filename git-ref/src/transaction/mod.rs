@@ -5,6 +5,7 @@
 //!
 //! * create or update reference
 //! * delete references
+//! * rename or copy references along with their reflog
 //!
 //! The following guarantees are made:
 //!
@@ -80,6 +81,33 @@ pub enum Change {
         /// How to thread the reference log during deletion.
         log: RefLog,
     },
+    /// Rename a reference to `new_name`, which must not exist yet, and move its reflog along with it before adding an entry
+    /// with the `log` message to it. `HEAD` is changed to point to `new_name` if it pointed to the reference before.
+    ///
+    /// Renames are turned into a deletion of the reference and an update of `new_name` when preparing the transaction, which
+    /// is what is returned once it was committed. The `deref` flag of the edit has no effect.
+    Rename {
+        /// The expected value of the reference, with the `MustNotExist` variant being invalid.
+        expected: PreviousValue,
+        /// The name the reference should have after the rename.
+        new_name: FullName,
+        /// The message for the entry in the reflog of `new_name`, which is also used for the reflog of `HEAD` if it is
+        /// changed as well.
+        log: LogChange,
+    },
+    /// Copy a reference to `new_name`, which must not exist yet, along with its reflog before adding an entry with the `log`
+    /// message to it.
+    ///
+    /// Copies are turned into an update of `new_name` and an update of the reference to its current value which only
+    /// assures it doesn't change, when preparing the transaction. The `deref` flag of the edit has no effect.
+    Copy {
+        /// The expected value of the reference, with the `MustNotExist` variant being invalid.
+        expected: PreviousValue,
+        /// The name of the copy.
+        new_name: FullName,
+        /// The message for the entry in the reflog of `new_name`.
+        log: LogChange,
+    },
 }
 
 impl Change {
@@ -102,6 +130,22 @@ impl Change {
             | Change::Delete {
                 expected: PreviousValue::ExistingMustMatch(previous),
                 ..
+            }
+            | Change::Rename {
+                expected: PreviousValue::MustExistAndMatch(previous),
+                ..
+            }
+            | Change::Rename {
+                expected: PreviousValue::ExistingMustMatch(previous),
+                ..
+            }
+            | Change::Copy {
+                expected: PreviousValue::MustExistAndMatch(previous),
+                ..
+            }
+            | Change::Copy {
+                expected: PreviousValue::ExistingMustMatch(previous),
+                ..
             } => previous,
             _ => return None,
         }
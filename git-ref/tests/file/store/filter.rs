@@ -0,0 +1,288 @@
+use std::cmp::Ordering;
+
+use git_hash::ObjectId;
+use git_odb::pack::Find;
+use git_ref::file::filter::{matches, version_cmp, Options, Sort};
+
+use crate::file::{store_at, Store};
+
+fn filtered(store: &Store, options: Options) -> crate::Result<Vec<String>> {
+    let odb = git_odb::at(store.base().join("objects"))?;
+    Ok(store
+        .iter()?
+        .filtered(&options, |oid, buf| {
+            odb.try_find(oid, buf)
+                .map(|obj| obj.map(|(obj, _)| (obj.kind, obj.data)))
+        })?
+        .into_iter()
+        .map(|r| r.name.as_bstr().to_string())
+        .collect())
+}
+
+fn id(store: &Store, name: &str) -> crate::Result<ObjectId> {
+    Ok(store.find(name)?.target.into_id())
+}
+
+#[test]
+fn version_comparison_orders_numbers_by_value() {
+    for &(a, b, expected) in &[
+        ("v1.9", "v1.10", Ordering::Less),
+        ("v1.10", "v1.10", Ordering::Equal),
+        ("v1.10.1", "v1.10", Ordering::Greater),
+        ("v2", "v10", Ordering::Less),
+        ("v01", "v1", Ordering::Greater),
+        ("v1.9", "w1", Ordering::Less),
+        ("", "v1", Ordering::Less),
+    ] {
+        assert_eq!(version_cmp(a.into(), b.into()), expected, "{} vs {}", a, b);
+    }
+}
+
+#[test]
+fn patterns_match_globs_and_path_prefixes() {
+    for &(pattern, name, expected) in &[
+        ("refs/heads/feature/*", "refs/heads/feature/old", true),
+        ("refs/heads/feature/*", "refs/heads/feature/sub/deep", false),
+        ("refs/heads/feature/**", "refs/heads/feature/sub/deep", true),
+        ("refs/heads", "refs/heads/main", true),
+        ("refs/heads/", "refs/heads/main", true),
+        ("refs/heads/ma", "refs/heads/main", false),
+        ("refs/heads/main", "refs/heads/main", true),
+        ("refs/*/main", "refs/heads/main", true),
+    ] {
+        assert_eq!(
+            matches(pattern.into(), name.into()),
+            expected,
+            "{} vs {}",
+            pattern,
+            name
+        );
+    }
+}
+
+#[test]
+fn without_options_all_references_are_returned_sorted_by_name() -> crate::Result {
+    let store = store_at("make_repo_for_ref_filtering.sh")?;
+    assert_eq!(
+        filtered(&store, Options::default())?,
+        vec![
+            "refs/heads/alias",
+            "refs/heads/dangling",
+            "refs/heads/feature/old",
+            "refs/heads/feature/sub/deep",
+            "refs/heads/main",
+            "refs/heads/topic",
+            "refs/tags/tree-tag",
+            "refs/tags/v1.10",
+            "refs/tags/v1.2",
+            "refs/tags/v1.9",
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn patterns_and_exclusions() -> crate::Result {
+    let store = store_at("make_repo_for_ref_filtering.sh")?;
+    assert_eq!(
+        filtered(
+            &store,
+            Options {
+                patterns: vec!["refs/heads/feature/*".into(), "refs/tags".into()],
+                exclude: vec!["refs/tags/v1.1*".into(), "refs/tags/tree-tag".into()],
+                ..Default::default()
+            }
+        )?,
+        vec!["refs/heads/feature/old", "refs/tags/v1.2", "refs/tags/v1.9"]
+    );
+    Ok(())
+}
+
+#[test]
+fn version_sort_ascending_and_descending() -> crate::Result {
+    let store = store_at("make_repo_for_ref_filtering.sh")?;
+    let options = Options {
+        patterns: vec!["refs/tags/v*".into()],
+        sort: Sort::Version,
+        ..Default::default()
+    };
+    assert_eq!(
+        filtered(&store, options.clone())?,
+        vec!["refs/tags/v1.2", "refs/tags/v1.9", "refs/tags/v1.10"]
+    );
+    assert_eq!(
+        filtered(
+            &store,
+            Options {
+                descending: true,
+                ..options
+            }
+        )?,
+        vec!["refs/tags/v1.10", "refs/tags/v1.9", "refs/tags/v1.2"]
+    );
+    Ok(())
+}
+
+#[test]
+fn committer_date_sort_peels_tags_and_puts_dangling_symbolic_refs_first() -> crate::Result {
+    let store = store_at("make_repo_for_ref_filtering.sh")?;
+    assert_eq!(
+        filtered(
+            &store,
+            Options {
+                sort: Sort::CommitterDate,
+                ..Default::default()
+            }
+        )?,
+        vec![
+            "refs/heads/dangling",
+            "refs/tags/tree-tag",
+            "refs/heads/feature/old",
+            "refs/tags/v1.2",
+            "refs/heads/alias",
+            "refs/heads/topic",
+            "refs/heads/feature/sub/deep",
+            "refs/tags/v1.9",
+            "refs/heads/main",
+            "refs/tags/v1.10",
+        ],
+        "ties are broken by name"
+    );
+    assert_eq!(
+        filtered(
+            &store,
+            Options {
+                sort: Sort::CommitterDate,
+                descending: true,
+                ..Default::default()
+            }
+        )?,
+        vec![
+            "refs/heads/main",
+            "refs/tags/v1.10",
+            "refs/heads/feature/sub/deep",
+            "refs/tags/v1.9",
+            "refs/heads/alias",
+            "refs/heads/topic",
+            "refs/heads/feature/old",
+            "refs/tags/v1.2",
+            "refs/heads/dangling",
+            "refs/tags/tree-tag",
+        ],
+        "ties are still broken by ascending name, like in git"
+    );
+    Ok(())
+}
+
+#[test]
+fn points_at_matches_direct_and_peeled_objects() -> crate::Result {
+    let store = store_at("make_repo_for_ref_filtering.sh")?;
+    let points_at = |id| {
+        filtered(
+            &store,
+            Options {
+                points_at: Some(id),
+                ..Default::default()
+            },
+        )
+    };
+    assert_eq!(
+        points_at(id(&store, "feature/sub/deep")?)?,
+        vec!["refs/heads/feature/sub/deep", "refs/tags/v1.9"]
+    );
+    assert_eq!(points_at(id(&store, "v1.9")?)?, vec!["refs/tags/v1.9"]);
+    assert_eq!(
+        points_at(id(&store, "topic")?)?,
+        vec!["refs/heads/alias", "refs/heads/topic"],
+        "symbolic refs are followed"
+    );
+    Ok(())
+}
+
+#[test]
+fn merged_and_no_merged_select_by_reachability_from_a_commit() -> crate::Result {
+    let store = store_at("make_repo_for_ref_filtering.sh")?;
+    let topic = id(&store, "topic")?;
+    assert_eq!(
+        filtered(
+            &store,
+            Options {
+                merged: Some(topic),
+                ..Default::default()
+            }
+        )?,
+        vec![
+            "refs/heads/alias",
+            "refs/heads/feature/old",
+            "refs/heads/feature/sub/deep",
+            "refs/heads/topic",
+            "refs/tags/v1.2",
+            "refs/tags/v1.9",
+        ]
+    );
+    assert_eq!(
+        filtered(
+            &store,
+            Options {
+                no_merged: Some(topic),
+                ..Default::default()
+            }
+        )?,
+        vec!["refs/heads/main", "refs/tags/v1.10"],
+        "references to non-commits are never selected"
+    );
+    assert_eq!(
+        filtered(
+            &store,
+            Options {
+                merged: Some(topic),
+                no_merged: Some(id(&store, "feature/old")?),
+                ..Default::default()
+            }
+        )?,
+        vec![
+            "refs/heads/alias",
+            "refs/heads/feature/sub/deep",
+            "refs/heads/topic",
+            "refs/tags/v1.9",
+        ],
+        "both are applied, each with its own commit"
+    );
+    Ok(())
+}
+
+#[test]
+fn contains_and_no_contains_select_by_history() -> crate::Result {
+    let store = store_at("make_repo_for_ref_filtering.sh")?;
+    let deep = id(&store, "feature/sub/deep")?;
+    assert_eq!(
+        filtered(
+            &store,
+            Options {
+                contains: Some(deep),
+                ..Default::default()
+            }
+        )?,
+        vec![
+            "refs/heads/alias",
+            "refs/heads/feature/sub/deep",
+            "refs/heads/main",
+            "refs/heads/topic",
+            "refs/tags/v1.10",
+            "refs/tags/v1.9",
+        ]
+    );
+    assert_eq!(
+        filtered(
+            &store,
+            Options {
+                contains: Some(deep),
+                no_contains: Some(id(&store, "main")?),
+                patterns: vec!["refs/heads".into()],
+                ..Default::default()
+            }
+        )?,
+        vec!["refs/heads/alias", "refs/heads/feature/sub/deep", "refs/heads/topic"]
+    );
+    Ok(())
+}
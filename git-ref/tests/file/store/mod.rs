@@ -1,3 +1,5 @@
+mod filter;
 mod find;
 mod iter;
+mod pseudo;
 mod reflog;
@@ -0,0 +1,117 @@
+use git_hash::ObjectId;
+use git_lock::acquire::Fail;
+use git_ref::pseudo::{FetchHeadEntry, Kind};
+
+use crate::file::{store_at, store_writable};
+
+fn id(store: &crate::file::Store, name: &str) -> crate::Result<ObjectId> {
+    Ok(store.find_loose(name)?.target.into_id())
+}
+
+#[test]
+fn fetch_head_with_markers_and_descriptions() -> crate::Result {
+    let store = store_at("make_repo_with_pseudo_refs.sh")?;
+    let entries = store.fetch_head()?.expect("present");
+    assert_eq!(
+        entries,
+        vec![
+            FetchHeadEntry {
+                id: id(&store, "a")?,
+                not_for_merge: false,
+                description: "branch 'a' of .".into()
+            },
+            FetchHeadEntry {
+                id: id(&store, "b")?,
+                not_for_merge: true,
+                description: "branch 'b' of .".into()
+            },
+            FetchHeadEntry {
+                id: id(&store, "refs/remotes/origin/main")?,
+                not_for_merge: true,
+                description: "branch 'main' of .".into()
+            },
+        ]
+    );
+    assert_eq!(
+        git_ref::pseudo::encode_fetch_head(&entries),
+        std::fs::read(store.pseudo_ref_path(Kind::FetchHead))?,
+        "round-trips exactly"
+    );
+    assert_eq!(
+        store.pseudo_ref_id(Kind::FetchHead)?,
+        Some(entries[0].id),
+        "the first entry is the one git resolves"
+    );
+    Ok(())
+}
+
+#[test]
+fn merge_heads_of_octopus_merge_and_orig_head() -> crate::Result {
+    let store = store_at("make_repo_with_pseudo_refs.sh")?;
+    assert_eq!(store.merge_heads()?, Some(vec![id(&store, "a")?, id(&store, "b")?]));
+    assert_eq!(store.pseudo_ref_id(Kind::MergeHead)?, Some(id(&store, "a")?));
+    assert_eq!(store.pseudo_ref_id(Kind::OrigHead)?, Some(id(&store, "main")?));
+    assert!(store.pseudo_ref_exists(Kind::MergeHead));
+
+    for kind in &[Kind::CherryPickHead, Kind::RevertHead, Kind::AutoMerge] {
+        assert!(!store.pseudo_ref_exists(*kind));
+        assert_eq!(store.pseudo_ref_id(*kind)?, None);
+    }
+    Ok(())
+}
+
+#[test]
+fn write_and_remove() -> crate::Result {
+    let (_keep, store) = store_writable("make_repo_with_pseudo_refs.sh")?;
+    let (a, b) = (id(&store, "a")?, id(&store, "b")?);
+
+    store.write_merge_heads(vec![b, a], Fail::Immediately)?;
+    assert_eq!(store.merge_heads()?, Some(vec![b, a]));
+
+    let entry = FetchHeadEntry {
+        id: b,
+        not_for_merge: true,
+        description: "tag 'v1' of https://example.com/repo".into(),
+    };
+    store.write_fetch_head(Some(&entry), Fail::Immediately)?;
+    assert_eq!(store.fetch_head()?, Some(vec![entry]));
+
+    for kind in Kind::ALL {
+        store.write_pseudo_ref_id(*kind, a, Fail::Immediately)?;
+        assert_eq!(store.pseudo_ref_id(*kind)?, Some(a));
+        assert_eq!(
+            store.find_loose(kind.file_name())?.target.into_id(),
+            a,
+            "the first id can also be read as loose reference, just like git does"
+        );
+        assert!(store.remove_pseudo_ref(*kind)?);
+        assert!(!store.remove_pseudo_ref(*kind)?, "it's already gone");
+        assert_eq!(store.pseudo_ref_id(*kind)?, None);
+    }
+    Ok(())
+}
+
+mod decode {
+    use git_ref::pseudo::decode;
+
+    #[test]
+    fn fetch_head_lines_without_tabs_and_empty_lines() -> crate::Result {
+        let entries = decode::fetch_head(b"\n0000000000000000000000000000000000000001\n")?;
+        assert_eq!(entries.len(), 1);
+        assert!(!entries[0].not_for_merge);
+        assert!(entries[0].description.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_lines_are_errors() {
+        for input in &[
+            &b"0000000000000000000000000000000000000001\tmerge\tdescription"[..],
+            b"not a hash",
+            b"000000000000000000000000000000000000000",
+        ] {
+            assert!(decode::fetch_head(input).is_err());
+        }
+        assert!(decode::ids(b"not a hash").is_err());
+    }
+}
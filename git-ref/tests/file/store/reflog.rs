@@ -65,3 +65,188 @@ mod iter_rev {
         Ok(())
     }
 }
+
+mod expire {
+    use git_lock::acquire::Fail;
+    use git_odb::pack::Find;
+    use git_ref::file::log::expire::{Options, Outcome};
+
+    use crate::file::Store;
+
+    fn store_writable() -> crate::Result<(git_testtools::tempfile::TempDir, Store, git_odb::Handle)> {
+        let (dir, store) = crate::file::store_writable("make_repo_for_reflog_expiry.sh")?;
+        let odb = git_odb::at(store.base().join("objects"))?;
+        Ok((dir, store, odb))
+    }
+
+    fn expire(store: &Store, odb: &git_odb::Handle, options: Options) -> crate::Result<Option<Outcome>> {
+        Ok(
+            store.reflog_expire("refs/heads/main", options, Fail::Immediately, |oid, buf| {
+                odb.try_find(oid, buf)
+                    .map(|obj| obj.map(|(obj, _)| (obj.kind, obj.data)))
+            })?,
+        )
+    }
+
+    fn log_times_and_messages(store: &Store) -> crate::Result<Vec<(u32, String)>> {
+        let mut buf = Vec::new();
+        Ok(store
+            .reflog_iter("refs/heads/main", &mut buf)?
+            .expect("log exists")
+            .map(|line| line.map(|line| (line.signature.time.time, line.message.to_string())))
+            .collect::<Result<_, _>>()?)
+    }
+
+    #[test]
+    fn nothing_happens_without_cutoffs_or_log() -> crate::Result {
+        let (_keep, store, odb) = store_writable()?;
+        let before = log_times_and_messages(&store)?;
+        assert_eq!(
+            expire(&store, &odb, Options::default())?,
+            Some(Outcome { kept: 5, removed: 0 })
+        );
+        assert_eq!(log_times_and_messages(&store)?, before);
+
+        assert_eq!(
+            store.reflog_expire(
+                "refs/heads/missing",
+                Options::default(),
+                Fail::Immediately,
+                git_ref::peel::none
+            )?,
+            None
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn entries_older_than_the_cutoff_are_removed_and_optionally_rewritten() -> crate::Result {
+        let (_keep, store, odb) = store_writable()?;
+        let options = Options {
+            expire: Some(1000000150),
+            dry_run: true,
+            ..Default::default()
+        };
+        assert_eq!(expire(&store, &odb, options)?, Some(Outcome { kept: 3, removed: 2 }));
+        assert_eq!(
+            log_times_and_messages(&store)?.len(),
+            5,
+            "dry-runs don't change anything"
+        );
+
+        let outcome = expire(
+            &store,
+            &odb,
+            Options {
+                dry_run: false,
+                rewrite: true,
+                ..options
+            },
+        )?;
+        assert_eq!(outcome, Some(Outcome { kept: 3, removed: 2 }));
+        assert_eq!(
+            log_times_and_messages(&store)?
+                .into_iter()
+                .map(|(time, _)| time)
+                .collect::<Vec<_>>(),
+            vec![1000000200, 1000000300, 1000000400]
+        );
+
+        let mut buf = Vec::new();
+        let lines: Vec<_> = store
+            .reflog_iter("refs/heads/main", &mut buf)?
+            .expect("log exists")
+            .map(|line| line.map(|line| (line.previous_oid(), line.new_oid())))
+            .collect::<Result<_, _>>()?;
+        assert!(lines[0].0.is_null(), "the first entry has no predecessor anymore");
+        assert_eq!(
+            lines[1].0, lines[0].1,
+            "each entry continues where the previous one left off"
+        );
+        assert_eq!(lines[2].0, lines[1].1);
+        Ok(())
+    }
+
+    #[test]
+    fn unreachable_entries_are_removed_if_older_than_their_cutoff() -> crate::Result {
+        let (_keep, store, odb) = store_writable()?;
+        let outcome = expire(
+            &store,
+            &odb,
+            Options {
+                expire_unreachable: Some(1000000250),
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(outcome, Some(Outcome { kept: 3, removed: 2 }));
+        assert_eq!(
+            log_times_and_messages(&store)?
+                .into_iter()
+                .map(|(_, message)| message)
+                .collect::<Vec<_>>(),
+            vec!["commit (initial): c1", "commit: c3", "commit: c4"],
+            "the commit that was reset away and the reset itself refer to a commit not reachable from main"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn entries_reachable_from_head_or_for_head_from_any_reference_are_kept() -> crate::Result {
+        let (_keep, store, odb) = store_writable()?;
+        let mut buf = Vec::new();
+        let c2 = store
+            .reflog_iter("refs/heads/main", &mut buf)?
+            .expect("log exists")
+            .nth(1)
+            .expect("second entry")?
+            .new_oid();
+        let options = Options {
+            expire_unreachable: Some(1000000250),
+            dry_run: true,
+            ..Default::default()
+        };
+
+        std::fs::write(store.base().join("HEAD"), format!("{}\n", c2))?;
+        assert_eq!(
+            expire(&store, &odb, options)?,
+            Some(Outcome { kept: 5, removed: 0 }),
+            "the commit that was reset away is reachable from the detached HEAD"
+        );
+
+        std::fs::write(store.base().join("refs/heads/other"), format!("{}\n", c2))?;
+        std::fs::write(store.base().join("HEAD"), "ref: refs/heads/main\n")?;
+        let outcome = store.reflog_expire("HEAD", options, Fail::Immediately, |oid, buf| {
+            odb.try_find(oid, buf)
+                .map(|obj| obj.map(|(obj, _)| (obj.kind, obj.data)))
+        })?;
+        assert_eq!(
+            outcome,
+            Some(Outcome { kept: 5, removed: 0 }),
+            "entries of the log of HEAD are reachable from any reference"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn stale_entries_are_removed_if_their_objects_are_missing() -> crate::Result {
+        let (_keep, store, odb) = store_writable()?;
+        let log_path = store.base().join("logs/refs/heads/main");
+        let mut log = std::fs::read(&log_path)?;
+        log.extend_from_slice(
+            b"0000000000000000000000000000000000000000 1111111111111111111111111111111111111111 committer <committer@example.com> 1000000500 +0000\tbroken\n",
+        );
+        std::fs::write(&log_path, log)?;
+
+        let outcome = expire(
+            &store,
+            &odb,
+            Options {
+                stale_fix: true,
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(outcome, Some(Outcome { kept: 5, removed: 1 }));
+        assert_eq!(log_times_and_messages(&store)?.len(), 5);
+        Ok(())
+    }
+}
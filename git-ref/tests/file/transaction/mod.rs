@@ -44,4 +44,9 @@ mod prepare_and_commit {
     mod create_or_update;
 
     mod delete;
+
+    #[cfg(unix)]
+    mod hook;
+
+    mod rename_or_copy;
 }
@@ -16,7 +16,7 @@ use git_ref::{
 use git_testtools::hex_to_id;
 
 use crate::file::{
-    store_with_packed_refs, store_writable,
+    store_at, store_with_packed_refs, store_writable,
     transaction::prepare_and_commit::{committer, empty_store, log_line, reflog_lines},
 };
 
@@ -772,3 +772,123 @@ fn packed_refs_creation_with_packed_refs_mode_leave_keeps_original_loose_refs()
     );
     Ok(())
 }
+
+#[test]
+fn initial_transactions_write_packed_refs_directly_with_the_same_outcome_as_loose_refs() -> crate::Result {
+    let source = store_at("make_ref_repository.sh")?;
+    let edits: Vec<_> = source
+        .loose_iter()?
+        .filter_map(|r| r.ok().filter(|r| r.kind() == git_ref::Kind::Peeled))
+        .map(|r| RefEdit {
+            change: Change::Update {
+                log: LogChange {
+                    message: "clone".into(),
+                    ..Default::default()
+                },
+                expected: PreviousValue::MustNotExist,
+                new: r.target,
+            },
+            name: r.name,
+            deref: false,
+        })
+        .collect();
+    let find = || -> crate::Result<Box<transaction::FindObjectFn>> {
+        let odb = git_odb::at(source.base().join("objects"))?;
+        Ok(Box::new(move |oid, buf| {
+            odb.try_find(oid, buf)
+                .map(|obj| obj.map(|obj| obj.kind))
+                .map_err(|err| Box::new(err) as Box<dyn std::error::Error + Send + Sync>)
+        }))
+    };
+
+    let (_keep, initial) = empty_store()?;
+    initial
+        .transaction()
+        .packed_refs(PackedRefs::DeletionsAndNonSymbolicUpdates(find()?))
+        .prepare(edits.clone(), Fail::Immediately)?
+        .commit(&committer())?;
+    let (_keep, loose) = empty_store()?;
+    loose
+        .transaction()
+        .prepare(edits.clone(), Fail::Immediately)?
+        .commit(&committer())?;
+
+    assert!(
+        !initial.base().join("refs").exists(),
+        "no loose references were written in the initial transaction"
+    );
+    let expected_pack_data: BString = std::fs::read(store_with_packed_refs()?.packed_refs_path())?.into();
+    let actual_packed_data: BString = std::fs::read(initial.packed_refs_path())?.into();
+    assert_eq!(
+        actual_packed_data, expected_pack_data,
+        "both gitoxide and git must agree on the packed refs file perfectly"
+    );
+    for edit in &edits {
+        assert_eq!(
+            initial.find(edit.name.to_partial())?.target,
+            loose.find(edit.name.to_partial())?.target
+        );
+        let name = edit.name.as_bstr().to_str()?;
+        match loose.reflog_iter(name, &mut Vec::new())? {
+            Some(_) => assert_eq!(reflog_lines(&initial, name)?, reflog_lines(&loose, name)?),
+            None => assert!(initial.reflog_iter(name, &mut Vec::new())?.is_none()),
+        }
+    }
+
+    initial
+        .transaction()
+        .packed_refs(PackedRefs::DeletionsAndNonSymbolicUpdates(find()?))
+        .prepare(
+            Some(RefEdit {
+                change: Change::Update {
+                    log: LogChange::default(),
+                    expected: PreviousValue::MustNotExist,
+                    new: initial.find("main")?.target,
+                },
+                name: "refs/heads/new".try_into()?,
+                deref: false,
+            }),
+            Fail::Immediately,
+        )?
+        .commit(&committer())?;
+    assert!(
+        initial.try_find_loose("refs/heads/new")?.is_some(),
+        "transactions on stores with references are never initial"
+    );
+
+    let conflicting_edits: Vec<_> = ["refs/heads/a/b", "refs/heads/a"]
+        .iter()
+        .map(|name| -> crate::Result<_> {
+            Ok(RefEdit {
+                change: Change::Update {
+                    log: LogChange::default(),
+                    expected: PreviousValue::MustNotExist,
+                    new: loose.find("main")?.target,
+                },
+                name: (*name).try_into()?,
+                deref: false,
+            })
+        })
+        .collect::<Result<_, _>>()?;
+    for packed_refs in [true, false].iter() {
+        let (_keep, store) = empty_store()?;
+        let transaction = store.transaction();
+        let transaction = if *packed_refs {
+            transaction.packed_refs(PackedRefs::DeletionsAndNonSymbolicUpdates(find()?))
+        } else {
+            transaction
+        };
+        match transaction.prepare(conflicting_edits.clone(), Fail::Immediately) {
+            Err(transaction::prepare::Error::NameConflict { full_name, existing }) => {
+                assert_eq!(full_name, "refs/heads/a/b");
+                assert_eq!(existing, "refs/heads/a");
+            }
+            _ => unreachable!("references can't be directories of other references"),
+        }
+        assert!(
+            !store.packed_refs_path().exists(),
+            "nothing is written if packed refs are used or not"
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,187 @@
+use std::{convert::TryInto, os::unix::fs::PermissionsExt, path::Path};
+
+use git_hash::ObjectId;
+use git_lock::acquire::Fail;
+use git_ref::{
+    file::transaction::{prepare, Hook},
+    transaction::{Change, LogChange, PreviousValue, RefEdit, RefLog},
+    Target,
+};
+
+use crate::file::{store_writable, transaction::prepare_and_commit::committer};
+
+/// Install a hook which appends its argument and input to `hook.log` in the working directory, and which rejects
+/// the transaction if the `reject` file exists.
+fn hook(dir: &Path) -> crate::Result<Hook> {
+    let path = dir.join("reference-transaction");
+    std::fs::write(
+        &path,
+        "#!/bin/sh\necho \"$1\" >> hook.log\ncat >> hook.log\ntest ! -f reject\n",
+    )?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+    Ok(Hook {
+        path,
+        working_dir: dir.to_owned(),
+    })
+}
+
+fn update(name: &str, new: ObjectId) -> crate::Result<RefEdit> {
+    Ok(RefEdit {
+        change: Change::Update {
+            log: LogChange::default(),
+            expected: PreviousValue::Any,
+            new: Target::Peeled(new),
+        },
+        name: name.try_into()?,
+        deref: true,
+    })
+}
+
+#[test]
+fn prepared_and_committed_phases_receive_all_changed_references() -> crate::Result {
+    let (dir, store) = store_writable("make_repo_for_reflog.sh")?;
+    let main = store.find_loose("main")?.target.into_id();
+    let new = git_hash::ObjectId::empty_tree(git_hash::Kind::Sha1);
+
+    store
+        .transaction()
+        .reference_transaction_hook(Some(hook(dir.path())?))
+        .prepare(
+            vec![
+                update("HEAD", new)?,
+                update("refs/heads/new", new)?,
+                RefEdit {
+                    change: Change::Update {
+                        log: LogChange::default(),
+                        expected: PreviousValue::MustNotExist,
+                        new: Target::Symbolic("refs/heads/new".try_into()?),
+                    },
+                    name: "refs/heads/symbolic".try_into()?,
+                    deref: false,
+                },
+            ],
+            Fail::Immediately,
+        )?
+        .commit(&committer())?;
+
+    let null = git_hash::Kind::Sha1.null();
+    let lines = format!(
+        "{null} {new} refs/heads/new\n{null} ref:refs/heads/new refs/heads/symbolic\n{null} {new} refs/heads/main\n",
+        new = new,
+        null = null
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("hook.log"))?,
+        format!("prepared\n{lines}committed\n{lines}", lines = lines),
+        "HEAD only changes its reflog and isn't passed to the hook, while its referent is split off and comes last, like in git"
+    );
+    assert_eq!(store.find_loose("main")?.target.into_id(), new);
+
+    std::fs::remove_file(dir.path().join("hook.log"))?;
+    store
+        .transaction()
+        .reference_transaction_hook(Some(hook(dir.path())?))
+        .prepare(
+            vec![
+                RefEdit {
+                    change: Change::Update {
+                        log: LogChange::default(),
+                        expected: PreviousValue::MustExistAndMatch(Target::Peeled(new)),
+                        new: Target::Peeled(main),
+                    },
+                    name: "refs/heads/main".try_into()?,
+                    deref: false,
+                },
+                RefEdit {
+                    change: Change::Delete {
+                        expected: PreviousValue::Any,
+                        log: RefLog::AndReference,
+                    },
+                    name: "refs/heads/new".try_into()?,
+                    deref: false,
+                },
+            ],
+            Fail::Immediately,
+        )?
+        .commit(&committer())?;
+    let lines = format!(
+        "{new} {main} refs/heads/main\n{null} {null} refs/heads/new\n",
+        main = main,
+        new = new,
+        null = null
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("hook.log"))?,
+        format!("prepared\n{lines}committed\n{lines}", lines = lines),
+        "the old value is the one expected by the caller, not the actual one"
+    );
+    Ok(())
+}
+
+#[test]
+fn rejection_in_prepared_phase_aborts_the_transaction() -> crate::Result {
+    let (dir, store) = store_writable("make_repo_for_reflog.sh")?;
+    let main = store.find_loose("main")?.target.into_id();
+    std::fs::write(dir.path().join("reject"), "")?;
+
+    let res = store
+        .transaction()
+        .reference_transaction_hook(Some(hook(dir.path())?))
+        .prepare(
+            Some(update("refs/heads/main", ObjectId::empty_tree(git_hash::Kind::Sha1))?),
+            Fail::Immediately,
+        );
+    match res {
+        Err(prepare::Error::HookRejected) => {}
+        _ => unreachable!("the hook rejects the transaction"),
+    }
+    assert_eq!(store.find_loose("main")?.target.into_id(), main, "nothing changed");
+    assert!(
+        !dir.path().join(".git/refs/heads/main.lock").exists(),
+        "locks were released"
+    );
+    let log = std::fs::read_to_string(dir.path().join("hook.log"))?;
+    let phases: Vec<_> = log.lines().filter(|line| !line.contains(' ')).collect();
+    assert_eq!(
+        phases,
+        vec!["prepared", "aborted"],
+        "the rejected transaction is aborted, like in git"
+    );
+    Ok(())
+}
+
+#[test]
+fn dropping_a_prepared_transaction_aborts_it() -> crate::Result {
+    let (dir, store) = store_writable("make_repo_for_reflog.sh")?;
+    let transaction = store
+        .transaction()
+        .reference_transaction_hook(Some(hook(dir.path())?))
+        .prepare(
+            Some(update("refs/heads/main", ObjectId::empty_tree(git_hash::Kind::Sha1))?),
+            Fail::Immediately,
+        )?;
+    drop(transaction);
+
+    let log = std::fs::read_to_string(dir.path().join("hook.log"))?;
+    let phases: Vec<_> = log.lines().filter(|line| !line.contains(' ')).collect();
+    assert_eq!(phases, vec!["prepared", "aborted"]);
+    Ok(())
+}
+
+#[test]
+fn missing_or_non_executable_hooks_are_ignored() -> crate::Result {
+    let (dir, store) = store_writable("make_repo_for_reflog.sh")?;
+    let hook = hook(dir.path())?;
+    std::fs::set_permissions(&hook.path, std::fs::Permissions::from_mode(0o644))?;
+    std::fs::write(dir.path().join("reject"), "")?;
+    store
+        .transaction()
+        .reference_transaction_hook(Some(hook))
+        .prepare(
+            Some(update("refs/heads/main", ObjectId::empty_tree(git_hash::Kind::Sha1))?),
+            Fail::Immediately,
+        )?
+        .commit(&committer())?;
+    assert!(!dir.path().join("hook.log").exists());
+    Ok(())
+}
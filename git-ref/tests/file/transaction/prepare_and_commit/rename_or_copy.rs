@@ -0,0 +1,222 @@
+use std::convert::TryInto;
+
+use git_lock::acquire::Fail;
+use git_ref::{
+    file::transaction,
+    transaction::{Change, LogChange, PreviousValue, RefEdit},
+    Target,
+};
+
+use crate::file::{
+    store_writable,
+    transaction::prepare_and_commit::{committer, log_line, reflog_lines},
+};
+
+fn edit(rename: bool, name: &str, new_name: &str) -> crate::Result<RefEdit> {
+    let (expected, new_name, log) = (
+        PreviousValue::Any,
+        new_name.try_into()?,
+        LogChange {
+            message: "moved".into(),
+            ..Default::default()
+        },
+    );
+    Ok(RefEdit {
+        change: if rename {
+            Change::Rename {
+                expected,
+                new_name,
+                log,
+            }
+        } else {
+            Change::Copy {
+                expected,
+                new_name,
+                log,
+            }
+        },
+        name: name.try_into()?,
+        deref: false,
+    })
+}
+
+#[test]
+fn rename_moves_the_reflog_and_updates_head() -> crate::Result {
+    let (_keep, store) = store_writable("make_repo_for_reflog.sh")?;
+    let main = store.find_loose("main")?;
+    let mut expected_log = reflog_lines(&store, "refs/heads/main")?;
+    assert_eq!(expected_log.len(), 5);
+
+    let edits = store
+        .transaction()
+        .prepare(
+            Some(edit(true, "refs/heads/main", "refs/heads/renamed")?),
+            Fail::Immediately,
+        )?
+        .commit(&committer())?;
+    assert_eq!(
+        edits.len(),
+        3,
+        "the deletion of main, the creation of renamed and an update of HEAD"
+    );
+    assert_eq!(
+        edits[0].change,
+        Change::Delete {
+            expected: PreviousValue::MustExistAndMatch(main.target.clone()),
+            log: git_ref::transaction::RefLog::AndReference,
+        }
+    );
+
+    assert!(store.try_find_loose("refs/heads/main")?.is_none());
+    assert!(
+        store.reflog_iter("refs/heads/main", &mut Vec::new())?.is_none(),
+        "the log was moved"
+    );
+    let renamed = store.find_loose("refs/heads/renamed")?;
+    assert_eq!(renamed.target, main.target);
+    let oid = main.target.as_id().expect("peeled").to_owned();
+    expected_log.push(log_line(oid, oid, "moved"));
+    assert_eq!(reflog_lines(&store, "refs/heads/renamed")?, expected_log);
+    assert_eq!(
+        store.find_loose("HEAD")?.target,
+        Target::Symbolic("refs/heads/renamed".try_into()?)
+    );
+    assert_eq!(
+        reflog_lines(&store, "HEAD")?.len(),
+        5,
+        "HEAD doesn't get a log entry for pointing to a new name"
+    );
+    Ok(())
+}
+
+#[test]
+fn copy_keeps_the_original_and_copies_its_reflog() -> crate::Result {
+    let (_keep, store) = store_writable("make_repo_for_reflog.sh")?;
+    let main = store.find_loose("main")?;
+    let original_log = reflog_lines(&store, "refs/heads/main")?;
+
+    let edits = store
+        .transaction()
+        .prepare(
+            Some(edit(false, "refs/heads/main", "refs/heads/copy")?),
+            Fail::Immediately,
+        )?
+        .commit(&committer())?;
+    assert_eq!(edits.len(), 2);
+
+    assert_eq!(store.find_loose("refs/heads/main")?.target, main.target);
+    assert_eq!(store.find_loose("refs/heads/copy")?.target, main.target);
+    assert_eq!(reflog_lines(&store, "refs/heads/main")?, original_log, "unchanged");
+    let oid = main.target.as_id().expect("peeled").to_owned();
+    let mut expected_log = original_log;
+    expected_log.push(log_line(oid, oid, "moved"));
+    assert_eq!(reflog_lines(&store, "refs/heads/copy")?, expected_log);
+    assert_eq!(
+        store.find_loose("HEAD")?.target,
+        Target::Symbolic("refs/heads/main".try_into()?),
+        "HEAD is only changed by renames"
+    );
+    Ok(())
+}
+
+#[test]
+fn rename_packed_reference() -> crate::Result {
+    let (_keep, store) = store_writable("make_packed_ref_repository.sh")?;
+    let d1 = store.find("refs/heads/d1")?;
+    assert!(store.try_find_loose("refs/heads/d1")?.is_none(), "only packed");
+
+    store
+        .transaction()
+        .prepare(Some(edit(true, "refs/heads/d1", "refs/heads/d2")?), Fail::Immediately)?
+        .commit(&committer())?;
+
+    assert!(store.try_find("refs/heads/d1")?.is_none(), "removed from packed-refs");
+    assert_eq!(store.find_loose("refs/heads/d2")?.target, d1.target);
+    Ok(())
+}
+
+#[test]
+fn directory_and_file_conflicts_are_detected() -> crate::Result {
+    let (_keep, store) = store_writable("make_packed_ref_repository.sh")?;
+    for (name, new_name, existing, renames) in &[
+        (
+            "refs/heads/d1",
+            "refs/heads/main/d1",
+            "refs/heads/main",
+            &[true, false][..],
+        ),
+        (
+            "refs/heads/d1",
+            "refs/remotes/origin",
+            "refs/remotes/origin/HEAD",
+            &[true, false],
+        ),
+        ("refs/heads/main", "refs/heads/main/sub", "refs/heads/main", &[false]),
+    ] {
+        for rename in *renames {
+            match store
+                .transaction()
+                .prepare(Some(edit(*rename, name, new_name)?), Fail::Immediately)
+            {
+                Err(transaction::prepare::Error::NameConflict {
+                    full_name,
+                    existing: actual,
+                }) => {
+                    assert_eq!(full_name, *new_name);
+                    assert_eq!(actual, *existing);
+                }
+                _ => unreachable!("the new name conflicts with an existing packed reference"),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn rename_into_or_out_of_its_own_directory() -> crate::Result {
+    let (_keep, store) = store_writable("make_repo_for_reflog.sh")?;
+    let main = store.find_loose("main")?;
+    let mut expected_log = reflog_lines(&store, "refs/heads/main")?;
+    let oid = main.target.as_id().expect("peeled").to_owned();
+
+    for (name, new_name) in &[
+        ("refs/heads/main", "refs/heads/main/nested"),
+        ("refs/heads/main/nested", "refs/heads/main"),
+    ] {
+        store
+            .transaction()
+            .prepare(Some(edit(true, name, new_name)?), Fail::Immediately)?
+            .commit(&committer())?;
+
+        assert!(!store.base().join(name).is_file(), "the original was removed");
+        assert_eq!(
+            store.find_loose(*new_name)?.target,
+            main.target,
+            "the reference doesn't conflict with itself"
+        );
+        expected_log.push(log_line(oid, oid, "moved"));
+        assert_eq!(reflog_lines(&store, new_name)?, expected_log, "the log moves along");
+        assert_eq!(
+            store.find_loose("HEAD")?.target,
+            Target::Symbolic((*new_name).try_into()?)
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn rename_of_missing_reference_fails() -> crate::Result {
+    let (_keep, store) = store_writable("make_repo_for_reflog.sh")?;
+    let res = store.transaction().prepare(
+        Some(edit(true, "refs/heads/missing", "refs/heads/new")?),
+        Fail::Immediately,
+    );
+    match res {
+        Ok(_) => unreachable!("the reference doesn't exist"),
+        Err(err) => assert_eq!(
+            err.to_string(),
+            "Edit preprocessing failed with error: The reference 'refs/heads/missing' to rename or copy does not exist"
+        ),
+    }
+    Ok(())
+}
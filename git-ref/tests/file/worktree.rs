@@ -0,0 +1,187 @@
+use std::{convert::TryInto, path::PathBuf};
+
+use git_ref::{
+    transaction::{Change, LogChange, PreviousValue, RefEdit},
+    Target,
+};
+
+use crate::file::Store;
+
+fn main_and_linked_store(git_dir: PathBuf) -> (Store, Store) {
+    let main = Store::at(&git_dir, git_ref::store::WriteReflog::Normal, git_hash::Kind::Sha1);
+    let linked = Store::for_linked_worktree(
+        git_dir.join("worktrees").join("wt"),
+        git_dir,
+        git_ref::store::WriteReflog::Normal,
+        git_hash::Kind::Sha1,
+    );
+    (main, linked)
+}
+
+fn names(store: &Store) -> crate::Result<Vec<String>> {
+    Ok(store
+        .iter()?
+        .all()?
+        .map(|r| r.map(|r| r.name.as_bstr().to_string()))
+        .collect::<Result<_, _>>()?)
+}
+
+#[test]
+fn per_worktree_refs_are_private_and_all_others_are_shared() -> crate::Result {
+    let dir = git_testtools::scripted_fixture_repo_read_only("make_worktree_repository.sh")?;
+    let (main, linked) = main_and_linked_store(dir.join(".git"));
+
+    assert_eq!(
+        main.find_loose("HEAD")?.target.as_name(),
+        Some("refs/heads/main".into())
+    );
+    assert_eq!(
+        linked.find_loose("HEAD")?.target.as_name(),
+        Some("refs/heads/other".into()),
+        "HEAD is read from the private git dir"
+    );
+    assert_eq!(linked.common_dir(), Some(main.base()));
+    assert_eq!(linked.packed_refs_path(), main.packed_refs_path());
+
+    for shared in ["refs/heads/main", "refs/heads/loose"] {
+        assert_eq!(linked.find(shared)?, main.find(shared)?);
+    }
+    assert!(
+        linked.try_find("refs/bisect/bad")?.is_none(),
+        "bisect refs are per worktree"
+    );
+    assert!(main.try_find("refs/bisect/good")?.is_none());
+    assert!(linked.try_find("refs/worktree/private")?.is_some());
+    assert!(linked.reflog_exists("HEAD")?);
+    assert!(
+        linked.reflog_exists("refs/heads/main")?,
+        "reflogs of shared refs are shared"
+    );
+
+    assert_eq!(
+        names(&linked)?,
+        [
+            "refs/bisect/good",
+            "refs/heads/loose",
+            "refs/heads/main",
+            "refs/heads/other",
+            "refs/worktree/private"
+        ],
+        "loose refs are merged from both directories in order, along with packed ones"
+    );
+    assert_eq!(
+        names(&main)?,
+        [
+            "refs/bisect/bad",
+            "refs/heads/loose",
+            "refs/heads/main",
+            "refs/heads/other"
+        ]
+    );
+    assert_eq!(
+        linked
+            .loose_iter_prefixed("refs/bisect")?
+            .map(|r| r.map(|r| r.name.as_bstr().to_string()))
+            .collect::<Result<Vec<_>, _>>()?,
+        ["refs/bisect/good"]
+    );
+    Ok(())
+}
+
+#[test]
+fn edits_are_written_to_the_directory_the_ref_belongs_to() -> crate::Result {
+    let dir = git_testtools::scripted_fixture_repo_writable("make_worktree_repository.sh")?;
+    let git_dir = dir.path().join(".git");
+    let (main, linked) = main_and_linked_store(git_dir.clone());
+    let id = main.find("refs/heads/main")?.target.into_id();
+
+    let edits = ["HEAD", "refs/worktree/new", "refs/heads/new"]
+        .iter()
+        .map(|name| RefEdit {
+            change: Change::Update {
+                log: LogChange::default(),
+                expected: PreviousValue::Any,
+                new: Target::Peeled(id),
+            },
+            name: (*name).try_into().expect("valid"),
+            deref: false,
+        });
+    linked
+        .transaction()
+        .prepare(edits, git_lock::acquire::Fail::Immediately)?
+        .commit(&git_actor::Signature {
+            name: "committer".into(),
+            email: "committer@example.com".into(),
+            time: git_actor::Time {
+                time: 1234,
+                offset: 0,
+                sign: git_actor::Sign::Plus,
+            },
+        })?;
+
+    let private_dir = git_dir.join("worktrees").join("wt");
+    for path in ["HEAD", "refs/worktree/new", "logs/HEAD"] {
+        assert!(private_dir.join(path).is_file(), "{}", path);
+    }
+    assert!(git_dir.join("refs/heads/new").is_file());
+    assert!(git_dir.join("logs/refs/heads/new").is_file());
+    assert_eq!(main.find("refs/heads/new")?.target.into_id(), id);
+    assert_eq!(
+        main.find_loose("HEAD")?.target.as_name(),
+        Some("refs/heads/main".into()),
+        "the main worktree is unaffected"
+    );
+    Ok(())
+}
+
+#[test]
+fn renaming_references_checked_out_in_other_worktrees_fails() -> crate::Result {
+    let dir = git_testtools::scripted_fixture_repo_writable("make_worktree_repository.sh")?;
+    let git_dir = dir.path().join(".git");
+    let (main, linked) = main_and_linked_store(git_dir.clone());
+    let rename = |name: &str| RefEdit {
+        change: Change::Rename {
+            log: LogChange::default(),
+            expected: PreviousValue::Any,
+            new_name: "refs/heads/renamed".try_into().expect("valid"),
+        },
+        name: name.try_into().expect("valid"),
+        deref: false,
+    };
+
+    for (store, name, other_git_dir) in &[
+        (&main, "refs/heads/other", git_dir.join("worktrees").join("wt")),
+        (&linked, "refs/heads/main", git_dir.clone()),
+    ] {
+        match store
+            .transaction()
+            .prepare(Some(rename(name)), git_lock::acquire::Fail::Immediately)
+        {
+            Err(git_ref::file::transaction::prepare::Error::RenamedReferenceCheckedOut { full_name, git_dir }) => {
+                assert_eq!(full_name, *name);
+                assert_eq!(&git_dir, other_git_dir);
+            }
+            _ => unreachable!("the HEAD of the other worktree would point to a missing reference"),
+        }
+        assert!(store.try_find(*name)?.is_some(), "nothing changed");
+    }
+
+    linked
+        .transaction()
+        .prepare(Some(rename("refs/heads/other")), git_lock::acquire::Fail::Immediately)?
+        .commit(&git_actor::Signature {
+            name: "committer".into(),
+            email: "committer@example.com".into(),
+            time: git_actor::Time {
+                time: 1234,
+                offset: 0,
+                sign: git_actor::Sign::Plus,
+            },
+        })?;
+    assert_eq!(
+        linked.find_loose("HEAD")?.target.as_name(),
+        Some("refs/heads/renamed".into()),
+        "the HEAD of the worktree doing the rename is updated"
+    );
+    Ok(())
+}
@@ -0,0 +1,20 @@
+#!/bin/bash
+set -eu -o pipefail
+
+git init -q --ref-format=reftable
+git config commit.gpgsign false
+
+git checkout -q -b main
+git commit -q --allow-empty -m c1
+git branch b1
+git tag t1
+git tag -m "tag object" dt1
+git symbolic-ref refs/heads/sym refs/heads/main
+
+for i in $(seq 200); do
+  git branch "many-$i"
+done
+git pack-refs --all
+
+git commit -q --allow-empty -m c2
+git branch -D b1
@@ -0,0 +1,29 @@
+#!/bin/bash
+set -eu -o pipefail
+
+git init -q
+git config commit.gpgsign false
+
+function at() {
+  local time=$1; shift
+  GIT_AUTHOR_DATE="$time +0000" GIT_COMMITTER_DATE="$time +0000" "$@"
+}
+
+git checkout -q -b main
+at 1000000000 git commit -q --allow-empty -m c1
+git branch feature/old
+git tag v1.2
+at 1000000200 git commit -q --allow-empty -m c2
+git branch feature/sub/deep
+at 1000000300 git tag -m "v1.9" v1.9
+at 1000000400 git commit -q --allow-empty -m c3
+at 1000000500 git tag -m "v1.10" v1.10
+
+git checkout -q -b topic HEAD~1
+at 1000000100 git commit -q --allow-empty -m "topic with old date"
+git checkout -q main
+
+git tag tree-tag "HEAD^{tree}"
+git pack-refs --all
+git symbolic-ref refs/heads/alias refs/heads/topic
+git symbolic-ref refs/heads/dangling refs/heads/missing
@@ -0,0 +1,17 @@
+#!/bin/bash
+set -eu -o pipefail
+
+git init -q
+git config commit.gpgsign false
+
+function at() {
+  local time=$1; shift
+  GIT_AUTHOR_DATE="$time +0000" GIT_COMMITTER_DATE="$time +0000" "$@"
+}
+
+git checkout -q -b main
+at 1000000000 git commit -q --allow-empty -m c1
+at 1000000100 git commit -q --allow-empty -m c2
+at 1000000200 git reset -q --hard HEAD~1
+at 1000000300 git commit -q --allow-empty -m c3
+at 1000000400 git commit -q --allow-empty -m c4
@@ -5,5 +5,6 @@ mod fullname;
 mod namespace;
 mod packed;
 mod reference;
+mod reftable;
 mod store;
 mod transaction;
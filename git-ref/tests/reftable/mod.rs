@@ -0,0 +1,683 @@
+use std::convert::TryInto;
+
+use git_actor::{Sign, Time};
+use git_hash::ObjectId;
+use git_lock::acquire::Fail;
+use git_object::bstr::ByteSlice;
+use git_ref::{
+    reftable::{self, LogRecord, RefRecord, RefValue, Stack, Table},
+    store::WriteReflog,
+    transaction::{Change, LogChange, PreviousValue, RefEdit, RefLog},
+    Target,
+};
+use git_testtools::hex_to_id;
+
+fn committer() -> git_actor::Signature {
+    git_actor::Signature {
+        name: "committer".into(),
+        email: "committer@example.com".into(),
+        time: Time {
+            time: 1234,
+            offset: -1800,
+            sign: Sign::Minus,
+        },
+    }
+}
+
+fn id(n: usize) -> ObjectId {
+    let mut bytes = [0u8; 20];
+    bytes[..8].copy_from_slice(&(n as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15).to_be_bytes());
+    bytes[19] = n as u8;
+    ObjectId::from(bytes)
+}
+
+fn empty_stack() -> crate::Result<(tempfile::TempDir, Stack)> {
+    let dir = tempfile::TempDir::new()?;
+    std::fs::create_dir(dir.path().join("reftable"))?;
+    let stack = Stack::at(dir.path().join("reftable"), WriteReflog::Normal, git_hash::Kind::Sha1)?;
+    Ok((dir, stack))
+}
+
+fn update(name: &str, new: Target, expected: PreviousValue) -> crate::Result<RefEdit> {
+    Ok(RefEdit {
+        change: Change::Update {
+            log: LogChange {
+                message: format!("set {}", name).into(),
+                ..Default::default()
+            },
+            expected,
+            new,
+        },
+        name: name.try_into()?,
+        deref: false,
+    })
+}
+
+#[test]
+fn tables_round_trip_across_many_blocks_with_indices() -> crate::Result {
+    let count = 2000;
+    let mut refs: Vec<_> = (0..count)
+        .map(|n| -> crate::Result<_> {
+            Ok(RefRecord {
+                name: format!("refs/heads/branch-{:05}", n).as_str().try_into()?,
+                update_index: 5 + (n % 3) as u64,
+                value: match n % 4 {
+                    0 => RefValue::Deletion,
+                    1 => RefValue::Peeled(id(n)),
+                    2 => RefValue::PeeledTag {
+                        target: id(n),
+                        peeled: id(n + 1),
+                    },
+                    _ => RefValue::Symbolic("refs/heads/main".try_into()?),
+                },
+            })
+        })
+        .collect::<Result<_, _>>()?;
+    refs.reverse();
+    let logs: Vec<_> = (0..count / 10)
+        .flat_map(|n| (5..8).map(move |update_index| (n, update_index)))
+        .map(|(n, update_index)| -> crate::Result<_> {
+            Ok(LogRecord {
+                name: format!("refs/heads/branch-{:05}", n).as_str().try_into()?,
+                update_index,
+                line: (update_index != 6).then(|| git_ref::log::Line {
+                    previous_oid: id(n),
+                    new_oid: id(n + 1),
+                    signature: committer(),
+                    message: format!("log {}", update_index).into(),
+                }),
+            })
+        })
+        .collect::<Result<_, _>>()?;
+
+    let options = reftable::write::Options {
+        block_size: 256,
+        ..Default::default()
+    };
+    let data = reftable::write::table(&refs, &logs, 5, 7, options)?;
+    assert_eq!(&data[..4], b"REFT", "the header starts with the magic");
+    let table = Table::from_bytes(data, git_hash::Kind::Sha1)?;
+    assert_eq!(table.min_update_index(), 5);
+    assert_eq!(table.max_update_index(), 7);
+
+    refs.sort();
+    assert_eq!(
+        table.refs()?.collect::<Result<Vec<_>, _>>()?,
+        refs,
+        "all references are ordered by name"
+    );
+    for r in refs.iter().step_by(7) {
+        assert_eq!(table.find(r.name.as_bstr())?.as_ref(), Some(r));
+    }
+    assert_eq!(table.find("refs/heads/branch".into())?, None);
+    assert_eq!(table.find("refs/heads/zzz".into())?, None);
+    assert_eq!(
+        table
+            .refs_prefixed("refs/heads/branch-0120".into())?
+            .map(|r| r.map(|r| r.name.as_bstr().to_owned()))
+            .collect::<Result<Vec<_>, _>>()?,
+        (1200..1210)
+            .map(|n| format!("refs/heads/branch-{:05}", n))
+            .collect::<Vec<_>>()
+    );
+
+    let pointing_to = |n: usize| -> crate::Result<Vec<_>> {
+        Ok(table
+            .refs_pointing_to(&id(n))?
+            .into_iter()
+            .map(|r| r.name.as_bstr().to_owned())
+            .collect())
+    };
+    assert_eq!(pointing_to(1001)?, vec!["refs/heads/branch-01001"]);
+    assert_eq!(
+        pointing_to(1003)?,
+        vec!["refs/heads/branch-01002"],
+        "peeled values of tags are indexed as well"
+    );
+    assert!(table
+        .refs_pointing_to(&hex_to_id("ffffffffffffffffffffffffffffffffffffffff"))?
+        .is_empty());
+
+    let branch_logs = table
+        .logs_for("refs/heads/branch-00150".into())?
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(
+        branch_logs.iter().map(|log| log.update_index).collect::<Vec<_>>(),
+        vec![7, 6, 5],
+        "logs are ordered from newest to oldest"
+    );
+    assert_eq!(branch_logs[1].line, None, "deletions are retained");
+    assert_eq!(
+        branch_logs[0]
+            .line
+            .as_ref()
+            .map(|l| (l.message.as_bstr(), &l.signature)),
+        Some(("log 7".into(), &committer())),
+        "negative time zones survive the round-trip"
+    );
+    assert_eq!(table.logs()?.count(), logs.len());
+    Ok(())
+}
+
+#[test]
+fn corrupt_tables_are_rejected() -> crate::Result {
+    let mut data = reftable::write::table(&[], &[], 1, 1, Default::default())?;
+    assert!(
+        Table::from_bytes(data.clone(), git_hash::Kind::Sha1)?
+            .refs()?
+            .next()
+            .is_none(),
+        "empty tables are valid"
+    );
+    let last = data.len() - 1;
+    data[last] ^= 1;
+    assert!(matches!(
+        Table::from_bytes(data, git_hash::Kind::Sha1),
+        Err(reftable::table::Error::Corrupt(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn transactions_add_tables_with_reflogs_and_newer_tables_win() -> crate::Result {
+    let (_keep, mut stack) = empty_stack()?;
+    let main = hex_to_id("134385f6d781b7e97062102c6a483440bfda2a03");
+    let edits = stack
+        .transaction()
+        .prepare(
+            vec![
+                update(
+                    "HEAD",
+                    Target::Symbolic("refs/heads/main".try_into()?),
+                    PreviousValue::Any,
+                )?,
+                update("refs/heads/main", Target::Peeled(main), PreviousValue::MustNotExist)?,
+                update("refs/tags/v1", Target::Peeled(main), PreviousValue::Any)?,
+            ],
+            Fail::Immediately,
+        )?
+        .commit(&committer())?;
+    assert_eq!(edits.len(), 3);
+    assert_eq!(stack.tables().len(), 1);
+
+    let next = hex_to_id("2222222222222222222222222222222222222222");
+    let mut head = update(
+        "HEAD",
+        Target::Peeled(next),
+        PreviousValue::MustExistAndMatch(Target::Peeled(main)),
+    )?;
+    head.deref = true;
+    let edits = stack
+        .transaction()
+        .prepare(Some(head), Fail::Immediately)?
+        .commit(&committer())?;
+    assert_eq!(edits.len(), 2, "the symbolic ref was split");
+
+    let found = stack.find("refs/heads/main".into())?.expect("present");
+    assert_eq!(found.value, RefValue::Peeled(next));
+    assert_eq!(found.update_index, 2);
+    assert_eq!(
+        stack.find("HEAD".into())?.map(|r| r.value),
+        Some(RefValue::Symbolic("refs/heads/main".try_into()?))
+    );
+
+    let lines: Vec<_> = stack
+        .logs_for("refs/heads/main".into())?
+        .into_iter()
+        .map(|log| log.line.expect("no deletion"))
+        .map(|line| (line.previous_oid, line.new_oid))
+        .collect();
+    assert_eq!(lines, vec![(main, next), (git_hash::Kind::Sha1.null(), main)]);
+    assert_eq!(
+        stack.logs_for("HEAD".into())?.len(),
+        1,
+        "HEAD got a log entry through the split, but not for becoming symbolic"
+    );
+    assert!(
+        stack.logs_for("refs/tags/v1".into())?.is_empty(),
+        "tags don't get reflogs by default"
+    );
+
+    let res = stack.transaction().prepare(
+        Some(update(
+            "refs/heads/main",
+            Target::Peeled(main),
+            PreviousValue::MustExistAndMatch(Target::Peeled(main)),
+        )?),
+        Fail::Immediately,
+    );
+    match res {
+        Err(reftable::transaction::Error::ReferenceOutOfDate { full_name, .. }) => {
+            assert_eq!(full_name, "refs/heads/main")
+        }
+        _ => unreachable!("the expected value doesn't match"),
+    }
+    Ok(())
+}
+
+#[test]
+fn deletions_hide_references_and_logs_until_compaction_drops_them() -> crate::Result {
+    let (dir, mut stack) = empty_stack()?;
+    stack.auto_compact = false;
+    for n in 0..4 {
+        stack
+            .transaction()
+            .prepare(
+                Some(update(
+                    &format!("refs/heads/b{}", n),
+                    Target::Peeled(id(n)),
+                    PreviousValue::MustNotExist,
+                )?),
+                Fail::Immediately,
+            )?
+            .commit(&committer())?;
+    }
+    stack
+        .transaction()
+        .prepare(
+            Some(RefEdit {
+                change: Change::Delete {
+                    expected: PreviousValue::MustExistAndMatch(Target::Peeled(id(1))),
+                    log: RefLog::AndReference,
+                },
+                name: "refs/heads/b1".try_into()?,
+                deref: false,
+            }),
+            Fail::Immediately,
+        )?
+        .commit(&committer())?;
+    assert_eq!(stack.tables().len(), 5);
+    let last = stack.tables().last().expect("five tables");
+    assert_eq!(
+        (last.min_update_index(), last.max_update_index()),
+        (5, 5),
+        "deleted log entries keep their update index without widening the range of the table"
+    );
+    assert_eq!(stack.find("refs/heads/b1".into())?, None);
+    assert!(
+        stack.logs_for("refs/heads/b1".into())?.is_empty(),
+        "the reflog was deleted as well"
+    );
+
+    let names = |stack: &Stack| -> crate::Result<Vec<_>> {
+        Ok(stack
+            .refs()?
+            .map(|r| r.map(|r| r.name.as_bstr().to_owned()))
+            .collect::<Result<Vec<_>, _>>()?)
+    };
+    let expected = vec!["refs/heads/b0", "refs/heads/b2", "refs/heads/b3"];
+    assert_eq!(names(&stack)?, expected);
+
+    stack.compact(Fail::Immediately)?;
+    assert_eq!(stack.tables().len(), 1);
+    assert_eq!(names(&stack)?, expected);
+    assert_eq!(stack.next_update_index(), 6);
+    let table = stack.tables().next().expect("one table");
+    assert_eq!(
+        table.refs()?.count(),
+        3,
+        "deletions are dropped when compacting the whole stack"
+    );
+    assert_eq!(table.logs()?.count(), 3);
+    assert_eq!(
+        std::fs::read_dir(dir.path().join("reftable"))?.count(),
+        2,
+        "only the compacted table and tables.list remain"
+    );
+
+    let reloaded = Stack::at(dir.path().join("reftable"), WriteReflog::Normal, git_hash::Kind::Sha1)?;
+    assert_eq!(names(&reloaded)?, expected);
+    Ok(())
+}
+
+#[test]
+fn renames_move_references_with_their_logs_and_update_head() -> crate::Result {
+    let (_keep, mut stack) = empty_stack()?;
+    stack
+        .transaction()
+        .prepare(
+            vec![
+                update(
+                    "HEAD",
+                    Target::Symbolic("refs/heads/main".try_into()?),
+                    PreviousValue::Any,
+                )?,
+                update("refs/heads/main", Target::Peeled(id(1)), PreviousValue::MustNotExist)?,
+                update("refs/heads/other", Target::Peeled(id(2)), PreviousValue::MustNotExist)?,
+            ],
+            Fail::Immediately,
+        )?
+        .commit(&committer())?;
+
+    let rename_or_copy = |rename: bool, name: &str, new_name: &str| -> crate::Result<RefEdit> {
+        let (expected, new_name, log) = (
+            PreviousValue::MustExist,
+            new_name.try_into()?,
+            LogChange {
+                message: "moved".into(),
+                ..Default::default()
+            },
+        );
+        Ok(RefEdit {
+            change: if rename {
+                Change::Rename {
+                    expected,
+                    new_name,
+                    log,
+                }
+            } else {
+                Change::Copy {
+                    expected,
+                    new_name,
+                    log,
+                }
+            },
+            name: name.try_into()?,
+            deref: false,
+        })
+    };
+    let edits = stack
+        .transaction()
+        .prepare(
+            vec![
+                rename_or_copy(true, "refs/heads/main", "refs/heads/renamed")?,
+                rename_or_copy(false, "refs/heads/other", "refs/heads/copied")?,
+            ],
+            Fail::Immediately,
+        )?
+        .commit(&committer())?;
+    assert_eq!(edits.len(), 5, "the renamed and copied references and HEAD were added");
+
+    assert_eq!(stack.find("refs/heads/main".into())?, None);
+    assert_eq!(
+        stack.find("HEAD".into())?.map(|r| r.value),
+        Some(RefValue::Symbolic("refs/heads/renamed".try_into()?))
+    );
+    for (name, expected) in &[
+        ("refs/heads/renamed", id(1)),
+        ("refs/heads/other", id(2)),
+        ("refs/heads/copied", id(2)),
+    ] {
+        assert_eq!(
+            stack.find(name.as_bytes().as_bstr())?.map(|r| r.value),
+            Some(RefValue::Peeled(*expected))
+        );
+    }
+    let lines = |name: &str| -> crate::Result<Vec<_>> {
+        Ok(stack
+            .logs_for(name.into())?
+            .into_iter()
+            .map(|log| log.line.expect("no deletion"))
+            .map(|line| (line.previous_oid, line.new_oid, line.message))
+            .collect())
+    };
+    let null = git_hash::Kind::Sha1.null();
+    assert_eq!(
+        lines("refs/heads/renamed")?,
+        vec![
+            (id(1), id(1), "moved".into()),
+            (null, id(1), "set refs/heads/main".into())
+        ]
+    );
+    assert_eq!(lines("refs/heads/main")?, vec![], "the log was moved");
+    assert_eq!(
+        lines("refs/heads/copied")?,
+        vec![
+            (id(2), id(2), "moved".into()),
+            (null, id(2), "set refs/heads/other".into())
+        ]
+    );
+    assert_eq!(lines("refs/heads/other")?.len(), 1, "the original log is unchanged");
+
+    let res = stack.transaction().prepare(
+        Some(rename_or_copy(true, "refs/heads/renamed", "refs/heads/copied/nested")?),
+        Fail::Immediately,
+    );
+    match res {
+        Err(reftable::transaction::Error::NameConflict { full_name, existing }) => {
+            assert_eq!(full_name, "refs/heads/copied/nested");
+            assert_eq!(existing, "refs/heads/copied");
+        }
+        _ => unreachable!("the new name conflicts with an existing reference"),
+    }
+
+    let mut reflog_len = 2;
+    for (name, new_name) in &[
+        ("refs/heads/renamed", "refs/heads/renamed/nested"),
+        ("refs/heads/renamed/nested", "refs/heads/renamed"),
+    ] {
+        stack
+            .transaction()
+            .prepare(Some(rename_or_copy(true, name, new_name)?), Fail::Immediately)?
+            .commit(&committer())?;
+        assert_eq!(stack.find(name.as_bytes().as_bstr())?, None);
+        assert_eq!(
+            stack.find(new_name.as_bytes().as_bstr())?.map(|r| r.value),
+            Some(RefValue::Peeled(id(1))),
+            "the renamed reference doesn't conflict with itself"
+        );
+        reflog_len += 1;
+        assert_eq!(
+            stack.logs_for(new_name.as_bytes().as_bstr())?.len(),
+            reflog_len,
+            "the log moves along"
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn auto_compaction_keeps_the_amount_of_tables_logarithmic() -> crate::Result {
+    let (_keep, mut stack) = empty_stack()?;
+    for n in 0..64 {
+        stack
+            .transaction()
+            .prepare(
+                Some(update(
+                    &format!("refs/heads/b{}", n),
+                    Target::Peeled(id(n)),
+                    PreviousValue::Any,
+                )?),
+                Fail::Immediately,
+            )?
+            .commit(&committer())?;
+    }
+    assert!(stack.tables().len() <= 7, "{} tables", stack.tables().len());
+    assert_eq!(stack.refs()?.count(), 64);
+    Ok(())
+}
+
+#[test]
+fn the_general_store_finds_references_in_reftables_by_partial_name() -> crate::Result {
+    let (dir, mut stack) = empty_stack()?;
+    let main = hex_to_id("134385f6d781b7e97062102c6a483440bfda2a03");
+    stack
+        .transaction()
+        .prepare(
+            vec![
+                update(
+                    "HEAD",
+                    Target::Symbolic("refs/heads/main".try_into()?),
+                    PreviousValue::Any,
+                )?,
+                update("refs/heads/main", Target::Peeled(main), PreviousValue::Any)?,
+                update("refs/remotes/origin/HEAD", Target::Peeled(main), PreviousValue::Any)?,
+                update(
+                    "refs/namespaces/foo/refs/heads/main",
+                    Target::Peeled(main),
+                    PreviousValue::Any,
+                )?,
+            ],
+            Fail::Immediately,
+        )?
+        .commit(&committer())?;
+
+    let store = git_ref::Store::at_with_format(
+        dir.path(),
+        git_ref::store::Format::Reftable,
+        WriteReflog::Normal,
+        git_hash::Kind::Sha1,
+    )?;
+    let handle = store.to_handle();
+    assert_eq!(
+        handle.find("HEAD")?.target,
+        Target::Symbolic("refs/heads/main".try_into()?)
+    );
+    assert_eq!(handle.find("main")?.name.as_bstr(), "refs/heads/main");
+    assert_eq!(handle.find("origin")?.name.as_bstr(), "refs/remotes/origin/HEAD");
+    assert!(handle.try_find("missing")?.is_none());
+
+    stack
+        .transaction()
+        .prepare(
+            Some(update("refs/tags/v1", Target::Peeled(main), PreviousValue::Any)?),
+            Fail::Immediately,
+        )?
+        .commit(&committer())?;
+    assert_eq!(
+        handle.find("v1")?.name.as_bstr(),
+        "refs/tags/v1",
+        "changes after the store was opened are visible"
+    );
+
+    let namespaced = store.to_handle_namespaced(Some(git_ref::namespace::expand("foo")?));
+    assert_eq!(namespaced.find("main")?.name.as_bstr(), "refs/heads/main");
+    Ok(())
+}
+
+#[test]
+fn the_general_store_iterates_and_changes_references_in_reftables_within_namespaces() -> crate::Result {
+    let (dir, _stack) = empty_stack()?;
+    let store = git_ref::Store::at_with_format(
+        dir.path(),
+        git_ref::store::Format::Reftable,
+        WriteReflog::Normal,
+        git_hash::Kind::Sha1,
+    )?;
+    let mut handle = store.to_handle();
+    let mut namespaced = store.to_handle_namespaced(Some(git_ref::namespace::expand("foo")?));
+
+    let edits = namespaced
+        .transaction()
+        .prepare(
+            vec![
+                update("refs/heads/main", Target::Peeled(id(1)), PreviousValue::MustNotExist)?,
+                update("refs/tags/v1", Target::Peeled(id(2)), PreviousValue::MustNotExist)?,
+            ],
+            Fail::Immediately,
+        )?
+        .commit(&committer())?;
+    assert_eq!(
+        edits.iter().map(|e| e.name.as_bstr().to_owned()).collect::<Vec<_>>(),
+        vec!["refs/heads/main", "refs/tags/v1"],
+        "the namespace isn't visible in the performed edits"
+    );
+    handle
+        .transaction()
+        .prepare(
+            Some(update("refs/heads/other", Target::Peeled(id(3)), PreviousValue::Any)?),
+            Fail::Immediately,
+        )?
+        .commit(&committer())?;
+
+    let names = |iter: git_ref::store::iter::Iter<'_>| -> crate::Result<Vec<String>> {
+        Ok(iter
+            .map(|r| r.map(|r| r.name.as_bstr().to_string()))
+            .collect::<Result<_, _>>()?)
+    };
+    assert_eq!(
+        names(handle.iter()?.all()?)?,
+        vec![
+            "refs/heads/other",
+            "refs/namespaces/foo/refs/heads/main",
+            "refs/namespaces/foo/refs/tags/v1"
+        ]
+    );
+    assert_eq!(
+        names(namespaced.iter()?.all()?)?,
+        vec!["refs/heads/main", "refs/tags/v1"],
+        "namespaced handles only see their own references, without the namespace"
+    );
+    assert_eq!(
+        names(namespaced.iter()?.prefixed("refs/heads/")?)?,
+        vec!["refs/heads/main"]
+    );
+    assert_eq!(namespaced.find("v1")?.target, Target::Peeled(id(2)));
+    Ok(())
+}
+
+/// Return true if the installed git can create repositories storing references in reftables, which needs git 2.45.
+fn git_supports_reftable() -> crate::Result<bool> {
+    let dir = tempfile::TempDir::new()?;
+    Ok(std::process::Command::new("git")
+        .args(&["init", "-q", "--ref-format=reftable"])
+        .arg(dir.path())
+        .stderr(std::process::Stdio::null())
+        .status()?
+        .success())
+}
+
+#[test]
+fn tables_written_by_git_can_be_read() -> crate::Result {
+    if !git_supports_reftable()? {
+        eprintln!("Skipping test as the installed git can't write reftables");
+        return Ok(());
+    }
+    let dir = git_testtools::scripted_fixture_repo_read_only("make_reftable_repository.sh")?;
+    let store = git_ref::Store::at_with_format(
+        dir.join(".git"),
+        git_ref::store::Format::Reftable,
+        WriteReflog::Normal,
+        git_hash::Kind::Sha1,
+    )?;
+    let handle = store.to_handle();
+    let refs = handle.iter()?.prefixed("refs/")?.collect::<Result<Vec<_>, _>>()?;
+
+    let output = std::process::Command::new("git")
+        .args(&["for-each-ref", "--format=%(refname) %(objectname)"])
+        .current_dir(&dir)
+        .output()?;
+    assert!(output.status.success());
+    let expected: Vec<_> = output.stdout.lines().map(|line| line.to_owned()).collect();
+    let actual: Vec<_> = refs
+        .iter()
+        .map(|r| {
+            let id = match &r.target {
+                Target::Peeled(id) => *id,
+                Target::Symbolic(name) => handle
+                    .find(name.as_bstr())
+                    .expect("symbolic refs point to existing refs")
+                    .target
+                    .into_id(),
+            };
+            format!("{} {}", r.name.as_bstr(), id).into_bytes()
+        })
+        .collect();
+    assert_eq!(actual, expected, "all refs are read like git reads them");
+    assert!(
+        handle.try_find("b1")?.is_none(),
+        "deletions in newer tables hide refs of older ones"
+    );
+
+    let main = handle.find("main")?;
+    assert_eq!(
+        handle.find("HEAD")?.target,
+        Target::Symbolic("refs/heads/main".try_into()?)
+    );
+    let tag = handle.find("dt1")?;
+    assert!(tag.peeled.is_some(), "annotated tags are stored with their peeled id");
+
+    let stack = Stack::at(
+        dir.join(".git").join("reftable"),
+        WriteReflog::Normal,
+        git_hash::Kind::Sha1,
+    )?;
+    let logs = stack.logs_for("refs/heads/main".into())?;
+    assert_eq!(logs.len(), 2, "one entry per commit");
+    assert_eq!(
+        logs[0].line.as_ref().expect("not deleted").new_oid,
+        main.target.into_id(),
+        "the newest log entry comes first"
+    );
+    Ok(())
+}
@@ -0,0 +1,205 @@
+//!
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+};
+
+use git_config::{file::GitConfig, values::Integer};
+
+use crate::bstr::{BStr, ByteSlice};
+
+/// The source a configuration value was read from, in ascending order of precedence.
+pub use git_config::fs::ConfigSource as Source;
+/// Well-known keys for use with [`Snapshot::get()`].
+pub use git_config::keys;
+
+/// Typed access to the configuration of a repository, merged from all of its sources, as obtained by
+/// [`easy::Handle::config()`][crate::easy::Handle::config()].
+///
+/// Keys are of the form `section[.subsection].name`, like `core.bare` or `remote.origin.url`.
+#[derive(Clone, Copy)]
+pub struct Snapshot<'repo> {
+    pub(crate) config: &'repo git_config::fs::Config<'static>,
+}
+
+impl<'repo> Snapshot<'repo> {
+    /// Return the boolean at `key`, or `None` if it isn't set.
+    ///
+    /// Empty values like `key =` are `false`, while keys without `=` are `true`.
+    /// Fails with the source of the value if the one taking precedence isn't a valid boolean.
+    pub fn boolean(&self, key: &str) -> Result<Option<bool>, value::Error> {
+        self.value(key, |config, section, subsection, name| {
+            if config.value_is_implicit(section, subsection, name).ok()? {
+                <bool as keys::Value>::from_implicit()
+            } else {
+                <bool as keys::Value>::from_bytes(config.get_raw_value(section, subsection, name).ok()?)
+            }
+        })
+    }
+
+    /// Return the integer at `key` with its suffix applied, or `None` if it isn't set.
+    ///
+    /// Fails with the source of the value if the one taking precedence isn't a valid integer or doesn't fit into an `i64`.
+    pub fn integer(&self, key: &str) -> Result<Option<i64>, value::Error> {
+        self.value(key, |config, section, subsection, name| {
+            let value: Integer = config.value(section, subsection, name).ok()?;
+            match value.suffix {
+                Some(suffix) => value.value.checked_mul(1 << suffix.bitwise_offset()),
+                None => Some(value.value),
+            }
+        })
+    }
+
+    /// Return the string at `key`, or `None` if it isn't set.
+    pub fn string(&self, key: &str) -> Option<Cow<'repo, BStr>> {
+        self.raw_value(key).map(|value| match value {
+            Cow::Borrowed(value) => Cow::Borrowed(value.as_bstr()),
+            Cow::Owned(value) => Cow::Owned(value.into()),
+        })
+    }
+
+    /// Return the source of the value at `key` that takes precedence, or `None` if it isn't set.
+    pub fn source(&self, key: &str) -> Option<Source> {
+        let (section, subsection, name) = split_key(key)?;
+        self.config
+            .value_origin(section, subsection, name)
+            .map(|(source, _)| source)
+    }
+
+    /// Return the path at `key` after [interpolating][git_config::values::Path::interpolate()] it, or `None` if it isn't set.
+    ///
+    /// `%(prefix)/` is expanded to the directory the current executable was installed into, like `/usr` for `/usr/bin/git`.
+    pub fn path(&self, key: &str) -> Result<Option<Cow<'repo, Path>>, path::Error> {
+        let path = match self.raw_value(key) {
+            Some(value) => git_config::values::Path::from(value),
+            None => return Ok(None),
+        };
+        path.interpolate(install_dir().as_deref())
+            .map(Some)
+            .map_err(|source| path::Error::Interpolate {
+                key: key.to_owned(),
+                source,
+            })
+    }
+
+    /// Return the path of the file the value at `key` was read from, or `None` if it isn't set or wasn't read from a file,
+    /// like values passed on the command-line.
+    pub fn source_file(&self, key: &str) -> Option<&'repo Path> {
+        let (section, subsection, name) = split_key(key)?;
+        self.config
+            .value_origin(section, subsection, name)
+            .and_then(|(_, path)| path)
+    }
+
+    /// Return the value of the well-known `key`, or its default if it isn't set, like `core.bare` or `fetch.prune`.
+    ///
+    /// Fails with the file and line of the value if it is invalid.
+    pub fn get<T: keys::Value>(&self, key: &keys::Key<T>) -> Result<Option<T>, keys::Error> {
+        self.config.get(key)
+    }
+
+    /// Return the underlying configuration, for access to all of its sources.
+    pub fn inner(&self) -> &'repo git_config::fs::Config<'static> {
+        self.config
+    }
+
+    /// Return the value at `key` that takes precedence as stored, or `None` if it isn't set.
+    fn raw_value(&self, key: &str) -> Option<Cow<'repo, [u8]>> {
+        self.value(key, |config, section, subsection, name| {
+            config.get_raw_value(section, subsection, name).ok()
+        })
+        .expect("values that are set can always be read")
+    }
+
+    /// Return the value at `key` that takes precedence after converting it with `convert`, or `None` if it isn't set.
+    ///
+    /// Like git, values with lower precedence are never used instead of one that `convert` fails on, which is an error
+    /// naming the source of the value.
+    fn value<T>(
+        &self,
+        key: &str,
+        convert: impl FnOnce(&'repo GitConfig<'static>, &str, Option<&str>, &str) -> Option<T>,
+    ) -> Result<Option<T>, value::Error> {
+        let (section, subsection, name) = match split_key(key) {
+            Some(parts) => parts,
+            None => return Ok(None),
+        };
+        let (source, path) = match self.config.value_origin(section, subsection, name) {
+            Some(origin) => origin,
+            None => return Ok(None),
+        };
+        let config = self
+            .config
+            .get_config(source)
+            .expect("values are read from existing sources");
+        match convert(config, section, subsection, name) {
+            Some(value) => Ok(Some(value)),
+            None => Err(value::Error::Invalid {
+                key: key.to_owned(),
+                value: config
+                    .get_raw_value(section, subsection, name)
+                    .map(|value| value.into_owned().into())
+                    .unwrap_or_default(),
+                config_source: source,
+                path: path.map(ToOwned::to_owned),
+            }),
+        }
+    }
+}
+
+/// The directory the current executable was installed into, assuming it lives in a `bin` directory.
+pub(crate) fn install_dir() -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    let dir = exe.parent()?;
+    Some(match dir.parent() {
+        Some(parent) if dir.file_name().map_or(false, |name| name == "bin") => parent.to_owned(),
+        _ => dir.to_owned(),
+    })
+}
+
+/// Split `key` into section, subsection and name, with the subsection possibly containing dots.
+fn split_key(key: &str) -> Option<(&str, Option<&str>, &str)> {
+    let (section, rest) = key.split_once('.')?;
+    Some(match rest.rsplit_once('.') {
+        Some((subsection, name)) => (section, Some(subsection), name),
+        None => (section, None, rest),
+    })
+}
+
+///
+pub mod value {
+    use std::path::PathBuf;
+
+    use crate::{bstr::BString, config::Source};
+
+    /// The error returned by [`Snapshot::boolean()`][super::Snapshot::boolean()] and
+    /// [`Snapshot::integer()`][super::Snapshot::integer()].
+    #[derive(Debug, thiserror::Error)]
+    #[allow(missing_docs)]
+    pub enum Error {
+        #[error(
+            "The value '{value}' of '{key}' in the {config_source:?} configuration{} is invalid",
+            .path.as_ref().map(|path| format!(" at '{}'", path.display())).unwrap_or_default()
+        )]
+        Invalid {
+            key: String,
+            value: BString,
+            config_source: Source,
+            path: Option<PathBuf>,
+        },
+    }
+}
+
+///
+pub mod path {
+    /// The error returned by [`Snapshot::path()`][super::Snapshot::path()].
+    #[derive(Debug, thiserror::Error)]
+    #[allow(missing_docs)]
+    pub enum Error {
+        #[error("The path at '{key}' could not be interpolated")]
+        Interpolate {
+            key: String,
+            source: git_config::values::PathInterpolationError,
+        },
+    }
+}
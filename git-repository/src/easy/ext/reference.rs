@@ -149,6 +149,9 @@ impl easy::Handle {
     ///
     /// Returns all reference edits, which might be more than where provided due the splitting of symbolic references, and
     /// whose previous (_old_) values are the ones seen on in storage after the reference was locked.
+    ///
+    /// The `reference-transaction` hook in the [hooks directory][easy::Handle::hooks_dir()] is run if it exists, and may
+    /// reject the edits.
     pub fn edit_references(
         &self,
         edits: impl IntoIterator<Item = RefEdit>,
@@ -163,8 +166,13 @@ impl easy::Handle {
                 &committer_storage
             }
         };
+        let hook = git_ref::file::transaction::Hook {
+            path: self.hooks_dir()?.join("reference-transaction"),
+            working_dir: self.work_tree().unwrap_or_else(|| self.refs.base()).to_owned(),
+        };
         self.refs
             .transaction()
+            .reference_transaction_hook(Some(hook))
             .prepare(edits, lock_mode)?
             .commit(committer)
             .map_err(Into::into)
@@ -189,6 +197,28 @@ impl easy::Handle {
         .attach(self))
     }
 
+    /// Return the entries of `FETCH_HEAD` as written by the last fetch, or `None` if there is no such file.
+    pub fn fetch_head(&self) -> Result<Option<Vec<git_ref::pseudo::FetchHeadEntry>>, git_ref::file::pseudo::Error> {
+        self.refs.fetch_head()
+    }
+
+    /// Return the ids of all commits being merged into `HEAD` as stored in `MERGE_HEAD`, or `None` if no merge is in progress.
+    pub fn merge_heads(&self) -> Result<Option<Vec<ObjectId>>, git_ref::file::pseudo::Error> {
+        self.refs.merge_heads()
+    }
+
+    /// Return the id stored in the pseudo-reference of the given `kind`, like `ORIG_HEAD` or `CHERRY_PICK_HEAD`, or `None`
+    /// if it doesn't exist.
+    ///
+    /// For `FETCH_HEAD` and `MERGE_HEAD` this is the first of their ids. Use
+    /// [`in_progress_operation()`][easy::Handle::in_progress_operation()] to learn which operation they belong to.
+    pub fn pseudo_reference_id(
+        &self,
+        kind: git_ref::pseudo::Kind,
+    ) -> Result<Option<ObjectId>, git_ref::file::pseudo::Error> {
+        self.refs.pseudo_ref_id(kind)
+    }
+
     /// Find the reference with the given partial or full `name`, like `main`, `HEAD`, `heads/branch` or `origin/other`,
     /// or return an error if it wasn't found.
     ///
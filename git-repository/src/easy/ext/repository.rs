@@ -14,6 +14,16 @@ impl easy::Handle {
         git_actor::Signature::empty()
     }
 
+    /// Return the configuration of the repository, merged from all of its sources, for typed access to its values.
+    pub fn config(&self) -> crate::config::Snapshot<'_> {
+        crate::config::Snapshot { config: &self.config }
+    }
+
+    /// Return the directory containing hooks, like [`Repository::hooks_dir()`][crate::Repository::hooks_dir()].
+    pub fn hooks_dir(&self) -> Result<std::path::PathBuf, crate::config::path::Error> {
+        crate::repository::hooks_dir(self.config(), self.work_tree(), &self.refs)
+    }
+
     /// The kind of object hash the repository is configured to use.
     pub fn object_hash(&self) -> git_hash::Kind {
         self.object_hash
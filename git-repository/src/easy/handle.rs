@@ -4,6 +4,8 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use git_features::threading::OwnShared;
+
 use crate::easy;
 
 impl Clone for easy::Handle {
@@ -13,6 +15,7 @@ impl Clone for easy::Handle {
             self.objects.clone(),
             self.object_hash,
             self.work_tree.clone(),
+            self.config.clone(),
         )
     }
 }
@@ -31,11 +34,13 @@ impl easy::Handle {
         objects: crate::OdbHandle,
         object_hash: git_hash::Kind,
         work_tree: Option<PathBuf>,
+        config: OwnShared<git_config::fs::Config<'static>>,
     ) -> Self {
         easy::Handle {
             bufs: RefCell::new(Vec::with_capacity(4)),
             object_hash,
             work_tree,
+            config,
             objects: {
                 #[cfg(feature = "max-performance")]
                 {
@@ -58,6 +63,7 @@ impl From<&crate::Repository> for easy::Handle {
             repo.objects.to_handle().into(),
             repo.object_hash,
             repo.work_tree.clone(),
+            repo.config.clone(),
         )
     }
 }
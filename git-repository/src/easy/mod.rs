@@ -13,6 +13,7 @@ pub mod head;
 pub mod object;
 pub mod oid;
 pub mod reference;
+pub mod state;
 pub mod tag;
 
 /// The head reference, as created from looking at `.git/HEAD`, able to represent all of its possible states.
@@ -123,6 +124,8 @@ pub struct Handle {
     work_tree: Option<PathBuf>,
     /// The kind of hash that is used or should be used for object ids
     object_hash: git_hash::Kind,
+    /// The configuration of the repository, merged from all of its sources
+    config: git_features::threading::OwnShared<git_config::fs::Config<'static>>,
     /// A free-list of re-usable object backing buffers
     bufs: RefCell<Vec<Vec<u8>>>,
 }
@@ -45,6 +45,28 @@ impl<'r> Platform<'r> {
             handle: self.handle,
         })
     }
+
+    /// Return all references selected by `options` in the order they configure, similar to `git for-each-ref`.
+    ///
+    /// Objects are looked up as needed to filter by the objects references point to or their history, or to sort them by
+    /// committer date.
+    pub fn filtered(
+        &self,
+        options: &git_ref::file::filter::Options,
+    ) -> Result<Vec<easy::Reference<'r>>, git_ref::file::filter::Error> {
+        let handle = self.handle;
+        Ok(self
+            .platform
+            .filtered(options, |oid, buf| {
+                handle
+                    .objects
+                    .try_find(oid, buf)
+                    .map(|po| po.map(|(o, _l)| (o.kind, o.data)))
+            })?
+            .into_iter()
+            .map(|r| easy::Reference::from_ref(r, handle))
+            .collect())
+    }
 }
 
 impl<'r> Iter<'r> {
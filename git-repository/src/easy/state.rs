@@ -0,0 +1,69 @@
+//!
+use crate::easy;
+
+/// An operation that is in progress and waiting for the user to continue or abort it, as indicated by the files
+/// git leaves in the `.git` directory while it's ongoing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InProgress {
+    /// `git am` is applying patches from a mailbox.
+    ApplyMailbox,
+    /// `git rebase` is replaying commits using the apply backend.
+    ApplyMailboxRebase,
+    /// `git bisect` is searching for a commit.
+    Bisect,
+    /// `git cherry-pick` stopped while picking a single commit, typically due to conflicts.
+    CherryPick,
+    /// `git cherry-pick` stopped while picking one of multiple commits.
+    CherryPickSequence,
+    /// `git merge` stopped before creating the merge commit.
+    Merge,
+    /// `git rebase` is replaying commits using the merge backend.
+    Rebase,
+    /// `git rebase --interactive` is replaying commits.
+    RebaseInteractive,
+    /// `git revert` stopped while reverting a single commit.
+    Revert,
+    /// `git revert` stopped while reverting one of multiple commits.
+    RevertSequence,
+}
+
+/// State
+impl easy::Handle {
+    /// Return the operation that is currently in progress in this repository or work tree, or `None` if there is none.
+    ///
+    /// The checks are performed in the same order as git does them to describe the repository state, so a merge
+    /// started while rebasing is reported as rebase.
+    pub fn in_progress_operation(&self) -> Option<InProgress> {
+        use git_ref::pseudo::Kind;
+
+        let git_dir = self.refs.base();
+        let is_sequence = || git_dir.join("sequencer").join("todo").is_file();
+        Some(if git_dir.join("rebase-apply").join("applying").is_file() {
+            InProgress::ApplyMailbox
+        } else if git_dir.join("rebase-apply").is_dir() {
+            InProgress::ApplyMailboxRebase
+        } else if git_dir.join("rebase-merge").join("interactive").is_file() {
+            InProgress::RebaseInteractive
+        } else if git_dir.join("rebase-merge").is_dir() {
+            InProgress::Rebase
+        } else if self.refs.pseudo_ref_exists(Kind::CherryPickHead) {
+            if is_sequence() {
+                InProgress::CherryPickSequence
+            } else {
+                InProgress::CherryPick
+            }
+        } else if self.refs.pseudo_ref_exists(Kind::MergeHead) {
+            InProgress::Merge
+        } else if git_dir.join("BISECT_LOG").is_file() {
+            InProgress::Bisect
+        } else if self.refs.pseudo_ref_exists(Kind::RevertHead) {
+            if is_sequence() {
+                InProgress::RevertSequence
+            } else {
+                InProgress::Revert
+            }
+        } else {
+            return None;
+        })
+    }
+}
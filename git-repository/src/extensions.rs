@@ -0,0 +1,141 @@
+//!
+use std::{borrow::Cow, convert::TryFrom};
+
+use git_config::{
+    file::GitConfig,
+    values::{Boolean, Integer},
+};
+
+use crate::bstr::BString;
+
+/// The error returned by [`Extensions::from_config()`].
+#[derive(Debug, thiserror::Error)]
+#[allow(missing_docs)]
+pub enum Error {
+    #[error("Invalid value for core.repositoryFormatVersion: {:?}", .value)]
+    InvalidFormatVersion { value: BString },
+    #[error("Expected repository format version 0 or 1, got {}", .version)]
+    UnsupportedFormatVersion { version: i64 },
+    #[error("Repository uses the unknown extension '{}'", .name)]
+    UnknownExtension { name: BString },
+    #[error("The extension '{}' requires repository format version 1, but the repository is at version 0", .name)]
+    ExtensionRequiresFormatVersion1 { name: BString },
+    #[error("Invalid value {:?} for extension '{}'", .value, .name)]
+    InvalidValue { name: BString, value: BString },
+    #[error("Cannot handle objects formatted as {:?}", .name)]
+    UnsupportedObjectFormat { name: BString },
+    #[error("Cannot handle references stored as {:?}", .name)]
+    UnsupportedRefStorage { name: BString },
+}
+
+/// The version of the repository format as stored in `core.repositoryFormatVersion`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Version {
+    /// The original format, without extensions, even though a few of them are understood for historical reasons.
+    V0,
+    /// The format which requires readers to understand all of its extensions.
+    V1,
+}
+
+impl Default for Version {
+    fn default() -> Self {
+        Version::V0
+    }
+}
+
+/// The repository format version along with all extensions the repository requires us to understand, as read from
+/// `core.repositoryFormatVersion` and the `extensions` section.
+///
+/// Opening a repository fails if it uses extensions we don't know as we would risk corrupting it otherwise.
+#[derive(Default, Debug, Clone, Eq, PartialEq)]
+pub struct Extensions {
+    /// The version of the repository format.
+    pub version: Version,
+    /// The kind of hash used for objects as set by `extensions.objectFormat`.
+    pub object_hash: git_hash::Kind,
+    /// If true, `extensions.worktreeConfig` was set and the `config.worktree` file of each work tree is read
+    /// in addition to the shared configuration.
+    pub worktree_config: bool,
+    /// If true, `extensions.preciousObjects` was set and objects must never be deleted from the object database,
+    /// as it's probably shared with other repositories.
+    pub precious_objects: bool,
+    /// The name of the promisor remote set by `extensions.partialClone`, which is the one to obtain missing objects from.
+    pub partial_clone: Option<BString>,
+    /// The format references are stored in as set by `extensions.refStorage`.
+    pub ref_storage: git_ref::store::Format,
+}
+
+impl Extensions {
+    /// Read the repository format version and extensions from `config`, the configuration file of the repository,
+    /// and fail if any of them is unknown or unsupported.
+    pub fn from_config(config: &GitConfig<'_>) -> Result<Self, Error> {
+        let version = match config.value::<Cow<'_, [u8]>>("core", None, "repositoryFormatVersion") {
+            Ok(value) => match Integer::try_from(value.as_ref()).map(|v| v.value) {
+                Ok(0) => Version::V0,
+                Ok(1) => Version::V1,
+                Ok(version) => return Err(Error::UnsupportedFormatVersion { version }),
+                Err(_) => {
+                    return Err(Error::InvalidFormatVersion {
+                        value: value.into_owned().into(),
+                    })
+                }
+            },
+            Err(_) => Version::V0,
+        };
+
+        let mut out = Extensions {
+            version,
+            ..Default::default()
+        };
+        for section in config.sections_by_name("extensions") {
+            for key in section.keys() {
+                let name = key.0.to_ascii_lowercase();
+                let value = section.value(key).unwrap_or_default();
+                let boolean = || {
+                    Boolean::try_from(value.clone())
+                        .map(|b| matches!(b, Boolean::True(_)))
+                        .map_err(|_| Error::InvalidValue {
+                            name: name.as_str().into(),
+                            value: value.as_ref().into(),
+                        })
+                };
+                match name.as_str() {
+                    // These are understood by version 0 for historical reasons.
+                    "noop" => {}
+                    "preciousobjects" => out.precious_objects = boolean()?,
+                    "partialclone" => out.partial_clone = Some(value.as_ref().into()),
+                    "worktreeconfig" => out.worktree_config = boolean()?,
+                    // In version 0 unknown extensions are ignored, but we must not silently ignore version 1 extensions.
+                    "noop-v1" | "objectformat" | "refstorage" if version == Version::V0 => {
+                        return Err(Error::ExtensionRequiresFormatVersion1 { name: name.into() })
+                    }
+                    "noop-v1" => {}
+                    "objectformat" => {
+                        out.object_hash = match value.as_ref() {
+                            b"sha1" => git_hash::Kind::Sha1,
+                            _ => {
+                                return Err(Error::UnsupportedObjectFormat {
+                                    name: value.as_ref().into(),
+                                })
+                            }
+                        }
+                    }
+                    "refstorage" => {
+                        out.ref_storage = match value.as_ref() {
+                            b"files" => git_ref::store::Format::Files,
+                            b"reftable" => git_ref::store::Format::Reftable,
+                            _ => {
+                                return Err(Error::UnsupportedRefStorage {
+                                    name: value.as_ref().into(),
+                                })
+                            }
+                        }
+                    }
+                    _ if version == Version::V0 => {}
+                    _ => return Err(Error::UnknownExtension { name: name.into() }),
+                }
+            }
+        }
+        Ok(out)
+    }
+}
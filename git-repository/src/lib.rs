@@ -118,6 +118,7 @@ pub use git_features::{parallel, progress, progress::Progress, threading};
 pub use git_hash as hash;
 #[doc(inline)]
 pub use git_hash::{oid, ObjectId};
+pub use git_index as index;
 pub use git_lock as lock;
 pub use git_object as objs;
 pub use git_object::bstr;
@@ -166,6 +167,14 @@ pub enum Path {
     WorkTree(PathBuf),
     /// The git repository itself
     Repository(PathBuf),
+    /// A work tree linked to a repository whose `.git` file points to its private git directory, usually
+    /// located at `$GIT_COMMON_DIR/worktrees/<id>`.
+    LinkedWorkTree {
+        /// The directory containing the checked out files.
+        work_dir: PathBuf,
+        /// The private git directory of the work tree, containing per-worktree files like `HEAD` and `index`.
+        git_dir: PathBuf,
+    },
 }
 
 /// A instance with access to everything a git repository entails, best imagined as container for _most_ for system resources required
@@ -176,6 +185,11 @@ pub enum Path {
 pub struct Repository {
     /// A store for references to point at objects
     pub refs: RefStore,
+    /// The stack of tables holding all references if `extensions.refStorage` is `reftable`, in which case the tables
+    /// are invisible to [`refs`][Repository::refs] which then only provides the paths of the repository.
+    ///
+    /// Use [`to_handle()`][git_ref::Store::to_handle()] to find, iterate and change references in it.
+    pub reftable: Option<git_ref::Store>,
     /// A store for objects that contain data
     #[cfg(feature = "unstable")]
     pub objects: OwnShared<git_odb::Store>,
@@ -184,10 +198,10 @@ pub struct Repository {
     /// The path to the worktree at which to find checked out files
     pub work_tree: Option<PathBuf>,
     pub(crate) object_hash: git_hash::Kind,
-    // TODO: git-config should be here - it's read a lot but not written much in must applications, so shouldn't be in `State`.
-    //       Probably it's best reload it on signal (in servers) or refresh it when it's known to have been changed similar to how
-    //       packs are refreshed. This would be `git_config::fs::Config` when ready.
-    // pub(crate) config: git_config::file::GitConfig<'static>,
+    pub(crate) extensions: extensions::Extensions,
+    // TODO: Probably it's best reload it on signal (in servers) or refresh it when it's known to have been changed similar to how
+    //       packs are refreshed.
+    pub(crate) config: OwnShared<git_config::fs::Config<'static>>,
 }
 
 ///
@@ -197,6 +211,14 @@ pub mod easy;
 pub mod commit;
 ///
 pub mod reference;
+///
+pub mod worktree;
+///
+pub mod extensions;
+///
+pub mod config;
+///
+pub mod reflog;
 
 /// The kind of `Repository`
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
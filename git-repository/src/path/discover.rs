@@ -1,9 +1,12 @@
 //!
 use std::{
     borrow::Cow,
-    path::{Component, Path},
+    convert::TryFrom,
+    path::{Component, Path, PathBuf},
 };
 
+use git_config::fs::ConfigSource;
+
 use crate::path;
 
 ///
@@ -18,13 +21,92 @@ pub mod existing {
         InaccessibleDirectory { path: PathBuf },
         #[error("Could find a git repository in '{}' or in any of its parents", .path.display())]
         NoGitRepository { path: PathBuf },
+        #[error("Could not find a git repository in '{}' or in any of its parents below the ceiling directory '{}'", .path.display(), .ceiling.display())]
+        NoGitRepositoryWithinCeiling { path: PathBuf, ceiling: PathBuf },
+        #[error("Could not find a git repository in '{}' or in any of its parents, stopping at the file system boundary at '{}'", .path.display(), .limit.display())]
+        NoGitRepositoryWithinFs { path: PathBuf, limit: PathBuf },
+        #[error("The repository at '{}' is owned by someone else and not listed as safe directory", .path.display())]
+        UntrustedOwner { path: PathBuf },
+    }
+}
+
+/// Options to control how repositories are discovered in [`existing_opts()`].
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Absolute paths of directories which the search won't ascend into, even though the directories below them are searched.
+    ///
+    /// The directory to start the search from is always searched. Relative paths are ignored.
+    pub ceiling_dirs: Vec<PathBuf>,
+    /// If true, continue the search in parent directories even if they are on another file system than the starting directory.
+    ///
+    /// Defaults to `false`, like git. Only supported on unix, other platforms always search across file systems.
+    pub cross_fs: bool,
+    /// If true, a discovered repository must be owned by the current user unless it's listed in `safe_directories`
+    /// or in `safe.directory` of the configuration files of `config`.
+    ///
+    /// The current user is the effective user of the process, or the one in `SUDO_UID` if it's run by root via `sudo`.
+    ///
+    /// Defaults to `true`, like git. Only supported on unix, other platforms consider all repositories to be owned by the current user.
+    pub check_ownership: bool,
+    /// Repositories to consider safe even if they are owned by someone else, in addition to those configured via
+    /// `safe.directory`, which are compared to the work tree directory or the git directory of bare repositories.
+    ///
+    /// A `*` trusts all repositories.
+    pub safe_directories: Vec<PathBuf>,
+    /// The configuration to read `safe.directory` from if a repository isn't owned by the current user. Like in git, only
+    /// the system and global configuration files and overrides passed as if on the command-line are used, as the
+    /// configuration of a repository can't be trusted before its ownership is known.
+    ///
+    /// Defaults to the files at their default locations without reading the environment.
+    pub config: git_config::fs::ConfigBuilder,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            ceiling_dirs: Vec::new(),
+            cross_fs: false,
+            check_ownership: true,
+            safe_directories: Vec::new(),
+            config: Default::default(),
+        }
+    }
+}
+
+impl Options {
+    /// Override our values with those of the `GIT_CEILING_DIRECTORIES` and `GIT_DISCOVERY_ACROSS_FILESYSTEM` environment
+    /// variables, if set, and let the environment affect which configuration files `safe.directory` is read from.
+    ///
+    /// The environment isn't read unless this method is called.
+    pub fn apply_environment(mut self) -> Self {
+        self.config.load_environment_entries(true);
+        if let Some(dirs) = std::env::var_os("GIT_CEILING_DIRECTORIES") {
+            self.ceiling_dirs = std::env::split_paths(&dirs).collect();
+        }
+        if let Some(cross_fs) = std::env::var_os("GIT_DISCOVERY_ACROSS_FILESYSTEM") {
+            self.cross_fs = cross_fs.to_str().map_or(false, |value| {
+                matches!(
+                    git_config::values::Boolean::try_from(value),
+                    Ok(git_config::values::Boolean::True(_))
+                )
+            });
+        }
+        self
     }
 }
 
-/// Find the location of the git repository directly in `directory` or in any of its parent directories.
+/// Find the location of the git repository directly in `directory` or in any of its parent directories,
+/// using the default [`Options`].
 ///
 /// Fail if no valid-looking git repository could be found.
 pub fn existing(directory: impl AsRef<Path>) -> Result<crate::Path, existing::Error> {
+    existing_opts(directory, Options::default())
+}
+
+/// Find the location of the git repository directly in `directory` or in any of its parent directories, as controlled by `options`.
+///
+/// Fail if no valid-looking git repository could be found, or if it's not trusted.
+pub fn existing_opts(directory: impl AsRef<Path>, options: Options) -> Result<crate::Path, existing::Error> {
     // Canonicalize the path so that `Path::parent` _actually_ gives
     // us the parent directory. (`Path::parent` just strips off the last
     // path component, which means it will not do what you expect when
@@ -37,25 +119,146 @@ pub fn existing(directory: impl AsRef<Path>) -> Result<crate::Path, existing::Er
             path: directory.into_owned(),
         });
     }
+    let directory = if directory.is_relative() && !options.ceiling_dirs.is_empty() {
+        // Ceiling directories are absolute, and we must be as well to compare with them.
+        directory
+            .canonicalize()
+            .map_err(|_| existing::Error::InaccessibleDirectory {
+                path: directory.clone().into_owned(),
+            })?
+            .into()
+    } else {
+        directory
+    };
+    let ceiling = options
+        .ceiling_dirs
+        .iter()
+        .filter(|dir| dir.is_absolute() && directory.starts_with(dir) && directory.as_ref() != dir.as_path())
+        .max_by_key(|dir| dir.components().count());
+    let fs_device = (!options.cross_fs).then(|| device_id(&directory)).flatten();
 
     let mut cursor: &Path = &directory;
-    loop {
+    let path = loop {
         if let Ok(kind) = path::is::git(cursor) {
-            break Ok(crate::Path::from_dot_git_dir(cursor, kind));
+            break crate::Path::from_dot_git_dir(cursor, kind);
         }
         let git_dir = cursor.join(".git");
         if let Ok(kind) = path::is::git(&git_dir) {
-            break Ok(crate::Path::from_dot_git_dir(git_dir, kind));
+            break crate::Path::from_dot_git_dir(git_dir, kind);
+        }
+        if git_dir.is_file() {
+            if let Some(git_dir) = path::from_gitdir_file(&git_dir)
+                .ok()
+                .filter(|git_dir| path::is::git(git_dir).is_ok())
+            {
+                break crate::Path::LinkedWorkTree {
+                    work_dir: cursor.to_owned(),
+                    git_dir,
+                };
+            }
         }
         match cursor.parent() {
-            Some(parent) => cursor = parent,
+            Some(parent) => {
+                if let Some(ceiling) = ceiling.filter(|ceiling| parent == ceiling.as_path()) {
+                    return Err(existing::Error::NoGitRepositoryWithinCeiling {
+                        path: directory.into_owned(),
+                        ceiling: ceiling.to_owned(),
+                    });
+                }
+                if fs_device.is_some() && fs_device != device_id(parent) {
+                    return Err(existing::Error::NoGitRepositoryWithinFs {
+                        path: directory.to_path_buf(),
+                        limit: cursor.to_owned(),
+                    });
+                }
+                cursor = parent;
+            }
             None => {
-                break Err(existing::Error::NoGitRepository {
+                return Err(existing::Error::NoGitRepository {
                     path: directory.into_owned(),
                 })
             }
         }
+    };
+
+    ensure_trusted(&path, &options)?;
+    Ok(path)
+}
+
+/// Fail if `options` ask for ownership checks and the repository at `path` isn't owned by the current user, unless it's
+/// listed as safe directory.
+pub(crate) fn ensure_trusted(path: &crate::Path, options: &Options) -> Result<(), existing::Error> {
+    let path = path.as_ref();
+    if !options.check_ownership || is_owned_by_current_user(path) {
+        return Ok(());
+    }
+    // Repositories without index look bare, but should still be trusted by their work tree directory.
+    let work_dir = path.file_name().filter(|name| *name == ".git").and(path.parent());
+    let is_safe = |safe: &PathBuf| safe.as_os_str() == "*" || safe == path || Some(safe.as_path()) == work_dir;
+    if options.safe_directories.iter().any(is_safe) || safe_directories(&options.config).iter().any(is_safe) {
+        return Ok(());
+    }
+    Err(existing::Error::UntrustedOwner { path: path.to_owned() })
+}
+
+/// Read all values of `safe.directory` from the trusted sources of `config`, where an empty value resets the list.
+fn safe_directories(config: &git_config::fs::ConfigBuilder) -> Vec<PathBuf> {
+    let config = config.build();
+    let install_dir = crate::config::install_dir();
+    let mut dirs = Vec::new();
+    for source in &[
+        ConfigSource::System,
+        ConfigSource::User,
+        ConfigSource::Global,
+        ConfigSource::Cli,
+    ] {
+        let values = config
+            .get_config(*source)
+            .and_then(|config| config.get_raw_multi_value("safe", None, "directory").ok())
+            .unwrap_or_default();
+        for value in values {
+            if value.is_empty() {
+                dirs.clear();
+            } else if let Ok(path) = git_config::values::Path::from(value).interpolate(install_dir.as_deref()) {
+                dirs.push(path.into_owned());
+            }
+        }
+    }
+    dirs
+}
+
+#[cfg(unix)]
+fn is_owned_by_current_user(path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).map_or(false, |m| m.uid() == current_user_id())
+}
+
+/// Return the effective user id of the process, or the one of the user who ran `sudo` if we are root, just like git.
+#[cfg(unix)]
+fn current_user_id() -> u32 {
+    let euid = nix::unistd::geteuid();
+    if euid.is_root() {
+        if let Some(uid) = std::env::var("SUDO_UID").ok().and_then(|uid| uid.parse().ok()) {
+            return uid;
+        }
     }
+    euid.as_raw()
+}
+
+#[cfg(not(unix))]
+fn is_owned_by_current_user(_path: &Path) -> bool {
+    true
+}
+
+#[cfg(unix)]
+fn device_id(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).map(|m| m.dev()).ok()
+}
+
+#[cfg(not(unix))]
+fn device_id(_path: &Path) -> Option<u64> {
+    None
 }
 
 fn maybe_canonicalize(path: &Path) -> std::io::Result<Cow<'_, Path>> {
@@ -13,6 +13,8 @@ pub enum Error {
     MissingObjectsDirectory { missing: PathBuf },
     #[error("Expected a refs directory at '{}'", .missing.display())]
     MissingRefsDirectory { missing: PathBuf },
+    #[error("Could not read the 'commondir' file in '{}'", .git_dir.display())]
+    InvalidCommonDir { source: std::io::Error, git_dir: PathBuf },
 }
 
 /// Returns true if the given `git_dir` seems to be a bare repository.
@@ -26,13 +28,19 @@ pub fn bare(git_dir: impl AsRef<Path>) -> bool {
 /// purely based on the presence of files. Note that the git-config ultimately decides what's bare.
 ///
 /// * [x] a valid head
-/// * [ ] git common directory
+/// * [x] git common directory
 ///   * [ ] respect GIT_COMMON_DIR
 /// * [x] an objects directory
-///   * [x] respect GIT_OBJECT_DIRECTORY
+///   * [ ] respect GIT_OBJECT_DIRECTORY, as the environment is only read if [requested][crate::open::Options::apply_environment()]
 /// * [x] a refs directory
 pub fn git(git_dir: impl AsRef<Path>) -> Result<crate::Kind, Error> {
     let dot_git = git_dir.as_ref();
+    let common_dir = crate::path::common_dir(dot_git)
+        .map_err(|err| Error::InvalidCommonDir {
+            source: err,
+            git_dir: dot_git.to_owned(),
+        })?
+        .unwrap_or_else(|| dot_git.to_owned());
 
     {
         // We expect to be able to parse any ref-hash, so we shouldn't have to know the repos hash here.
@@ -53,15 +61,13 @@ pub fn git(git_dir: impl AsRef<Path>) -> Result<crate::Kind, Error> {
     }
 
     {
-        let objects_path = std::env::var("GIT_OBJECT_DIRECTORY")
-            .map(PathBuf::from)
-            .unwrap_or_else(|_| dot_git.join("objects"));
+        let objects_path = common_dir.join("objects");
         if !objects_path.is_dir() {
             return Err(Error::MissingObjectsDirectory { missing: objects_path });
         }
     }
     {
-        let refs_path = dot_git.join("refs");
+        let refs_path = common_dir.join("refs");
         if !refs_path.is_dir() {
             return Err(Error::MissingRefsDirectory { missing: refs_path });
         }
@@ -11,6 +11,7 @@ impl AsRef<std::path::Path> for Path {
     fn as_ref(&self) -> &std::path::Path {
         match self {
             Path::WorkTree(path) | Path::Repository(path) => path,
+            Path::LinkedWorkTree { work_dir, .. } => work_dir,
         }
     }
 }
@@ -18,8 +19,13 @@ impl AsRef<std::path::Path> for Path {
 impl Path {
     /// Instantiate a new path from `dir` which is expected to be the `.git` directory, with `kind` indicating
     /// whether it's a bare repository or not.
+    ///
+    /// If `dir` is the private git directory of a linked work tree, the work tree location is read from its `gitdir` file.
     pub fn from_dot_git_dir(dir: impl Into<PathBuf>, kind: Kind) -> Self {
         let dir = dir.into();
+        if let Some(work_dir) = linked_work_dir(&dir) {
+            return Path::LinkedWorkTree { work_dir, git_dir: dir };
+        }
         match kind {
             Kind::WorkTree => Path::WorkTree(dir.parent().expect("this is a sub-directory").to_owned()),
             Kind::Bare => Path::Repository(dir),
@@ -28,7 +34,7 @@ impl Path {
     /// Returns the [kind][Kind] of this repository path.
     pub fn kind(&self) -> Kind {
         match self {
-            Path::WorkTree(_) => Kind::WorkTree,
+            Path::WorkTree(_) | Path::LinkedWorkTree { .. } => Kind::WorkTree,
             Path::Repository(_) => Kind::Bare,
         }
     }
@@ -37,7 +43,85 @@ impl Path {
     pub fn into_repository_and_work_tree_directories(self) -> (PathBuf, Option<PathBuf>) {
         match self {
             crate::Path::WorkTree(working_tree) => (working_tree.join(".git"), Some(working_tree)),
+            crate::Path::LinkedWorkTree { work_dir, git_dir } => (git_dir, Some(work_dir)),
             crate::Path::Repository(repository) => (repository, None),
         }
     }
 }
+
+/// Read the `.git` file at `path`, as used by linked work trees and submodules, and return the path to the git
+/// directory it points to in its `gitdir: <path>` line.
+///
+/// Relative paths are resolved relative to the directory containing the file.
+pub fn from_gitdir_file(path: impl AsRef<std::path::Path>) -> std::io::Result<PathBuf> {
+    let path = path.as_ref();
+    let contents = read_path_file(path)?;
+    let git_dir = contents.strip_prefix("gitdir: ").ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Expected 'gitdir: <path>' in '{}'", path.display()),
+        )
+    })?;
+    Ok(relative_to_parent_of(path, git_dir))
+}
+
+/// Return the directory shared by all work trees of the repository at `git_dir` as stored in its `commondir` file,
+/// or `None` if there is no such file as `git_dir` isn't the private git directory of a linked work tree.
+///
+/// Relative paths are resolved relative to `git_dir`.
+pub fn common_dir(git_dir: impl AsRef<std::path::Path>) -> std::io::Result<Option<PathBuf>> {
+    let git_dir = git_dir.as_ref();
+    match read_path_file(&git_dir.join("commondir")) {
+        Ok(common_dir) => Ok(Some(join_lexically(git_dir, &common_dir))),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Return the work tree directory of the linked work tree whose private git directory is `git_dir`, as stored in its
+/// `gitdir` file which points to the `.git` file of the work tree.
+fn linked_work_dir(git_dir: &std::path::Path) -> Option<PathBuf> {
+    if !git_dir.join("commondir").is_file() {
+        return None;
+    }
+    let dot_git = from_plain_file(&git_dir.join("gitdir")).ok()?;
+    dot_git.parent().map(ToOwned::to_owned)
+}
+
+/// Read the file at `path` which contains nothing but a path, resolving it relative to the directory containing the file.
+pub(crate) fn from_plain_file(path: &std::path::Path) -> std::io::Result<PathBuf> {
+    Ok(relative_to_parent_of(path, &read_path_file(path)?))
+}
+
+fn read_path_file(path: &std::path::Path) -> std::io::Result<String> {
+    let mut contents = std::fs::read_to_string(path)?;
+    let len = contents.trim_end_matches(&['\r', '\n'][..]).len();
+    contents.truncate(len);
+    if contents.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Expected a path in '{}'", path.display()),
+        ));
+    }
+    Ok(contents)
+}
+
+fn relative_to_parent_of(file: &std::path::Path, path: &str) -> PathBuf {
+    join_lexically(file.parent().expect("files have a parent directory"), path)
+}
+
+/// Join `path` to `base` while resolving `.` and `..` components without touching the file system.
+fn join_lexically(base: &std::path::Path, path: &str) -> PathBuf {
+    use std::path::Component;
+    let mut out = base.to_owned();
+    for component in std::path::Path::new(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if out.file_name().is_some() => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
@@ -0,0 +1,218 @@
+//!
+use std::{
+    convert::TryFrom,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use git_odb::Find;
+
+use crate::{
+    bstr::{BStr, BString, ByteSlice},
+    config,
+    refs::{file::log::expire, FullNameRef},
+};
+
+/// The error returned by [`Repository::reflog_expire_options()`][crate::Repository::reflog_expire_options()] and
+/// [`Repository::reflog_expire()`][crate::Repository::reflog_expire()].
+#[derive(Debug, thiserror::Error)]
+#[allow(missing_docs)]
+pub enum Error {
+    #[error("Invalid expiry date {:?} for {}", .value, .key)]
+    InvalidDate { key: String, value: BString },
+    #[error(transparent)]
+    Expire(#[from] expire::Error),
+}
+
+/// The value of `gc.reflogExpire` if unset.
+const DEFAULT_EXPIRE_DAYS: u64 = 90;
+/// The value of `gc.reflogExpireUnreachable` if unset.
+const DEFAULT_EXPIRE_UNREACHABLE_DAYS: u64 = 30;
+const DAY: u64 = 24 * 60 * 60;
+
+/// The configuration sources in the order git reads them, which is the order in which `gc.<pattern>` sections are tried.
+const SOURCES: &[config::Source] = &[
+    config::Source::System,
+    config::Source::User,
+    config::Source::Global,
+    config::Source::Repository,
+    config::Source::Worktree,
+    config::Source::Env,
+    config::Source::Cli,
+];
+
+/// Reflogs
+impl crate::Repository {
+    /// Return the options to expire the reflog of the reference `full_name` with, as configured by `gc.reflogExpire` and
+    /// `gc.reflogExpireUnreachable` relative to `now`.
+    ///
+    /// The values of the first `gc.<pattern>` section whose pattern matches `full_name` take precedence, with unset values
+    /// meaning that nothing expires, just like in git. Entries of `refs/stash` never expire unless a pattern matches.
+    /// Dates are either `never`, `now`, `all`, relative like `2.weeks.ago` or `3 days ago`, or absolute like `2021-12-24`.
+    pub fn reflog_expire_options(&self, full_name: &BStr, now: SystemTime) -> Result<expire::Options, Error> {
+        let now = now.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+        let parse = |key: &str, value: &[u8]| {
+            parse_date(value.as_bstr(), now).ok_or_else(|| Error::InvalidDate {
+                key: key.into(),
+                value: value.into(),
+            })
+        };
+
+        match matching_pattern(&self.config, full_name) {
+            Some(Pattern {
+                pattern,
+                expire,
+                expire_unreachable,
+            }) => {
+                let parse_value = |key: &str, value: Option<Vec<u8>>| match value {
+                    Some(value) => parse(&format!("gc.{}.{}", pattern, key), &value),
+                    None => Ok(None),
+                };
+                return Ok(expire::Options {
+                    expire: parse_value("reflogExpire", expire)?,
+                    expire_unreachable: parse_value("reflogExpireUnreachable", expire_unreachable)?,
+                    ..Default::default()
+                });
+            }
+            None if full_name == "refs/stash" => return Ok(expire::Options::default()),
+            None => {}
+        }
+
+        let config = self.config();
+        let expire = match config.string("gc.reflogExpire") {
+            Some(value) => parse("gc.reflogExpire", value.as_bytes())?,
+            None => Some(to_time(now.saturating_sub(DEFAULT_EXPIRE_DAYS * DAY))),
+        };
+        let expire_unreachable = match config.string("gc.reflogExpireUnreachable") {
+            Some(value) => parse("gc.reflogExpireUnreachable", value.as_bytes())?,
+            None => Some(to_time(now.saturating_sub(DEFAULT_EXPIRE_UNREACHABLE_DAYS * DAY))),
+        };
+        Ok(expire::Options {
+            expire,
+            expire_unreachable,
+            ..Default::default()
+        })
+    }
+
+    /// Remove entries from the reflog of the reference `full_name` according to `options`, typically obtained with
+    /// [`reflog_expire_options()`][crate::Repository::reflog_expire_options()], using the object database for
+    /// reachability checks.
+    ///
+    /// Returns `Ok(None)` if there is no reflog. See [`git_ref::file::Store::reflog_expire()`] for details.
+    pub fn reflog_expire(
+        &self,
+        full_name: FullNameRef<'_>,
+        options: expire::Options,
+        lock_fail_mode: git_lock::acquire::Fail,
+    ) -> Result<Option<expire::Outcome>, Error> {
+        let objects: crate::OdbHandle = self.objects.to_handle().into();
+        Ok(self
+            .refs
+            .reflog_expire(full_name, options, lock_fail_mode, |oid, buf| {
+                objects
+                    .try_find(oid, buf)
+                    .map(|obj| obj.map(|obj| (obj.kind, obj.data)))
+            })?)
+    }
+}
+
+/// The values of a `gc.<pattern>` section.
+struct Pattern {
+    pattern: String,
+    expire: Option<Vec<u8>>,
+    expire_unreachable: Option<Vec<u8>>,
+}
+
+/// Return the values of the first `gc.<pattern>` section whose pattern matches `full_name`, merged with those of all
+/// other sections with the same pattern.
+fn matching_pattern(config: &git_config::fs::Config<'_>, full_name: &BStr) -> Option<Pattern> {
+    let mut patterns = Vec::<Pattern>::new();
+    for config in SOURCES.iter().filter_map(|source| config.get_config(*source)) {
+        for (header, body) in config.sections_by_name_with_header("gc") {
+            let name = match header.subsection_name.as_deref() {
+                Some(name) => name,
+                None => continue,
+            };
+            let idx = match patterns.iter().position(|p| p.pattern == name) {
+                Some(idx) => idx,
+                None => {
+                    patterns.push(Pattern {
+                        pattern: name.to_owned(),
+                        expire: None,
+                        expire_unreachable: None,
+                    });
+                    patterns.len() - 1
+                }
+            };
+            for key in body.keys() {
+                let value = || body.value(key).map(|v| v.into_owned());
+                match key.0.to_ascii_lowercase().as_str() {
+                    "reflogexpire" => patterns[idx].expire = value(),
+                    "reflogexpireunreachable" => patterns[idx].expire_unreachable = value(),
+                    _ => {}
+                }
+            }
+        }
+    }
+    patterns
+        .into_iter()
+        .find(|p| git_glob::wildmatch(p.pattern.as_str().into(), full_name, git_glob::wildmatch::Mode::empty()))
+}
+
+fn to_time(seconds: u64) -> u32 {
+    seconds.min(u32::MAX as u64) as u32
+}
+
+/// Parse an expiry date relative to `now` in seconds since the unix epoch, with `None` meaning that nothing expires.
+fn parse_date(value: &BStr, now: u64) -> Option<Option<u32>> {
+    let value = value.to_str().ok()?.trim().to_ascii_lowercase();
+    match value.as_str() {
+        "never" | "false" => return Some(None),
+        "now" => return Some(Some(to_time(now))),
+        "all" => return Some(Some(u32::MAX)),
+        _ => {}
+    }
+    if let Some(time) = parse_iso_date(&value) {
+        return Some(Some(to_time(time)));
+    }
+
+    let mut tokens = value
+        .split(|c: char| c == '.' || c.is_whitespace())
+        .filter(|token| !token.is_empty());
+    let amount: u64 = tokens.next()?.parse().ok()?;
+    let unit = match tokens.next()?.trim_end_matches('s') {
+        "second" => 1,
+        "minute" => 60,
+        "hour" => 60 * 60,
+        "day" => DAY,
+        "week" => 7 * DAY,
+        "month" => 30 * DAY,
+        "year" => 365 * DAY,
+        _ => return None,
+    };
+    match (tokens.next(), tokens.next()) {
+        (None, None) | (Some("ago"), None) => {}
+        _ => return None,
+    }
+    Some(Some(to_time(now.saturating_sub(amount.saturating_mul(unit)))))
+}
+
+/// Parse `YYYY-MM-DD` into seconds since the unix epoch at midnight UTC.
+fn parse_iso_date(value: &str) -> Option<u64> {
+    let mut parts = value.splitn(3, '-');
+    let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+    if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return None;
+    }
+    let (year, month, day): (i64, i64, i64) = (year.parse().ok()?, month.parse().ok()?, day.parse().ok()?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    // Days since the epoch in the proleptic gregorian calendar, see http://howardhinnant.github.io/date_algorithms.html
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    u64::try_from(days).ok().map(|days| days * DAY)
+}
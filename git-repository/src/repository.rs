@@ -34,20 +34,26 @@ mod from_path {
 
 ///
 pub mod open {
-    use std::{borrow::Cow, path::PathBuf};
+    use std::path::PathBuf;
 
     use git_config::{
-        file::GitConfig,
-        values::{Boolean, Integer},
+        fs::{ConfigBuilder, ConfigSource},
+        keys,
     };
     use git_features::threading::OwnShared;
 
     use crate::Repository;
 
     /// The options used in [`Repository::open_opts
-    #[derive(Default)]
+    #[derive(Default, Clone)]
     pub struct Options {
         object_store_slots: git_odb::store::init::Slots,
+        object_dir: Option<PathBuf>,
+        alternate_object_dirs: Vec<PathBuf>,
+        work_tree: Option<PathBuf>,
+        /// The work tree to use for non-bare repositories if neither `work_tree` nor `core.worktree` are set.
+        pub(crate) default_work_tree: Option<PathBuf>,
+        config: ConfigBuilder,
     }
 
     impl Options {
@@ -58,6 +64,51 @@ pub mod open {
             self
         }
 
+        /// Use the objects in `path` instead of those in the `objects` directory of the repository.
+        pub fn object_dir(mut self, path: impl Into<PathBuf>) -> Self {
+            self.object_dir = Some(path.into());
+            self
+        }
+
+        /// Read objects from `dirs` as well, as if they were listed in the `info/alternates` file of the object database.
+        pub fn alternate_object_dirs(mut self, dirs: impl IntoIterator<Item = PathBuf>) -> Self {
+            self.alternate_object_dirs = dirs.into_iter().collect();
+            self
+        }
+
+        /// Use `path` as work tree instead of the one that would be inferred from the location of the repository.
+        pub fn work_tree(mut self, path: impl Into<PathBuf>) -> Self {
+            self.work_tree = Some(path.into());
+            self
+        }
+
+        /// Use `config` to control which configuration files are read in addition to the ones of the repository, whose
+        /// locations are always set by us, and which overrides to apply.
+        ///
+        /// By default, the system and global configuration files are read, but not the environment.
+        pub fn config(mut self, config: ConfigBuilder) -> Self {
+            self.config = config;
+            self
+        }
+
+        /// Override our values with those of the `GIT_OBJECT_DIRECTORY`, `GIT_ALTERNATE_OBJECT_DIRECTORIES` and `GIT_WORK_TREE`
+        /// environment variables, if set, and read configuration from the environment as well.
+        ///
+        /// The environment isn't read unless this method is called.
+        pub fn apply_environment(mut self) -> Self {
+            self.config.load_environment_entries(true);
+            if let Some(path) = std::env::var_os("GIT_OBJECT_DIRECTORY") {
+                self.object_dir = Some(path.into());
+            }
+            if let Some(dirs) = std::env::var_os("GIT_ALTERNATE_OBJECT_DIRECTORIES") {
+                self.alternate_object_dirs = std::env::split_paths(&dirs).collect();
+            }
+            if let Some(path) = std::env::var_os("GIT_WORK_TREE") {
+                self.work_tree = Some(path.into());
+            }
+            self
+        }
+
         /// Open a repository at `path` with the options set so far.
         pub fn open(self, path: impl Into<std::path::PathBuf>) -> Result<Repository, Error> {
             Repository::open_opts(path, self)
@@ -69,13 +120,23 @@ pub mod open {
     #[allow(missing_docs)]
     pub enum Error {
         #[error(transparent)]
-        Config(#[from] git_config::parser::ParserOrIoError<'static>),
+        Config(#[from] git_config::fs::Error),
         #[error(transparent)]
         NotARepository(#[from] crate::path::is::Error),
         #[error(transparent)]
         ObjectStoreInitialization(#[from] std::io::Error),
-        #[error("Cannot handle objects formatted as {:?}", .name)]
-        UnsupportedObjectFormat { name: crate::bstr::BString },
+        #[error(transparent)]
+        Extensions(#[from] crate::extensions::Error),
+        #[error(transparent)]
+        ConfigValue(#[from] git_config::keys::Error),
+        #[error(transparent)]
+        ConfigPath(#[from] crate::config::path::Error),
+        #[error("The reftable stack of the repository could not be loaded")]
+        Reftable(#[from] git_ref::store::init::Error),
+        #[error("Could not read the '.git' file at '{}'", .path.display())]
+        InvalidGitDirFile { source: std::io::Error, path: PathBuf },
+        #[error("Could not read the 'commondir' file in '{}'", .git_dir.display())]
+        InvalidCommonDir { source: std::io::Error, git_dir: PathBuf },
     }
 
     impl Repository {
@@ -85,82 +146,131 @@ pub mod open {
         }
 
         /// Open a git repository at the given `path`, possibly expanding it to `path/.git` if `path` is a work tree dir.
+        ///
+        /// If `path/.git` is a file as in linked work trees, the git directory it points to is opened instead.
         fn open_opts(path: impl Into<std::path::PathBuf>, options: Options) -> Result<Self, Error> {
             let path = path.into();
-            let (path, kind) = match crate::path::is::git(&path) {
-                Ok(kind) => (path, kind),
+            let repo_path = match crate::path::is::git(&path) {
+                Ok(kind) => crate::Path::from_dot_git_dir(path, kind),
                 Err(_) => {
-                    let git_dir = path.join(".git");
-                    crate::path::is::git(&git_dir).map(|kind| (git_dir, kind))?
+                    let dot_git = path.join(".git");
+                    if dot_git.is_file() {
+                        let git_dir =
+                            crate::path::from_gitdir_file(&dot_git).map_err(|err| Error::InvalidGitDirFile {
+                                source: err,
+                                path: dot_git,
+                            })?;
+                        crate::path::is::git(&git_dir)?;
+                        crate::Path::LinkedWorkTree {
+                            work_dir: path,
+                            git_dir,
+                        }
+                    } else {
+                        let kind = crate::path::is::git(&dot_git)?;
+                        crate::Path::from_dot_git_dir(dot_git, kind)
+                    }
                 }
             };
-            let (git_dir, worktree_dir) =
-                crate::Path::from_dot_git_dir(path, kind).into_repository_and_work_tree_directories();
+            let (git_dir, worktree_dir) = repo_path.into_repository_and_work_tree_directories();
             Repository::open_from_paths(git_dir, worktree_dir, options)
         }
 
-        pub(in crate::repository) fn open_from_paths(
+        pub(crate) fn open_from_paths(
             git_dir: PathBuf,
             mut worktree_dir: Option<PathBuf>,
-            Options { object_store_slots }: Options,
+            Options {
+                object_store_slots,
+                object_dir,
+                alternate_object_dirs,
+                work_tree,
+                default_work_tree,
+                config: mut config_builder,
+            }: Options,
         ) -> Result<Self, Error> {
-            let config = git_config::file::GitConfig::open(git_dir.join("config"))?;
-            if worktree_dir.is_none() {
-                let is_bare = config_bool(&config, "core.bare", false);
-                if !is_bare {
-                    worktree_dir = Some(git_dir.parent().expect("parent is always available").to_owned());
-                }
+            let common_dir = crate::path::common_dir(&git_dir).map_err(|err| Error::InvalidCommonDir {
+                source: err,
+                git_dir: git_dir.clone(),
+            })?;
+            let common_dir_resolved = common_dir.as_deref().unwrap_or(&git_dir);
+            config_builder
+                .repository_config_path(Some(common_dir_resolved.join("config")))
+                .worktree_config_path(None)
+                .git_dir(Some(git_dir.clone()))
+                .branch_name(head_ref_name(&git_dir));
+            let mut config = config_builder.try_build()?;
+            let extensions = crate::extensions::Extensions::from_config(
+                config
+                    .get_config(ConfigSource::Repository)
+                    .expect("the repository configuration must exist"),
+            )?;
+            if extensions.worktree_config {
+                config = config_builder
+                    .worktree_config_path(Some(git_dir.join("config.worktree")))
+                    .try_build()?;
             }
-            let use_multi_pack_index = config_bool(&config, "core.multiPackIndex", true);
-            let repo_format_version = config
-                .value::<Integer>("core", None, "repositoryFormatVersion")
-                .map_or(0, |v| v.value);
-            let object_hash = if repo_format_version == 1 {
-                if let Ok(format) = config.value::<Cow<'_, [u8]>>("extensions", None, "objectFormat") {
-                    match format.as_ref() {
-                        b"sha1" => git_hash::Kind::Sha1,
-                        _ => {
-                            return Err(Error::UnsupportedObjectFormat {
-                                name: format.to_vec().into(),
-                            })
-                        }
+            if work_tree.is_some() {
+                worktree_dir = work_tree;
+            } else {
+                let is_bare = config.get(&keys::core::BARE)?.unwrap_or_default();
+                let configured_work_tree = crate::config::Snapshot { config: &config }
+                    .path(keys::core::WORKTREE.name())?
+                    .map(|path| git_dir.join(path));
+                if let Some(dir) = configured_work_tree.or(default_work_tree) {
+                    if !is_bare {
+                        worktree_dir = Some(dir);
                     }
-                } else {
-                    git_hash::Kind::Sha1
+                // The work tree of a linked work tree can't be derived from its private git dir, it must be known already.
+                } else if worktree_dir.is_none() && common_dir.is_none() && !is_bare {
+                    worktree_dir = Some(git_dir.parent().expect("parent is always available").to_owned());
                 }
+            }
+            let use_multi_pack_index = config.get(&keys::core::MULTI_PACK_INDEX)?.unwrap_or(true);
+            let object_hash = extensions.object_hash;
+            let write_reflog = if worktree_dir.is_none() {
+                git_ref::store::WriteReflog::Disable
             } else {
-                git_hash::Kind::Sha1
+                git_ref::store::WriteReflog::Normal
+            };
+            // All work trees share the tables in the common directory.
+            let reftable = match extensions.ref_storage {
+                git_ref::store::Format::Files => None,
+                format @ git_ref::store::Format::Reftable => Some(git_ref::Store::at_with_format(
+                    common_dir_resolved,
+                    format,
+                    write_reflog,
+                    object_hash,
+                )?),
             };
 
             Ok(crate::Repository {
                 objects: OwnShared::new(git_odb::Store::at_opts(
-                    git_dir.join("objects"),
+                    object_dir.unwrap_or_else(|| common_dir_resolved.join("objects")),
                     git_odb::store::init::Options {
                         slots: object_store_slots,
                         object_hash,
                         use_multi_pack_index,
+                        extra_alternates: alternate_object_dirs,
                     },
                 )?),
-                refs: crate::RefStore::at(
-                    git_dir,
-                    if worktree_dir.is_none() {
-                        git_ref::store::WriteReflog::Disable
-                    } else {
-                        git_ref::store::WriteReflog::Normal
-                    },
-                    object_hash,
-                ),
+                refs: match common_dir {
+                    Some(common_dir) => {
+                        crate::RefStore::for_linked_worktree(git_dir, common_dir, write_reflog, object_hash)
+                    }
+                    None => crate::RefStore::at(git_dir, write_reflog, object_hash),
+                },
+                reftable,
                 work_tree: worktree_dir,
                 object_hash,
+                extensions,
+                config: OwnShared::new(config),
             })
         }
     }
 
-    fn config_bool(config: &GitConfig<'_>, key: &str, default: bool) -> bool {
-        let (section, key) = key.split_once(".").expect("valid section.key format");
-        config
-            .value::<Boolean<'_>>(section, None, key)
-            .map_or(default, |b| matches!(b, Boolean::True(_)))
+    /// Read the name of the reference `HEAD` points to, if it is symbolic, without the ref store which isn't available yet.
+    fn head_ref_name(git_dir: &std::path::Path) -> Option<String> {
+        let head = std::fs::read_to_string(git_dir.join("HEAD")).ok()?;
+        head.strip_prefix("ref: ").map(|name| name.trim_end().to_owned())
     }
 }
 
@@ -193,6 +303,8 @@ pub mod init {
 }
 
 mod location {
+    use git_config::keys;
+
     use crate::Repository;
 
     impl Repository {
@@ -217,12 +329,100 @@ mod location {
         pub fn objects_dir(&self) -> &std::path::Path {
             self.objects.path()
         }
+
+        /// Return the path to the directory shared by all work trees, containing objects, shared references and configuration.
+        ///
+        /// It's the same as [`git_dir()`][Repository::git_dir()] unless this is a linked work tree.
+        pub fn common_dir(&self) -> &std::path::Path {
+            self.refs.common_dir_resolved()
+        }
+
+        /// Return the directory containing hooks, which is `core.hooksPath` if set, or the `hooks` directory in the
+        /// [common directory][Repository::common_dir()].
+        ///
+        /// A relative `core.hooksPath` is relative to the work tree, or to the git directory in bare repositories.
+        pub fn hooks_dir(&self) -> Result<std::path::PathBuf, crate::config::path::Error> {
+            hooks_dir(self.config(), self.workdir(), &self.refs)
+        }
+
+        /// Return the path to the file with additional exclude patterns, which is `core.excludesFile` if set,
+        /// or `$XDG_CONFIG_HOME/git/ignore` otherwise. It may not exist.
+        ///
+        /// `$XDG_CONFIG_HOME` is only used if the environment [was applied][crate::open::Options::apply_environment()]
+        /// when opening the repository, otherwise it is `~/.config`.
+        pub fn excludes_file(&self) -> Result<Option<std::path::PathBuf>, crate::config::path::Error> {
+            Ok(match self.config().path(keys::core::EXCLUDES_FILE.name())? {
+                Some(path) => Some(path.into_owned()),
+                None => self.config.xdg_config_path("ignore"),
+            })
+        }
+    }
+
+    /// The implementation of [`Repository::hooks_dir()`] shared with [`easy::Handle`][crate::easy::Handle].
+    pub(crate) fn hooks_dir(
+        config: crate::config::Snapshot<'_>,
+        workdir: Option<&std::path::Path>,
+        refs: &crate::RefStore,
+    ) -> Result<std::path::PathBuf, crate::config::path::Error> {
+        Ok(match config.path(keys::core::HOOKS_PATH.name())? {
+            Some(path) => workdir.unwrap_or_else(|| refs.base()).join(path),
+            None => refs.common_dir_resolved().join("hooks"),
+        })
+    }
+}
+
+pub(crate) use location::hooks_dir;
+
+mod index {
+    use std::convert::TryInto;
+
+    use git_config::keys;
+
+    use crate::Repository;
+
+    impl Repository {
+        /// Return the options to use when writing the index, which is written as split index if `core.splitIndex` is set.
+        pub fn index_write_options(&self) -> Result<git_index::write::Options, keys::Error> {
+            let config = self.config();
+            let split_index = if config.get(&keys::core::SPLIT_INDEX)?.unwrap_or_default() {
+                Some(git_index::write::SplitIndex {
+                    max_percent_change: config
+                        .get(&keys::split_index::MAX_PERCENT_CHANGE)?
+                        .unwrap_or(20)
+                        .try_into()
+                        .expect("validated to be within 0 and 100"),
+                })
+            } else {
+                None
+            };
+            Ok(git_index::write::Options {
+                object_hash: self.object_hash,
+                split_index,
+                ..Default::default()
+            })
+        }
+    }
+}
+
+mod format {
+    use crate::{extensions::Extensions, Repository};
+
+    impl Repository {
+        /// Return the repository format version and the extensions it uses, which are all understood by us.
+        pub fn extensions(&self) -> &Extensions {
+            &self.extensions
+        }
+
+        /// Return the configuration of the repository, merged from all of its sources, for typed access to its values.
+        pub fn config(&self) -> crate::config::Snapshot<'_> {
+            crate::config::Snapshot { config: &self.config }
+        }
     }
 }
 
 ///
 pub mod discover {
-    use std::{convert::TryInto, path::Path};
+    use std::path::{Path, PathBuf};
 
     use crate::{path::discover, Repository};
 
@@ -234,13 +434,54 @@ pub mod discover {
         Discover(#[from] discover::existing::Error),
         #[error(transparent)]
         Open(#[from] crate::open::Error),
+        #[error("The current directory could not be obtained to serve as work tree")]
+        CurrentDir(#[source] std::io::Error),
     }
 
     impl Repository {
         /// Try to open a git repository in `directory` and search upwards through its parents until one is found.
         pub fn discover(directory: impl AsRef<Path>) -> Result<Self, Error> {
-            let path = discover::existing(directory)?;
-            Ok(path.try_into()?)
+            Self::discover_opts(directory, Default::default(), Default::default())
+        }
+
+        /// Try to open a git repository in `directory` and search upwards through its parents until one is found,
+        /// as controlled by `options`, and open it with `open_options`.
+        pub fn discover_opts(
+            directory: impl AsRef<Path>,
+            options: discover::Options,
+            open_options: crate::open::Options,
+        ) -> Result<Self, Error> {
+            let path = discover::existing_opts(directory, options)?;
+            let (git_dir, worktree_dir) = path.into_repository_and_work_tree_directories();
+            Ok(Repository::open_from_paths(git_dir, worktree_dir, open_options)?)
+        }
+
+        /// Like [`discover_opts()`][Repository::discover_opts()], but apply overrides from the environment like git does.
+        ///
+        /// If `GIT_DIR` is set, the repository it points to is opened without any search, with its work tree being
+        /// `core.worktree` or the current directory unless it's bare, as long as it [can be trusted][discover::Options::check_ownership]. Otherwise `GIT_CEILING_DIRECTORIES` and `GIT_DISCOVERY_ACROSS_FILESYSTEM`
+        /// [affect the search][discover::Options::apply_environment()].
+        /// Either way, `GIT_WORK_TREE` and the object database variables are [respected][crate::open::Options::apply_environment()].
+        pub fn discover_with_environment_overrides_opts(
+            directory: impl AsRef<Path>,
+            options: discover::Options,
+            open_options: crate::open::Options,
+        ) -> Result<Self, Error> {
+            let mut open_options = open_options.apply_environment();
+            let options = options.apply_environment();
+            match std::env::var_os("GIT_DIR") {
+                Some(git_dir) => {
+                    let git_dir = PathBuf::from(git_dir);
+                    let kind = crate::path::is::git(&git_dir).map_err(crate::open::Error::from)?;
+                    let path = crate::Path::from_dot_git_dir(git_dir, kind);
+                    discover::ensure_trusted(&path, &options)?;
+                    // Like git, the work tree isn't inferred from the location of the git dir.
+                    let (git_dir, _inferred_work_tree) = path.into_repository_and_work_tree_directories();
+                    open_options.default_work_tree = Some(std::env::current_dir().map_err(Error::CurrentDir)?);
+                    Ok(Repository::open_from_paths(git_dir, None, open_options)?)
+                }
+                None => Self::discover_opts(directory, options, open_options),
+            }
         }
     }
 }
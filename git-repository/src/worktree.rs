@@ -0,0 +1,356 @@
+//! Create, list, lock, move and prune linked work trees, whose private git directories are located in
+//! `$GIT_COMMON_DIR/worktrees/<id>`.
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    bstr::{BStr, BString, ByteSlice},
+    Repository,
+};
+
+/// A handle to a linked work tree, pointing to its private git directory.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Proxy {
+    git_dir: PathBuf,
+}
+
+impl Proxy {
+    /// Return the id of the work tree, which is the name of its private git directory.
+    pub fn id(&self) -> &OsStr {
+        self.git_dir
+            .file_name()
+            .expect("worktree directories always have a name")
+    }
+
+    /// Return the private git directory of the work tree, containing its `HEAD` and `index` among others.
+    pub fn git_dir(&self) -> &Path {
+        &self.git_dir
+    }
+
+    /// Return the directory with the checked out files as recorded in the private git directory, which may not exist anymore.
+    pub fn base(&self) -> std::io::Result<PathBuf> {
+        let dot_git = crate::path::from_plain_file(&self.git_dir.join("gitdir"))?;
+        Ok(dot_git.parent().map(ToOwned::to_owned).unwrap_or(dot_git))
+    }
+
+    /// Return true if the work tree is locked, which prevents it from being moved or pruned.
+    pub fn is_locked(&self) -> bool {
+        self.git_dir.join("locked").is_file()
+    }
+
+    /// Return the possibly empty reason for the work tree to be locked, or `None` if it isn't locked.
+    pub fn lock_reason(&self) -> Option<BString> {
+        std::fs::read(self.git_dir.join("locked"))
+            .ok()
+            .map(|reason| reason.trim_end().into())
+    }
+
+    /// Lock the work tree with the given, possibly empty, `reason` to prevent it from being moved or pruned,
+    /// for instance because it's on a removable device.
+    pub fn lock(&self, reason: &BStr) -> Result<(), lock::Error> {
+        use std::io::Write;
+        match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(self.git_dir.join("locked"))
+        {
+            Ok(mut file) => Ok(file.write_all(reason)?),
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => Err(lock::Error::AlreadyLocked {
+                reason: self.lock_reason().unwrap_or_default(),
+            }),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Remove the lock from the work tree, returning true if it was locked.
+    pub fn unlock(&self) -> std::io::Result<bool> {
+        match std::fs::remove_file(self.git_dir.join("locked")) {
+            Ok(()) => Ok(true),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Move the directory with the checked out files to `destination`, which must not exist yet, and update the
+    /// private git directory accordingly.
+    pub fn move_to(&self, destination: impl AsRef<Path>) -> Result<(), move_to::Error> {
+        if let Some(reason) = self.lock_reason() {
+            return Err(move_to::Error::Locked { reason });
+        }
+        let destination = absolute(destination.as_ref())?;
+        if destination.exists() {
+            return Err(move_to::Error::DestinationExists { path: destination });
+        }
+        std::fs::rename(self.base()?, &destination)?;
+        write_links(&absolute(&self.git_dir)?, &destination)?;
+        Ok(())
+    }
+
+    /// Open the repository of this work tree.
+    pub fn into_repo(self) -> Result<Repository, crate::open::Error> {
+        Repository::open(self.git_dir)
+    }
+}
+
+///
+pub mod lock {
+    use crate::bstr::BString;
+
+    /// The error returned by [`Proxy::lock()`][super::Proxy::lock()].
+    #[derive(Debug, thiserror::Error)]
+    #[allow(missing_docs)]
+    pub enum Error {
+        #[error("The work tree is already locked with reason {:?}", .reason)]
+        AlreadyLocked { reason: BString },
+        #[error(transparent)]
+        Io(#[from] std::io::Error),
+    }
+}
+
+///
+pub mod move_to {
+    use std::path::PathBuf;
+
+    use crate::bstr::BString;
+
+    /// The error returned by [`Proxy::move_to()`][super::Proxy::move_to()].
+    #[derive(Debug, thiserror::Error)]
+    #[allow(missing_docs)]
+    pub enum Error {
+        #[error("Locked work trees cannot be moved, the lock reason is {:?}", .reason)]
+        Locked { reason: BString },
+        #[error("Refusing to move the work tree to the existing path '{}'", .path.display())]
+        DestinationExists { path: PathBuf },
+        #[error(transparent)]
+        Io(#[from] std::io::Error),
+    }
+}
+
+///
+pub mod add {
+    use std::path::PathBuf;
+
+    use crate::bstr::BString;
+
+    /// What the `HEAD` of a new work tree should point to.
+    #[derive(Debug, Clone, Eq, PartialEq)]
+    pub enum Head {
+        /// Check out the branch with the given full name, like `refs/heads/main`, which must not be checked out elsewhere.
+        Branch(git_ref::FullName),
+        /// Detach `HEAD` at the given commit.
+        Detached(git_hash::ObjectId),
+    }
+
+    /// The error returned by [`Repository::worktree_add()`][crate::Repository::worktree_add()].
+    #[derive(Debug, thiserror::Error)]
+    #[allow(missing_docs)]
+    pub enum Error {
+        #[error("Refusing to add a work tree in the non-empty directory '{}'", .path.display())]
+        DirectoryNotEmpty { path: PathBuf },
+        #[error("The branch '{}' is already checked out in the work tree with git directory '{}'", .name, .git_dir.display())]
+        BranchCheckedOut { name: BString, git_dir: PathBuf },
+        #[error(transparent)]
+        Io(#[from] std::io::Error),
+    }
+}
+
+///
+pub mod prune {
+    use std::{ffi::OsString, time::SystemTime};
+
+    /// Options for use in [`Repository::worktrees_prune()`][crate::Repository::worktrees_prune()].
+    #[derive(Debug, Default, Clone, Copy)]
+    pub struct Options {
+        /// If true, only report which work trees would be pruned without removing anything.
+        pub dry_run: bool,
+        /// If set, only prune work trees whose directory went missing if their administrative files weren't touched since then.
+        pub expire: Option<SystemTime>,
+    }
+
+    /// The reason for a work tree to be pruned.
+    #[derive(Debug, Clone, Copy, Eq, PartialEq)]
+    pub enum Reason {
+        /// The entry in `$GIT_COMMON_DIR/worktrees` isn't a directory.
+        NotADirectory,
+        /// The `gitdir` file pointing to the work tree doesn't exist.
+        MissingGitDirFile,
+        /// The `gitdir` file couldn't be read or is empty.
+        InvalidGitDirFile,
+        /// The location the `gitdir` file points to doesn't exist anymore.
+        WorkTreeMissing,
+    }
+
+    /// A work tree that was pruned, or would have been pruned in a dry-run.
+    #[derive(Debug, Clone, Eq, PartialEq)]
+    pub struct Pruned {
+        /// The id of the work tree.
+        pub id: OsString,
+        /// The reason for it to be pruned.
+        pub reason: Reason,
+    }
+}
+
+impl Repository {
+    /// Return all linked work trees of this repository, sorted by id, which excludes the main work tree.
+    pub fn worktrees(&self) -> std::io::Result<Vec<Proxy>> {
+        let mut worktrees = Vec::new();
+        let dir = match std::fs::read_dir(self.common_dir().join("worktrees")) {
+            Ok(dir) => dir,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(worktrees),
+            Err(err) => return Err(err),
+        };
+        for entry in dir {
+            let git_dir = entry?.path();
+            if git_dir.is_dir() {
+                worktrees.push(Proxy { git_dir });
+            }
+        }
+        worktrees.sort();
+        Ok(worktrees)
+    }
+
+    /// Add a new linked work tree at `path`, which must be an empty or non-existing directory, with its `HEAD` pointing to `head`.
+    ///
+    /// Its id is derived from the last component of `path`, with a number appended if it is already taken.
+    ///
+    /// Note that neither files are checked out nor is an index written, which is left to the caller, for instance
+    /// by using the `git-worktree` crate with the tree of `head`.
+    pub fn worktree_add(&self, path: impl AsRef<Path>, head: add::Head) -> Result<Proxy, add::Error> {
+        let path = absolute(path.as_ref())?;
+        if path.is_dir() && std::fs::read_dir(&path)?.next().is_some() {
+            return Err(add::Error::DirectoryNotEmpty { path });
+        }
+        if let add::Head::Branch(name) = &head {
+            let git_dirs = std::iter::once(self.common_dir().to_owned())
+                .chain(self.worktrees()?.into_iter().map(|worktree| worktree.git_dir));
+            for git_dir in git_dirs {
+                let store = crate::RefStore::at(&git_dir, git_ref::store::WriteReflog::Disable, self.object_hash);
+                let checked_out = store
+                    .try_find_loose("HEAD")
+                    .ok()
+                    .flatten()
+                    .map_or(false, |head| head.target.as_name() == Some(name.as_bstr()));
+                if checked_out {
+                    return Err(add::Error::BranchCheckedOut {
+                        name: name.as_bstr().into(),
+                        git_dir,
+                    });
+                }
+            }
+        }
+
+        let worktrees_dir = absolute(&self.common_dir().join("worktrees"))?;
+        std::fs::create_dir_all(&worktrees_dir)?;
+        let name = path
+            .file_name()
+            .map_or_else(|| "worktree".into(), |name| name.to_string_lossy().into_owned());
+        let mut git_dir = worktrees_dir.join(&name);
+        let mut suffix = 1;
+        loop {
+            match std::fs::create_dir(&git_dir) {
+                Ok(()) => break,
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+                    git_dir = worktrees_dir.join(format!("{}{}", name, suffix));
+                    suffix += 1;
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        let worktree = Proxy { git_dir };
+        let res = (|| -> std::io::Result<()> {
+            // Like git, keep the work tree locked while it's incomplete to prevent it from being pruned.
+            std::fs::write(worktree.git_dir.join("locked"), "initializing")?;
+            std::fs::write(worktree.git_dir.join("commondir"), "../..\n")?;
+            std::fs::write(
+                worktree.git_dir.join("HEAD"),
+                match &head {
+                    add::Head::Branch(name) => format!("ref: {}\n", name.as_bstr()),
+                    add::Head::Detached(id) => format!("{}\n", id),
+                },
+            )?;
+            std::fs::create_dir_all(&path)?;
+            write_links(&worktree.git_dir, &path)?;
+            std::fs::remove_file(worktree.git_dir.join("locked"))
+        })();
+        match res {
+            Ok(()) => Ok(worktree),
+            Err(err) => {
+                std::fs::remove_dir_all(&worktree.git_dir).ok();
+                Err(err.into())
+            }
+        }
+    }
+
+    /// Remove the private git directories of all linked work trees that are invalid or whose checked out files
+    /// don't exist anymore, unless they are locked, and return information about each pruned work tree.
+    pub fn worktrees_prune(&self, options: prune::Options) -> std::io::Result<Vec<prune::Pruned>> {
+        let mut pruned = Vec::new();
+        let dir = match std::fs::read_dir(self.common_dir().join("worktrees")) {
+            Ok(dir) => dir,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(pruned),
+            Err(err) => return Err(err),
+        };
+        for entry in dir {
+            let entry = entry?;
+            let path = entry.path();
+            let reason = if !path.is_dir() {
+                prune::Reason::NotADirectory
+            } else {
+                let worktree = Proxy { git_dir: path.clone() };
+                if worktree.is_locked() {
+                    continue;
+                }
+                let gitdir_file = path.join("gitdir");
+                if !gitdir_file.is_file() {
+                    prune::Reason::MissingGitDirFile
+                } else {
+                    match crate::path::from_plain_file(&gitdir_file) {
+                        Err(_) => prune::Reason::InvalidGitDirFile,
+                        Ok(dot_git) if !dot_git.exists() => {
+                            let expired = match options.expire {
+                                Some(expire) => gitdir_file.metadata()?.modified()? <= expire,
+                                None => true,
+                            };
+                            if !expired {
+                                continue;
+                            }
+                            prune::Reason::WorkTreeMissing
+                        }
+                        Ok(_) => continue,
+                    }
+                }
+            };
+            if !options.dry_run {
+                if path.is_dir() {
+                    std::fs::remove_dir_all(&path)?;
+                } else {
+                    std::fs::remove_file(&path)?;
+                }
+            }
+            pruned.push(prune::Pruned {
+                id: entry.file_name(),
+                reason,
+            });
+        }
+        pruned.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(pruned)
+    }
+}
+
+/// Point the private `git_dir` of a linked work tree to its `.git` file in `work_dir`, and vice versa.
+fn write_links(git_dir: &Path, work_dir: &Path) -> std::io::Result<()> {
+    let dot_git = work_dir.join(".git");
+    std::fs::write(git_dir.join("gitdir"), format!("{}\n", dot_git.display()))?;
+    std::fs::write(dot_git, format!("gitdir: {}\n", git_dir.display()))
+}
+
+fn absolute(path: &Path) -> std::io::Result<PathBuf> {
+    Ok(if path.is_absolute() {
+        path.to_owned()
+    } else {
+        std::env::current_dir()?.join(path)
+    })
+}
@@ -0,0 +1,204 @@
+use std::io::Write;
+
+use git_repository::{config::Source, open};
+
+#[test]
+fn values_are_typed_and_overridden_by_precedence() -> crate::Result {
+    let tmp = tempfile::tempdir()?;
+    let repo = git_repository::init(tmp.path())?;
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(repo.git_dir().join("config"))?
+        .write_all(b"[pack]\n\twindowMemory = 2k\n[remote \"origin.mirror\"]\n\turl = https://example.com\n[user]\n\tname = local\n")?;
+
+    let mut config = git_config::fs::ConfigBuilder::default();
+    config
+        .no_system(true)
+        .no_global(true)
+        .cli_overrides(vec!["user.name=cli", "core.flag"]);
+    let repo = open::Options::default().config(config).open(tmp.path())?;
+    let config = repo.config();
+
+    assert_eq!(config.boolean("core.bare")?, Some(false));
+    assert_eq!(config.source("core.bare"), Some(Source::Repository));
+    assert_eq!(config.boolean("core.flag")?, Some(true), "keys without value are true");
+    assert_eq!(config.integer("pack.windowMemory")?, Some(2048));
+    assert_eq!(
+        config.string("remote.origin.mirror.url").as_deref(),
+        Some("https://example.com".into()),
+        "subsections may contain dots"
+    );
+    assert_eq!(config.string("user.name").as_deref(), Some("cli".into()));
+    assert_eq!(config.source("user.name"), Some(Source::Cli));
+    assert_eq!(config.string("user.email"), None);
+    assert_eq!(config.integer("user.email")?, None);
+    Ok(())
+}
+
+#[test]
+fn invalid_values_fail_with_their_source_instead_of_falling_back() -> crate::Result {
+    let tmp = tempfile::tempdir()?;
+    let repo = git_repository::init(tmp.path())?;
+    let config_path = repo.git_dir().join("config");
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(&config_path)?
+        .write_all(b"[pack]\n\twindowMemory = lots\n\tthreads = 1\n")?;
+
+    let mut config = git_config::fs::ConfigBuilder::default();
+    config
+        .no_system(true)
+        .no_global(true)
+        .cli_overrides(vec!["pack.threads=many", "core.flag=sometimes"]);
+    let repo = open::Options::default().config(config).open(tmp.path())?;
+    let config = repo.config();
+
+    match config.integer("pack.windowMemory") {
+        Err(git_repository::config::value::Error::Invalid {
+            key,
+            value,
+            config_source,
+            path,
+        }) => {
+            assert_eq!(key, "pack.windowMemory");
+            assert_eq!(value, "lots");
+            assert_eq!(config_source, Source::Repository);
+            assert_eq!(path.as_deref(), Some(config_path.as_path()));
+        }
+        res => panic!("expected an invalid value, got {:?}", res),
+    }
+    match config.integer("pack.threads") {
+        Err(git_repository::config::value::Error::Invalid {
+            config_source, path, ..
+        }) => {
+            assert_eq!(
+                config_source,
+                Source::Cli,
+                "the valid repository value isn't used instead"
+            );
+            assert_eq!(path, None);
+        }
+        res => panic!("expected an invalid value, got {:?}", res),
+    }
+    assert!(config.boolean("core.flag").is_err());
+    Ok(())
+}
+
+#[test]
+fn includes_are_resolved_with_repository_context() -> crate::Result {
+    let tmp = tempfile::tempdir()?;
+    let repo = git_repository::init(tmp.path())?;
+    let git_dir = repo.git_dir().to_owned();
+    std::fs::write(git_dir.join("HEAD"), "ref: refs/heads/topic\n")?;
+    std::fs::write(git_dir.join("on-topic"), "[core]\n\ttopic = true\n")?;
+    std::fs::write(git_dir.join("in-repo"), "[core]\n\trepo = true\n")?;
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(git_dir.join("config"))?
+        .write_all(
+            b"[includeIf \"onbranch:topic\"]\n\tpath = on-topic\n[includeIf \"gitdir:.git\"]\n\tpath = in-repo\n",
+        )?;
+
+    let mut config = git_config::fs::ConfigBuilder::default();
+    config.no_system(true).no_global(true);
+    let repo = open::Options::default().config(config).open(tmp.path())?;
+    let config = repo.config();
+    assert_eq!(config.boolean("core.topic")?, Some(true));
+    assert_eq!(
+        config.source_file("core.topic"),
+        Some(git_dir.join("on-topic").as_path())
+    );
+    assert_eq!(config.boolean("core.repo")?, Some(true));
+    assert_eq!(config.source("core.repo"), Some(Source::Repository));
+    assert_eq!(config.source_file("core.bare"), Some(git_dir.join("config").as_path()));
+    Ok(())
+}
+
+#[test]
+fn paths_are_interpolated_and_name_the_key_on_error() -> crate::Result {
+    let tmp = tempfile::tempdir()?;
+    let repo = git_repository::init(tmp.path())?;
+    assert_eq!(repo.hooks_dir()?, repo.common_dir().join("hooks"));
+
+    let mut config = git_config::fs::ConfigBuilder::default();
+    config.no_system(true).no_global(true).cli_overrides(vec![
+        "core.hooksPath=custom-hooks",
+        "core.excludesFile=/absolute/ignore",
+        "core.broken=~does-not-exist/path",
+    ]);
+    let repo = open::Options::default().config(config).open(tmp.path())?;
+    assert_eq!(repo.hooks_dir()?, tmp.path().join("custom-hooks"));
+    assert_eq!(repo.excludes_file()?, Some("/absolute/ignore".into()));
+
+    let err = repo.config().path("core.broken").unwrap_err();
+    assert!(err.to_string().contains("'core.broken'"));
+    assert_eq!(repo.config().path("core.missing")?, None);
+    Ok(())
+}
+
+#[test]
+fn well_known_keys_are_typed_and_invalid_ones_fail_opening() -> crate::Result {
+    use git_repository::config::keys;
+
+    let tmp = tempfile::tempdir()?;
+    let repo = git_repository::init(tmp.path())?;
+    let config_path = repo.git_dir().join("config");
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(&config_path)?
+        .write_all(b"[fetch]\n\tprune = yes\n")?;
+
+    let mut config = git_config::fs::ConfigBuilder::default();
+    config.no_system(true).no_global(true);
+    let repo = open::Options::default().config(config.clone()).open(tmp.path())?;
+    assert_eq!(repo.config().get(&keys::fetch::PRUNE)?, Some(true));
+    assert_eq!(repo.config().get(&keys::core::BARE)?, Some(false));
+    assert_eq!(repo.config().get(&keys::pack::DEPTH)?, Some(50), "defaults are applied");
+
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(&config_path)?
+        .write_all(b"[core]\n\tbare = sometimes\n")?;
+    match open::Options::default().config(config).open(tmp.path()) {
+        Err(open::Error::ConfigValue(err)) => {
+            assert_eq!(err.key, "core.bare");
+            assert_eq!(err.path.as_deref(), Some(config_path.as_path()));
+            assert!(err.line.is_some());
+        }
+        Ok(_) => panic!("invalid booleans must be rejected"),
+        Err(err) => return Err(err.into()),
+    }
+    Ok(())
+}
+
+#[test]
+fn index_write_options_enable_split_indices_with_core_split_index() -> crate::Result {
+    use git_repository::index::write::SplitIndex;
+
+    let tmp = tempfile::tempdir()?;
+    let repo = git_repository::init(tmp.path())?;
+    assert_eq!(repo.index_write_options()?.split_index, None);
+
+    let open = |overrides: Vec<&str>| {
+        let mut config = git_config::fs::ConfigBuilder::default();
+        config.no_system(true).no_global(true).cli_overrides(overrides);
+        open::Options::default().config(config).open(tmp.path())
+    };
+    assert_eq!(
+        open(vec!["core.splitIndex=true"])?.index_write_options()?.split_index,
+        Some(SplitIndex { max_percent_change: 20 })
+    );
+    assert_eq!(
+        open(vec!["core.splitIndex=true", "splitIndex.maxPercentChange=50"])?
+            .index_write_options()?
+            .split_index,
+        Some(SplitIndex { max_percent_change: 50 })
+    );
+    assert!(
+        open(vec!["core.splitIndex=true", "splitIndex.maxPercentChange=101"])?
+            .index_write_options()
+            .is_err(),
+        "percentages are validated"
+    );
+    Ok(())
+}
@@ -95,6 +95,102 @@ mod existing {
         Ok(())
     }
 
+    #[test]
+    fn ceiling_dirs_are_not_entered() -> crate::Result {
+        let working_dir = repo_path()?.canonicalize()?;
+        let dir = working_dir.join("some/very/deeply/nested/subdir");
+        let options = |ceiling_dirs: Vec<PathBuf>| git_repository::path::discover::Options {
+            ceiling_dirs,
+            ..Default::default()
+        };
+
+        let err =
+            git_repository::path::discover::existing_opts(&dir, options(vec![working_dir.join("some")])).unwrap_err();
+        assert!(matches!(
+            err,
+            git_repository::path::discover::existing::Error::NoGitRepositoryWithinCeiling { ceiling, .. }
+                if ceiling == working_dir.join("some")
+        ));
+
+        for ceiling_dirs in [
+            vec![working_dir.parent().expect("parent").to_owned()],
+            vec![dir.clone(), "relative".into()],
+        ] {
+            let path = git_repository::path::discover::existing_opts(&dir, options(ceiling_dirs))?;
+            assert_eq!(
+                path.as_ref(),
+                working_dir,
+                "directories below the ceiling are searched, and ceilings that are no parent are ignored"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn repositories_owned_by_the_current_user_are_trusted() -> crate::Result {
+        let working_dir = repo_path()?;
+        let options = git_repository::path::discover::Options {
+            safe_directories: vec!["/some/other/repo".into()],
+            ..Default::default()
+        };
+        assert!(
+            options.check_ownership,
+            "ownership is checked by default, like git does"
+        );
+        let path = git_repository::path::discover::existing_opts(&working_dir, options)?;
+        assert_eq!(path.as_ref(), working_dir);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn repositories_owned_by_someone_else_are_untrusted_unless_listed_as_safe() -> crate::Result {
+        let is_root = std::process::Command::new("id").arg("-u").output()?.stdout == b"0\n";
+        if !is_root || std::env::var_os("SUDO_UID").is_some() {
+            eprintln!("Skipping as only root can hand the repository to another user");
+            return Ok(());
+        }
+        let dir = git_testtools::scripted_fixture_repo_writable("make_basic_repo.sh")?;
+        let working_dir = dir.path().canonicalize()?;
+        let status = std::process::Command::new("chown")
+            .arg("-R")
+            .arg("12345")
+            .arg(&working_dir)
+            .status()?;
+        assert!(status.success());
+
+        let err = git_repository::path::discover::existing(&working_dir).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                git_repository::path::discover::existing::Error::UntrustedOwner { path } if path == working_dir
+            ),
+            "the repository isn't owned by the effective user of the process"
+        );
+
+        let options = git_repository::path::discover::Options {
+            safe_directories: vec![working_dir.clone()],
+            ..Default::default()
+        };
+        let path = git_repository::path::discover::existing_opts(&working_dir, options)?;
+        assert_eq!(path.as_ref(), working_dir);
+        Ok(())
+    }
+
+    #[test]
+    fn open_options_are_used_when_discovering() -> crate::Result {
+        let working_dir = repo_path()?;
+        let work_tree = working_dir.join("some");
+        let repo = git_repository::Repository::discover_opts(
+            working_dir.join("some/very"),
+            Default::default(),
+            git_repository::open::Options::default().work_tree(&work_tree),
+        )?;
+        assert_eq!(repo.workdir(), Some(work_tree.as_path()));
+        assert_eq!(repo.git_dir(), working_dir.join(".git"));
+        Ok(())
+    }
+
     fn repo_path() -> crate::Result<PathBuf> {
         git_testtools::scripted_fixture_repo_read_only("make_basic_repo.sh")
     }
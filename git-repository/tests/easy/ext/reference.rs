@@ -224,3 +224,37 @@ mod head {
         Ok(())
     }
 }
+
+#[cfg(unix)]
+mod edit_references {
+    use std::os::unix::fs::PermissionsExt;
+
+    use git_repository as git;
+    use git_repository::refs::transaction::PreviousValue;
+
+    #[test]
+    fn reference_transaction_hook_can_reject_edits() -> crate::Result {
+        let (repo, dir) = crate::basic_rw_repo()?;
+        let hooks_dir = repo.hooks_dir()?;
+        assert_eq!(hooks_dir, dir.path().join(".git").join("hooks"));
+        std::fs::create_dir_all(&hooks_dir)?;
+        let hook = hooks_dir.join("reference-transaction");
+        std::fs::write(&hook, "#!/bin/sh\ncat > hook.input\ntest \"$1\" != prepared\n")?;
+        std::fs::set_permissions(&hook, std::fs::Permissions::from_mode(0o755))?;
+
+        let id = git::ObjectId::empty_tree(git::hash::Kind::Sha1);
+        match repo.tag_reference("rejected", id, PreviousValue::MustNotExist) {
+            Err(git::easy::reference::edit::Error::FileTransactionPrepare(
+                git::refs::file::transaction::prepare::Error::HookRejected,
+            )) => {}
+            _ => unreachable!("the hook rejects all transactions"),
+        }
+        assert!(repo.try_find_reference("rejected")?.is_none());
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("hook.input"))?,
+            format!("{} {} refs/tags/rejected\n", git::hash::Kind::Sha1.null(), id),
+            "hooks run in the work tree"
+        );
+        Ok(())
+    }
+}
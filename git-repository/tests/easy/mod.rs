@@ -2,3 +2,4 @@ mod ext;
 mod object;
 mod oid;
 mod reference;
+mod state;
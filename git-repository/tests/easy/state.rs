@@ -0,0 +1,55 @@
+use git_repository::{easy::state::InProgress, refs::pseudo::Kind};
+
+#[test]
+fn merge_in_progress_with_fetch_and_merge_heads() -> crate::Result {
+    let repo: git_repository::easy::Handle = crate::repo("make_repo_with_merge_in_progress.sh")?.into();
+    let (a, b) = (
+        repo.find_reference("a")?.id().detach(),
+        repo.find_reference("b")?.id().detach(),
+    );
+    assert_eq!(repo.in_progress_operation(), Some(InProgress::Merge));
+    assert_eq!(repo.merge_heads()?, Some(vec![a, b]));
+
+    let fetch_head = repo.fetch_head()?.expect("present");
+    assert_eq!(
+        fetch_head.iter().map(|entry| entry.id).collect::<Vec<_>>(),
+        vec![a, b],
+        "both branches were fetched for merge"
+    );
+    assert!(fetch_head.iter().all(|entry| !entry.not_for_merge));
+    assert_eq!(fetch_head[0].description, "branch 'a' of .");
+
+    assert_eq!(repo.pseudo_reference_id(Kind::MergeHead)?, Some(a));
+    assert_eq!(
+        repo.pseudo_reference_id(Kind::OrigHead)?,
+        Some(repo.find_reference("main")?.id().detach())
+    );
+    assert_eq!(repo.pseudo_reference_id(Kind::CherryPickHead)?, None);
+    Ok(())
+}
+
+#[test]
+fn operations_are_detected_by_their_state_files() -> crate::Result {
+    let (repo, dir) = crate::basic_rw_repo()?;
+    assert_eq!(repo.in_progress_operation(), None);
+
+    let git_dir = dir.path().join(".git");
+    let id = repo.head()?.id().expect("born").detach().to_string();
+    for (kind, expected) in &[
+        (Kind::RevertHead, InProgress::Revert),
+        (Kind::CherryPickHead, InProgress::CherryPick),
+    ] {
+        std::fs::write(git_dir.join(kind.file_name()), format!("{}\n", id))?;
+        assert_eq!(repo.in_progress_operation(), Some(*expected));
+    }
+
+    std::fs::create_dir_all(git_dir.join("sequencer"))?;
+    std::fs::write(git_dir.join("sequencer").join("todo"), "")?;
+    assert_eq!(repo.in_progress_operation(), Some(InProgress::CherryPickSequence));
+
+    std::fs::create_dir(git_dir.join("rebase-merge"))?;
+    assert_eq!(repo.in_progress_operation(), Some(InProgress::Rebase));
+    std::fs::write(git_dir.join("rebase-merge").join("interactive"), "")?;
+    assert_eq!(repo.in_progress_operation(), Some(InProgress::RebaseInteractive));
+    Ok(())
+}
@@ -0,0 +1,22 @@
+#!/bin/bash
+set -eu -o pipefail
+
+git init -q
+git config commit.gpgsign false
+
+git checkout -q -b main
+git commit -q --allow-empty -m c1
+
+git checkout -q -b a
+touch a
+git add a
+git commit -q -m a
+
+git checkout -q -b b main
+touch b
+git add b
+git commit -q -m b
+
+git checkout -q main
+git fetch -q . a b
+git merge -q --no-commit a b >/dev/null
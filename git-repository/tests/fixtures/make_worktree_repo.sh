@@ -0,0 +1,15 @@
+#!/bin/bash
+set -eu -o pipefail
+
+git init -q repo
+(cd repo
+  git config commit.gpgsign false
+  git checkout -q -b main
+  git commit -q --allow-empty -m c1
+  git branch other
+
+  git worktree add -q ../wt other
+  git worktree add -q --detach ../locked-wt
+  git worktree lock --reason "on a removable device" ../locked-wt
+)
+mkdir -p wt/some/dir
@@ -0,0 +1,176 @@
+mod extensions {
+    use std::{convert::TryInto, io::Write};
+
+    use git_repository::extensions::{Error, Version};
+
+    fn repo_with_config(config: &str) -> crate::Result<tempfile::TempDir> {
+        let tmp = tempfile::tempdir()?;
+        let repo = git_repository::init(tmp.path())?;
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(repo.git_dir().join("config"))?
+            .write_all(config.as_bytes())?;
+        Ok(tmp)
+    }
+
+    fn open_err(config: &str) -> crate::Result<Error> {
+        let tmp = repo_with_config(config)?;
+        match git_repository::open(tmp.path()) {
+            Err(git_repository::open::Error::Extensions(err)) => Ok(err),
+            res => panic!("expected an extension error, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn known_extensions_are_understood() -> crate::Result {
+        let tmp = repo_with_config(
+            "[core]\n\trepositoryFormatVersion = 1\n[extensions]\n\tobjectFormat = sha1\n\tpreciousObjects = true\n\tpartialClone = origin\n\tNOOP-v1\n",
+        )?;
+        let repo = git_repository::open(tmp.path())?;
+        let extensions = repo.extensions();
+        assert_eq!(extensions.version, Version::V1);
+        assert_eq!(extensions.object_hash, git_repository::hash::Kind::Sha1);
+        assert!(extensions.precious_objects);
+        assert_eq!(extensions.partial_clone.as_ref().expect("set"), "origin");
+        assert!(!extensions.worktree_config);
+        Ok(())
+    }
+
+    #[test]
+    fn worktree_config_is_read_on_top_of_the_shared_configuration() -> crate::Result {
+        let tmp = repo_with_config("[extensions]\n\tworktreeConfig = true\n")?;
+        let git_dir = tmp.path().join(".git");
+        assert_eq!(git_repository::open(tmp.path())?.kind(), git_repository::Kind::WorkTree);
+
+        std::fs::write(git_dir.join("config.worktree"), "[core]\n\tbare = true\n")?;
+        let repo = git_repository::open(&git_dir)?;
+        assert!(repo.extensions().worktree_config, "understood even in version 0");
+        assert_eq!(repo.kind(), git_repository::Kind::Bare);
+        Ok(())
+    }
+
+    #[test]
+    fn configured_work_tree_is_relative_to_the_git_dir() -> crate::Result {
+        let tmp = repo_with_config("[core]\n\tworktree = ../work-tree\n")?;
+        let git_dir = tmp.path().join(".git");
+        assert_eq!(
+            git_repository::open(&git_dir)?.workdir(),
+            Some(git_dir.join("../work-tree").as_path())
+        );
+
+        std::fs::write(
+            git_dir.join("config"),
+            "[core]\n\tbare = true\n\tworktree = ../work-tree\n",
+        )?;
+        assert_eq!(
+            git_repository::open(&git_dir)?.kind(),
+            git_repository::Kind::Bare,
+            "bare repositories ignore it"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_extensions_are_only_ignored_in_version_0() -> crate::Result {
+        let tmp = repo_with_config("[extensions]\n\tsomethingNew = true\n")?;
+        assert_eq!(git_repository::open(tmp.path())?.extensions().version, Version::V0);
+
+        assert!(matches!(
+            open_err("[core]\n\trepositoryFormatVersion = 1\n[extensions]\n\tsomethingNew = true\n")?,
+            Error::UnknownExtension { name } if name == "somethingnew"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn unsupported_formats_are_rejected() -> crate::Result {
+        assert!(matches!(
+            open_err("[core]\n\trepositoryFormatVersion = 2\n")?,
+            Error::UnsupportedFormatVersion { version: 2 }
+        ));
+        assert!(matches!(
+            open_err("[core]\n\trepositoryFormatVersion = 1\n[extensions]\n\tobjectFormat = sha256\n")?,
+            Error::UnsupportedObjectFormat { name } if name == "sha256"
+        ));
+        assert!(matches!(
+            open_err("[extensions]\n\tobjectFormat = sha1\n")?,
+            Error::ExtensionRequiresFormatVersion1 { name } if name == "objectformat"
+        ));
+        assert!(matches!(
+            open_err("[extensions]\n\tpreciousObjects = maybe\n")?,
+            Error::InvalidValue { .. }
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn ref_storage_is_understood() -> crate::Result {
+        let tmp = repo_with_config("[core]\n\trepositoryFormatVersion = 1\n[extensions]\n\trefStorage = files\n")?;
+        let repo = git_repository::open(tmp.path())?;
+        assert_eq!(
+            repo.extensions().ref_storage,
+            git_repository::refs::store::Format::Files
+        );
+        assert!(repo.reftable.is_none());
+
+        let tmp = repo_with_config("[core]\n\trepositoryFormatVersion = 1\n[extensions]\n\trefStorage = reftable\n")?;
+        let repo = git_repository::open(tmp.path())?;
+        assert_eq!(
+            repo.extensions().ref_storage,
+            git_repository::refs::store::Format::Reftable
+        );
+        assert!(
+            repo.reftable.expect("opened").to_handle().try_find("HEAD")?.is_none(),
+            "a missing stack is empty"
+        );
+        assert!(matches!(
+            open_err("[core]\n\trepositoryFormatVersion = 1\n[extensions]\n\trefStorage = other\n")?,
+            Error::UnsupportedRefStorage { name } if name == "other"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn reftable_repositories_written_by_git_can_be_opened() -> crate::Result {
+        let tmp = tempfile::tempdir()?;
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .args(args)
+                .current_dir(tmp.path())
+                .env("GIT_AUTHOR_NAME", "author")
+                .env("GIT_AUTHOR_EMAIL", "author@example.com")
+                .env("GIT_COMMITTER_NAME", "committer")
+                .env("GIT_COMMITTER_EMAIL", "committer@example.com")
+                .stderr(std::process::Stdio::null())
+                .status()
+        };
+        if !git(&["init", "-q", "--ref-format=reftable"])?.success() {
+            eprintln!("Skipping test as the installed git can't write reftables");
+            return Ok(());
+        }
+        for args in &[
+            &["checkout", "-q", "-b", "main"][..],
+            &["config", "commit.gpgsign", "false"],
+            &["commit", "-q", "--allow-empty", "-m", "c1"],
+            &["tag", "t1"],
+        ] {
+            assert!(git(args)?.success(), "git {:?}", args);
+        }
+
+        let repo = git_repository::open(tmp.path())?;
+        assert_eq!(repo.kind(), git_repository::Kind::WorkTree);
+        assert_eq!(
+            repo.extensions().ref_storage,
+            git_repository::refs::store::Format::Reftable
+        );
+        let refs = repo.reftable.as_ref().expect("reftables are used").to_handle();
+        assert_eq!(
+            refs.find("HEAD")?.target,
+            git_repository::refs::Target::Symbolic("refs/heads/main".try_into()?)
+        );
+        let main = refs.find("main")?;
+        assert_eq!(main.name.as_bstr(), "refs/heads/main");
+        assert_eq!(refs.find("t1")?.target, main.target);
+        Ok(())
+    }
+}
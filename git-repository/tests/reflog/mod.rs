@@ -0,0 +1,106 @@
+use std::{
+    io::Write,
+    time::{Duration, UNIX_EPOCH},
+};
+
+use git_repository::{refs::file::log::expire::Options, Repository};
+
+const NOW: u32 = 1_600_000_000;
+const DAY: u32 = 24 * 60 * 60;
+
+fn repo_with_config(config: &str) -> crate::Result<(Repository, tempfile::TempDir)> {
+    let tmp = tempfile::tempdir()?;
+    let repo = git_repository::init(tmp.path())?;
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(repo.git_dir().join("config"))?
+        .write_all(config.as_bytes())?;
+    Ok((git_repository::open(tmp.path())?, tmp))
+}
+
+fn options(repo: &Repository, full_name: &str) -> Result<Options, git_repository::reflog::Error> {
+    repo.reflog_expire_options(full_name.into(), UNIX_EPOCH + Duration::from_secs(NOW as u64))
+}
+
+#[test]
+fn defaults_expire_after_90_days_and_unreachable_entries_after_30_days() -> crate::Result {
+    let (repo, _keep) = repo_with_config("")?;
+    let options = options(&repo, "refs/heads/main")?;
+    assert_eq!(options.expire, Some(NOW - 90 * DAY));
+    assert_eq!(options.expire_unreachable, Some(NOW - 30 * DAY));
+    assert!(!options.stale_fix && !options.rewrite && !options.dry_run);
+
+    assert_eq!(
+        self::options(&repo, "refs/stash")?,
+        Options::default(),
+        "the stash never expires by default"
+    );
+    Ok(())
+}
+
+#[test]
+fn the_first_matching_pattern_overrides_the_global_settings() -> crate::Result {
+    let (repo, _keep) = repo_with_config(
+        "[gc]\n\treflogExpire = 2.weeks.ago\n\treflogExpireUnreachable = now\n\
+         [gc \"refs/remotes/*\"]\n\treflogExpire = never\n\
+         [gc \"refs/remotes/origin/*\"]\n\treflogExpire = 1 day ago\n\
+         [gc \"refs/remotes/*\"]\n\treflogExpireUnreachable = 2021-01-01\n",
+    )?;
+    let main = options(&repo, "refs/heads/main")?;
+    assert_eq!(main.expire, Some(NOW - 14 * DAY));
+    assert_eq!(main.expire_unreachable, Some(NOW));
+
+    let remote = options(&repo, "refs/remotes/origin/main")?;
+    assert_eq!(
+        remote.expire, None,
+        "the first pattern wins, merged with later sections of the same pattern"
+    );
+    assert_eq!(remote.expire_unreachable, Some(1_609_459_200));
+
+    assert_eq!(
+        options(&repo, "refs/stash")?,
+        Options::default(),
+        "the stash is only affected by patterns"
+    );
+    Ok(())
+}
+
+#[test]
+fn unset_values_of_matching_patterns_never_expire() -> crate::Result {
+    let (repo, _keep) = repo_with_config(
+        "[gc]\n\treflogExpire = 2.weeks.ago\n\treflogExpireUnreachable = now\n\
+         [gc \"refs/heads/*\"]\n\treflogExpire = 1 day ago\n\
+         [gc \"refs/tags/*\"]\n\treflogExpireUnreachable = 3 days ago\n",
+    )?;
+    assert_eq!(
+        options(&repo, "refs/heads/main")?,
+        Options {
+            expire: Some(NOW - DAY),
+            expire_unreachable: None,
+            ..Default::default()
+        },
+        "the global value doesn't apply to the unset slot of a matching pattern"
+    );
+    assert_eq!(
+        options(&repo, "refs/tags/v1")?,
+        Options {
+            expire: None,
+            expire_unreachable: Some(NOW - 3 * DAY),
+            ..Default::default()
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn invalid_dates_are_errors() -> crate::Result {
+    let (repo, _keep) = repo_with_config("[gc]\n\treflogExpire = sometime\n")?;
+    match options(&repo, "refs/heads/main") {
+        Err(git_repository::reflog::Error::InvalidDate { key, value }) => {
+            assert_eq!(key, "gc.reflogExpire");
+            assert_eq!(value, "sometime");
+        }
+        res => panic!("expected an invalid date, got {:?}", res),
+    }
+    Ok(())
+}
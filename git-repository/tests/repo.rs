@@ -24,7 +24,11 @@ fn basic_rw_repo() -> crate::Result<(Handle, tempfile::TempDir)> {
     easy_repo_rw("make_basic_repo.sh")
 }
 
+mod config;
 mod discover;
 mod easy;
 mod init;
+mod open;
 mod reference;
+mod reflog;
+mod worktree;
@@ -0,0 +1,153 @@
+use std::{convert::TryInto, path::PathBuf};
+
+use git_repository::{
+    worktree::{add, prune},
+    Repository,
+};
+
+fn fixture() -> crate::Result<PathBuf> {
+    Ok(git_testtools::scripted_fixture_repo_read_only("make_worktree_repo.sh")?.canonicalize()?)
+}
+
+fn head_name(repo: &Repository) -> crate::Result<String> {
+    Ok(repo
+        .refs
+        .find_loose("HEAD")?
+        .target
+        .as_name()
+        .expect("symbolic")
+        .to_string())
+}
+
+#[test]
+fn linked_worktrees_are_discovered_and_opened_through_their_dot_git_file() -> crate::Result {
+    let dir = fixture()?;
+    let work_dir = dir.join("wt");
+    let git_dir = dir.join("repo/.git/worktrees/wt");
+
+    let path = git_repository::path::discover::existing(work_dir.join("some/dir"))?;
+    assert_eq!(
+        path,
+        git_repository::Path::LinkedWorkTree {
+            work_dir: work_dir.clone(),
+            git_dir: git_dir.clone()
+        }
+    );
+    assert_eq!(path.kind(), git_repository::Kind::WorkTree);
+
+    let repo = git_repository::discover(work_dir.join("some"))?;
+    assert_eq!(repo.git_dir(), git_dir);
+    assert_eq!(repo.common_dir(), dir.join("repo/.git"));
+    assert_eq!(repo.workdir(), Some(work_dir.as_path()));
+    assert_eq!(repo.objects_dir(), dir.join("repo/.git/objects"));
+    assert_eq!(head_name(&repo)?, "refs/heads/other", "HEAD is per worktree");
+    assert!(repo.refs.find("main").is_ok(), "branches are shared");
+
+    assert_eq!(git_repository::open(&work_dir)?, repo);
+    assert_eq!(
+        git_repository::open(&git_dir)?,
+        repo,
+        "the work tree is found through the private git dir as well"
+    );
+    Ok(())
+}
+
+#[test]
+fn list() -> crate::Result {
+    let dir = fixture()?;
+    let repo = git_repository::open(dir.join("repo"))?;
+    assert_eq!(repo.common_dir(), repo.git_dir());
+
+    let worktrees = repo.worktrees()?;
+    assert_eq!(
+        worktrees.iter().map(|wt| wt.id().to_owned()).collect::<Vec<_>>(),
+        ["locked-wt", "wt"]
+    );
+    assert_eq!(worktrees[0].base()?, dir.join("locked-wt"));
+    assert_eq!(worktrees[0].lock_reason().expect("locked"), "on a removable device");
+    assert!(!worktrees[1].is_locked());
+    assert_eq!(
+        git_repository::open(dir.join("wt"))?.worktrees()?,
+        worktrees,
+        "linked work trees see all their siblings"
+    );
+    assert_eq!(
+        worktrees[1].clone().into_repo()?.workdir(),
+        Some(dir.join("wt").as_path())
+    );
+    Ok(())
+}
+
+#[test]
+fn add_lock_move_and_prune() -> crate::Result {
+    let tmp = tempfile::tempdir()?;
+    let repo = git_repository::init(tmp.path().join("repo"))?;
+    let feature = add::Head::Branch("refs/heads/feature".try_into()?);
+
+    let wt = repo.worktree_add(tmp.path().join("wt"), feature.clone())?;
+    assert_eq!(wt.id(), "wt");
+    assert_eq!(wt.base()?, tmp.path().join("wt"));
+    let linked = git_repository::open(tmp.path().join("wt"))?;
+    assert_eq!(linked.git_dir(), wt.git_dir());
+    assert_eq!(head_name(&linked)?, "refs/heads/feature");
+    assert!(!wt.is_locked(), "the lock is only held while initializing");
+
+    let other = linked.worktree_add(
+        tmp.path().join("other/wt"),
+        add::Head::Detached(git_repository::hash::Kind::Sha1.null()),
+    )?;
+    assert_eq!(other.id(), "wt1", "ids are unique");
+    for head in [feature, add::Head::Branch("refs/heads/main".try_into()?)] {
+        assert!(
+            matches!(
+                repo.worktree_add(tmp.path().join("third"), head),
+                Err(add::Error::BranchCheckedOut { .. })
+            ),
+            "branches can only be checked out once"
+        );
+    }
+    std::fs::write(tmp.path().join("file"), "")?;
+    assert!(matches!(
+        repo.worktree_add(tmp.path(), add::Head::Detached(git_repository::hash::Kind::Sha1.null())),
+        Err(add::Error::DirectoryNotEmpty { .. })
+    ));
+
+    wt.lock("reason".into())?;
+    assert!(wt.lock("".into()).is_err(), "locks can't be acquired twice");
+    assert!(
+        wt.move_to(tmp.path().join("moved")).is_err(),
+        "locked work trees can't be moved"
+    );
+    std::fs::remove_dir_all(tmp.path().join("other"))?;
+    std::fs::rename(tmp.path().join("wt"), tmp.path().join("wt-elsewhere"))?;
+    assert_eq!(
+        repo.worktrees_prune(prune::Options::default())?,
+        [prune::Pruned {
+            id: "wt1".into(),
+            reason: prune::Reason::WorkTreeMissing
+        }],
+        "locked work trees are kept even if they are missing"
+    );
+    std::fs::rename(tmp.path().join("wt-elsewhere"), tmp.path().join("wt"))?;
+
+    assert!(wt.unlock()?);
+    assert!(!wt.unlock()?);
+    assert!(
+        wt.move_to(tmp.path().join("repo")).is_err(),
+        "existing destinations are rejected"
+    );
+    wt.move_to(tmp.path().join("moved"))?;
+    assert_eq!(wt.base()?, tmp.path().join("moved"));
+    assert_eq!(git_repository::open(tmp.path().join("moved"))?.git_dir(), wt.git_dir());
+
+    std::fs::remove_dir_all(tmp.path().join("moved"))?;
+    let options = prune::Options {
+        dry_run: true,
+        ..Default::default()
+    };
+    assert_eq!(repo.worktrees_prune(options)?.len(), 1);
+    assert!(wt.git_dir().is_dir(), "nothing is removed in a dry-run");
+    repo.worktrees_prune(prune::Options::default())?;
+    assert!(repo.worktrees()?.is_empty());
+    Ok(())
+}
@@ -60,6 +60,9 @@ pub mod visit {
 ///
 pub mod recorder;
 
+///
+pub mod pathspec;
+
 ///
 pub mod breadthfirst;
 pub use breadthfirst::impl_::traverse as breadthfirst;
@@ -0,0 +1,98 @@
+use std::collections::VecDeque;
+
+use git_object::{
+    bstr::{BStr, BString, ByteSlice, ByteVec},
+    tree,
+};
+use git_pathspec::{AttributeState, Search};
+
+use crate::tree::{visit::Action, Visit};
+
+/// A [`Visit`] implementation which passes only entries included by a pathspec [`Search`] on to its `delegate`, and skips
+/// trees that can't contain any included entry.
+///
+/// Trees leading to included entries are passed on as well, so the `delegate` can keep track of paths and skip them on its own.
+pub struct Filter<'a, V, A> {
+    search: &'a Search,
+    attributes: A,
+    path_deque: VecDeque<BString>,
+    path: BString,
+    /// The delegate receiving all included entries.
+    pub delegate: V,
+}
+
+impl<'a, V, A> Filter<'a, V, A>
+where
+    V: Visit,
+    A: FnMut(&BStr, &BStr) -> AttributeState,
+{
+    /// Create a new instance to pass entries included by `search` on to `delegate`, with `attributes(path, name)` returning
+    /// the state of the attribute `name` for `path`, which is only called for pathspecs with `:(attr:…)` magic.
+    pub fn new(search: &'a Search, delegate: V, attributes: A) -> Self {
+        Filter {
+            search,
+            attributes,
+            path_deque: VecDeque::new(),
+            path: BString::default(),
+            delegate,
+        }
+    }
+
+    fn push_element(&mut self, name: &BStr) {
+        if !self.path.is_empty() {
+            self.path.push(b'/');
+        }
+        self.path.push_str(name);
+    }
+}
+
+impl<'a, V, A> Visit for Filter<'a, V, A>
+where
+    V: Visit,
+    A: FnMut(&BStr, &BStr) -> AttributeState,
+{
+    fn pop_front_tracked_path_and_set_current(&mut self) {
+        self.path = self
+            .path_deque
+            .pop_front()
+            .expect("every call is matched with push_tracked_path_component");
+        self.delegate.pop_front_tracked_path_and_set_current();
+    }
+
+    fn push_back_tracked_path_component(&mut self, component: &BStr) {
+        self.push_element(component);
+        self.path_deque.push_back(self.path.clone());
+        self.delegate.push_back_tracked_path_component(component);
+    }
+
+    fn push_path_component(&mut self, component: &BStr) {
+        self.push_element(component);
+        self.delegate.push_path_component(component);
+    }
+
+    fn pop_path_component(&mut self) {
+        match self.path.rfind_byte(b'/') {
+            Some(pos) => self.path.truncate(pos),
+            None => self.path.clear(),
+        }
+        self.delegate.pop_path_component();
+    }
+
+    fn visit_tree(&mut self, entry: &tree::EntryRef<'_>) -> Action {
+        if !self.search.can_match_relative_path(self.path.as_bstr()) {
+            return Action::Skip;
+        }
+        self.delegate.visit_tree(entry)
+    }
+
+    fn visit_nontree(&mut self, entry: &tree::EntryRef<'_>) -> Action {
+        let is_dir = entry.mode == tree::EntryMode::Commit;
+        match self
+            .search
+            .pattern_matching_relative_path(self.path.as_bstr(), is_dir, &mut self.attributes)
+        {
+            Some(_) => self.delegate.visit_nontree(entry),
+            None => Action::Continue,
+        }
+    }
+}
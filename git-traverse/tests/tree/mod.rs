@@ -95,3 +95,43 @@ fn basic_nesting() -> crate::Result<()> {
     );
     Ok(())
 }
+
+#[test]
+fn pathspecs_limit_entries_and_skip_trees_that_cannot_match() -> crate::Result<()> {
+    let db = db()?;
+    let mut buf = Vec::new();
+    let mut buf2 = Vec::new();
+    let mut commit = db
+        .find_commit_iter(hex_to_id("85df34aa34848b8138b2b3dcff5fb5c2b734e0ce"), &mut buf)?
+        .0;
+    let search = git_pathspec::Search::from_patterns(
+        vec![
+            git_pathspec::parse(b"f/*", Default::default())?,
+            git_pathspec::parse(b":!f/z", Default::default())?,
+        ],
+        "".into(),
+    )?;
+    let mut filter = tree::pathspec::Filter::new(&search, tree::Recorder::default(), |_, _| {
+        git_pathspec::AttributeState::Unspecified
+    });
+    git_traverse::tree::breadthfirst(
+        db.find_tree_iter(commit.tree_id().expect("a tree is available in a commit"), &mut buf2)?
+            .0,
+        tree::breadthfirst::State::default(),
+        |oid, buf| db.find_tree_iter(oid, buf).ok().map(|t| t.0),
+        &mut filter,
+    )?;
+
+    let paths: Vec<_> = filter
+        .delegate
+        .records
+        .iter()
+        .map(|entry| entry.filepath.to_string())
+        .collect();
+    assert_eq!(
+        paths,
+        vec!["f", "f/c", "f/d", "f/d/x"],
+        "only trees leading to included entries are visited, and excluded entries are dropped"
+    );
+    Ok(())
+}
@@ -0,0 +1,267 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use bstr::{BStr, BString, ByteSlice};
+
+use crate::{glob::Case, pattern};
+
+///
+pub mod parse;
+pub use parse::{parse, Assignment};
+
+/// The state an attribute can be in, as assigned in `.gitattributes` files.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum State {
+    /// The attribute is set, as in `text`.
+    Set,
+    /// The attribute is unset, as in `-text`.
+    Unset,
+    /// The attribute is set to a value, as in `eol=lf`.
+    Value(BString),
+    /// The attribute is explicitly reset to be unspecified, as in `!text`, overriding assignments of lower precedence.
+    Unspecified,
+}
+
+impl std::fmt::Display for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            State::Set => f.write_str("set"),
+            State::Unset => f.write_str("unset"),
+            State::Value(value) => std::fmt::Display::fmt(value, f),
+            State::Unspecified => f.write_str("unspecified"),
+        }
+    }
+}
+
+impl pattern::Pattern for parse::Line {
+    /// Just like git, lines that can't be parsed are ignored. Use [`parse()`] to learn about them.
+    fn bytes_to_patterns(bytes: &[u8]) -> Vec<Self> {
+        parse(bytes).filter_map(Result::ok).collect()
+    }
+
+    /// Macros may only be defined in attribute files that apply to the whole working tree.
+    fn may_be_defined_in_sub_directory(&self) -> bool {
+        matches!(self.kind, parse::Kind::Pattern(_))
+    }
+}
+
+/// A list of patterns and macros read from a single source, typically a file, where later lines override earlier ones.
+pub type PatternList = pattern::List<parse::Line>;
+
+/// An attribute of a path, along with information about where it was assigned for `git check-attr`-style debugging.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    /// The name of the attribute.
+    pub name: BString,
+    /// The state of the attribute.
+    pub state: State,
+    /// The file containing the line assigning the attribute, if any. It may also have been assigned by a macro on that line.
+    pub source: Option<PathBuf>,
+    /// The line number of the assignment in `source`, starting at 1.
+    pub sequence_number: usize,
+}
+
+/// The attributes of a path as filled in by [`Stack::matching_attributes()`], meant to be reused across queries to avoid
+/// allocations.
+#[derive(Debug, Clone, Default)]
+pub struct Outcome {
+    /// The names of the attributes we are interested in, or all if empty.
+    selection: Vec<BString>,
+    /// The amount of attributes in `selection` we found.
+    selected_found: usize,
+    /// All attributes whose state is known, including those that aren't selected which are needed to expand macros correctly.
+    matches: Vec<Match>,
+}
+
+impl Outcome {
+    /// Create an instance that only looks for the attributes with the given `names`, which makes queries faster as they
+    /// can stop as soon as all of them were found.
+    pub fn with_selection(names: impl IntoIterator<Item = impl Into<BString>>) -> Self {
+        Outcome {
+            selection: names.into_iter().map(Into::into).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Return all matched attributes that were selected, in the order they were found.
+    pub fn iter(&self) -> impl Iterator<Item = &Match> + '_ {
+        self.matches.iter().filter(move |m| self.is_selected(m.name.as_bstr()))
+    }
+
+    /// Return the attribute with `name`, or `None` if it is unspecified as no line assigned it.
+    pub fn get(&self, name: impl AsRef<[u8]>) -> Option<&Match> {
+        let name = name.as_ref();
+        self.iter().find(|m| m.name == name)
+    }
+
+    fn is_selected(&self, name: &BStr) -> bool {
+        self.selection.is_empty() || self.selection.iter().any(|selected| selected == name)
+    }
+
+    fn is_done(&self) -> bool {
+        !self.selection.is_empty() && self.selected_found == self.selection.len()
+    }
+
+    fn reset(&mut self) {
+        self.matches.clear();
+        self.selected_found = 0;
+    }
+
+    /// Record all `assignments` of attributes which aren't known yet, expanding `macros` recursively.
+    fn fill(
+        &mut self,
+        assignments: &[Assignment],
+        source: Option<&Path>,
+        sequence_number: usize,
+        macros: &HashMap<BString, Vec<Assignment>>,
+    ) {
+        for assignment in assignments.iter().rev() {
+            if self.is_done() {
+                return;
+            }
+            if self.matches.iter().any(|m| m.name == assignment.name) {
+                continue;
+            }
+            if !self.selection.is_empty() && self.is_selected(assignment.name.as_bstr()) {
+                self.selected_found += 1;
+            }
+            self.matches.push(Match {
+                name: assignment.name.clone(),
+                state: assignment.state.clone(),
+                source: source.map(ToOwned::to_owned),
+                sequence_number,
+            });
+            if assignment.state == State::Set {
+                if let Some(macro_assignments) = macros.get(&assignment.name) {
+                    self.fill(macro_assignments, source, sequence_number, macros);
+                }
+            }
+        }
+    }
+}
+
+/// A stack of per-directory attribute files like `.gitattributes`, following the directories of the paths it is queried
+/// with, along with attributes that apply to the whole working tree.
+///
+/// Just like in git, attributes in `.git/info/attributes` have the highest precedence, followed by those in the deepest
+/// directory, its parent directories and finally those in `core.attributesFile`.
+/// Macros like `[attr]binary -diff -merge -text` can be defined everywhere but in attribute files of sub-directories.
+#[derive(Clone)]
+pub struct Stack {
+    case: Case,
+    /// The name of the attributes file in each directory.
+    attributes_filename_per_dir: BString,
+    /// Patterns with the highest precedence, typically from `.git/info/attributes`.
+    overrides: PatternList,
+    /// Patterns applying to the whole working tree with the lowest precedence, in order of their precedence.
+    globals: Vec<PatternList>,
+    /// All known macros, available once the root directory was read.
+    macros: HashMap<BString, Vec<Assignment>>,
+    directories: pattern::Stack<parse::Line, ()>,
+}
+
+impl Stack {
+    /// Create a new stack for the working tree at `root`, with `overrides` taking precedence over all per-directory
+    /// attributes, which in turn take precedence over `globals` which are ordered by their precedence. `case` controls
+    /// how patterns are matched.
+    pub fn new(root: impl Into<PathBuf>, overrides: PatternList, globals: Vec<PatternList>, case: Case) -> Self {
+        Stack {
+            case,
+            attributes_filename_per_dir: ".gitattributes".into(),
+            overrides,
+            globals,
+            macros: HashMap::new(),
+            directories: pattern::Stack::new(root.into()),
+        }
+    }
+
+    /// Create a new stack for the working tree at `root`, reading attributes from `info/attributes` in `git_dir` and from
+    /// `attributes_file`, typically the value of `core.attributesFile`, if set. Files that don't exist are ignored.
+    pub fn from_git_dir(
+        root: impl Into<PathBuf>,
+        git_dir: impl AsRef<Path>,
+        attributes_file: Option<PathBuf>,
+        case: Case,
+    ) -> std::io::Result<Self> {
+        let mut buf = Vec::new();
+        let overrides = PatternList::from_file(git_dir.as_ref().join("info").join("attributes"), None, &mut buf)?
+            .unwrap_or_default();
+        let mut globals = Vec::new();
+        if let Some(path) = attributes_file {
+            globals.extend(PatternList::from_file(path, None, &mut buf)?);
+        }
+        Ok(Stack::new(root, overrides, globals, case))
+    }
+
+    /// Fill `out` with the attributes of `rela_path`, relative to the root of the working tree, with `is_dir` being true
+    /// if it is a directory.
+    ///
+    /// The stack is adjusted to the directory containing `rela_path`, reading attribute files of directories as needed,
+    /// which makes queries in traversal order efficient.
+    pub fn matching_attributes(&mut self, rela_path: &BStr, is_dir: bool, out: &mut Outcome) -> std::io::Result<()> {
+        out.reset();
+        let dir = rela_path
+            .rfind_byte(b'/')
+            .map_or_else(|| b"".as_bstr(), |pos| rela_path[..pos].as_bstr());
+        let needs_macros = self.directories.directories().is_empty();
+        self.directories
+            .set_directory(dir, self.attributes_filename_per_dir.as_bstr(), |_, _| ((), true))?;
+        if needs_macros {
+            self.macros = self.collect_macros();
+        }
+
+        let lists = std::iter::once(&self.overrides)
+            .chain(self.directories.directories().iter().rev().map(|dir| &dir.patterns))
+            .chain(self.globals.iter());
+        for list in lists {
+            let rela_path = match list.strip_base(rela_path) {
+                Some(path) => path,
+                None => continue,
+            };
+            for line in list.patterns.iter().rev() {
+                if out.is_done() {
+                    return Ok(());
+                }
+                match &line.kind {
+                    parse::Kind::Pattern(pattern)
+                        if pattern.matches_repo_relative_path(rela_path, is_dir, self.case) =>
+                    {
+                        out.fill(
+                            &line.assignments,
+                            list.source.as_deref(),
+                            line.line_number,
+                            &self.macros,
+                        )
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Collect all macros in order of increasing precedence so that those with higher precedence override the others.
+    fn collect_macros(&self) -> HashMap<BString, Vec<Assignment>> {
+        let root = &self.directories.directories().first().expect("root was read").patterns;
+        let mut macros = HashMap::new();
+        macros.insert(
+            "binary".into(),
+            ["diff", "merge", "text"]
+                .iter()
+                .map(|name| Assignment {
+                    name: (*name).into(),
+                    state: State::Unset,
+                })
+                .collect(),
+        );
+        let lists = self.globals.iter().rev().chain(Some(root)).chain(Some(&self.overrides));
+        for line in lists.flat_map(|list| list.patterns.iter()) {
+            if let parse::Kind::Macro(name) = &line.kind {
+                macros.insert(name.clone(), line.assignments.clone());
+            }
+        }
+        macros
+    }
+}
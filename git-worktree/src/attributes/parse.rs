@@ -0,0 +1,213 @@
+use bstr::{BStr, BString, ByteSlice};
+
+use crate::{attributes::State, glob::Pattern};
+
+mod error {
+    use bstr::BString;
+    use quick_error::quick_error;
+
+    quick_error! {
+        /// The error returned by [`parse()`][super::parse()] for lines that git would ignore with a warning.
+        #[derive(Debug)]
+        #[allow(missing_docs)]
+        pub enum Error {
+            PatternNegation { line_number: usize, line: BString } {
+                display("Line {} has a negative pattern, for literal characters use \\!: {}", line_number, line)
+            }
+            AttributeName { line_number: usize, attribute: BString } {
+                display("Line {} has an invalid attribute name '{}'", line_number, attribute)
+            }
+            MacroName { line_number: usize, macro_name: BString } {
+                display("Line {} has an invalid macro name '{}'", line_number, macro_name)
+            }
+        }
+    }
+}
+pub use error::Error;
+
+/// What a line of an attributes file assigns attributes to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Kind {
+    /// All paths matching the pattern.
+    Pattern(Pattern),
+    /// The macro with the given name, defined with `[attr]name`.
+    Macro(BString),
+}
+
+/// The name of an attribute along with the state it is set to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assignment {
+    /// The name of the attribute.
+    pub name: BString,
+    /// The state of the attribute.
+    pub state: State,
+}
+
+/// A parsed line of an attributes file, as returned by [`parse()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    /// What the attributes are assigned to.
+    pub kind: Kind,
+    /// All assignments in the order they appear on the line.
+    pub assignments: Vec<Assignment>,
+    /// The number of the line, starting at 1.
+    pub line_number: usize,
+}
+
+/// An iterator over the lines of an attributes file, as returned by [`parse()`].
+pub struct Lines<'a> {
+    lines: bstr::Lines<'a>,
+    line_number: usize,
+}
+
+impl<'a> Iterator for Lines<'a> {
+    type Item = Result<Line, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for line in self.lines.by_ref() {
+            self.line_number += 1;
+            let line = line.trim_start_with(is_blank);
+            if line.is_empty() || line[0] == b'#' {
+                continue;
+            }
+            match parse_line(line.as_bstr(), self.line_number) {
+                Ok(None) => continue,
+                Ok(Some(line)) => return Some(Ok(line)),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+        None
+    }
+}
+
+/// Parse the lines in `buf`, the content of a `.gitattributes`-style file.
+///
+/// Empty lines and comments are skipped. Lines that git would ignore with a warning are returned as error, and parsing
+/// may continue with the next line.
+pub fn parse(buf: &[u8]) -> Lines<'_> {
+    let buf = buf.strip_prefix(b"\xef\xbb\xbf").unwrap_or(buf);
+    Lines {
+        lines: buf.lines(),
+        line_number: 0,
+    }
+}
+
+fn is_blank(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\r' | '\n')
+}
+
+fn parse_line(line: &BStr, line_number: usize) -> Result<Option<Line>, Error> {
+    // Like git, we fall back to using the pattern as is if it can't be unquoted.
+    let (name, assignments) = match line.strip_prefix(b"\"").and_then(unquote) {
+        Some(name_and_rest) => name_and_rest,
+        None => {
+            let end = line.find_byteset(b" \t\r\n").unwrap_or(line.len());
+            (line[..end].into(), line[end..].as_bytes())
+        }
+    };
+
+    let kind = match name.strip_prefix(b"[attr]") {
+        Some(macro_name) if !macro_name.is_empty() => {
+            if !is_valid_attribute_name(macro_name) {
+                return Err(Error::MacroName {
+                    line_number,
+                    macro_name: macro_name.into(),
+                });
+            }
+            Kind::Macro(macro_name.into())
+        }
+        _ => match Pattern::from_bytes(&name) {
+            Some(pattern) if pattern.is_negative() => {
+                return Err(Error::PatternNegation {
+                    line_number,
+                    line: line.into(),
+                })
+            }
+            Some(pattern) => Kind::Pattern(pattern),
+            None => return Ok(None),
+        },
+    };
+    Ok(Some(Line {
+        kind,
+        assignments: parse_assignments(assignments, line_number)?,
+        line_number,
+    }))
+}
+
+fn parse_assignments(input: &[u8], line_number: usize) -> Result<Vec<Assignment>, Error> {
+    input
+        .fields_with(is_blank)
+        .map(|token| {
+            let (name, state) = match token[0] {
+                b'-' => (&token[1..], State::Unset),
+                b'!' => (&token[1..], State::Unspecified),
+                _ => match token.find_byte(b'=') {
+                    Some(pos) => (&token[..pos], State::Value(token[pos + 1..].into())),
+                    None => (token, State::Set),
+                },
+            };
+            let name = match state {
+                State::Unset | State::Unspecified => name.find_byte(b'=').map_or(name, |pos| &name[..pos]),
+                _ => name,
+            };
+            if !is_valid_attribute_name(name) {
+                return Err(Error::AttributeName {
+                    line_number,
+                    attribute: name.into(),
+                });
+            }
+            Ok(Assignment {
+                name: name.into(),
+                state,
+            })
+        })
+        .collect()
+}
+
+fn is_valid_attribute_name(name: &[u8]) -> bool {
+    !name.is_empty()
+        && name[0] != b'-'
+        && name
+            .iter()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_'))
+}
+
+/// Unquote a C-style quoted string whose opening quote was already consumed, returning it along with the rest of the input
+/// after the closing quote.
+fn unquote(input: &[u8]) -> Option<(BString, &[u8])> {
+    let mut out = BString::default();
+    let mut bytes = input.iter().enumerate();
+    while let Some((pos, b)) = bytes.next() {
+        match b {
+            b'"' => return Some((out, &input[pos + 1..])),
+            b'\\' => {
+                let (_, escaped) = bytes.next()?;
+                let unescaped = match escaped {
+                    b'a' => 0x07,
+                    b'b' => 0x08,
+                    b'f' => 0x0c,
+                    b'n' => b'\n',
+                    b'r' => b'\r',
+                    b't' => b'\t',
+                    b'v' => 0x0b,
+                    b'"' | b'\\' => *escaped,
+                    b'0'..=b'3' => {
+                        let mut value = escaped - b'0';
+                        for _ in 0..2 {
+                            let (_, digit) = bytes.next()?;
+                            if !(b'0'..=b'7').contains(digit) {
+                                return None;
+                            }
+                            value = (value << 3) | (digit - b'0');
+                        }
+                        value
+                    }
+                    _ => return None,
+                };
+                out.push(unescaped);
+            }
+            _ => out.push(*b),
+        }
+    }
+    None
+}
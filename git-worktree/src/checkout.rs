@@ -0,0 +1,452 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use bstr::{BStr, BString, ByteSlice};
+use git_features::parallel::{self, in_parallel_if, Reduce};
+use git_hash::oid;
+use git_index::{entry, Entry};
+
+use crate::{attributes, filter};
+
+mod error {
+    use std::path::PathBuf;
+
+    use bstr::BString;
+    use git_hash::ObjectId;
+    use quick_error::quick_error;
+
+    quick_error! {
+        /// The error returned by [`checkout()`][super::checkout()].
+        #[derive(Debug)]
+        #[allow(missing_docs)]
+        pub enum Error {
+            Io(err: std::io::Error) {
+                display("An IO error occurred while checking out files")
+                source(err)
+                from()
+            }
+            ObjectNotFound { oid: ObjectId, path: PathBuf } {
+                display("The blob {} for checking out '{}' could not be found", oid, path.display())
+            }
+            InvalidPath { path: BString } {
+                display("The path '{}' could leave the checkout directory or write into the repository and was not checked out", path)
+            }
+            IllformedUtf8 { path: BString } {
+                display("The path '{}' or the target of its symbolic link could not be converted to a path on this platform", path)
+            }
+            Filter(err: crate::filter::Error) {
+                display("A file could not be converted for the working tree")
+                source(err)
+                from()
+            }
+            Interrupted {
+                display("The checkout was interrupted")
+            }
+        }
+    }
+}
+pub use error::Error;
+
+/// Configure how files are checked out, typically derived from `core.*` configuration.
+#[derive(Debug, Clone, Copy)]
+pub struct Options {
+    /// If true, symbolic links are created as such, like `core.symlinks`. Otherwise they are checked out as files
+    /// containing the link target.
+    pub symlinks: bool,
+    /// If true, executable files are checked out with their executable bit set, like `core.fileMode`.
+    pub executable_bit: bool,
+    /// If true, existing files or directories in the way of files to check out are removed. Otherwise they are reported as
+    /// [collisions][Outcome::collisions].
+    pub overwrite_existing: bool,
+    /// If true, errors other than collisions don't abort the checkout but are collected in [`Outcome::errors`].
+    pub keep_going: bool,
+    /// If true, only entries marked with [`entry::Flags::UPDATE`] are checked out, like those that were included by
+    /// [`sparse::apply()`][crate::sparse::apply()].
+    pub marked_entries_only: bool,
+    /// The amount of threads to use for writing files, or `None` to use all logical cores.
+    pub thread_limit: Option<usize>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            symlinks: true,
+            executable_bit: true,
+            overwrite_existing: false,
+            keep_going: false,
+            marked_entries_only: false,
+            thread_limit: None,
+        }
+    }
+}
+
+/// A path that couldn't be checked out as something else was in the way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Collision {
+    /// The path relative to the root of the checkout.
+    pub path: BString,
+    /// The kind of error that signalled the collision.
+    pub error_kind: std::io::ErrorKind,
+}
+
+/// An error that occurred while checking out a path if [`Options::keep_going`] is set.
+#[derive(Debug)]
+pub struct ErrorRecord {
+    /// The path relative to the root of the checkout.
+    pub path: BString,
+    /// The error that prevented the path to be checked out.
+    pub error: Error,
+}
+
+/// The outcome of [`checkout()`].
+#[derive(Debug, Default)]
+pub struct Outcome {
+    /// The amount of files, symlinks and submodule directories that were created.
+    pub files_updated: usize,
+    /// The total amount of bytes written into files.
+    pub bytes_written: u64,
+    /// All paths that couldn't be checked out as something else was in the way.
+    pub collisions: Vec<Collision>,
+    /// All errors that occurred if [`Options::keep_going`] was set.
+    pub errors: Vec<ErrorRecord>,
+}
+
+/// Write all entries of `index` into the directory `dir` using `find(blob_id, buf) -> Option<BlobRef>` to obtain their
+/// content, configured by `options`, and update the stat information of all written entries.
+///
+/// The content of files is converted with `filters` according to their attributes in `attributes`, which should be
+/// created for `dir`. Both are cloned for each thread. As attribute files are read from `dir`, all `.gitattributes` files
+/// are checked out first.
+///
+/// Files are written in parallel on multiple threads if there are enough of them, and the operation stops early with
+/// [`Error::Interrupted`] once `should_interrupt` is set.
+/// Entries marked as _skip-worktree_, unmerged entries and directories of a sparse index are not checked out. Submodules
+/// are checked out as empty directories.
+///
+/// Entries with paths that [aren't valid][is_valid_path()] fail with [`Error::InvalidPath`] without touching the file system.
+pub fn checkout<Find>(
+    index: &mut git_index::State,
+    dir: impl AsRef<Path>,
+    find: Find,
+    should_interrupt: &AtomicBool,
+    filters: &filter::Pipeline,
+    attributes: &attributes::Stack,
+    options: Options,
+) -> Result<Outcome, Error>
+where
+    Find: for<'a> FnMut(&oid, &'a mut Vec<u8>) -> Option<git_object::BlobRef<'a>> + Send + Clone,
+{
+    let dir = dir.as_ref();
+    let (out, stat_updates) = {
+        let index = &*index;
+        let options = &options;
+        let entries = index.entries();
+        let (chunk_size, thread_limit, _) =
+            parallel::optimize_chunk_size_and_thread_limit(100, Some(entries.len()), options.thread_limit, None);
+        let new_checkout = move |_| Checkout {
+            root: dir,
+            options,
+            find: find.clone(),
+            buf: Vec::new(),
+            converted: Vec::new(),
+            created_dir: BString::default(),
+            filters: filters.clone(),
+            attributes: attributes.clone(),
+            attribute_outcome: attributes::Outcome::with_selection(filter::ATTRIBUTES.iter().copied()),
+            out: Outcome::default(),
+            stat_updates: Vec::new(),
+        };
+        let is_attributes_file =
+            |rela_path: &BStr| rela_path == ".gitattributes" || rela_path.ends_with(b"/.gitattributes");
+
+        let mut checkout = new_checkout(0);
+        for (entry_index, entry) in entries.iter().enumerate() {
+            let rela_path = entry.path(index);
+            if is_attributes_file(rela_path) {
+                checkout.entry(entry_index, entry, rela_path)?;
+            }
+        }
+        in_parallel_if(
+            || entries.len() > chunk_size,
+            entries.chunks(chunk_size).enumerate(),
+            thread_limit,
+            new_checkout,
+            move |(chunk_index, entries), checkout| {
+                for (offset, entry) in entries.iter().enumerate() {
+                    if should_interrupt.load(Ordering::Relaxed) {
+                        return Err(Error::Interrupted);
+                    }
+                    let rela_path = entry.path(index);
+                    if !is_attributes_file(rela_path) {
+                        checkout.entry(chunk_index * chunk_size + offset, entry, rela_path)?;
+                    }
+                }
+                Ok((
+                    std::mem::take(&mut checkout.out),
+                    std::mem::take(&mut checkout.stat_updates),
+                ))
+            },
+            Reducer {
+                out: checkout.out,
+                stat_updates: checkout.stat_updates,
+            },
+        )?
+    };
+
+    let entries = index.entries_mut();
+    for (entry_index, stat) in stat_updates {
+        entries[entry_index].stat = stat;
+    }
+    Ok(out)
+}
+
+struct Checkout<'a, Find> {
+    root: &'a Path,
+    options: &'a Options,
+    find: Find,
+    buf: Vec<u8>,
+    converted: Vec<u8>,
+    /// The last directory we created, relative to `root`, to avoid creating it again.
+    created_dir: BString,
+    filters: filter::Pipeline,
+    attributes: attributes::Stack,
+    attribute_outcome: attributes::Outcome,
+    out: Outcome,
+    stat_updates: Vec<(usize, entry::Stat)>,
+}
+
+impl<'a, Find> Checkout<'a, Find>
+where
+    Find: for<'b> FnMut(&oid, &'b mut Vec<u8>) -> Option<git_object::BlobRef<'b>>,
+{
+    fn entry(&mut self, entry_index: usize, entry: &Entry, rela_path: &BStr) -> Result<(), Error> {
+        if entry.flags.contains(entry::Flags::SKIP_WORKTREE)
+            || entry.stage() != 0
+            || entry.mode.is_sparse()
+            || (self.options.marked_entries_only && !entry.flags.contains(entry::Flags::UPDATE))
+        {
+            return Ok(());
+        }
+        let res = self.checked_path(entry, rela_path).and_then(|path| {
+            self.create_leading_directories(rela_path)?;
+            self.write(entry, rela_path, &path)?;
+            std::fs::symlink_metadata(&path).map_err(Error::from)
+        });
+        match res {
+            Ok(metadata) => {
+                self.stat_updates.push((entry_index, entry::Stat::from_fs(&metadata)));
+                self.out.files_updated += 1;
+            }
+            Err(Error::Io(err)) if is_collision(&err) => self.out.collisions.push(Collision {
+                path: rela_path.into(),
+                error_kind: err.kind(),
+            }),
+            Err(err @ Error::Interrupted) => return Err(err),
+            Err(err) if self.options.keep_going => self.out.errors.push(ErrorRecord {
+                path: rela_path.into(),
+                error: err,
+            }),
+            Err(err) => return Err(err),
+        }
+        Ok(())
+    }
+
+    /// Return the path to write `entry` at `rela_path` to, if it's valid.
+    fn checked_path(&self, entry: &Entry, rela_path: &BStr) -> Result<PathBuf, Error> {
+        if !is_valid_path(rela_path, entry.mode) {
+            return Err(Error::InvalidPath { path: rela_path.into() });
+        }
+        rela_path
+            .to_path()
+            .map(|path| self.root.join(path))
+            .map_err(|_| Error::IllformedUtf8 { path: rela_path.into() })
+    }
+
+    fn write(&mut self, entry: &Entry, rela_path: &BStr, path: &Path) -> Result<(), Error> {
+        if entry.mode == entry::Mode::COMMIT {
+            return match std::fs::create_dir(path) {
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists && path.is_dir() => Ok(()),
+                res => res.map_err(Into::into),
+            };
+        }
+
+        let blob = (self.find)(&entry.id, &mut self.buf).ok_or_else(|| Error::ObjectNotFound {
+            oid: entry.id,
+            path: path.to_owned(),
+        })?;
+        if entry.mode == entry::Mode::SYMLINK && self.options.symlinks {
+            let target = blob
+                .data
+                .to_path()
+                .map_err(|_| Error::IllformedUtf8 { path: rela_path.into() })?;
+            if self.options.overwrite_existing {
+                remove_existing(path)?;
+            }
+            symlink(target, path)?;
+            return Ok(());
+        }
+
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true);
+        if self.options.overwrite_existing {
+            remove_existing(path)?;
+        }
+        options.create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            let is_executable = self.options.executable_bit && entry.mode == entry::Mode::FILE_EXECUTABLE;
+            options.mode(if is_executable { 0o777 } else { 0o666 });
+        }
+        self.attributes
+            .matching_attributes(rela_path, false, &mut self.attribute_outcome)?;
+        self.filters
+            .to_worktree(blob.data, rela_path, &self.attribute_outcome, &mut self.converted)?;
+        let mut file = options.open(path)?;
+        file.write_all(&self.converted)?;
+        self.out.bytes_written += self.converted.len() as u64;
+        Ok(())
+    }
+
+    /// Create all directories leading up to `rela_path`, replacing files in the way if [`Options::overwrite_existing`] is set.
+    fn create_leading_directories(&mut self, rela_path: &BStr) -> std::io::Result<()> {
+        let dir = match rela_path.rfind_byte(b'/') {
+            Some(pos) => rela_path[..pos].as_bstr(),
+            None => return Ok(()),
+        };
+        if self.created_dir == dir {
+            return Ok(());
+        }
+        let mut path = self.root.to_owned();
+        for component in dir.split_str("/") {
+            path.push(component.to_path().expect("UTF-8 conversion succeeds"));
+            match std::fs::create_dir(&path) {
+                Ok(()) => {}
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+                    if std::fs::symlink_metadata(&path)?.is_dir() {
+                        continue;
+                    }
+                    if !self.options.overwrite_existing {
+                        return Err(err);
+                    }
+                    std::fs::remove_file(&path)?;
+                    std::fs::create_dir(&path)?;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        self.created_dir.clear();
+        self.created_dir.extend_from_slice(dir);
+        Ok(())
+    }
+}
+
+/// Return true if `rela_path` of an entry with `mode` can be checked out without leaving the checkout directory or writing into
+/// the repository, similar to git's `verify_path()` with `core.protectHFS` and `core.protectNTFS` enabled.
+///
+/// Paths may not be absolute or contain empty, `.` or `..` components, backslashes, or components that file systems may treat
+/// as `.git`, like `.GIT`, `.git.` or `git~1`. Symbolic links may also not be named like `.gitmodules`.
+pub fn is_valid_path(rela_path: &BStr, mode: entry::Mode) -> bool {
+    let mut components = rela_path.split_str("/").peekable();
+    while let Some(component) = components.next() {
+        if component.is_empty()
+            || component == b"."
+            || component == b".."
+            || component.contains(&b'\\')
+            || (cfg!(windows) && component.contains(&b':'))
+            || is_dot_git_variant(component, "git")
+        {
+            return false;
+        }
+        if components.peek().is_none() && mode == entry::Mode::SYMLINK && is_dot_git_variant(component, "gitmodules") {
+            return false;
+        }
+    }
+    true
+}
+
+/// Return true if `component` may be treated as `.<name>` by case-insensitive file systems, HFS+ which ignores some
+/// unicode code points, or NTFS which ignores trailing dots and spaces, alternate data streams and knows short names.
+fn is_dot_git_variant(component: &[u8], name: &str) -> bool {
+    let mut normalized: Vec<u8> = component
+        .chars()
+        .filter(|c| !is_ignored_by_hfs(*c))
+        .flat_map(|c| {
+            let mut buf = [0; 4];
+            c.to_ascii_lowercase().encode_utf8(&mut buf).as_bytes().to_vec()
+        })
+        .collect();
+    if let Some(pos) = normalized.find_byte(b':') {
+        normalized.truncate(pos);
+    }
+    while let Some(b' ') | Some(b'.') = normalized.last() {
+        normalized.pop();
+    }
+    let short_name_prefix = &name.as_bytes()[..name.len().min(6)];
+    (normalized.first() == Some(&b'.') && normalized[1..] == *name.as_bytes())
+        || (normalized.len() == short_name_prefix.len() + 2
+            && normalized.starts_with(short_name_prefix)
+            && normalized[short_name_prefix.len()] == b'~'
+            && matches!(normalized[short_name_prefix.len() + 1], b'1'..=b'4'))
+}
+
+/// Return true if HFS+ ignores `c` when comparing file names.
+fn is_ignored_by_hfs(c: char) -> bool {
+    matches!(c, '\u{200c}'..='\u{200f}' | '\u{202a}'..='\u{202e}' | '\u{206a}'..='\u{206f}' | '\u{feff}')
+}
+
+fn remove_existing(path: &Path) -> std::io::Result<()> {
+    let res = match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(path),
+        Ok(_) => std::fs::remove_file(path),
+        Err(err) => Err(err),
+    };
+    match res {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        res => res,
+    }
+}
+
+fn is_collision(err: &std::io::Error) -> bool {
+    err.kind() == std::io::ErrorKind::AlreadyExists
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, path: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, path)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, path: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_file(target, path)
+}
+
+#[derive(Default)]
+struct Reducer {
+    out: Outcome,
+    stat_updates: Vec<(usize, entry::Stat)>,
+}
+
+impl Reduce for Reducer {
+    type Input = Result<(Outcome, Vec<(usize, entry::Stat)>), Error>;
+    type FeedProduce = ();
+    type Output = (Outcome, Vec<(usize, entry::Stat)>);
+    type Error = Error;
+
+    fn feed(&mut self, item: Self::Input) -> Result<Self::FeedProduce, Self::Error> {
+        let (chunk, stat_updates) = item?;
+        self.out.files_updated += chunk.files_updated;
+        self.out.bytes_written += chunk.bytes_written;
+        self.out.collisions.extend(chunk.collisions);
+        self.out.errors.extend(chunk.errors);
+        self.stat_updates.extend(stat_updates);
+        Ok(())
+    }
+
+    fn finalize(self) -> Result<Self::Output, Self::Error> {
+        Ok((self.out, self.stat_updates))
+    }
+}
@@ -0,0 +1,244 @@
+use std::path::{Path, PathBuf};
+
+use bstr::{BStr, BString, ByteSlice};
+
+use crate::{
+    glob::{Case, Pattern},
+    pattern,
+};
+
+/// A pattern along with the line number it was defined on in its source, starting at 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mapping {
+    /// The parsed pattern.
+    pub pattern: Pattern,
+    /// The line number of the pattern in its source, or its position in a list of patterns, starting at 1.
+    pub sequence_number: usize,
+}
+
+/// An iterator over the patterns of a `.gitignore`-style file, as returned by [`parse()`].
+pub struct Lines<'a> {
+    lines: bstr::Lines<'a>,
+    line_number: usize,
+}
+
+impl<'a> Iterator for Lines<'a> {
+    type Item = (Pattern, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for line in self.lines.by_ref() {
+            self.line_number += 1;
+            if line.first() == Some(&b'#') {
+                continue;
+            }
+            if let Some(pattern) = Pattern::from_bytes(trim_trailing_spaces(line)) {
+                return Some((pattern, self.line_number));
+            }
+        }
+        None
+    }
+}
+
+/// Parse the patterns in `buf`, the content of a `.gitignore`-style file, along with the line number they are defined on.
+///
+/// Empty lines and comments are skipped, as are trailing spaces unless they are escaped with a backslash.
+pub fn parse(buf: &[u8]) -> Lines<'_> {
+    let buf = buf.strip_prefix(b"\xef\xbb\xbf").unwrap_or(buf);
+    Lines {
+        lines: buf.lines(),
+        line_number: 0,
+    }
+}
+
+fn trim_trailing_spaces(line: &[u8]) -> &[u8] {
+    let mut last_space = None;
+    let mut bytes = line.iter().enumerate();
+    while let Some((pos, b)) = bytes.next() {
+        match b {
+            b' ' => {
+                last_space.get_or_insert(pos);
+            }
+            b'\\' => {
+                if bytes.next().is_none() {
+                    return line;
+                }
+                last_space = None;
+            }
+            _ => last_space = None,
+        }
+    }
+    last_space.map_or(line, |pos| &line[..pos])
+}
+
+impl pattern::Pattern for Mapping {
+    fn bytes_to_patterns(bytes: &[u8]) -> Vec<Self> {
+        parse(bytes)
+            .map(|(pattern, sequence_number)| Mapping {
+                pattern,
+                sequence_number,
+            })
+            .collect()
+    }
+}
+
+/// A list of exclude patterns read from a single source, typically a file.
+pub type PatternList = pattern::List<Mapping>;
+
+impl PatternList {
+    /// Return the last pattern matching `rela_path`, which is relative to the root of the working tree, or `None` if there
+    /// is no matching pattern or if `rela_path` is outside of our [`base`][PatternList::base] directory.
+    /// `is_dir` is true if `rela_path` is a directory, and `case` controls case-sensitivity.
+    ///
+    /// Note that the returned pattern may be negative.
+    pub fn pattern_matching_relative_path(&self, rela_path: &BStr, is_dir: bool, case: Case) -> Option<&Mapping> {
+        let rela_path = self.strip_base(rela_path)?;
+        self.patterns
+            .iter()
+            .rev()
+            .find(|mapping| mapping.pattern.matches_repo_relative_path(rela_path, is_dir, case))
+    }
+}
+
+/// A pattern that matched a path, along with information about where it was defined for `git check-ignore -v`-style output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Match<'a> {
+    /// The matching pattern. If it is [negative][Pattern::is_negative()], the path is explicitly _not_ excluded.
+    pub pattern: &'a Pattern,
+    /// The file the pattern was read from, if any.
+    pub source: Option<&'a Path>,
+    /// The line number of the pattern in `source`, starting at 1.
+    pub sequence_number: usize,
+}
+
+/// The pattern excluding a directory or one of its parents, with the file it was defined in.
+type ExcludedBy = Option<(Mapping, Option<PathBuf>)>;
+
+/// A stack of per-directory exclude files like `.gitignore`, following the directories of the paths it is queried with,
+/// along with patterns that apply to the whole working tree.
+///
+/// Just like in git, patterns from the deepest directory have the highest precedence, followed by those of its parent
+/// directories, those in `.git/info/exclude` and finally those in `core.excludesFile`. Patterns given on the command-line
+/// can be set as [`overrides`][Stack::overrides] which override all other patterns.
+/// Directories that are excluded exclude all of their content as well, and their exclude files are not read.
+pub struct Stack {
+    case: Case,
+    /// The name of the exclude file in each directory, usually `.gitignore`.
+    pub exclude_filename_per_dir: BString,
+    /// Patterns with the highest precedence, like those given on the command-line.
+    pub overrides: PatternList,
+    /// Patterns applying to the whole working tree with the lowest precedence, in order of their precedence, typically
+    /// from `.git/info/exclude` followed by `core.excludesFile`.
+    pub globals: Vec<PatternList>,
+    directories: pattern::Stack<Mapping, ExcludedBy>,
+}
+
+impl Stack {
+    /// Create a new stack for the working tree at `root` without any global patterns, matching with `case`.
+    pub fn new(root: impl Into<PathBuf>, case: Case) -> Self {
+        Stack {
+            case,
+            exclude_filename_per_dir: ".gitignore".into(),
+            overrides: PatternList::default(),
+            globals: Vec::new(),
+            directories: pattern::Stack::new(root.into()),
+        }
+    }
+
+    /// Create a new stack for the working tree at `root`, reading global patterns from `info/exclude` in `git_dir` and
+    /// from `excludes_file`, typically the value of `core.excludesFile`, if set. Files that don't exist are ignored.
+    pub fn from_git_dir(
+        root: impl Into<PathBuf>,
+        git_dir: impl AsRef<Path>,
+        excludes_file: Option<PathBuf>,
+        case: Case,
+    ) -> std::io::Result<Self> {
+        let mut stack = Stack::new(root, case);
+        let mut buf = Vec::new();
+        let info_exclude = git_dir.as_ref().join("info").join("exclude");
+        for path in std::iter::once(info_exclude).chain(excludes_file) {
+            if let Some(list) = PatternList::from_file(path, None, &mut buf)? {
+                stack.globals.push(list);
+            }
+        }
+        Ok(stack)
+    }
+
+    /// Return true if `rela_path`, relative to the root of the working tree, is excluded, with `is_dir` being true if it is a directory.
+    pub fn is_excluded(&mut self, rela_path: &BStr, is_dir: bool) -> std::io::Result<bool> {
+        Ok(self
+            .matching_exclude_pattern(rela_path, is_dir)?
+            .map_or(false, |m| !m.pattern.is_negative()))
+    }
+
+    /// Return the pattern deciding whether `rela_path`, relative to the root of the working tree, is excluded, with `is_dir`
+    /// being true if it is a directory, or `None` if no pattern matches.
+    ///
+    /// The stack is adjusted to the directory containing `rela_path`, reading exclude files of directories as needed, which
+    /// makes queries in traversal order efficient.
+    /// If a leading directory of `rela_path` is excluded, the pattern excluding it is returned.
+    pub fn matching_exclude_pattern(&mut self, rela_path: &BStr, is_dir: bool) -> std::io::Result<Option<Match<'_>>> {
+        let dir = rela_path
+            .rfind_byte(b'/')
+            .map_or_else(|| b"".as_bstr(), |pos| rela_path[..pos].as_bstr());
+        let (overrides, globals, case) = (&self.overrides, &self.globals, self.case);
+        self.directories
+            .set_directory(dir, self.exclude_filename_per_dir.as_bstr(), |stack, dir| {
+                let parent = stack.directories().last().expect("root");
+                let excluded_by = match &parent.data {
+                    Some(excluded_by) => Some(excluded_by.clone()),
+                    None => pattern_matching_relative_path(overrides, stack.directories(), globals, case, dir, true)
+                        .filter(|m| !m.pattern.is_negative())
+                        .map(|m| {
+                            (
+                                Mapping {
+                                    pattern: m.pattern.clone(),
+                                    sequence_number: m.sequence_number,
+                                },
+                                m.source.map(ToOwned::to_owned),
+                            )
+                        }),
+                };
+                let read_exclude_file = excluded_by.is_none();
+                (excluded_by, read_exclude_file)
+            })?;
+        let directories = self.directories.directories();
+        let top = directories.last().expect("root is always present");
+        if let Some((mapping, source)) = &top.data {
+            return Ok(Some(Match {
+                pattern: &mapping.pattern,
+                source: source.as_deref(),
+                sequence_number: mapping.sequence_number,
+            }));
+        }
+        Ok(pattern_matching_relative_path(
+            &self.overrides,
+            directories,
+            &self.globals,
+            self.case,
+            rela_path,
+            is_dir,
+        ))
+    }
+}
+
+/// Return the first pattern matching `rela_path` in order of precedence, see [`Stack`].
+fn pattern_matching_relative_path<'a>(
+    overrides: &'a PatternList,
+    directories: &'a [pattern::Directory<Mapping, ExcludedBy>],
+    globals: &'a [PatternList],
+    case: Case,
+    rela_path: &BStr,
+    is_dir: bool,
+) -> Option<Match<'a>> {
+    std::iter::once(overrides)
+        .chain(directories.iter().rev().map(|dir| &dir.patterns))
+        .chain(globals.iter())
+        .find_map(|list| {
+            list.pattern_matching_relative_path(rela_path, is_dir, case)
+                .map(|mapping| Match {
+                    pattern: &mapping.pattern,
+                    source: list.source.as_deref(),
+                    sequence_number: mapping.sequence_number,
+                })
+        })
+}
@@ -0,0 +1,291 @@
+use std::{
+    collections::HashMap,
+    io::{self, Read, Write},
+    path::Path,
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+use bstr::{BStr, BString, ByteSlice};
+use git_packetline::{decode::PacketLineOrWantedSize, PacketLineRef};
+
+use super::{Driver, Error};
+
+/// The direction in which a driver converts content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Operation {
+    /// Convert content from the working tree for storage in git.
+    Clean,
+    /// Convert content from git for storage in the working tree.
+    Smudge,
+}
+
+impl Operation {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Operation::Clean => "clean",
+            Operation::Smudge => "smudge",
+        }
+    }
+}
+
+/// Apply `driver` to `src` for `operation` on the file at `rela_path` into `out`, returning true if `out` was written.
+///
+/// Long-running `processes` are started as needed and kept for reuse. Just like git, failures of drivers that aren't
+/// required leave the content as is.
+pub(crate) fn apply(
+    driver: &Driver,
+    operation: Operation,
+    src: &[u8],
+    rela_path: &BStr,
+    worktree: &Path,
+    processes: &mut HashMap<BString, Process>,
+    out: &mut Vec<u8>,
+) -> Result<bool, Error> {
+    let filtered = match driver.process.as_ref().filter(|cmd| !cmd.is_empty()) {
+        Some(cmd) => {
+            if !processes.contains_key(&driver.name) {
+                if let Ok(process) = Process::spawn(cmd.as_bstr(), worktree) {
+                    processes.insert(driver.name.clone(), process);
+                }
+            }
+            match processes
+                .get_mut(&driver.name)
+                .map(|process| process.apply(operation, src, rela_path, out))
+            {
+                Some(Ok(filtered)) => filtered,
+                Some(Err(_)) => {
+                    // The process is in an unknown state, so we start a new one next time.
+                    processes.remove(&driver.name);
+                    false
+                }
+                None => false,
+            }
+        }
+        None => {
+            let cmd = match operation {
+                Operation::Clean => driver.clean.as_ref(),
+                Operation::Smudge => driver.smudge.as_ref(),
+            };
+            match cmd {
+                Some(cmd) => run_once(cmd.as_bstr(), src, rela_path, worktree, out)?,
+                None => false,
+            }
+        }
+    };
+    if !filtered && driver.required {
+        return Err(Error::Driver {
+            path: rela_path.into(),
+            name: driver.name.clone(),
+            operation: operation.as_str(),
+        });
+    }
+    Ok(filtered)
+}
+
+fn shell(cmd: &BStr, worktree: &Path) -> io::Result<Command> {
+    let cmd = cmd
+        .to_os_str()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    let mut shell = Command::new("sh");
+    shell
+        .arg("-c")
+        .arg(cmd)
+        .current_dir(worktree)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped());
+    Ok(shell)
+}
+
+/// Quote `path` for use as single argument in a shell command, like git's `sq_quote_buf()`.
+fn shell_quote(path: &BStr) -> BString {
+    let mut quoted = BString::from("'");
+    for b in path.iter() {
+        match b {
+            b'\'' | b'!' => {
+                quoted.extend_from_slice(b"'\\");
+                quoted.push(*b);
+                quoted.push(b'\'');
+            }
+            _ => quoted.push(*b),
+        }
+    }
+    quoted.push(b'\'');
+    quoted
+}
+
+/// Run `cmd` with `%f` replaced by `rela_path` once, passing `src` on stdin and reading `out` from its stdout.
+fn run_once(cmd: &BStr, src: &[u8], rela_path: &BStr, worktree: &Path, out: &mut Vec<u8>) -> Result<bool, Error> {
+    let cmd = cmd.replace("%f", shell_quote(rela_path));
+    let mut child = match shell(cmd.as_bstr(), worktree)?.spawn() {
+        Ok(child) => child,
+        Err(_) => return Ok(false),
+    };
+
+    let mut stdin = child.stdin.take().expect("configured");
+    let input = src.to_vec();
+    let writer = std::thread::spawn(move || match stdin.write_all(&input) {
+        // Filters may not need all of their input.
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        res => res,
+    });
+    out.clear();
+    let read = child.stdout.take().expect("configured").read_to_end(out);
+    let written = writer.join().expect("no panic while writing");
+    let status = child.wait()?;
+    Ok(read.is_ok() && written.is_ok() && status.success())
+}
+
+/// The maximum amount of data in a single packet line.
+const MAX_DATA_LEN: usize = 65516;
+
+/// A filter process speaking git's long-running process protocol, see `gitattributes(5)`.
+///
+/// We only use packet line decoding of `git-packetline` as its readers and writers require a choice between blocking and
+/// async IO, which is up to the application.
+pub(crate) struct Process {
+    child: Child,
+    /// The input of the process, which is closed to let it shut down.
+    input: Option<ChildStdin>,
+    output: io::BufReader<ChildStdout>,
+    capabilities: Vec<BString>,
+    buf: Vec<u8>,
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        self.input.take();
+        self.child.wait().ok();
+    }
+}
+
+impl Process {
+    /// Start `cmd` and perform the handshake.
+    fn spawn(cmd: &BStr, worktree: &Path) -> io::Result<Self> {
+        let mut child = shell(cmd, worktree)?.spawn()?;
+        let mut process = Process {
+            input: child.stdin.take(),
+            output: io::BufReader::new(child.stdout.take().expect("configured")),
+            child,
+            capabilities: Vec::new(),
+            buf: Vec::new(),
+        };
+
+        process.write_text(b"git-filter-client")?;
+        process.write_text(b"version=2")?;
+        process.write_flush()?;
+        let lines = process.read_text_lines()?;
+        if lines.first().map(|line| line.as_slice()) != Some(b"git-filter-server")
+            || !lines.iter().any(|line| line == "version=2")
+        {
+            return Err(invalid_data(
+                "the filter process doesn't support version 2 of the protocol",
+            ));
+        }
+
+        process.write_text(b"capability=clean")?;
+        process.write_text(b"capability=smudge")?;
+        process.write_flush()?;
+        process.capabilities = process
+            .read_text_lines()?
+            .into_iter()
+            .filter_map(|line| line.strip_prefix(b"capability=").map(Into::into))
+            .collect();
+        Ok(process)
+    }
+
+    /// Let the process convert `src` of the file at `rela_path` into `out` if it supports `operation`, returning true if
+    /// `out` was written.
+    fn apply(&mut self, operation: Operation, src: &[u8], rela_path: &BStr, out: &mut Vec<u8>) -> io::Result<bool> {
+        let operation = operation.as_str();
+        if !self.capabilities.iter().any(|capability| capability == operation) {
+            return Ok(false);
+        }
+        let mut line = BString::from("command=");
+        line.extend_from_slice(operation.as_bytes());
+        self.write_text(&line)?;
+        let mut line = BString::from("pathname=");
+        line.extend_from_slice(rela_path);
+        self.write_text(&line)?;
+        self.write_flush()?;
+        for chunk in src.chunks(MAX_DATA_LEN) {
+            self.write_packet(chunk)?;
+        }
+        self.write_flush()?;
+
+        match self.read_status()? {
+            Some(status) if status == "success" => {}
+            Some(status) if status == "abort" => {
+                self.capabilities.retain(|capability| capability != operation);
+                return Ok(false);
+            }
+            _ => return Ok(false),
+        }
+        out.clear();
+        while self.read_packet()? {
+            out.extend_from_slice(&self.buf);
+        }
+        // An empty list keeps the previous status.
+        Ok(self.read_status()?.map_or(true, |status| status == "success"))
+    }
+
+    fn input(&mut self) -> &mut ChildStdin {
+        self.input.as_mut().expect("only taken on drop")
+    }
+
+    fn write_packet(&mut self, data: &[u8]) -> io::Result<()> {
+        let input = self.input();
+        write!(input, "{:04x}", data.len() + 4)?;
+        input.write_all(data)
+    }
+
+    fn write_text(&mut self, text: &[u8]) -> io::Result<()> {
+        let mut line = Vec::with_capacity(text.len() + 1);
+        line.extend_from_slice(text);
+        line.push(b'\n');
+        self.write_packet(&line)
+    }
+
+    fn write_flush(&mut self) -> io::Result<()> {
+        let input = self.input();
+        input.write_all(b"0000")?;
+        input.flush()
+    }
+
+    /// Read the next packet line into `buf`, returning false if it was a flush packet.
+    fn read_packet(&mut self) -> io::Result<bool> {
+        let mut prefix = [0u8; 4];
+        self.output.read_exact(&mut prefix)?;
+        match git_packetline::decode::hex_prefix(&prefix).map_err(|err| invalid_data(err.to_string()))? {
+            PacketLineOrWantedSize::Line(PacketLineRef::Flush) => Ok(false),
+            PacketLineOrWantedSize::Line(line) => Err(invalid_data(format!("unexpected packet line {:?}", line))),
+            PacketLineOrWantedSize::Wanted(len) => {
+                self.buf.resize(len as usize, 0);
+                self.output.read_exact(&mut self.buf)?;
+                Ok(true)
+            }
+        }
+    }
+
+    /// Read text lines without their trailing newline until the next flush packet.
+    fn read_text_lines(&mut self) -> io::Result<Vec<BString>> {
+        let mut lines = Vec::new();
+        while self.read_packet()? {
+            let line = self.buf.strip_suffix(b"\n").unwrap_or(&self.buf);
+            lines.push(line.into());
+        }
+        Ok(lines)
+    }
+
+    /// Read a list of keys and values until the next flush packet, returning the last value of the `status` key, if any.
+    fn read_status(&mut self) -> io::Result<Option<BString>> {
+        Ok(self
+            .read_text_lines()?
+            .into_iter()
+            .rev()
+            .find_map(|line| line.strip_prefix(b"status=").map(Into::into)))
+    }
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
@@ -0,0 +1,134 @@
+use bstr::{BStr, ByteSlice};
+use encoding_rs::Encoding;
+
+use super::Error;
+
+/// The encoding named by the `working-tree-encoding` attribute.
+enum Target {
+    /// UTF-16, which `encoding_rs` can only decode, with the `bom` being required if it's not known whether it's big-endian.
+    Utf16 { big_endian: bool, bom: bool },
+    /// Any other encoding known to `encoding_rs`.
+    Other(&'static Encoding),
+}
+
+const BOM_BE: &[u8] = b"\xfe\xff";
+const BOM_LE: &[u8] = b"\xff\xfe";
+
+/// Return the encoding called `name`, or `None` if it is UTF-8 which needs no conversion.
+fn lookup(name: &BStr, rela_path: &BStr) -> Result<Option<Target>, Error> {
+    let normalized: Vec<u8> = name
+        .iter()
+        .filter(|b| !matches!(b, b'-' | b'_'))
+        .map(|b| b.to_ascii_uppercase())
+        .collect();
+    Ok(Some(match normalized.as_slice() {
+        b"UTF8" => return Ok(None),
+        b"UTF16" => Target::Utf16 {
+            big_endian: true,
+            bom: true,
+        },
+        b"UTF16BE" => Target::Utf16 {
+            big_endian: true,
+            bom: false,
+        },
+        b"UTF16LE" => Target::Utf16 {
+            big_endian: false,
+            bom: false,
+        },
+        _ => match Encoding::for_label(name) {
+            Some(encoding) if encoding != encoding_rs::UTF_16BE && encoding != encoding_rs::UTF_16LE => {
+                Target::Other(encoding)
+            }
+            _ => {
+                return Err(Error::UnknownEncoding {
+                    path: rela_path.into(),
+                    encoding: name.into(),
+                })
+            }
+        },
+    }))
+}
+
+/// Decode `src` from the encoding called `name` into UTF-8 in `out`, returning true if `out` was written.
+pub(crate) fn to_git(src: &[u8], name: &BStr, rela_path: &BStr, out: &mut Vec<u8>) -> Result<bool, Error> {
+    let target = match lookup(name, rela_path)? {
+        Some(target) => target,
+        None => return Ok(false),
+    };
+    let err = |message: &'static str| Error::Encoding {
+        path: rela_path.into(),
+        encoding: name.into(),
+        message,
+    };
+    let decoded = match target {
+        Target::Utf16 { big_endian, bom } => {
+            let has_bom = src.starts_with(BOM_BE) || src.starts_with(BOM_LE);
+            let (encoding, src) = if bom {
+                if !has_bom {
+                    return Err(err("a byte order mark is required"));
+                }
+                let encoding = if src.starts_with(BOM_BE) {
+                    encoding_rs::UTF_16BE
+                } else {
+                    encoding_rs::UTF_16LE
+                };
+                (encoding, &src[2..])
+            } else {
+                if has_bom {
+                    return Err(err("a byte order mark is prohibited"));
+                }
+                let encoding = if big_endian {
+                    encoding_rs::UTF_16BE
+                } else {
+                    encoding_rs::UTF_16LE
+                };
+                (encoding, src)
+            };
+            encoding.decode_without_bom_handling_and_without_replacement(src)
+        }
+        Target::Other(encoding) => encoding.decode_without_bom_handling_and_without_replacement(src),
+    }
+    .ok_or_else(|| err("the content isn't valid in this encoding"))?;
+    out.clear();
+    out.extend_from_slice(decoded.as_bytes());
+    Ok(true)
+}
+
+/// Encode the UTF-8 in `src` with the encoding called `name` into `out`, returning true if `out` was written.
+pub(crate) fn to_worktree(src: &[u8], name: &BStr, rela_path: &BStr, out: &mut Vec<u8>) -> Result<bool, Error> {
+    let target = match lookup(name, rela_path)? {
+        Some(target) => target,
+        None => return Ok(false),
+    };
+    let err = |message: &'static str| Error::Encoding {
+        path: rela_path.into(),
+        encoding: name.into(),
+        message,
+    };
+    let src = src
+        .to_str()
+        .map_err(|_| err("the content in the repository isn't valid UTF-8"))?;
+    out.clear();
+    match target {
+        Target::Utf16 { big_endian, bom } => {
+            if bom {
+                out.extend_from_slice(if big_endian { BOM_BE } else { BOM_LE });
+            }
+            for unit in src.encode_utf16() {
+                out.extend_from_slice(&if big_endian {
+                    unit.to_be_bytes()
+                } else {
+                    unit.to_le_bytes()
+                });
+            }
+        }
+        Target::Other(encoding) => {
+            let (encoded, _, had_unmappable_characters) = encoding.encode(src);
+            if had_unmappable_characters {
+                return Err(err("the content can't be represented in this encoding"));
+            }
+            out.extend_from_slice(&encoded);
+        }
+    }
+    Ok(true)
+}
@@ -0,0 +1,307 @@
+use crate::attributes::State;
+
+/// The value of `core.autocrlf`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutoCrlf {
+    /// Don't convert line endings of files without `text` or `eol` attributes.
+    Disabled,
+    /// Convert line endings of text files to LF when adding them and to CRLF when checking them out.
+    Enabled,
+    /// Convert line endings of text files to LF when adding them, but don't touch them on checkout.
+    Input,
+}
+
+impl Default for AutoCrlf {
+    fn default() -> Self {
+        AutoCrlf::Disabled
+    }
+}
+
+/// A line ending as configured with `core.eol` or the `eol` attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Line endings are `\n`.
+    Lf,
+    /// Line endings are `\r\n`.
+    Crlf,
+}
+
+/// The value of `core.safecrlf`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SafeCrlf {
+    /// Don't check if line ending conversions are reversible.
+    Off,
+    /// Report line ending conversions that aren't reversible.
+    Warn,
+    /// Fail if line ending conversions aren't reversible.
+    Fail,
+}
+
+impl Default for SafeCrlf {
+    fn default() -> Self {
+        SafeCrlf::Warn
+    }
+}
+
+/// The configuration affecting line ending conversions, for paths that don't define them with attributes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Configuration {
+    /// The value of `core.autocrlf`.
+    pub auto_crlf: AutoCrlf,
+    /// The value of `core.eol`, or `None` for `native` line endings which are CRLF on windows only.
+    pub eol: Option<Mode>,
+}
+
+impl Configuration {
+    /// Return true if text files use CRLF line endings in the working tree.
+    fn text_eol_is_crlf(&self) -> bool {
+        match self.auto_crlf {
+            AutoCrlf::Enabled => true,
+            AutoCrlf::Input => false,
+            AutoCrlf::Disabled => match self.eol {
+                Some(mode) => mode == Mode::Crlf,
+                None => cfg!(windows),
+            },
+        }
+    }
+}
+
+/// Line endings of a file that wouldn't survive adding it and checking it out again, as detected if `core.safecrlf` is set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundTrip {
+    /// The file has CRLF line endings which would be checked out as LF.
+    CrlfToLf,
+    /// The file has LF line endings which would be checked out as CRLF.
+    LfToCrlf,
+}
+
+impl std::fmt::Display for RoundTrip {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            RoundTrip::CrlfToLf => "CRLF would be replaced by LF",
+            RoundTrip::LfToCrlf => "LF would be replaced by CRLF",
+        })
+    }
+}
+
+/// How the line endings of a path are converted, like git's `crlf_action`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Action {
+    Binary,
+    Text,
+    TextInput,
+    TextCrlf,
+    Auto,
+    AutoInput,
+    AutoCrlf,
+}
+
+impl Action {
+    /// Derive the action from the `text`, the legacy `crlf` and the `eol` attributes, falling back to `config`.
+    pub(crate) fn from_attributes(
+        text: Option<&State>,
+        crlf: Option<&State>,
+        eol: Option<&State>,
+        config: &Configuration,
+    ) -> Self {
+        fn from_state(state: &State) -> Option<Action> {
+            match state {
+                State::Set => Some(Action::Text),
+                State::Unset => Some(Action::Binary),
+                State::Value(value) if value == "input" => Some(Action::TextInput),
+                State::Value(value) if value == "auto" => Some(Action::Auto),
+                _ => None,
+            }
+        }
+        let mut action = text.and_then(from_state).or_else(|| crlf.and_then(from_state));
+        if action != Some(Action::Binary) {
+            let is_auto = action == Some(Action::Auto);
+            match eol {
+                Some(State::Value(value)) if value == "lf" => {
+                    action = Some(if is_auto { Action::AutoInput } else { Action::TextInput })
+                }
+                Some(State::Value(value)) if value == "crlf" => {
+                    action = Some(if is_auto { Action::AutoCrlf } else { Action::TextCrlf })
+                }
+                _ => {}
+            }
+        }
+        match action {
+            Some(Action::Text) if config.text_eol_is_crlf() => Action::TextCrlf,
+            Some(Action::Text) => Action::TextInput,
+            Some(action) => action,
+            None => match config.auto_crlf {
+                AutoCrlf::Disabled => Action::Binary,
+                AutoCrlf::Enabled => Action::AutoCrlf,
+                AutoCrlf::Input => Action::AutoInput,
+            },
+        }
+    }
+
+    fn is_auto(&self) -> bool {
+        matches!(self, Action::Auto | Action::AutoInput | Action::AutoCrlf)
+    }
+
+    /// The line endings the path has in the working tree, or `None` if they are left alone.
+    fn output_eol(&self, config: &Configuration) -> Option<Mode> {
+        match self {
+            Action::Binary => None,
+            Action::TextCrlf | Action::AutoCrlf => Some(Mode::Crlf),
+            Action::TextInput | Action::AutoInput => Some(Mode::Lf),
+            Action::Text | Action::Auto => Some(if config.text_eol_is_crlf() {
+                Mode::Crlf
+            } else {
+                Mode::Lf
+            }),
+        }
+    }
+}
+
+/// Statistics about the content of a file to determine if it's binary and which line endings it has.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct Stats {
+    nul: usize,
+    lone_cr: usize,
+    lone_lf: usize,
+    crlf: usize,
+    printable: usize,
+    non_printable: usize,
+}
+
+impl Stats {
+    pub(crate) fn from_bytes(buf: &[u8]) -> Self {
+        let mut stats = Stats::default();
+        let mut bytes = buf.iter().peekable();
+        while let Some(b) = bytes.next() {
+            match b {
+                b'\r' => {
+                    if bytes.peek() == Some(&&b'\n') {
+                        bytes.next();
+                        stats.crlf += 1;
+                    } else {
+                        stats.lone_cr += 1;
+                    }
+                }
+                b'\n' => stats.lone_lf += 1,
+                127 => stats.non_printable += 1,
+                b'\x08' | b'\t' | b'\x1b' | b'\x0c' => stats.printable += 1,
+                0 => {
+                    stats.nul += 1;
+                    stats.non_printable += 1;
+                }
+                b if *b < 32 => stats.non_printable += 1,
+                _ => stats.printable += 1,
+            }
+        }
+        // A trailing EOF character as used by some DOS editors isn't considered binary.
+        if buf.last() == Some(&b'\x1a') {
+            stats.non_printable -= 1;
+        }
+        stats
+    }
+
+    pub(crate) fn is_binary(&self) -> bool {
+        self.lone_cr > 0 || self.nul > 0 || (self.printable >> 7) < self.non_printable
+    }
+
+    fn will_convert_lf_to_crlf(&self, action: Action, config: &Configuration) -> bool {
+        if action.output_eol(config) != Some(Mode::Crlf) || self.lone_lf == 0 {
+            return false;
+        }
+        !(action.is_auto() && (self.lone_cr > 0 || self.crlf > 0 || self.is_binary()))
+    }
+}
+
+/// Return true if `blob`, the version of a file in the index, is text with CRLF line endings, which prevents automatic
+/// conversions to keep it unchanged.
+fn has_crlf(blob: &[u8]) -> bool {
+    if !blob.contains(&b'\r') {
+        return false;
+    }
+    let stats = Stats::from_bytes(blob);
+    !stats.is_binary() && stats.crlf > 0
+}
+
+/// Convert CRLF line endings in `src` to LF into `out` if `action` says so, returning true if `out` was written.
+///
+/// `index_object` is called to obtain the version of the file in the index if needed, and `safe_crlf` controls if lossy
+/// conversions are detected and returned.
+pub(crate) fn to_git(
+    src: &[u8],
+    action: Action,
+    config: &Configuration,
+    safe_crlf: SafeCrlf,
+    index_object: impl FnOnce(&mut Vec<u8>) -> Option<()>,
+    out: &mut Vec<u8>,
+) -> (bool, Option<RoundTrip>) {
+    if action == Action::Binary || src.is_empty() {
+        return (false, None);
+    }
+    let stats = Stats::from_bytes(src);
+    let mut convert_crlf_to_lf = stats.crlf > 0;
+    if action.is_auto() {
+        if stats.is_binary() {
+            return (false, None);
+        }
+        if convert_crlf_to_lf {
+            let mut buf = Vec::new();
+            if index_object(&mut buf).is_some() && has_crlf(&buf) {
+                convert_crlf_to_lf = false;
+            }
+        }
+    }
+
+    let mut round_trip = None;
+    if safe_crlf != SafeCrlf::Off {
+        let mut new_stats = stats;
+        if convert_crlf_to_lf {
+            new_stats.lone_lf += new_stats.crlf;
+            new_stats.crlf = 0;
+        }
+        if new_stats.will_convert_lf_to_crlf(action, config) {
+            new_stats.crlf += new_stats.lone_lf;
+            new_stats.lone_lf = 0;
+        }
+        if stats.crlf > 0 && new_stats.crlf == 0 {
+            round_trip = Some(RoundTrip::CrlfToLf);
+        } else if stats.lone_lf > 0 && new_stats.lone_lf == 0 {
+            round_trip = Some(RoundTrip::LfToCrlf);
+        }
+    }
+    if !convert_crlf_to_lf {
+        return (false, round_trip);
+    }
+
+    out.clear();
+    out.reserve(src.len() - stats.crlf);
+    let mut bytes = src.iter().peekable();
+    while let Some(b) = bytes.next() {
+        if !(*b == b'\r' && bytes.peek() == Some(&&b'\n')) {
+            out.push(*b);
+        }
+    }
+    (true, round_trip)
+}
+
+/// Convert LF line endings in `src` to CRLF into `out` if `action` says so, returning true if `out` was written.
+pub(crate) fn to_worktree(src: &[u8], action: Action, config: &Configuration, out: &mut Vec<u8>) -> bool {
+    if action == Action::Binary || src.is_empty() {
+        return false;
+    }
+    let stats = Stats::from_bytes(src);
+    if !stats.will_convert_lf_to_crlf(action, config) {
+        return false;
+    }
+
+    out.clear();
+    out.reserve(src.len() + stats.lone_lf);
+    let mut last = 0;
+    for b in src {
+        if *b == b'\n' && last != b'\r' {
+            out.push(b'\r');
+        }
+        out.push(*b);
+        last = *b;
+    }
+    true
+}
@@ -0,0 +1,68 @@
+use bstr::ByteSlice;
+
+/// Collapse all `$Id: <anything> $` keywords in `src` to `$Id$` into `out`, returning true if `out` was written.
+pub(crate) fn undo(src: &[u8], out: &mut Vec<u8>) -> bool {
+    if src.find(b"$Id").is_none() {
+        return false;
+    }
+    out.clear();
+    let mut src = src;
+    while let Some(dollar) = src.find_byte(b'$') {
+        out.extend_from_slice(&src[..=dollar]);
+        src = &src[dollar + 1..];
+        if src.len() > 3 && src.starts_with(b"Id:") {
+            let end = match src[3..].find_byte(b'$') {
+                Some(pos) => pos + 3,
+                None => break,
+            };
+            if src[3..end].contains(&b'\n') {
+                continue;
+            }
+            out.extend_from_slice(b"Id$");
+            src = &src[end + 1..];
+        }
+    }
+    out.extend_from_slice(src);
+    true
+}
+
+/// Expand all `$Id$` keywords in `src` to `$Id: <id> $` into `out`, with `<id>` being the id of the blob with the content
+/// of `src`, returning true if `out` was written. Keywords that were already expanded are updated.
+pub(crate) fn apply(src: &[u8], object_hash: git_hash::Kind, out: &mut Vec<u8>) -> bool {
+    if src.find(b"$Id").is_none() {
+        return false;
+    }
+    let id = crate::blob_id(src, object_hash);
+    out.clear();
+    let mut src = src;
+    while let Some(dollar) = src.find_byte(b'$') {
+        out.extend_from_slice(&src[..=dollar]);
+        src = &src[dollar + 1..];
+        if src.len() < 3 || !src.starts_with(b"Id") {
+            continue;
+        }
+        match src[2] {
+            b'$' => src = &src[3..],
+            b':' => {
+                let end = match src[3..].find_byte(b'$') {
+                    Some(pos) => pos + 3,
+                    None => break,
+                };
+                if src[3..end].contains(&b'\n') {
+                    continue;
+                }
+                // Spaces in unexpected places indicate an id of another version control system, which we keep.
+                if end > 4 && src[4..end].find_byte(b' ').map_or(false, |pos| pos + 4 < end - 1) {
+                    continue;
+                }
+                src = &src[end + 1..];
+            }
+            _ => continue,
+        }
+        out.extend_from_slice(b"Id: ");
+        id.write_hex_to(&mut *out).expect("writes to memory always succeed");
+        out.extend_from_slice(b" $");
+    }
+    out.extend_from_slice(src);
+    true
+}
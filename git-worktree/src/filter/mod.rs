@@ -0,0 +1,267 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use bstr::{BStr, BString, ByteSlice};
+
+use crate::attributes::{self, State};
+
+mod driver;
+mod encoding;
+///
+pub mod eol;
+mod ident;
+
+mod error {
+    use bstr::BString;
+    use quick_error::quick_error;
+
+    use crate::filter::eol;
+
+    quick_error! {
+        /// The error returned by [`Pipeline::to_git()`][super::Pipeline::to_git()] and [`Pipeline::to_worktree()`][super::Pipeline::to_worktree()].
+        #[derive(Debug)]
+        #[allow(missing_docs)]
+        pub enum Error {
+            Io(err: std::io::Error) {
+                display("An IO error occurred while running a filter")
+                from()
+                source(err)
+            }
+            RoundTrip { path: BString, round_trip: eol::RoundTrip } {
+                display("{} in {}", round_trip, path)
+            }
+            UnknownEncoding { path: BString, encoding: BString } {
+                display("The working-tree-encoding '{}' of {} isn't supported", encoding, path)
+            }
+            Encoding { path: BString, encoding: BString, message: &'static str } {
+                display("Could not convert {} from or to '{}' as {}", path, encoding, message)
+            }
+            Driver { path: BString, name: BString, operation: &'static str } {
+                display("The required {} filter '{}' failed on {}", operation, name, path)
+            }
+        }
+    }
+}
+pub use error::Error;
+
+/// The names of all attributes the [`Pipeline`] needs, for use with [`attributes::Outcome::with_selection()`].
+pub const ATTRIBUTES: &[&str] = &["text", "eol", "crlf", "ident", "filter", "working-tree-encoding"];
+
+/// A filter driver as configured with `filter.<name>.*` and used by paths with the `filter=<name>` attribute.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Driver {
+    /// The name of the driver.
+    pub name: BString,
+    /// The command converting content from the working tree for storage in git, with `%f` being replaced by the
+    /// quoted path of the file.
+    pub clean: Option<BString>,
+    /// The command converting content from git for storage in the working tree, with `%f` being replaced by the
+    /// quoted path of the file.
+    pub smudge: Option<BString>,
+    /// The command starting a long-running process that converts all files using git's pkt-line based protocol.
+    /// It takes precedence over `clean` and `smudge`.
+    pub process: Option<BString>,
+    /// If true, failing to convert a file is an error. Otherwise it is used as is.
+    pub required: bool,
+}
+
+/// Options for the [`Pipeline`], typically derived from configuration.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// The configuration controlling line ending conversions of paths without `text` or `eol` attributes.
+    pub eol: eol::Configuration,
+    /// The value of `core.safecrlf`, controlling if irreversible line ending conversions are reported.
+    pub safe_crlf: eol::SafeCrlf,
+    /// All configured filter drivers.
+    pub drivers: Vec<Driver>,
+    /// The kind of hash to compute ids of blobs with when expanding `$Id$`.
+    pub object_hash: git_hash::Kind,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            eol: Default::default(),
+            safe_crlf: Default::default(),
+            drivers: Vec::new(),
+            object_hash: git_hash::Kind::Sha1,
+        }
+    }
+}
+
+/// Converts content between its form in the working tree and in git, controlled by attributes and configuration.
+///
+/// Converting to git runs the `clean` filter driver, decodes the `working-tree-encoding`, converts line endings to LF and
+/// collapses `$Id$` keywords, while converting to the working tree does the same steps in reverse.
+pub struct Pipeline {
+    worktree: PathBuf,
+    options: Options,
+    /// Long-running filter processes by driver name.
+    processes: HashMap<BString, driver::Process>,
+    buf: Vec<u8>,
+}
+
+/// Long-running filter processes aren't cloned, but are started again by the clone as needed.
+impl Clone for Pipeline {
+    fn clone(&self) -> Self {
+        Pipeline::new(self.worktree.clone(), self.options.clone())
+    }
+}
+
+/// The attributes affecting conversions of a single path.
+struct Attributes<'a> {
+    text: Option<&'a State>,
+    eol: Option<&'a State>,
+    crlf: Option<&'a State>,
+    ident: bool,
+    filter: Option<&'a BStr>,
+    encoding: Option<&'a BStr>,
+}
+
+impl<'a> Attributes<'a> {
+    fn from_outcome(outcome: &'a attributes::Outcome) -> Self {
+        let state = |name: &str| {
+            outcome
+                .get(name)
+                .map(|m| &m.state)
+                .filter(|s| **s != State::Unspecified)
+        };
+        let value = |name: &str| match state(name) {
+            Some(State::Value(value)) => Some(value.as_bstr()),
+            _ => None,
+        };
+        Attributes {
+            text: state("text"),
+            eol: state("eol"),
+            crlf: state("crlf"),
+            ident: state("ident") == Some(&State::Set),
+            filter: value("filter"),
+            encoding: value("working-tree-encoding"),
+        }
+    }
+}
+
+impl Pipeline {
+    /// Create a new pipeline for the working tree at `worktree`, in which filter drivers are run.
+    pub fn new(worktree: impl Into<PathBuf>, options: Options) -> Self {
+        Pipeline {
+            worktree: worktree.into(),
+            options,
+            processes: HashMap::new(),
+            buf: Vec::new(),
+        }
+    }
+
+    /// Return the options the pipeline was created with.
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// Convert `src`, the content of the file at `rela_path` in the working tree, into its form in git in `out`,
+    /// according to `attributes` which should contain all [`ATTRIBUTES`] of `rela_path`.
+    ///
+    /// `index_object` is called to fill the given buffer with the version of `rela_path` in the index, or to return
+    /// `None` if there is none, as files with CRLF line endings in the index keep them when converting automatically.
+    ///
+    /// If line endings would change when checking out the result and `core.safecrlf` is set to warn, this is returned
+    /// for the caller to report it.
+    pub fn to_git(
+        &mut self,
+        src: &[u8],
+        rela_path: &BStr,
+        attributes: &attributes::Outcome,
+        index_object: impl FnOnce(&mut Vec<u8>) -> Option<()>,
+        out: &mut Vec<u8>,
+    ) -> Result<Option<eol::RoundTrip>, Error> {
+        let attrs = Attributes::from_outcome(attributes);
+        out.clear();
+        out.extend_from_slice(src);
+
+        if let Some(driver) = find_driver(&self.options.drivers, attrs.filter) {
+            if driver::apply(
+                driver,
+                driver::Operation::Clean,
+                out,
+                rela_path,
+                &self.worktree,
+                &mut self.processes,
+                &mut self.buf,
+            )? {
+                std::mem::swap(out, &mut self.buf);
+            }
+        }
+        if let Some(encoding) = attrs.encoding {
+            if encoding::to_git(out, encoding, rela_path, &mut self.buf)? {
+                std::mem::swap(out, &mut self.buf);
+            }
+        }
+        let action = eol::Action::from_attributes(attrs.text, attrs.crlf, attrs.eol, &self.options.eol);
+        let (converted, round_trip) = eol::to_git(
+            out,
+            action,
+            &self.options.eol,
+            self.options.safe_crlf,
+            index_object,
+            &mut self.buf,
+        );
+        if converted {
+            std::mem::swap(out, &mut self.buf);
+        }
+        if attrs.ident && ident::undo(out, &mut self.buf) {
+            std::mem::swap(out, &mut self.buf);
+        }
+
+        match round_trip {
+            Some(round_trip) if self.options.safe_crlf == eol::SafeCrlf::Fail => Err(Error::RoundTrip {
+                path: rela_path.into(),
+                round_trip,
+            }),
+            round_trip => Ok(round_trip),
+        }
+    }
+
+    /// Convert `src`, the content of the file at `rela_path` in git, into its form in the working tree in `out`,
+    /// according to `attributes` which should contain all [`ATTRIBUTES`] of `rela_path`.
+    pub fn to_worktree(
+        &mut self,
+        src: &[u8],
+        rela_path: &BStr,
+        attributes: &attributes::Outcome,
+        out: &mut Vec<u8>,
+    ) -> Result<(), Error> {
+        let attrs = Attributes::from_outcome(attributes);
+        out.clear();
+        out.extend_from_slice(src);
+
+        if attrs.ident && ident::apply(out, self.options.object_hash, &mut self.buf) {
+            std::mem::swap(out, &mut self.buf);
+        }
+        let action = eol::Action::from_attributes(attrs.text, attrs.crlf, attrs.eol, &self.options.eol);
+        if eol::to_worktree(out, action, &self.options.eol, &mut self.buf) {
+            std::mem::swap(out, &mut self.buf);
+        }
+        if let Some(encoding) = attrs.encoding {
+            if encoding::to_worktree(out, encoding, rela_path, &mut self.buf)? {
+                std::mem::swap(out, &mut self.buf);
+            }
+        }
+        if let Some(driver) = find_driver(&self.options.drivers, attrs.filter) {
+            if driver::apply(
+                driver,
+                driver::Operation::Smudge,
+                out,
+                rela_path,
+                &self.worktree,
+                &mut self.processes,
+                &mut self.buf,
+            )? {
+                std::mem::swap(out, &mut self.buf);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn find_driver<'a>(drivers: &'a [Driver], name: Option<&BStr>) -> Option<&'a Driver> {
+    let name = name?;
+    drivers.iter().find(|driver| driver.name == name)
+}
@@ -1 +1,36 @@
 #![forbid(unsafe_code, rust_2018_idioms)]
+#![deny(missing_docs)]
+//! Everything around working trees, like checking out files, matching exclude patterns, looking up attributes, converting content with filters, sparse checkouts, finding untracked files or comparing the index with the working tree.
+
+///
+pub mod attributes;
+///
+pub mod checkout;
+///
+pub mod excludes;
+///
+pub mod filter;
+///
+pub mod pattern;
+///
+pub mod sparse;
+///
+pub mod status;
+///
+pub mod untracked;
+
+pub use git_glob as glob;
+
+/// Like git, we consider `stat` racy if its file was modified in the same instant or after the index was written at
+/// `index_timestamp`, as changes in that instant can't be detected by looking at the stat information alone.
+fn is_racy(stat: &git_index::entry::Stat, index_timestamp: filetime::FileTime) -> bool {
+    (stat.mtime.secs as i64, stat.mtime.nsecs) >= (index_timestamp.unix_seconds(), index_timestamp.nanoseconds())
+}
+
+/// Compute the id of a blob with `data` as content.
+fn blob_id(data: &[u8], object_hash: git_hash::Kind) -> git_hash::ObjectId {
+    let mut hasher = git_features::hash::hasher(object_hash);
+    hasher.update(format!("blob {}\0", data.len()).as_bytes());
+    hasher.update(data);
+    git_hash::ObjectId::from(hasher.digest())
+}
@@ -0,0 +1,189 @@
+use std::path::{Path, PathBuf};
+
+use bstr::{BStr, BString, ByteSlice, ByteVec};
+
+/// A pattern as read from a `.gitignore` or `.gitattributes`-style file.
+pub trait Pattern: Clone + PartialEq + Eq + std::fmt::Debug {
+    /// Parse all patterns in `bytes`, the content of a file.
+    fn bytes_to_patterns(bytes: &[u8]) -> Vec<Self>;
+
+    /// Return false if this pattern must be ignored if it is defined in a file of a sub-directory, like attribute macros.
+    fn may_be_defined_in_sub_directory(&self) -> bool {
+        true
+    }
+}
+
+/// A list of patterns read from a single source, typically a file, where later patterns override earlier ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct List<T: Pattern> {
+    /// All patterns in the order they were defined in.
+    pub patterns: Vec<T>,
+    /// The file the patterns were read from, if any.
+    pub source: Option<PathBuf>,
+    /// The directory containing the source of per-directory patterns relative to the root of the working tree, with a
+    /// trailing slash, or `None` if the patterns apply to the whole working tree.
+    pub base: Option<BString>,
+}
+
+impl<T: Pattern> Default for List<T> {
+    fn default() -> Self {
+        List {
+            patterns: Vec::new(),
+            source: None,
+            base: None,
+        }
+    }
+}
+
+impl<T: Pattern> List<T> {
+    /// Parse all patterns in `bytes` and associate them with their `source` and `base` directory, see [`List::base`].
+    pub fn from_bytes(bytes: &[u8], source: impl Into<Option<PathBuf>>, base: Option<BString>) -> Self {
+        List {
+            patterns: T::bytes_to_patterns(bytes),
+            source: source.into(),
+            base,
+        }
+    }
+
+    /// Read the file at `path` with `buf` and parse it like [`from_bytes()`][List::from_bytes()], or return `None`
+    /// if it doesn't exist.
+    pub fn from_file(
+        path: impl Into<PathBuf>,
+        base: Option<BString>,
+        buf: &mut Vec<u8>,
+    ) -> std::io::Result<Option<Self>> {
+        let path = path.into();
+        Ok(read_file(&path, buf)?.then(|| List::from_bytes(buf, path, base)))
+    }
+
+    /// Return `rela_path`, relative to the root of the working tree, relative to our [`base`][List::base] directory, or
+    /// `None` if it is outside of it.
+    pub(crate) fn strip_base<'a>(&self, rela_path: &'a BStr) -> Option<&'a BStr> {
+        match &self.base {
+            Some(base) => rela_path.strip_prefix(base.as_slice()).map(ByteSlice::as_bstr),
+            None => Some(rela_path),
+        }
+    }
+}
+
+/// Read the file at `path` into `buf` and return true, or return false if it doesn't exist.
+pub(crate) fn read_file(path: &Path, buf: &mut Vec<u8>) -> std::io::Result<bool> {
+    use std::io::Read;
+    buf.clear();
+    match std::fs::File::open(path) {
+        Ok(mut file) => {
+            file.read_to_end(buf)?;
+            Ok(true)
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// A directory on the [`Stack`] along with the patterns defined in it.
+#[derive(Clone)]
+pub(crate) struct Directory<T: Pattern, D> {
+    /// The length of the path of this directory in `Stack::current`.
+    path_len: usize,
+    pub patterns: List<T>,
+    /// Additional information the user of the stack associated with the directory.
+    pub data: D,
+}
+
+/// A stack of per-directory pattern files like `.gitignore` or `.gitattributes`, following the directories of the paths
+/// it is queried with.
+#[derive(Clone)]
+pub(crate) struct Stack<T: Pattern, D> {
+    root: PathBuf,
+    /// The directories on the stack, with the root of the working tree at the bottom once it was read.
+    directories: Vec<Directory<T, D>>,
+    /// The path of the top-most directory, relative to `root`.
+    current: BString,
+    buf: Vec<u8>,
+}
+
+impl<T: Pattern, D: Default> Stack<T, D> {
+    /// Create a new stack for the working tree at `root`.
+    pub fn new(root: PathBuf) -> Self {
+        Stack {
+            root,
+            directories: Vec::new(),
+            current: BString::default(),
+            buf: Vec::new(),
+        }
+    }
+
+    /// Return all directories on the stack, starting at the root of the working tree.
+    pub fn directories(&self) -> &[Directory<T, D>] {
+        &self.directories
+    }
+
+    /// Pop and push directories so that the top of the stack is `dir`, relative to the root of the working tree, reading
+    /// `filename_per_dir` in each of them.
+    ///
+    /// `push(stack, path)` is called before pushing the directory at `path` with the stack holding its parents, and
+    /// returns the data to associate with it along with whether or not its file should be read. The root is always read.
+    pub fn set_directory(
+        &mut self,
+        dir: &BStr,
+        filename_per_dir: &BStr,
+        mut push: impl FnMut(&Self, &BStr) -> (D, bool),
+    ) -> std::io::Result<()> {
+        if self.directories.is_empty() {
+            let patterns = self.read_file(None, filename_per_dir)?;
+            self.directories.push(Directory {
+                path_len: 0,
+                patterns,
+                data: D::default(),
+            });
+        }
+        while self.directories.len() > 1 && !is_leading_directory(self.current.as_bstr(), dir) {
+            self.directories.pop();
+            let path_len = self.directories.last().expect("root").path_len;
+            self.current.truncate(path_len);
+        }
+
+        let remaining = dir[self.current.len()..].trim_start_with(|c| c == '/');
+        if remaining.is_empty() {
+            return Ok(());
+        }
+        for component in remaining.split_str("/") {
+            if !self.current.is_empty() {
+                self.current.push_byte(b'/');
+            }
+            self.current.push_str(component);
+
+            let (data, read) = push(self, self.current.as_bstr());
+            let patterns = if read {
+                let mut base = self.current.clone();
+                base.push_byte(b'/');
+                let mut patterns = self.read_file(Some(base), filename_per_dir)?;
+                patterns.patterns.retain(Pattern::may_be_defined_in_sub_directory);
+                patterns
+            } else {
+                List::default()
+            };
+            self.directories.push(Directory {
+                path_len: self.current.len(),
+                patterns,
+                data,
+            });
+        }
+        Ok(())
+    }
+
+    fn read_file(&mut self, base: Option<BString>, filename: &BStr) -> std::io::Result<List<T>> {
+        let mut path = self.root.clone();
+        if let Some(base) = &base {
+            path.push(base.to_path().expect("UTF-8 conversion succeeds"));
+        }
+        path.push(filename.to_path().expect("UTF-8 conversion succeeds"));
+        Ok(List::from_file(path, base, &mut self.buf)?.unwrap_or_default())
+    }
+}
+
+/// Return true if `dir` is `leading_dir` or inside of it.
+fn is_leading_directory(leading_dir: &BStr, dir: &BStr) -> bool {
+    leading_dir.is_empty()
+        || (dir.starts_with(leading_dir) && (dir.len() == leading_dir.len() || dir[leading_dir.len()] == b'/'))
+}
@@ -0,0 +1,504 @@
+use std::{
+    collections::BTreeSet,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::atomic::AtomicBool,
+};
+
+use bstr::{BStr, BString, ByteSlice, ByteVec};
+use git_hash::oid;
+use git_index::entry;
+
+use crate::{
+    attributes, checkout,
+    excludes::PatternList,
+    filter,
+    glob::{pattern, Case},
+    status,
+};
+
+mod error {
+    use quick_error::quick_error;
+
+    use crate::checkout;
+
+    quick_error! {
+        /// The error returned by [`update_worktree()`][super::update_worktree()].
+        #[derive(Debug)]
+        #[allow(missing_docs)]
+        pub enum Error {
+            Io(err: std::io::Error) {
+                display("An IO error occurred while removing files that are no longer included")
+                source(err)
+                from()
+            }
+            Checkout(err: checkout::Error) {
+                display("Files that are now included could not be checked out")
+                source(err)
+                from()
+            }
+            Filter(err: crate::filter::Error) {
+                display("Files that are no longer included could not be converted to compare them with the index")
+                source(err)
+                from()
+            }
+        }
+    }
+}
+pub use error::Error;
+
+/// The directories of a sparse checkout in cone mode, as written by `git sparse-checkout set --cone`.
+///
+/// All files in the root directory and in `parents` are included, along with everything inside of `recursive`
+/// directories.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Cone {
+    /// Directories whose entire content is included, without leading or trailing slash.
+    pub recursive: BTreeSet<BString>,
+    /// Directories of which only files directly inside of them are included, typically the leading directories of
+    /// `recursive` ones.
+    pub parents: BTreeSet<BString>,
+}
+
+impl Cone {
+    /// Create a cone including the entire content of all `directories` and the files in their leading directories.
+    pub fn from_directories(directories: impl IntoIterator<Item = impl AsRef<[u8]>>) -> Self {
+        let mut cone = Cone::default();
+        for directory in directories {
+            cone.add(directory.as_ref().as_bstr());
+        }
+        cone
+    }
+
+    /// Include the entire content of `directory` and the files in its leading directories.
+    pub fn add(&mut self, directory: &BStr) {
+        let directory = directory.trim_with(|c| c == '/');
+        if directory.is_empty() {
+            return;
+        }
+        let mut pos = 0;
+        while let Some(slash) = directory[pos..].find_byte(b'/') {
+            pos += slash;
+            self.parents.insert(directory[..pos].into());
+            pos += 1;
+        }
+        self.recursive.insert(directory.into());
+    }
+
+    /// Parse cone patterns in `bytes`, or return `None` if there are patterns which can't be represented in cone mode.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let mut cone = Cone::default();
+        for line in bytes.lines() {
+            let line = line.trim_end();
+            if line.is_empty() || line[0] == b'#' || line == b"/*" || line == b"!/*/" {
+                continue;
+            }
+            if let Some(directory) = line.strip_prefix(b"!/").and_then(|l| l.strip_suffix(b"/*/")) {
+                // Directories are first included recursively and then have their sub-directories excluded again.
+                let directory = unescape(directory)?;
+                if !cone.recursive.remove(&directory) && !cone.parents.contains(&directory) {
+                    return None;
+                }
+                cone.parents.insert(directory);
+            } else if let Some(directory) = line.strip_prefix(b"/").and_then(|l| l.strip_suffix(b"/")) {
+                let directory = unescape(directory)?;
+                if directory.is_empty() {
+                    return None;
+                }
+                cone.recursive.insert(directory);
+            } else {
+                return None;
+            }
+        }
+        Some(cone)
+    }
+
+    /// Write all patterns of this cone to `out` in the same way as git does.
+    pub fn write_to(&self, mut out: impl Write) -> std::io::Result<()> {
+        out.write_all(b"/*\n!/*/\n")?;
+        for parent in &self.parents {
+            if self.recursive.contains(parent) || self.has_recursive_parent(parent.as_bstr()) {
+                continue;
+            }
+            let parent = escape(parent.as_bstr());
+            out.write_all(b"/")?;
+            out.write_all(&parent)?;
+            out.write_all(b"/\n!/")?;
+            out.write_all(&parent)?;
+            out.write_all(b"/*/\n")?;
+        }
+        for directory in &self.recursive {
+            if self.has_recursive_parent(directory.as_bstr()) {
+                continue;
+            }
+            out.write_all(b"/")?;
+            out.write_all(&escape(directory.as_bstr()))?;
+            out.write_all(b"/\n")?;
+        }
+        Ok(())
+    }
+
+    /// Return true if `rela_path` is included, with `is_dir` indicating whether it is a directory.
+    pub fn is_included(&self, rela_path: &BStr, is_dir: bool, case: Case) -> bool {
+        let contains = |set: &BTreeSet<BString>, path: &[u8]| match case {
+            Case::Sensitive => set.contains(path.as_bstr()),
+            Case::Fold => set.iter().any(|item| item.eq_ignore_ascii_case(path)),
+        };
+        let directory = if is_dir {
+            rela_path.as_bytes()
+        } else {
+            match rela_path.rfind_byte(b'/') {
+                Some(pos) => &rela_path[..pos],
+                None => return true,
+            }
+        };
+        if directory.is_empty() || contains(&self.parents, directory) || contains(&self.recursive, directory) {
+            return true;
+        }
+        directory
+            .find_iter("/")
+            .any(|pos| contains(&self.recursive, &directory[..pos]))
+    }
+
+    fn has_recursive_parent(&self, directory: &BStr) -> bool {
+        directory
+            .find_iter("/")
+            .any(|pos| self.recursive.contains(directory[..pos].as_bstr()))
+    }
+}
+
+/// Escape glob characters in `directory` for use in a pattern.
+fn escape(directory: &BStr) -> BString {
+    let mut out = BString::default();
+    for b in directory.iter() {
+        if matches!(b, b'*' | b'?' | b'[' | b'\\') {
+            out.push(b'\\');
+        }
+        out.push(*b);
+    }
+    out
+}
+
+/// Remove escapes from `directory`, or return `None` if it contains wildcards.
+fn unescape(directory: &[u8]) -> Option<BString> {
+    let mut out = BString::default();
+    let mut bytes = directory.iter();
+    while let Some(b) = bytes.next() {
+        match b {
+            b'\\' => out.push(*bytes.next()?),
+            b'*' | b'?' | b'[' => return None,
+            _ => out.push(*b),
+        }
+    }
+    Some(out)
+}
+
+/// The patterns in `info/sparse-checkout` deciding which files of the index are checked out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Patterns {
+    /// `.gitignore`-style patterns, where paths matched by non-negative patterns are included.
+    Full(PatternList),
+    /// Directories whose content is included, as used if `core.sparseCheckoutCone` is set.
+    Cone(Cone),
+}
+
+impl Patterns {
+    /// Parse `bytes` as cone patterns if `cone` is true, or as `.gitignore`-style patterns otherwise.
+    ///
+    /// Just like git, we fall back to `.gitignore`-style patterns if the patterns can't be represented as cone.
+    pub fn from_bytes(bytes: &[u8], source: impl Into<Option<PathBuf>>, cone: bool) -> Self {
+        match cone.then(|| Cone::from_bytes(bytes)).flatten() {
+            Some(cone) => Patterns::Cone(cone),
+            None => Patterns::Full(PatternList::from_bytes(bytes, source, None)),
+        }
+    }
+
+    /// Read the file at `path`, typically `info/sparse-checkout`, with `buf` and parse it like
+    /// [`from_bytes()`][Patterns::from_bytes()], or return `None` if it doesn't exist.
+    pub fn from_file(path: impl Into<PathBuf>, cone: bool, buf: &mut Vec<u8>) -> std::io::Result<Option<Self>> {
+        let path = path.into();
+        Ok(crate::pattern::read_file(&path, buf)?.then(|| Patterns::from_bytes(buf, path, cone)))
+    }
+
+    /// Write the patterns to `out` in the format of `info/sparse-checkout`.
+    pub fn write_to(&self, mut out: impl Write) -> std::io::Result<()> {
+        match self {
+            Patterns::Cone(cone) => cone.write_to(out),
+            Patterns::Full(list) => {
+                for mapping in &list.patterns {
+                    let pattern = &mapping.pattern;
+                    if pattern.is_negative() {
+                        out.write_all(b"!")?;
+                    }
+                    if pattern.mode.contains(pattern::Mode::ABSOLUTE) {
+                        out.write_all(b"/")?;
+                    }
+                    out.write_all(&pattern.text)?;
+                    if pattern.mode.contains(pattern::Mode::MUST_BE_DIR) {
+                        out.write_all(b"/")?;
+                    }
+                    out.write_all(b"\n")?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Write the patterns into the file at `path`, typically `info/sparse-checkout`, creating its leading directories
+    /// as needed.
+    pub fn write_to_file(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let mut buf = Vec::new();
+        self.write_to(&mut buf)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, buf)
+    }
+
+    /// Return true if `rela_path`, relative to the root of the working tree, is included in the sparse checkout, with
+    /// `is_dir` indicating whether it is a directory and `case` controlling case-sensitivity.
+    ///
+    /// With `.gitignore`-style patterns, the last pattern matching the path decides, or the one matching its closest
+    /// leading directory if there is none. Paths that aren't matched at all are excluded.
+    pub fn is_included(&self, rela_path: &BStr, is_dir: bool, case: Case) -> bool {
+        let list = match self {
+            Patterns::Cone(cone) => return cone.is_included(rela_path, is_dir, case),
+            Patterns::Full(list) => list,
+        };
+        let mut path = rela_path;
+        let mut is_dir = is_dir;
+        loop {
+            if let Some(mapping) = list.pattern_matching_relative_path(path, is_dir, case) {
+                return !mapping.pattern.is_negative();
+            }
+            match path.rfind_byte(b'/') {
+                Some(pos) => {
+                    path = path[..pos].as_bstr();
+                    is_dir = true;
+                }
+                None => return false,
+            }
+        }
+    }
+}
+
+/// The result of [`apply()`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Changes {
+    /// The amount of entries that are now marked as _skip-worktree_, which are also marked with
+    /// [`entry::Flags::WORKTREE_REMOVE`].
+    pub skipped: usize,
+    /// The amount of entries that are no longer marked as _skip-worktree_, which are also marked with
+    /// [`entry::Flags::UPDATE`].
+    pub included: usize,
+}
+
+/// Set or clear the _skip-worktree_ bit of all entries in `index` depending on whether they are included by `patterns`,
+/// matching paths with `case`.
+///
+/// Entries whose bit changes are marked for [`update_worktree()`] to remove or check them out. Unmerged entries and
+/// directories of a sparse index are left alone.
+pub fn apply(index: &mut git_index::State, patterns: &Patterns, case: Case) -> Changes {
+    let mut changes = Changes::default();
+    let included: Vec<_> = index
+        .entries()
+        .iter()
+        .map(|entry| patterns.is_included(entry.path(index), false, case))
+        .collect();
+    for (entry, is_included) in index.entries_mut().iter_mut().zip(included) {
+        if entry.stage() != 0 || entry.mode.is_sparse() {
+            continue;
+        }
+        let is_skipped = entry.flags.contains(entry::Flags::SKIP_WORKTREE);
+        if is_included && is_skipped {
+            entry
+                .flags
+                .remove(entry::Flags::SKIP_WORKTREE | entry::Flags::WORKTREE_REMOVE);
+            entry.flags.insert(entry::Flags::UPDATE);
+            changes.included += 1;
+        } else if !is_included && !is_skipped {
+            entry.flags.remove(entry::Flags::UPDATE);
+            entry
+                .flags
+                .insert(entry::Flags::SKIP_WORKTREE | entry::Flags::WORKTREE_REMOVE);
+            changes.skipped += 1;
+        }
+    }
+    changes
+}
+
+/// Options for [`update_worktree()`].
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Control how files that are now included are checked out.
+    pub checkout: checkout::Options,
+    /// Control how files that are no longer included are compared with their entries to see if they were modified, along
+    /// with the kind of hash to use when their content has to be hashed.
+    pub status: status::Options,
+}
+
+/// The result of [`update_worktree()`].
+#[derive(Debug, Default)]
+pub struct Outcome {
+    /// The amount of files that were removed as they are no longer included.
+    pub files_removed: usize,
+    /// Paths that are no longer included but were kept as they were modified, which remain in the working tree without
+    /// the _skip-worktree_ bit.
+    pub modified: Vec<BString>,
+    /// The outcome of checking out entries that are now included.
+    pub checkout: checkout::Outcome,
+}
+
+/// Bring the working tree at `dir` up to date with `index` after [`apply()`] marked entries to be removed or checked out,
+/// using `find`, `should_interrupt`, `filters` and `attributes` like [`checkout()`][checkout::checkout()] does.
+///
+/// Files of entries which are no longer included are removed along with directories that become empty, unless they were
+/// modified. Just like in [`status::index_to_worktree()`], files are considered unmodified if their stat information
+/// matches the one of their entry, and are otherwise converted with `filters` according to `attributes` and hashed.
+/// All marks are cleared afterwards.
+pub fn update_worktree<Find>(
+    index: &mut git_index::State,
+    dir: impl AsRef<Path>,
+    find: Find,
+    should_interrupt: &AtomicBool,
+    filters: &mut filter::Pipeline,
+    attributes: &mut attributes::Stack,
+    options: Options,
+) -> Result<Outcome, Error>
+where
+    Find: for<'a> FnMut(&oid, &'a mut Vec<u8>) -> Option<git_object::BlobRef<'a>> + Send + Clone,
+{
+    let dir = dir.as_ref();
+    let mut out = Outcome::default();
+    let mut kept = Vec::new();
+    let mut compare = Compare {
+        index: &*index,
+        find: find.clone(),
+        filters,
+        attributes,
+        attribute_outcome: attributes::Outcome::with_selection(filter::ATTRIBUTES.iter().copied()),
+        options: &options.status,
+        buf: Vec::new(),
+        converted: Vec::new(),
+    };
+    for (entry_index, entry) in index.entries().iter().enumerate() {
+        if !entry.flags.contains(entry::Flags::WORKTREE_REMOVE) {
+            continue;
+        }
+        let rela_path = entry.path(index);
+        let path = dir.join(rela_path.to_path().expect("UTF-8 conversion succeeds"));
+        match compare.is_unmodified(entry, rela_path, &path)? {
+            Some(true) => {
+                if entry.mode == entry::Mode::COMMIT {
+                    std::fs::remove_dir(&path).ok();
+                } else {
+                    std::fs::remove_file(&path)?;
+                }
+                remove_empty_leading_directories(dir, &path);
+                out.files_removed += 1;
+            }
+            Some(false) => {
+                kept.push(entry_index);
+                out.modified.push(rela_path.into());
+            }
+            None => {}
+        }
+    }
+
+    let entries = index.entries_mut();
+    for entry_index in kept {
+        entries[entry_index].flags.remove(entry::Flags::SKIP_WORKTREE);
+    }
+    out.checkout = checkout::checkout(
+        index,
+        dir,
+        find,
+        should_interrupt,
+        filters,
+        attributes,
+        checkout::Options {
+            marked_entries_only: true,
+            ..options.checkout
+        },
+    )?;
+    for entry in index.entries_mut() {
+        entry.flags.remove(entry::Flags::UPDATE | entry::Flags::WORKTREE_REMOVE);
+    }
+    Ok(out)
+}
+
+struct Compare<'a, Find> {
+    index: &'a git_index::State,
+    find: Find,
+    filters: &'a mut filter::Pipeline,
+    attributes: &'a mut attributes::Stack,
+    attribute_outcome: attributes::Outcome,
+    options: &'a status::Options,
+    buf: Vec<u8>,
+    converted: Vec<u8>,
+}
+
+impl<'a, Find> Compare<'a, Find>
+where
+    Find: for<'b> FnMut(&oid, &'b mut Vec<u8>) -> Option<git_object::BlobRef<'b>>,
+{
+    /// Return true if the file at `path` has the content of `entry` at `rela_path`, or `None` if it doesn't exist.
+    fn is_unmodified(
+        &mut self,
+        entry: &git_index::Entry,
+        rela_path: &BStr,
+        path: &Path,
+    ) -> Result<Option<bool>, Error> {
+        let metadata = match std::fs::symlink_metadata(path) {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        if entry.mode == entry::Mode::COMMIT {
+            return Ok(Some(metadata.is_dir()));
+        }
+        let file_type = metadata.file_type();
+        if !file_type.is_symlink() && !file_type.is_file() {
+            return Ok(Some(false));
+        }
+        if self.options.is_same_stat(&entry.stat, &entry::Stat::from_fs(&metadata))
+            && !crate::is_racy(&entry.stat, self.index.timestamp())
+        {
+            return Ok(Some(true));
+        }
+
+        self.buf.clear();
+        let content = if file_type.is_symlink() {
+            self.buf
+                .extend_from_slice(&Vec::from_path_lossy(&std::fs::read_link(path)?));
+            &self.buf
+        } else {
+            use std::io::Read;
+            std::fs::File::open(path)?.read_to_end(&mut self.buf)?;
+            self.attributes
+                .matching_attributes(rela_path, false, &mut self.attribute_outcome)?;
+            let find = &mut self.find;
+            self.filters.to_git(
+                &self.buf,
+                rela_path,
+                &self.attribute_outcome,
+                |buf| find(&entry.id, buf).map(|_| ()),
+                &mut self.converted,
+            )?;
+            &self.converted
+        };
+        Ok(Some(crate::blob_id(content, self.options.object_hash) == entry.id))
+    }
+}
+
+/// Remove the directories leading to `path` below `root` as long as they are empty.
+fn remove_empty_leading_directories(root: &Path, path: &Path) {
+    let mut dir = path.parent();
+    while let Some(parent) = dir.filter(|dir| *dir != root && dir.starts_with(root)) {
+        if std::fs::remove_dir(parent).is_err() {
+            break;
+        }
+        dir = parent.parent();
+    }
+}
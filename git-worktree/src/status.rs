@@ -0,0 +1,380 @@
+use std::path::Path;
+
+use bstr::{BStr, BString, ByteSlice, ByteVec};
+use git_features::parallel::{self, in_parallel_if, Reduce};
+use git_hash::oid;
+use git_index::{entry, Entry};
+
+use crate::{attributes, filter, untracked};
+
+mod error {
+    use quick_error::quick_error;
+
+    quick_error! {
+        /// The error returned by [`index_to_worktree()`][super::index_to_worktree()].
+        #[derive(Debug)]
+        #[allow(missing_docs)]
+        pub enum Error {
+            Io(err: std::io::Error) {
+                display("An IO error occurred while comparing the index with the working tree")
+                source(err)
+                from()
+            }
+            Untracked(err: crate::untracked::Error) {
+                display("Could not find untracked files")
+                source(err)
+                from()
+            }
+            Filter(err: crate::filter::Error) {
+                display("A file could not be converted for comparison with the index")
+                source(err)
+                from()
+            }
+        }
+    }
+}
+pub use error::Error;
+
+/// Configure how the index is compared with the working tree, typically derived from `core.*` configuration.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// If true, the executable bit of files is compared to the one stored in the index, like `core.fileMode`.
+    ///
+    /// It defaults to false on platforms without an executable bit, as it would otherwise always be considered removed.
+    pub executable_bit: bool,
+    /// If true, symbolic links are expected to be checked out as such, like `core.symlinks`. Otherwise they are expected
+    /// to be files containing the link target.
+    pub symlinks: bool,
+    /// If true, changes to the ctime of files are considered a modification that needs checking, like `core.trustCTime`.
+    pub trust_ctime: bool,
+    /// If false, only the mtime in seconds and the size are compared, like `core.checkStat=minimal`.
+    pub check_stat: bool,
+    /// If true, nanoseconds of the ctime and mtime are compared as well.
+    pub use_nsec: bool,
+    /// If true, the device of files is compared as well.
+    pub use_stdev: bool,
+    /// The kind of hash to use when hashing files whose stat information doesn't allow to decide whether they changed.
+    pub object_hash: git_hash::Kind,
+    /// The amount of threads to use for obtaining the stat information of files, or `None` to use all logical cores.
+    pub thread_limit: Option<usize>,
+    /// If set, untracked files are looked for as well and the untracked cache is updated according to these options.
+    pub untracked: Option<untracked::Options>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            executable_bit: cfg!(unix),
+            symlinks: true,
+            trust_ctime: true,
+            check_stat: true,
+            use_nsec: false,
+            use_stdev: false,
+            object_hash: Default::default(),
+            thread_limit: None,
+            untracked: None,
+        }
+    }
+}
+
+impl Options {
+    /// Return true if the stat information `fs` of a file matches `index`, the one stored in its entry, as far as
+    /// these options are concerned.
+    pub(crate) fn is_same_stat(&self, index: &entry::Stat, fs: &entry::Stat) -> bool {
+        let Options {
+            trust_ctime,
+            check_stat,
+            use_nsec,
+            use_stdev,
+            ..
+        } = *self;
+        index.mtime.secs == fs.mtime.secs
+            && index.size == fs.size
+            && (!use_nsec || index.mtime.nsecs == fs.mtime.nsecs)
+            && (!check_stat
+                || ((!trust_ctime
+                    || (index.ctime.secs == fs.ctime.secs && (!use_nsec || index.ctime.nsecs == fs.ctime.nsecs)))
+                    && index.ino == fs.ino
+                    && index.uid == fs.uid
+                    && index.gid == fs.gid
+                    && (!use_stdev || index.dev == fs.dev)))
+    }
+}
+
+/// How an index entry differs from the working tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    /// The file or symlink was removed from the working tree, or replaced by a directory.
+    Removed,
+    /// The content or executable bit of the file differs from what is stored in the index.
+    Modified,
+    /// The type of the entry changed, for instance from a file to a symlink.
+    TypeChange,
+    /// The entry is marked as _intent-to-add_ and thus has no content in the index yet.
+    IntentToAdd,
+    /// The path has conflicts which are recorded in the index in stages 1 to 3. It is listed only once.
+    Unmerged,
+}
+
+/// A change between an index entry and the working tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Item {
+    /// The index of the entry in the index.
+    pub entry_index: usize,
+    /// The path of the entry relative to the root of the working tree.
+    pub path: BString,
+    /// The kind of change.
+    pub change: Change,
+}
+
+/// The outcome of [`index_to_worktree()`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    /// All changes in the order of their entries in the index.
+    pub changes: Vec<Item>,
+    /// All untracked paths if [`Options::untracked`] was set, see [`untracked::Outcome::untracked`].
+    pub untracked: Vec<BString>,
+    /// The amount of entries whose files had to be hashed as their stat information wasn't conclusive.
+    pub entries_hashed: usize,
+}
+
+/// Compare all entries of `index` with their files in `worktree` to find those which changed, configured by `options`.
+///
+/// Just like git, the stat information of files is compared to the one stored in entries, and only if it differs without
+/// indicating a change for sure, or if the file might have been changed in the same instant the index was written
+/// (_racy-git_), the file is hashed to compare it to the entry's object id.
+/// Before hashing, files are converted with `filters` according to their attributes in `attributes`, using
+/// `find(blob_id, buf) -> Option<BlobRef>` to obtain the content of entries if needed, just like when adding them.
+/// The latter three are cloned for each thread.
+/// Entries marked as _skip-worktree_ or _assume-valid_ are never considered changed.
+///
+/// If [`options.untracked`][Options::untracked] is set, untracked files for which `is_excluded(relative_path, is_dir)`
+/// returns false are looked for as well, see [`untracked::find()`].
+pub fn index_to_worktree<Find>(
+    worktree: impl AsRef<Path>,
+    index: &mut git_index::State,
+    find: Find,
+    filters: &filter::Pipeline,
+    attributes: &attributes::Stack,
+    options: Options,
+    is_excluded: impl FnMut(&BStr, bool) -> bool,
+) -> Result<Outcome, Error>
+where
+    Find: for<'a> FnMut(&oid, &'a mut Vec<u8>) -> Option<git_object::BlobRef<'a>> + Send + Clone,
+{
+    let worktree = worktree.as_ref();
+    let mut out = {
+        let index = &*index;
+        let options = &options;
+        let entries = index.entries();
+        let (chunk_size, thread_limit, _) =
+            parallel::optimize_chunk_size_and_thread_limit(500, Some(entries.len()), options.thread_limit, None);
+        in_parallel_if(
+            || entries.len() > chunk_size,
+            entries.chunks(chunk_size).enumerate(),
+            thread_limit,
+            move |_| Compare {
+                worktree,
+                index,
+                options,
+                find: find.clone(),
+                buf: Vec::new(),
+                converted: Vec::new(),
+                leading_dir: LeadingDirectory::default(),
+                filters: filters.clone(),
+                attributes: attributes.clone(),
+                attribute_outcome: attributes::Outcome::with_selection(filter::ATTRIBUTES.iter().copied()),
+                out: Outcome::default(),
+            },
+            move |(chunk_index, entries), state| {
+                for (offset, entry) in entries.iter().enumerate() {
+                    state.entry(chunk_index * chunk_size + offset, entry)?;
+                }
+                Ok(std::mem::take(&mut state.out))
+            },
+            Reducer::default(),
+        )?
+    };
+
+    if let Some(untracked_options) = options.untracked {
+        out.untracked = untracked::find(worktree, index, untracked_options, is_excluded)?.untracked;
+    }
+    Ok(out)
+}
+
+/// Remembers whether the last leading directory we checked is a symlink, or missing.
+#[derive(Default)]
+struct LeadingDirectory {
+    path: BString,
+    is_usable: bool,
+}
+
+struct Compare<'a, Find> {
+    worktree: &'a Path,
+    index: &'a git_index::State,
+    options: &'a Options,
+    find: Find,
+    buf: Vec<u8>,
+    converted: Vec<u8>,
+    leading_dir: LeadingDirectory,
+    filters: filter::Pipeline,
+    attributes: attributes::Stack,
+    attribute_outcome: attributes::Outcome,
+    out: Outcome,
+}
+
+impl<'a, Find> Compare<'a, Find>
+where
+    Find: for<'b> FnMut(&oid, &'b mut Vec<u8>) -> Option<git_object::BlobRef<'b>>,
+{
+    fn entry(&mut self, entry_index: usize, entry: &Entry) -> Result<(), Error> {
+        if entry
+            .flags
+            .intersects(entry::Flags::SKIP_WORKTREE | entry::Flags::ASSUME_VALID)
+        {
+            return Ok(());
+        }
+        let rela_path = entry.path(self.index);
+        let change = if entry.stage() != 0 {
+            let is_first_stage_of_path = entry_index == 0 || {
+                let previous = &self.index.entries()[entry_index - 1];
+                previous.path(self.index) != rela_path
+            };
+            is_first_stage_of_path.then(|| Change::Unmerged)
+        } else {
+            self.compare(entry, rela_path)?
+        };
+        if let Some(change) = change {
+            self.out.changes.push(Item {
+                entry_index,
+                path: rela_path.into(),
+                change,
+            });
+        }
+        Ok(())
+    }
+
+    fn compare(&mut self, entry: &Entry, rela_path: &BStr) -> Result<Option<Change>, Error> {
+        if !self.has_usable_leading_directory(rela_path)? {
+            return Ok(Some(Change::Removed));
+        }
+        let path = self
+            .worktree
+            .join(rela_path.to_path().expect("UTF-8 conversion succeeds"));
+        let metadata = match std::fs::symlink_metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Some(Change::Removed)),
+            Err(err) => return Err(err.into()),
+        };
+        if entry.flags.contains(entry::Flags::INTENT_TO_ADD) {
+            return Ok(Some(Change::IntentToAdd));
+        }
+
+        let file_type = metadata.file_type();
+        if entry.mode == entry::Mode::COMMIT {
+            // We can't see whether the submodule's HEAD changed here, only whether it is still a directory.
+            return Ok((!file_type.is_dir()).then(|| Change::TypeChange));
+        }
+        if file_type.is_dir() {
+            return Ok(Some(Change::Removed));
+        }
+        let is_symlink_in_index = entry.mode == entry::Mode::SYMLINK;
+        if file_type.is_symlink() != is_symlink_in_index && (self.options.symlinks || !is_symlink_in_index) {
+            return Ok(Some(Change::TypeChange));
+        }
+        if self.options.executable_bit
+            && !is_symlink_in_index
+            && git_features::fs::is_executable(&metadata) != (entry.mode == entry::Mode::FILE_EXECUTABLE)
+        {
+            return Ok(Some(Change::Modified));
+        }
+
+        let stat = entry::Stat::from_fs(&metadata);
+        // Entries with a size of 0 may have been smudged by git to mark them as racily clean, they need a closer look.
+        if stat.size != entry.stat.size && entry.stat.size != 0 {
+            return Ok(Some(Change::Modified));
+        }
+        if self.options.is_same_stat(&entry.stat, &stat) && !crate::is_racy(&entry.stat, self.index.timestamp()) {
+            return Ok(None);
+        }
+
+        self.out.entries_hashed += 1;
+        self.buf.clear();
+        let content = if file_type.is_symlink() {
+            let target = std::fs::read_link(&path)?;
+            self.buf.extend_from_slice(&Vec::from_path_lossy(&target));
+            &self.buf
+        } else {
+            use std::io::Read;
+            std::fs::File::open(&path)?.read_to_end(&mut self.buf)?;
+            self.attributes
+                .matching_attributes(rela_path, false, &mut self.attribute_outcome)?;
+            let find = &mut self.find;
+            self.filters.to_git(
+                &self.buf,
+                rela_path,
+                &self.attribute_outcome,
+                |buf| find(&entry.id, buf).map(|_| ()),
+                &mut self.converted,
+            )?;
+            &self.converted
+        };
+        let id = crate::blob_id(content, self.options.object_hash);
+        Ok((id != entry.id).then(|| Change::Modified))
+    }
+
+    /// Return true if all leading directories of `rela_path` are actual directories, and not symlinks.
+    fn has_usable_leading_directory(&mut self, rela_path: &BStr) -> std::io::Result<bool> {
+        let dir = match rela_path.rfind_byte(b'/') {
+            Some(pos) => rela_path[..pos].as_bstr(),
+            None => return Ok(true),
+        };
+        if self.leading_dir.path != dir {
+            let mut is_usable = true;
+            let mut path = self.worktree.to_owned();
+            for component in dir.split_str("/") {
+                path.push(component.to_path().expect("UTF-8 conversion succeeds"));
+                match std::fs::symlink_metadata(&path) {
+                    Ok(metadata) if metadata.is_dir() => continue,
+                    Ok(_) => {}
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(err) => return Err(err),
+                }
+                is_usable = false;
+                break;
+            }
+            self.leading_dir.path = dir.into();
+            self.leading_dir.is_usable = is_usable;
+        }
+        Ok(self.leading_dir.is_usable)
+    }
+}
+
+#[derive(Default)]
+struct Reducer {
+    chunks: Vec<(usize, Outcome)>,
+}
+
+impl Reduce for Reducer {
+    type Input = Result<Outcome, Error>;
+    type FeedProduce = ();
+    type Output = Outcome;
+    type Error = Error;
+
+    fn feed(&mut self, item: Self::Input) -> Result<Self::FeedProduce, Self::Error> {
+        let outcome = item?;
+        let first_entry_index = outcome.changes.first().map_or(usize::MAX, |item| item.entry_index);
+        self.chunks.push((first_entry_index, outcome));
+        Ok(())
+    }
+
+    fn finalize(mut self) -> Result<Self::Output, Self::Error> {
+        self.chunks.sort_by_key(|(first_entry_index, _)| *first_entry_index);
+        let mut out = Outcome::default();
+        for (_, chunk) in self.chunks {
+            out.changes.extend(chunk.changes);
+            out.entries_hashed += chunk.entries_hashed;
+        }
+        Ok(out)
+    }
+}
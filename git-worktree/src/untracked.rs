@@ -0,0 +1,489 @@
+use std::path::{Path, PathBuf};
+
+use bstr::{BStr, BString, ByteSlice, ByteVec};
+use git_index::{
+    entry,
+    extension::{
+        untracked_cache::{dir_flags, Directory, OidStat},
+        UntrackedCache,
+    },
+};
+
+mod error {
+    use quick_error::quick_error;
+
+    quick_error! {
+        /// The error returned by [`find()`][super::find()].
+        #[derive(Debug)]
+        #[allow(missing_docs)]
+        pub enum Error {
+            Io(err: std::io::Error) {
+                display("An IO error occurred while reading the working tree")
+                source(err)
+                from()
+            }
+        }
+    }
+}
+pub use error::Error;
+
+/// Configure how untracked files are found.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// If true, untracked directories are listed as a whole with a trailing slash instead of listing all files within them.
+    pub show_other_directories: bool,
+    /// If true and if `show_other_directories` is set, untracked directories that don't contain any untracked file aren't listed.
+    pub hide_empty_directories: bool,
+    /// The name of the file with exclude patterns in each directory, usually `.gitignore`.
+    pub exclude_filename_per_dir: BString,
+    /// The path to the `info/exclude` file of the repository, if it should be considered.
+    pub info_exclude: Option<PathBuf>,
+    /// The path to the file configured in `core.excludesFile`, if set.
+    pub excludes_file: Option<PathBuf>,
+    /// If true, the untracked cache of the index is used to avoid reading unchanged directories, and it will be updated or
+    /// created to reflect what was found.
+    pub use_untracked_cache: bool,
+    /// The kind of hash to use when hashing exclude files.
+    pub object_hash: git_hash::Kind,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            show_other_directories: true,
+            hide_empty_directories: true,
+            exclude_filename_per_dir: ".gitignore".into(),
+            info_exclude: None,
+            excludes_file: None,
+            use_untracked_cache: true,
+            object_hash: Default::default(),
+        }
+    }
+}
+
+impl Options {
+    fn dir_flags(&self) -> u32 {
+        let mut flags = 0;
+        if self.show_other_directories {
+            flags |= dir_flags::SHOW_OTHER_DIRECTORIES;
+        }
+        if self.hide_empty_directories {
+            flags |= dir_flags::HIDE_EMPTY_DIRECTORIES;
+        }
+        flags
+    }
+}
+
+/// The outcome of [`find()`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    /// All untracked paths relative to the root of the working tree in sorted order, with directories ending in a slash.
+    pub untracked: Vec<BString>,
+    /// The amount of directories whose entries had to be read from disk.
+    pub directories_read: usize,
+    /// The amount of directories whose untracked entries could be taken from the untracked cache.
+    pub directories_cached: usize,
+}
+
+/// Find all untracked files and directories in the working tree at `worktree` which aren't tracked by `index` and for which
+/// `is_excluded(relative_path, is_dir)` returns false, configured by `options`.
+///
+/// If enabled in `options`, the untracked cache stored in `index` is used to skip directories whose stat information
+/// and exclude file didn't change, and is updated afterwards so it can be written back with the index.
+/// The cache is ignored and left untouched if it was created for another working tree location or system, as identified by
+/// [`identifier()`].
+pub fn find(
+    worktree: impl AsRef<Path>,
+    index: &mut git_index::State,
+    options: Options,
+    is_excluded: impl FnMut(&BStr, bool) -> bool,
+) -> Result<Outcome, Error> {
+    let worktree = worktree.as_ref();
+    let identifier = identifier(worktree);
+    let dir_flags = options.dir_flags();
+    let info_exclude = oid_stat(options.info_exclude.as_deref(), options.object_hash)?;
+    let excludes_file = oid_stat(options.excludes_file.as_deref(), options.object_hash)?;
+
+    let (update_cache, cached_directories) = match index.untracked().filter(|_| options.use_untracked_cache) {
+        Some(cache) if cache.identifier.find(&identifier).is_none() => (false, None),
+        Some(cache)
+            if cache.dir_flags == dir_flags
+                && cache.exclude_filename_per_dir == options.exclude_filename_per_dir
+                && is_same_oid_stat(&cache.info_exclude, &info_exclude)
+                && is_same_oid_stat(&cache.excludes_file, &excludes_file) =>
+        {
+            (true, Some(cache.directories.as_slice()))
+        }
+        Some(_) | None => (options.use_untracked_cache, None),
+    };
+
+    let mut walk = Walk {
+        worktree,
+        index,
+        options: &options,
+        is_excluded,
+        cached: cached_directories.filter(|dirs| !dirs.is_empty()),
+        directories: Vec::new(),
+        out: Outcome::default(),
+    };
+    walk.directory(&mut BString::default(), "".into(), walk.cached.map(|_| 0), false)?;
+    let Walk {
+        directories, mut out, ..
+    } = walk;
+    out.untracked.sort();
+
+    if update_cache {
+        index.set_untracked(Some(UntrackedCache {
+            identifier,
+            info_exclude,
+            excludes_file,
+            exclude_filename_per_dir: options.exclude_filename_per_dir,
+            dir_flags,
+            directories,
+        }));
+    }
+    Ok(out)
+}
+
+/// Invalidate all directories leading to `path`, relative to the root of the working tree, in the untracked `cache`.
+///
+/// This must be called whenever `path` is added to or removed from the index as it doesn't affect the stat information of
+/// the directory containing it.
+pub fn invalidate_path(cache: &mut UntrackedCache, path: &BStr) {
+    if cache.directories.is_empty() {
+        return;
+    }
+    let mut dir_index = 0;
+    let mut components = path.split_str("/").peekable();
+    loop {
+        let dir = &mut cache.directories[dir_index];
+        dir.stat = None;
+        dir.untracked_entries.clear();
+
+        let component = match components.next() {
+            Some(component) if components.peek().is_some() => component,
+            _ => break,
+        };
+        match cache.directories[dir_index]
+            .sub_directories
+            .iter()
+            .find(|idx| cache.directories[**idx].name == component)
+        {
+            Some(idx) => dir_index = *idx,
+            None => break,
+        }
+    }
+}
+
+/// Return the identifier git would use for an untracked cache of the working tree at `worktree` on this system.
+pub fn identifier(worktree: &Path) -> BString {
+    let system = match std::env::consts::OS {
+        "linux" | "android" => "Linux",
+        "macos" | "ios" => "Darwin",
+        "windows" => "Windows",
+        "freebsd" => "FreeBSD",
+        "netbsd" => "NetBSD",
+        "openbsd" => "OpenBSD",
+        "dragonfly" => "DragonFly",
+        "solaris" | "illumos" => "SunOS",
+        other => other,
+    };
+    let mut id = BString::from("Location ");
+    id.push_str(Vec::from_path_lossy(worktree));
+    id.push_str(", system ");
+    id.push_str(system);
+    id.push_byte(0);
+    id
+}
+
+struct Walk<'a, Find> {
+    worktree: &'a Path,
+    index: &'a git_index::State,
+    options: &'a Options,
+    is_excluded: Find,
+    /// The directories of the untracked cache, if it can be used.
+    cached: Option<&'a [Directory]>,
+    /// The new cache directories, in the order in which they were encountered.
+    directories: Vec<Directory>,
+    out: Outcome,
+}
+
+impl<'a, Find> Walk<'a, Find>
+where
+    Find: FnMut(&BStr, bool) -> bool,
+{
+    /// Handle the directory at `rel_path` (empty or with trailing slash) which is named `name` and whose cached version
+    /// is at `cached_index`, returning the index of its new version. If `check_only`, we only determine whether it
+    /// contains at least one untracked file.
+    fn directory(
+        &mut self,
+        rel_path: &mut BString,
+        name: &BStr,
+        cached_index: Option<usize>,
+        check_only: bool,
+    ) -> std::io::Result<usize> {
+        let path = self
+            .worktree
+            .join(rel_path.to_path().expect("UTF-8 conversion succeeds"));
+        let stat = entry::Stat::from_fs(&std::fs::symlink_metadata(&path)?);
+        let mut exclude_file = rel_path.clone();
+        exclude_file.push_str(&self.options.exclude_filename_per_dir);
+        let exclude_file_oid = exclude_file_id(
+            &path.join(
+                self.options
+                    .exclude_filename_per_dir
+                    .to_path()
+                    .expect("UTF-8 conversion succeeds"),
+            ),
+            Some((self.index, exclude_file.as_bstr())),
+            self.options.object_hash,
+        )?;
+
+        // If exclude patterns changed, nothing we cached about this directory or its children can be trusted.
+        let cached = cached_index
+            .and_then(|idx| self.cached.map(|dirs| &dirs[idx]))
+            .filter(|dir| dir.exclude_file_oid == exclude_file_oid);
+        let is_valid = cached.map_or(false, |dir| {
+            dir.stat == Some(stat) && !crate::is_racy(&stat, self.index.timestamp()) && (check_only || !dir.check_only)
+        });
+
+        let idx = self.directories.len();
+        self.directories.push(Directory {
+            name: name.into(),
+            untracked_entries: Vec::new(),
+            sub_directories: Vec::new(),
+            stat: Some(stat),
+            exclude_file_oid,
+            check_only,
+        });
+
+        match cached {
+            Some(cached) if is_valid => self.directory_from_cache(rel_path, idx, cached, check_only)?,
+            _ => self.directory_from_disk(rel_path, &path, idx, cached, check_only)?,
+        }
+        Ok(idx)
+    }
+
+    fn directory_from_cache(
+        &mut self,
+        rel_path: &mut BString,
+        idx: usize,
+        cached: &'a Directory,
+        check_only: bool,
+    ) -> std::io::Result<()> {
+        self.out.directories_cached += 1;
+        let check_only_dirs: Vec<_> = cached
+            .sub_directories
+            .iter()
+            .map(|idx| &self.cached.expect("cache present")[*idx])
+            .filter(|dir| dir.check_only)
+            .map(|dir| dir.name.as_bstr())
+            .collect();
+        for name in &cached.untracked_entries {
+            if name
+                .strip_suffix(b"/")
+                .map_or(false, |name| check_only_dirs.contains(&name.as_bstr()))
+            {
+                // re-determined by the sub-directory itself
+                continue;
+            }
+            let prev_len = rel_path.len();
+            rel_path.push_str(name);
+            let is_tracked = match rel_path.strip_suffix(b"/") {
+                Some(dir) => {
+                    is_tracked(self.index, dir.as_bstr()) || has_tracked_entries(self.index, rel_path.as_bstr())
+                }
+                None => is_tracked(self.index, rel_path.as_bstr()),
+            };
+            if !is_tracked {
+                self.add_untracked(idx, name.as_bstr(), rel_path.as_bstr(), check_only);
+            }
+            rel_path.truncate(prev_len);
+        }
+
+        for cached_subdir_idx in &cached.sub_directories {
+            let subdir = &self.cached.expect("cache present")[*cached_subdir_idx];
+            let prev_len = rel_path.len();
+            rel_path.push_str(&subdir.name);
+            rel_path.push_byte(b'/');
+            self.sub_directory(
+                rel_path,
+                idx,
+                subdir.name.as_bstr(),
+                Some(*cached_subdir_idx),
+                subdir.check_only,
+            )?;
+            rel_path.truncate(prev_len);
+        }
+        Ok(())
+    }
+
+    fn directory_from_disk(
+        &mut self,
+        rel_path: &mut BString,
+        path: &Path,
+        idx: usize,
+        cached: Option<&'a Directory>,
+        check_only: bool,
+    ) -> std::io::Result<()> {
+        self.out.directories_read += 1;
+        let mut entries = std::fs::read_dir(path)?
+            .map(|entry| entry.and_then(|e| Ok((e.file_name(), e.file_type()?.is_dir()))))
+            .collect::<Result<Vec<_>, _>>()?;
+        entries.sort();
+
+        for (name, is_dir) in entries {
+            let name = Vec::from_os_str_lossy(&name);
+            let name = name.as_bstr();
+            if rel_path.is_empty() && name == ".git" {
+                continue;
+            }
+            let prev_len = rel_path.len();
+            rel_path.push_str(name);
+            if is_tracked(self.index, rel_path.as_bstr()) {
+                // a file, or a directory which is a submodule
+            } else if is_dir {
+                rel_path.push_byte(b'/');
+                if has_tracked_entries(self.index, rel_path.as_bstr()) {
+                    let cached_subdir_idx = self.cached_sub_directory(cached, name);
+                    self.sub_directory(rel_path, idx, name, cached_subdir_idx, false)?;
+                } else if !(self.is_excluded)(rel_path[..rel_path.len() - 1].as_bstr(), true) {
+                    let is_nested_repository = path
+                        .join(name.to_path().expect("UTF-8 conversion succeeds"))
+                        .join(".git")
+                        .exists();
+                    if is_nested_repository
+                        || (self.options.show_other_directories && !self.options.hide_empty_directories)
+                    {
+                        let mut dir_name = BString::from(name);
+                        dir_name.push_byte(b'/');
+                        self.add_untracked(idx, dir_name.as_bstr(), rel_path.as_bstr(), check_only);
+                    } else {
+                        let cached_subdir_idx = self.cached_sub_directory(cached, name);
+                        let check_only = check_only || self.options.show_other_directories;
+                        self.sub_directory(rel_path, idx, name, cached_subdir_idx, check_only)?;
+                    }
+                }
+            } else if !(self.is_excluded)(rel_path.as_bstr(), false) {
+                self.add_untracked(idx, name, rel_path.as_bstr(), check_only);
+            }
+            rel_path.truncate(prev_len);
+
+            if check_only && !self.directories[idx].untracked_entries.is_empty() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle the sub-directory `name` at `rel_path` of the directory at `parent_idx`. If it was `check_only` and contains
+    /// untracked files, it is listed as untracked directory in its parent.
+    fn sub_directory(
+        &mut self,
+        rel_path: &mut BString,
+        parent_idx: usize,
+        name: &BStr,
+        cached_index: Option<usize>,
+        check_only: bool,
+    ) -> std::io::Result<()> {
+        let sub_directory_idx = match self.directory(rel_path, name, cached_index, check_only) {
+            Ok(idx) => idx,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        self.directories[parent_idx].sub_directories.push(sub_directory_idx);
+
+        if check_only && !self.directories[sub_directory_idx].untracked_entries.is_empty() {
+            let mut dir_name = BString::from(name);
+            dir_name.push_byte(b'/');
+            let parent_is_check_only = self.directories[parent_idx].check_only;
+            self.add_untracked(parent_idx, dir_name.as_bstr(), rel_path.as_bstr(), parent_is_check_only);
+        }
+        Ok(())
+    }
+
+    fn cached_sub_directory(&self, cached: Option<&Directory>, name: &BStr) -> Option<usize> {
+        let dirs = self.cached?;
+        cached?
+            .sub_directories
+            .iter()
+            .find(|idx| dirs[**idx].name == name)
+            .copied()
+    }
+
+    fn add_untracked(&mut self, dir_idx: usize, name: &BStr, rel_path: &BStr, check_only: bool) {
+        self.directories[dir_idx].untracked_entries.push(name.into());
+        if !check_only {
+            self.out.untracked.push(rel_path.into());
+        }
+    }
+}
+
+fn is_tracked(index: &git_index::State, path: &BStr) -> bool {
+    index.entries().binary_search_by(|e| e.path(index).cmp(path)).is_ok()
+}
+
+/// Note that `dir` must end with a slash.
+fn has_tracked_entries(index: &git_index::State, dir: &BStr) -> bool {
+    let entries = index.entries();
+    let pos = entries.partition_point(|e| e.path(index) < dir);
+    entries.get(pos).map_or(false, |e| e.path(index).starts_with(dir))
+}
+
+fn is_same_oid_stat(lhs: &Option<OidStat>, rhs: &Option<OidStat>) -> bool {
+    match (lhs, rhs) {
+        (Some(lhs), Some(rhs)) => lhs.id == rhs.id && lhs.stat == rhs.stat,
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+fn oid_stat(path: Option<&Path>, object_hash: git_hash::Kind) -> std::io::Result<Option<OidStat>> {
+    let path = match path {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    let stat = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => entry::Stat::from_fs(&metadata),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    Ok(exclude_file_id(path, None, object_hash)?.map(|id| OidStat { stat, id }))
+}
+
+/// Return the id git would record for the exclude file at `path`, or `None` if it doesn't exist. If it's tracked in `index`
+/// at the given relative path and unchanged, that's the id of its entry.
+///
+/// Otherwise it's the id of its content with a newline appended, as git always terminates the last line before parsing
+/// and hashes what it parsed.
+fn exclude_file_id(
+    path: &Path,
+    index_entry: Option<(&git_index::State, &BStr)>,
+    object_hash: git_hash::Kind,
+) -> std::io::Result<Option<git_hash::ObjectId>> {
+    let mut data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    if data.is_empty() {
+        return Ok(Some(crate::blob_id(&data, object_hash)));
+    }
+    if let Some((index, rel_path)) = index_entry {
+        let unchanged_entry = index
+            .entries()
+            .binary_search_by(|e| e.path(index).cmp(rel_path))
+            .ok()
+            .map(|pos| &index.entries()[pos])
+            .filter(|entry| {
+                std::fs::symlink_metadata(path).map_or(false, |metadata| {
+                    entry.stat == entry::Stat::from_fs(&metadata) && !crate::is_racy(&entry.stat, index.timestamp())
+                })
+            });
+        if let Some(entry) = unchanged_entry {
+            return Ok(Some(entry.id));
+        }
+    }
+    data.push(b'\n');
+    Ok(Some(crate::blob_id(&data, object_hash)))
+}
@@ -0,0 +1,141 @@
+use std::collections::BTreeSet;
+
+use bstr::{BStr, ByteSlice};
+use git_worktree::{
+    attributes::{self, parse::Kind, Outcome, PatternList, Stack, State},
+    glob::Case,
+};
+
+#[test]
+fn parse_assignments_macros_and_quoted_patterns() {
+    let input =
+        b"\xef\xbb\xbf# comment\n\n*.txt text -diff !merge eol=lf\n[attr]bin -text\n\"quoted\\tname\" a=b\n   \n[attr] c-attr\n";
+    let lines: Vec<_> = attributes::parse(input).map(Result::unwrap).collect();
+    assert_eq!(lines.len(), 4);
+
+    assert!(matches!(&lines[0].kind, Kind::Pattern(p) if p.to_string() == "*.txt"));
+    assert_eq!(lines[0].line_number, 3);
+    let states: Vec<_> = lines[0]
+        .assignments
+        .iter()
+        .map(|a| (a.name.to_string(), a.state.clone()))
+        .collect();
+    assert_eq!(
+        states,
+        vec![
+            ("text".to_string(), State::Set),
+            ("diff".into(), State::Unset),
+            ("merge".into(), State::Unspecified),
+            ("eol".into(), State::Value("lf".into()))
+        ]
+    );
+
+    assert_eq!(lines[1].kind, Kind::Macro("bin".into()));
+    assert!(matches!(&lines[2].kind, Kind::Pattern(p) if p.text == "quoted\tname"));
+    assert!(
+        matches!(&lines[3].kind, Kind::Pattern(p) if p.text == "[attr]"),
+        "without a name, it's a pattern"
+    );
+    assert_eq!(
+        lines[3].assignments[0].name, "c-attr",
+        "which is a valid attribute name"
+    );
+}
+
+#[test]
+fn parse_errors_are_recoverable() {
+    let mut lines = attributes::parse(b"!negated text\n*.c -=x\n[attr]-bad x\n*.c ok\n");
+    assert!(matches!(
+        lines.next(),
+        Some(Err(attributes::parse::Error::PatternNegation { line_number: 1, .. }))
+    ));
+    assert!(matches!(
+        lines.next(),
+        Some(Err(attributes::parse::Error::AttributeName { line_number: 2, .. }))
+    ));
+    assert!(matches!(
+        lines.next(),
+        Some(Err(attributes::parse::Error::MacroName { line_number: 3, .. }))
+    ));
+    assert_eq!(lines.next().unwrap().unwrap().line_number, 4);
+    assert!(lines.next().is_none());
+}
+
+#[test]
+fn check_attr_like_git() {
+    let dir = git_testtools::scripted_fixture_repo_read_only("make_attributes_repo.sh").unwrap();
+    let mut stack = Stack::from_git_dir(
+        &dir,
+        dir.join(".git"),
+        Some(dir.join("user.attributes")),
+        Case::Sensitive,
+    )
+    .unwrap();
+
+    let baseline = std::fs::read(dir.join("git-check-attr.baseline")).unwrap();
+    let mut expected = Vec::<(&BStr, BTreeSet<String>)>::new();
+    for line in baseline.lines() {
+        let mut tokens = line.rsplitn(3, |b| *b == b':');
+        let (value, name, path) = (
+            tokens.next().unwrap()[1..].as_bstr(),
+            tokens.next().unwrap()[1..].as_bstr(),
+            tokens.next().unwrap().as_bstr(),
+        );
+        let assignment = format!("{}={}", name, value);
+        match expected.last_mut() {
+            Some((last, assignments)) if *last == path => {
+                assignments.insert(assignment);
+            }
+            _ => expected.push((path, Some(assignment).into_iter().collect())),
+        }
+    }
+
+    let mut out = Outcome::default();
+    for (path, expected) in &expected {
+        // `git check-attr` doesn't know about directories.
+        stack.matching_attributes(path, false, &mut out).unwrap();
+        let actual: BTreeSet<_> = out
+            .iter()
+            .filter(|m| m.state != State::Unspecified)
+            .map(|m| format!("{}={}", m.name, m.state))
+            .collect();
+        assert_eq!(&actual, expected, "{}", path);
+    }
+    assert_eq!(expected.len(), 16);
+
+    stack.matching_attributes("docs".into(), true, &mut out).unwrap();
+    assert_eq!(out.get("export-ignore").map(|m| &m.state), Some(&State::Set));
+}
+
+#[test]
+fn selections_and_sources() {
+    let dir = git_testtools::scripted_fixture_repo_read_only("make_attributes_repo.sh").unwrap();
+    let mut stack = Stack::new(
+        &dir,
+        PatternList::from_bytes(b"*.bin diff", None, None),
+        Vec::new(),
+        Case::Sensitive,
+    );
+
+    let mut out = Outcome::with_selection(Some("diff"));
+    stack.matching_attributes("sub/a.bin".into(), false, &mut out).unwrap();
+    let m = out.get("diff").expect("overrides have the highest precedence");
+    assert_eq!(m.state, State::Set);
+    assert_eq!(m.source, None);
+    assert_eq!(m.sequence_number, 1);
+    assert_eq!(out.iter().count(), 1, "only selected attributes are returned");
+
+    let mut out = Outcome::with_selection(Some("text"));
+    stack.matching_attributes("sub/a.bin".into(), false, &mut out).unwrap();
+    let m = out.get("text").expect("set by the binary macro");
+    assert_eq!(m.state, State::Unset);
+    assert!(m.source.as_ref().unwrap().ends_with(".gitattributes"));
+    assert_eq!(m.sequence_number, 5, "the line using the macro");
+
+    stack
+        .matching_attributes("sub/deeper/a.txt".into(), false, &mut out)
+        .unwrap();
+    assert_eq!(out.get("text").map(|m| &m.state), Some(&State::Unspecified));
+    stack.matching_attributes("sub/A.TXT".into(), false, &mut out).unwrap();
+    assert_eq!(out.get("text"), None);
+}
@@ -0,0 +1,338 @@
+use std::{
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use bstr::ByteSlice;
+use git_hash::ObjectId;
+use git_odb::{linked, pack::Find};
+use git_worktree::{attributes, checkout, filter, glob::Case};
+
+fn fixture() -> (std::path::PathBuf, linked::Store) {
+    fixture_at("make_checkout_repo.sh")
+}
+
+fn fixture_at(script_name: &str) -> (std::path::PathBuf, linked::Store) {
+    let dir = git_testtools::scripted_fixture_repo_read_only(script_name).unwrap();
+    let db = linked::Store::at(dir.join(".git").join("objects")).unwrap();
+    (dir, db)
+}
+
+pub fn index_from_head(dir: &Path, db: &linked::Store) -> git_index::State {
+    let head = ObjectId::from_hex(
+        std::fs::read(dir.join(".git").join("refs").join("heads").join("main"))
+            .unwrap()
+            .as_bstr()
+            .trim(),
+    )
+    .unwrap();
+    let mut buf = Vec::new();
+    let tree_id = db
+        .try_find(head, &mut buf)
+        .unwrap()
+        .expect("head commit present")
+        .0
+        .decode()
+        .unwrap()
+        .into_commit()
+        .expect("a commit")
+        .tree();
+    git_index::State::from_tree(&tree_id, |oid, buf| {
+        db.try_find(oid, buf)
+            .ok()
+            .flatten()
+            .and_then(|obj| obj.0.try_into_tree_iter())
+    })
+    .unwrap()
+}
+
+pub fn find<'a>(db: &linked::Store, oid: &git_hash::oid, buf: &'a mut Vec<u8>) -> Option<git_object::BlobRef<'a>> {
+    db.try_find(oid, buf)
+        .ok()
+        .flatten()
+        .and_then(|obj| obj.0.try_into_blob())
+}
+
+pub fn attributes(destination: &Path) -> attributes::Stack {
+    attributes::Stack::new(destination, Default::default(), Vec::new(), Case::Sensitive)
+}
+
+fn checkout(
+    index: &mut git_index::State,
+    destination: &Path,
+    db: &linked::Store,
+    options: checkout::Options,
+) -> Result<checkout::Outcome, checkout::Error> {
+    checkout_with_filters(index, destination, db, Default::default(), options)
+}
+
+fn checkout_with_filters(
+    index: &mut git_index::State,
+    destination: &Path,
+    db: &linked::Store,
+    filters: filter::Options,
+    options: checkout::Options,
+) -> Result<checkout::Outcome, checkout::Error> {
+    checkout::checkout(
+        index,
+        destination,
+        |oid, buf| find(db, oid, buf),
+        &AtomicBool::default(),
+        &filter::Pipeline::new(destination, filters),
+        &attributes(destination),
+        options,
+    )
+}
+
+fn read(path: impl AsRef<Path>) -> String {
+    std::fs::read_to_string(path).unwrap()
+}
+
+#[test]
+fn index_from_tree_lists_all_non_tree_entries_in_order() {
+    let (dir, db) = fixture();
+    let index = index_from_head(&dir, &db);
+    let paths: Vec<_> = index
+        .entries()
+        .iter()
+        .map(|entry| entry.path(&index).to_str().expect("valid UTF-8"))
+        .collect();
+    assert_eq!(paths, vec!["a", "dir/b", "dir/executable", "dir/sub/c", "link"]);
+}
+
+#[test]
+fn checkout_writes_files_and_updates_stat_information() {
+    let (dir, db) = fixture();
+    let mut index = index_from_head(&dir, &db);
+    let destination = tempfile::tempdir().unwrap();
+
+    let outcome = checkout(&mut index, destination.path(), &db, Default::default()).unwrap();
+    assert_eq!(outcome.files_updated, 5);
+    assert_eq!(outcome.bytes_written, 2 + 2 + 10 + 2, "only files count, not symlinks");
+    assert!(outcome.collisions.is_empty());
+    assert!(outcome.errors.is_empty());
+
+    let destination = destination.path();
+    assert_eq!(read(destination.join("a")), "a\n");
+    assert_eq!(read(destination.join("dir/sub/c")), "c\n");
+    assert_eq!(
+        std::fs::read_link(destination.join("link")).unwrap(),
+        Path::new("dir/b"),
+        "symlinks are created as such"
+    );
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = |path: &str| std::fs::metadata(destination.join(path)).unwrap().permissions().mode();
+        assert_ne!(mode("dir/executable") & 0o100, 0, "the executable bit is set");
+        assert_eq!(mode("a") & 0o111, 0);
+    }
+
+    let status = crate::status::index_to_worktree(destination, &mut index, &db, Default::default(), Default::default());
+    assert!(status.changes.is_empty(), "the index matches the checked out files");
+}
+
+#[test]
+fn files_are_converted_when_checking_them_out_and_comparing_them_with_the_index() {
+    let (dir, db) = fixture();
+    let mut index = index_from_head(&dir, &db);
+    let destination = tempfile::tempdir().unwrap();
+    let destination = destination.path();
+    let filters = filter::Options {
+        eol: filter::eol::Configuration {
+            auto_crlf: filter::eol::AutoCrlf::Enabled,
+            eol: None,
+        },
+        ..Default::default()
+    };
+
+    let outcome = checkout_with_filters(&mut index, destination, &db, filters.clone(), Default::default()).unwrap();
+    assert_eq!(outcome.bytes_written, 3 + 3 + 11 + 3, "each line now ends with CRLF");
+    assert_eq!(read(destination.join("a")), "a\r\n");
+    assert_eq!(
+        std::fs::read_link(destination.join("link")).unwrap(),
+        Path::new("dir/b"),
+        "symlinks aren't converted"
+    );
+
+    let mtime = filetime::FileTime::from_unix_time(filetime::FileTime::now().unix_seconds() - 10, 0);
+    for path in &["a", "dir/b", "dir/executable", "dir/sub/c"] {
+        filetime::set_file_mtime(destination.join(path), mtime).unwrap();
+    }
+    let status = crate::status::index_to_worktree(destination, &mut index, &db, filters, Default::default());
+    assert!(
+        status.changes.is_empty(),
+        "files are converted back before hashing them"
+    );
+
+    let status = crate::status::index_to_worktree(destination, &mut index, &db, Default::default(), Default::default());
+    assert_eq!(
+        status.changes.len(),
+        4,
+        "without conversion, the line endings differ from what's stored in git"
+    );
+}
+
+#[test]
+fn checkout_without_symlink_support_writes_the_link_target_into_a_file() {
+    let (dir, db) = fixture();
+    let mut index = index_from_head(&dir, &db);
+    let destination = tempfile::tempdir().unwrap();
+
+    checkout(
+        &mut index,
+        destination.path(),
+        &db,
+        checkout::Options {
+            symlinks: false,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let link = destination.path().join("link");
+    assert!(std::fs::symlink_metadata(&link).unwrap().is_file());
+    assert_eq!(read(link), "dir/b");
+}
+
+#[test]
+fn existing_files_are_collisions_unless_they_may_be_overwritten() {
+    let (dir, db) = fixture();
+    let mut index = index_from_head(&dir, &db);
+    let destination = tempfile::tempdir().unwrap();
+    std::fs::write(destination.path().join("a"), "in the way").unwrap();
+    std::fs::write(destination.path().join("dir"), "a file where a directory should be").unwrap();
+
+    let outcome = checkout(&mut index, destination.path(), &db, Default::default()).unwrap();
+    let collisions: Vec<_> = outcome
+        .collisions
+        .iter()
+        .map(|collision| collision.path.to_str().expect("valid UTF-8"))
+        .collect();
+    assert_eq!(collisions, vec!["a", "dir/b", "dir/executable", "dir/sub/c"]);
+    assert_eq!(outcome.files_updated, 1, "only the symlink could be created");
+    assert_eq!(
+        read(destination.path().join("a")),
+        "in the way",
+        "nothing was overwritten"
+    );
+
+    let outcome = checkout(
+        &mut index,
+        destination.path(),
+        &db,
+        checkout::Options {
+            overwrite_existing: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert!(outcome.collisions.is_empty());
+    assert_eq!(outcome.files_updated, 5);
+    assert_eq!(read(destination.path().join("a")), "a\n");
+    assert_eq!(read(destination.path().join("dir/b")), "b\n");
+}
+
+#[test]
+fn errors_are_recorded_per_entry_when_keeping_going() {
+    let (dir, db) = fixture();
+    let mut index = index_from_head(&dir, &db);
+    let destination = tempfile::tempdir().unwrap();
+    let missing = index.entries()[0].id;
+
+    let outcome = checkout::checkout(
+        &mut index,
+        destination.path(),
+        |oid, buf| {
+            if oid == missing {
+                return None;
+            }
+            find(&db, oid, buf)
+        },
+        &AtomicBool::default(),
+        &filter::Pipeline::new(destination.path(), Default::default()),
+        &attributes(destination.path()),
+        checkout::Options {
+            keep_going: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let failed: Vec<_> = outcome
+        .errors
+        .iter()
+        .map(|record| record.path.to_str().expect("valid UTF-8"))
+        .collect();
+    assert_eq!(failed, vec!["a"]);
+    assert_eq!(outcome.files_updated, 4, "all other entries are still checked out");
+    assert!(!destination.path().join("a").exists());
+}
+
+#[test]
+fn paths_leaving_the_checkout_or_writing_into_the_repository_are_rejected() {
+    let (dir, db) = fixture_at("make_checkout_repo_with_invalid_paths.sh");
+    let mut index = index_from_head(&dir, &db);
+    let tmp = tempfile::tempdir().unwrap();
+    let destination = tmp.path().join("checkout");
+    std::fs::create_dir(&destination).unwrap();
+
+    let err = checkout(&mut index, &destination, &db, Default::default()).unwrap_err();
+    assert!(matches!(err, checkout::Error::InvalidPath { path } if path == "../escaped"));
+
+    let outcome = checkout(
+        &mut index,
+        &destination,
+        &db,
+        checkout::Options {
+            keep_going: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let rejected: Vec<_> = outcome
+        .errors
+        .iter()
+        .map(|record| {
+            assert!(matches!(record.error, checkout::Error::InvalidPath { .. }));
+            record.path.to_str().expect("valid UTF-8")
+        })
+        .collect();
+    assert_eq!(
+        rejected,
+        vec![
+            "../escaped",
+            ".GIT/hooks/post-checkout",
+            ".gitmodules",
+            "sub/.git ",
+            "sub/.gi\u{200c}t",
+            "sub/git~1"
+        ]
+    );
+    assert_eq!(outcome.files_updated, 2, "only valid paths are checked out");
+    assert!(destination.join("ok").is_file());
+    assert!(destination.join("sub/valid").is_file());
+    assert!(!tmp.path().join("escaped").exists());
+    assert!(!destination.join(".GIT").exists());
+    assert!(!destination.join(".gitmodules").exists());
+}
+
+#[test]
+fn checkout_can_be_interrupted() {
+    let (dir, db) = fixture();
+    let mut index = index_from_head(&dir, &db);
+    let destination = tempfile::tempdir().unwrap();
+    let should_interrupt = AtomicBool::default();
+    should_interrupt.store(true, Ordering::SeqCst);
+
+    let err = checkout::checkout(
+        &mut index,
+        destination.path(),
+        |oid, buf| find(&db, oid, buf),
+        &should_interrupt,
+        &filter::Pipeline::new(destination.path(), Default::default()),
+        &attributes(destination.path()),
+        Default::default(),
+    )
+    .unwrap_err();
+    assert!(matches!(err, checkout::Error::Interrupted));
+    assert!(!destination.path().join("a").exists());
+}
@@ -0,0 +1,95 @@
+use std::path::Path;
+
+use bstr::{BStr, ByteSlice};
+use git_worktree::{
+    excludes::{self, PatternList, Stack},
+    glob::Case,
+};
+
+#[test]
+fn parse_skips_comments_and_empty_lines_and_keeps_line_numbers() {
+    let input = b"\xef\xbb\xbf# comment\n\n*.o\n!keep.o   \n\\#not-a-comment\nescaped-space\\ \n/\n";
+    let patterns: Vec<_> = excludes::parse(input)
+        .map(|(pattern, line)| (pattern.to_string(), line))
+        .collect();
+    assert_eq!(
+        patterns,
+        vec![
+            ("*.o".to_string(), 3),
+            ("!keep.o".into(), 4),
+            ("\\#not-a-comment".into(), 5),
+            ("escaped-space\\ ".into(), 6)
+        ]
+    );
+}
+
+#[test]
+fn check_ignore_like_git() {
+    let dir = git_testtools::scripted_fixture_repo_read_only("make_excludes_repo.sh").unwrap();
+    let mut stack =
+        Stack::from_git_dir(&dir, dir.join(".git"), Some(dir.join("user.exclude")), Case::Sensitive).unwrap();
+
+    let baseline = std::fs::read(dir.join("git-check-ignore.baseline")).unwrap();
+    let mut count = 0;
+    for line in baseline.lines() {
+        let mut tokens = line.splitn(2, |b| *b == b'\t');
+        let source_line_pattern = tokens.next().unwrap();
+        let path = tokens.next().unwrap().as_bstr();
+        let mut tokens = source_line_pattern.splitn(3, |b| *b == b':');
+        let (source, line_number, pattern) = (
+            tokens.next().unwrap().to_str().unwrap(),
+            tokens.next().unwrap().to_str().unwrap(),
+            tokens.next().unwrap().as_bstr(),
+        );
+
+        let is_dir = dir.join(path.to_str().unwrap()).is_dir();
+        let actual = stack.matching_exclude_pattern(path, is_dir).unwrap();
+        if source.is_empty() {
+            assert_eq!(actual, None, "{} should not match any pattern", path);
+        } else {
+            let actual = actual.unwrap_or_else(|| panic!("{} should match {}", path, pattern));
+            assert_eq!(actual.pattern.to_string(), pattern.to_string(), "{}", path);
+            assert_eq!(actual.sequence_number.to_string(), line_number, "{}", path);
+            assert!(
+                is_same_source(actual.source.expect("all patterns are read from files"), source),
+                "{}: {:?} should be {}",
+                path,
+                actual.source,
+                source
+            );
+        }
+        count += 1;
+    }
+    assert_eq!(count, 26);
+}
+
+fn is_same_source(actual: &Path, expected: &str) -> bool {
+    let expected = Path::new(expected);
+    if expected.is_absolute() {
+        actual.file_name() == expected.file_name()
+    } else {
+        actual.ends_with(expected)
+    }
+}
+
+#[test]
+fn overrides_have_the_highest_precedence_and_case_can_be_folded() {
+    let dir = git_testtools::scripted_fixture_repo_read_only("make_excludes_repo.sh").unwrap();
+    let path = |p: &'static str| -> &BStr { p.into() };
+
+    let mut stack = Stack::new(&dir, Case::Sensitive);
+    assert!(stack.is_excluded(path("a.o"), false).unwrap());
+    assert!(!stack.is_excluded(path("A.O"), false).unwrap());
+
+    stack.overrides = PatternList::from_bytes(b"!a.o", None, None);
+    let m = stack.matching_exclude_pattern(path("a.o"), false).unwrap().unwrap();
+    assert!(m.pattern.is_negative());
+    assert_eq!(m.source, None);
+    assert_eq!(m.sequence_number, 1);
+    assert!(!stack.is_excluded(path("a.o"), false).unwrap());
+
+    let mut stack = Stack::new(&dir, Case::Fold);
+    assert!(stack.is_excluded(path("A.O"), false).unwrap());
+    assert!(stack.is_excluded(path("BUILD/x"), false).unwrap());
+    assert!(!stack.is_excluded(path("sub/deeper/A.O"), false).unwrap());
+}
@@ -0,0 +1,175 @@
+use bstr::{BStr, ByteSlice};
+use git_worktree::{
+    attributes::{Outcome, PatternList, Stack},
+    filter::{self, eol, Driver, Options, Pipeline},
+    glob::Case,
+};
+
+fn outcome_for(stack: &mut Stack, path: &BStr) -> Outcome {
+    let mut out = Outcome::with_selection(filter::ATTRIBUTES.iter().copied());
+    stack.matching_attributes(path, false, &mut out).unwrap();
+    out
+}
+
+#[test]
+fn to_git_and_to_worktree_like_git() {
+    let dir = git_testtools::scripted_fixture_repo_read_only("make_filter_repo.sh").unwrap();
+    let mut stack = Stack::from_git_dir(&dir, dir.join(".git"), None, Case::Sensitive).unwrap();
+    let mut pipeline = Pipeline::new(
+        &dir,
+        Options {
+            eol: eol::Configuration {
+                auto_crlf: eol::AutoCrlf::Enabled,
+                eol: None,
+            },
+            safe_crlf: eol::SafeCrlf::Off,
+            drivers: vec![
+                Driver {
+                    name: "upper".into(),
+                    clean: Some("tr a-z A-Z".into()),
+                    smudge: Some("tr A-Z a-z".into()),
+                    ..Default::default()
+                },
+                Driver {
+                    name: "proc".into(),
+                    process: Some("perl .git/filter-process.pl".into()),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        },
+    );
+
+    let baseline = dir.join("baseline");
+    let paths = std::fs::read(baseline.join("paths")).unwrap();
+    let mut out = Vec::new();
+    let mut count = 0;
+    for path in paths.lines().map(ByteSlice::as_bstr) {
+        let attrs = outcome_for(&mut stack, path);
+        let input = std::fs::read(baseline.join("input").join(path.to_str().unwrap())).unwrap();
+        let index_object = |buf: &mut Vec<u8>| {
+            (path == "in-index.auto").then(|| *buf = std::fs::read(baseline.join("in-index.previous")).unwrap())
+        };
+        pipeline.to_git(&input, path, &attrs, index_object, &mut out).unwrap();
+        let expected = std::fs::read(baseline.join("index").join(path.to_str().unwrap())).unwrap();
+        assert_eq!(out.as_bstr(), expected.as_bstr(), "to git: {}", path);
+
+        pipeline.to_worktree(&expected, path, &attrs, &mut out).unwrap();
+        let expected = std::fs::read(dir.join(path.to_str().unwrap())).unwrap();
+        assert_eq!(out.as_bstr(), expected.as_bstr(), "to worktree: {}", path);
+        count += 1;
+    }
+    assert_eq!(count, 15);
+}
+
+#[test]
+fn irreversible_line_endings_are_reported_or_fail() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut stack = Stack::new(
+        dir.path(),
+        PatternList::from_bytes(b"*.lf text eol=lf\n*.crlf text eol=crlf", None, None),
+        Vec::new(),
+        Case::Sensitive,
+    );
+    let mut out = Vec::new();
+    for (safe_crlf, expected) in [
+        (eol::SafeCrlf::Off, None),
+        (eol::SafeCrlf::Warn, Some(eol::RoundTrip::CrlfToLf)),
+    ] {
+        let mut pipeline = Pipeline::new(
+            dir.path(),
+            Options {
+                safe_crlf,
+                ..Default::default()
+            },
+        );
+        let attrs = outcome_for(&mut stack, "a.lf".into());
+        let round_trip = pipeline
+            .to_git(b"one\r\ntwo\n", "a.lf".into(), &attrs, |_| None, &mut out)
+            .unwrap();
+        assert_eq!(round_trip, expected);
+        assert_eq!(out.as_bstr(), "one\ntwo\n");
+    }
+
+    let mut pipeline = Pipeline::new(
+        dir.path(),
+        Options {
+            safe_crlf: eol::SafeCrlf::Fail,
+            ..Default::default()
+        },
+    );
+    let attrs = outcome_for(&mut stack, "a.crlf".into());
+    let err = pipeline
+        .to_git(b"one\ntwo\n", "a.crlf".into(), &attrs, |_| None, &mut out)
+        .unwrap_err();
+    assert_eq!(err.to_string(), "LF would be replaced by CRLF in a.crlf");
+}
+
+#[test]
+fn failing_drivers_and_encodings() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut stack = Stack::new(
+        dir.path(),
+        PatternList::from_bytes(
+            b"*.optional filter=optional\n*.required filter=required\n*.utf16 working-tree-encoding=UTF-16\n",
+            None,
+            None,
+        ),
+        Vec::new(),
+        Case::Sensitive,
+    );
+    let driver = |name: &str, required: bool| Driver {
+        name: name.into(),
+        clean: Some("exit 1".into()),
+        required,
+        ..Default::default()
+    };
+    let mut pipeline = Pipeline::new(
+        dir.path(),
+        Options {
+            drivers: vec![driver("optional", false), driver("required", true)],
+            ..Default::default()
+        },
+    );
+
+    let mut out = Vec::new();
+    let attrs = outcome_for(&mut stack, "a.optional".into());
+    pipeline
+        .to_git(b"content", "a.optional".into(), &attrs, |_| None, &mut out)
+        .unwrap();
+    assert_eq!(out.as_bstr(), "content", "failures of optional drivers are ignored");
+
+    let attrs = outcome_for(&mut stack, "a.required".into());
+    assert!(matches!(
+        pipeline.to_git(b"content", "a.required".into(), &attrs, |_| None, &mut out),
+        Err(filter::Error::Driver { operation: "clean", .. })
+    ));
+    assert!(
+        matches!(
+            pipeline.to_worktree(b"content", "a.required".into(), &attrs, &mut out),
+            Err(filter::Error::Driver {
+                operation: "smudge",
+                ..
+            })
+        ),
+        "required drivers without a command fail as well"
+    );
+
+    let attrs = outcome_for(&mut stack, "a.utf16".into());
+    pipeline
+        .to_worktree("hé".as_bytes(), "a.utf16".into(), &attrs, &mut out)
+        .unwrap();
+    assert_eq!(
+        out, b"\xfe\xff\0h\0\xe9",
+        "UTF-16 is written big-endian with a byte order mark"
+    );
+    let mut back = Vec::new();
+    pipeline
+        .to_git(&out, "a.utf16".into(), &attrs, |_| None, &mut back)
+        .unwrap();
+    assert_eq!(back.as_bstr(), "hé");
+    assert!(matches!(
+        pipeline.to_git(b"\0h", "a.utf16".into(), &attrs, |_| None, &mut back),
+        Err(filter::Error::Encoding { .. })
+    ));
+}
@@ -0,0 +1,61 @@
+#!/bin/bash
+set -eu -o pipefail
+
+git init -q
+
+cat <<EOT > .gitattributes
+# a comment
+[attr]custom text eol=crlf -diff
+* whitespace=trailing-space
+*.txt text
+*.bin binary
+*.jpg -text !whitespace
+*.sh eol=lf
+docs/ export-ignore
+docs/*.md diff=markdown
+"quoted name.c" filter=clean
+*.cust custom
+EOT
+
+mkdir -p sub/deeper docs
+cat <<EOT > sub/.gitattributes
+# macros are ignored in sub-directories
+[attr]ignored-macro text
+*.txt -text
+/local.c diff=cpp
+*.ign ignored-macro
+EOT
+cat <<EOT > sub/deeper/.gitattributes
+*.txt !text eol=lf
+EOT
+
+cat <<EOT > .git/info/attributes
+*.info merge=ours
+[attr]custom -text
+EOT
+cat <<EOT > user.attributes
+*.txt delta
+*.bin text
+EOT
+git config core.attributesFile "$PWD/user.attributes"
+
+cat <<EOT > paths
+a.txt
+a.bin
+a.jpg
+a.sh
+a.info
+a.cust
+docs
+docs/readme.md
+quoted name.c
+sub/a.txt
+sub/local.c
+sub/deeper/local.c
+sub/a.ign
+sub/deeper/a.txt
+sub/deeper/a.bin
+normal
+EOT
+
+git check-attr -a --stdin < paths > git-check-attr.baseline
@@ -0,0 +1,16 @@
+#!/bin/bash
+set -eu -o pipefail
+
+git init -q
+git config commit.gpgsign false
+git checkout -q -b main
+
+mkdir -p dir/sub
+echo "a" > a
+echo "b" > dir/b
+echo "c" > dir/sub/c
+echo "#!/bin/sh" > dir/executable && chmod +x dir/executable
+ln -s dir/b link
+
+git add .
+git commit -q -m "init"
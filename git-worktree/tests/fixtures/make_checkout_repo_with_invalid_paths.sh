@@ -0,0 +1,19 @@
+#!/bin/bash
+set -eu -o pipefail
+
+git init -q
+git config commit.gpgsign false
+
+blob=$(echo "content" | git hash-object -w --stdin)
+function tree() {
+  printf "$@" | git mktree
+}
+
+hooks=$(tree "100755 blob $blob\tpost-checkout\n")
+dot_git=$(tree "040000 tree $hooks\thooks\n")
+escaped=$(tree "100644 blob $blob\tescaped\n")
+sub=$(tree "100644 blob $blob\t.git \n100644 blob $blob\t.gi\xe2\x80\x8ct\n100644 blob $blob\tgit~1\n100644 blob $blob\tvalid\n")
+root=$(tree "040000 tree $escaped\t..\n040000 tree $dot_git\t.GIT\n120000 blob $blob\t.gitmodules\n100644 blob $blob\tok\n040000 tree $sub\tsub\n")
+
+commit=$(git commit-tree -m "invalid paths" "$root")
+git update-ref refs/heads/main "$commit"
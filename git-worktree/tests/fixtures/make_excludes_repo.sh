@@ -0,0 +1,71 @@
+#!/bin/bash
+set -eu -o pipefail
+
+git init -q
+
+cat <<EOT > .gitignore
+# a comment
+*.o
+!important.o
+/top-only
+build/
+doc/*.html
+**/cache
+\#hash
+\!bang
+EOT
+
+mkdir -p sub/deeper build doc/api dir/cache
+cat <<EOT > sub/.gitignore
+*.txt
+!keep.txt
+/local-only
+EOT
+cat <<EOT > sub/deeper/.gitignore
+!*.o
+EOT
+# not read as its directory is excluded
+cat <<EOT > build/.gitignore
+!important.o
+EOT
+
+echo "from-info-exclude" >> .git/info/exclude
+echo "*.user" > user.exclude
+git config core.excludesFile "$PWD/user.exclude"
+
+touch a.o important.o top-only sub/top-only sub/local-only sub/deeper/local-only \
+      sub/a.txt sub/keep.txt sub/deeper/a.o sub/deeper/b.txt \
+      build/x build/important.o doc/index.html doc/api/index.html \
+      cache dir/cache/x from-info-exclude sub/from-info-exclude x.user sub/x.user \
+      '#hash' '!bang' normal
+
+cat <<EOT > paths
+a.o
+important.o
+top-only
+sub/top-only
+sub/local-only
+sub/deeper/local-only
+sub/a.txt
+sub/keep.txt
+sub/deeper/a.o
+sub/deeper/b.txt
+build
+build/x
+build/important.o
+doc/index.html
+doc/api/index.html
+cache
+dir/cache
+dir/cache/x
+from-info-exclude
+sub/from-info-exclude
+x.user
+sub/x.user
+#hash
+!bang
+normal
+sub
+EOT
+
+git check-ignore -vn --no-index --stdin < paths > git-check-ignore.baseline || true
@@ -0,0 +1,113 @@
+#!/bin/bash
+set -eu -o pipefail
+
+git init -q
+
+# a long-running filter process upper-casing content when cleaning and lower-casing it when smudging
+cat <<'EOT' > .git/filter-process.pl
+use strict;
+use warnings;
+binmode STDIN;
+binmode STDOUT;
+$| = 1;
+
+sub read_packet {
+    my $len;
+    read(STDIN, $len, 4) == 4 or exit 0;
+    return undef if $len eq "0000";
+    my $size = hex($len) - 4;
+    my $buf = '';
+    while (length($buf) < $size) {
+        read(STDIN, $buf, $size - length($buf), length($buf)) or die "unexpected EOF";
+    }
+    return $buf;
+}
+sub write_packet { my $data = shift; printf STDOUT "%04x%s", length($data) + 4, $data; }
+sub write_flush { print STDOUT "0000"; }
+sub read_list {
+    my @lines;
+    while (defined(my $line = read_packet())) { chomp $line; push @lines, $line; }
+    return @lines;
+}
+
+read_list();
+write_packet("git-filter-server\n");
+write_packet("version=2\n");
+write_flush();
+read_list();
+write_packet("capability=clean\n");
+write_packet("capability=smudge\n");
+write_flush();
+
+while (1) {
+    my %request = map { split /=/, $_, 2 } read_list();
+    my $content = '';
+    while (defined(my $data = read_packet())) { $content .= $data; }
+    if ($request{pathname} =~ /error/) {
+        write_packet("status=error\n");
+        write_flush();
+        next;
+    }
+    my $out = $request{command} eq 'clean' ? uc($content) : lc($content);
+    write_packet("status=success\n");
+    write_flush();
+    write_packet($out) if length $out;
+    write_flush();
+    write_flush();
+}
+EOT
+
+git config core.safecrlf false
+git config filter.upper.clean "tr a-z A-Z"
+git config filter.upper.smudge "tr A-Z a-z"
+git config filter.proc.process "perl $PWD/.git/filter-process.pl"
+
+# a text file with CRLF in the index keeps them when converting automatically
+printf 'one\r\n' > in-index.auto
+git add in-index.auto
+mkdir -p baseline/index baseline/input
+echo baseline >> .git/info/exclude
+git cat-file blob :in-index.auto > baseline/in-index.previous
+
+cat <<EOT > .gitattributes
+*.auto text=auto
+*.txt text
+*.lf text eol=lf
+*.crlf text eol=crlf
+*.bin -text
+*.id ident
+*.utf16 text working-tree-encoding=UTF-16LE
+*.upper filter=upper
+*.proc filter=proc
+legacy.* crlf=input
+EOT
+
+git config core.autocrlf true
+
+printf 'one\r\ntwo\r\n' > a.auto
+printf 'one\r\ntwo\n' > mixed.auto
+printf 'a\0\r\n' > binary.auto
+printf 'one\r\ntwo\r\n' > in-index.auto
+printf 'one\r\ntwo\n' > a.txt
+printf 'one\r\n' > a.lf
+printf 'one\ntwo\n' > a.crlf
+printf 'one\r\n' > a.bin
+printf 'one\r\n' > plain
+printf '$Id$\n$Id: old $\n$Id: an other id $\n' > a.id
+printf 'h\0\xe9\0\r\0\n\0' > a.utf16
+printf 'Hello\n' > a.upper
+printf 'Hello\n' > a.proc
+printf 'Hello\n' > an-error.proc
+printf 'one\r\n' > legacy.input
+
+paths="a.auto mixed.auto binary.auto in-index.auto a.txt a.lf a.crlf a.bin plain a.id a.utf16 a.upper a.proc an-error.proc legacy.input"
+for path in $paths; do
+  cp "$path" baseline/input/
+done
+git add $paths .gitattributes 2>/dev/null
+for path in $paths; do
+  git cat-file blob ":$path" > "baseline/index/$path"
+  rm "$path"
+done
+git checkout -- $paths
+echo "$paths" | tr ' ' '\n' > baseline/paths
@@ -0,0 +1,15 @@
+#!/bin/bash
+set -eu -o pipefail
+
+git init -q
+git config commit.gpgsign false
+
+mkdir -p dir/sub
+touch a dir/a dir/sub/a
+echo "*.o" > .gitignore
+
+git add .
+git commit -q -m "init"
+
+mkdir untracked-dir empty-dir
+touch b dir/b untracked-dir/x untracked-dir/y ignored.o dir/ignored.o
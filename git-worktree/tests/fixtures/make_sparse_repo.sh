@@ -0,0 +1,21 @@
+#!/bin/bash
+set -eu -o pipefail
+
+git init -q
+git config commit.gpgsign false
+git checkout -q -b main
+
+mkdir -p b/c/d e/g 'w*ld'
+for path in a b/x b/c/y b/c/d/z e/f e/g/h e/g/h.txt i.txt 'w*ld/j'; do
+  echo "$path" > "$path"
+done
+git add .
+git commit -q -m "init"
+
+git sparse-checkout set --cone --skip-checks b/c/d 'w*ld'
+cp .git/info/sparse-checkout cone-patterns
+git ls-files -t > cone-ls-files
+
+git sparse-checkout set --no-cone '/*' '!/*/' '*.txt' '/b/' '!/b/c/d/'
+cp .git/info/sparse-checkout full-patterns
+git ls-files -t > full-ls-files
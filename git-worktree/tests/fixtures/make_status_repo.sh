@@ -0,0 +1,20 @@
+#!/bin/bash
+set -eu -o pipefail
+
+git init -q
+git config commit.gpgsign false
+
+mkdir dir moved
+for name in unchanged modified removed type-change executable dir/file moved/file; do
+  echo "$name" > "$name"
+done
+
+git add .
+git commit -q -m "init"
+
+echo "more" >> modified
+rm removed
+rm type-change && ln -s unchanged type-change
+chmod +x executable
+mv moved moved-away && ln -s moved-away moved
+touch untracked
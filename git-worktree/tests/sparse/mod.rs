@@ -0,0 +1,181 @@
+use std::{path::Path, sync::atomic::AtomicBool};
+
+use bstr::ByteSlice;
+use git_index::entry;
+use git_odb::linked;
+use git_worktree::{
+    attributes, checkout, filter,
+    glob::Case,
+    sparse::{self, Cone, Patterns},
+};
+
+use crate::checkout::{find, index_from_head};
+
+fn fixture() -> (std::path::PathBuf, linked::Store) {
+    let dir = git_testtools::scripted_fixture_repo_read_only("make_sparse_repo.sh").unwrap();
+    let db = linked::Store::at(dir.join(".git").join("objects")).unwrap();
+    (dir, db)
+}
+
+fn patterns(dir: &Path, name: &str) -> Patterns {
+    Patterns::from_file(dir.join(name), true, &mut Vec::new())
+        .unwrap()
+        .expect("present")
+}
+
+/// Assert that `patterns` include exactly the paths git didn't mark as _skip-worktree_ in `ls-files -t` output.
+fn assert_matches_git(dir: &Path, patterns: &Patterns, ls_files: &str) {
+    let ls_files = std::fs::read(dir.join(ls_files)).unwrap();
+    for line in ls_files.lines() {
+        let (tag, path) = (line[0], line[2..].as_bstr());
+        assert_eq!(
+            patterns.is_included(path, false, Case::Sensitive),
+            tag == b'H',
+            "{}",
+            path
+        );
+    }
+}
+
+fn write_to_vec(patterns: &Patterns) -> Vec<u8> {
+    let mut buf = Vec::new();
+    patterns.write_to(&mut buf).unwrap();
+    buf
+}
+
+#[test]
+fn cone_patterns_are_read_written_and_matched_like_git() {
+    let (dir, _db) = fixture();
+    let patterns = patterns(&dir, "cone-patterns");
+    assert_eq!(
+        patterns,
+        Patterns::Cone(Cone::from_directories(["b/c/d", "w*ld"])),
+        "escaped characters are unescaped"
+    );
+    assert_eq!(
+        write_to_vec(&patterns).as_bstr(),
+        std::fs::read(dir.join("cone-patterns")).unwrap().as_bstr()
+    );
+    assert_matches_git(&dir, &patterns, "cone-ls-files");
+
+    let mut cone = Cone::from_directories(["b/", "b/c/d"]);
+    cone.add("e/f".into());
+    assert_eq!(
+        write_to_vec(&Patterns::Cone(cone)).as_bstr(),
+        "/*\n!/*/\n/e/\n!/e/*/\n/b/\n/e/f/\n",
+        "directories inside of recursive ones are implied"
+    );
+    assert!(Cone::from_bytes(b"/*\n!/*/\n/a/*.txt\n").is_none());
+    assert!(
+        Cone::from_bytes(b"!/a/*/\n").is_none(),
+        "parents must be included first"
+    );
+}
+
+#[test]
+fn full_patterns_are_read_written_and_matched_like_git() {
+    let (dir, _db) = fixture();
+    let patterns = patterns(&dir, "full-patterns");
+    assert!(
+        matches!(patterns, Patterns::Full(_)),
+        "non-cone patterns are read as full patterns even if cone mode is requested"
+    );
+    assert_eq!(
+        write_to_vec(&patterns).as_bstr(),
+        std::fs::read(dir.join("full-patterns")).unwrap().as_bstr()
+    );
+    assert_matches_git(&dir, &patterns, "full-ls-files");
+}
+
+#[test]
+fn apply_sets_skip_worktree_bits_and_updates_the_worktree() {
+    let (dir, db) = fixture();
+    let mut index = index_from_head(&dir, &db);
+    let destination = tempfile::tempdir().unwrap();
+    let mut filters = filter::Pipeline::new(
+        destination.path(),
+        filter::Options {
+            eol: filter::eol::Configuration {
+                auto_crlf: filter::eol::AutoCrlf::Enabled,
+                eol: None,
+            },
+            ..Default::default()
+        },
+    );
+    let mut attributes = attributes::Stack::new(destination.path(), Default::default(), Vec::new(), Case::Sensitive);
+    checkout::checkout(
+        &mut index,
+        destination.path(),
+        |oid, buf| find(&db, oid, buf),
+        &AtomicBool::default(),
+        &filters,
+        &attributes,
+        Default::default(),
+    )
+    .unwrap();
+
+    let mut update = |index: &mut git_index::State| {
+        sparse::update_worktree(
+            index,
+            destination.path(),
+            |oid, buf| find(&db, oid, buf),
+            &AtomicBool::default(),
+            &mut filters,
+            &mut attributes,
+            Default::default(),
+        )
+        .unwrap()
+    };
+    let skipped = |index: &git_index::State| -> Vec<String> {
+        index
+            .entries()
+            .iter()
+            .filter(|e| e.flags.contains(entry::Flags::SKIP_WORKTREE))
+            .map(|e| e.path(index).to_string())
+            .collect()
+    };
+
+    let changes = sparse::apply(&mut index, &patterns(&dir, "cone-patterns"), Case::Sensitive);
+    assert_eq!(
+        changes,
+        sparse::Changes {
+            skipped: 3,
+            included: 0
+        }
+    );
+    assert_eq!(skipped(&index), vec!["e/f", "e/g/h", "e/g/h.txt"]);
+    let out = update(&mut index);
+    assert_eq!(out.files_removed, 3);
+    assert!(out.modified.is_empty());
+    assert_eq!(out.checkout.files_updated, 0);
+    assert!(!destination.path().join("e").exists(), "empty directories are removed");
+
+    std::fs::write(destination.path().join("w*ld/j"), "modified").unwrap();
+    std::fs::write(destination.path().join("b/c/d/z"), "b/c/d/z\r\n").unwrap();
+    let changes = sparse::apply(&mut index, &patterns(&dir, "full-patterns"), Case::Sensitive);
+    assert_eq!(
+        changes,
+        sparse::Changes {
+            skipped: 2,
+            included: 1
+        }
+    );
+    let out = update(&mut index);
+    assert_eq!(
+        out.files_removed, 1,
+        "files are converted before comparing them, so line endings changed by `core.autocrlf` don't matter"
+    );
+    assert_eq!(out.modified, vec!["w*ld/j"], "modified files are kept");
+    assert_eq!(out.checkout.files_updated, 1);
+    assert_eq!(
+        std::fs::read(destination.path().join("e/g/h.txt")).unwrap().as_bstr(),
+        "e/g/h.txt\r\n",
+        "files are converted when checking them out"
+    );
+    assert!(!destination.path().join("b/c/d").exists());
+    assert_eq!(skipped(&index), vec!["b/c/d/z", "e/f", "e/g/h"]);
+    assert!(index
+        .entries()
+        .iter()
+        .all(|e| !e.flags.intersects(entry::Flags::UPDATE | entry::Flags::WORKTREE_REMOVE)));
+}
@@ -0,0 +1,179 @@
+use std::path::Path;
+
+use bstr::ByteSlice;
+use git_odb::linked;
+use git_worktree::{attributes, filter, glob::Case, status, status::Change, untracked};
+
+use crate::checkout::find;
+
+fn index_at(worktree: &Path) -> git_index::File {
+    git_index::File::at(
+        worktree.join(".git").join("index"),
+        git_index::decode::Options::default(),
+    )
+    .unwrap()
+}
+
+fn db(worktree: &Path) -> linked::Store {
+    linked::Store::at(worktree.join(".git").join("objects")).unwrap()
+}
+
+/// Compare `index` with `worktree` like git would when configured with `filters`, without excluding untracked files.
+pub fn index_to_worktree(
+    worktree: &Path,
+    index: &mut git_index::State,
+    db: &linked::Store,
+    filters: filter::Options,
+    options: status::Options,
+) -> status::Outcome {
+    status::index_to_worktree(
+        worktree,
+        index,
+        |oid, buf| find(db, oid, buf),
+        &filter::Pipeline::new(worktree, filters),
+        &attributes::Stack::new(worktree, Default::default(), Vec::new(), Case::Sensitive),
+        options,
+        |_, _| false,
+    )
+    .unwrap()
+}
+
+fn changes(outcome: &status::Outcome) -> Vec<(&str, Change)> {
+    outcome
+        .changes
+        .iter()
+        .map(|item| (item.path.to_str().expect("valid UTF-8"), item.change))
+        .collect()
+}
+
+#[test]
+fn index_to_worktree_finds_changes_like_git() {
+    let dir = git_testtools::scripted_fixture_repo_read_only("make_status_repo.sh").unwrap();
+    let mut index = index_at(&dir);
+    let outcome = index_to_worktree(
+        &dir,
+        &mut index.state,
+        &db(&dir),
+        Default::default(),
+        status::Options {
+            untracked: Some(untracked::Options {
+                use_untracked_cache: false,
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    );
+
+    let mut expected = vec![
+        ("modified", Change::Modified),
+        ("moved/file", Change::Removed),
+        ("removed", Change::Removed),
+        ("type-change", Change::TypeChange),
+    ];
+    if cfg!(unix) {
+        expected.insert(0, ("executable", Change::Modified));
+    }
+    assert_eq!(
+        changes(&outcome),
+        expected,
+        "the executable bit is only compared by default where the filesystem has one"
+    );
+    assert_eq!(outcome.untracked, vec!["moved", "moved-away/", "untracked"]);
+}
+
+#[test]
+fn executable_bit_can_be_ignored() {
+    let dir = git_testtools::scripted_fixture_repo_read_only("make_status_repo.sh").unwrap();
+    let mut index = index_at(&dir);
+    let outcome = index_to_worktree(
+        &dir,
+        &mut index.state,
+        &db(&dir),
+        Default::default(),
+        status::Options {
+            executable_bit: false,
+            thread_limit: Some(1),
+            ..Default::default()
+        },
+    );
+    assert!(outcome.changes.iter().all(|item| item.path != "executable"));
+    assert!(
+        outcome.untracked.is_empty(),
+        "untracked files are only looked for if configured"
+    );
+}
+
+#[test]
+fn files_with_equal_size_are_hashed_if_their_stat_changed_or_if_they_are_racy() {
+    let dir = git_testtools::scripted_fixture_repo_writable("make_status_repo.sh").unwrap();
+    let path = dir.path().join("unchanged");
+    let mtime = filetime::FileTime::from_unix_time(filetime::FileTime::now().unix_seconds() - 10, 0);
+    filetime::set_file_mtime(&path, mtime).unwrap();
+    // the copy changed all stat information, let git refresh the index so it matches again.
+    let status = std::process::Command::new("git")
+        .arg("status")
+        .current_dir(dir.path())
+        .stdout(std::process::Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    std::fs::write(&path, b"unchangeD\n").unwrap();
+    filetime::set_file_mtime(&path, mtime).unwrap();
+
+    let mut index = index_at(dir.path());
+    let outcome = index_to_worktree(
+        dir.path(),
+        &mut index.state,
+        &db(dir.path()),
+        Default::default(),
+        status::Options {
+            use_nsec: true,
+            ..Default::default()
+        },
+    );
+    assert!(
+        outcome.changes.contains(&status::Item {
+            entry_index: index
+                .entries()
+                .iter()
+                .position(|e| e.path(&index) == "unchanged")
+                .unwrap(),
+            path: "unchanged".into(),
+            change: Change::Modified,
+        }),
+        "the ctime changed and forced hashing"
+    );
+    assert!(outcome.entries_hashed > 0);
+
+    let options = status::Options {
+        check_stat: false,
+        ..Default::default()
+    };
+    let outcome = index_to_worktree(
+        dir.path(),
+        &mut index.state,
+        &db(dir.path()),
+        Default::default(),
+        options.clone(),
+    );
+    assert!(
+        outcome.changes.iter().all(|item| item.path != "unchanged"),
+        "it's impossible to see the change if only mtime and size are checked"
+    );
+
+    let index_path = dir.path().join(".git").join("index");
+    filetime::set_file_mtime(&index_path, mtime).unwrap();
+    let mut index = index_at(dir.path());
+    let outcome = index_to_worktree(
+        dir.path(),
+        &mut index.state,
+        &db(dir.path()),
+        Default::default(),
+        options,
+    );
+    assert!(
+        outcome.changes.iter().any(|item| item.path == "unchanged"),
+        "but if the index was written in the same instant, all files are hashed"
+    );
+}
@@ -0,0 +1,163 @@
+use bstr::{BStr, ByteSlice};
+use git_worktree::untracked;
+
+fn is_object_file(path: &BStr, _is_dir: bool) -> bool {
+    path.ends_with(b".o")
+}
+
+fn untracked_paths(outcome: &untracked::Outcome) -> Vec<&BStr> {
+    outcome.untracked.iter().map(|path| path.as_bstr()).collect()
+}
+
+fn index_at(worktree: &std::path::Path) -> git_index::File {
+    git_index::File::at(
+        worktree.join(".git").join("index"),
+        git_index::decode::Options::default(),
+    )
+    .unwrap()
+}
+
+#[test]
+fn find_lists_untracked_files_and_directories_like_git() {
+    let dir = git_testtools::scripted_fixture_repo_writable("make_repo_with_untracked_files.sh").unwrap();
+    let mut index = index_at(dir.path());
+    let outcome = untracked::find(
+        dir.path(),
+        &mut index.state,
+        untracked::Options {
+            use_untracked_cache: false,
+            ..Default::default()
+        },
+        is_object_file,
+    )
+    .unwrap();
+    assert_eq!(
+        untracked_paths(&outcome),
+        vec!["b", "dir/b", "untracked-dir/"],
+        "empty directories are hidden and ignored files are excluded"
+    );
+    assert!(index.untracked().is_none(), "the cache isn't created unless enabled");
+
+    let outcome = untracked::find(
+        dir.path(),
+        &mut index.state,
+        untracked::Options {
+            use_untracked_cache: false,
+            show_other_directories: false,
+            ..Default::default()
+        },
+        is_object_file,
+    )
+    .unwrap();
+    assert_eq!(
+        untracked_paths(&outcome),
+        vec!["b", "dir/b", "untracked-dir/x", "untracked-dir/y"]
+    );
+}
+
+#[test]
+fn untracked_cache_is_created_and_used_once_the_index_was_written() {
+    let dir = git_testtools::scripted_fixture_repo_writable("make_repo_with_untracked_files.sh").unwrap();
+    let mut index = index_at(dir.path());
+    let first = untracked::find(dir.path(), &mut index.state, Default::default(), is_object_file).unwrap();
+    assert_eq!(first.directories_cached, 0, "there is no cache yet");
+    assert!(first.directories_read > 0);
+    let cache = index.untracked().expect("cache was created");
+    assert_eq!(cache.identifier, untracked::identifier(dir.path()));
+    index.write(git_index::write::Options::default()).unwrap();
+
+    let mut index = index_at(dir.path());
+    assert!(index.untracked().is_some(), "the cache was written with the index");
+    let second = untracked::find(dir.path(), &mut index.state, Default::default(), is_object_file).unwrap();
+    assert_eq!(second.untracked, first.untracked);
+    assert_eq!(
+        second.directories_read, 0,
+        "nothing changed since the index was written"
+    );
+    assert_eq!(second.directories_cached, first.directories_read);
+
+    std::fs::write(dir.path().join("dir").join("c"), b"").unwrap();
+    index.write(git_index::write::Options::default()).unwrap();
+    let third = untracked::find(dir.path(), &mut index.state, Default::default(), is_object_file).unwrap();
+    assert_eq!(untracked_paths(&third), vec!["b", "dir/b", "dir/c", "untracked-dir/"]);
+    assert_eq!(third.directories_read, 1, "only the changed directory is read again");
+}
+
+#[test]
+fn changed_exclude_file_invalidates_the_directory() {
+    let dir = git_testtools::scripted_fixture_repo_writable("make_repo_with_untracked_files.sh").unwrap();
+    let mut index = index_at(dir.path());
+    untracked::find(dir.path(), &mut index.state, Default::default(), is_object_file).unwrap();
+    index.write(git_index::write::Options::default()).unwrap();
+
+    std::fs::write(dir.path().join("dir").join(".gitignore"), b"b\n").unwrap();
+    let outcome = untracked::find(dir.path(), &mut index.state, Default::default(), |path, is_dir| {
+        is_object_file(path, is_dir) || path == "dir/b"
+    })
+    .unwrap();
+    assert_eq!(untracked_paths(&outcome), vec!["b", "dir/.gitignore", "untracked-dir/"]);
+}
+
+#[test]
+fn invalidate_path_forces_reading_all_leading_directories() {
+    let dir = git_testtools::scripted_fixture_repo_writable("make_repo_with_untracked_files.sh").unwrap();
+    let mut index = index_at(dir.path());
+    untracked::find(dir.path(), &mut index.state, Default::default(), is_object_file).unwrap();
+    index.write(git_index::write::Options::default()).unwrap();
+
+    let mut cache = index.state.set_untracked(None).expect("present");
+    untracked::invalidate_path(&mut cache, "dir/sub/a".into());
+    index.state.set_untracked(Some(cache));
+    let outcome = untracked::find(dir.path(), &mut index.state, Default::default(), is_object_file).unwrap();
+    assert_eq!(outcome.directories_read, 3, "the root, dir and dir/sub");
+}
+
+#[test]
+fn untracked_cache_written_by_git_is_used() {
+    let dir = git_testtools::scripted_fixture_repo_writable("make_repo_with_untracked_files.sh").unwrap();
+    // neither git nor we trust stat information that is as recent as the index
+    let past = filetime::FileTime::from_unix_time(filetime::FileTime::now().unix_seconds() - 10, 0);
+    set_mtime_recursively(dir.path(), past);
+    let output = std::process::Command::new("git")
+        .args(&["-c", "core.untrackedCache=true", "status", "--porcelain"])
+        .current_dir(dir.path())
+        .env("HOME", dir.path())
+        .env("XDG_CONFIG_HOME", dir.path().join("no-config"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout.as_bstr(), "?? b\n?? dir/b\n?? untracked-dir/\n");
+
+    let mut index = index_at(dir.path());
+    assert!(index.untracked().is_some(), "git wrote the cache");
+    let outcome = untracked::find(
+        dir.path(),
+        &mut index.state,
+        untracked::Options {
+            info_exclude: Some(dir.path().join(".git").join("info").join("exclude")),
+            ..Default::default()
+        },
+        is_object_file,
+    )
+    .unwrap();
+    assert_eq!(untracked_paths(&outcome), vec!["b", "dir/b", "untracked-dir/"]);
+    assert_eq!(
+        outcome.directories_read, 0,
+        "everything is known from the cache git wrote"
+    );
+    assert!(outcome.directories_cached > 0);
+}
+
+fn set_mtime_recursively(dir: &std::path::Path, time: filetime::FileTime) {
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let entry = entry.unwrap();
+        if entry.file_name() == ".git" {
+            continue;
+        }
+        if entry.file_type().unwrap().is_dir() {
+            set_mtime_recursively(&entry.path(), time);
+        }
+        filetime::set_file_mtime(entry.path(), time).unwrap();
+    }
+    filetime::set_file_mtime(dir, time).unwrap();
+}
@@ -0,0 +1,7 @@
+mod attributes;
+mod checkout;
+mod excludes;
+mod filter;
+mod sparse;
+mod status;
+mod untracked;